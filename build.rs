@@ -0,0 +1,23 @@
+fn main() {
+    #[cfg(feature = "capi")]
+    generate_c_header();
+}
+
+/// Generate the header of the C API in the output directory of the build script
+#[cfg(feature = "capi")]
+fn generate_c_header() {
+    println!("cargo:rerun-if-changed=src/capi.rs");
+    println!("cargo:rerun-if-changed=cbindgen.toml");
+    let crate_dir = std::env::var("CARGO_MANIFEST_DIR").unwrap();
+    let config = cbindgen::Config::from_file(format!("{crate_dir}/cbindgen.toml"))
+        .expect("invalid cbindgen.toml");
+    cbindgen::Builder::new()
+        .with_crate(&crate_dir)
+        .with_config(config)
+        .generate()
+        .expect("could not generate the C header")
+        .write_to_file(format!(
+            "{}/dependency_runner.h",
+            std::env::var("OUT_DIR").unwrap()
+        ));
+}
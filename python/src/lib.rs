@@ -0,0 +1,290 @@
+//! Python bindings of the dependency_runner library
+//!
+//! The queries and lookup paths are Python objects wrapping the ones of the library, while the
+//! results of a scan and of the checks are converted to dicts, with the same layout as the JSON
+//! documents written by deprun.
+
+use std::path::PathBuf;
+
+use pyo3::create_exception;
+use pyo3::exceptions::PyException;
+use pyo3::prelude::*;
+use pyo3::types::PyList;
+
+use dependency_runner::common::LookupError;
+use dependency_runner::path::{PathProfile, PathProfileEntry};
+
+create_exception!(
+    dependency_runner_py,
+    DependencyRunnerError,
+    PyException,
+    "Error raised by the scanner"
+);
+
+fn to_py_err(e: LookupError) -> PyErr {
+    DependencyRunnerError::new_err(e.to_string())
+}
+
+/// Convert a serializable value to the corresponding Python objects (dicts, lists, strings...)
+fn to_python<T: serde::Serialize>(py: Python<'_>, value: &T) -> PyResult<PyObject> {
+    let json = serde_json::to_string(value)
+        .map_err(|e| DependencyRunnerError::new_err(e.to_string()))?;
+    Ok(py.import("json")?.call_method1("loads", (json,))?.unbind())
+}
+
+/// Specification of a scan: the target executable, its environment and the scan parameters
+#[pyclass(name = "LookupQuery")]
+#[derive(Clone)]
+struct PyLookupQuery(dependency_runner::query::LookupQuery);
+
+#[pymethods]
+impl PyLookupQuery {
+    /// Deduce the query from the location of the executable (see
+    /// `LookupQuery::deduce_from_executable_location`)
+    #[new]
+    fn new(target_exe: PathBuf) -> PyResult<Self> {
+        dependency_runner::query::LookupQuery::deduce_from_executable_location(target_exe)
+            .map(Self)
+            .map_err(to_py_err)
+    }
+
+    #[getter]
+    fn target_exe(&self) -> PathBuf {
+        self.0.target.target_exe.clone()
+    }
+
+    /// Add another executable scanned along with the target (e.g. one of its plugins)
+    fn add_target(&mut self, path: PathBuf) {
+        self.0.add_target(path);
+    }
+
+    #[getter]
+    fn working_dir(&self) -> PathBuf {
+        self.0.target.working_dir.clone()
+    }
+
+    #[setter]
+    fn set_working_dir(&mut self, dir: PathBuf) {
+        self.0.target.working_dir = dir;
+    }
+
+    #[getter]
+    fn user_path(&self) -> Vec<PathBuf> {
+        self.0.target.user_path.clone()
+    }
+
+    #[setter]
+    fn set_user_path(&mut self, dirs: Vec<PathBuf>) {
+        self.0.target.user_path = dirs;
+    }
+
+    #[getter]
+    fn max_depth(&self) -> Option<usize> {
+        self.0.parameters.max_depth
+    }
+
+    #[setter]
+    fn set_max_depth(&mut self, max_depth: Option<usize>) {
+        self.0.parameters.max_depth = max_depth;
+    }
+
+    #[getter]
+    fn skip_system_dlls(&self) -> bool {
+        self.0.parameters.skip_system_dlls
+    }
+
+    #[setter]
+    fn set_skip_system_dlls(&mut self, value: bool) {
+        self.0.parameters.skip_system_dlls = value;
+    }
+
+    #[getter]
+    fn scan_system_dlls(&self) -> bool {
+        self.0.parameters.scan_system_dlls
+    }
+
+    #[setter]
+    fn set_scan_system_dlls(&mut self, value: bool) {
+        self.0.parameters.scan_system_dlls = value;
+    }
+
+    #[getter]
+    fn extract_symbols(&self) -> bool {
+        self.0.parameters.extract_symbols
+    }
+
+    #[setter]
+    fn set_extract_symbols(&mut self, value: bool) {
+        self.0.parameters.extract_symbols = value;
+    }
+
+    #[getter]
+    fn compute_hashes(&self) -> bool {
+        self.0.parameters.compute_hashes
+    }
+
+    #[setter]
+    fn set_compute_hashes(&mut self, value: bool) {
+        self.0.parameters.compute_hashes = value;
+    }
+
+    /// Load a DLL from the given file, or consider it "present" or "missing", regardless of the
+    /// lookup path
+    fn override_dll(&mut self, dllname: &str, resolution: &str) -> PyResult<()> {
+        self.0
+            .override_dll(dllname, resolution.parse().map_err(to_py_err)?);
+        Ok(())
+    }
+}
+
+/// Directories searched for the DLLs, in order
+///
+/// The path is stored as a profile (see `path::PathProfile`), resolved against the query it was
+/// created for: the directories depending on the executable and on the system are filled in by
+/// `run`.
+#[pyclass(name = "LookupPath")]
+#[derive(Clone)]
+struct PyLookupPath {
+    query: dependency_runner::query::LookupQuery,
+    profile: PathProfile,
+    /// directory the relative user directories of the profile are resolved against
+    base_dir: PathBuf,
+}
+
+impl PyLookupPath {
+    fn lookup_path<'a>(
+        &self,
+        query: &'a dependency_runner::query::LookupQuery,
+    ) -> Result<dependency_runner::path::LookupPath<'a>, LookupError> {
+        self.profile.lookup_path(query, &self.base_dir)
+    }
+}
+
+#[pymethods]
+impl PyLookupPath {
+    /// Deduce the lookup path of the query, as the Windows loader would search it
+    #[new]
+    fn new(query: &PyLookupQuery) -> Self {
+        let lookup_path = dependency_runner::path::LookupPath::deduce(&query.0);
+        Self {
+            query: query.0.clone(),
+            profile: PathProfile::from_lookup_path(&lookup_path),
+            base_dir: query.0.target.app_dir.clone(),
+        }
+    }
+
+    /// Read the lookup path of the query from a path profile (JSON) file
+    #[staticmethod]
+    fn from_profile(query: &PyLookupQuery, profile_path: PathBuf) -> PyResult<Self> {
+        let profile = PathProfile::read(&profile_path).map_err(to_py_err)?;
+        Ok(Self {
+            query: query.0.clone(),
+            profile,
+            base_dir: profile_path
+                .parent()
+                .map(PathBuf::from)
+                .unwrap_or_default(),
+        })
+    }
+
+    /// Search the given directory before all the others
+    fn prepend_user_dir(&mut self, dir: PathBuf) {
+        self.profile
+            .entries
+            .insert(0, PathProfileEntry::UserPath(dir));
+    }
+
+    /// Search the given directory after all the others
+    fn append_user_dir(&mut self, dir: PathBuf) {
+        self.profile.entries.push(PathProfileEntry::UserPath(dir));
+    }
+
+    /// Directories searched, in order
+    fn search_path(&self) -> PyResult<Vec<PathBuf>> {
+        Ok(self.lookup_path(&self.query).map_err(to_py_err)?.search_path())
+    }
+}
+
+/// Executables found by a scan
+#[pyclass(name = "Executables")]
+struct PyExecutables(dependency_runner::executable::Executables);
+
+#[pymethods]
+impl PyExecutables {
+    fn __len__(&self) -> usize {
+        self.0.len()
+    }
+
+    fn __contains__(&self, dllname: &str) -> bool {
+        self.0.contains(dllname)
+    }
+
+    /// Names of the executables, in order of first appearance
+    fn names(&self) -> Vec<String> {
+        self.0
+            .iter()
+            .map(|e| e.dllname.clone())
+            .collect()
+    }
+
+    /// Executable with the given name as a dict, or None
+    fn get(&self, py: Python<'_>, dllname: &str) -> PyResult<Option<PyObject>> {
+        self.0.get(dllname).map(|e| to_python(py, e)).transpose()
+    }
+
+    /// Whole result of the scan as a dict, with the layout of the JSON output of deprun
+    fn to_dict(&self, py: Python<'_>) -> PyResult<PyObject> {
+        to_python(py, &self.0)
+    }
+
+    /// Whole result of the scan as a JSON document
+    fn to_json(&self) -> PyResult<String> {
+        serde_json::to_string(&self.0).map_err(|e| DependencyRunnerError::new_err(e.to_string()))
+    }
+
+    /// Check the missing DLLs, the runtime mismatches and, if they were extracted, the missing
+    /// symbols, returning the findings as a list of dicts
+    #[pyo3(signature = (check_symbols = false))]
+    fn check<'py>(&self, py: Python<'py>, check_symbols: bool) -> PyResult<Bound<'py, PyList>> {
+        let report = self.0.check(check_symbols).map_err(to_py_err)?;
+        let findings = report
+            .findings()
+            .iter()
+            .map(|f| to_python(py, f))
+            .collect::<PyResult<Vec<_>>>()?;
+        PyList::new(py, findings)
+    }
+}
+
+/// Scan the dependencies of the target of the query, in its deduced lookup path unless another
+/// one is given
+#[pyfunction]
+#[pyo3(signature = (query, lookup_path = None))]
+fn run(
+    py: Python<'_>,
+    query: &PyLookupQuery,
+    lookup_path: Option<&PyLookupPath>,
+) -> PyResult<PyExecutables> {
+    py.allow_threads(|| {
+        let lookup_path = match lookup_path {
+            Some(lookup_path) => lookup_path.lookup_path(&query.0)?,
+            None => dependency_runner::path::LookupPath::deduce(&query.0),
+        };
+        dependency_runner::runner::run(&query.0, &lookup_path)
+    })
+    .map(PyExecutables)
+    .map_err(to_py_err)
+}
+
+#[pymodule]
+fn dependency_runner_py(m: &Bound<'_, PyModule>) -> PyResult<()> {
+    m.add_class::<PyLookupQuery>()?;
+    m.add_class::<PyLookupPath>()?;
+    m.add_class::<PyExecutables>()?;
+    m.add_function(wrap_pyfunction!(run, m)?)?;
+    m.add(
+        "DependencyRunnerError",
+        m.py().get_type::<DependencyRunnerError>(),
+    )?;
+    Ok(())
+}
@@ -0,0 +1,36 @@
+import pathlib
+
+import dependency_runner_py as dr
+
+DEBUG_DIR = (
+    pathlib.Path(__file__).parents[2]
+    / "test_data/test_project1/DepRunTest/build-same-output/bin/Debug"
+)
+
+
+def test_run():
+    query = dr.LookupQuery(str(DEBUG_DIR / "DepRunTest.exe"))
+    query.skip_system_dlls = True
+    executables = dr.run(query)
+    assert "DepRunTestLib.dll" in executables
+    assert executables.names()[0] == "DepRunTest.exe"
+    assert executables.get("DepRunTestLib.dll")["found"]
+    assert executables.to_dict()["schema_version"] == 1
+    findings = executables.check()
+    assert all(f["code"] for f in findings)
+
+
+def test_lookup_path():
+    query = dr.LookupQuery(str(DEBUG_DIR / "DepRunTest.exe"))
+    lookup_path = dr.LookupPath(query)
+    lookup_path.prepend_user_dir("/some/dir")
+    assert lookup_path.search_path()[0] == "/some/dir"
+    assert "DepRunTestLib.dll" in dr.run(query, lookup_path)
+
+
+def test_error():
+    try:
+        dr.run(dr.LookupQuery("no/such/file.exe"))
+    except dr.DependencyRunnerError:
+        return
+    raise AssertionError("no error raised")
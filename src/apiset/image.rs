@@ -61,3 +61,100 @@ pub struct API_SET_VALUE_ENTRY {
     /// Host library name length
     pub ValueLength: u32,
 }
+
+// Legacy schema layouts: all offsets are relative to the start of the schema, and the contract
+// names lack the "api-" prefix
+
+#[derive(Copy, Clone, Debug, Pod, serde::Serialize)]
+#[repr(C)]
+pub struct API_SET_NAMESPACE_V2 {
+    /// always 2 (Windows 7)
+    pub Version: u32,
+    /// number of API_SET_NAMESPACE_ENTRY_V2 following the header
+    pub Count: u32,
+}
+
+#[derive(Copy, Clone, Debug, Pod, serde::Serialize)]
+#[repr(C)]
+pub struct API_SET_NAMESPACE_ENTRY_V2 {
+    /// Offset to the ApiSet library name PWCHAR (e.g. "MS-Win-Core-Console-L1-1-0")
+    pub NameOffset: u32,
+    /// Apiset library name length
+    pub NameLength: u32,
+    /// Offset to the API_SET_VALUE_ARRAY_V2 listing the hosts
+    pub DataOffset: u32,
+}
+
+#[derive(Copy, Clone, Debug, Pod, serde::Serialize)]
+#[repr(C)]
+pub struct API_SET_VALUE_ARRAY_V2 {
+    /// number of API_SET_VALUE_ENTRY_V2 following the header
+    pub Count: u32,
+}
+
+#[derive(Copy, Clone, Debug, Pod, serde::Serialize)]
+#[repr(C)]
+pub struct API_SET_VALUE_ENTRY_V2 {
+    /// Offset to the name of the importing library this redirection is restricted to (if any)
+    pub NameOffset: u32,
+    /// Importing library name length
+    pub NameLength: u32,
+    /// Offset to the Host library name PWCHAR (e.g. "kernel32.dll")
+    pub ValueOffset: u32,
+    /// Host library name length
+    pub ValueLength: u32,
+}
+
+#[derive(Copy, Clone, Debug, Pod, serde::Serialize)]
+#[repr(C)]
+pub struct API_SET_NAMESPACE_V4 {
+    /// always 4 (Windows 8.1)
+    pub Version: u32,
+    /// apiset map size
+    pub Size: u32,
+    /// sealed flag in bit 0
+    pub Flags: u32,
+    /// number of API_SET_NAMESPACE_ENTRY_V4 following the header
+    pub Count: u32,
+}
+
+#[derive(Copy, Clone, Debug, Pod, serde::Serialize)]
+#[repr(C)]
+pub struct API_SET_NAMESPACE_ENTRY_V4 {
+    /// sealed flag in bit 0
+    pub Flags: u32,
+    /// Offset to the ApiSet library name PWCHAR (e.g. "ms-win-core-job-l2-1-1")
+    pub NameOffset: u32,
+    /// Apiset library name length
+    pub NameLength: u32,
+    /// Offset to the name alias
+    pub AliasOffset: u32,
+    /// Alias length
+    pub AliasLength: u32,
+    /// Offset to the API_SET_VALUE_ARRAY_V4 listing the hosts
+    pub DataOffset: u32,
+}
+
+#[derive(Copy, Clone, Debug, Pod, serde::Serialize)]
+#[repr(C)]
+pub struct API_SET_VALUE_ARRAY_V4 {
+    /// sealed flag in bit 0
+    pub Flags: u32,
+    /// number of API_SET_VALUE_ENTRY_V4 following the header
+    pub Count: u32,
+}
+
+#[derive(Copy, Clone, Debug, Pod, serde::Serialize)]
+#[repr(C)]
+pub struct API_SET_VALUE_ENTRY_V4 {
+    /// sealed flag in bit 0
+    pub Flags: u32,
+    /// Offset to the name of the importing library this redirection is restricted to (if any)
+    pub NameOffset: u32,
+    /// Importing library name length
+    pub NameLength: u32,
+    /// Offset to the Host library name PWCHAR (e.g. "kernelbase.dll")
+    pub ValueOffset: u32,
+    /// Host library name length
+    pub ValueLength: u32,
+}
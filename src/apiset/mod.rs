@@ -3,6 +3,13 @@ Wrapper for PeLite apiset example
 
 Fun with MS Api Set Schemas
 
+The schema can be loaded from any apisetschema.dll with [`parse_apiset`], queried for a contract
+with [`lookup_apiset`] and exported as a whole with [`apiset_map_to_json`] or [`apiset_map_to_csv`].
+
+When no Windows partition is available, a pre-extracted map can be read back with
+[`load_apiset_map`], or one of the maps bundled with the library can be used (see
+[`bundled_apiset_map`]).
+
 Resources:
 
 * https://ofekshilon.com/2016/03/27/on-api-ms-win-xxxxx-dll-and-other-dependency-walker-glitches/
@@ -15,35 +22,429 @@ Resources:
 
 mod image;
 mod win10;
+mod win7;
+mod win81;
 
 use crate::common::LookupError;
+use dataview::{DataView, Pod};
+use fs_err as fs;
+use std::collections::BTreeMap;
+use std::mem::size_of;
 use std::path::Path;
 use win10::Entry;
 
+/// Map from API set contract name (lowercase, without extension) to the DLLs implementing it
 pub type ApisetMap = std::collections::HashMap<String, Vec<String>>;
 
+/// Find the hosts implementing the given API set contract (e.g. "api-ms-win-core-job-l2-1-1.dll")
+///
+/// As the loader does, the minor version at the end of the contract name is ignored if no exact
+/// match is found; if several contracts differ from the name only by their minor version, the one
+/// sharing the longest prefix with it is picked (the highest version on ties). The first host is
+/// the default one, the others are importer-specific.
+pub fn lookup_apiset<'a>(map: &'a ApisetMap, library: &str) -> Option<&'a Vec<String>> {
+    let name = library.to_lowercase();
+    let name = name.trim_end_matches(".dll");
+    map.get(name).or_else(|| {
+        let (hashed_name, _) = name.rsplit_once('-')?;
+        let common_prefix = |k: &str| {
+            k.bytes()
+                .zip(name.bytes())
+                .take_while(|(a, b)| a == b)
+                .count()
+        };
+        map.iter()
+            .filter(|(k, _)| k.rsplit_once('-').map(|(h, _)| h) == Some(hashed_name))
+            .max_by(|(k1, _), (k2, _)| {
+                common_prefix(k1)
+                    .cmp(&common_prefix(k2))
+                    .then_with(|| minor_version(k1).cmp(&minor_version(k2)))
+                    .then_with(|| k1.cmp(k2))
+            })
+            .map(|(_, v)| v)
+    })
+}
+
+/// Minor version at the end of a contract name, e.g. 12 for "api-ms-win-core-job-l2-1-12"
+fn minor_version(contract: &str) -> Option<u32> {
+    contract.rsplit_once('-')?.1.parse().ok()
+}
+
+/// Read a UTF-16 string given its offset and length in bytes
+fn read_utf16(image: &[u8], offset: u32, length: u32) -> pelite::Result<String> {
+    let bytes = image
+        .get(offset as usize..offset as usize + length as usize)
+        .ok_or(pelite::Error::Bounds)?;
+    let wide: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    Ok(String::from_utf16_lossy(&wide))
+}
+
 fn parse_apiset_entry(e: Entry) -> Result<(String, Vec<String>), LookupError> {
     Ok((
         String::from_utf16_lossy(e.name()?).to_lowercase(),
         e.values()?
             .iter()
             .map(|v| String::from_utf16_lossy(v.host_name().unwrap()))
+            .filter(|h| !h.is_empty())
             .collect(),
     ))
 }
 
+/// Structures of a legacy (v2, v4) schema, which are laid out in the same way and only differ by
+/// their fields
+///
+/// The schema is a header followed by an array of namespace entries; each of them points to a
+/// value array, followed by the value entries holding the hosts of the contract.
+trait LegacyLayout {
+    const VERSION: u32;
+    type Namespace: Pod;
+    type NamespaceEntry: Pod;
+    type ValueArray: Pod;
+    type ValueEntry: Pod;
+
+    /// Version and number of namespace entries of the schema
+    fn namespace(header: &Self::Namespace) -> (u32, u32);
+    /// Offset and length of the contract name, offset of the value array
+    fn namespace_entry(entry: &Self::NamespaceEntry) -> (u32, u32, u32);
+    /// Number of value entries
+    fn value_array(values: &Self::ValueArray) -> u32;
+    /// Offset and length of the host name
+    fn value_entry(value: &Self::ValueEntry) -> (u32, u32);
+}
+
+/// Parse a legacy schema into a list of (contract name, host names) pairs
+///
+/// The contract names are returned as stored, i.e. without prefix
+fn parse_legacy<L: LegacyLayout>(image: &[u8]) -> pelite::Result<Vec<(String, Vec<String>)>> {
+    let view = DataView::from(image);
+    let header: L::Namespace = view.try_read(0).ok_or(pelite::Error::Bounds)?;
+    let (version, count) = L::namespace(&header);
+    if version != L::VERSION {
+        return Err(pelite::Error::BadMagic);
+    }
+    (0..count as usize)
+        .map(|i| {
+            let entry: L::NamespaceEntry = view
+                .try_read(size_of::<L::Namespace>() + i * size_of::<L::NamespaceEntry>())
+                .ok_or(pelite::Error::Bounds)?;
+            let (name_offset, name_length, data_offset) = L::namespace_entry(&entry);
+            let name = read_utf16(image, name_offset, name_length)?;
+            let values: L::ValueArray = view
+                .try_read(data_offset as usize)
+                .ok_or(pelite::Error::Bounds)?;
+            let hosts = (0..L::value_array(&values) as usize)
+                .map(|j| {
+                    let value: L::ValueEntry = view
+                        .try_read(
+                            data_offset as usize
+                                + size_of::<L::ValueArray>()
+                                + j * size_of::<L::ValueEntry>(),
+                        )
+                        .ok_or(pelite::Error::Bounds)?;
+                    let (value_offset, value_length) = L::value_entry(&value);
+                    read_utf16(image, value_offset, value_length)
+                })
+                .collect::<pelite::Result<Vec<String>>>()?;
+            Ok((name, hosts))
+        })
+        .collect()
+}
+
+/// Bring the entries of a legacy (v2, v4) schema to the form used from Windows 10 onwards
+///
+/// Legacy schemas store the contract names without prefix, and the loader accepts any of the given
+/// prefixes in front of them.
+fn normalize_legacy_entries(entries: Vec<(String, Vec<String>)>, prefixes: &[&str]) -> ApisetMap {
+    entries
+        .into_iter()
+        .flat_map(|(name, hosts)| {
+            let name = name.to_lowercase();
+            let hosts: Vec<String> = hosts.into_iter().filter(|h| !h.is_empty()).collect();
+            prefixes
+                .iter()
+                .map(move |p| (format!("{p}{name}"), hosts.clone()))
+                .collect::<Vec<_>>()
+        })
+        .collect()
+}
+
+/// Parse the content of the .apiset section of apisetschema.dll, whatever the schema version
+pub fn parse_apiset_schema(image: &[u8]) -> Result<ApisetMap, LookupError> {
+    let version: u32 = DataView::from(image)
+        .try_read(0)
+        .ok_or(pelite::Error::Bounds)?;
+    match version {
+        2 => Ok(normalize_legacy_entries(
+            parse_legacy::<win7::Layout>(image)?,
+            &["api-"],
+        )),
+        4 => Ok(normalize_legacy_entries(
+            parse_legacy::<win81::Layout>(image)?,
+            &["api-", "ext-"],
+        )),
+        6 => win10::Schema::parse(image)?
+            .entries()?
+            .iter()
+            .map(parse_apiset_entry)
+            .collect(),
+        _ => Err(LookupError::ParseError(format!(
+            "Unsupported API set schema version {version}"
+        ))),
+    }
+}
+
+/// Read the API set map from the .apiset section of the given apisetschema.dll
 pub fn parse_apiset<P: AsRef<Path>>(apisetschema_path: P) -> Result<ApisetMap, LookupError> {
     let filemap = pelite::FileMap::open(apisetschema_path.as_ref())?;
     let pefile = pelite::PeFile::from_bytes(&filemap)?;
     if let Some(section) = pefile.section_headers().by_name(".apiset") {
-        let apisetschema = win10::Schema::parse(pefile.get_section_bytes(section)?)?;
-        let entrymap: Result<ApisetMap, LookupError> = apisetschema
-            .entries()?
-            .iter()
-            .map(parse_apiset_entry)
-            .collect();
-        entrymap
+        parse_apiset_schema(pefile.get_section_bytes(section)?)
     } else {
         Ok(ApisetMap::new())
     }
 }
+
+/// Serialize the map to a JSON object, with the contracts sorted by name
+pub fn apiset_map_to_json(map: &ApisetMap) -> Result<String, LookupError> {
+    let sorted: BTreeMap<&String, &Vec<String>> = map.iter().collect();
+    Ok(serde_json::to_string_pretty(&sorted)?)
+}
+
+/// Serialize the map to CSV, one contract per line, with the hosts separated by semicolons
+pub fn apiset_map_to_csv(map: &ApisetMap) -> String {
+    let sorted: BTreeMap<&String, &Vec<String>> = map.iter().collect();
+    let mut ret = "contract,hosts\n".to_owned();
+    for (contract, hosts) in sorted {
+        ret.push_str(&format!("{},{}\n", contract, hosts.join(";")));
+    }
+    ret
+}
+
+/// Parse a map serialized with [`apiset_map_to_json`]
+pub fn apiset_map_from_json(s: &str) -> Result<ApisetMap, LookupError> {
+    Ok(serde_json::from_str(s)?)
+}
+
+/// Parse a map serialized with [`apiset_map_to_csv`]
+pub fn apiset_map_from_csv(s: &str) -> Result<ApisetMap, LookupError> {
+    s.lines()
+        .skip(1)
+        .filter(|l| !l.trim().is_empty())
+        .map(|l| {
+            let (contract, hosts) = l.split_once(',').ok_or_else(|| {
+                LookupError::ParseError(format!("Invalid line in API set map: {l}"))
+            })?;
+            Ok((
+                contract.trim().to_lowercase(),
+                hosts
+                    .split(';')
+                    .map(str::trim)
+                    .filter(|h| !h.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            ))
+        })
+        .collect()
+}
+
+/// Load a pre-extracted map from a .json or .csv file
+pub fn load_apiset_map<P: AsRef<Path>>(p: P) -> Result<ApisetMap, LookupError> {
+    let content = fs::read_to_string(p.as_ref())?;
+    if p.as_ref()
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case("csv"))
+        .unwrap_or(false)
+    {
+        apiset_map_from_csv(&content)
+    } else {
+        apiset_map_from_json(&content)
+    }
+}
+
+/// Names of the API set maps bundled with the library
+pub const BUNDLED_APISET_MAPS: &[&str] = &["win10"];
+
+/// Get one of the API set maps bundled with the library, by name (see [`BUNDLED_APISET_MAPS`])
+///
+/// The bundled maps only contain the contracts commonly found in the import tables of
+/// applications, and are meant for scans performed without access to a Windows partition.
+pub fn bundled_apiset_map(name: &str) -> Option<ApisetMap> {
+    match name.to_lowercase().as_str() {
+        "win10" => apiset_map_from_json(include_str!("data/win10.json")).ok(),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::apiset::{
+        apiset_map_from_csv, apiset_map_to_csv, apiset_map_to_json, bundled_apiset_map,
+        lookup_apiset, parse_apiset_schema, ApisetMap, BUNDLED_APISET_MAPS,
+    };
+    use crate::common::LookupError;
+
+    fn push_u32(image: &mut Vec<u8>, v: u32) {
+        image.extend_from_slice(&v.to_le_bytes());
+    }
+
+    fn push_utf16(image: &mut Vec<u8>, s: &str) -> (u32, u32) {
+        let offset = image.len() as u32;
+        for c in s.encode_utf16() {
+            image.extend_from_slice(&c.to_le_bytes());
+        }
+        (offset, image.len() as u32 - offset)
+    }
+
+    #[test]
+    fn parse_schema_v2() -> Result<(), LookupError> {
+        // header, one namespace entry, one value array with two values (one of which is empty)
+        let mut image = Vec::new();
+        push_u32(&mut image, 2);
+        push_u32(&mut image, 1);
+        let entry_offset = image.len();
+        image.resize(entry_offset + 12, 0);
+        let data_offset = image.len() as u32;
+        push_u32(&mut image, 2);
+        let values_offset = image.len();
+        image.resize(values_offset + 32, 0);
+
+        let (name_offset, name_length) = push_utf16(&mut image, "MS-Win-Core-Console-L1-1-0");
+        let (host_offset, host_length) = push_utf16(&mut image, "kernel32.dll");
+        for (i, v) in [name_offset, name_length, data_offset].iter().enumerate() {
+            image[entry_offset + 4 * i..entry_offset + 4 * i + 4].copy_from_slice(&v.to_le_bytes());
+        }
+        for (i, v) in [0, 0, host_offset, host_length].iter().enumerate() {
+            image[values_offset + 4 * i..values_offset + 4 * i + 4]
+                .copy_from_slice(&v.to_le_bytes());
+        }
+
+        let map = parse_apiset_schema(&image)?;
+        assert_eq!(map.len(), 1);
+        assert_eq!(
+            map.get("api-ms-win-core-console-l1-1-0"),
+            Some(&vec!["kernel32.dll".to_owned()])
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn parse_schema_v4() -> Result<(), LookupError> {
+        let mut image = Vec::new();
+        push_u32(&mut image, 4);
+        push_u32(&mut image, 0);
+        push_u32(&mut image, 0);
+        push_u32(&mut image, 1);
+        let entry_offset = image.len();
+        image.resize(entry_offset + 24, 0);
+        let data_offset = image.len() as u32;
+        push_u32(&mut image, 0);
+        push_u32(&mut image, 1);
+        let values_offset = image.len();
+        image.resize(values_offset + 20, 0);
+
+        let (name_offset, name_length) = push_utf16(&mut image, "ms-win-core-job-l2-1-1");
+        let (host_offset, host_length) = push_utf16(&mut image, "kernelbase.dll");
+        for (i, v) in [0, name_offset, name_length, 0, 0, data_offset]
+            .iter()
+            .enumerate()
+        {
+            image[entry_offset + 4 * i..entry_offset + 4 * i + 4].copy_from_slice(&v.to_le_bytes());
+        }
+        for (i, v) in [0, 0, 0, host_offset, host_length].iter().enumerate() {
+            image[values_offset + 4 * i..values_offset + 4 * i + 4]
+                .copy_from_slice(&v.to_le_bytes());
+        }
+
+        let map = parse_apiset_schema(&image)?;
+        let expected_hosts = vec!["kernelbase.dll".to_owned()];
+        assert_eq!(map.get("api-ms-win-core-job-l2-1-1"), Some(&expected_hosts));
+        assert_eq!(map.get("ext-ms-win-core-job-l2-1-1"), Some(&expected_hosts));
+        Ok(())
+    }
+
+    #[test]
+    fn lookup_contract() {
+        let mut map = ApisetMap::new();
+        map.insert(
+            "api-ms-win-core-job-l2-1-1".to_owned(),
+            vec!["kernelbase.dll".to_owned()],
+        );
+        let expected_hosts = vec!["kernelbase.dll".to_owned()];
+        assert_eq!(
+            lookup_apiset(&map, "API-MS-Win-Core-Job-L2-1-1.dll"),
+            Some(&expected_hosts)
+        );
+        assert_eq!(
+            lookup_apiset(&map, "api-ms-win-core-job-l2-1-0.dll"),
+            Some(&expected_hosts)
+        );
+        assert_eq!(lookup_apiset(&map, "api-ms-win-core-job-l1-1-0.dll"), None);
+
+        // several candidates for the minor version: the closest one, then the highest version,
+        // whatever the order of the map
+        map.insert(
+            "api-ms-win-core-job-l2-1-12".to_owned(),
+            vec!["other.dll".to_owned()],
+        );
+        map.insert(
+            "api-ms-win-core-job-l2-1-3".to_owned(),
+            vec!["third.dll".to_owned()],
+        );
+        let other_hosts = vec!["other.dll".to_owned()];
+        for _ in 0..10 {
+            assert_eq!(
+                lookup_apiset(&map, "api-ms-win-core-job-l2-1-15.dll"),
+                Some(&other_hosts)
+            );
+        }
+        assert_eq!(
+            lookup_apiset(&map, "api-ms-win-core-job-l2-1-0.dll"),
+            Some(&other_hosts)
+        );
+    }
+
+    #[test]
+    fn export_map() -> Result<(), LookupError> {
+        let mut map = ApisetMap::new();
+        map.insert(
+            "api-ms-win-core-job-l2-1-1".to_owned(),
+            vec!["kernelbase.dll".to_owned()],
+        );
+        map.insert(
+            "api-ms-win-crt-math-l1-1-0".to_owned(),
+            vec!["ucrtbase.dll".to_owned(), "ucrtbased.dll".to_owned()],
+        );
+        assert_eq!(
+            apiset_map_to_csv(&map),
+            "contract,hosts\n\
+            api-ms-win-core-job-l2-1-1,kernelbase.dll\n\
+            api-ms-win-crt-math-l1-1-0,ucrtbase.dll;ucrtbased.dll\n"
+        );
+        let reparsed: ApisetMap = serde_json::from_str(&apiset_map_to_json(&map)?)?;
+        assert_eq!(reparsed, map);
+        assert_eq!(apiset_map_from_csv(&apiset_map_to_csv(&map))?, map);
+        Ok(())
+    }
+
+    #[test]
+    fn bundled_maps() {
+        for name in BUNDLED_APISET_MAPS {
+            assert!(bundled_apiset_map(name).is_some_and(|m| !m.is_empty()));
+        }
+        let win10 = bundled_apiset_map("win10").unwrap();
+        assert_eq!(
+            lookup_apiset(&win10, "api-ms-win-crt-runtime-l1-1-0.dll"),
+            Some(&vec!["ucrtbase.dll".to_owned()])
+        );
+        assert!(bundled_apiset_map("win95").is_none());
+    }
+
+    #[test]
+    fn unsupported_schema_version() {
+        let image = 3u32.to_le_bytes();
+        assert!(parse_apiset_schema(&image).is_err());
+    }
+}
@@ -28,7 +28,7 @@ impl<'a> Schema<'a> {
     fn slice_len<T: Pod>(&self, offset: u32, len: usize) -> Result<&'a [T]> {
         let slice = self
             .image
-            .get(offset as usize..offset as usize + len as usize)
+            .get(offset as usize..offset as usize + len)
             .ok_or(Error::Bounds)?;
         if !slice.as_ptr().aligned_to(mem::align_of::<T>()) {
             return Err(Error::Misaligned);
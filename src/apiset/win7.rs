@@ -1,43 +1,31 @@
-use pelite::Pod;
+//! API set schema version 2, as found in Windows 7
 
-#[derive(Debug, Pod)]
-#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
-#[repr(C)]
-pub struct ApiSetMap {
-	pub Version: u32,
-	pub NumberOfHosts: u32,
-	#[cfg_attr(feature = "serde", serde(skip))]
-	pub Descriptors: [DllHostDescriptor; 0],
-}
+use super::image::*;
+use super::LegacyLayout;
 
-#[derive(Copy, Clone, Debug, Pod)]
-#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
-#[repr(C)]
-pub struct DllHostDescriptor {
-	pub OffsetDllString: u32,
-	pub StringLength: u32,
-	pub OffsetDllRedirector: u32,
-}
+/// Layout of the version 2 schema, parsed with [`super::parse_legacy`]
+pub struct Layout;
 
-#[derive(Debug, Pod)]
-#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
-#[repr(C)]
-pub struct DllRedirector {
-	pub NumberOfRedirections: u32,
-	#[cfg_attr(feature = "serde", serde(skip))]
-	pub Redirection: [Redirection; 0],
-}
+impl LegacyLayout for Layout {
+    const VERSION: u32 = 2;
+    type Namespace = API_SET_NAMESPACE_V2;
+    type NamespaceEntry = API_SET_NAMESPACE_ENTRY_V2;
+    type ValueArray = API_SET_VALUE_ARRAY_V2;
+    type ValueEntry = API_SET_VALUE_ENTRY_V2;
+
+    fn namespace(header: &Self::Namespace) -> (u32, u32) {
+        (header.Version, header.Count)
+    }
+
+    fn namespace_entry(entry: &Self::NamespaceEntry) -> (u32, u32, u32) {
+        (entry.NameOffset, entry.NameLength, entry.DataOffset)
+    }
+
+    fn value_array(values: &Self::ValueArray) -> u32 {
+        values.Count
+    }
 
-#[derive(Copy, Clone, Debug, Pod)]
-#[cfg_attr(feature = "serde", derive(::serde::Serialize))]
-#[repr(C)]
-pub struct Redirection {
-	pub OffsetRedirection1: u32,
-	pub RedirectionLength1: u16,
-	#[cfg_attr(feature = "serde", serde(skip))]
-	pub _pad1: u16,
-	pub OffsetRedirection2: u32,
-	pub RedirectionLength2: u16,
-	#[cfg_attr(feature = "serde", serde(skip))]
-	pub _pad2: u16,
+    fn value_entry(value: &Self::ValueEntry) -> (u32, u32) {
+        (value.ValueOffset, value.ValueLength)
+    }
 }
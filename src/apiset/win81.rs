@@ -0,0 +1,31 @@
+//! API set schema version 4, as found in Windows 8.1
+
+use super::image::*;
+use super::LegacyLayout;
+
+/// Layout of the version 4 schema, parsed with [`super::parse_legacy`]
+pub struct Layout;
+
+impl LegacyLayout for Layout {
+    const VERSION: u32 = 4;
+    type Namespace = API_SET_NAMESPACE_V4;
+    type NamespaceEntry = API_SET_NAMESPACE_ENTRY_V4;
+    type ValueArray = API_SET_VALUE_ARRAY_V4;
+    type ValueEntry = API_SET_VALUE_ENTRY_V4;
+
+    fn namespace(header: &Self::Namespace) -> (u32, u32) {
+        (header.Version, header.Count)
+    }
+
+    fn namespace_entry(entry: &Self::NamespaceEntry) -> (u32, u32, u32) {
+        (entry.NameOffset, entry.NameLength, entry.DataOffset)
+    }
+
+    fn value_array(values: &Self::ValueArray) -> u32 {
+        values.Count
+    }
+
+    fn value_entry(value: &Self::ValueEntry) -> (u32, u32) {
+        (value.ValueOffset, value.ValueLength)
+    }
+}
@@ -0,0 +1,286 @@
+//! Package graph of the apps packaged with MSIX/AppX
+//!
+//! The loader looks up the DLLs of a packaged app in the directories of its package graph (its own
+//! package, then the packages it depends on, as declared in its AppxManifest.xml) before the
+//! directory of the executable and the system directory.
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+
+use crate::common::{FileOperation, LookupError};
+
+/// Name of the manifest at the root of every package
+pub const MANIFEST_FILE_NAME: &str = "AppxManifest.xml";
+
+/// Package another one depends on, as declared in the manifest
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct PackageDependency {
+    pub name: String,
+    pub publisher: Option<String>,
+    pub min_version: Option<String>,
+}
+
+/// Content of an AppxManifest.xml relevant to the DLL lookup
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct AppxManifest {
+    /// directory of the package, i.e. the one containing the manifest
+    pub package_dir: PathBuf,
+    pub name: String,
+    pub version: Option<String>,
+    /// processor architecture of the package, e.g. "x64" or "neutral"
+    pub architecture: Option<String>,
+    /// packages the app depends on (e.g. frameworks such as the VC++ runtime), in order
+    pub dependencies: Vec<PackageDependency>,
+    /// executables of the applications declared by the package, relative to the package directory
+    pub executables: Vec<PathBuf>,
+}
+
+/// Parse a version of the form "a.b.c.d" into comparable numbers
+fn parse_version(version: &str) -> Vec<u32> {
+    version
+        .split('.')
+        .map(|n| n.trim().parse().unwrap_or(0))
+        .collect()
+}
+
+impl AppxManifest {
+    /// Read the manifest of a package
+    pub fn read<P: AsRef<Path>>(manifest_path: P) -> Result<Self, LookupError> {
+        let manifest_path = manifest_path.as_ref();
+        let content = fs::read_to_string(manifest_path)
+            .map_err(|e| LookupError::from(e).in_file(manifest_path, FileOperation::Read))?;
+        let package_dir = manifest_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        Self::parse(&content, package_dir)
+            .map_err(|e| e.in_file(manifest_path, FileOperation::Parse))
+    }
+
+    /// Parse the content of the manifest of the package in the given directory
+    fn parse(content: &str, package_dir: PathBuf) -> Result<Self, LookupError> {
+        let doc = roxmltree::Document::parse(content.trim_start_matches('\u{feff}'))
+            .map_err(|e| LookupError::ParseError(e.to_string()))?;
+        let identity = doc
+            .descendants()
+            .find(|n| n.has_tag_name("Identity"))
+            .ok_or_else(|| LookupError::ParseError("no package identity found".to_owned()))?;
+        let dependencies = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("PackageDependency"))
+            .filter_map(|n| {
+                Some(PackageDependency {
+                    name: n.attribute("Name")?.to_owned(),
+                    publisher: n.attribute("Publisher").map(str::to_owned),
+                    min_version: n.attribute("MinVersion").map(str::to_owned),
+                })
+            })
+            .collect();
+        let executables = doc
+            .descendants()
+            .filter(|n| n.has_tag_name("Application"))
+            .filter_map(|n| n.attribute("Executable"))
+            .map(|e| PathBuf::from(e.replace('\\', "/")))
+            .collect();
+        Ok(Self {
+            package_dir,
+            name: identity.attribute("Name").unwrap_or_default().to_owned(),
+            version: identity.attribute("Version").map(str::to_owned),
+            architecture: identity
+                .attribute("ProcessorArchitecture")
+                .map(str::to_owned),
+            dependencies,
+            executables,
+        })
+    }
+
+    /// Find the manifest of the package an executable belongs to, i.e. the closest one in the
+    /// directory of the executable or in its ancestors
+    pub fn find_for_executable<P: AsRef<Path>>(exe_path: P) -> Option<PathBuf> {
+        exe_path
+            .as_ref()
+            .ancestors()
+            .skip(1)
+            .map(|d| d.join(MANIFEST_FILE_NAME))
+            .find(|m| m.is_file())
+    }
+
+    /// Directory of the installed package satisfying a dependency, looked up in the given
+    /// package roots (e.g. C:\Program Files\WindowsApps)
+    ///
+    /// Package directories are named Name_Version_Architecture__PublisherId; the highest version
+    /// at least equal to the required one is picked, among the ones built for the architecture
+    /// of the app or neutral.
+    pub fn find_dependency_dir(
+        &self,
+        dependency: &PackageDependency,
+        package_roots: &[PathBuf],
+    ) -> Option<PathBuf> {
+        let min_version = dependency
+            .min_version
+            .as_deref()
+            .map(parse_version)
+            .unwrap_or_default();
+        let prefix = format!("{}_", dependency.name.to_lowercase());
+        let mut best: Option<(Vec<u32>, PathBuf)> = None;
+        for root in package_roots {
+            let entries = match fs::read_dir(root) {
+                Ok(entries) => entries,
+                Err(_) => continue,
+            };
+            for entry in entries.flatten() {
+                let dir_name = entry.file_name().to_string_lossy().to_lowercase();
+                let rest = match dir_name.strip_prefix(&prefix) {
+                    Some(rest) => rest,
+                    None => continue,
+                };
+                let mut fields = rest.split('_');
+                let version = parse_version(fields.next().unwrap_or_default());
+                let architecture = fields.next().unwrap_or_default();
+                let architecture_matches = architecture == "neutral"
+                    || self
+                        .architecture
+                        .as_deref()
+                        .map(|a| a.eq_ignore_ascii_case(architecture))
+                        .unwrap_or(true);
+                if !architecture_matches || version < min_version || !entry.path().is_dir() {
+                    continue;
+                }
+                if best.as_ref().map(|(v, _)| version > *v).unwrap_or(true) {
+                    best = Some((version, entry.path()));
+                }
+            }
+        }
+        best.map(|(_, dir)| dir)
+    }
+
+    /// Directories of the package graph, in lookup order: the package itself, then the packages
+    /// it depends on; the names of the dependencies not found in the package roots are returned
+    /// separately
+    pub fn package_graph(&self, package_roots: &[PathBuf]) -> (Vec<PathBuf>, Vec<String>) {
+        let mut dirs = vec![self.package_dir.clone()];
+        let mut missing = Vec::new();
+        for dependency in &self.dependencies {
+            match self.find_dependency_dir(dependency, package_roots) {
+                Some(dir) => dirs.push(dir),
+                None => missing.push(dependency.name.clone()),
+            }
+        }
+        (dirs, missing)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::appx::AppxManifest;
+    use crate::common::LookupError;
+    use crate::path::{LookupPath, PathProfile};
+    use crate::query::LookupQuery;
+    use fs_err as fs;
+    use std::path::PathBuf;
+
+    #[test]
+    fn package_graph() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let manifest_path = d.join("test_data/appx/AppxManifest.xml");
+        let manifest = AppxManifest::read(&manifest_path)?;
+        assert_eq!(manifest.name, "DepRunTest");
+        assert_eq!(manifest.architecture.as_deref(), Some("x64"));
+        assert_eq!(manifest.dependencies.len(), 2);
+        assert_eq!(
+            manifest.dependencies[0].name,
+            "Microsoft.VCLibs.140.00.Debug"
+        );
+        assert_eq!(manifest.executables, vec![PathBuf::from("DepRunTest.exe")]);
+
+        // installed packages: only the newest compatible version of the first dependency is picked
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().to_path_buf();
+        for package in [
+            "Microsoft.VCLibs.140.00.Debug_14.0.30000.0_x64__8wekyb3d8bbwe",
+            "Microsoft.VCLibs.140.00.Debug_14.0.30704.0_x64__8wekyb3d8bbwe",
+            "Microsoft.VCLibs.140.00.Debug_14.0.30704.0_x86__8wekyb3d8bbwe",
+            "Microsoft.VCLibs.140.00.Debug_14.0.33519.0_x64__8wekyb3d8bbwe",
+        ] {
+            fs::create_dir_all(root.join(package))?;
+        }
+        let (dirs, missing) = manifest.package_graph(std::slice::from_ref(&root));
+
+        // the errors name the manifest
+        let broken = root.join("AppxManifest.xml");
+        fs::write(&broken, "<Package></Package>")?;
+        let err = AppxManifest::read(&broken).unwrap_err();
+        assert_eq!(err.path(), Some(broken.as_path()));
+
+        assert_eq!(dirs.len(), 2);
+        assert_eq!(dirs[0], manifest_path.parent().unwrap());
+        assert!(dirs[1].ends_with("Microsoft.VCLibs.140.00.Debug_14.0.33519.0_x64__8wekyb3d8bbwe"));
+        assert_eq!(missing, vec!["Microsoft.WindowsAppRuntime.1.4".to_owned()]);
+
+        assert_eq!(
+            AppxManifest::find_for_executable(d.join("test_data/appx/DepRunTest.exe")),
+            Some(manifest_path)
+        );
+
+        // the package graph comes before the directory of the executable, and the working
+        // directory is not looked into
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = None;
+        assert_eq!(
+            query.update_from_appx_manifest(&manifest),
+            vec![
+                "Microsoft.VCLibs.140.00.Debug".to_owned(),
+                "Microsoft.WindowsAppRuntime.1.4".to_owned()
+            ]
+        );
+        let path = LookupPath::deduce(&query);
+        let kinds: Vec<&str> = path.entries.iter().map(|e| e.kind_name()).collect();
+        assert_eq!(kinds[..2], ["PackageGraph", "ExecutableDir"]);
+        assert!(!kinds.contains(&"WorkingDir"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn package_graph_lookup_path() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+        // the package of the app, installed next to the one of a framework it depends on
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().to_path_buf();
+        let package_dir = root.join("DepRunTest_1.0.0.0_x64__8wekyb3d8bbwe");
+        let framework_dir =
+            root.join("Microsoft.VCLibs.140.00.Debug_14.0.33519.0_x64__8wekyb3d8bbwe");
+        fs::create_dir_all(&package_dir)?;
+        fs::create_dir_all(&framework_dir)?;
+        fs::copy(
+            d.join("test_data/appx/AppxManifest.xml"),
+            package_dir.join("AppxManifest.xml"),
+        )?;
+        let manifest = AppxManifest::read(package_dir.join("AppxManifest.xml"))?;
+
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = None;
+        query.update_from_appx_manifest(&manifest);
+        assert_eq!(query.target.package_graph, vec![package_dir, framework_dir]);
+
+        // every directory of the package graph is a separate entry of the lookup path
+        let path = LookupPath::builder(&query).build()?;
+        let kinds: Vec<&str> = path.entries.iter().map(|e| e.kind_name()).collect();
+        assert_eq!(
+            kinds[..3],
+            ["PackageGraph", "PackageGraph", "ExecutableDir"]
+        );
+
+        let profile = PathProfile::from_lookup_path(&path);
+        let from_profile = profile.lookup_path(&query, &root)?;
+        assert_eq!(from_profile.entries, path.entries);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,448 @@
+//! Scan of the executables contained in an archive (.zip or .7z) of a deployed application
+//!
+//! The files of the archive are exposed through a `FileProvider` under the path of the archive
+//! itself (e.g. dist/app-1.2.3.zip/bin/app.exe), so that they can be looked up along with the
+//! DLLs of the Windows partition, without unpacking the archive to disk.
+
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::io::Read;
+use std::path::{Component, Path, PathBuf};
+use std::sync::{Mutex, PoisonError};
+use std::time::SystemTime;
+
+use fs_err as fs;
+
+use crate::common::LookupError;
+use crate::executable::FileStamp;
+use crate::provider::{FileProvider, LocalFileSystem};
+use crate::query::LookupQuery;
+use crate::system::WindowsSystem;
+
+/// Separator between the path of an archive and the path of a file inside it, as in
+/// app-1.2.3.zip!/bin/app.exe
+pub const ARCHIVE_MEMBER_SEPARATOR: char = '!';
+
+/// Largest file read from an archive, well above the size of any executable: the sizes recorded
+/// in the archive are not trusted to allocate memory
+const MAX_MEMBER_SIZE: u64 = 1 << 30;
+
+/// Archive formats that can be scanned
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ArchiveFormat {
+    Zip,
+    SevenZip,
+}
+
+impl ArchiveFormat {
+    /// Format of an archive, deduced from its extension
+    pub fn from_path<P: AsRef<Path>>(path: P) -> Option<Self> {
+        let extension = path.as_ref().extension()?.to_str()?.to_lowercase();
+        match extension.as_str() {
+            "zip" => Some(Self::Zip),
+            "7z" => Some(Self::SevenZip),
+            _ => None,
+        }
+    }
+}
+
+/// Split a path of the form archive.zip!/path/inside/archive into the path of the archive and
+/// the path of the file relative to the root of the archive
+///
+/// Returns None if the path does not refer to a file inside an archive of a known format.
+pub fn split_archive_path(path: &str) -> Option<(PathBuf, PathBuf)> {
+    let (archive, member) = path.split_once(ARCHIVE_MEMBER_SEPARATOR)?;
+    ArchiveFormat::from_path(archive)?;
+    let member = relative_member_path(member);
+    if member.as_os_str().is_empty() {
+        return None;
+    }
+    Some((PathBuf::from(archive), member))
+}
+
+/// Path of a file inside an archive, relative to its root and with the separators of this host
+fn relative_member_path(name: &str) -> PathBuf {
+    name.split(['/', '\\'])
+        .filter(|c| !c.is_empty() && *c != ".")
+        .collect()
+}
+
+/// Query for the scan of an executable contained in an archive
+///
+/// The Windows partition is deduced from the location of the archive, as for the executables
+/// lying on disk; the application and working directories are the one of the executable inside
+/// the archive.
+pub fn lookup_query<P: AsRef<Path>, Q: AsRef<Path>>(
+    archive_path: P,
+    member: Q,
+) -> Result<LookupQuery, LookupError> {
+    let archive_path = fs::canonicalize(archive_path.as_ref())?;
+    let query = LookupQuery::deduce_from_executable_location(&archive_path)?;
+    Ok(retarget(query, archive_path.join(member.as_ref())))
+}
+
+/// Same as `lookup_query`, for a target given by its full path under the (canonical) path of the
+/// archive, and with the given Windows installation (see `session::ScanSession`)
+pub(crate) fn member_query(
+    archive_path: &Path,
+    target_exe: &Path,
+    system: Option<WindowsSystem>,
+) -> Result<LookupQuery, LookupError> {
+    let query = LookupQuery::deduce_with_system(archive_path, system)?;
+    Ok(retarget(query, target_exe.to_owned()))
+}
+
+/// Query deduced for an archive, targeting an executable inside it
+fn retarget(mut query: LookupQuery, target_exe: PathBuf) -> LookupQuery {
+    let app_dir = target_exe
+        .parent()
+        .map(Path::to_path_buf)
+        .unwrap_or_else(|| query.target.app_dir.clone());
+    query.target.target_exe = target_exe;
+    query.target.app_dir = app_dir.clone();
+    query.target.working_dir = app_dir;
+    query
+}
+
+/// Content of a file of an archive, checked against the size recorded in the archive
+fn read_member(data: impl Read, size: u64, member: &str) -> std::io::Result<Vec<u8>> {
+    let invalid = |message| std::io::Error::new(std::io::ErrorKind::InvalidData, message);
+    if size > MAX_MEMBER_SIZE {
+        return Err(invalid(format!("{member} is too large ({size} bytes)")));
+    }
+    let mut buffer = Vec::new();
+    data.take(size + 1).read_to_end(&mut buffer)?;
+    if buffer.len() as u64 != size {
+        return Err(invalid(format!(
+            "{member} does not have the size recorded in the archive"
+        )));
+    }
+    Ok(buffer)
+}
+
+enum ArchiveContent {
+    Zip(Mutex<zip::ZipArchive<std::fs::File>>),
+    /// 7z archives are usually solid, i.e. their files are compressed together in blocks: a file
+    /// is read by decompressing its block up to it
+    SevenZip {
+        archive: Box<sevenz_rust::Archive>,
+        file: Mutex<std::fs::File>,
+    },
+}
+
+/// Provides the files contained in an archive under the path of the archive, and any other file
+/// from the local file system
+pub struct ArchiveFileProvider {
+    archive_path: PathBuf,
+    archive_stamp: FileStamp,
+    /// index and size of the files of the archive, by path relative to its root
+    files: HashMap<PathBuf, (usize, u64)>,
+    /// names of the files in each directory of the archive, relative to its root
+    files_in_dirs: HashMap<PathBuf, Vec<OsString>>,
+    /// names of the subdirectories of each directory of the archive, relative to its root
+    subdirectories: HashMap<PathBuf, BTreeSet<OsString>>,
+    content: ArchiveContent,
+}
+
+fn archive_error(archive_path: &Path, e: impl std::fmt::Display) -> LookupError {
+    LookupError::ArchiveError(format!("{}: {e}", archive_path.display()))
+}
+
+impl ArchiveFileProvider {
+    /// Read the list of files of an archive
+    pub fn open<P: AsRef<Path>>(archive_path: P) -> Result<Self, LookupError> {
+        let archive_path = fs::canonicalize(archive_path.as_ref())?;
+        let format = ArchiveFormat::from_path(&archive_path).ok_or_else(|| {
+            archive_error(
+                &archive_path,
+                "unknown archive format (expected .zip or .7z)",
+            )
+        })?;
+        let mut names = Vec::new();
+        let content = match format {
+            ArchiveFormat::Zip => {
+                let mut archive = zip::ZipArchive::new(std::fs::File::open(&archive_path)?)
+                    .map_err(|e| archive_error(&archive_path, e))?;
+                for index in 0..archive.len() {
+                    let entry = archive
+                        .by_index_raw(index)
+                        .map_err(|e| archive_error(&archive_path, e))?;
+                    if entry.is_file() {
+                        names.push((entry.name().to_owned(), index, entry.size()));
+                    }
+                }
+                ArchiveContent::Zip(Mutex::new(archive))
+            }
+            ArchiveFormat::SevenZip => {
+                // only the headers are read, the files are decompressed when they are read
+                let mut file = std::fs::File::open(&archive_path)?;
+                let length = file.metadata()?.len();
+                let archive = sevenz_rust::Archive::read(&mut file, length, &[])
+                    .map_err(|e| archive_error(&archive_path, e))?;
+                for (index, entry) in archive.files.iter().enumerate() {
+                    if !entry.is_directory() {
+                        names.push((entry.name().to_owned(), index, entry.size()));
+                    }
+                }
+                ArchiveContent::SevenZip {
+                    archive: Box::new(archive),
+                    file: Mutex::new(file),
+                }
+            }
+        };
+
+        let mut files = HashMap::new();
+        let mut files_in_dirs: HashMap<PathBuf, Vec<OsString>> = HashMap::new();
+        let mut subdirectories: HashMap<PathBuf, BTreeSet<OsString>> = HashMap::new();
+        for (name, index, size) in names {
+            let member = relative_member_path(&name);
+            // entries escaping the root of the archive are ignored
+            if member.components().any(|c| c == Component::ParentDir) {
+                continue;
+            }
+            if let Some(file_name) = member.file_name() {
+                let dir = member.parent().map(Path::to_path_buf).unwrap_or_default();
+                for ancestor in dir.ancestors() {
+                    if let (Some(parent), Some(name)) = (ancestor.parent(), ancestor.file_name()) {
+                        subdirectories
+                            .entry(parent.to_path_buf())
+                            .or_default()
+                            .insert(name.to_os_string());
+                    }
+                }
+                files_in_dirs
+                    .entry(dir)
+                    .or_default()
+                    .push(file_name.to_os_string());
+                files.insert(member, (index, size));
+            }
+        }
+
+        Ok(Self {
+            archive_stamp: FileStamp::read(&archive_path)?,
+            archive_path,
+            files,
+            files_in_dirs,
+            subdirectories,
+            content,
+        })
+    }
+
+    /// Path of the archive, under which its files are provided
+    pub fn archive_path(&self) -> &Path {
+        &self.archive_path
+    }
+
+    /// Paths of the files contained in the archive, relative to its root
+    pub fn members(&self) -> impl Iterator<Item = &Path> {
+        self.files.keys().map(PathBuf::as_path)
+    }
+
+    /// Whether the archive contains a file at the given path, relative to its root
+    pub fn contains<P: AsRef<Path>>(&self, member: P) -> bool {
+        self.files.contains_key(member.as_ref())
+    }
+
+    /// Path relative to the root of the archive, if the given path lies inside it
+    fn member<'p>(&self, path: &'p Path) -> Option<&'p Path> {
+        path.strip_prefix(&self.archive_path).ok()
+    }
+}
+
+impl FileProvider for ArchiveFileProvider {
+    fn list_files(&self, dir: &Path) -> Result<Vec<OsString>, LookupError> {
+        match self.member(dir) {
+            Some(member) => Ok(self.files_in_dirs.get(member).cloned().unwrap_or_default()),
+            None => LocalFileSystem.list_files(dir),
+        }
+    }
+
+    fn list_subdirectories(&self, dir: &Path) -> Result<Vec<OsString>, LookupError> {
+        match self.member(dir) {
+            Some(member) => Ok(self
+                .subdirectories
+                .get(member)
+                .map(|names| names.iter().cloned().collect())
+                .unwrap_or_default()),
+            None => LocalFileSystem.list_subdirectories(dir),
+        }
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>, LookupError> {
+        let member = match self.member(path) {
+            Some(member) => member,
+            None => return LocalFileSystem.read_file(path),
+        };
+        let &(index, size) = self.files.get(member).ok_or_else(|| {
+            archive_error(
+                &self.archive_path,
+                format!("no file {} in the archive", member.display()),
+            )
+        })?;
+        let name = member.to_string_lossy();
+        match &self.content {
+            ArchiveContent::Zip(archive) => {
+                let mut archive = archive.lock().unwrap_or_else(PoisonError::into_inner);
+                let entry = archive
+                    .by_index(index)
+                    .map_err(|e| archive_error(&self.archive_path, e))?;
+                read_member(entry, size, &name).map_err(|e| archive_error(&self.archive_path, e))
+            }
+            ArchiveContent::SevenZip { archive, file } => {
+                let Some(block) = archive.stream_map.file_folder_index[index] else {
+                    // empty file
+                    return Ok(Vec::new());
+                };
+                let mut file = file.lock().unwrap_or_else(PoisonError::into_inner);
+                let mut entry_index = archive.stream_map.folder_first_file_index[block];
+                let mut content = None;
+                sevenz_rust::BlockDecoder::new(block, archive, &[], &mut *file)
+                    .for_each_entries(&mut |_, data| {
+                        if entry_index == index {
+                            content = Some(read_member(data, size, &name));
+                            return Ok(false);
+                        }
+                        // the files of a block are decompressed in sequence
+                        std::io::copy(data, &mut std::io::sink())?;
+                        entry_index += 1;
+                        Ok(true)
+                    })
+                    .map_err(|e| archive_error(&self.archive_path, e))?;
+                match content {
+                    Some(content) => content.map_err(|e| archive_error(&self.archive_path, e)),
+                    None => Err(archive_error(
+                        &self.archive_path,
+                        format!("{name} not found"),
+                    )),
+                }
+            }
+        }
+    }
+
+    fn file_stamp(&self, path: &Path) -> Result<FileStamp, LookupError> {
+        match self.member(path) {
+            // the files of the archive change only if the archive does
+            Some(member) => match self.files.get(member) {
+                Some(&(_, size)) => Ok(FileStamp {
+                    size,
+                    modified: self.archive_stamp.modified,
+                }),
+                None => Err(archive_error(
+                    &self.archive_path,
+                    format!("no file {} in the archive", member.display()),
+                )),
+            },
+            None => LocalFileSystem.file_stamp(path),
+        }
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        match self.member(path) {
+            Some(_) => self.archive_stamp.modified,
+            None => LocalFileSystem.modified(path),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use crate::archive::{lookup_query, read_member, split_archive_path, ArchiveFileProvider};
+    use crate::common::{LookupError, NamePatterns};
+    use crate::directory::{scan_directory, DirectoryScanOptions};
+    use crate::path::LookupPath;
+    use crate::runner::run;
+
+    #[test]
+    fn scan_archives() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        assert_eq!(
+            split_archive_path("dist/app.ZIP!/bin\\app.exe"),
+            Some((PathBuf::from("dist/app.ZIP"), PathBuf::from("bin/app.exe")))
+        );
+        assert_eq!(split_archive_path("dist/app.tar!/bin/app.exe"), None);
+        assert_eq!(split_archive_path("dist/app.exe"), None);
+
+        for archive in ["DepRunTest-1.0.zip", "DepRunTest-1.0.7z"] {
+            let archive_path = d.join("test_data/archive").join(archive);
+            let provider = ArchiveFileProvider::open(&archive_path)?;
+            assert_eq!(provider.members().count(), 2);
+
+            let member = PathBuf::from("DepRunTest-1.0/bin/DepRunTest.exe");
+            let mut query = lookup_query(&archive_path, &member)?;
+            query.parameters.extract_symbols = true;
+            let mut lookup_path = LookupPath::deduce(&query);
+            lookup_path.set_file_provider(Arc::new(provider));
+            let res = run(&query, &lookup_path)?;
+
+            let lib = res.get("DepRunTestLib.dll").unwrap();
+            assert!(lib.found);
+            let lib_details = lib.details.as_ref().unwrap();
+            assert_eq!(
+                lib_details.full_path,
+                Some(
+                    fs_err::canonicalize(&archive_path)?
+                        .join("DepRunTest-1.0/bin/DepRunTestLib.dll")
+                )
+            );
+            assert_eq!(lib_details.file_stamp.as_ref().unwrap().size, 58368);
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn scan_directories_of_archives() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let options = DirectoryScanOptions {
+            recursive: true,
+            max_depth: None,
+            include: NamePatterns::default(),
+            exclude: NamePatterns::default(),
+        };
+        for archive in ["DepRunTest-1.0.zip", "DepRunTest-1.0.7z"] {
+            let archive_path = d.join("test_data/archive").join(archive);
+            let root = fs_err::canonicalize(&archive_path)?;
+            let bin_dir = root.join("DepRunTest-1.0/bin");
+            let archive_path = archive_path.to_str().unwrap();
+
+            // the whole archive, a directory inside it, and a single executable
+            for (dir, scanned_dir, targets) in [
+                (archive_path.to_owned(), &root, 2),
+                (format!("{archive_path}!/DepRunTest-1.0/bin"), &bin_dir, 2),
+                (
+                    format!("{archive_path}!/DepRunTest-1.0/bin/DepRunTest.exe"),
+                    &bin_dir,
+                    1,
+                ),
+            ] {
+                let scan = scan_directory(&dir, &options, |_| {})?;
+                assert!(scan.failures.is_empty(), "{:?}", scan.failures);
+                assert_eq!(&scan.dir, scanned_dir);
+                assert_eq!(scan.targets.len(), targets);
+                let exe = scan
+                    .targets
+                    .iter()
+                    .find(|t| t.path.ends_with("DepRunTest.exe"));
+                assert!(
+                    exe.unwrap()
+                        .executables
+                        .get("DepRunTestLib.dll")
+                        .unwrap()
+                        .found
+                );
+            }
+        }
+        Ok(())
+    }
+
+    #[test]
+    fn read_member_sizes() {
+        assert_eq!(read_member(&b"MZ"[..], 2, "a.dll").unwrap(), b"MZ");
+        // the content must match the size recorded in the archive, which is bounded
+        assert!(read_member(&b"MZ"[..], 1, "a.dll").is_err());
+        assert!(read_member(&b"MZ"[..], 3, "a.dll").is_err());
+        assert!(read_member(&b"MZ"[..], u64::MAX, "a.dll").is_err());
+    }
+}
@@ -0,0 +1,176 @@
+//! Audit of the DLL search order for hijacking opportunities
+//!
+//! A DLL resolved (or not resolved at all) through a location that an attacker could write to can
+//! be replaced by a malicious one, which will then be loaded by the target with its privileges.
+
+use std::path::{Path, PathBuf};
+
+use serde::{Deserialize, Serialize};
+
+use crate::common::LookupError;
+use crate::executable::Executables;
+use crate::path::{LookupPath, LookupPathEntry};
+
+/// Reason why a dependency could be hijacked
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum HijackRiskKind {
+    /// resolved from the working directory of the process
+    WorkingDirectory,
+    /// resolved from a directory that is typically writable by unprivileged users
+    UserWritableDirectory,
+    /// resolved from a PATH entry searched before the system directories
+    PathBeforeSystemDirs,
+    /// resolved outside the system directories, although a system DLL with the same name exists
+    ShadowsSystemDll,
+    /// not found: a DLL planted in the working directory would be loaded
+    UnresolvedFallsThroughToWorkingDir,
+}
+
+impl std::fmt::Display for HijackRiskKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let description = match self {
+            Self::WorkingDirectory => "resolved from the working directory",
+            Self::UserWritableDirectory => "resolved from a user-writable directory",
+            Self::PathBeforeSystemDirs => "resolved from a PATH entry preceding the system dirs",
+            Self::ShadowsSystemDll => "shadows a system DLL",
+            Self::UnresolvedFallsThroughToWorkingDir => {
+                "not found, would be loaded from the working directory"
+            }
+        };
+        f.write_str(description)
+    }
+}
+
+/// Dependency that could be hijacked
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct HijackRisk {
+    pub dllname: String,
+    pub kind: HijackRiskKind,
+    /// file the DLL is currently resolved to, if found
+    pub path: Option<PathBuf>,
+}
+
+/// Find the dependencies of the scan which could be hijacked, given the lookup path used for it
+///
+/// The root executable is not audited. The executable directory is not considered user-writable,
+/// since whoever can write there can replace the target itself.
+pub fn audit(
+    executables: &Executables,
+    lookup_path: &LookupPath,
+) -> Result<Vec<HijackRisk>, LookupError> {
+    let can_fall_through_to_working_dir = lookup_path
+        .entries
+        .iter()
+        .any(|e| matches!(e, LookupPathEntry::WorkingDir(_)));
+    let first_system_entry = lookup_path.entries.iter().position(|e| e.is_system());
+
+    let mut risks = Vec::new();
+    for exe in executables.iter() {
+        if exe.depth_first_appearance == 0 {
+            continue;
+        }
+        if !exe.found {
+            if can_fall_through_to_working_dir {
+                risks.push(HijackRisk {
+                    dllname: exe.dllname.clone(),
+                    kind: HijackRiskKind::UnresolvedFallsThroughToWorkingDir,
+                    path: None,
+                });
+            }
+            continue;
+        }
+
+        let locations = lookup_path.search_dll_everywhere(&exe.dllname)?;
+        let resolved = match locations.first() {
+            Some(resolved) => resolved,
+            None => continue,
+        };
+        if resolved.location.is_system() {
+            continue;
+        }
+        let mut push = |kind| {
+            risks.push(HijackRisk {
+                dllname: exe.dllname.clone(),
+                kind,
+                path: resolved.fullpath.clone(),
+            })
+        };
+
+        match &resolved.location {
+            LookupPathEntry::WorkingDir(_) => push(HijackRiskKind::WorkingDirectory),
+            LookupPathEntry::SystemPath(_) | LookupPathEntry::UserPath(_) => {
+                let index = lookup_path
+                    .entries
+                    .iter()
+                    .position(|e| *e == resolved.location);
+                if let (Some(index), Some(first_system_entry)) = (index, first_system_entry) {
+                    if index < first_system_entry {
+                        push(HijackRiskKind::PathBeforeSystemDirs);
+                    }
+                }
+            }
+            _ => {}
+        }
+        if !matches!(resolved.location, LookupPathEntry::ExecutableDir(_))
+            && resolved
+                .fullpath
+                .as_deref()
+                .and_then(Path::parent)
+                .map(is_user_writable)
+                .unwrap_or(false)
+        {
+            push(HijackRiskKind::UserWritableDirectory);
+        }
+        if locations.iter().skip(1).any(|l| l.location.is_system()) {
+            push(HijackRiskKind::ShadowsSystemDll);
+        }
+    }
+    Ok(risks)
+}
+
+/// Heuristic check for directories unprivileged users can usually write to
+fn is_user_writable(dir: &Path) -> bool {
+    const USER_WRITABLE_COMPONENTS: &[&str] = &["users", "programdata", "appdata", "temp", "tmp"];
+    dir.components().any(|c| {
+        c.as_os_str()
+            .to_str()
+            .map(|s| {
+                USER_WRITABLE_COMPONENTS
+                    .iter()
+                    .any(|w| s.eq_ignore_ascii_case(w))
+            })
+            .unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::audit::{audit, HijackRiskKind};
+    use crate::common::LookupError;
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+
+    #[test]
+    fn audit_working_directory() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe");
+        let lib_dir = d.join("test_data/test_project1/DepRunTest/build/DepRunTestLib/Debug");
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.target.working_dir = lib_dir;
+        let lookup_path = LookupPath::deduce(&query);
+        let exes = run(&query, &lookup_path)?;
+
+        let risks = audit(&exes, &lookup_path)?;
+        assert!(risks.iter().any(
+            |r| r.dllname == "DepRunTestLib.dll" && r.kind == HijackRiskKind::WorkingDirectory
+        ));
+        assert!(risks.iter().any(|r| r.dllname == "KERNEL32.dll"
+            && r.kind == HijackRiskKind::UnresolvedFallsThroughToWorkingDir));
+        assert!(risks.iter().all(|r| r.dllname != "DepRunTest.exe"));
+
+        Ok(())
+    }
+}
@@ -0,0 +1,190 @@
+//! Lists of forbidden imports, e.g. the C runtime functions banned by the Security Development
+//! Lifecycle (SDL), or functions whose use is deprecated in a code base
+//!
+//! A list has one entry per line, in the form `[DLL!]SYMBOL`, where both parts are patterns
+//! matched case-insensitively (e.g. `strcpy`, `kernel32.dll!LoadLibraryA` or `msvcr*.dll!_mbs*`);
+//! anything after a # is the reason given in the findings.
+
+use std::path::Path;
+
+use fs_err as fs;
+
+use crate::common::{LookupError, NamePatterns};
+
+/// Functions banned by the SDL, as listed in banned.h
+const SDL_BANNED_FUNCTIONS: &str = "\
+strcpy  # unbounded copy, use strcpy_s
+strcpyA
+strcpyW
+wcscpy
+_tcscpy
+_mbscpy
+lstrcpy
+lstrcpyA
+lstrcpyW
+StrCpy
+StrCpyA
+StrCpyW
+strcat  # unbounded concatenation, use strcat_s
+strcatA
+strcatW
+wcscat
+_tcscat
+_mbscat
+lstrcat
+lstrcatA
+lstrcatW
+StrCat
+StrCatA
+StrCatW
+sprintf  # unbounded formatting, use sprintf_s
+swprintf
+wsprintfA
+wsprintfW
+vsprintf
+vswprintf
+wvsprintfA
+wvsprintfW
+gets  # unbounded read, use gets_s
+_getws
+strncpy  # no null termination guaranteed, use strncpy_s
+wcsncpy
+lstrcpynA
+lstrcpynW
+strtok  # not reentrant, use strtok_s
+wcstok
+_mbstok
+";
+
+/// Forbidden import: a symbol pattern, optionally restricted to the DLLs matching a pattern
+#[derive(Debug, Clone)]
+pub struct BannedImport {
+    /// the entry as written in the list
+    pub entry: String,
+    dll: Option<NamePatterns>,
+    symbol: NamePatterns,
+    /// why the import is forbidden, if given
+    pub reason: Option<String>,
+}
+
+impl BannedImport {
+    /// Whether the given symbol imported from the given DLL is forbidden by this entry
+    pub fn matches(&self, dllname: &str, symbol: &str) -> bool {
+        self.dll.as_ref().is_none_or(|d| d.matches(dllname)) && self.symbol.matches(symbol)
+    }
+}
+
+/// List of forbidden imports
+#[derive(Debug, Clone, Default)]
+pub struct BannedImports {
+    pub entries: Vec<BannedImport>,
+}
+
+impl BannedImports {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Parse a list, one `[DLL!]SYMBOL [# reason]` entry per line; empty lines are ignored
+    pub fn parse(s: &str) -> Result<Self, LookupError> {
+        let mut entries = Vec::new();
+        for line in s.lines() {
+            let (entry, reason) = match line.split_once('#') {
+                Some((entry, reason)) => (entry.trim(), Some(reason.trim().to_owned())),
+                None => (line.trim(), None),
+            };
+            if entry.is_empty() {
+                continue;
+            }
+            let (dll, symbol) = match entry.split_once('!') {
+                Some((dll, symbol)) => (Some(NamePatterns::new(&[dll.trim()])?), symbol.trim()),
+                None => (None, entry),
+            };
+            entries.push(BannedImport {
+                entry: entry.to_owned(),
+                dll,
+                symbol: NamePatterns::new(&[symbol])?,
+                reason: reason.filter(|r| !r.is_empty()),
+            });
+        }
+        Ok(Self { entries })
+    }
+
+    /// Read a list from a file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, LookupError> {
+        Self::parse(&fs::read_to_string(path.as_ref())?)
+    }
+
+    /// The C runtime and Windows string functions banned by the SDL
+    pub fn sdl() -> Self {
+        Self::parse(SDL_BANNED_FUNCTIONS).expect("valid banned function list")
+    }
+
+    pub fn extend(&mut self, other: BannedImports) {
+        self.entries.extend(other.entries);
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.entries.is_empty()
+    }
+
+    /// First entry forbidding the given symbol imported from the given DLL, if any
+    pub fn find(&self, dllname: &str, symbol: &str) -> Option<&BannedImport> {
+        self.entries.iter().find(|e| e.matches(dllname, symbol))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::banned::BannedImports;
+    use crate::common::LookupError;
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::report::FindingKind;
+    use crate::runner::run;
+
+    #[test]
+    fn banned_imports() -> Result<(), LookupError> {
+        let sdl = BannedImports::sdl();
+        let strcpy = sdl.find("msvcrt.dll", "STRCPY").unwrap();
+        assert_eq!(
+            strcpy.reason.as_deref(),
+            Some("unbounded copy, use strcpy_s")
+        );
+        assert!(sdl.find("msvcrt.dll", "strcpy_s").is_none());
+
+        let banned = BannedImports::parse(
+            "# deprecated in this code base\n\nDepRunTest*.dll!*testMethod*  # use the new API\nother.dll!*\n",
+        )?;
+        assert_eq!(banned.entries.len(), 2);
+        assert!(banned.find("OTHER.DLL", "anything").is_some());
+
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = None;
+        query.target.user_path.clear();
+        query.parameters.extract_symbols = true;
+        let lookup_path = LookupPath::deduce(&query);
+        let report = run(&query, &lookup_path)?.check_banned_imports(&banned);
+        let findings: Vec<_> = report.with_code("DR0010").collect();
+        assert_eq!(findings.len(), 1);
+        match &findings[0].kind {
+            FindingKind::BannedImport {
+                importer,
+                dll,
+                reason,
+                ..
+            } => {
+                assert_eq!(importer, "DepRunTest.exe");
+                assert_eq!(dll, "DepRunTestLib.dll");
+                assert_eq!(reason.as_deref(), Some("use the new API"));
+            }
+            kind => panic!("unexpected finding {kind}"),
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,565 @@
+extern crate dependency_runner;
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::Duration;
+
+use clap::Parser;
+use eframe::egui;
+use egui::collapsing_header::CollapsingState;
+use egui::{Color32, RichText};
+
+use dependency_runner::common::{demangled, open_location, readable_path};
+use dependency_runner::executable::{Executable, Executables};
+use dependency_runner::path::LookupPath;
+use dependency_runner::query::LookupQuery;
+use dependency_runner::report::{Finding, Severity};
+
+#[derive(Parser)]
+#[clap(author, version, about = "Graphical viewer of the dependencies of Windows executables", long_about = None)]
+struct GuiCli {
+    #[clap(value_parser)]
+    /// Target file (.exe or .dll), or JSON result of a previous scan (deprun --format json)
+    input: Option<PathBuf>,
+    #[clap(value_parser, long)]
+    /// Read the complete DLL lookup path from a .dwp file (Dependency Walker's format)
+    dwp_path: Option<PathBuf>,
+    #[clap(value_parser, long, conflicts_with = "dwp_path")]
+    /// Read the complete DLL lookup path from a JSON path profile (see deprun --save-path-profile)
+    path_profile: Option<PathBuf>,
+}
+
+/// Source of the lookup path of the scans
+#[derive(Clone, Default)]
+enum PathSource {
+    #[default]
+    Deduced,
+    Dwp(PathBuf),
+    Profile(PathBuf),
+}
+
+/// Scan the target, or read the result of a previous scan from a JSON file
+fn scan(input: &Path, path_source: &PathSource) -> anyhow::Result<Executables> {
+    if input
+        .extension()
+        .is_some_and(|e| e.eq_ignore_ascii_case("json"))
+    {
+        return Ok(Executables::from_json_file(input)?);
+    }
+    let mut query = LookupQuery::deduce_from_executable_location(input)?;
+    query.parameters.extract_symbols = true;
+    let lookup_path = match path_source {
+        PathSource::Deduced => LookupPath::deduce(&query),
+        PathSource::Dwp(dwp_path) => LookupPath::from_dwp_file(dwp_path, &query)?,
+        PathSource::Profile(profile_path) => LookupPath::from_path_profile(profile_path, &query)?,
+    };
+    Ok(dependency_runner::runner::run(&query, &lookup_path)?)
+}
+
+/// Result of a scan, with the findings of the checks
+struct Scanned {
+    executables: Executables,
+    roots: Vec<String>,
+    findings: Vec<Finding>,
+}
+
+impl Scanned {
+    fn new(executables: Executables) -> anyhow::Result<Self> {
+        let roots = executables
+            .get_roots()?
+            .iter()
+            .map(|r| r.dllname.clone())
+            .collect();
+        let findings = executables.check(true)?.findings().to_vec();
+        Ok(Self {
+            executables,
+            roots,
+            findings,
+        })
+    }
+}
+
+struct DeprunGui {
+    input: String,
+    path_source: PathSource,
+    /// scan running in the background, if any
+    pending: Option<Receiver<anyhow::Result<Scanned>>>,
+    scanned: Option<Scanned>,
+    error: Option<String>,
+    selected: Option<String>,
+    /// chain of names to expand in the tree to show the selected executable
+    reveal: Option<Vec<String>>,
+    filter: String,
+    hide_system: bool,
+}
+
+impl DeprunGui {
+    fn new(cli: GuiCli) -> Self {
+        let path_source = match (cli.dwp_path, cli.path_profile) {
+            (Some(dwp_path), _) => PathSource::Dwp(dwp_path),
+            (_, Some(profile_path)) => PathSource::Profile(profile_path),
+            _ => PathSource::Deduced,
+        };
+        let mut gui = Self {
+            input: cli
+                .input
+                .as_ref()
+                .map(|p| p.display().to_string())
+                .unwrap_or_default(),
+            path_source,
+            pending: None,
+            scanned: None,
+            error: None,
+            selected: None,
+            reveal: None,
+            filter: String::new(),
+            hide_system: false,
+        };
+        if cli.input.is_some() {
+            gui.start_scan();
+        }
+        gui
+    }
+
+    fn start_scan(&mut self) {
+        let input = PathBuf::from(self.input.trim());
+        let path_source = self.path_source.clone();
+        let (sender, receiver) = channel();
+        std::thread::spawn(move || {
+            let _ = sender.send(scan(&input, &path_source).and_then(Scanned::new));
+        });
+        self.pending = Some(receiver);
+        self.error = None;
+    }
+
+    fn poll_scan(&mut self, ctx: &egui::Context) {
+        let Some(receiver) = &self.pending else {
+            return;
+        };
+        match receiver.try_recv() {
+            Ok(result) => {
+                self.pending = None;
+                match result {
+                    Ok(scanned) => {
+                        self.selected = scanned.roots.first().cloned();
+                        self.reveal = None;
+                        self.scanned = Some(scanned);
+                    }
+                    Err(e) => self.error = Some(format!("{e:#}")),
+                }
+            }
+            Err(_) => ctx.request_repaint_after(Duration::from_millis(100)),
+        }
+    }
+
+    /// Select the executable, expanding the tree down to it
+    fn select(&mut self, dllname: &str) {
+        self.selected = Some(dllname.to_owned());
+        self.reveal = self
+            .scanned
+            .as_ref()
+            .and_then(|s| s.executables.dependency_chains(dllname).ok())
+            .and_then(|chains| chains.into_iter().next());
+    }
+
+    fn top_bar(&mut self, ui: &mut egui::Ui) {
+        ui.horizontal(|ui| {
+            ui.label("Target:");
+            let edit = ui.add(
+                egui::TextEdit::singleline(&mut self.input)
+                    .hint_text("path/to/executable.exe or scan.json")
+                    .desired_width(ui.available_width() - 80.0),
+            );
+            let enter = edit.lost_focus() && ui.input(|i| i.key_pressed(egui::Key::Enter));
+            let idle = self.pending.is_none();
+            if (ui.add_enabled(idle, egui::Button::new("Scan")).clicked() || enter)
+                && idle
+                && !self.input.trim().is_empty()
+            {
+                self.start_scan();
+            }
+        });
+        if self.pending.is_some() {
+            ui.horizontal(|ui| {
+                ui.spinner();
+                ui.label("Scanning...");
+            });
+        }
+        if let Some(error) = &self.error {
+            ui.colored_label(Color32::RED, error);
+        }
+    }
+
+    fn tree_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(scanned) = &self.scanned else {
+            ui.label("No target scanned");
+            return;
+        };
+        ui.horizontal(|ui| {
+            ui.label("Filter:");
+            ui.text_edit_singleline(&mut self.filter);
+        });
+        ui.checkbox(&mut self.hide_system, "Hide system DLLs");
+        ui.separator();
+
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                let filter = self.filter.trim().to_lowercase();
+                if filter.is_empty() {
+                    let mut tree = Tree {
+                        executables: &scanned.executables,
+                        selected: self.selected.as_deref(),
+                        reveal: self.reveal.as_deref(),
+                        hide_system: self.hide_system,
+                        clicked: &mut clicked,
+                    };
+                    for root in &scanned.roots {
+                        tree.show(ui, vec![root.clone()]);
+                    }
+                } else {
+                    // a flat list of the matches, selecting one shows it in the tree
+                    for exe in scanned.executables.iter() {
+                        if !exe.dllname.to_lowercase().contains(&filter)
+                            || (self.hide_system && is_system(exe))
+                        {
+                            continue;
+                        }
+                        let selected = self.selected.as_deref() == Some(exe.dllname.as_str());
+                        if ui
+                            .selectable_label(selected, node_text(exe, &exe.dllname))
+                            .clicked()
+                        {
+                            clicked = Some(exe.dllname.clone());
+                        }
+                    }
+                }
+            });
+        // the chain was expanded by this frame
+        self.reveal = None;
+        if let Some(dllname) = clicked {
+            if self.filter.trim().is_empty() {
+                self.selected = Some(dllname);
+            } else {
+                self.filter.clear();
+                self.select(&dllname);
+            }
+        }
+    }
+
+    fn findings_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(scanned) = &self.scanned else {
+            return;
+        };
+        ui.heading(format!("Findings ({})", scanned.findings.len()));
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                if scanned.findings.is_empty() {
+                    ui.label("No problems found");
+                }
+                for finding in &scanned.findings {
+                    let involves_selected = self
+                        .selected
+                        .as_deref()
+                        .is_some_and(|s| finding.involves(s));
+                    let text = RichText::new(format!(
+                        "{} {}: {}",
+                        finding.code,
+                        finding.severity,
+                        finding.message()
+                    ))
+                    .color(severity_color(finding.severity));
+                    if ui.selectable_label(involves_selected, text).clicked() {
+                        clicked = finding.files.last().cloned();
+                    }
+                }
+            });
+        if let Some(dllname) = clicked {
+            self.select(&dllname);
+        }
+    }
+
+    fn details_panel(&mut self, ui: &mut egui::Ui) {
+        let Some(scanned) = &self.scanned else {
+            ui.label("Enter the path of an executable and press Scan");
+            return;
+        };
+        let Some(exe) = self
+            .selected
+            .as_deref()
+            .and_then(|s| scanned.executables.get(s))
+        else {
+            ui.label("Select a DLL in the tree");
+            return;
+        };
+        ui.heading(&exe.dllname);
+        let mut clicked = None;
+        egui::ScrollArea::vertical()
+            .auto_shrink([false; 2])
+            .show(ui, |ui| {
+                egui::Grid::new("details").num_columns(2).show(ui, |ui| {
+                    let mut row = |name: &str, value: String| {
+                        ui.label(name);
+                        ui.label(value);
+                        ui.end_row();
+                    };
+                    match &exe.details {
+                        None => row("status", "not found".to_owned()),
+                        Some(d) => {
+                            if let Some(path) = &d.full_path {
+                                row("path", readable_path(path));
+                            }
+                            row("kind", kind(exe).to_owned());
+                            if let Some(host) = &d.api_set_host {
+                                row("implemented by", host.clone());
+                            }
+                            if let Some(architecture) = d.architecture {
+                                row("architecture", architecture.to_string());
+                            }
+                            if let Some(subsystem) = d.subsystem {
+                                row("subsystem", subsystem.to_string());
+                            }
+                            if let Some(version) = &d.file_version {
+                                row("version", version.clone());
+                            }
+                            if let Some(stamp) = &d.file_stamp {
+                                row("size", format!("{} bytes", stamp.size));
+                            }
+                        }
+                    }
+                    row(
+                        "first appears at depth",
+                        exe.depth_first_appearance.to_string(),
+                    );
+                });
+                if let Some(path) = exe.details.as_ref().and_then(|d| d.full_path.as_ref()) {
+                    if ui.button("Open location").clicked() {
+                        if let Err(e) = open_location(path) {
+                            self.error = Some(format!(
+                                "Could not open the location of {}: {e}",
+                                path.display()
+                            ));
+                        }
+                    }
+                }
+
+                ui.separator();
+                let importers = scanned.executables.importers_of(&exe.dllname);
+                ui.collapsing(format!("Imported by ({})", importers.len()), |ui| {
+                    for edge in importers {
+                        if ui
+                            .link(format!("{} ({})", edge.importer, edge.kind))
+                            .clicked()
+                        {
+                            clicked = Some(edge.importer.clone());
+                        }
+                    }
+                });
+                let dependencies = exe
+                    .details
+                    .as_ref()
+                    .map(|d| d.all_dependencies())
+                    .unwrap_or_default();
+                ui.collapsing(format!("Dependencies ({})", dependencies.len()), |ui| {
+                    for dependency in dependencies {
+                        if ui.link(dependency).clicked() {
+                            clicked = Some(dependency.to_owned());
+                        }
+                    }
+                });
+                let findings: Vec<&Finding> = scanned
+                    .findings
+                    .iter()
+                    .filter(|f| f.involves(&exe.dllname))
+                    .collect();
+                ui.collapsing(format!("Findings ({})", findings.len()), |ui| {
+                    for finding in findings {
+                        ui.colored_label(
+                            severity_color(finding.severity),
+                            format!("{}: {}", finding.code, finding.message()),
+                        );
+                    }
+                });
+
+                match exe.details.as_ref().and_then(|d| d.symbols.as_ref()) {
+                    None => {
+                        ui.label("No symbols extracted");
+                    }
+                    Some(symbols) => {
+                        let mut exported: Vec<&String> = symbols.exported.iter().collect();
+                        exported.sort();
+                        ui.collapsing(format!("Exports ({})", exported.len()), |ui| {
+                            for symbol in exported {
+                                ui.label(demangled(symbol));
+                            }
+                        });
+                        let mut imported: Vec<(&String, _)> = symbols.imported.iter().collect();
+                        imported.sort_by(|a, b| a.0.cmp(b.0));
+                        for (dll, names) in imported {
+                            let mut names: Vec<&String> = names.iter().collect();
+                            names.sort();
+                            ui.collapsing(format!("Imports from {dll} ({})", names.len()), |ui| {
+                                for symbol in names {
+                                    ui.label(demangled(symbol));
+                                }
+                            });
+                        }
+                    }
+                }
+            });
+        if let Some(dllname) = clicked {
+            self.select(&dllname);
+        }
+    }
+}
+
+impl eframe::App for DeprunGui {
+    fn update(&mut self, ctx: &egui::Context, _frame: &mut eframe::Frame) {
+        self.poll_scan(ctx);
+        egui::TopBottomPanel::top("target").show(ctx, |ui| self.top_bar(ui));
+        egui::SidePanel::left("tree")
+            .resizable(true)
+            .default_width(350.0)
+            .show(ctx, |ui| self.tree_panel(ui));
+        egui::TopBottomPanel::bottom("findings")
+            .resizable(true)
+            .default_height(180.0)
+            .show(ctx, |ui| self.findings_panel(ui));
+        egui::CentralPanel::default().show(ctx, |ui| self.details_panel(ui));
+    }
+}
+
+/// Recursive view of the dependency tree
+struct Tree<'a> {
+    executables: &'a Executables,
+    selected: Option<&'a str>,
+    reveal: Option<&'a [String]>,
+    hide_system: bool,
+    clicked: &'a mut Option<String>,
+}
+
+impl Tree<'_> {
+    fn show(&mut self, ui: &mut egui::Ui, chain: Vec<String>) {
+        let name = chain.last().cloned().unwrap_or_default();
+        let Some(exe) = self.executables.get(&name) else {
+            return;
+        };
+        if self.hide_system && chain.len() > 1 && is_system(exe) {
+            return;
+        }
+        // a DLL already in the chain would be expanded forever
+        let cyclic = chain[..chain.len() - 1]
+            .iter()
+            .any(|n| n.eq_ignore_ascii_case(&name));
+        let children: Vec<&str> = match &exe.details {
+            Some(d) if !cyclic => d.all_dependencies(),
+            _ => Vec::new(),
+        };
+        let selected = self.selected.is_some_and(|s| s.eq_ignore_ascii_case(&name));
+        let revealed = self.reveal.is_some_and(|r| same_chain(r, &chain));
+        let text = node_text(exe, &name);
+
+        if children.is_empty() {
+            let response = ui.selectable_label(selected, text);
+            if revealed {
+                response.scroll_to_me(Some(egui::Align::Center));
+            }
+            if response.clicked() {
+                *self.clicked = Some(name);
+            }
+            return;
+        }
+
+        let key: Vec<String> = chain.iter().map(|n| n.to_lowercase()).collect();
+        let mut state = CollapsingState::load_with_default_open(
+            ui.ctx(),
+            egui::Id::new(&key),
+            chain.len() == 1,
+        );
+        // open the ancestors of the executable to reveal
+        if self
+            .reveal
+            .is_some_and(|r| r.len() > chain.len() && same_chain(&r[..chain.len()], &chain))
+        {
+            state.set_open(true);
+        }
+        state
+            .show_header(ui, |ui| {
+                let response = ui.selectable_label(selected, text);
+                if revealed {
+                    response.scroll_to_me(Some(egui::Align::Center));
+                }
+                if response.clicked() {
+                    *self.clicked = Some(name.clone());
+                }
+            })
+            .body(|ui| {
+                for child in children {
+                    let mut child_chain = chain.clone();
+                    child_chain.push(child.to_owned());
+                    self.show(ui, child_chain);
+                }
+            });
+    }
+}
+
+fn same_chain(a: &[String], b: &[String]) -> bool {
+    a.len() == b.len() && a.iter().zip(b).all(|(a, b)| a.eq_ignore_ascii_case(b))
+}
+
+fn is_system(exe: &Executable) -> bool {
+    exe.details.as_ref().is_some_and(|d| d.is_system)
+}
+
+fn kind(exe: &Executable) -> &'static str {
+    match &exe.details {
+        None => "missing",
+        Some(d) if d.is_api_set => "API set",
+        Some(d) if d.is_known_dll => "known DLL",
+        Some(d) if d.is_system => "system",
+        Some(_) => "user",
+    }
+}
+
+/// Name of the executable in the tree: missing DLLs in red, system ones grayed out
+fn node_text(exe: &Executable, name: &str) -> RichText {
+    let text = RichText::new(name);
+    if !exe.found {
+        text.color(Color32::RED)
+    } else if is_system(exe) {
+        text.color(Color32::GRAY)
+    } else {
+        text
+    }
+}
+
+fn severity_color(severity: Severity) -> Color32 {
+    match severity {
+        Severity::Error => Color32::RED,
+        Severity::Warning => Color32::from_rgb(230, 160, 0),
+        Severity::Info => Color32::GRAY,
+    }
+}
+
+fn main() -> anyhow::Result<()> {
+    // the warnings of the library go to the standard error; RUST_LOG overrides the filter
+    env_logger::Builder::new()
+        .parse_filters("warn")
+        .parse_default_env()
+        .format_timestamp(None)
+        .init();
+    let cli = GuiCli::parse();
+    let options = eframe::NativeOptions {
+        viewport: egui::ViewportBuilder::default().with_inner_size([1100.0, 750.0]),
+        ..Default::default()
+    };
+    eframe::run_native(
+        "deprun-gui",
+        options,
+        Box::new(|_cc| Ok(Box::new(DeprunGui::new(cli)))),
+    )
+    .map_err(|e| anyhow::anyhow!("could not start the user interface: {e}"))
+}
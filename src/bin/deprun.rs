@@ -1,169 +1,653 @@
 extern crate dependency_runner;
 
-#[cfg(windows)]
 use dependency_runner::common::LookupError;
-use dependency_runner::path::LookupPath;
-#[cfg(windows)]
-use dependency_runner::vcx::{parse_vcxproj, parse_vcxproj_user};
+use dependency_runner::path::{LookupPath, PathProfile, Probe};
+use dependency_runner::pe::ParserBackend;
+use dependency_runner::vcx::{parse_vcxproj, parse_vcxproj_user, select_configuration};
 
 use anyhow::Context;
-use clap::Parser;
-#[cfg(not(windows))]
-use dependency_runner::common::path_to_string;
-use dependency_runner::common::{decanonicalize, readable_canonical_path};
-use dependency_runner::executable::{Executable, Executables};
-use dependency_runner::pe::demangle_symbol;
-use dependency_runner::query::LookupQuery;
-#[cfg(not(windows))]
+use clap::{Args, Parser, Subcommand};
+use dependency_runner::apiset::{bundled_apiset_map, load_apiset_map};
+use dependency_runner::appx::AppxManifest;
+use dependency_runner::archive::{self, split_archive_path, ArchiveFileProvider};
+use dependency_runner::audit::audit;
+use dependency_runner::banned::BannedImports;
+use dependency_runner::bundle::{
+    bundle, deployment_list, DeploymentListFormat, DeploymentPolicy, OverwritePolicy, UCRT_DLLS,
+};
+use dependency_runner::cmake::{request_file_api_reply, scan_build_dir};
+use dependency_runner::common::{decanonicalize, path_to_string, readable_path, NamePatterns};
+use dependency_runner::diff::ExecutablesDiff;
+use dependency_runner::directory::{
+    parse_target_list, scan_directory, scan_targets, DirectoryScanOptions,
+};
+use dependency_runner::environment::Environment;
+use dependency_runner::executable::{
+    Architecture, Executables, ExecutablesCheckReport, SymbolPattern,
+};
+use dependency_runner::inventory::{bundled_os_profile, OsProfile};
+use dependency_runner::lock::DependencyLock;
+use dependency_runner::minidump::{override_with_modules, read_modules};
+use dependency_runner::output::{render, ColorChoice, OutputFormat, OutputOptions};
+use dependency_runner::query::{DuplicatePolicy, LookupQuery, TraversalStrategy};
+use dependency_runner::registry::Registry;
+use dependency_runner::report::{rule, FailOn, FindingKind, ScanOutcome, EXIT_SCAN_ERROR, RULES};
+use dependency_runner::sbom::{to_sbom, SbomFormat};
+use dependency_runner::shortcut::Shortcut;
+#[cfg(all(feature = "skim", not(windows)))]
 use dependency_runner::skim::{skim_dlls, skim_symbols};
-#[cfg(not(windows))]
 use dependency_runner::system::WindowsSystem;
+use dependency_runner::verify::{executables_in_dir, verify_closure, ClosureViolationKind};
+use dependency_runner::watch::ScanWatcher;
 use fs_err as fs;
-use std::path::PathBuf;
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
 
-#[cfg(windows)]
-fn pick_configuration(
-    configs: &Vec<&String>,
-    user_config: &Option<String>,
+fn pick_configuration<T>(
+    per_config: &HashMap<String, T>,
+    scan: &ScanArgs,
     file_path: &str,
 ) -> Result<String, LookupError> {
-    if let Some(vcx_config) = user_config {
-        if configs.contains(&&vcx_config.to_string()) {
-            Ok(vcx_config.to_owned().to_string())
-        } else {
-            return Err(LookupError::ContextDeductionError(format!(
-                "The specified configuration {} was not found in project file {}\n\
-                Available configurations: {:?}",
-                vcx_config, file_path, configs
-            )));
-        }
-    } else {
-        if configs.len() == 1 {
-            let single_config = configs.last().unwrap();
-            eprintln!(
-                "Visual Studio configuration not specified, using {} for file {}",
-                single_config, file_path
-            );
-            Ok(single_config.to_owned().to_string())
-        } else {
-            return Err(LookupError::ContextDeductionError(format!(
-                "Must specify a configuration with --vcx-config=<CONFIG> for project file {}\n\
-                Available configurations: {:?}",
-                file_path, configs
-            )));
-        }
+    let configuration = select_configuration(
+        per_config,
+        scan.vcxproj_configuration.as_deref(),
+        scan.vcxproj_platform.as_deref(),
+    )
+    .map_err(|e| match e {
+        LookupError::ContextDeductionError(msg) => LookupError::ContextDeductionError(format!(
+            "{msg}\nSelect one with --vcxproj-configuration and --vcxproj-platform for project file {file_path}"
+        )),
+        e => e,
+    })?;
+    if scan.vcxproj_configuration.is_none() || scan.vcxproj_platform.is_none() {
+        eprintln!(
+            "Visual Studio configuration not fully specified, using {} for file {}",
+            configuration, file_path
+        );
     }
+    Ok(configuration.clone())
 }
 
-fn visit_depth_first(
-    e: &Executable,
-    current_depth: usize,
-    max_depth: Option<usize>,
-    exes: &Executables,
-    print_system_dlls: bool,
-) {
-    if (print_system_dlls || !e.details.as_ref().map(|d| d.is_system).unwrap_or(false))
-        && max_depth.map(|d| current_depth < d).unwrap_or(true)
-    {
-        let folder = if !e.found {
-            "not found".to_owned()
-        } else if let Some(details) = &e.details {
-            readable_canonical_path(details.full_path.parent().unwrap())
-                .unwrap_or_else(|_| "INVALID".to_owned())
-        } else {
-            "not searched".to_owned()
-        };
-        let extra_tag = if e.details.as_ref().map(|d| d.is_known_dll).unwrap_or(false) {
-            "[Known DLL]"
-        } else {
-            ""
-        };
-        println!(
-            "{}{} => {} {}",
-            "\t".repeat(current_depth),
-            e.dllname,
-            folder,
-            extra_tag
-        );
+/// Enumerate the Windows installations visible from the target and let the user choose one
+#[cfg(not(windows))]
+fn pick_windows_root(
+    binary_path: &Path,
+    mount_points: &[String],
+) -> anyhow::Result<Option<PathBuf>> {
+    let mut search_dirs: Vec<PathBuf> = if mount_points.is_empty() {
+        WindowsSystem::default_mount_points()
+    } else {
+        mount_points.iter().map(PathBuf::from).collect()
+    };
+    search_dirs.extend(binary_path.ancestors().skip(1).map(Path::to_owned));
 
-        if let Some(details) = &e.details {
-            if let Some(dependencies) = &details.dependencies {
-                for d in dependencies {
-                    if let Some(de) = exes.get(d) {
-                        visit_depth_first(
-                            de,
-                            current_depth + 1,
-                            max_depth,
-                            exes,
-                            print_system_dlls,
-                        );
-                    }
+    let candidates = WindowsSystem::enumerate_roots(&search_dirs);
+    match candidates.len() {
+        0 => {
+            eprintln!("No Windows installation found; system DLL imports will not be resolved");
+            Ok(None)
+        }
+        1 => Ok(Some(candidates[0].root.clone())),
+        _ => {
+            println!("Multiple Windows installations found:");
+            for (i, c) in candidates.iter().enumerate() {
+                println!(
+                    "\t[{}] {} ({})",
+                    i,
+                    path_to_string(&c.root),
+                    c.version
+                        .map(|v| format!("version {v}"))
+                        .unwrap_or_else(|| "unknown version".to_owned())
+                );
+            }
+            loop {
+                print!("Pick one [0-{}]: ", candidates.len() - 1);
+                use std::io::Write;
+                std::io::stdout().flush()?;
+                let mut answer = String::new();
+                if std::io::stdin().read_line(&mut answer)? == 0 {
+                    anyhow::bail!("No Windows installation picked");
+                }
+                if let Some(c) = answer
+                    .trim()
+                    .parse::<usize>()
+                    .ok()
+                    .and_then(|i| candidates.get(i))
+                {
+                    return Ok(Some(c.root.clone()));
                 }
             }
         }
     }
 }
 
-#[derive(Parser)]
-#[clap(author, version, about, long_about = None)]
-struct DeprunCli {
-    #[clap(value_parser)]
-    /// Target file (.exe, .dll or .vcxproj)
-    input: String,
-    #[clap(value_parser, short, long)]
-    /// Path for output in JSON format
-    output_json_path: Option<String>,
+/// Read a scan result from a JSON file written by deprun, or scan the given executable
+fn load_or_scan(path: &str) -> anyhow::Result<Executables> {
+    let path = PathBuf::from(path);
+    if path.extension().map(|e| e == "json").unwrap_or(false) {
+        return Ok(Executables::from_json_file(&path)?);
+    }
+    let path = fs::canonicalize(path)?;
+    let query = LookupQuery::deduce_from_executable_location(&path)?;
+    let lookup_path = LookupPath::deduce(&query);
+    Ok(dependency_runner::runner::run(&query, &lookup_path)?)
+}
+
+#[derive(Subcommand)]
+enum DeprunCommand {
+    /// Compare two scans, given as JSON outputs of deprun or as executables to scan
+    Diff {
+        #[clap(value_parser)]
+        /// Old scan (.json file) or executable
+        old: String,
+        #[clap(value_parser)]
+        /// New scan (.json file) or executable
+        new: String,
+        #[clap(long)]
+        /// Print the differences in JSON format
+        json: bool,
+    },
+    /// List the dependencies which could be hijacked by planting a DLL in the search path
+    Audit {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(long)]
+        /// Print the findings in JSON format
+        json: bool,
+    },
+    /// Copy the found non-system DLLs of the scan into a directory, for deployment
+    Bundle {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser, long)]
+        /// Destination directory (default: the directory of the target)
+        output_dir: Option<String>,
+        #[clap(long)]
+        /// Only print what would be copied
+        dry_run: bool,
+        #[clap(value_parser, long, default_value = "if-different")]
+        /// What to do with existing files: never, if-different or always
+        overwrite: OverwritePolicy,
+        #[clap(value_parser, long)]
+        /// Path for a JSON manifest of the copied files
+        manifest: Option<String>,
+        #[clap(flatten)]
+        deployment: DeploymentArgs,
+    },
+    /// Write a lock file with the location, version and SHA-256 digest of the non-system DLLs, to be checked later with --lock
+    Lock {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser, short, long, default_value = "deps.lock")]
+        /// Path of the lock file
+        output: String,
+    },
+    /// Print the files to install with the target (itself and its non-system DLLs), for an installer or a copy script
+    DeploymentList {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser, short, long)]
+        /// Format: wix (WiX fragment), nsis (NSIS File instructions), cmd or sh (copy script taking the destination directory as argument)
+        format: DeploymentListFormat,
+        #[clap(value_parser, short, long)]
+        /// Write the list to this file (default: standard output)
+        output: Option<String>,
+        #[clap(flatten)]
+        deployment: DeploymentArgs,
+    },
+    /// Check that a directory contains all the non-system dependencies of its .exe and .dll files
+    Verify {
+        #[clap(value_parser)]
+        /// Deployment directory
+        dir: String,
+        #[clap(value_parser, long)]
+        /// DLL name pattern allowed to be missing from the directory, e.g. "msvcp140*.dll" (can be repeated)
+        allow: Vec<String>,
+        #[clap(long)]
+        /// Print the violations in JSON format
+        json: bool,
+    },
+    /// Scan every .exe and .dll file of a directory, and print an aggregated report
+    Scan {
+        #[clap(value_parser)]
+        /// Directory to scan (or .zip or .7z archive, directory or executable inside one, e.g. app.zip!/bin), or @FILE listing the executables to scan (one per line), or - to read that list from the standard input
+        dir: String,
+        #[clap(long, conflicts_with_all = ["recursive", "depth", "include", "exclude"])]
+        /// DIR is a Meson build directory: scan the executables and DLLs of its targets
+        meson: bool,
+        #[clap(short, long)]
+        /// Also scan the files in the subdirectories
+        recursive: bool,
+        #[clap(value_parser, long)]
+        /// Maximum depth of the subdirectories to scan (default: unlimited)
+        depth: Option<usize>,
+        #[clap(value_parser, long)]
+        /// Only scan files matching this pattern, e.g. "*.exe" (can be repeated)
+        include: Vec<String>,
+        #[clap(value_parser, long)]
+        /// Do not scan files matching this pattern (can be repeated)
+        exclude: Vec<String>,
+        #[clap(short, long)]
+        /// Also check that all imported symbols are found
+        check_symbols: bool,
+        #[cfg(not(windows))]
+        #[clap(value_parser, long)]
+        /// Windows partition to use for system DLLs lookup
+        windows_root: Option<String>,
+        #[clap(long)]
+        /// Print the results in JSON format
+        json: bool,
+    },
+    /// Scan and check the executables and DLLs built by a CMake build directory
+    Cmake {
+        #[clap(value_parser)]
+        /// CMake build directory
+        build_dir: String,
+        #[clap(value_parser, long)]
+        /// Only scan the targets of this configuration (Debug, Release, ...)
+        config: Option<String>,
+        #[clap(long)]
+        /// Make CMake describe the targets of the build directory at its next run (via the file API), then exit
+        request_file_api: bool,
+        #[clap(short, long)]
+        /// Also check that all imported symbols are found
+        check_symbols: bool,
+        #[cfg(not(windows))]
+        #[clap(value_parser, long)]
+        /// Windows partition to use for system DLLs lookup
+        windows_root: Option<String>,
+        #[clap(long)]
+        /// Print the results in JSON format
+        json: bool,
+    },
+    /// Print the shortest chains of dependencies through which the target pulls in a DLL
+    // the DLL is the only positional argument if the target is given by --service & co.
+    #[command(allow_missing_positional = true)]
+    Why {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser)]
+        /// Name of the DLL
+        dll: String,
+    },
+    /// Print every entry of the lookup path probed for a DLL, what was found there, and which one
+    /// the DLL is loaded from
+    // the DLL is the only positional argument if the target is given by --service & co.
+    #[command(allow_missing_positional = true)]
+    Explain {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser)]
+        /// Name of the DLL
+        dll: String,
+    },
+    /// Print where a DLL would be loaded from for the target, and the files it shadows, without
+    /// scanning the target
+    // the DLL is the only positional argument if the target is given by --service & co.
+    #[command(allow_missing_positional = true)]
+    Probe {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser)]
+        /// Name of the DLL
+        dll: String,
+        #[clap(long)]
+        /// Print the report as JSON
+        json: bool,
+    },
+    /// Print the scanned modules exporting or importing the symbols matching a pattern
+    // the pattern is the only positional argument if the target is given by --service & co.
+    #[command(allow_missing_positional = true)]
+    FindSymbol {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser)]
+        /// Pattern matched against the raw and the demangled names, e.g. "*QString*"
+        pattern: String,
+        #[clap(long)]
+        /// The pattern is a regular expression instead of a glob
+        regex: bool,
+        #[clap(long)]
+        /// Print the matches as JSON
+        json: bool,
+    },
+    /// Print how the scan would change if some entries of the lookup path were removed
+    WhatIf {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser, long, required = true)]
+        /// Entry of the lookup path to disable: its position (starting from 0), its kind (e.g. WorkingDir, SystemPath) or its directory (can be repeated)
+        disable: Vec<String>,
+        #[clap(long)]
+        /// Print the differences in JSON format
+        json: bool,
+    },
+    /// Scan the executable of a running process, and compare the DLLs it loaded with the ones
+    /// predicted by the scan
+    #[cfg(windows)]
+    Live {
+        #[clap(value_parser)]
+        /// Identifier of the process
+        pid: u32,
+        #[clap(long)]
+        /// Print the differences in JSON format
+        json: bool,
+    },
+    /// Print the dependency tree of the modules loaded by a crashed process, from its minidump, or compare them with a static scan
+    // the target, given after the dump, is the executable of the process on this machine (by
+    // default the path recorded in the dump)
+    #[command(mut_arg("input", |a| a.required_unless_present("dump")))]
+    Minidump {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser)]
+        /// Minidump (.dmp) file
+        dump: String,
+        #[clap(long)]
+        /// Compare the modules with a static scan of the executable instead
+        diff: bool,
+        #[clap(long)]
+        /// Print the modules, or the differences with --diff, in JSON format
+        json: bool,
+    },
+    /// Scan the target, then rescan it and print the differences whenever a DLL on its lookup path
+    /// appears, disappears or changes
+    Watch {
+        #[clap(flatten)]
+        scan: ScanArgs,
+        #[clap(value_parser, long, default_value = "auto")]
+        /// Color the tree: auto (if printing to a terminal and NO_COLOR is not set), always or never
+        color: ColorChoice,
+    },
+    /// Browse the dependency tree and the symbols of the DLLs interactively in the terminal
+    Tui {
+        #[clap(flatten)]
+        scan: ScanArgs,
+    },
+    /// Print the documentation of the codes of the findings (e.g. DR0001)
+    Rules {
+        #[clap(value_parser)]
+        /// Only print the rule with this code
+        code: Option<String>,
+        #[clap(long)]
+        /// Print the rules in JSON format
+        json: bool,
+    },
+}
+
+/// Target and DLL lookup options, shared by the commands that perform a scan
+#[derive(Args)]
+struct ScanArgs {
+    #[clap(value_parser, required_unless_present_any = ["service", "scheduled_task", "com_clsid"])]
+    /// Target file (.exe, .dll, .vcxproj or .lnk shortcut), or executable inside a .zip or .7z archive (e.g. app.zip!/bin/app.exe)
+    input: Option<String>,
+    #[clap(value_parser, long, value_name = "NAME", conflicts_with = "input")]
+    /// Scan the executable started by this Windows service (read from the registry of the Windows installation)
+    service: Option<String>,
+    #[clap(value_parser, long, value_name = "NAME_OR_XML", conflicts_with_all = ["input", "service"])]
+    /// Scan the executable started by this scheduled task (name, or path of its XML definition)
+    scheduled_task: Option<String>,
+    #[clap(value_parser, long, value_name = "CLSID", conflicts_with_all = ["input", "service", "scheduled_task"])]
+    /// Scan the server (DLL or executable) of this COM class
+    com_clsid: Option<String>,
+    #[clap(value_parser, long, value_name = "EXE")]
+    /// Scan this executable (e.g. a plugin) along with the target, printing one tree for each (can be repeated)
+    also_scan: Vec<String>,
     #[clap(value_parser, short, long)]
     /// Maximum recursion depth (default: unlimited)
     max_depth: Option<usize>,
+    #[clap(long)]
+    /// Visit the dependency tree depth-first, following the import tables in order (default: breadth-first, in parallel)
+    depth_first: bool,
+    #[clap(value_parser, long)]
+    /// Do not scan the dependencies of DLLs matching this pattern, e.g. "nv*.dll" (can be repeated)
+    prune: Vec<String>,
+    #[clap(value_parser, long)]
+    /// Do not look up DLLs matching this pattern, e.g. "nv*.dll" (can be repeated)
+    skip: Vec<String>,
+    #[clap(value_parser, long)]
+    /// Only scan the dependencies of DLLs matching this pattern (can be repeated)
+    only_descend_into: Vec<String>,
+    #[clap(value_parser, long, value_name = "DLL=PATH|present|missing")]
+    /// Load a DLL from the given file, or consider it found or missing, regardless of the lookup path (can be repeated)
+    dll_override: Vec<String>,
     #[clap(short, long)]
     /// Activate verbose output
     verbose: bool,
-    #[clap(short = 'e', long)]
-    /// Only show executables with missing dependencies or missing symbols
-    errors_only: bool,
-    #[clap(short, long)]
-    /// Include system DLLs in the output
-    print_system_dlls: bool,
-    #[clap(short, long)]
-    /// Check that all imported symbols are found within the (non-system) dependencies
-    check_symbols: bool,
-    #[cfg(not(windows))]
-    #[clap(short, long)]
-    /// Start a fuzzy search on the found DLLs, then on the symbols of the selected DLL
-    skim: bool,
-    #[cfg(not(windows))]
     #[clap(long)]
-    /// Start a fuzzy search on the symbols of all found DLLs
-    skim_symbols: bool,
+    /// Compute the SHA-256 and MD5 hashes of the found files (included in the JSON output)
+    hashes: bool,
+    #[clap(long)]
+    /// Also follow the DLL names found among the strings of the scanned files, as possible dynamic loads (heuristic)
+    dynamic_loads: bool,
+    #[clap(long)]
+    /// Parse the files one at a time, and keep only the exported symbols imported by the scanned files (for very large trees)
+    low_memory: bool,
+    #[clap(value_parser, long, default_value = "prefer-goblin")]
+    /// PE parsing library: prefer-goblin, prefer-pelite, or compare (read with both and report their disagreements as diagnostics)
+    parser_backend: ParserBackend,
+    #[clap(value_parser, long, default_value = "first-wins")]
+    /// When two different files have the same DLL name: first-wins (report the other one as a diagnostic), error, or keep-all
+    duplicate_policy: DuplicatePolicy,
+    #[clap(value_parser, long, conflicts_with = "also_scan")]
+    /// Scan the target DLL as loaded by this executable: its directory is looked up instead of the one of the DLL, and is the working directory
+    host_exe: Option<String>,
+    #[clap(long, requires = "host_exe")]
+    /// The host loads the DLL with LOAD_WITH_ALTERED_SEARCH_PATH: the directory of the DLL is looked up instead of the one of the host
+    altered_search_path: bool,
     #[clap(value_parser, short, long)]
     /// Working directory to be considered in the DLL lookup path (default: same as the shell deprun runs in)
     working_directory: Option<String>,
     #[clap(value_parser, short, long)]
     /// User path to be considered in the DLL lookup path (default: same as the shell deprun runs in)
     user_path: Option<String>,
-    #[cfg(windows)]
+    #[clap(value_parser, long)]
+    /// Take the PATH of the simulated process from this .env file (NAME=VALUE lines), instead of the current shell
+    env_file: Option<String>,
     #[clap(value_parser, long)]
     /// Read the complete DLL lookup path from a .dwp file (Dependency Walker's format)
     dwp_path: Option<String>,
-    #[cfg(windows)]
-    #[clap(value_parser, long, conflicts_with = "dwp-path")]
+    #[clap(value_parser, long, conflicts_with = "dwp_path")]
+    /// Read the complete DLL lookup path from a JSON path profile (see --save-path-profile)
+    path_profile: Option<String>,
+    #[clap(value_parser, long)]
+    /// Save the DLL lookup path to a JSON path profile, to be edited and reused with --path-profile
+    save_path_profile: Option<String>,
+    #[clap(value_parser, long, conflicts_with = "dwp_path")]
     /// Path to a .vcxproj.user file to parse for PATH entries to be added to the search path
     vcxproj_user_path: Option<String>,
-    #[cfg(windows)]
-    #[clap(value_parser, long, conflicts_with = "dwp-path")]
+    #[clap(value_parser, long, conflicts_with = "dwp_path")]
     /// Configuration to use (Debug, Release, ...) if the target is a .vcxproj file, or if a .vcxproj.user was provided
     vcxproj_configuration: Option<String>,
+    #[clap(value_parser, long, conflicts_with = "dwp_path")]
+    /// Platform to use (x64, Win32, ARM64, ...) if the project defines several; Win32 executables use SysWOW64 as system directory
+    vcxproj_platform: Option<String>,
+    #[clap(long)]
+    /// Add to the user path the directories deduced from the linker settings and post-build copy steps of the .vcxproj
+    vcxproj_linker_path: bool,
+    #[clap(value_parser, long)]
+    /// AppxManifest.xml of the package of a packaged (MSIX/AppX) app (default: the one found in the directory of the executable or its ancestors)
+    appx_manifest: Option<String>,
     #[cfg(not(windows))]
     #[clap(value_parser, long)]
     /// Windows partition to use for system DLLs lookup (if not specified, the partition where INPUT lies will be tested and used if valid)
     windows_root: Option<String>,
+    #[clap(value_parser, long)]
+    /// API set map to use if none can be read from the Windows partition: path to a .json/.csv file, or name of a bundled map (win10)
+    apiset_map: Option<String>,
+    #[clap(value_parser, long)]
+    /// Windows version the target runs on, whose DLLs are assumed present if not found: path to a .json profile, or name of a bundled one (win7, win81, win10-22h2, win11-23h2)
+    target_os: Option<String>,
+    #[clap(value_parser, long)]
+    /// Architecture the target runs as: x86, x64, arm or arm64 (default: read from the executable); selects the system directory, KnownDLLs and API sets
+    arch: Option<Architecture>,
+    #[cfg(not(windows))]
+    #[clap(long, conflicts_with = "windows_root")]
+    /// List the Windows partitions found on the mount points and pick one interactively
+    pick_windows_root: bool,
+    #[cfg(not(windows))]
+    #[clap(value_parser, long)]
+    /// Directory where Windows partitions are mounted, for --pick-windows-root (can be repeated; default: /mnt, /media, ...)
+    mount_point: Vec<String>,
 }
 
-fn main() -> anyhow::Result<()> {
-    let args = DeprunCli::parse();
+/// Choice of the files to ship, shared by bundle and deployment-list
+#[derive(Args)]
+struct DeploymentArgs {
+    #[clap(value_parser, long)]
+    /// System DLL name pattern to ship anyway, e.g. "vcruntime140*.dll" (can be repeated)
+    include: Vec<String>,
+    #[clap(value_parser, long)]
+    /// DLL name pattern not to ship (can be repeated)
+    exclude: Vec<String>,
+    #[clap(long)]
+    /// Ship the Universal CRT too (ucrtbase.dll and the api-ms-win-crt-*.dll forwarders, if found as files)
+    ucrt: bool,
+}
+
+impl DeploymentArgs {
+    fn policy(&self) -> anyhow::Result<DeploymentPolicy> {
+        let mut include = self.include.clone();
+        if self.ucrt {
+            include.extend(UCRT_DLLS.iter().map(|s| s.to_string()));
+        }
+        Ok(DeploymentPolicy {
+            include_roots: false,
+            include: NamePatterns::new(&include)?,
+            exclude: NamePatterns::new(&self.exclude)?,
+        })
+    }
+}
+
+#[derive(Parser)]
+#[clap(
+    author,
+    version,
+    about,
+    long_about = None,
+    args_conflicts_with_subcommands = true,
+    subcommand_negates_reqs = true
+)]
+struct DeprunCli {
+    #[clap(subcommand)]
+    command: Option<DeprunCommand>,
+    #[clap(flatten)]
+    scan: ScanArgs,
+    #[clap(value_parser, short, long, value_delimiter = ',')]
+    /// Output format: tree (default), ldd, json, ndjson, csv, dot, html, sarif, junit, dependencies or dw-modules (can be repeated)
+    format: Vec<OutputFormat>,
+    #[clap(value_parser, long, default_value = "auto")]
+    /// Color the tree: auto (if printing to a terminal and NO_COLOR is not set), always or never
+    color: ColorChoice,
+    #[clap(value_parser, short, long)]
+    /// Destination of the output in the --format at the same position ("-" or omitted: standard output)
+    output: Vec<String>,
+    #[clap(value_parser, long)]
+    /// Path for a software bill of materials of the found DLLs, in the --sbom-format
+    output_sbom: Option<String>,
+    #[clap(value_parser, long, default_value = "cyclonedx")]
+    /// Format of the software bill of materials: cyclonedx (CycloneDX 1.5 JSON) or spdx (SPDX 2.3 JSON)
+    sbom_format: SbomFormat,
+    #[clap(value_parser, long)]
+    /// JSON output of a previous scan of the same target: only the files that changed since then are parsed again
+    previous_json_path: Option<String>,
+    #[clap(short = 'e', long)]
+    /// Only show executables with missing dependencies or missing symbols
+    errors_only: bool,
+    #[clap(long)]
+    /// Print the time spent in each phase of the scan (directory listing, PE parsing, symbol extraction) to stderr
+    timings: bool,
+    #[clap(short, long)]
+    /// Include system DLLs in the output
+    print_system_dlls: bool,
+    #[clap(value_parser, long, default_value = "none")]
+    /// Exit with a non-zero code on: none, missing-dlls, missing-symbols or warnings
+    fail_on: FailOn,
+    #[clap(value_parser, long)]
+    /// JSON file of known findings, which are not reported nor make the run fail
+    baseline: Option<String>,
+    #[clap(long, requires = "baseline")]
+    /// Write the current findings to the --baseline file
+    update_baseline: bool,
+    #[clap(short, long)]
+    /// Check that all imported symbols are found within the (non-system) dependencies
+    check_symbols: bool,
+    #[clap(long)]
+    /// Also check that the non-system executables are built with ASLR and DEP (reported as DR0008)
+    check_security: bool,
+    #[clap(value_parser, long)]
+    /// List of forbidden imports, one [DLL!]SYMBOL pattern per line, or "sdl" for the functions banned by the SDL (can be repeated; reported as DR0010)
+    banned_imports: Vec<String>,
+    #[clap(value_parser, long)]
+    /// Directory where the non-system DLLs are allowed to be loaded from, e.g. the installation directory (can be repeated; the others are reported as DR0011)
+    allowed_dir: Vec<String>,
+    #[clap(value_parser, long)]
+    /// Lock file written by deprun lock: the DLLs differing from it are reported as DR0013
+    lock: Option<String>,
+    #[cfg(all(feature = "skim", not(windows)))]
+    #[clap(short, long)]
+    /// Start a fuzzy search on the found DLLs, then on the symbols of the selected DLL
+    skim: bool,
+    #[cfg(all(feature = "skim", not(windows)))]
+    #[clap(long)]
+    /// Start a fuzzy search on the symbols of all found DLLs
+    skim_symbols: bool,
+}
+
+/// Build the query for the executable registered as the service, scheduled task or COM class given
+/// on the command line, if any
+fn build_registration_query(scan: &ScanArgs) -> anyhow::Result<Option<LookupQuery>> {
+    if scan.service.is_none() && scan.scheduled_task.is_none() && scan.com_clsid.is_none() {
+        return Ok(None);
+    }
 
-    let binary_path = PathBuf::from(args.input);
+    #[cfg(windows)]
+    let (system, registry) = (WindowsSystem::current()?, || anyhow::Ok(Registry::Live));
+    #[cfg(not(windows))]
+    let root = scan.windows_root.as_deref().ok_or_else(|| {
+        anyhow::anyhow!("--service, --scheduled-task and --com-clsid require --windows-root")
+    })?;
+    #[cfg(not(windows))]
+    let (system, registry) = (
+        WindowsSystem::from_root(root)
+            .ok_or_else(|| anyhow::anyhow!("No Windows installation found at {root}"))?,
+        || anyhow::Ok(Registry::from_root(root)?),
+    );
+
+    let query = if let Some(service) = &scan.service {
+        LookupQuery::from_service(service, &registry()?, system)?
+    } else if let Some(task) = &scan.scheduled_task {
+        LookupQuery::from_scheduled_task(task, system)?
+    } else if let Some(clsid) = &scan.com_clsid {
+        LookupQuery::from_com_class(clsid, &registry()?, system)?
+    } else {
+        unreachable!()
+    };
+    if scan.verbose {
+        println!(
+            "Registered executable: {}",
+            decanonicalize(query.target.target_exe.to_str().unwrap_or("---"))
+        );
+    }
+    Ok(Some(query))
+}
+
+/// Build the query for the target given on the command line, applying the user overrides
+fn build_query(scan: &ScanArgs) -> anyhow::Result<LookupQuery> {
+    if let Some(mut query) = build_registration_query(scan)? {
+        apply_query_overrides(scan, &mut query)?;
+        return Ok(query);
+    }
+
+    // required by clap
+    let input = scan.input.clone().expect("missing input");
+    // an executable inside an archive is scanned from the archive, without unpacking it
+    let archive_member = split_archive_path(&input);
+    let binary_path = match &archive_member {
+        Some((archive_path, _)) => archive_path.clone(),
+        None => PathBuf::from(input),
+    };
 
     if !binary_path.exists() {
         eprintln!(
@@ -171,7 +655,7 @@ fn main() -> anyhow::Result<()> {
             binary_path.to_str().unwrap(),
             std::env::current_dir()?.to_str().unwrap(),
         );
-        std::process::exit(1);
+        std::process::exit(EXIT_SCAN_ERROR);
     }
 
     if binary_path.is_dir() {
@@ -179,16 +663,29 @@ fn main() -> anyhow::Result<()> {
             "The specified path is a directory, not a PE executable file: {}",
             binary_path.to_str().unwrap(),
         );
-        std::process::exit(1);
+        std::process::exit(EXIT_SCAN_ERROR);
     }
 
     let binary_path = fs::canonicalize(binary_path)?;
 
-    #[cfg(not(windows))]
-    let mut query = LookupQuery::deduce_from_executable_location(&binary_path)?;
-
-    #[cfg(windows)]
-    let mut query = if binary_path
+    let mut query = if let Some((_, member)) = &archive_member {
+        archive::lookup_query(&binary_path, member)?
+    } else if binary_path
+        .extension()
+        .map(|e| e.eq_ignore_ascii_case("lnk"))
+        .unwrap_or(false)
+    {
+        let query = LookupQuery::from_shortcut(&binary_path)?;
+        if scan.verbose {
+            let shortcut = Shortcut::read(&binary_path)?;
+            println!(
+                "Shortcut target: {} {}",
+                decanonicalize(query.target.target_exe.to_str().unwrap_or("---")),
+                shortcut.arguments.unwrap_or_default()
+            );
+        }
+        query
+    } else if binary_path
         .extension()
         .map(|e| e == "vcxproj")
         .unwrap_or(false)
@@ -196,8 +693,8 @@ fn main() -> anyhow::Result<()> {
         let vcxproj_path = &binary_path;
         let vcx_exe_info_per_config = parse_vcxproj(&vcxproj_path)?;
         let vcx_config_to_use = pick_configuration(
-            &vcx_exe_info_per_config.keys().collect::<Vec<_>>(),
-            &args.vcxproj_configuration,
+            &vcx_exe_info_per_config,
+            scan,
             vcxproj_path
                 .to_str()
                 .ok_or(LookupError::ContextDeductionError(format!(
@@ -207,26 +704,40 @@ fn main() -> anyhow::Result<()> {
         )?;
         let vcx_exe_info = &vcx_exe_info_per_config[&vcx_config_to_use];
 
-        LookupQuery::read_from_vcx_executable_information(vcx_exe_info)?
+        let mut query = LookupQuery::read_from_vcx_executable_information(vcx_exe_info)?;
+        if scan.vcxproj_linker_path {
+            query
+                .target
+                .user_path
+                .extend(vcx_exe_info.suggested_path.iter().cloned());
+        } else if scan.verbose && !vcx_exe_info.suggested_path.is_empty() {
+            println!(
+                "Directories likely containing DLLs, according to the linker settings (use --vcxproj-linker-path): {}",
+                vcx_exe_info
+                    .suggested_path
+                    .iter()
+                    .map(|p| decanonicalize(&p.to_string_lossy()))
+                    .collect::<Vec<_>>()
+                    .join(", ")
+            );
+        }
+        query
     } else {
         let mut query = LookupQuery::deduce_from_executable_location(&binary_path)?;
 
-        if let Some(vcxproj_user_path_str) = args.vcxproj_user_path {
-            let vcxproj_user_path = std::path::Path::new(&vcxproj_user_path_str);
+        if let Some(vcxproj_user_path_str) = &scan.vcxproj_user_path {
+            let vcxproj_user_path = std::path::Path::new(vcxproj_user_path_str);
             if !vcxproj_user_path.exists() || vcxproj_user_path.is_dir() {
                 eprintln!(
                     "Specified vcxproj.user file not found at {}",
                     vcxproj_user_path_str,
                 );
-                std::process::exit(1);
+                std::process::exit(EXIT_SCAN_ERROR);
             }
 
             let vcx_debug_info_per_config = parse_vcxproj_user(&vcxproj_user_path)?;
-            let config_to_use = pick_configuration(
-                &vcx_debug_info_per_config.keys().collect::<Vec<_>>(),
-                &args.vcxproj_configuration,
-                &vcxproj_user_path_str,
-            )?;
+            let config_to_use =
+                pick_configuration(&vcx_debug_info_per_config, scan, vcxproj_user_path_str)?;
             let vcx_debug_info = &vcx_debug_info_per_config[&config_to_use];
 
             query.update_from_vcx_debugging_configuration(vcx_debug_info);
@@ -234,26 +745,15 @@ fn main() -> anyhow::Result<()> {
         query
     };
 
-    if let Some(max_depth) = args.max_depth {
-        query.parameters.max_depth = Some(max_depth);
-    }
-
-    #[cfg(not(windows))]
-    {
-        query.parameters.extract_symbols = args.check_symbols || args.skim_symbols || args.skim;
-    }
-
-    #[cfg(windows)]
-    {
-        query.parameters.extract_symbols = args.check_symbols;
-    }
-
     // overrides (must be last)
 
     #[cfg(not(windows))]
-    if let Some(overridden_winroot) = args.windows_root {
+    if let Some(overridden_winroot) = &scan.windows_root {
         query.system = WindowsSystem::from_root(overridden_winroot);
-    } else if args.verbose {
+    } else if scan.pick_windows_root {
+        query.system =
+            pick_windows_root(&binary_path, &scan.mount_point)?.and_then(WindowsSystem::from_root);
+    } else if scan.verbose {
         if let Some(system) = &query.system {
             println!(
                 "Windows partition root not specified, assumed {}",
@@ -264,15 +764,125 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
-    if let Some(overridden_workdir) = args.working_directory {
+    apply_query_overrides(scan, &mut query)?;
+    Ok(query)
+}
+
+/// Apply the lookup parameters and the user overrides given on the command line to the query
+fn apply_query_overrides(scan: &ScanArgs, query: &mut LookupQuery) -> anyhow::Result<()> {
+    if let Some(max_depth) = scan.max_depth {
+        query.parameters.max_depth = Some(max_depth);
+    }
+    if scan.depth_first {
+        query.parameters.traversal = TraversalStrategy::DepthFirst;
+    }
+    query.parameters.prune_below = scan.prune.clone();
+    query.parameters.skip_dlls = scan.skip.clone();
+    query.parameters.only_descend_into = scan.only_descend_into.clone();
+    query.parameters.compute_hashes = scan.hashes;
+    query.parameters.detect_dynamic_loads = scan.dynamic_loads;
+    query.parameters.low_memory = scan.low_memory;
+    query.parameters.parser_backend = scan.parser_backend;
+    query.parameters.duplicate_policy = scan.duplicate_policy;
+    if let Some(host_exe) = &scan.host_exe {
+        let target_exe = &query.target.target_exe;
+        if target_exe
+            .extension()
+            .is_some_and(|e| e.eq_ignore_ascii_case("exe"))
+        {
+            anyhow::bail!(
+                "--host-exe requires a DLL target, {} is an executable",
+                readable_path(target_exe)
+            );
+        }
+        *query = query
+            .clone()
+            .with_host(host_exe, scan.altered_search_path)
+            .with_context(|| format!("couldn't read host executable {host_exe}"))?;
+        if scan.verbose {
+            println!("Scanning the DLL as loaded by {host_exe}");
+        }
+    }
+    for dll_override in &scan.dll_override {
+        let (dllname, resolution) = dll_override.split_once('=').ok_or_else(|| {
+            anyhow::anyhow!("invalid DLL override {dll_override}, expected DLL=VALUE")
+        })?;
+        query.override_dll(dllname, resolution.parse()?);
+    }
+    // applied again, in case the Windows partition was overridden
+    if let Some(architecture) = scan.arch.or(query.target.architecture) {
+        query.set_architecture(architecture);
+        if scan.verbose {
+            if let Some(system) = &query.system {
+                println!(
+                    "Target architecture {architecture}, system directory {}",
+                    path_to_string(&system.sys_dir)
+                );
+            }
+        }
+    }
+    for also_scan in &scan.also_scan {
+        let path = PathBuf::from(also_scan);
+        if !path.is_file() {
+            anyhow::bail!("specified executable {also_scan} does not exist");
+        }
+        query.add_target(fs::canonicalize(path)?);
+    }
+
+    if let Some(apiset_map) = &scan.apiset_map {
+        query.fallback_apiset_map = Some(match bundled_apiset_map(apiset_map) {
+            Some(m) => m,
+            None => load_apiset_map(apiset_map)
+                .with_context(|| format!("couldn't load API set map {apiset_map}"))?,
+        });
+    }
+
+    if let Some(target_os) = &scan.target_os {
+        // a path to a .json profile, otherwise the name of a bundled one
+        query.target_os = Some(if Path::new(target_os).is_file() {
+            OsProfile::load(target_os)
+                .with_context(|| format!("couldn't load OS profile {target_os}"))?
+        } else {
+            bundled_os_profile(target_os)?
+        });
+    }
+
+    let appx_manifest_path = match &scan.appx_manifest {
+        Some(p) => Some(PathBuf::from(p)),
+        None => AppxManifest::find_for_executable(&query.target.target_exe),
+    };
+    if let Some(appx_manifest_path) = appx_manifest_path {
+        let manifest = AppxManifest::read(&appx_manifest_path).context(format!(
+            "couldn't read package manifest {}",
+            appx_manifest_path.display()
+        ))?;
+        let missing_packages = query.update_from_appx_manifest(&manifest);
+        if scan.verbose {
+            println!(
+                "Packaged app {}, looking up DLLs in its package graph",
+                manifest.name
+            );
+            for package in missing_packages {
+                println!("Package dependency {package} not found among the installed packages");
+            }
+        }
+    }
+
+    if let Some(env_file) = &scan.env_file {
+        let environment = Environment::from_env_file(env_file)
+            .context(format!("couldn't read environment file {env_file}"))?;
+        *query = query.clone().with_environment(environment);
+    }
+
+    if let Some(overridden_workdir) = &scan.working_directory {
         query.target.working_dir = PathBuf::from(overridden_workdir);
-    } else if args.verbose {
+    } else if scan.verbose {
         println!(
             "Working directory not specified, assuming directory of executable: {}",
             decanonicalize(query.target.working_dir.to_str().unwrap_or("---"))
         );
     }
-    if let Some(overridden_path) = args.user_path {
+    if let Some(overridden_path) = &scan.user_path {
         let canonicalized_path: Vec<PathBuf> = overridden_path
             .split(';')
             .filter_map(|s| {
@@ -286,7 +896,7 @@ fn main() -> anyhow::Result<()> {
             })
             .collect::<Result<Vec<_>, std::io::Error>>()?;
         query.target.user_path.extend(canonicalized_path);
-    } else if args.verbose {
+    } else if scan.verbose {
         #[cfg(windows)]
         {
             let decanonicalized_path: Vec<String> = query
@@ -307,20 +917,84 @@ fn main() -> anyhow::Result<()> {
         );
     };
 
-    #[cfg(not(windows))]
-    let lookup_path = LookupPath::deduce(&query);
+    Ok(())
+}
 
-    #[cfg(windows)]
-    let lookup_path = if let Some(dwp_file_path) = args.dwp_path {
-        dependency_runner::path::LookupPath::from_dwp_file(dwp_file_path, &query)?
+/// Run the checks on the result of a scan, including the ones against the target OS of the query
+fn check_scan(
+    executables: &Executables,
+    query: &LookupQuery,
+) -> Result<ExecutablesCheckReport, LookupError> {
+    let mut report = executables.check(query.parameters.extract_symbols)?;
+    if let Some(target_os) = &query.target_os {
+        report.extend(executables.check_target_os(target_os));
+        report.sort();
+    }
+    Ok(report)
+}
+
+/// Print the findings of a report with the given rule code, under the title of their check
+fn print_check(report: &ExecutablesCheckReport, title: &str, code: &str) {
+    println!("\n{title}...\n");
+    let mut no_findings = true;
+    for finding in report.with_code(code) {
+        println!("{finding}");
+        no_findings = false;
+    }
+    if no_findings {
+        println!("No problems detected");
+    }
+}
+
+/// Indices of the entries of the lookup path designated on the command line by their position,
+/// their kind or their directory
+fn lookup_path_entries_matching(lookup_path: &LookupPath, designation: &str) -> Vec<usize> {
+    if let Ok(index) = designation.parse::<usize>() {
+        return vec![index]
+            .into_iter()
+            .filter(|&i| i < lookup_path.entries.len())
+            .collect();
+    }
+    let dir = fs::canonicalize(designation).ok();
+    lookup_path
+        .entries
+        .iter()
+        .enumerate()
+        .filter(|(_, e)| {
+            e.kind_name().eq_ignore_ascii_case(designation)
+                || (dir.is_some() && e.get_path() == dir)
+        })
+        .map(|(i, _)| i)
+        .collect()
+}
+
+/// Build the DLL lookup path for the query, applying the user overrides
+fn build_lookup_path<'a>(
+    scan: &ScanArgs,
+    query: &'a LookupQuery,
+) -> anyhow::Result<LookupPath<'a>> {
+    let mut lookup_path = if let Some(dwp_file_path) = &scan.dwp_path {
+        LookupPath::from_dwp_file(dwp_file_path, query)?
+    } else if let Some(profile_path) = &scan.path_profile {
+        LookupPath::from_path_profile(profile_path, query)
+            .context(format!("couldn't load path profile {profile_path}"))?
     } else {
-        dependency_runner::path::LookupPath::deduce(&query)
+        LookupPath::deduce(query)
     };
+    if let Some((archive_path, _)) = scan.input.as_deref().and_then(split_archive_path) {
+        lookup_path.set_file_provider(Arc::new(
+            ArchiveFileProvider::open(&archive_path)
+                .context(format!("couldn't open archive {}", archive_path.display()))?,
+        ));
+    }
+    if let Some(profile_path) = &scan.save_path_profile {
+        PathProfile::from_lookup_path(&lookup_path).write(profile_path)?;
+    }
 
-    if args.verbose {
+    if scan.verbose {
         println!(
             "Looking for dependencies of binary {}",
-            readable_canonical_path(&binary_path)?
+            readable_path(&query.target.target_exe)
         );
         if let Some(kd) = query.system.as_ref().and_then(|s| s.known_dlls.as_ref()) {
             println!("Known DLLs: {:?}", kd.entries.keys());
@@ -332,8 +1006,9 @@ fn main() -> anyhow::Result<()> {
             .unwrap_or(false)
         {
             println!("API set map available");
+        } else if query.fallback_apiset_map.is_some() {
+            println!("Using the provided API set map");
         }
-        let lookup_path = LookupPath::deduce(&query);
         let decanonicalized_path: Vec<String> = lookup_path
             .search_path()
             .iter()
@@ -342,143 +1017,807 @@ fn main() -> anyhow::Result<()> {
         println!("Search path: {}\n", decanonicalized_path.join(", "));
     }
 
-    let mut executables = dependency_runner::runner::run(&query, &lookup_path)?;
+    Ok(lookup_path)
+}
 
-    if args.errors_only {
-        executables = executables.filter_only_notfound()?;
-        if executables.is_empty() {
-            println!("No missing DLLs identified");
+fn main() {
+    std::process::exit(match run_deprun() {
+        Ok(exit_code) => exit_code,
+        Err(e) => {
+            eprintln!("Error: {e:?}");
+            EXIT_SCAN_ERROR
+        }
+    });
+}
+
+/// Print the events of the library to the standard error: warnings only, or also the lookups and
+/// the parsed files with --verbose; RUST_LOG overrides the filter
+fn init_logging(verbose: bool) {
+    let filter = if verbose {
+        "warn,dependency_runner=debug"
+    } else {
+        "warn"
+    };
+    env_logger::Builder::new()
+        .parse_filters(filter)
+        .parse_default_env()
+        .format_timestamp(None)
+        .init();
+}
+
+impl DeprunCli {
+    /// Whether verbose output was requested, for the scan of the target or of the subcommand
+    fn verbose(&self) -> bool {
+        match &self.command {
+            None => self.scan.verbose,
+            Some(
+                DeprunCommand::Audit { scan, .. }
+                | DeprunCommand::Bundle { scan, .. }
+                | DeprunCommand::Lock { scan, .. }
+                | DeprunCommand::DeploymentList { scan, .. }
+                | DeprunCommand::Why { scan, .. }
+                | DeprunCommand::Explain { scan, .. }
+                | DeprunCommand::Probe { scan, .. }
+                | DeprunCommand::FindSymbol { scan, .. }
+                | DeprunCommand::WhatIf { scan, .. }
+                | DeprunCommand::Minidump { scan, .. }
+                | DeprunCommand::Watch { scan, .. }
+                | DeprunCommand::Tui { scan, .. },
+            ) => scan.verbose,
+            Some(_) => false,
         }
     }
+}
 
-    let sorted_executables = executables.sorted_by_first_appearance();
+/// Run the command given on the command line, returning the exit code of the process
+fn run_deprun() -> anyhow::Result<i32> {
+    let args = DeprunCli::parse();
+    init_logging(args.verbose());
 
-    #[cfg(not(windows))]
+    match args.command {
+        Some(DeprunCommand::Diff { old, new, json }) => {
+            let diff = ExecutablesDiff::compare(&load_or_scan(&old)?, &load_or_scan(&new)?);
+            if json {
+                println!("{}", serde_json::to_string(&diff)?);
+            } else {
+                print!("{diff}");
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::Rules { code, json }) => {
+            let rules = match code {
+                Some(code) => vec![rule(&code).context(format!("unknown rule {code}"))?],
+                None => RULES.iter().collect(),
+            };
+            if json {
+                println!("{}", serde_json::to_string(&rules)?);
+            } else {
+                for rule in rules {
+                    println!("{} {}: {}", rule.code, rule.name, rule.summary);
+                    println!("    {}\n", rule.explanation);
+                }
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::Tui { scan }) => {
+            let mut query = build_query(&scan)?;
+            query.parameters.extract_symbols = true;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            dependency_runner::tui::browse(&executables)?;
+            return Ok(0);
+        }
+        Some(DeprunCommand::Audit { scan, json }) => {
+            let query = build_query(&scan)?;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let risks = audit(&executables, &lookup_path)?;
+            if json {
+                println!("{}", serde_json::to_string(&risks)?);
+            } else if risks.is_empty() {
+                println!("No hijacking risks detected");
+            } else {
+                for risk in risks {
+                    let location = risk
+                        .path
+                        .as_ref()
+                        .map(|p| format!(" ({})", readable_path(p)))
+                        .unwrap_or_default();
+                    println!("{}: {}{}", risk.dllname, risk.kind, location);
+                }
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::Lock { scan, output }) => {
+            let mut query = build_query(&scan)?;
+            query.parameters.compute_hashes = true;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let lock = DependencyLock::from_executables(&executables)?;
+            lock.write(&output)
+                .context(format!("couldn't write to {output}"))?;
+            println!(
+                "Locked {} dependencies in {output}",
+                lock.dependencies.len()
+            );
+            return Ok(0);
+        }
+        Some(DeprunCommand::Bundle {
+            scan,
+            output_dir,
+            dry_run,
+            overwrite,
+            manifest,
+            deployment,
+        }) => {
+            let query = build_query(&scan)?;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let output_dir = output_dir
+                .map(PathBuf::from)
+                .unwrap_or_else(|| query.target.app_dir.clone());
+            let bundle_manifest = bundle(
+                &executables,
+                &output_dir,
+                &deployment.policy()?,
+                overwrite,
+                dry_run,
+            )?;
+            for entry in &bundle_manifest.entries {
+                println!(
+                    "{:?}: {} -> {}",
+                    entry.action,
+                    entry.source.display(),
+                    entry.destination.display()
+                );
+            }
+            if let Some(manifest_path) = manifest {
+                fs::write(
+                    &manifest_path,
+                    serde_json::to_string_pretty(&bundle_manifest)?,
+                )
+                .context(format!("couldn't write to {manifest_path}"))?;
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::DeploymentList {
+            scan,
+            format,
+            output,
+            deployment,
+        }) => {
+            let query = build_query(&scan)?;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let list = deployment_list(&executables, &deployment.policy()?, format)?;
+            match output {
+                Some(output_path) => fs::write(&output_path, list)
+                    .context(format!("couldn't write to {output_path}"))?,
+                None => print!("{list}"),
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::Verify { dir, allow, json }) => {
+            let dir = fs::canonicalize(dir)?;
+            let first_executable = executables_in_dir(&dir)?
+                .into_iter()
+                .next()
+                .ok_or_else(|| anyhow::anyhow!("No executable found in {}", dir.display()))?;
+            let query = LookupQuery::deduce_from_executable_location(first_executable)?;
+            let lookup_path = LookupPath::deduce(&query);
+            let violations = verify_closure(&dir, &lookup_path, &NamePatterns::new(&allow)?)?;
+            if json {
+                println!("{}", serde_json::to_string(&violations)?);
+            } else if violations.is_empty() {
+                println!("The directory contains all the non-system dependencies");
+            } else {
+                for violation in &violations {
+                    let importer = violation
+                        .importer
+                        .file_name()
+                        .map(|f| f.to_string_lossy())
+                        .unwrap_or_default();
+                    match &violation.kind {
+                        ClosureViolationKind::NotFound => {
+                            println!("{importer}: {} not found", violation.dllname)
+                        }
+                        ClosureViolationKind::ResolvedOutside(p) => println!(
+                            "{importer}: {} resolved outside the directory ({})",
+                            violation.dllname,
+                            readable_path(p)
+                        ),
+                    }
+                }
+            }
+            return Ok(if violations.is_empty() {
+                ScanOutcome::Clean
+            } else {
+                ScanOutcome::MissingDlls
+            }
+            .exit_code());
+        }
+        Some(DeprunCommand::Scan {
+            dir,
+            meson,
+            recursive,
+            depth,
+            include,
+            exclude,
+            check_symbols,
+            #[cfg(not(windows))]
+            windows_root,
+            json,
+        }) => {
+            let options = DirectoryScanOptions {
+                recursive,
+                max_depth: depth,
+                include: NamePatterns::new(&include)?,
+                exclude: NamePatterns::new(&exclude)?,
+            };
+            let configure = |query: &mut LookupQuery| {
+                query.parameters.extract_symbols = check_symbols;
+                #[cfg(not(windows))]
+                if let Some(windows_root) = &windows_root {
+                    query.system = WindowsSystem::from_root(windows_root);
+                }
+            };
+            let target_list = if dir == "-" {
+                Some(std::io::read_to_string(std::io::stdin())?)
+            } else if let Some(list_file) = dir.strip_prefix('@') {
+                Some(fs::read_to_string(list_file)?)
+            } else {
+                None
+            };
+            let selects_files =
+                meson || recursive || depth.is_some() || !include.is_empty() || !exclude.is_empty();
+            if target_list.is_some() && selects_files {
+                anyhow::bail!(
+                    "--meson, --recursive, --depth, --include and --exclude cannot be used with a target list"
+                );
+            }
+            let directory_scan = if let Some(target_list) = target_list {
+                let paths = parse_target_list(&target_list);
+                scan_targets(std::env::current_dir()?, &paths, configure)?
+            } else if meson {
+                dependency_runner::meson::scan_build_dir(&dir, configure)?
+            } else {
+                scan_directory(&dir, &options, configure)?
+            };
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "targets": directory_scan.targets,
+                        "failures": directory_scan.failures,
+                        "summary": directory_scan.summary(),
+                    })
+                );
+            } else {
+                print!("{directory_scan}");
+            }
+            return Ok(if directory_scan.failures.is_empty() {
+                0
+            } else {
+                EXIT_SCAN_ERROR
+            });
+        }
+        Some(DeprunCommand::Cmake {
+            build_dir,
+            config,
+            request_file_api,
+            check_symbols,
+            #[cfg(not(windows))]
+            windows_root,
+            json,
+        }) => {
+            if request_file_api {
+                request_file_api_reply(&build_dir)?;
+                println!("Run CMake on {build_dir} again, then deprun cmake {build_dir}");
+                return Ok(0);
+            }
+            let build_scan = scan_build_dir(&build_dir, config.as_deref(), |query| {
+                query.parameters.extract_symbols = check_symbols;
+                #[cfg(not(windows))]
+                if let Some(windows_root) = &windows_root {
+                    query.system = WindowsSystem::from_root(windows_root);
+                }
+            })?;
+            if json {
+                println!(
+                    "{}",
+                    serde_json::json!({
+                        "targets": build_scan.targets,
+                        "failures": build_scan.failures,
+                        "summary": build_scan.summary(),
+                    })
+                );
+            } else {
+                print!("{build_scan}");
+            }
+            return Ok(if build_scan.failures.is_empty() {
+                0
+            } else {
+                EXIT_SCAN_ERROR
+            });
+        }
+        Some(DeprunCommand::Why { scan, dll }) => {
+            let query = build_query(&scan)?;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let chains = executables.dependency_chains(&dll)?;
+            if chains.is_empty() {
+                println!("{dll} is not a dependency of the target");
+            }
+            for chain in chains {
+                println!("{}", chain.join(" → "));
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::Explain { scan, dll }) => {
+            let mut query = build_query(&scan)?;
+            query.parameters.record_probes = true;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let probes = match executables.get(&dll).and_then(|e| e.probes.clone()) {
+                Some(probes) => probes,
+                None => {
+                    println!("{dll} is not a dependency of the target, probing anyway");
+                    lookup_path.probe_dll(&dll)?
+                }
+            };
+            println!("Lookup of {dll}:");
+            let winner = Probe::winner(&probes);
+            for (i, probe) in probes.iter().enumerate() {
+                let mark = match winner {
+                    Some(w) if w == i => " <= loaded from here",
+                    Some(w) if w < i && probe.is_found() => " (shadowed)",
+                    None if i > 0 && probe.is_found() => " (ignored, overridden)",
+                    _ => "",
+                };
+                println!("\t{probe}{mark}");
+            }
+            if winner.is_none() {
+                println!("{dll} not found");
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::Probe { scan, dll, json }) => {
+            let query = build_query(&scan)?;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let report = lookup_path.probe(&query, &dll)?;
+            if json {
+                println!("{}", serde_json::to_string(&report)?);
+            } else {
+                print!("{report}");
+            }
+            return Ok(if report.found { 0 } else { 1 });
+        }
+        Some(DeprunCommand::FindSymbol {
+            scan,
+            pattern,
+            regex,
+            json,
+        }) => {
+            let pattern = if regex {
+                SymbolPattern::regex(&pattern)?
+            } else {
+                SymbolPattern::glob(&pattern)?
+            };
+            let mut query = build_query(&scan)?;
+            query.parameters.extract_symbols = true;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let matches = executables.find_symbol(&pattern);
+            if json {
+                println!("{}", serde_json::to_string(&matches)?);
+            } else {
+                for found in &matches {
+                    println!("{found}");
+                }
+            }
+            return Ok(if matches.is_empty() { 1 } else { 0 });
+        }
+        Some(DeprunCommand::WhatIf {
+            scan,
+            disable,
+            json,
+        }) => {
+            let query = build_query(&scan)?;
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let mut disabled_entries = Vec::new();
+            for entry in &disable {
+                let matching = lookup_path_entries_matching(&lookup_path, entry);
+                if matching.is_empty() {
+                    anyhow::bail!("no entry of the lookup path matches {entry}");
+                }
+                disabled_entries.extend(matching);
+            }
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let diff = dependency_runner::runner::what_if_disabled(
+                &query,
+                &lookup_path,
+                &executables,
+                &disabled_entries,
+            )?;
+            if json {
+                println!("{}", serde_json::to_string(&diff)?);
+            } else {
+                print!("{diff}");
+            }
+            return Ok(0);
+        }
+        #[cfg(windows)]
+        Some(DeprunCommand::Live { pid, json }) => {
+            let modules = dependency_runner::live::loaded_modules(pid)?;
+            let exe_path = modules
+                .first()
+                .map(|m| m.path.clone())
+                .ok_or_else(|| anyhow::anyhow!("no modules loaded by process {pid}"))?;
+            let query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+            let lookup_path = LookupPath::deduce(&query);
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let comparison =
+                dependency_runner::live::LiveComparison::compare(&executables, &modules);
+            if json {
+                println!("{}", serde_json::to_string(&comparison)?);
+            } else {
+                print!("{comparison}");
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::Minidump {
+            mut scan,
+            dump,
+            diff,
+            json,
+        }) => {
+            let modules = read_modules(&dump).context(format!("couldn't read {dump}"))?;
+            let target_given = scan.input.is_some()
+                || scan.service.is_some()
+                || scan.scheduled_task.is_some()
+                || scan.com_clsid.is_some();
+            if !target_given {
+                let recorded_exe = modules
+                    .first()
+                    .map(|m| m.path.clone())
+                    .filter(|p| p.is_file())
+                    .ok_or_else(|| {
+                        anyhow::anyhow!(
+                            "the executable of the dump was not found on this machine, give it after the dump"
+                        )
+                    })?;
+                scan.input = Some(path_to_string(&recorded_exe));
+            }
+            let mut query = build_query(&scan)?;
+            if diff {
+                let lookup_path = build_lookup_path(&scan, &query)?;
+                let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+                let loaded: Vec<_> = modules.iter().map(|m| m.loaded_module()).collect();
+                let comparison =
+                    dependency_runner::live::LiveComparison::compare(&executables, &loaded);
+                if json {
+                    println!("{}", serde_json::to_string(&comparison)?);
+                } else {
+                    print!("{comparison}");
+                }
+                return Ok(0);
+            }
+            if json {
+                println!("{}", serde_json::to_string(&modules)?);
+                return Ok(0);
+            }
+            override_with_modules(&mut query, &modules);
+            let lookup_path = build_lookup_path(&scan, &query)?;
+            let executables = dependency_runner::runner::run(&query, &lookup_path)?;
+            let options = OutputOptions {
+                include_system_dlls: true,
+                max_depth: query.parameters.max_depth,
+                show_architecture: false,
+                color: false,
+            };
+            print!(
+                "{}",
+                render(
+                    OutputFormat::Tree,
+                    &executables,
+                    &ExecutablesCheckReport::new(),
+                    &options
+                )?
+            );
+            let runtime_loads: Vec<_> = modules
+                .iter()
+                .filter(|m| !executables.contains(&m.name))
+                .collect();
+            if !runtime_loads.is_empty() {
+                println!("\nLoaded at runtime:");
+                for module in runtime_loads {
+                    println!(
+                        "{} {} => {}",
+                        module.name,
+                        module.version.as_deref().unwrap_or("-"),
+                        module.path.display()
+                    );
+                }
+            }
+            return Ok(0);
+        }
+        Some(DeprunCommand::Watch { scan, color }) => {
+            let query = build_query(&scan)?;
+            let mut lookup_path = build_lookup_path(&scan, &query)?;
+            // the changes are not always notified (e.g. on network shares)
+            lookup_path.validate_modification_times();
+            let mut watcher = ScanWatcher::new(&query, &lookup_path)?;
+            let options = OutputOptions {
+                include_system_dlls: false,
+                max_depth: query.parameters.max_depth,
+                show_architecture: false,
+                color: color.enabled(),
+            };
+            print!(
+                "{}",
+                render(
+                    OutputFormat::Tree,
+                    watcher.executables(),
+                    &ExecutablesCheckReport::new(),
+                    &options
+                )?
+            );
+            println!(
+                "\nWatching {} directories for changes...",
+                watcher.directories().len()
+            );
+            while let Some(change) = watcher.next_change()? {
+                println!();
+                print!("{change}");
+            }
+            return Ok(0);
+        }
+        None => {}
+    }
+
+    let formats = if args.format.is_empty() {
+        vec![OutputFormat::Tree]
+    } else {
+        args.format.clone()
+    };
+    if args.output.len() > formats.len() {
+        anyhow::bail!("More destinations (-o) than output formats (--format) were given");
+    }
+    // baseline of known findings, compared to the findings of this scan (or replaced by them)
+    let known_findings = match &args.baseline {
+        Some(baseline_path) if !args.update_baseline => Some(
+            ExecutablesCheckReport::from_json_file(baseline_path)
+                .context(format!("couldn't read {baseline_path}"))?,
+        ),
+        _ => None,
+    };
+    let baseline_has_symbols = known_findings.as_ref().is_some_and(|b| {
+        b.findings()
+            .iter()
+            .any(|f| matches!(f.kind, FindingKind::MissingSymbol { .. }))
+    });
+    let needs_check = formats.iter().any(OutputFormat::needs_check)
+        || args.fail_on != FailOn::None
+        || args.check_security
+        || !args.banned_imports.is_empty()
+        || !args.allowed_dir.is_empty()
+        || args.baseline.is_some()
+        || args.lock.is_some()
+        || args.scan.target_os.is_some()
+        || args.check_symbols;
+    // an updated baseline records all the findings, to be compared with any --fail-on later
+    let needs_symbols = formats.iter().any(OutputFormat::needs_check)
+        || args.fail_on >= FailOn::MissingSymbols
+        || !args.banned_imports.is_empty()
+        || args.update_baseline
+        || baseline_has_symbols
+        || args.scan.target_os.is_some();
+    let mut banned_imports = BannedImports::new();
+    for list in &args.banned_imports {
+        banned_imports.extend(if list == "sdl" {
+            BannedImports::sdl()
+        } else {
+            BannedImports::read(list).context(format!("couldn't read banned imports {list}"))?
+        });
+    }
+
+    let mut query = build_query(&args.scan)?;
+    query.parameters.compute_hashes |= args.output_sbom.is_some() || args.lock.is_some();
+
+    #[cfg(all(feature = "skim", not(windows)))]
+    {
+        query.parameters.extract_symbols =
+            args.check_symbols || needs_symbols || args.skim_symbols || args.skim;
+    }
+
+    #[cfg(not(all(feature = "skim", not(windows))))]
+    {
+        query.parameters.extract_symbols = args.check_symbols || needs_symbols;
+    }
+
+    let lookup_path = build_lookup_path(&args.scan, &query)?;
+
+    let executables = if let Some(previous_json_path) = args.previous_json_path {
+        let previous = Executables::from_json_file(&previous_json_path)
+            .context(format!("couldn't read {previous_json_path}"))?;
+        dependency_runner::runner::rescan(&query, &lookup_path, &previous)?
+    } else {
+        dependency_runner::runner::run(&query, &lookup_path)?
+    };
+
+    for diagnostic in executables.diagnostics() {
+        eprintln!("{diagnostic}");
+    }
+    if args.timings {
+        if let Some(metrics) = executables.scan_metrics() {
+            eprint!("{metrics}");
+        }
+    }
+
+    let stats = executables.stats();
+    // the checks and the exit code are computed on the whole scan, only the tree shown is pruned
+    let pruned = if args.errors_only {
+        let pruned = executables.filter_only_notfound()?;
+        if pruned.is_empty() {
+            println!("No missing DLLs identified");
+        }
+        Some(pruned)
+    } else {
+        None
+    };
+    let shown = pruned.as_ref().unwrap_or(&executables);
+
+    #[cfg(all(feature = "skim", not(windows)))]
     let do_skim = args.skim;
-    #[cfg(not(windows))]
+    #[cfg(all(feature = "skim", not(windows)))]
     let do_skim_symbols = args.skim_symbols;
-    #[cfg(windows)]
+    #[cfg(not(all(feature = "skim", not(windows))))]
     let do_skim = false;
-    #[cfg(windows)]
+    #[cfg(not(all(feature = "skim", not(windows))))]
     let do_skim_symbols = false;
 
-    // print results
-    if !(do_skim || do_skim_symbols) {
-        // printing in depth order // TODO: arg to choose output format
-        //
-        // for e in sorted_executables {
-        //     if !e.is_system.unwrap_or(false) {
-        //         if let Some(folder) = e.folder {
-        //             println!("Found executable {}\n", &e.name);
-        //             println!("\tDepth: {}", &e.depth);
-        //             println!("\tcontaining folder: {}", folder);
-        //
-        //             if let Some(deps) = e.dependencies {
-        //                 println!("\tdependencies:");
-        //                 for d in deps {
-        //                     println!("\t\t{}", d);
-        //                 }
-        //             }
-        //         } else {
-        //             println!("Executable {} not found\n", &e.name);
-        //         }
-        //         println!();
-        //
-        //     }
-        // }
-
-        // printing depth-first
-        println!();
-        if let Some(root) = executables.get_root()? {
-            visit_depth_first(
-                root,
-                0,
-                query.parameters.max_depth,
-                &executables,
-                args.print_system_dlls,
-            );
-        }
+    let scan_report = if needs_check {
+        check_scan(&executables, &query)?
+    } else {
+        ExecutablesCheckReport::new()
+    };
+    let mut report = scan_report.clone();
+    if args.check_security {
+        report.extend(executables.check_security());
+        report.sort();
+    }
+    if !banned_imports.is_empty() {
+        report.extend(executables.check_banned_imports(&banned_imports));
+        report.sort();
+    }
+    if !args.allowed_dir.is_empty() {
+        let allowed_dirs = args
+            .allowed_dir
+            .iter()
+            .map(|d| fs::canonicalize(d).context(format!("allowed directory {d} not found")))
+            .collect::<Result<Vec<_>, _>>()?;
+        report.extend(executables.check_allowed_directories(&allowed_dirs));
+        report.sort();
+    }
+    if let Some(lock_path) = &args.lock {
+        let lock = DependencyLock::read(lock_path).context(format!("couldn't read {lock_path}"))?;
+        report.extend(executables.check_lock(&lock)?);
+        report.sort();
+    }
 
-        if args.check_symbols {
-            println!("\nChecking symbols...\n");
-
-            let sym_check = executables.check(query.parameters.extract_symbols);
-            match sym_check {
-                Ok(report) => {
-                    if !report.not_found_libraries.is_empty() {
-                        println!("Missing libraries detected!");
-                        println!("[Importing executable, missing dependencies]\n");
-                        for (importer, missing_dependencies) in report.not_found_libraries.iter() {
-                            if !missing_dependencies.is_empty() {
-                                println!("{importer}");
-                                for missing_import_dll in missing_dependencies {
-                                    println!("\t{missing_import_dll}");
-                                }
-                            }
-                        }
-                        println!();
-                    } else {
-                        println!("No missing libraries detected");
-                    }
+    let baseline = match &args.baseline {
+        Some(baseline_path) if args.update_baseline => {
+            fs::write(baseline_path, serde_json::to_string_pretty(&report)?)
+                .context(format!("couldn't write to {baseline_path}"))?;
+            Some(report.clone())
+        }
+        _ => known_findings,
+    };
+    if let Some(baseline) = &baseline {
+        report = report.new_since(baseline);
+    }
+    let options = OutputOptions {
+        include_system_dlls: args.print_system_dlls,
+        max_depth: query.parameters.max_depth,
+        show_architecture: false,
+        color: false,
+    };
+    let stdout_options = OutputOptions {
+        color: args.color.enabled(),
+        ..options.clone()
+    };
 
-                    if let Some(missing_symbols) = report.not_found_symbols {
-                        println!("\nMissing symbols detected!");
-                        println!("[Importing executable, exporting executable, missing symbols]\n");
-                        for (filename, missing_imports) in missing_symbols.iter() {
-                            if !missing_imports.is_empty() {
-                                println!("{filename}");
-                                for (missing_import_dll, missing_symbols) in missing_imports {
-                                    println!("\t{missing_import_dll}");
-                                    for missing_symbol in missing_symbols {
-                                        println!(
-                                            "\t\t{}",
-                                            demangle_symbol(missing_symbol)
-                                                .as_ref()
-                                                .unwrap_or(missing_symbol)
-                                        );
-                                    }
-                                }
-                            }
-                        }
-                    } else {
-                        println!("No missing symbols detected");
-                    }
+    // write the outputs: the ones without a destination go to the standard output, unless skimming
+    for (i, format) in formats.iter().enumerate() {
+        let destination = args.output.get(i).filter(|o| *o != "-");
+        let format_options = match destination {
+            Some(_) => &options,
+            None => &stdout_options,
+        };
+        // the reports of the checks locate the findings in the whole scan
+        let format_executables = if format.needs_check() {
+            &executables
+        } else {
+            shown
+        };
+        let rendered = render(*format, format_executables, &report, format_options)?;
+        match destination {
+            Some(path) => {
+                fs::write(path, rendered).context(format!("couldn't write to {path}"))?;
+                if args.scan.verbose {
+                    println!("successfully wrote to {path}");
                 }
-                Err(sym_check_error) => println!("{sym_check_error:?}"),
             }
+            None if !(do_skim || do_skim_symbols) => {
+                println!();
+                print!("{rendered}");
+            }
+            None => {}
+        }
+    }
+    let tree_on_stdout = formats
+        .iter()
+        .enumerate()
+        .any(|(i, f)| *f == OutputFormat::Tree && args.output.get(i).is_none_or(|o| o == "-"));
+    if tree_on_stdout && !(do_skim || do_skim_symbols) {
+        print!("\n{stats}");
+    }
+
+    if !(do_skim || do_skim_symbols) && args.check_symbols {
+        println!("\nChecking symbols...\n");
+
+        let sym_check = match &baseline {
+            Some(baseline) => scan_report.new_since(baseline),
+            None => scan_report,
+        };
+        if sym_check.is_empty() {
+            println!("No problems detected");
+        }
+        for finding in sym_check.findings() {
+            println!("{finding}");
+        }
+    }
+
+    if !(do_skim || do_skim_symbols) {
+        if !banned_imports.is_empty() {
+            print_check(&report, "Checking banned imports", "DR0010");
+        }
+        if !args.allowed_dir.is_empty() {
+            print_check(&report, "Checking the directories of the DLLs", "DR0011");
+        }
+        if args.scan.target_os.is_some() {
+            print_check(
+                &report,
+                "Checking the availability on the target OS",
+                "DR0009",
+            );
+        }
+        if args.lock.is_some() {
+            print_check(&report, "Checking the DLLs against the lock file", "DR0013");
+        }
+        if args.check_security {
+            print_check(&report, "Checking security mitigations", "DR0008");
         }
     }
 
     // skimming
-    #[cfg(not(windows))]
+    #[cfg(all(feature = "skim", not(windows)))]
     if args.skim {
-        while let Some(selected_dlls) = skim_dlls(&executables) {
-            skim_symbols(&executables, Some(selected_dlls));
+        while let Some(selected_dlls) = skim_dlls(shown) {
+            skim_symbols(shown, Some(selected_dlls));
         }
     } else if args.skim_symbols {
-        skim_symbols(&executables, None);
+        skim_symbols(shown, None);
     }
 
-    // JSON representation
-
-    if let Some(json_output_path) = args.output_json_path {
-        let js = serde_json::to_string(&sorted_executables).context("Error serializing")?;
+    // software bill of materials
 
-        use std::io::prelude::*;
-        let path = std::path::Path::new(&json_output_path);
-        let display = path.display();
-
-        // Open a file in write-only mode, returns `io::Result<File>`
-        let mut file = fs::File::create(path).context(format!("couldn't create {display}"))?;
-
-        // Write to `file`, returns `io::Result<()>`
-        file.write_all(js.as_bytes())
-            .context(format!("couldn't write to {display}"))?;
-
-        if args.verbose {
-            println!("successfully wrote to {display}");
-        }
+    if let Some(sbom_output_path) = args.output_sbom {
+        let sbom = to_sbom(&executables, args.sbom_format, std::time::SystemTime::now());
+        let sbom = serde_json::to_string_pretty(&sbom)?;
+        fs::write(&sbom_output_path, sbom)
+            .context(format!("couldn't write to {sbom_output_path}"))?;
     }
 
-    Ok(())
+    Ok(report.outcome(args.fail_on).exit_code())
 }
@@ -1,64 +1,191 @@
 extern crate dependency_runner;
 
 use clap::Parser;
-use dependency_runner::common::{decanonicalize, path_to_string, readable_canonical_path};
-use dependency_runner::executable::Executable;
+#[cfg(not(windows))]
+use dependency_runner::common::path_to_string;
+use dependency_runner::common::NamePatterns;
+use dependency_runner::directory::{find_importers, DirectoryScanOptions};
+use dependency_runner::output::{render, OutputFormat, OutputOptions};
+use dependency_runner::report::FindingKind;
 use fs_err as fs;
+use std::path::{Path, PathBuf};
 
 use dependency_runner::path::LookupPath;
 use dependency_runner::query::LookupQuery;
-use dependency_runner::runner::run;
+use dependency_runner::session::ScanSession;
 #[cfg(not(windows))]
 use dependency_runner::system::WindowsSystem;
+use dependency_runner::vcx::{parse_vcxproj, select_configuration};
 
 #[derive(Parser)]
 #[clap(author, version, about, long_about = None)]
 struct WlddCli {
-    #[clap(value_parser)]
-    /// Target file (.exe, .dll or .vcxproj)
-    input: String,
+    #[clap(value_parser, required = true)]
+    /// Target files (.exe, .dll or .vcxproj), or glob patterns matching them (directories with --requires)
+    input: Vec<String>,
     #[clap(short, long)]
     /// Activate verbose output
     verbose: bool,
     #[clap(short = 's', long)]
     /// Do not include system DLLs in the output
     hide_system_dlls: bool,
+    #[clap(short = 'r', long)]
+    /// Also check the imported symbols, and report the missing ones as undefined (like ldd -r)
+    function_relocs: bool,
+    #[clap(value_parser, long)]
+    /// Maximum recursion depth (default: unlimited)
+    max_depth: Option<usize>,
+    #[clap(long)]
+    /// Also scan the dependencies of system DLLs, as Dependency Walker does (default: stop at them)
+    full_system: bool,
+    #[clap(short = 'a', long)]
+    /// Print the architecture and subsystem of each DLL, and warn about the ones whose architecture differs from the target
+    architecture: bool,
+    #[clap(value_parser, long, value_name = "DLL")]
+    /// Instead of printing the dependencies, list the executables under the target directories that reference this DLL
+    requires: Option<String>,
+    #[clap(value_parser, long)]
+    /// Read the complete DLL lookup path from a .dwp file (Dependency Walker's format)
+    dwp_path: Option<String>,
+    #[clap(value_parser, long, conflicts_with = "dwp_path")]
+    /// Read the complete DLL lookup path from a JSON path profile (see deprun --save-path-profile)
+    path_profile: Option<String>,
+    #[clap(value_parser, long)]
+    /// Configuration to use (Debug, Release, ...) if the target is a .vcxproj file
+    vcxproj_configuration: Option<String>,
+    #[clap(value_parser, long)]
+    /// Platform to use (x64, Win32, ARM64, ...) if the target is a .vcxproj file defining several
+    vcxproj_platform: Option<String>,
+    #[clap(value_parser, short, long, default_value = "ldd")]
+    /// Output format: ldd, tree, json, ndjson, csv, dot, html, sarif, junit, dependencies or dw-modules
+    format: OutputFormat,
     #[cfg(not(windows))]
     #[clap(value_parser, short, long)]
     /// Windows partition to use for system DLLs lookup (if not specified, the partition where INPUT lies will be tested and used if valid)
     windows_root: Option<String>,
 }
 
-fn main() -> anyhow::Result<()> {
+/// Expand the arguments containing glob patterns (the Windows shell does not do it)
+fn expand_inputs(inputs: &[String]) -> anyhow::Result<Vec<PathBuf>> {
+    let mut paths = Vec::new();
+    for input in inputs {
+        if !input.contains(['*', '?', '[']) {
+            paths.push(PathBuf::from(input));
+            continue;
+        }
+        let matches = glob::glob(input)?.collect::<Result<Vec<_>, _>>()?;
+        if matches.is_empty() {
+            anyhow::bail!("No file matches {input}");
+        }
+        paths.extend(matches);
+    }
+    Ok(paths)
+}
+
+/// Print the events of the library to the standard error: warnings only, or also the lookups and
+/// the parsed files with --verbose; RUST_LOG overrides the filter
+fn init_logging(verbose: bool) {
+    let filter = if verbose {
+        "warn,dependency_runner=debug"
+    } else {
+        "warn"
+    };
+    env_logger::Builder::new()
+        .parse_filters(filter)
+        .parse_default_env()
+        .format_timestamp(None)
+        .init();
+}
+
+fn main() {
     let args = WlddCli::parse();
+    init_logging(args.verbose);
 
-    let binary_path = std::path::PathBuf::from(args.input);
+    let targets = match expand_inputs(&args.input) {
+        Ok(targets) => targets,
+        Err(e) => {
+            eprintln!("wldd: {e}");
+            std::process::exit(1);
+        }
+    };
 
+    // as ldd, print a header for each target if there are several, and fail if any of them fails
+    let mut exit_code = 0;
+    // the system DLLs are listed and parsed only once for all the targets
+    let mut session = ScanSession::new();
+    for target in &targets {
+        if targets.len() > 1 {
+            println!("{}:", target.display());
+        }
+        let result = match &args.requires {
+            Some(dllname) => print_importers(target, dllname),
+            None => print_dependencies(&args, &mut session, target),
+        };
+        if let Err(e) = result {
+            eprintln!("wldd: {}: {e}", target.display());
+            exit_code = 1;
+        }
+    }
+    std::process::exit(exit_code);
+}
+
+fn print_importers(dir: &Path, dllname: &str) -> anyhow::Result<()> {
+    if !dir.is_dir() {
+        anyhow::bail!("--requires expects a directory");
+    }
+    let options = DirectoryScanOptions {
+        recursive: true,
+        max_depth: None,
+        include: NamePatterns::default(),
+        exclude: NamePatterns::default(),
+    };
+    for importer in find_importers(dir, &options, dllname)? {
+        let kinds: Vec<String> = importer.kinds.iter().map(|k| k.to_string()).collect();
+        println!("\t{} ({})", importer.path.display(), kinds.join(", "));
+    }
+    Ok(())
+}
+
+fn print_dependencies(
+    args: &WlddCli,
+    session: &mut ScanSession,
+    binary_path: &Path,
+) -> anyhow::Result<()> {
     if !binary_path.exists() {
-        eprintln!(
-            "Specified file not found at {}",
-            binary_path.to_str().unwrap()
-        );
-        std::process::exit(1);
+        anyhow::bail!("file not found");
     }
 
     if binary_path.is_dir() {
-        eprintln!(
-            "The specified path is a directory, not a PE executable file: {}",
-            binary_path.to_str().unwrap(),
-        );
-        std::process::exit(1);
+        anyhow::bail!("the specified path is a directory, not a PE executable file");
     }
 
     let binary_path = fs::canonicalize(binary_path)?;
 
-    #[cfg(not(windows))]
-    let mut query = LookupQuery::deduce_from_executable_location(binary_path)?;
-    #[cfg(windows)]
-    let query = LookupQuery::deduce_from_executable_location(binary_path)?;
+    let mut query = if binary_path
+        .extension()
+        .map(|e| e == "vcxproj")
+        .unwrap_or(false)
+    {
+        let mut exe_info_per_config = parse_vcxproj(&binary_path)?;
+        let config = select_configuration(
+            &exe_info_per_config,
+            args.vcxproj_configuration.as_deref(),
+            args.vcxproj_platform.as_deref(),
+        )?
+        .clone();
+        let exe_info = exe_info_per_config
+            .remove(&config)
+            .expect("selected configuration");
+        LookupQuery::read_from_vcx_executable_information(&exe_info)?
+    } else {
+        session.deduce_query(binary_path)?
+    };
+    query.parameters.extract_symbols = args.function_relocs;
+    query.parameters.max_depth = args.max_depth;
+    query.parameters.scan_system_dlls = args.full_system;
 
     #[cfg(not(windows))]
-    if let Some(overridden_winroot) = args.windows_root {
+    if let Some(overridden_winroot) = &args.windows_root {
         query.system = WindowsSystem::from_root(overridden_winroot);
     } else if args.verbose {
         if let Some(system) = &query.system {
@@ -71,34 +198,63 @@ fn main() -> anyhow::Result<()> {
         }
     }
 
-    let lookup_path = LookupPath::deduce(&query);
-    let executables = run(&query, &lookup_path)?;
-
-    // printing in depth order
-    let sorted_executables: Vec<&Executable> = executables.sorted_by_first_appearance();
-
-    let prefix = " ".repeat(8); // as ldd
-
-    for e in sorted_executables.iter().skip(1) {
-        if !(e.details.as_ref().map(|d| d.is_system).unwrap_or(false) && args.hide_system_dlls) {
-            if e.found {
-                println!(
-                    "{}{} => {}",
-                    &prefix,
-                    &e.dllname,
-                    decanonicalize(&path_to_string(
-                        e.details.as_ref().map(|d| &d.full_path).unwrap()
-                    ))
-                );
-            } else {
-                println!(
-                    "{}{} => not found",
-                    &prefix,
-                    e.details
-                        .as_ref()
-                        .and_then(|d| readable_canonical_path(&d.full_path).ok())
-                        .unwrap_or(format!("{:?}", e.dllname))
-                );
+    let mut lookup_path = match (&args.dwp_path, &args.path_profile) {
+        (Some(dwp_path), _) => LookupPath::from_dwp_file(dwp_path, &query)?,
+        (None, Some(profile_path)) => LookupPath::from_path_profile(profile_path, &query)?,
+        (None, None) => LookupPath::deduce(&query),
+    };
+    lookup_path.set_cache(session.cache().clone());
+    let executables = session.scan(&query, &lookup_path)?;
+    for diagnostic in executables.diagnostics() {
+        eprintln!("{diagnostic}");
+    }
+
+    if args.architecture {
+        let root_architecture = executables
+            .get_roots()?
+            .first()
+            .and_then(|r| r.details.as_ref())
+            .and_then(|d| d.architecture);
+        for e in executables.iter() {
+            let architecture = e.details.as_ref().and_then(|d| d.architecture);
+            if let (Some(architecture), Some(root_architecture)) = (architecture, root_architecture)
+            {
+                if architecture != root_architecture {
+                    eprintln!(
+                        "warning: {} is {architecture}, but the target is {root_architecture}",
+                        e.dllname
+                    );
+                }
+            }
+        }
+    }
+
+    let report = executables.check(args.function_relocs)?;
+    let options = OutputOptions {
+        include_system_dlls: !args.hide_system_dlls,
+        max_depth: None,
+        show_architecture: args.architecture,
+        color: false,
+    };
+    print!("{}", render(args.format, &executables, &report, &options)?);
+
+    // mixing C runtimes is a frequent cause of broken deployments
+    for finding in report.findings().iter().filter(|f| {
+        matches!(
+            f.kind,
+            FindingKind::MixedCrt { .. } | FindingKind::CrtGenerations { .. }
+        )
+    }) {
+        eprintln!("WARNING: {}", finding.kind);
+    }
+
+    if args.function_relocs {
+        for finding in report.findings() {
+            if let FindingKind::MissingSymbol {
+                importer, symbol, ..
+            } = &finding.kind
+            {
+                println!("undefined symbol: {symbol}\t(needed by {importer})");
             }
         }
     }
@@ -0,0 +1,595 @@
+//! Copy of the dependency closure of an executable into a directory, for deployment
+//!
+//! The same files can instead be listed for an installer (WiX, NSIS) or a copy script, see
+//! [`deployment_list`].
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{readable_path, xml_escape, LookupError, NamePatterns};
+use crate::executable::Executables;
+
+/// What to do when a file with the same name already exists in the output directory
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum OverwritePolicy {
+    /// keep the existing file
+    Never,
+    /// replace the existing file if its content differs
+    IfDifferent,
+    /// always replace the existing file
+    Always,
+}
+
+impl std::str::FromStr for OverwritePolicy {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "never" => Ok(Self::Never),
+            "if-different" => Ok(Self::IfDifferent),
+            "always" => Ok(Self::Always),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown overwrite policy {s} (expected never, if-different or always)"
+            ))),
+        }
+    }
+}
+
+/// Outcome for a single file of the bundle
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum BundleAction {
+    /// the file was copied to the output directory
+    Copied,
+    /// an existing file was replaced
+    Overwritten,
+    /// an existing file was kept, according to the overwrite policy
+    KeptExisting,
+    /// the existing file is identical, or is the source itself
+    UpToDate,
+}
+
+/// File of the dependency closure, with its source and destination
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleEntry {
+    pub dllname: String,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+    pub action: BundleAction,
+}
+
+/// List of the files considered for the bundle, and what was done with each one
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BundleManifest {
+    pub output_dir: PathBuf,
+    /// if set, no file was actually written and the actions are the ones that would be performed
+    pub dry_run: bool,
+    pub entries: Vec<BundleEntry>,
+}
+
+/// Which files of a scan are shipped with the application (see
+/// `Executables::deployment_closure`)
+#[derive(Debug, Clone, Default)]
+pub struct DeploymentPolicy {
+    /// ship the roots of the scan as well
+    pub include_roots: bool,
+    /// system DLLs and KnownDLLs to ship anyway (e.g. the Universal CRT, see [`UCRT_DLLS`])
+    pub include: NamePatterns,
+    /// DLLs not to ship, even if they are not system DLLs
+    pub exclude: NamePatterns,
+}
+
+/// DLLs of the Universal CRT, for an app-local deployment on Windows versions predating Windows 10
+pub const UCRT_DLLS: &[&str] = &["ucrtbase.dll", "api-ms-win-crt-*.dll"];
+
+/// Copy the files of the deployment closure of the scan (see `Executables::deployment_closure`)
+/// into the output directory
+///
+/// The root executable is not copied, and the files are copied into the output directory
+/// itself. With `dry_run`, the manifest is computed without touching the filesystem.
+pub fn bundle<P: AsRef<Path>>(
+    executables: &Executables,
+    output_dir: P,
+    policy: &DeploymentPolicy,
+    overwrite: OverwritePolicy,
+    dry_run: bool,
+) -> Result<BundleManifest, LookupError> {
+    let output_dir = output_dir.as_ref();
+    let mut manifest = BundleManifest {
+        output_dir: output_dir.to_owned(),
+        dry_run,
+        entries: Vec::new(),
+    };
+
+    if !dry_run {
+        fs::create_dir_all(output_dir)?;
+    }
+
+    let policy = DeploymentPolicy {
+        include_roots: false,
+        ..policy.clone()
+    };
+    for file in executables.deployment_closure(&policy)? {
+        let Some(file_name) = file.source.file_name() else {
+            continue;
+        };
+        let destination = output_dir.join(file_name);
+        let source = file.source;
+
+        let action = if !destination.exists() {
+            BundleAction::Copied
+        } else if is_same_file(&source, &destination)? {
+            BundleAction::UpToDate
+        } else {
+            match overwrite {
+                OverwritePolicy::Never => BundleAction::KeptExisting,
+                OverwritePolicy::Always => BundleAction::Overwritten,
+                OverwritePolicy::IfDifferent => {
+                    if fs::read(&source)? == fs::read(&destination)? {
+                        BundleAction::UpToDate
+                    } else {
+                        BundleAction::Overwritten
+                    }
+                }
+            }
+        };
+
+        if !dry_run && matches!(action, BundleAction::Copied | BundleAction::Overwritten) {
+            fs::copy(&source, &destination)?;
+        }
+
+        manifest.entries.push(BundleEntry {
+            dllname: file.dllname,
+            source,
+            destination,
+            action,
+        });
+    }
+
+    Ok(manifest)
+}
+
+/// Format of a list of the files to deploy
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize)]
+pub enum DeploymentListFormat {
+    /// WiX (v4 or later) fragment with a `<Component>` per file, in a `ComponentGroup`
+    Wix,
+    /// NSIS `SetOutPath` and `File` instructions, to be included in an installer section
+    Nsis,
+    /// Windows batch script copying the files to the directory given as first argument
+    Cmd,
+    /// POSIX shell script copying the files to the directory given as first argument
+    Sh,
+}
+
+impl std::str::FromStr for DeploymentListFormat {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "wix" => Ok(Self::Wix),
+            "nsis" => Ok(Self::Nsis),
+            "cmd" => Ok(Self::Cmd),
+            "sh" => Ok(Self::Sh),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown deployment list format {s} (expected wix, nsis, cmd or sh)"
+            ))),
+        }
+    }
+}
+
+/// File to install, with its destination relative to the installation directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DeploymentFile {
+    pub dllname: String,
+    pub source: PathBuf,
+    pub destination: PathBuf,
+}
+
+/// Files to install for the scanned executables: the roots and their found dependencies which
+/// are neither system DLLs nor KnownDLLs, plus the ones included by the policy (see
+/// `Executables::deployment_closure`)
+///
+/// As for [`bundle`], the DLLs are installed next to the executable, wherever they were found.
+pub fn deployment_files(
+    executables: &Executables,
+    policy: &DeploymentPolicy,
+) -> Result<Vec<DeploymentFile>, LookupError> {
+    executables.deployment_closure(&DeploymentPolicy {
+        include_roots: true,
+        ..policy.clone()
+    })
+}
+
+/// List of the files to install for the scanned executables (see [`deployment_files`]), in the
+/// format of a packaging tool or as a copy script
+pub fn deployment_list(
+    executables: &Executables,
+    policy: &DeploymentPolicy,
+    format: DeploymentListFormat,
+) -> Result<String, LookupError> {
+    let files = deployment_files(executables, policy)?;
+    let mut out = String::new();
+    match format {
+        DeploymentListFormat::Wix => {
+            out.push_str("<?xml version=\"1.0\" encoding=\"utf-8\"?>\n");
+            out.push_str("<Wix xmlns=\"http://wixtoolset.org/schemas/v4/wxs\">\n");
+            out.push_str("  <Fragment>\n");
+            out.push_str(
+                "    <ComponentGroup Id=\"DependencyRunnerFiles\" Directory=\"INSTALLFOLDER\">\n",
+            );
+            for file in &files {
+                let subdirectory = file
+                    .destination
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                    .map(|dir| {
+                        format!(
+                            " Subdirectory=\"{}\"",
+                            xml_escape(&windows_relative_path(dir))
+                        )
+                    })
+                    .unwrap_or_default();
+                out.push_str(&format!("      <Component{subdirectory}>\n"));
+                out.push_str(&format!(
+                    "        <File Id=\"{}\" Source=\"{}\" />\n",
+                    wix_id(&file.destination),
+                    xml_escape(&readable_path(&file.source))
+                ));
+                out.push_str("      </Component>\n");
+            }
+            out.push_str("    </ComponentGroup>\n");
+            out.push_str("  </Fragment>\n");
+            out.push_str("</Wix>\n");
+        }
+        DeploymentListFormat::Nsis => {
+            let mut current_dir = None;
+            for file in &files {
+                let dir = file
+                    .destination
+                    .parent()
+                    .map(windows_relative_path)
+                    .unwrap_or_default();
+                if current_dir.as_ref() != Some(&dir) {
+                    let out_path = if dir.is_empty() {
+                        "$INSTDIR".to_owned()
+                    } else {
+                        format!("$INSTDIR\\{}", nsis_escape(&dir))
+                    };
+                    out.push_str(&format!("SetOutPath \"{out_path}\"\n"));
+                    current_dir = Some(dir);
+                }
+                out.push_str(&format!(
+                    "File \"{}\"\n",
+                    nsis_escape(&readable_path(&file.source))
+                ));
+            }
+        }
+        DeploymentListFormat::Cmd => {
+            out.push_str("@echo off\r\n");
+            out.push_str("if \"%~1\"==\"\" (echo usage: %~nx0 DESTINATION & exit /b 1)\r\n");
+            out.push_str("if not exist \"%~1\" mkdir \"%~1\" || exit /b 1\r\n");
+            let mut created = Vec::new();
+            for file in &files {
+                let destination = windows_relative_path(&file.destination);
+                if let Some(dir) = file
+                    .destination
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                {
+                    let dir = windows_relative_path(dir);
+                    if !created.contains(&dir) {
+                        out.push_str(&format!(
+                            "if not exist \"%~1\\{dir}\" mkdir \"%~1\\{dir}\" || exit /b 1\r\n"
+                        ));
+                        created.push(dir);
+                    }
+                }
+                out.push_str(&format!(
+                    "copy /Y \"{}\" \"%~1\\{destination}\" >nul || exit /b 1\r\n",
+                    readable_path(&file.source).replace('%', "%%")
+                ));
+            }
+        }
+        DeploymentListFormat::Sh => {
+            out.push_str("#!/bin/sh\n");
+            out.push_str("set -e\n");
+            out.push_str("dest=\"${1:?usage: $0 DESTINATION}\"\n");
+            out.push_str("mkdir -p \"$dest\"\n");
+            let mut created = Vec::new();
+            for file in &files {
+                let destination = posix_relative_path(&file.destination);
+                if let Some(dir) = file
+                    .destination
+                    .parent()
+                    .filter(|dir| !dir.as_os_str().is_empty())
+                {
+                    let dir = posix_relative_path(dir);
+                    if !created.contains(&dir) {
+                        out.push_str(&format!("mkdir -p \"$dest\"/{}\n", sh_quote(&dir)));
+                        created.push(dir);
+                    }
+                }
+                out.push_str(&format!(
+                    "cp {} \"$dest\"/{}\n",
+                    sh_quote(&readable_path(&file.source)),
+                    sh_quote(&destination)
+                ));
+            }
+        }
+    }
+    Ok(out)
+}
+
+fn windows_relative_path(p: &Path) -> String {
+    p.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("\\")
+}
+
+fn posix_relative_path(p: &Path) -> String {
+    p.components()
+        .map(|c| c.as_os_str().to_string_lossy())
+        .collect::<Vec<_>>()
+        .join("/")
+}
+
+/// Identifier of a WiX element: letters, digits, underscores and dots, starting with a letter or
+/// an underscore, at most 72 characters
+///
+/// The replaced characters and the truncation can make the paths of two files collide, so a hash
+/// of the whole path is appended.
+fn wix_id(destination: &Path) -> String {
+    let path = posix_relative_path(destination);
+    // FNV-1a, case-insensitive as the paths on Windows
+    let hash = path.to_lowercase().bytes().fold(0x811c_9dc5u32, |h, b| {
+        (h ^ b as u32).wrapping_mul(0x0100_0193)
+    });
+    let id: String = path
+        .chars()
+        .map(|c| {
+            if c.is_ascii_alphanumeric() || c == '.' {
+                c
+            } else {
+                '_'
+            }
+        })
+        .collect();
+    // keep the end, which tells the files of the same directory apart
+    let id = &id[id.len().saturating_sub(60)..];
+    format!("dr_{id}_{hash:08x}")
+}
+
+/// Escape the characters with a special meaning in NSIS strings
+fn nsis_escape(s: &str) -> String {
+    s.replace('$', "$$").replace('"', "$\\\"")
+}
+
+/// Single-quoted POSIX shell word
+fn sh_quote(s: &str) -> String {
+    format!("'{}'", s.replace('\'', "'\\''"))
+}
+
+fn is_same_file(a: &Path, b: &Path) -> Result<bool, LookupError> {
+    Ok(fs::canonicalize(a)? == fs::canonicalize(b)?)
+}
+
+#[cfg(test)]
+mod tests {
+    use fs_err as fs;
+
+    use crate::bundle::{
+        bundle, deployment_files, deployment_list, wix_id, BundleAction, DeploymentListFormat,
+        DeploymentPolicy, OverwritePolicy,
+    };
+    use crate::common::{LookupError, NamePatterns};
+    use crate::executable::Executables;
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+
+    #[test]
+    fn bundle_closure() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+
+        let policy = DeploymentPolicy::default();
+        let temp_dir = tempfile::tempdir()?;
+        let output_dir = temp_dir.path().join("bundle");
+
+        let dry_run = bundle(
+            &exes,
+            &output_dir,
+            &policy,
+            OverwritePolicy::IfDifferent,
+            true,
+        )?;
+        assert!(!output_dir.exists());
+        let lib = dry_run
+            .entries
+            .iter()
+            .find(|e| e.dllname == "DepRunTestLib.dll")
+            .unwrap();
+        assert_eq!(lib.action, BundleAction::Copied);
+        assert!(dry_run
+            .entries
+            .iter()
+            .all(|e| e.dllname != "DepRunTest.exe"));
+
+        let copied = bundle(
+            &exes,
+            &output_dir,
+            &policy,
+            OverwritePolicy::IfDifferent,
+            false,
+        )?;
+        assert!(output_dir.join("DepRunTestLib.dll").exists());
+        assert_eq!(copied.entries.len(), dry_run.entries.len());
+
+        let again = bundle(
+            &exes,
+            &output_dir,
+            &policy,
+            OverwritePolicy::IfDifferent,
+            false,
+        )?;
+        assert!(again
+            .entries
+            .iter()
+            .all(|e| e.action == BundleAction::UpToDate));
+
+        fs::write(output_dir.join("DepRunTestLib.dll"), b"stale")?;
+        let kept = bundle(&exes, &output_dir, &policy, OverwritePolicy::Never, false)?;
+        assert!(kept
+            .entries
+            .iter()
+            .any(|e| e.action == BundleAction::KeptExisting));
+        Ok(())
+    }
+
+    #[test]
+    fn deployment_lists() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build");
+        let mut query = LookupQuery::deduce_from_executable_location(
+            build_dir.join("DepRunTest/Debug/DepRunTest.exe"),
+        )?;
+        query.target.user_path = vec![build_dir.join("DepRunTestLib/Debug")];
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+
+        let policy = DeploymentPolicy::default();
+        // the DLL found on the user path is installed next to the executable
+        let files = deployment_files(&exes, &policy)?;
+        let destinations: Vec<_> = files.iter().map(|f| f.destination.clone()).collect();
+        assert_eq!(
+            destinations,
+            vec![
+                std::path::PathBuf::from("DepRunTest.exe"),
+                std::path::PathBuf::from("DepRunTestLib.dll")
+            ]
+        );
+        assert!(files[1]
+            .source
+            .ends_with("DepRunTestLib/Debug/DepRunTestLib.dll"));
+
+        let wix = deployment_list(&exes, &policy, DeploymentListFormat::Wix)?;
+        assert!(wix.contains("<ComponentGroup Id=\"DependencyRunnerFiles\""));
+        assert!(wix.contains("<File Id=\"dr_DepRunTestLib.dll_"));
+        assert_eq!(wix.matches("<Component>").count(), 2);
+        assert!(roxmltree::Document::parse(&wix).is_ok());
+
+        let nsis = deployment_list(&exes, &policy, DeploymentListFormat::Nsis)?;
+        assert_eq!(nsis.lines().next(), Some("SetOutPath \"$INSTDIR\""));
+        assert_eq!(nsis.lines().filter(|l| l.starts_with("File \"")).count(), 2);
+
+        let sh = deployment_list(&exes, &policy, DeploymentListFormat::Sh)?;
+        assert!(sh.lines().any(|l| l.starts_with("cp '")
+            && l.ends_with("DepRunTestLib.dll' \"$dest\"/'DepRunTestLib.dll'")));
+        let cmd = deployment_list(&exes, &policy, DeploymentListFormat::Cmd)?;
+        assert_eq!(cmd.matches("copy /Y").count(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn deployment_closure_policy() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+        let names = |policy: &DeploymentPolicy| -> Result<Vec<String>, LookupError> {
+            Ok(exes
+                .deployment_closure(policy)?
+                .into_iter()
+                .map(|f| f.dllname)
+                .collect())
+        };
+
+        let mut policy = DeploymentPolicy::default();
+        assert_eq!(names(&policy)?, vec!["DepRunTestLib.dll"]);
+        policy.include_roots = true;
+        assert_eq!(names(&policy)?, vec!["DepRunTest.exe", "DepRunTestLib.dll"]);
+
+        policy.exclude = NamePatterns::new(&["deprunTESTlib.dll"])?;
+        assert_eq!(names(&policy)?, vec!["DepRunTest.exe"]);
+
+        // system DLLs are shipped only if included
+        let exes: Executables = exes
+            .into_iter()
+            .map(|mut e| {
+                if e.dllname == "DepRunTestLib.dll" {
+                    e.details.as_mut().unwrap().is_system = true;
+                }
+                e
+            })
+            .collect();
+        let policy = DeploymentPolicy::default();
+        assert!(exes.deployment_closure(&policy)?.is_empty());
+        let policy = DeploymentPolicy {
+            include: NamePatterns::new(&["deprun*.dll"])?,
+            ..policy
+        };
+        let files = exes.deployment_closure(&policy)?;
+        assert_eq!(files.len(), 1);
+        assert_eq!(
+            files[0].destination,
+            std::path::PathBuf::from("DepRunTestLib.dll")
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn deployment_destinations() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+
+        // application with its DLL in a subdirectory on the PATH, and a helper executable
+        let temp_dir = tempfile::tempdir()?;
+        let app_dir = fs::canonicalize(temp_dir.path())?;
+        fs::create_dir_all(app_dir.join("lib"))?;
+        fs::create_dir_all(app_dir.join("plugins"))?;
+        fs::copy(
+            build_dir.join("DepRunTest.exe"),
+            app_dir.join("DepRunTest.exe"),
+        )?;
+        fs::copy(
+            build_dir.join("DepRunTestLib.dll"),
+            app_dir.join("lib/DepRunTestLib.dll"),
+        )?;
+        fs::copy(
+            build_dir.join("DepRunTest.exe"),
+            app_dir.join("plugins/Plugin.exe"),
+        )?;
+        let mut query =
+            LookupQuery::deduce_from_executable_location(app_dir.join("DepRunTest.exe"))?;
+        query.target.user_path = vec![app_dir.join("lib")];
+        query.add_target(app_dir.join("plugins/Plugin.exe"));
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+
+        // the helper keeps its location, the DLL is installed next to the executable
+        let files = deployment_files(&exes, &DeploymentPolicy::default())?;
+        let destinations: Vec<_> = files.iter().map(|f| f.destination.clone()).collect();
+        assert_eq!(
+            destinations,
+            vec![
+                std::path::PathBuf::from("DepRunTest.exe"),
+                std::path::PathBuf::from("plugins/Plugin.exe"),
+                std::path::PathBuf::from("DepRunTestLib.dll"),
+            ]
+        );
+
+        assert_ne!(
+            wix_id(std::path::Path::new("a-b.dll")),
+            wix_id(std::path::Path::new("a_b.dll"))
+        );
+        let long = |c: char| std::path::PathBuf::from(format!("{c}{}.dll", "x".repeat(80)));
+        assert_ne!(wix_id(&long('a')), wix_id(&long('b')));
+        assert!(wix_id(&long('a')).len() <= 72);
+        Ok(())
+    }
+}
@@ -0,0 +1,349 @@
+//! C API, to embed the scanner in applications written in other languages
+//!
+//! Built with the `capi` feature, which also generates the `dependency_runner.h` header in the
+//! output directory of the build script.
+//! A scan either returns the whole result as a JSON document (`dr_scan`, the same document written
+//! by `deprun --format json`), or a handle whose executables are read through accessors
+//! (`dr_scan_open`).
+//!
+//! The strings returned by the library are owned by the caller and must be released with
+//! `dr_string_free`. Functions failing return NULL (or a negative value), and the reason can be
+//! read with `dr_last_error`.
+
+use std::cell::RefCell;
+use std::ffi::{c_char, CStr, CString};
+use std::panic::{catch_unwind, AssertUnwindSafe};
+use std::path::PathBuf;
+use std::ptr;
+
+use crate::common::LookupError;
+use crate::executable::{Executable, Executables};
+use crate::path::LookupPath;
+use crate::query::LookupQuery;
+use crate::runner::run;
+
+/// Parameters of a scan
+///
+/// A NULL pointer in place of the options selects the default ones (see `dr_options_default`).
+#[repr(C)]
+#[derive(Debug, Clone, Copy)]
+pub struct DrScanOptions {
+    /// maximum depth of the scan, or a negative value for no limit
+    pub max_depth: i32,
+    /// do not look up the dependencies of the system DLLs
+    pub skip_system_dlls: bool,
+    /// extract the imported and exported symbols
+    pub extract_symbols: bool,
+    /// compute the SHA-256 and MD5 hashes of the found files
+    pub compute_hashes: bool,
+    /// directories searched after the system ones, separated by ';' (NULL for none)
+    pub user_path: *const c_char,
+    /// working directory of the simulated process (NULL for the directory of the target)
+    pub working_dir: *const c_char,
+}
+
+impl Default for DrScanOptions {
+    fn default() -> Self {
+        Self {
+            max_depth: -1,
+            skip_system_dlls: false,
+            extract_symbols: false,
+            compute_hashes: false,
+            user_path: ptr::null(),
+            working_dir: ptr::null(),
+        }
+    }
+}
+
+/// Result of a scan, read through the `dr_scan_*` accessors and released with `dr_scan_free`
+pub struct DrScan {
+    /// executables in order of first appearance, indexed by the accessors
+    executables: Vec<Executable>,
+}
+
+thread_local! {
+    static LAST_ERROR: RefCell<Option<CString>> = const { RefCell::new(None) };
+}
+
+fn set_last_error(message: String) {
+    let message = CString::new(message.replace('\0', " ")).unwrap_or_default();
+    LAST_ERROR.with(|e| *e.borrow_mut() = Some(message));
+}
+
+/// Run `f`, recording its error (or panic, which must not unwind into the caller) as the last one
+fn guarded<T>(f: impl FnOnce() -> Result<T, LookupError>) -> Option<T> {
+    match catch_unwind(AssertUnwindSafe(f)) {
+        Ok(Ok(value)) => Some(value),
+        Ok(Err(e)) => {
+            set_last_error(e.to_string());
+            None
+        }
+        Err(_) => {
+            set_last_error("internal error".to_owned());
+            None
+        }
+    }
+}
+
+fn into_c_string(s: &str) -> *mut c_char {
+    CString::new(s.replace('\0', " ")).map_or(ptr::null_mut(), CString::into_raw)
+}
+
+/// # Safety
+/// `s` must be NULL or a valid NUL-terminated string
+unsafe fn read_c_string(s: *const c_char) -> Result<Option<String>, LookupError> {
+    if s.is_null() {
+        return Ok(None);
+    }
+    CStr::from_ptr(s)
+        .to_str()
+        .map(|s| Some(s.to_owned()))
+        .map_err(|_| LookupError::ScanError("string argument is not valid UTF-8".to_owned()))
+}
+
+/// # Safety
+/// `path` and the strings of `options` must be NULL or valid NUL-terminated strings, `options`
+/// NULL or a valid pointer
+unsafe fn scan_executables(
+    path: *const c_char,
+    options: *const DrScanOptions,
+) -> Result<Executables, LookupError> {
+    let path = read_c_string(path)?
+        .ok_or_else(|| LookupError::ScanError("no executable given".to_owned()))?;
+    let options = options.as_ref().copied().unwrap_or_default();
+    let mut query = LookupQuery::deduce_from_executable_location(&path)?;
+    query.parameters.max_depth = usize::try_from(options.max_depth).ok();
+    query.parameters.skip_system_dlls = options.skip_system_dlls;
+    query.parameters.extract_symbols = options.extract_symbols;
+    query.parameters.compute_hashes = options.compute_hashes;
+    if let Some(user_path) = read_c_string(options.user_path)? {
+        query.target.user_path = user_path
+            .split(';')
+            .filter(|d| !d.is_empty())
+            .map(PathBuf::from)
+            .collect();
+    }
+    if let Some(working_dir) = read_c_string(options.working_dir)? {
+        query.target.working_dir = PathBuf::from(working_dir);
+    }
+    let lookup_path = LookupPath::deduce(&query);
+    run(&query, &lookup_path)
+}
+
+/// Default parameters of a scan
+#[no_mangle]
+pub extern "C" fn dr_options_default() -> DrScanOptions {
+    DrScanOptions::default()
+}
+
+/// Scan the dependencies of an executable, returning the result as a JSON document (NULL on error)
+///
+/// # Safety
+/// `path` must be a valid NUL-terminated UTF-8 string; `options` must be NULL or point to valid
+/// options
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan(
+    path: *const c_char,
+    options: *const DrScanOptions,
+) -> *mut c_char {
+    guarded(|| {
+        let executables = scan_executables(path, options)?;
+        serde_json::to_string(&executables)
+            .map_err(|e| LookupError::ScanError(format!("could not serialize the result: {e}")))
+    })
+    .map_or(ptr::null_mut(), |json| into_c_string(&json))
+}
+
+/// Scan the dependencies of an executable, returning a handle to the result (NULL on error)
+///
+/// # Safety
+/// `path` must be a valid NUL-terminated UTF-8 string; `options` must be NULL or point to valid
+/// options
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_open(
+    path: *const c_char,
+    options: *const DrScanOptions,
+) -> *mut DrScan {
+    guarded(|| {
+        let executables = scan_executables(path, options)?.into_iter().collect();
+        Ok(Box::new(DrScan { executables }))
+    })
+    .map_or(ptr::null_mut(), Box::into_raw)
+}
+
+/// Release the result of a scan
+///
+/// # Safety
+/// `scan` must be NULL or a handle returned by `dr_scan_open`, not released yet
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_free(scan: *mut DrScan) {
+    if !scan.is_null() {
+        drop(Box::from_raw(scan));
+    }
+}
+
+/// # Safety
+/// `scan` must be NULL or a valid handle
+unsafe fn executable_at<'a>(scan: *const DrScan, index: usize) -> Option<&'a Executable> {
+    let executable = scan.as_ref().and_then(|s| s.executables.get(index));
+    if executable.is_none() {
+        set_last_error(format!("no executable at index {index}"));
+    }
+    executable
+}
+
+/// Number of executables found by the scan (the targets and their dependencies, found or not)
+///
+/// # Safety
+/// `scan` must be NULL or a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_count(scan: *const DrScan) -> usize {
+    scan.as_ref().map_or(0, |s| s.executables.len())
+}
+
+/// Name of an executable of the scan, in order of first appearance (NULL if out of range)
+///
+/// # Safety
+/// `scan` must be NULL or a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_name(scan: *const DrScan, index: usize) -> *mut c_char {
+    executable_at(scan, index).map_or(ptr::null_mut(), |e| into_c_string(&e.dllname))
+}
+
+/// Path an executable of the scan was found at (NULL if not found, resolved without a file, or
+/// out of range)
+///
+/// # Safety
+/// `scan` must be NULL or a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_path(scan: *const DrScan, index: usize) -> *mut c_char {
+    executable_at(scan, index)
+        .and_then(|e| e.details.as_ref()?.full_path.as_deref())
+        .map_or(ptr::null_mut(), |p| into_c_string(&p.to_string_lossy()))
+}
+
+/// Whether an executable of the scan was found: 1 if found, 0 if missing, -1 if out of range
+///
+/// # Safety
+/// `scan` must be NULL or a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_found(scan: *const DrScan, index: usize) -> i32 {
+    executable_at(scan, index).map_or(-1, |e| i32::from(e.found))
+}
+
+/// Whether an executable of the scan was found in a system directory: 1 if so, 0 if not (or not
+/// found), -1 if out of range
+///
+/// # Safety
+/// `scan` must be NULL or a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_is_system(scan: *const DrScan, index: usize) -> i32 {
+    executable_at(scan, index).map_or(-1, |e| {
+        i32::from(e.details.as_ref().is_some_and(|d| d.is_system))
+    })
+}
+
+/// Minimum depth an executable of the scan appears at (0 for the targets), -1 if out of range
+///
+/// # Safety
+/// `scan` must be NULL or a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_depth(scan: *const DrScan, index: usize) -> i64 {
+    executable_at(scan, index).map_or(-1, |e| {
+        i64::try_from(e.depth_first_appearance).unwrap_or(i64::MAX)
+    })
+}
+
+/// Names of the dependencies of an executable of the scan, separated by ';' (NULL if they were
+/// not scanned or out of range)
+///
+/// # Safety
+/// `scan` must be NULL or a valid handle
+#[no_mangle]
+pub unsafe extern "C" fn dr_scan_dependencies(scan: *const DrScan, index: usize) -> *mut c_char {
+    executable_at(scan, index)
+        .and_then(|e| e.details.as_ref())
+        .and_then(|d| d.dependencies.as_ref())
+        .map_or(ptr::null_mut(), |deps| into_c_string(&deps.join(";")))
+}
+
+/// Message of the last error of the calling thread (NULL if none), to be released with
+/// `dr_string_free`
+#[no_mangle]
+pub extern "C" fn dr_last_error() -> *mut c_char {
+    LAST_ERROR.with(|e| {
+        e.borrow()
+            .as_ref()
+            .map_or(ptr::null_mut(), |m| m.clone().into_raw())
+    })
+}
+
+/// Release a string returned by the library
+///
+/// # Safety
+/// `s` must be NULL or a string returned by the library, not released yet
+#[no_mangle]
+pub unsafe extern "C" fn dr_string_free(s: *mut c_char) {
+    if !s.is_null() {
+        drop(CString::from_raw(s));
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::ffi::{CStr, CString};
+    use std::path::PathBuf;
+    use std::ptr;
+
+    use crate::capi::*;
+    use crate::common::LookupError;
+    use crate::executable::Executables;
+
+    /// Take ownership of a string returned by the library
+    unsafe fn take_string(s: *mut c_char) -> Option<String> {
+        if s.is_null() {
+            return None;
+        }
+        let owned = CStr::from_ptr(s).to_str().unwrap().to_owned();
+        dr_string_free(s);
+        Some(owned)
+    }
+
+    #[test]
+    fn scan_through_c_api() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let path = CString::new(exe_path.to_str().unwrap()).unwrap();
+        let mut options = dr_options_default();
+        options.skip_system_dlls = true;
+
+        unsafe {
+            let json = take_string(dr_scan(path.as_ptr(), &options)).unwrap();
+            let executables = Executables::from_json(&json)?;
+            assert!(executables.get("DepRunTestLib.dll").unwrap().found);
+
+            let scan = dr_scan_open(path.as_ptr(), ptr::null());
+            assert!(!scan.is_null());
+            assert_eq!(dr_scan_count(scan), executables.len());
+            assert_eq!(
+                take_string(dr_scan_name(scan, 0)).as_deref(),
+                Some("DepRunTest.exe")
+            );
+            assert_eq!(dr_scan_depth(scan, 0), 0);
+            assert_eq!(dr_scan_found(scan, 0), 1);
+            assert!(take_string(dr_scan_dependencies(scan, 0))
+                .unwrap()
+                .split(';')
+                .any(|d| d == "DepRunTestLib.dll"));
+            assert_eq!(dr_scan_found(scan, dr_scan_count(scan)), -1);
+            assert!(take_string(dr_last_error()).is_some());
+            dr_scan_free(scan);
+
+            let missing = CString::new("no/such/file.exe").unwrap();
+            assert!(dr_scan(missing.as_ptr(), ptr::null()).is_null());
+            assert!(take_string(dr_last_error()).is_some());
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,346 @@
+//! Enumeration of the runtime targets (executables and DLLs) of a CMake build directory
+//!
+//! The targets are read from the reply of the CMake file API
+//! (https://cmake.org/cmake/help/latest/manual/cmake-file-api.7.html). If the build directory was
+//! configured without requesting it, the executables found in the runtime output directory
+//! recorded in CMakeCache.txt are listed instead.
+
+use std::collections::HashMap;
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{host_path, LookupError, NamePatterns};
+use crate::directory::{find_executables, scan_executables, DirectoryScan, DirectoryScanOptions};
+use crate::query::LookupQuery;
+use crate::session::ScanSession;
+
+/// Kind of a CMake runtime target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum CMakeTargetKind {
+    Executable,
+    SharedLibrary,
+}
+
+/// Executable or DLL produced by a CMake build directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct CMakeTarget {
+    pub name: String,
+    pub kind: CMakeTargetKind,
+    /// configuration (Debug, Release, ...), if known
+    pub configuration: Option<String>,
+    /// path of the produced file
+    pub artifact: PathBuf,
+}
+
+impl CMakeTarget {
+    /// Query for the scan of the produced file, deduced from its location
+    pub fn lookup_query(&self) -> Result<LookupQuery, LookupError> {
+        LookupQuery::deduce_from_executable_location(&self.artifact)
+    }
+}
+
+#[derive(Deserialize)]
+struct FileApiIndex {
+    objects: Vec<FileApiObject>,
+}
+
+#[derive(Deserialize)]
+struct FileApiObject {
+    kind: String,
+    #[serde(rename = "jsonFile")]
+    json_file: String,
+}
+
+#[derive(Deserialize)]
+struct Codemodel {
+    configurations: Vec<CodemodelConfiguration>,
+}
+
+#[derive(Deserialize)]
+struct CodemodelConfiguration {
+    name: String,
+    targets: Vec<CodemodelTarget>,
+}
+
+#[derive(Deserialize)]
+struct CodemodelTarget {
+    #[serde(rename = "jsonFile")]
+    json_file: String,
+}
+
+#[derive(Deserialize)]
+struct FileApiTarget {
+    name: String,
+    #[serde(rename = "type")]
+    target_type: String,
+    #[serde(rename = "nameOnDisk")]
+    name_on_disk: Option<String>,
+    #[serde(default)]
+    artifacts: Vec<FileApiArtifact>,
+}
+
+#[derive(Deserialize)]
+struct FileApiArtifact {
+    /// as written by CMake on Windows, e.g. C:/build/bin/Debug/app.exe or bin/Debug/app.exe
+    path: String,
+}
+
+fn file_api_dir<P: AsRef<Path>>(build_dir: P) -> PathBuf {
+    build_dir.as_ref().join(".cmake").join("api").join("v1")
+}
+
+/// Ask CMake to write the code model of the build directory at the next configuration
+///
+/// After running CMake again on the build directory, `read_build_dir` finds all the targets.
+pub fn request_file_api_reply<P: AsRef<Path>>(build_dir: P) -> Result<(), LookupError> {
+    let query_dir = file_api_dir(build_dir).join("query");
+    fs::create_dir_all(&query_dir)?;
+    fs::write(query_dir.join("codemodel-v2"), "")?;
+    Ok(())
+}
+
+/// Runtime targets of the build directory, read from the reply of the CMake file API
+///
+/// Returns None if the build directory contains no reply.
+pub fn read_file_api_reply<P: AsRef<Path>>(
+    build_dir: P,
+) -> Result<Option<Vec<CMakeTarget>>, LookupError> {
+    let build_dir = build_dir.as_ref();
+    let reply_dir = file_api_dir(build_dir).join("reply");
+    if !reply_dir.is_dir() {
+        return Ok(None);
+    }
+    // the name of the index files contains a timestamp: the last one is the current one
+    let index_path = fs::read_dir(&reply_dir)?
+        .filter_map(Result::ok)
+        .map(|e| e.path())
+        .filter(|p| {
+            p.file_name()
+                .and_then(|n| n.to_str())
+                .map(|n| n.starts_with("index-") && n.ends_with(".json"))
+                .unwrap_or(false)
+        })
+        .max();
+    let index_path = match index_path {
+        Some(index_path) => index_path,
+        None => return Ok(None),
+    };
+    let index: FileApiIndex = serde_json::from_str(&fs::read_to_string(index_path)?)?;
+    let codemodel_file = match index.objects.iter().find(|o| o.kind == "codemodel") {
+        Some(object) => &object.json_file,
+        None => return Ok(None),
+    };
+    let codemodel: Codemodel =
+        serde_json::from_str(&fs::read_to_string(reply_dir.join(codemodel_file))?)?;
+
+    let mut targets = Vec::new();
+    for configuration in codemodel.configurations {
+        for target in configuration.targets {
+            let target: FileApiTarget =
+                serde_json::from_str(&fs::read_to_string(reply_dir.join(&target.json_file))?)?;
+            let kind = match target.target_type.as_str() {
+                "EXECUTABLE" => CMakeTargetKind::Executable,
+                "SHARED_LIBRARY" | "MODULE_LIBRARY" => CMakeTargetKind::SharedLibrary,
+                _ => continue,
+            };
+            // the artifacts of a DLL also include its import library and debug symbols
+            let artifact = target
+                .artifacts
+                .iter()
+                .find(|a| a.path.rsplit(['/', '\\']).next() == target.name_on_disk.as_deref())
+                .or_else(|| target.artifacts.first());
+            if let Some(artifact) = artifact {
+                targets.push(CMakeTarget {
+                    name: target.name,
+                    kind,
+                    configuration: Some(configuration.name.clone()),
+                    // relative paths are relative to the build directory
+                    artifact: host_path(&artifact.path, build_dir),
+                });
+            }
+        }
+    }
+    Ok(Some(targets))
+}
+
+/// Entries of a CMakeCache.txt file (KEY:TYPE=VALUE lines)
+fn read_cmake_cache<P: AsRef<Path>>(build_dir: P) -> Result<HashMap<String, String>, LookupError> {
+    let cache_path = build_dir.as_ref().join("CMakeCache.txt");
+    if !cache_path.is_file() {
+        return Err(LookupError::ContextDeductionError(format!(
+            "{} is not a CMake build directory (no CMakeCache.txt found)",
+            build_dir.as_ref().display()
+        )));
+    }
+    Ok(fs::read_to_string(cache_path)?
+        .lines()
+        .filter(|l| !l.starts_with('#') && !l.starts_with("//"))
+        .filter_map(|l| {
+            let (key, value) = l.split_once('=')?;
+            let key = key.split(':').next()?;
+            Some((key.to_owned(), value.to_owned()))
+        })
+        .collect())
+}
+
+/// Runtime targets of a CMake build directory
+///
+/// Read from the reply of the CMake file API if available (see `request_file_api_reply`);
+/// otherwise, the .exe and .dll files found in the runtime output directory set in CMakeCache.txt
+/// (or in the whole build directory) are returned, without configuration.
+pub fn read_build_dir<P: AsRef<Path>>(build_dir: P) -> Result<Vec<CMakeTarget>, LookupError> {
+    let build_dir = fs::canonicalize(build_dir.as_ref())?;
+    if let Some(targets) = read_file_api_reply(&build_dir)? {
+        return Ok(targets);
+    }
+    let cache = read_cmake_cache(&build_dir)?;
+    let output_dir = cache
+        .get("CMAKE_RUNTIME_OUTPUT_DIRECTORY")
+        .filter(|d| !d.is_empty())
+        .map(|d| host_path(d, &build_dir))
+        .unwrap_or_else(|| build_dir.clone());
+    let options = DirectoryScanOptions {
+        recursive: true,
+        max_depth: None,
+        include: NamePatterns::default(),
+        exclude: NamePatterns::default(),
+    };
+    Ok(find_executables(output_dir, &options)?
+        .into_iter()
+        .map(|artifact| {
+            let is_dll = artifact
+                .extension()
+                .map(|e| e.eq_ignore_ascii_case("dll"))
+                .unwrap_or(false);
+            CMakeTarget {
+                name: artifact
+                    .file_stem()
+                    .map(|n| n.to_string_lossy().into_owned())
+                    .unwrap_or_default(),
+                kind: if is_dll {
+                    CMakeTargetKind::SharedLibrary
+                } else {
+                    CMakeTargetKind::Executable
+                },
+                configuration: None,
+                artifact,
+            }
+        })
+        .collect())
+}
+
+/// Scan and check the runtime targets of a CMake build directory that exist on disk
+///
+/// If a configuration is given, only its targets are scanned. As for `scan_directory`, the query
+/// of each target is passed to `configure` for adjustments.
+pub fn scan_build_dir<P: AsRef<Path>, F: Fn(&mut LookupQuery)>(
+    build_dir: P,
+    configuration: Option<&str>,
+    configure: F,
+) -> Result<DirectoryScan, LookupError> {
+    let build_dir = fs::canonicalize(build_dir.as_ref())?;
+    let artifacts = read_build_dir(&build_dir)?
+        .into_iter()
+        .filter(|t| {
+            configuration.is_none_or(|c| {
+                t.configuration
+                    .as_deref()
+                    .is_none_or(|tc| tc.eq_ignore_ascii_case(c))
+            })
+        })
+        .map(|t| t.artifact)
+        .filter(|a| a.is_file())
+        .collect();
+    Ok(scan_executables(
+        ScanSession::new(),
+        build_dir,
+        artifacts,
+        configure,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::cmake::{read_build_dir, scan_build_dir, CMakeTargetKind};
+    use crate::common::LookupError;
+    use fs_err as fs;
+
+    #[test]
+    fn file_api_reply() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build-same-output");
+
+        let mut targets = read_build_dir(&build_dir)?;
+        targets.sort_by(|a, b| a.name.cmp(&b.name));
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].name, "DepRunTest");
+        assert_eq!(targets[0].kind, CMakeTargetKind::Executable);
+        assert_eq!(targets[0].configuration.as_deref(), Some("Debug"));
+        assert!(targets[0].artifact.ends_with("bin/Debug/DepRunTest.exe"));
+        assert_eq!(targets[1].kind, CMakeTargetKind::SharedLibrary);
+        assert!(targets[1].artifact.ends_with("bin/Debug/DepRunTestLib.dll"));
+
+        let query = targets[0].lookup_query()?;
+        assert_eq!(query.target.target_exe, targets[0].artifact);
+
+        // the DLL is found next to the executable
+        let scan = scan_build_dir(&build_dir, Some("debug"), |_| {})?;
+        assert_eq!(scan.targets.len(), 2);
+        assert!(!scan.summary().missing_dlls.contains("DepRunTestLib.dll"));
+        assert!(scan_build_dir(&build_dir, Some("Release"), |_| {})?
+            .targets
+            .is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn file_api_reply_absolute_paths() -> Result<(), LookupError> {
+        // build directory configured at C:\src\build, and copied elsewhere
+        let temp_dir = tempfile::tempdir()?;
+        let build_dir = fs::canonicalize(temp_dir.path())?.join("src").join("build");
+        let reply_dir = build_dir.join(".cmake/api/v1/reply");
+        fs::create_dir_all(&reply_dir)?;
+        fs::create_dir_all(build_dir.join("bin"))?;
+        fs::write(build_dir.join("bin/App.exe"), b"")?;
+        fs::write(
+            reply_dir.join("index-1.json"),
+            r#"{ "objects": [ { "kind": "codemodel", "jsonFile": "codemodel.json" } ] }"#,
+        )?;
+        fs::write(
+            reply_dir.join("codemodel.json"),
+            r#"{ "configurations": [ { "name": "Debug", "targets": [ { "jsonFile": "app.json" } ] } ] }"#,
+        )?;
+        fs::write(
+            reply_dir.join("app.json"),
+            r#"{ "name": "App", "nameOnDisk": "App.exe", "type": "EXECUTABLE",
+                 "artifacts": [ { "path": "C:/src/build/bin/App.pdb" }, { "path": "C:/src/build/bin/App.exe" } ] }"#,
+        )?;
+
+        let targets = read_build_dir(&build_dir)?;
+        assert_eq!(targets.len(), 1);
+        #[cfg(not(windows))]
+        assert_eq!(targets[0].artifact, build_dir.join("bin/App.exe"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn cmake_cache() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build");
+
+        let targets = read_build_dir(&build_dir)?;
+        assert_eq!(targets.len(), 2);
+        assert_eq!(targets[0].name, "DepRunTest");
+        assert_eq!(targets[0].kind, CMakeTargetKind::Executable);
+        assert_eq!(targets[1].kind, CMakeTargetKind::SharedLibrary);
+        assert!(targets.iter().all(|t| t.configuration.is_none()));
+
+        assert!(read_build_dir(d.join("test_data/vcx")).is_err());
+
+        Ok(())
+    }
+}
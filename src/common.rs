@@ -2,9 +2,16 @@
 //! path manipulation.
 
 use fs_err as fs;
-use std::path::Path;
+use serde::{Deserialize, Serialize};
+use std::path::{Path, PathBuf};
 use thiserror::Error;
 
+/// Clock measuring the phases of a scan (the one of std panics on wasm32-unknown-unknown)
+#[cfg(not(target_family = "wasm"))]
+pub(crate) use std::time::Instant;
+#[cfg(target_family = "wasm")]
+pub(crate) use web_time::Instant;
+
 #[derive(Error, Debug)]
 pub enum LookupError {
     #[error("File system access error while scanning: {}", .0)]
@@ -19,12 +26,52 @@ pub enum LookupError {
     #[error("Lookup context building error: {}", .0)]
     ContextDeductionError(String),
 
+    #[error("Invalid lookup path: {}", .0)]
+    LookupPathError(String),
+
+    #[error("Archive read error: {}", .0)]
+    ArchiveError(String),
+
+    #[error("Windows registry read error: {}", .0)]
+    RegistryError(String),
+
     #[error("Could not demangle symbol")]
     DemanglingError(String),
 
+    #[deprecated(note = "not returned anymore, see `WrongFileFormat`")]
     #[error("The target file is not a 64-bit DLL")]
     WrongFileFormatError(pelite::Error),
 
+    #[error("{} is not a PE executable or DLL: it looks like {}", .path.display(), .detected)]
+    WrongFileFormat {
+        path: PathBuf,
+        detected: crate::pe::FileFormat,
+    },
+
+    #[error("Found two DLLs named {}: {} and {}", .dllname, .first, .second)]
+    DuplicateName {
+        dllname: String,
+        first: String,
+        second: String,
+    },
+
+    #[error("Error {} {}: {}", .operation, .path.display(), .source)]
+    FileError {
+        path: PathBuf,
+        operation: FileOperation,
+        source: Box<LookupError>,
+    },
+
+    #[error("Could not scan {}{}: {}", .dllname, dependency_context(.importer, .entry), .source)]
+    DependencyError {
+        dllname: String,
+        /// executable importing the DLL, if it is not a root of the scan
+        importer: Option<String>,
+        /// lookup path entry the DLL was found in, if it was found
+        entry: Option<String>,
+        source: Box<LookupError>,
+    },
+
     #[error(transparent)]
     VarError(#[from] std::env::VarError),
     #[error(transparent)]
@@ -38,12 +85,242 @@ pub enum LookupError {
     #[error(transparent)]
     Utf8Error(#[from] std::str::Utf8Error),
     #[error(transparent)]
+    JsonError(#[from] serde_json::Error),
+    #[error(transparent)]
+    PatternError(#[from] glob::PatternError),
+    #[cfg(feature = "notify")]
+    #[error(transparent)]
+    WatchError(#[from] notify::Error),
+    #[error(transparent)]
     InternalError(#[from] anyhow::Error),
 }
 
+impl LookupError {
+    /// Attach the file and the operation that failed, unless the error already names a file
+    pub fn in_file<P: AsRef<Path>>(self, path: P, operation: FileOperation) -> Self {
+        if self.path().is_some() {
+            return self;
+        }
+        Self::FileError {
+            path: path.as_ref().to_owned(),
+            operation,
+            source: Box::new(self),
+        }
+    }
+
+    /// Attach the DLL whose scan failed, unless the error already names one
+    pub fn in_dependency(
+        self,
+        dllname: &str,
+        importer: Option<&str>,
+        entry: Option<String>,
+    ) -> Self {
+        if self.dllname().is_some() {
+            return self;
+        }
+        Self::DependencyError {
+            dllname: dllname.to_owned(),
+            importer: importer.map(str::to_owned),
+            entry,
+            source: Box::new(self),
+        }
+    }
+
+    /// File the error refers to, if known
+    pub fn path(&self) -> Option<&Path> {
+        match self {
+            Self::FileError { path, .. } | Self::WrongFileFormat { path, .. } => Some(path),
+            Self::DependencyError { source, .. } => source.path(),
+            _ => None,
+        }
+    }
+
+    /// DLL whose scan failed, if known
+    pub fn dllname(&self) -> Option<&str> {
+        match self {
+            Self::DependencyError { dllname, .. } => Some(dllname),
+            _ => None,
+        }
+    }
+}
+
+fn dependency_context(importer: &Option<String>, entry: &Option<String>) -> String {
+    let mut context = String::new();
+    if let Some(importer) = importer {
+        context += &format!(" (imported by {importer})");
+    }
+    if let Some(entry) = entry {
+        context += &format!(" (found in {entry})");
+    }
+    context
+}
+
+/// Operation on a file that failed, reported by `LookupError::FileError`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum FileOperation {
+    Read,
+    Parse,
+    ListDirectory,
+}
+
+impl std::fmt::Display for FileOperation {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Read => "reading",
+            Self::Parse => "parsing",
+            Self::ListDirectory => "listing",
+        })
+    }
+}
+
+/// Kind of a non-fatal issue found during a scan
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DiagnosticKind {
+    /// The file is not in the PE format
+    WrongFileFormat,
+    /// One of the PE parsing backends failed to read the file
+    ParseError,
+    /// Two different files were found for the same DLL name; only the first one was kept
+    DuplicateName,
+    /// The imported or exported symbols of the file could not be read
+    SymbolExtractionError,
+    /// The goblin and pelite backends read different tables from the file (see
+    /// `pe::ParserBackend::Compare`)
+    ParserMismatch,
+}
+
+/// Non-fatal issue found during a scan, reported alongside the results
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Diagnostic {
+    pub kind: DiagnosticKind,
+    /// File the issue refers to, if any
+    pub path: Option<PathBuf>,
+    pub message: String,
+}
+
+impl Diagnostic {
+    pub fn new<P: AsRef<Path>>(kind: DiagnosticKind, path: Option<P>, message: String) -> Self {
+        Self {
+            kind,
+            path: path.map(|p| p.as_ref().to_owned()),
+            message,
+        }
+    }
+}
+
+impl std::fmt::Display for Diagnostic {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(path) = &self.path {
+            write!(
+                f,
+                "{}: {}",
+                decanonicalize(&path_to_string(path)),
+                self.message
+            )
+        } else {
+            write!(f, "{}", self.message)
+        }
+    }
+}
+
+/// DLL name patterns (e.g. "nv*.dll"), matched case-insensitively as Windows does
+#[derive(Debug, Clone, Default)]
+pub struct NamePatterns {
+    patterns: Vec<glob::Pattern>,
+}
+
+impl NamePatterns {
+    pub fn new<S: AsRef<str>>(patterns: &[S]) -> Result<Self, LookupError> {
+        Ok(Self {
+            patterns: patterns
+                .iter()
+                .map(|p| glob::Pattern::new(p.as_ref()))
+                .collect::<Result<Vec<_>, _>>()?,
+        })
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.patterns.is_empty()
+    }
+
+    /// Check if the name matches any of the patterns
+    pub fn matches(&self, name: &str) -> bool {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        self.patterns.iter().any(|p| p.matches_with(name, options))
+    }
+}
+
+/// Escape the characters with a special meaning in XML (and HTML) text and attribute values
+pub(crate) fn xml_escape(s: &str) -> String {
+    let mut escaped = String::with_capacity(s.len());
+    for c in s.chars() {
+        match c {
+            '&' => escaped.push_str("&amp;"),
+            '<' => escaped.push_str("&lt;"),
+            '>' => escaped.push_str("&gt;"),
+            '"' => escaped.push_str("&quot;"),
+            '\'' => escaped.push_str("&apos;"),
+            c => escaped.push(c),
+        }
+    }
+    escaped
+}
+
+/// Prefix of the extended-length paths, as returned by the canonicalization on Windows
+const EXTENDED_PATH_PREFIX: &str = r"\\?\";
+
+/// Longest path accepted by the Windows API without the extended path prefix
+const MAX_PATH: usize = 260;
+
 /// Remove the extended path prefix (\\?\) for readability
+///
+/// Extended UNC paths (\\?\UNC\server\share\...) are given back their usual form
+/// (\\server\share\...).
 pub fn decanonicalize(s: &str) -> String {
-    s.replacen(r"\\?\", "", 1)
+    match s.strip_prefix(EXTENDED_PATH_PREFIX) {
+        Some(rest)
+            if rest
+                .get(..4)
+                .is_some_and(|p| p.eq_ignore_ascii_case(r"UNC\")) =>
+        {
+            format!(r"\\{}", &rest[4..])
+        }
+        Some(rest) => rest.to_owned(),
+        None => s.to_owned(),
+    }
+}
+
+/// Extended-length form of an absolute Windows path longer than MAX_PATH, if it needs one
+fn extended_length_path_str(s: &str) -> Option<String> {
+    if s.len() < MAX_PATH || s.starts_with(EXTENDED_PATH_PREFIX) || s.starts_with(r"\\.\") {
+        return None;
+    }
+    // the extended paths are passed to the file system as they are, without normalization
+    let s = s.replace('/', "\\");
+    if let Some(unc) = s.strip_prefix(r"\\") {
+        Some(format!(r"{EXTENDED_PATH_PREFIX}UNC\{unc}"))
+    } else if s.as_bytes().get(1) == Some(&b':') && s.as_bytes().get(2) == Some(&b'\\') {
+        Some(format!("{EXTENDED_PATH_PREFIX}{s}"))
+    } else {
+        None
+    }
+}
+
+/// Form of a path that can be opened even if it is longer than MAX_PATH (260 characters)
+///
+/// On Windows, the absolute paths beyond the limit get the extended path prefix (\\?\, or
+/// \\?\UNC\ for network shares); other paths are returned as they are.
+pub fn extended_length_path<P: AsRef<Path>>(p: P) -> PathBuf {
+    let p = p.as_ref();
+    if cfg!(windows) {
+        if let Some(extended) = p.to_str().and_then(extended_length_path_str) {
+            return PathBuf::from(extended);
+        }
+    }
+    p.to_path_buf()
 }
 
 /// Provide the canonical form of the Path as a string, or die trying
@@ -58,6 +335,115 @@ pub fn readable_canonical_path<P: AsRef<Path>>(p: P) -> Result<String, LookupErr
     )?))
 }
 
+/// Readable form of a path, canonical if it exists on disk (it may not, e.g. if it lies inside an
+/// archive)
+pub fn readable_path<P: AsRef<Path>>(p: P) -> String {
+    readable_canonical_path(&p).unwrap_or_else(|_| decanonicalize(&path_to_string(p)))
+}
+
+/// Translate a path written in a Windows file (e.g. a .vcxproj or .dwp) to a path on this host
+///
+/// On Windows the path is used as is. Elsewhere, relative paths are taken relative to `anchor`,
+/// and absolute ones lose their drive and are looked up under each ancestor of `anchor` (typically
+/// the mount point of the partition, or the file the path was read from), also dropping their
+/// leading components, so that the trees mounted or copied to another location are found.
+#[cfg(windows)]
+pub fn host_path<P: AsRef<Path>>(windows_path: &str, _anchor: P) -> PathBuf {
+    PathBuf::from(windows_path)
+}
+
+/// Translate a path written in a Windows file (e.g. a .vcxproj or .dwp) to a path on this host
+///
+/// On Windows the path is used as is. Elsewhere, relative paths are taken relative to `anchor`,
+/// and absolute ones lose their drive and are looked up under each ancestor of `anchor` (typically
+/// the mount point of the partition, or the file the path was read from), also dropping their
+/// leading components, so that the trees mounted or copied to another location are found.
+/// Paths that are not found are rooted at /.
+///
+/// The server and share of UNC paths (\\server\share\...) are handled like a drive.
+#[cfg(not(windows))]
+pub fn host_path<P: AsRef<Path>>(windows_path: &str, anchor: P) -> PathBuf {
+    let windows_path = decanonicalize(windows_path);
+    let windows_path = windows_path.as_str();
+    let share = windows_path
+        .strip_prefix(r"\\")
+        .or_else(|| windows_path.strip_prefix("//"))
+        .map(|unc| {
+            // skip the server and the share
+            let mut separators = unc.match_indices(['\\', '/']).map(|(i, _)| i);
+            separators.nth(1).map(|i| &unc[i..]).unwrap_or_default()
+        });
+    let has_drive = share.is_some() || windows_path.as_bytes().get(1) == Some(&b':');
+    let without_drive = if let Some(share) = share {
+        share
+    } else if has_drive {
+        &windows_path[2..]
+    } else {
+        windows_path
+    };
+    let components: Vec<&str> = without_drive
+        .split(['\\', '/'])
+        .filter(|c| !c.is_empty())
+        .collect();
+    if !has_drive && !without_drive.starts_with(['\\', '/']) {
+        return anchor.as_ref().join(components.join("/"));
+    }
+    for first in 0..components.len() {
+        let rest = components[first..].join("/");
+        for ancestor in anchor.as_ref().ancestors() {
+            // the leading components are only dropped where the ancestor has the last one's name,
+            // i.e. where the tree was relocated
+            let relocated_here = first == 0
+                || ancestor
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|n| n.eq_ignore_ascii_case(components[first - 1]))
+                    .unwrap_or(false);
+            if !relocated_here {
+                continue;
+            }
+            let candidate = ancestor.join(&rest);
+            if candidate.exists() {
+                return candidate;
+            }
+        }
+    }
+    PathBuf::from("/").join(components.join("/"))
+}
+
+/// Demangled form of a symbol name, or the name itself if it is not mangled
+pub fn demangled(symbol: &str) -> String {
+    crate::pe::demangle_symbol(symbol).unwrap_or_else(|_| symbol.to_owned())
+}
+
+/// Show the file in the file manager of the platform
+#[cfg(not(target_family = "wasm"))]
+pub fn open_location(path: &Path) -> std::io::Result<()> {
+    #[cfg(windows)]
+    let mut command = {
+        let mut command = std::process::Command::new("explorer");
+        command.arg(format!("/select,{}", path.display()));
+        command
+    };
+    #[cfg(target_os = "macos")]
+    let mut command = {
+        let mut command = std::process::Command::new("open");
+        command.arg("-R").arg(path);
+        command
+    };
+    #[cfg(not(any(windows, target_os = "macos")))]
+    let mut command = {
+        let mut command = std::process::Command::new("xdg-open");
+        command.arg(path.parent().unwrap_or(path));
+        command
+    };
+    command
+        .stdout(std::process::Stdio::null())
+        .stderr(std::process::Stdio::null())
+        .spawn()
+        .map(|_| ())
+}
+
 /// Shorthand to get some kind of readable representation of a path
 pub fn path_to_string<P: AsRef<Path>>(p: P) -> String {
     p.as_ref()
@@ -68,7 +454,10 @@ pub fn path_to_string<P: AsRef<Path>>(p: P) -> String {
 
 #[cfg(test)]
 mod tests {
-    use crate::common::{decanonicalize, readable_canonical_path, LookupError};
+    use crate::common::{
+        decanonicalize, extended_length_path_str, readable_canonical_path, LookupError,
+        NamePatterns,
+    };
     use fs_err as fs;
 
     #[test]
@@ -85,4 +474,80 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn unc_and_extended_paths() -> Result<(), LookupError> {
+        assert_eq!(decanonicalize(r"\\?\C:\Windows"), r"C:\Windows");
+        assert_eq!(
+            decanonicalize(r"\\?\UNC\server\share\bin"),
+            r"\\server\share\bin"
+        );
+        assert_eq!(decanonicalize(r"\\server\share\bin"), r"\\server\share\bin");
+
+        let long_dir = "a".repeat(300);
+        assert_eq!(extended_length_path_str(r"C:\short"), None);
+        assert_eq!(
+            extended_length_path_str(&format!(r"C:\{long_dir}")),
+            Some(format!(r"\\?\C:\{long_dir}"))
+        );
+        assert_eq!(
+            extended_length_path_str(&format!(r"\\server\share/{long_dir}")),
+            Some(format!(r"\\?\UNC\server\share\{long_dir}"))
+        );
+        assert_eq!(
+            extended_length_path_str(&format!(r"\\?\C:\{long_dir}")),
+            None
+        );
+        assert_eq!(
+            extended_length_path_str(&format!(r"relative\{long_dir}")),
+            None
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn name_patterns() -> Result<(), LookupError> {
+        let patterns = NamePatterns::new(&["nv*.dll", "d3d1?.dll"])?;
+        assert!(patterns.matches("NVAPI64.DLL"));
+        assert!(patterns.matches("d3d11.dll"));
+        assert!(!patterns.matches("d3d9.dll"));
+        assert!(!NamePatterns::new::<&str>(&[])?.matches("nvapi64.dll"));
+        assert!(NamePatterns::new(&["[nv"]).is_err());
+        Ok(())
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn host_path_relocates_windows_paths() -> Result<(), LookupError> {
+        use crate::common::host_path;
+
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let project_dir = d.join("test_data/test_project1/DepRunTest/build/DepRunTest");
+        let relocated = host_path(
+            r"C:\Users\someone\dependency_runner\test_data\test_project1\DepRunTest\build\DepRunTest\Debug\DepRunTest.exe",
+            &project_dir,
+        );
+        assert_eq!(relocated, project_dir.join("Debug/DepRunTest.exe"));
+        assert_eq!(
+            host_path(r"Debug\", &project_dir),
+            project_dir.join("Debug")
+        );
+        assert_eq!(
+            host_path(r"D:\does\not\exist", &project_dir),
+            std::path::PathBuf::from("/does/not/exist")
+        );
+        // the server and share are dropped like a drive, also from extended paths
+        assert_eq!(
+            host_path(
+                r"\\buildserver\artifacts\test_project1\DepRunTest\build\DepRunTest\Debug",
+                &project_dir
+            ),
+            project_dir.join("Debug")
+        );
+        assert_eq!(
+            host_path(r"\\?\UNC\buildserver\artifacts\bin", &project_dir),
+            std::path::PathBuf::from("/bin")
+        );
+        Ok(())
+    }
 }
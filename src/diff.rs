@@ -0,0 +1,208 @@
+//! Comparison of the results of two scans, e.g. of two releases of the same application
+
+use std::collections::HashSet;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+use crate::executable::{Executable, Executables};
+
+/// Difference found for an executable present in both scans
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ExecutableChange {
+    /// the DLL was resolved to a different file
+    PathChanged {
+        old: Option<PathBuf>,
+        new: Option<PathBuf>,
+    },
+    /// the version resource of the file changed
+    VersionChanged {
+        old: Option<String>,
+        new: Option<String>,
+    },
+    /// the DLL was found by the old scan, but is missing now
+    NowMissing,
+    /// the DLL was missing in the old scan, but is found now
+    NowFound,
+}
+
+/// Changes of a single executable between two scans
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ChangedExecutable {
+    pub dllname: String,
+    pub changes: Vec<ExecutableChange>,
+}
+
+/// Dependencies added, removed or changed between two scans
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct ExecutablesDiff {
+    /// names of the executables only present in the new scan
+    pub added: Vec<String>,
+    /// names of the executables only present in the old scan
+    pub removed: Vec<String>,
+    /// executables present in both scans, with differences
+    pub changed: Vec<ChangedExecutable>,
+}
+
+impl ExecutablesDiff {
+    /// Compare two scans, reporting the executables in order of first appearance
+    pub fn compare(old: &Executables, new: &Executables) -> Self {
+        let old_sorted = old.sorted_by_first_appearance();
+        let new_sorted = new.sorted_by_first_appearance();
+
+        let added = new_sorted
+            .iter()
+            .filter(|e| !old.contains(&e.dllname))
+            .map(|e| e.dllname.clone())
+            .collect();
+        let removed = old_sorted
+            .iter()
+            .filter(|e| !new.contains(&e.dllname))
+            .map(|e| e.dllname.clone())
+            .collect();
+        let changed = new_sorted
+            .iter()
+            .filter_map(|new_exe| {
+                let old_exe = old.get(&new_exe.dllname)?;
+                let changes = compare_executables(old_exe, new_exe);
+                if changes.is_empty() {
+                    None
+                } else {
+                    Some(ChangedExecutable {
+                        dllname: new_exe.dllname.clone(),
+                        changes,
+                    })
+                }
+            })
+            .collect();
+
+        Self {
+            added,
+            removed,
+            changed,
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.added.is_empty() && self.removed.is_empty() && self.changed.is_empty()
+    }
+
+    /// Names of the DLLs that were found by the old scan, and are referenced but missing now
+    pub fn newly_missing(&self) -> HashSet<&str> {
+        self.changed
+            .iter()
+            .filter(|c| c.changes.contains(&ExecutableChange::NowMissing))
+            .map(|c| c.dllname.as_str())
+            .collect()
+    }
+}
+
+impl std::fmt::Display for ExecutablesDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "No differences");
+        }
+        for name in &self.added {
+            writeln!(f, "+ {name}")?;
+        }
+        for name in &self.removed {
+            writeln!(f, "- {name}")?;
+        }
+        for changed in &self.changed {
+            writeln!(f, "~ {}", changed.dllname)?;
+            for change in &changed.changes {
+                match change {
+                    ExecutableChange::PathChanged { old, new } => {
+                        let display =
+                            |p: &Option<PathBuf>| p.as_ref().map(|p| p.display().to_string());
+                        writeln!(
+                            f,
+                            "\tpath: {} -> {}",
+                            display(old).as_deref().unwrap_or("none"),
+                            display(new).as_deref().unwrap_or("none")
+                        )?
+                    }
+                    ExecutableChange::VersionChanged { old, new } => writeln!(
+                        f,
+                        "\tversion: {} -> {}",
+                        old.as_deref().unwrap_or("none"),
+                        new.as_deref().unwrap_or("none")
+                    )?,
+                    ExecutableChange::NowMissing => writeln!(f, "\tnot found anymore")?,
+                    ExecutableChange::NowFound => writeln!(f, "\tfound now")?,
+                }
+            }
+        }
+        Ok(())
+    }
+}
+
+fn compare_executables(old: &Executable, new: &Executable) -> Vec<ExecutableChange> {
+    let mut changes = Vec::new();
+    match (old.found, new.found) {
+        (true, false) => changes.push(ExecutableChange::NowMissing),
+        (false, true) => changes.push(ExecutableChange::NowFound),
+        _ => {}
+    }
+    if let (Some(old_details), Some(new_details)) = (old.details.as_ref(), new.details.as_ref()) {
+        if old_details.full_path != new_details.full_path {
+            changes.push(ExecutableChange::PathChanged {
+                old: old_details.full_path.clone(),
+                new: new_details.full_path.clone(),
+            });
+        }
+        if old_details.file_version != new_details.file_version {
+            changes.push(ExecutableChange::VersionChanged {
+                old: old_details.file_version.clone(),
+                new: new_details.file_version.clone(),
+            });
+        }
+    }
+    changes
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::LookupError;
+    use crate::diff::{ExecutableChange, ExecutablesDiff};
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+
+    #[test]
+    fn diff_scans() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let complete_exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let broken_exe_path =
+            d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe");
+
+        let complete_query = LookupQuery::deduce_from_executable_location(complete_exe_path)?;
+        let complete = run(&complete_query, &LookupPath::deduce(&complete_query))?;
+        let broken_query = LookupQuery::deduce_from_executable_location(broken_exe_path)?;
+        let broken = run(&broken_query, &LookupPath::deduce(&broken_query))?;
+
+        assert!(ExecutablesDiff::compare(&complete, &complete).is_empty());
+
+        let diff = ExecutablesDiff::compare(&complete, &broken);
+        assert!(diff.added.is_empty());
+        assert!(diff.removed.contains(&"MSVCP140D.dll".to_owned()));
+        assert!(diff.newly_missing().contains("DepRunTestLib.dll"));
+        let exe_changes = &diff
+            .changed
+            .iter()
+            .find(|c| c.dllname == "DepRunTest.exe")
+            .unwrap()
+            .changes;
+        assert!(matches!(
+            exe_changes.as_slice(),
+            [ExecutableChange::PathChanged { .. }]
+        ));
+
+        let reverse_diff = ExecutablesDiff::compare(&broken, &complete);
+        assert!(reverse_diff.added.contains(&"MSVCP140D.dll".to_owned()));
+        assert!(reverse_diff.newly_missing().is_empty());
+
+        Ok(())
+    }
+}
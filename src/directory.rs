@@ -0,0 +1,455 @@
+//! Scan of all the executables found under a directory, with an aggregated report
+
+use std::collections::BTreeSet;
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use serde::Serialize;
+
+use crate::archive::{split_archive_path, ArchiveFileProvider, ArchiveFormat};
+use crate::common::{LookupError, NamePatterns};
+use crate::executable::{DependencyKind, Executables, ExecutablesCheckReport};
+use crate::pe::{PEFile, PEFileMap};
+use crate::provider::{FileProvider, LocalFileSystem};
+use crate::query::LookupQuery;
+use crate::report::{FindingKind, Severity};
+use crate::session::ScanSession;
+
+/// Which files under the directory are scanned
+pub struct DirectoryScanOptions {
+    /// descend into subdirectories
+    pub recursive: bool,
+    /// maximum depth of the subdirectories to descend into (None: unlimited)
+    pub max_depth: Option<usize>,
+    /// only scan files matching these patterns, e.g. "*.exe" (empty: all .exe and .dll files)
+    pub include: NamePatterns,
+    /// do not scan files matching these patterns
+    pub exclude: NamePatterns,
+}
+
+/// Result of the scan of one of the executables of the directory
+#[derive(Debug, Serialize)]
+pub struct TargetScan {
+    pub path: PathBuf,
+    pub executables: Executables,
+    pub report: ExecutablesCheckReport,
+}
+
+/// Executable of the directory that could not be scanned, e.g. because it is not a PE file
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct TargetFailure {
+    pub path: PathBuf,
+    pub error: String,
+}
+
+/// Totals over all the scanned executables
+#[derive(Debug, Default, Clone, PartialEq, Eq, Serialize)]
+pub struct DirectoryScanSummary {
+    pub targets: usize,
+    /// executables that could not be scanned
+    pub failed_targets: usize,
+    /// executables with at least one finding of severity error
+    pub targets_with_errors: usize,
+    pub errors: usize,
+    pub warnings: usize,
+    /// names of the DLLs missing for at least one executable
+    pub missing_dlls: BTreeSet<String>,
+}
+
+/// Scan results of all the executables of a directory
+#[derive(Debug, Serialize)]
+pub struct DirectoryScan {
+    pub dir: PathBuf,
+    pub targets: Vec<TargetScan>,
+    /// executables whose scan failed, which do not stop the scan of the others
+    pub failures: Vec<TargetFailure>,
+}
+
+impl DirectoryScan {
+    pub fn summary(&self) -> DirectoryScanSummary {
+        let mut summary = DirectoryScanSummary {
+            targets: self.targets.len(),
+            failed_targets: self.failures.len(),
+            ..Default::default()
+        };
+        for target in &self.targets {
+            let errors = target.report.at_least(Severity::Error).count();
+            summary.errors += errors;
+            summary.warnings += target.report.at_least(Severity::Warning).count() - errors;
+            if errors > 0 {
+                summary.targets_with_errors += 1;
+            }
+            for finding in target.report.findings() {
+                if let FindingKind::MissingDll { dll, .. } = &finding.kind {
+                    summary.missing_dlls.insert(dll.clone());
+                }
+            }
+        }
+        summary
+    }
+}
+
+impl std::fmt::Display for DirectoryScan {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for target in &self.targets {
+            let name = target.path.strip_prefix(&self.dir).unwrap_or(&target.path);
+            writeln!(f, "== {} ==", name.display())?;
+            if target.report.is_empty() {
+                writeln!(f, "No problems detected")?;
+            }
+            for finding in target.report.findings() {
+                writeln!(f, "{finding}")?;
+            }
+            writeln!(f)?;
+        }
+        for failure in &self.failures {
+            let name = failure
+                .path
+                .strip_prefix(&self.dir)
+                .unwrap_or(&failure.path);
+            writeln!(f, "== {} ==", name.display())?;
+            writeln!(f, "Could not be scanned: {}\n", failure.error)?;
+        }
+        let summary = self.summary();
+        writeln!(
+            f,
+            "Scanned {} executables: {} with errors, {} errors, {} warnings",
+            summary.targets, summary.targets_with_errors, summary.errors, summary.warnings
+        )?;
+        if summary.failed_targets > 0 {
+            writeln!(
+                f,
+                "{} executables could not be scanned",
+                summary.failed_targets
+            )?;
+        }
+        if !summary.missing_dlls.is_empty() {
+            let missing: Vec<&str> = summary.missing_dlls.iter().map(String::as_str).collect();
+            writeln!(f, "Missing DLLs: {}", missing.join(", "))?;
+        }
+        Ok(())
+    }
+}
+
+/// Paths of the .exe and .dll files under the directory selected by the options, sorted
+pub fn find_executables<P: AsRef<Path>>(
+    dir: P,
+    options: &DirectoryScanOptions,
+) -> Result<Vec<PathBuf>, LookupError> {
+    find_executables_with_provider(&LocalFileSystem, dir, options)
+}
+
+/// Same as `find_executables`, listing the directories through a file provider, e.g. the ones of
+/// an archive
+pub fn find_executables_with_provider<P: AsRef<Path>>(
+    provider: &dyn FileProvider,
+    dir: P,
+    options: &DirectoryScanOptions,
+) -> Result<Vec<PathBuf>, LookupError> {
+    let mut executables = Vec::new();
+    let mut dirs = vec![(dir.as_ref().to_owned(), 0)];
+    while let Some((dir, depth)) = dirs.pop() {
+        for file_name in provider.list_files(&dir)? {
+            let path = dir.join(&file_name);
+            let name = match file_name.to_str() {
+                Some(name) => name,
+                None => continue,
+            };
+            let is_executable = path
+                .extension()
+                .and_then(|e| e.to_str())
+                .map(|e| e.eq_ignore_ascii_case("exe") || e.eq_ignore_ascii_case("dll"))
+                .unwrap_or(false);
+            if is_executable
+                && (options.include.is_empty() || options.include.matches(name))
+                && !options.exclude.matches(name)
+            {
+                executables.push(path);
+            }
+        }
+        if options.recursive && options.max_depth.map(|m| depth < m).unwrap_or(true) {
+            for subdirectory in provider.list_subdirectories(&dir)? {
+                dirs.push((dir.join(subdirectory), depth + 1));
+            }
+        }
+    }
+    executables.sort();
+    Ok(executables)
+}
+
+/// Scan and check every executable under the directory selected by the options
+///
+/// The query of each executable is deduced from its location, then passed to `configure` for
+/// adjustments. The files already parsed for a previous executable, e.g. the system DLLs, are not
+/// parsed again.
+///
+/// The directory can also be an archive, a directory inside it or a single executable inside it
+/// (e.g. app.zip, app.zip!/bin or app.zip!/bin/app.exe), which is scanned without unpacking it.
+pub fn scan_directory<P: AsRef<Path>, F: Fn(&mut LookupQuery)>(
+    dir: P,
+    options: &DirectoryScanOptions,
+    configure: F,
+) -> Result<DirectoryScan, LookupError> {
+    let dir = dir.as_ref();
+    let archive_member = match dir.to_str().and_then(split_archive_path) {
+        Some(archive_member) => Some(archive_member),
+        None if ArchiveFormat::from_path(dir).is_some() && dir.is_file() => {
+            Some((dir.to_owned(), PathBuf::new()))
+        }
+        None => None,
+    };
+    if let Some((archive_path, member)) = archive_member {
+        let provider = ArchiveFileProvider::open(archive_path)?;
+        let path = provider.archive_path().join(&member);
+        let (dir, paths) = if provider.contains(&member) {
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            (dir, vec![path])
+        } else {
+            let paths = find_executables_with_provider(&provider, &path, options)?;
+            (path, paths)
+        };
+        let session = ScanSession::with_archive(provider);
+        return Ok(scan_executables(session, dir, paths, configure));
+    }
+    let dir = fs::canonicalize(dir)?;
+    let paths = find_executables(&dir, options)?;
+    Ok(scan_executables(ScanSession::new(), dir, paths, configure))
+}
+
+/// Paths of a list of targets, one per line, as written by a build system for `deprun scan
+/// @targets.txt`; blank lines and lines starting with # are skipped
+pub fn parse_target_list(list: &str) -> Vec<PathBuf> {
+    list.lines()
+        .map(str::trim)
+        .filter(|l| !l.is_empty() && !l.starts_with('#'))
+        .map(PathBuf::from)
+        .collect()
+}
+
+/// Scan and check the given executables, e.g. the ones of a target list (see
+/// [`parse_target_list`]), in a single session as for `scan_directory`
+///
+/// The relative paths are resolved against `base_dir`, which the paths of the report are
+/// relative to. The paths that do not exist are recorded as failures.
+pub fn scan_targets<P: AsRef<Path>, F: Fn(&mut LookupQuery)>(
+    base_dir: P,
+    paths: &[PathBuf],
+    configure: F,
+) -> Result<DirectoryScan, LookupError> {
+    let base_dir = fs::canonicalize(base_dir.as_ref())?;
+    let mut existing = Vec::new();
+    let mut missing = Vec::new();
+    for path in paths {
+        let path = base_dir.join(path);
+        match fs::canonicalize(&path) {
+            Ok(path) => existing.push(path),
+            Err(e) => missing.push(TargetFailure {
+                path,
+                error: e.to_string(),
+            }),
+        }
+    }
+    let mut scan = scan_executables(ScanSession::new(), base_dir, existing, configure);
+    scan.failures.splice(0..0, missing);
+    Ok(scan)
+}
+
+/// Scan and check the given executables in a single session, sharing the Windows installation,
+/// the directory listings and the parsed files between them
+///
+/// The executables that cannot be scanned are recorded as failures.
+pub(crate) fn scan_executables<F: Fn(&mut LookupQuery)>(
+    mut session: ScanSession,
+    dir: PathBuf,
+    paths: Vec<PathBuf>,
+    configure: F,
+) -> DirectoryScan {
+    let mut targets = Vec::new();
+    let mut failures = Vec::new();
+    for path in paths {
+        let mut scan_target = || -> Result<(Executables, ExecutablesCheckReport), LookupError> {
+            let mut query = session.deduce_query(&path)?;
+            configure(&mut query);
+            let lookup_path = session.deduce_lookup_path(&query);
+            let executables = session.scan(&query, &lookup_path)?;
+            let report = executables.check(query.parameters.extract_symbols)?;
+            Ok((executables, report))
+        };
+        match scan_target() {
+            Ok((executables, report)) => targets.push(TargetScan {
+                path,
+                executables,
+                report,
+            }),
+            Err(e) => failures.push(TargetFailure {
+                path,
+                error: e.to_string(),
+            }),
+        }
+    }
+    DirectoryScan {
+        dir,
+        targets,
+        failures,
+    }
+}
+
+/// Executable of a directory referencing a DLL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct DllImporter {
+    pub path: PathBuf,
+    /// tables the DLL is referenced in: import table (static), delay-load import table or export
+    /// forwarders
+    pub kinds: Vec<DependencyKind>,
+}
+
+/// Find the executables under the directory selected by the options whose import, delay-load
+/// import or export forwarder tables reference the given DLL name (case-insensitively)
+///
+/// Files that cannot be parsed are skipped.
+pub fn find_importers<P: AsRef<Path>>(
+    dir: P,
+    options: &DirectoryScanOptions,
+    dllname: &str,
+) -> Result<Vec<DllImporter>, LookupError> {
+    let mut importers = Vec::new();
+    for path in find_executables(dir, options)? {
+        let pefilemap = PEFileMap::new(&path)?;
+        let pefile = match PEFile::new(&pefilemap) {
+            Ok(pefile) => pefile,
+            Err(_) => continue,
+        };
+        let tables = [
+            (pefile.read_dependencies(), DependencyKind::Static),
+            (
+                pefile.read_delay_load_dependencies(),
+                DependencyKind::DelayLoad,
+            ),
+            (
+                pefile.read_forwarded_dependencies(),
+                DependencyKind::Forwarded,
+            ),
+        ];
+        let kinds: Vec<DependencyKind> = tables
+            .into_iter()
+            .filter(|(names, _)| {
+                names
+                    .as_ref()
+                    .map(|names| names.iter().any(|n| n.eq_ignore_ascii_case(dllname)))
+                    .unwrap_or(false)
+            })
+            .map(|(_, kind)| kind)
+            .collect();
+        if !kinds.is_empty() {
+            importers.push(DllImporter { path, kinds });
+        }
+    }
+    Ok(importers)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{LookupError, NamePatterns};
+    use crate::directory::{
+        find_executables, find_importers, parse_target_list, scan_directory, scan_targets,
+        DirectoryScanOptions,
+    };
+    use crate::executable::DependencyKind;
+    use crate::report::FindingKind;
+    use fs_err as fs;
+
+    #[test]
+    fn scan_test_project() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = d.join("test_data/test_project1/DepRunTest");
+        let options = DirectoryScanOptions {
+            recursive: true,
+            max_depth: None,
+            include: NamePatterns::new(&["*.exe"])?,
+            exclude: NamePatterns::new::<&str>(&[])?,
+        };
+
+        let scan = scan_directory(&dir, &options, |_| {})?;
+        assert_eq!(scan.targets.len(), 3);
+        let summary = scan.summary();
+        assert!(summary.missing_dlls.contains("DepRunTestLib.dll"));
+        assert!(summary.targets_with_errors >= 1);
+        assert!(scan.to_string().contains("Scanned 3 executables"));
+
+        let shallow = DirectoryScanOptions {
+            max_depth: Some(1),
+            ..options
+        };
+        assert!(find_executables(&dir, &shallow)?.is_empty());
+
+        // a file that is not a PE file does not stop the scan of the others
+        let tmp = tempfile::tempdir()?;
+        let exe = dir.join("build-same-output/bin/Debug/DepRunTest.exe");
+        fs::copy(&exe, tmp.path().join("DepRunTest.exe"))?;
+        fs::write(tmp.path().join("broken.dll"), b"MZ")?;
+        let all_files = DirectoryScanOptions {
+            recursive: false,
+            max_depth: None,
+            include: NamePatterns::new::<&str>(&[])?,
+            exclude: NamePatterns::new::<&str>(&[])?,
+        };
+        let scan = scan_directory(tmp.path(), &all_files, |_| {})?;
+        assert_eq!(scan.targets.len(), 1);
+        assert_eq!(scan.failures.len(), 1);
+        assert!(scan.failures[0].path.ends_with("broken.dll"));
+        assert_eq!(scan.summary().failed_targets, 1);
+        assert!(scan
+            .to_string()
+            .contains("1 executables could not be scanned"));
+
+        let all_files = DirectoryScanOptions {
+            recursive: true,
+            max_depth: None,
+            include: NamePatterns::new::<&str>(&[])?,
+            exclude: NamePatterns::new::<&str>(&[])?,
+        };
+        let importers = find_importers(&dir, &all_files, "depruntestlib.dll")?;
+        assert_eq!(importers.len(), 3);
+        assert!(
+            importers
+                .iter()
+                .all(|i| i.path.ends_with("DepRunTest.exe")
+                    && i.kinds == vec![DependencyKind::Static])
+        );
+        assert_eq!(find_importers(&dir, &all_files, "MSVCP140D.dll")?.len(), 3);
+
+        Ok(())
+    }
+
+    #[test]
+    fn scan_target_list() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dir = d.join("test_data/test_project1/DepRunTest");
+        let list = "# built by the CI\n\
+                    build-same-output/bin/Debug/DepRunTest.exe\n\
+                    \n  build/DepRunTest/Debug/DepRunTest.exe  \n";
+        let paths = parse_target_list(list);
+        assert_eq!(paths.len(), 2);
+
+        let scan = scan_targets(&dir, &paths, |_| {})?;
+        assert_eq!(scan.targets.len(), 2);
+        let missing_lib = |i: usize| {
+            scan.targets[i]
+                .report
+                .findings()
+                .iter()
+                .any(|f| matches!(&f.kind, FindingKind::MissingDll { dll, .. } if dll == "DepRunTestLib.dll"))
+        };
+        assert!(!missing_lib(0));
+        assert!(missing_lib(1));
+        assert!(scan
+            .to_string()
+            .contains("== build-same-output/bin/Debug/DepRunTest.exe =="));
+        let paths = [std::path::PathBuf::from("missing.exe"), paths[0].clone()];
+        let scan = scan_targets(&dir, &paths, |_| {})?;
+        assert_eq!(scan.targets.len(), 1);
+        assert_eq!(scan.failures.len(), 1);
+        assert_eq!(scan.failures[0].path, dir.join("missing.exe"));
+        Ok(())
+    }
+}
@@ -0,0 +1,155 @@
+//! Environment block of the simulated process
+//!
+//! By default the PATH of the scan is the one of the current shell (on Windows) or of the Windows
+//! installation; an explicit environment (e.g. captured from the configuration of a service, or read
+//! from a .env file) can be given instead with `LookupQuery::with_environment`.
+
+use std::collections::HashMap;
+use std::path::Path;
+
+use fs_err as fs;
+
+use crate::common::LookupError;
+use crate::registration::{default_environment_variable, expand_variables};
+
+/// Environment variables, with case-insensitive names as on Windows
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Environment {
+    /// values by uppercase name
+    variables: HashMap<String, String>,
+}
+
+impl Environment {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Environment of the current process
+    pub fn current() -> Self {
+        std::env::vars().collect()
+    }
+
+    /// Read a .env file: one NAME=VALUE assignment per line, optionally preceded by `export` and
+    /// with the value in quotes; empty lines and lines starting with # are ignored
+    pub fn from_env_file<P: AsRef<Path>>(path: P) -> Result<Self, LookupError> {
+        let content = fs::read_to_string(path.as_ref())?;
+        let mut environment = Self::new();
+        for (i, line) in content.lines().enumerate() {
+            let line = line.trim();
+            if line.is_empty() || line.starts_with('#') {
+                continue;
+            }
+            let line = line.strip_prefix("export ").unwrap_or(line);
+            let (name, value) = line.split_once('=').ok_or_else(|| {
+                LookupError::ParseError(format!(
+                    "{}:{}: expected NAME=VALUE",
+                    path.as_ref().display(),
+                    i + 1
+                ))
+            })?;
+            let value = value.trim();
+            let value = ['"', '\'']
+                .iter()
+                .find_map(|q| value.strip_prefix(*q)?.strip_suffix(*q))
+                .unwrap_or(value);
+            environment.set(name.trim(), value);
+        }
+        Ok(environment)
+    }
+
+    pub fn set(&mut self, name: &str, value: &str) {
+        self.variables.insert(name.to_uppercase(), value.to_owned());
+    }
+
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.variables.get(&name.to_uppercase()).map(String::as_str)
+    }
+
+    /// Replace the references to environment variables (e.g. %ProgramFiles%) with their value
+    ///
+    /// As in ExpandEnvironmentStrings, the values are not expanded in turn. Variables not set in
+    /// this environment take the default value of the standard system directories, if any, and are
+    /// left as they are otherwise.
+    pub fn expand(&self, s: &str) -> String {
+        expand_variables(s, |name| {
+            self.get(name)
+                .or_else(|| default_environment_variable(name))
+                .map(str::to_owned)
+        })
+    }
+
+    /// Entries of the PATH, expanded, as written for Windows
+    pub fn path(&self) -> Vec<String> {
+        self.get("PATH")
+            .map(|path| {
+                path.split(';')
+                    .filter(|entry| !entry.trim().is_empty())
+                    .map(|entry| self.expand(entry.trim()))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+}
+
+impl<K: AsRef<str>, V: AsRef<str>> FromIterator<(K, V)> for Environment {
+    fn from_iter<I: IntoIterator<Item = (K, V)>>(iter: I) -> Self {
+        let mut environment = Self::new();
+        for (name, value) in iter {
+            environment.set(name.as_ref(), value.as_ref());
+        }
+        environment
+    }
+}
+
+impl From<HashMap<String, String>> for Environment {
+    fn from(variables: HashMap<String, String>) -> Self {
+        variables.into_iter().collect()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::common::LookupError;
+    use crate::environment::Environment;
+    use crate::query::LookupQuery;
+
+    #[test]
+    fn explicit_environment() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let environment = Environment::from_env_file(d.join("test_data/environment/service.env"))?;
+        assert_eq!(
+            environment.get("appdir"),
+            Some(r"C:\work\test_data\test_project1\DepRunTest\build")
+        );
+        assert_eq!(
+            environment.path(),
+            vec![
+                r"C:\work\test_data\test_project1\DepRunTest\build\DepRunTestLib\Debug".to_owned(),
+                r"C:\Windows\System32".to_owned(),
+                r"%UNKNOWN%\bin".to_owned(),
+            ]
+        );
+
+        // the DLL is found only through the PATH of the environment
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = None;
+        query.target.user_path.clear();
+        let query = query.with_environment(environment);
+        assert!(query.environment.is_some());
+        #[cfg(not(windows))]
+        {
+            use crate::path::LookupPath;
+            use crate::runner::run;
+
+            let lookup_path = LookupPath::deduce(&query);
+            let res = run(&query, &lookup_path)?;
+            assert!(res.get("DepRunTestLib.dll").unwrap().found);
+        }
+
+        Ok(())
+    }
+}
@@ -1,15 +1,32 @@
 //! This crate contains the data structures used to hold the results of a dependency scan
-use std::collections::{HashMap, HashSet};
-use std::path::PathBuf;
+use std::collections::{BTreeMap, BTreeSet, HashMap, HashSet};
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
 
-use serde::Serialize;
+use fs_err as fs;
+use md5::Md5;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
 
-use crate::common::{LookupError, readable_canonical_path};
+use crate::banned::BannedImports;
+use crate::bundle::{DeploymentFile, DeploymentPolicy};
+use crate::common::{
+    decanonicalize, path_to_string, readable_path, Diagnostic, DiagnosticKind, LookupError,
+};
+use crate::inventory::{OsProfile, SymbolAvailability, WindowsFeatures};
+use crate::lock::{self, DependencyLock};
+use crate::path::{Probe, ResolutionSource};
+use crate::pe::demangle_symbol;
+use crate::query::DuplicatePolicy;
+use crate::redist::runtime_suggestion;
+use crate::report::{Finding, FindingKind, Severity};
+
+pub use crate::report::ExecutablesCheckReport;
 
 /// Information about a DLL that was mentioned as target for the search
 /// If the file was actually found, additional info is available. Otherwise it represents a
 /// missing/broken dependency.
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct Executable {
     /// Name as it appears in the import table
     pub dllname: String,
@@ -19,10 +36,104 @@ pub struct Executable {
     pub found: bool,
     /// metadata extracted from the actual executable file
     pub details: Option<ExecutableDetails>,
+    /// entries of the lookup path probed for the file, if requested in the query
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub probes: Option<Vec<Probe>>,
+}
+
+impl Executable {
+    /// Symbols matching the pattern, by raw or demangled name, exported (sorted by name) or
+    /// imported (sorted by module, then by name) by this executable
+    ///
+    /// The symbols are demangled as the iterator is consumed; see `Executables::find_symbol`.
+    pub fn find_symbol<'a>(
+        &'a self,
+        pattern: &'a SymbolPattern,
+    ) -> impl Iterator<Item = SymbolMatch> + 'a {
+        let symbols = self.details.as_ref().and_then(|d| d.symbols.as_ref());
+        let mut exported: Vec<&String> = symbols
+            .map(|s| s.exported.iter().collect())
+            .unwrap_or_default();
+        exported.sort();
+        let mut imported: Vec<(&String, &String)> = symbols
+            .map(|s| {
+                s.imported
+                    .iter()
+                    .flat_map(|(dll, names)| names.iter().map(move |n| (dll, n)))
+                    .filter(|(_, n)| !n.is_empty())
+                    .collect()
+            })
+            .unwrap_or_default();
+        imported.sort();
+        exported
+            .into_iter()
+            .map(|n| (n, SymbolUse::Exported))
+            .chain(
+                imported
+                    .into_iter()
+                    .map(|(dll, n)| (n, SymbolUse::Imported(dll.clone()))),
+            )
+            .filter_map(move |(symbol, usage)| {
+                let demangled = demangle_symbol(symbol).ok();
+                let matches = pattern.matches(symbol)
+                    || demangled.as_deref().is_some_and(|d| pattern.matches(d));
+                matches.then(|| SymbolMatch {
+                    dllname: self.dllname.clone(),
+                    symbol: symbol.clone(),
+                    demangled,
+                    usage,
+                })
+            })
+    }
+}
+
+/// Size and modification time of a file, used to detect changes between scans
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileStamp {
+    pub size: u64,
+    pub modified: Option<SystemTime>,
+}
+
+impl FileStamp {
+    /// Read the stamp of the file at the given path
+    pub fn read<P: AsRef<Path>>(p: P) -> Result<Self, LookupError> {
+        let metadata = fs::metadata(p.as_ref())?;
+        Ok(Self {
+            size: metadata.len(),
+            modified: metadata.modified().ok(),
+        })
+    }
+}
+
+/// Read a full path, written as an empty string for the files without a path before schema
+/// version 2
+fn deserialize_full_path<'de, D: serde::Deserializer<'de>>(
+    deserializer: D,
+) -> Result<Option<PathBuf>, D::Error> {
+    let path: Option<PathBuf> = Option::deserialize(deserializer)?;
+    Ok(path.filter(|p| !p.as_os_str().is_empty()))
+}
+
+/// Digests of the content of a file, as lowercase hexadecimal strings
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct FileHashes {
+    pub sha256: String,
+    /// for the tools and databases still indexing files by MD5
+    pub md5: String,
+}
+
+impl FileHashes {
+    pub fn compute(content: &[u8]) -> Self {
+        let to_hex = |digest: &[u8]| digest.iter().map(|b| format!("{b:02x}")).collect();
+        Self {
+            sha256: to_hex(&Sha256::digest(content)),
+            md5: to_hex(&Md5::digest(content)),
+        }
+    }
 }
 
 /// Metadata for a found executable file
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutableDetails {
     /// virtual DLL which just forwards to an implementation
     pub is_api_set: bool,
@@ -30,16 +141,264 @@ pub struct ExecutableDetails {
     pub is_system: bool,
     /// it is among the KnownDLLs list, or a dependency thereof
     pub is_known_dll: bool,
-    /// full path
-    pub full_path: PathBuf,
+    /// name of the DLL implementing the contract, if this is an API set
+    pub api_set_host: Option<String>,
+    /// full path (for API sets, path of the host DLL; None if resolved without a file, e.g. through
+    /// an API set map without a system partition)
+    #[serde(deserialize_with = "deserialize_full_path")]
+    pub full_path: Option<PathBuf>,
+    /// entry of the lookup path the file was found in
+    #[serde(default)]
+    pub resolved_from: Option<ResolutionSource>,
+    /// size and modification time of the file at the time of the scan
+    #[serde(default)]
+    pub file_stamp: Option<FileStamp>,
+    /// file version from the version resource, if any (e.g. "10.0.19041.1")
+    #[serde(default)]
+    pub file_version: Option<String>,
+    /// digests of the file content, if requested in the query
+    #[serde(default)]
+    pub hashes: Option<FileHashes>,
+    /// machine type from the COFF header
+    #[serde(default)]
+    pub architecture: Option<Architecture>,
+    /// subsystem from the optional header
+    #[serde(default)]
+    pub subsystem: Option<Subsystem>,
+    /// exploit mitigations enabled in the headers
+    #[serde(default)]
+    pub security: Option<SecurityFeatures>,
+    /// address (RVA) of the entry point, if the file has one (resource-only DLLs do not)
+    #[serde(default)]
+    pub entry_point: Option<u32>,
+    /// names of the Authenticode signers, if the file is signed (the signatures are not verified)
+    #[serde(default)]
+    pub signers: Option<Vec<String>>,
+    /// side-by-side assemblies requested by the embedded manifest
+    #[serde(default)]
+    pub assembly_dependencies: Option<Vec<AssemblyDependency>>,
     /// names of the DLLs this executable file depends on
     pub dependencies: Option<Vec<String>>,
+    /// names of the DLLs this executable file loads on first use (delay-load imports)
+    #[serde(default)]
+    pub delay_load_dependencies: Option<Vec<String>>,
+    /// names of the DLLs some exports of this file are forwarded to
+    #[serde(default)]
+    pub forwarded_dependencies: Option<Vec<String>>,
+    /// names of DLLs found among the strings of this file, which it possibly loads at runtime
+    /// (heuristic, only if requested in the query)
+    #[serde(default)]
+    pub dynamic_load_dependencies: Option<Vec<String>>,
     /// Symbols import / export table
     pub symbols: Option<ExecutableSymbols>,
 }
 
+/// Machine type an executable was built for
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Architecture {
+    X86,
+    X64,
+    Arm,
+    Arm64,
+    /// any other machine type, with the value of the COFF header field
+    Other(u16),
+}
+
+impl Architecture {
+    pub fn from_machine(machine: u16) -> Self {
+        match machine {
+            0x014c => Self::X86,
+            0x8664 => Self::X64,
+            0x01c0 | 0x01c4 => Self::Arm,
+            0xaa64 => Self::Arm64,
+            m => Self::Other(m),
+        }
+    }
+}
+
+impl std::str::FromStr for Architecture {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "x86" | "i386" | "win32" => Ok(Self::X86),
+            "x64" | "amd64" => Ok(Self::X64),
+            "arm" => Ok(Self::Arm),
+            "arm64" | "aarch64" => Ok(Self::Arm64),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown architecture {s} (expected x86, x64, arm or arm64)"
+            ))),
+        }
+    }
+}
+
+impl std::fmt::Display for Architecture {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::X86 => f.write_str("x86"),
+            Self::X64 => f.write_str("x64"),
+            Self::Arm => f.write_str("ARM"),
+            Self::Arm64 => f.write_str("ARM64"),
+            Self::Other(m) => write!(f, "machine {m:#06x}"),
+        }
+    }
+}
+
+/// Subsystem an executable is built to run in
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum Subsystem {
+    Native,
+    /// graphical Windows application
+    WindowsGui,
+    /// Windows console application
+    WindowsConsole,
+    /// EFI application or driver
+    Efi,
+    /// any other subsystem, with the value of the optional header field
+    Other(u16),
+}
+
+impl Subsystem {
+    pub fn from_value(subsystem: u16) -> Self {
+        match subsystem {
+            1 => Self::Native,
+            2 => Self::WindowsGui,
+            3 => Self::WindowsConsole,
+            10..=13 => Self::Efi,
+            s => Self::Other(s),
+        }
+    }
+}
+
+impl std::fmt::Display for Subsystem {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Native => f.write_str("native"),
+            Self::WindowsGui => f.write_str("GUI"),
+            Self::WindowsConsole => f.write_str("console"),
+            Self::Efi => f.write_str("EFI"),
+            Self::Other(s) => write!(f, "subsystem {s}"),
+        }
+    }
+}
+
+/// Exploit mitigations enabled in the headers of an executable
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct SecurityFeatures {
+    /// can be loaded at a random address (dynamic base, and relocations not stripped)
+    pub aslr: bool,
+    /// ASLR with 64-bit entropy
+    pub high_entropy_va: bool,
+    /// compatible with data execution prevention (NX)
+    pub dep: bool,
+    /// Control Flow Guard
+    pub cfg: bool,
+}
+
+impl SecurityFeatures {
+    const DYNAMIC_BASE: u16 = 0x0040;
+    const HIGH_ENTROPY_VA: u16 = 0x0020;
+    const NX_COMPAT: u16 = 0x0100;
+    const GUARD_CF: u16 = 0x4000;
+    const RELOCS_STRIPPED: u16 = 0x0001;
+
+    /// Read from the DllCharacteristics field of the optional header and the Characteristics
+    /// field of the COFF header
+    pub fn from_characteristics(dll_characteristics: u16, characteristics: u16) -> Self {
+        let aslr = dll_characteristics & Self::DYNAMIC_BASE != 0
+            && characteristics & Self::RELOCS_STRIPPED == 0;
+        Self {
+            aslr,
+            high_entropy_va: aslr && dll_characteristics & Self::HIGH_ENTROPY_VA != 0,
+            dep: dll_characteristics & Self::NX_COMPAT != 0,
+            cfg: dll_characteristics & Self::GUARD_CF != 0,
+        }
+    }
+
+    /// Names of the essential mitigations (ASLR and DEP) that are not enabled
+    pub fn missing_essential(&self) -> Vec<String> {
+        let mut missing = Vec::new();
+        if !self.aslr {
+            missing.push("ASLR".to_owned());
+        }
+        if !self.dep {
+            missing.push("DEP".to_owned());
+        }
+        missing
+    }
+}
+
+impl std::fmt::Display for SecurityFeatures {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let features: Vec<&str> = [
+            (self.aslr, "ASLR"),
+            (self.high_entropy_va, "high-entropy VA"),
+            (self.dep, "DEP"),
+            (self.cfg, "CFG"),
+        ]
+        .into_iter()
+        .filter(|(enabled, _)| *enabled)
+        .map(|(_, name)| name)
+        .collect();
+        if features.is_empty() {
+            f.write_str("no mitigations")
+        } else {
+            f.write_str(&features.join(", "))
+        }
+    }
+}
+
+/// Side-by-side assembly referenced by the manifest of an executable
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct AssemblyDependency {
+    /// assembly name, e.g. "Microsoft.VC90.CRT"
+    pub name: String,
+    /// requested version, e.g. "9.0.21022.8"
+    pub version: Option<String>,
+}
+
+/// Fields of the import descriptor of a DLL, as found in the import table
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ImportDescriptor {
+    /// RVA of the import name table (OriginalFirstThunk, a.k.a. Characteristics)
+    pub characteristics: u32,
+    /// zero, unless the imports were bound to a specific build of the DLL
+    pub time_date_stamp: u32,
+    pub forwarder_chain: u32,
+    /// RVA of the import address table (FirstThunk)
+    pub first_thunk: u32,
+}
+
+impl ImportDescriptor {
+    /// The addresses of the imported symbols were precomputed for a specific build of the DLL
+    pub fn is_bound(&self) -> bool {
+        self.time_date_stamp != 0
+    }
+}
+
+/// Entry of the import table of an executable
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct ImportedSymbol {
+    /// name of the DLL, as referenced by the import table
+    pub dll: String,
+    /// name of the symbol, if imported by name
+    pub name: Option<String>,
+    /// ordinal of the symbol, if imported by ordinal
+    pub ordinal: Option<u16>,
+    /// index of the name in the export table of the DLL suggested to the loader, if imported by
+    /// name
+    pub hint: Option<u16>,
+    pub descriptor: ImportDescriptor,
+}
+
+impl ImportedSymbol {
+    pub fn is_by_ordinal(&self) -> bool {
+        self.ordinal.is_some()
+    }
+}
+
 /// Symbols information for a found executable file
-#[derive(Debug, Clone, Serialize)]
+#[derive(Debug, Clone, Serialize, Deserialize)]
 pub struct ExecutableSymbols {
     /// Exported symbols
     pub exported: HashSet<String>,
@@ -47,45 +406,376 @@ pub struct ExecutableSymbols {
     pub imported: HashMap<String, HashSet<String>>,
 }
 
-#[derive(Debug, Clone, Serialize)]
-pub struct ExecutablesCheckReport {
-    /// Map from dependent to list of non found dependees
-    pub not_found_libraries: HashMap<String, HashSet<String>>,
-    /// Map from importer to list of non found imported symbols, grouped by dependent DLL
-    pub not_found_symbols: Option<HashMap<String, HashMap<String, HashSet<String>>>>,
+/// Pattern matched against the raw and the demangled symbol names (see `Executables::find_symbol`)
+#[derive(Debug, Clone)]
+pub enum SymbolPattern {
+    /// shell-like pattern, e.g. "*QString*"
+    Glob(glob::Pattern),
+    Regex(regex::Regex),
 }
 
-impl Default for ExecutablesCheckReport {
-    fn default() -> Self {
-        ExecutablesCheckReport::new()
+impl SymbolPattern {
+    pub fn glob(pattern: &str) -> Result<Self, LookupError> {
+        Ok(Self::Glob(glob::Pattern::new(pattern)?))
+    }
+
+    pub fn regex(pattern: &str) -> Result<Self, LookupError> {
+        Ok(Self::Regex(regex::Regex::new(pattern)?))
+    }
+
+    pub fn matches(&self, name: &str) -> bool {
+        match self {
+            Self::Glob(pattern) => pattern.matches(name),
+            Self::Regex(regex) => regex.is_match(name),
+        }
     }
 }
 
-impl ExecutablesCheckReport {
-    pub fn new() -> Self {
-        Self {
-            not_found_libraries: HashMap::new(),
-            not_found_symbols: None,
+/// How a module uses a symbol found by `Executables::find_symbol`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum SymbolUse {
+    Exported,
+    /// imported from the given DLL
+    Imported(String),
+}
+
+/// Symbol of a module matching the pattern given to `Executables::find_symbol`
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SymbolMatch {
+    /// module exporting or importing the symbol
+    pub dllname: String,
+    pub symbol: String,
+    /// readable name, if the symbol is mangled
+    pub demangled: Option<String>,
+    pub usage: SymbolUse,
+}
+
+impl std::fmt::Display for SymbolMatch {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = self.demangled.as_deref().unwrap_or(&self.symbol);
+        match &self.usage {
+            SymbolUse::Exported => write!(f, "{} exports {name}", self.dllname),
+            SymbolUse::Imported(from) => write!(f, "{} imports {name} from {from}", self.dllname),
         }
     }
+}
 
-    pub fn extend(&mut self, other: ExecutablesCheckReport) {
-        self.not_found_libraries.extend(other.not_found_libraries);
+/// How an executable refers to one of its dependencies
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum DependencyKind {
+    /// listed in the import table, loaded together with the importer
+    Static,
+    /// listed in the delay-load import table, loaded on first use
+    DelayLoad,
+    /// DLL implementing an API set contract
+    ApiSet,
+    /// target of an export forwarder
+    Forwarded,
+    /// DLL name found among the strings of the importer, possibly loaded with LoadLibrary
+    DynamicLoad,
+}
 
-        if let Some(other_symbols) = other.not_found_symbols {
-            if let Some(our_symbols) = self.not_found_symbols.as_mut() {
-                our_symbols.extend(other_symbols)
-            } else {
-                self.not_found_symbols = Some(other_symbols)
+impl std::fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyKind::Static => write!(f, "static"),
+            DependencyKind::DelayLoad => write!(f, "delay-load"),
+            DependencyKind::ApiSet => write!(f, "api set"),
+            DependencyKind::Forwarded => write!(f, "forwarded"),
+            DependencyKind::DynamicLoad => write!(f, "possible dynamic load"),
+        }
+    }
+}
+
+/// Dependency relationship between two executables of the tree
+#[derive(Debug, Clone, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub struct DependencyEdge {
+    /// name of the depending executable
+    pub importer: String,
+    /// name of the dependency, as referenced by the importer
+    pub importee: String,
+    pub kind: DependencyKind,
+}
+
+impl ExecutableDetails {
+    /// Dependencies of this executable, along with the way they are referenced
+    pub fn dependency_edges(&self) -> Vec<(&str, DependencyKind)> {
+        let static_kind = if self.is_api_set {
+            DependencyKind::ApiSet
+        } else {
+            DependencyKind::Static
+        };
+        [
+            (&self.dependencies, static_kind),
+            (&self.delay_load_dependencies, DependencyKind::DelayLoad),
+            (&self.forwarded_dependencies, DependencyKind::Forwarded),
+            (&self.dynamic_load_dependencies, DependencyKind::DynamicLoad),
+        ]
+        .into_iter()
+        .flat_map(|(deps, kind)| deps.iter().flatten().map(move |d| (d.as_str(), kind)))
+        .collect()
+    }
+
+    /// Names of all the dependencies of this executable, regardless of how they are referenced
+    pub fn all_dependencies(&self) -> Vec<&str> {
+        let mut names: Vec<&str> = Vec::new();
+        for (name, _) in self.dependency_edges() {
+            if !names.iter().any(|n| n.eq_ignore_ascii_case(name)) {
+                names.push(name);
             }
         }
+        names
+    }
+}
+
+/// Measurements taken while performing a scan
+///
+/// The time spent in each phase is summed over the threads of the scan, so with a breadth-first
+/// traversal it can exceed the duration of the whole scan.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ScanMetrics {
+    pub duration_ms: u64,
+    /// file lookups answered from the directory listings already cached by the lookup path
+    pub cache_hits: usize,
+    /// file lookups that required reading a directory
+    pub cache_misses: usize,
+    /// time spent listing the directories of the lookup path, in microseconds
+    #[serde(default)]
+    pub directory_listing_us: u64,
+    /// time spent reading and parsing the PE files, except for the symbols, in microseconds
+    #[serde(default)]
+    pub pe_parsing_us: u64,
+    /// time spent extracting the imported and exported symbols, in microseconds
+    #[serde(default)]
+    pub symbol_extraction_us: u64,
+    /// PE files read during the scan
+    #[serde(default)]
+    pub files_parsed: usize,
+    /// files whose details were taken from a previous scan, as they did not change
+    #[serde(default)]
+    pub files_reused: usize,
+}
+
+impl std::fmt::Display for ScanMetrics {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let ms = |us: u64| us as f64 / 1000.0;
+        writeln!(f, "Scan duration: {} ms", self.duration_ms)?;
+        writeln!(
+            f,
+            "Directory listing: {:.1} ms ({} lookups from the cache, {} from disk)",
+            ms(self.directory_listing_us),
+            self.cache_hits,
+            self.cache_misses
+        )?;
+        writeln!(
+            f,
+            "PE parsing: {:.1} ms ({} files parsed, {} reused from the previous scan)",
+            ms(self.pe_parsing_us),
+            self.files_parsed,
+            self.files_reused
+        )?;
+        writeln!(
+            f,
+            "Symbol extraction: {:.1} ms",
+            ms(self.symbol_extraction_us)
+        )
     }
 }
 
+/// Summary statistics of a scan
+#[derive(Debug, Clone, Default, PartialEq, Serialize, Deserialize)]
+pub struct ExecutablesStats {
+    /// found executables outside of the system directories, including the root
+    pub user: usize,
+    /// found DLLs of the system directories, other than API sets and known DLLs
+    pub system: usize,
+    pub api_sets: usize,
+    pub known_dlls: usize,
+    pub missing: usize,
+    /// total size in bytes of the found non-system files
+    pub closure_size: u64,
+    pub max_depth: usize,
+    pub scan_duration_ms: Option<u64>,
+    /// fraction of the file lookups answered from the cache of the lookup path
+    pub cache_hit_rate: Option<f64>,
+}
+
+impl std::fmt::Display for ExecutablesStats {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(
+            f,
+            "Executables: {} user, {} system, {} API sets, {} known DLLs, {} missing",
+            self.user, self.system, self.api_sets, self.known_dlls, self.missing
+        )?;
+        writeln!(
+            f,
+            "Non-system closure size: {:.1} MiB; maximum depth: {}",
+            self.closure_size as f64 / (1024.0 * 1024.0),
+            self.max_depth
+        )?;
+        if let Some(duration) = self.scan_duration_ms {
+            write!(f, "Scan duration: {duration} ms")?;
+            if let Some(rate) = self.cache_hit_rate {
+                write!(f, "; cache hit rate: {:.0}%", rate * 100.0)?;
+            }
+            writeln!(f)?;
+        }
+        Ok(())
+    }
+}
+
+/// Version of the JSON document produced by serializing Executables, increased on breaking changes
+pub const JSON_SCHEMA_VERSION: u32 = 2;
+
+#[derive(Serialize)]
+struct ExecutablesDocumentRef<'a> {
+    schema_version: u32,
+    executables: Vec<&'a Executable>,
+    diagnostics: &'a [Diagnostic],
+    #[serde(skip_serializing_if = "Option::is_none")]
+    scan_metrics: Option<&'a ScanMetrics>,
+    stats: ExecutablesStats,
+}
+
+#[derive(Deserialize)]
+struct ExecutablesDocument {
+    schema_version: u32,
+    executables: Vec<Executable>,
+    #[serde(default)]
+    diagnostics: Vec<Diagnostic>,
+    #[serde(default)]
+    scan_metrics: Option<ScanMetrics>,
+}
+
+#[derive(Deserialize)]
+#[serde(untagged)]
+enum SerializedExecutables {
+    Versioned(ExecutablesDocument),
+    /// list of executables, as written before the introduction of the schema version
+    Legacy(Vec<Executable>),
+}
+
 /// Collection of Executable objects, result of a DLL search
 #[derive(Debug, Clone)]
 pub struct Executables {
-    index: HashMap<String, Executable>,
+    /// executables by the lowercase full path of their file, or by their lowercase name if they
+    /// were not read from a file (e.g. missing DLLs)
+    executables: HashMap<String, Executable>,
+    /// keys of the executables with each lowercase name, in order of insertion
+    names: HashMap<String, Vec<String>>,
+    /// keys of the root executables, in order of insertion
+    roots: Vec<String>,
+    edges: Vec<DependencyEdge>,
+    diagnostics: Vec<Diagnostic>,
+    scan_metrics: Option<ScanMetrics>,
+    /// name of the executable loading the roots, and the side-by-side assemblies it requests, if
+    /// they were scanned as loaded by it
+    host: Option<(String, Vec<AssemblyDependency>)>,
+}
+
+impl Serialize for Executables {
+    fn serialize<S: serde::Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        ExecutablesDocumentRef {
+            schema_version: JSON_SCHEMA_VERSION,
+            executables: self.sorted_by_first_appearance(),
+            diagnostics: &self.diagnostics,
+            scan_metrics: self.scan_metrics.as_ref(),
+            stats: self.stats(),
+        }
+        .serialize(serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for Executables {
+    fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        let (executables, diagnostics, scan_metrics) =
+            match SerializedExecutables::deserialize(deserializer)? {
+                SerializedExecutables::Versioned(document) => {
+                    if document.schema_version > JSON_SCHEMA_VERSION {
+                        return Err(serde::de::Error::custom(format!(
+                        "unsupported schema version {} (latest supported: {JSON_SCHEMA_VERSION})",
+                        document.schema_version
+                    )));
+                    }
+                    (
+                        document.executables,
+                        document.diagnostics,
+                        document.scan_metrics,
+                    )
+                }
+                SerializedExecutables::Legacy(executables) => (executables, Vec::new(), None),
+            };
+        let mut ret = Self::new();
+        // written by a scan, so the duplicates were kept on purpose, and already reported
+        for e in executables {
+            ret.insert_with_policy(e, DuplicatePolicy::KeepAll)
+                .map_err(serde::de::Error::custom)?;
+        }
+        ret.diagnostics = diagnostics;
+        ret.scan_metrics = scan_metrics;
+        Ok(ret)
+    }
+}
+
+impl<'a> IntoIterator for &'a Executables {
+    type Item = &'a Executable;
+    type IntoIter = std::vec::IntoIter<&'a Executable>;
+
+    fn into_iter(self) -> Self::IntoIter {
+        self.iter()
+    }
+}
+
+impl IntoIterator for Executables {
+    type Item = Executable;
+    type IntoIter = std::vec::IntoIter<Executable>;
+
+    /// The executables in order of first appearance
+    fn into_iter(self) -> Self::IntoIter {
+        let roots = self.roots;
+        let mut executables: Vec<_> = self
+            .executables
+            .into_iter()
+            .map(|(key, e)| (Self::sort_key(&roots, &key, &e), e))
+            .collect();
+        executables.sort_by(|(a_key, a), (b_key, b)| {
+            a.depth_first_appearance
+                .cmp(&b.depth_first_appearance)
+                .then_with(|| a_key.cmp(b_key))
+        });
+        executables
+            .into_iter()
+            .map(|(_, e)| e)
+            .collect::<Vec<_>>()
+            .into_iter()
+    }
+}
+
+/// Executables added as by a scan, the ones with the name of a previous one being dropped
+impl Extend<Executable> for Executables {
+    fn extend<I: IntoIterator<Item = Executable>>(&mut self, iter: I) {
+        for e in iter {
+            self.insert(e);
+        }
+    }
+}
+
+impl FromIterator<Executable> for Executables {
+    fn from_iter<I: IntoIterator<Item = Executable>>(iter: I) -> Self {
+        let mut ret = Self::new();
+        ret.extend(iter);
+        ret
+    }
+}
+
+/// Executable with the given name, panicking if there is none (see `Executables::get`)
+impl std::ops::Index<&str> for Executables {
+    type Output = Executable;
+
+    fn index(&self, dllname: &str) -> &Executable {
+        self.get(dllname)
+            .unwrap_or_else(|| panic!("no executable named {dllname}"))
+    }
 }
 
 impl Default for Executables {
@@ -97,170 +787,1035 @@ impl Default for Executables {
 impl Executables {
     pub fn new() -> Self {
         Self {
-            index: HashMap::new(),
+            executables: HashMap::new(),
+            names: HashMap::new(),
+            roots: Vec::new(),
+            edges: Vec::new(),
+            diagnostics: Vec::new(),
+            scan_metrics: None,
+            host: None,
         }
     }
 
+    /// Read back executables serialized to JSON, either as a versioned document (as done by
+    /// deprun) or as a plain list (as done by older versions)
+    pub fn from_json(s: &str) -> Result<Self, LookupError> {
+        Ok(serde_json::from_str(s)?)
+    }
+
+    /// Read back executables serialized to a JSON file
+    pub fn from_json_file<P: AsRef<Path>>(p: P) -> Result<Self, LookupError> {
+        Self::from_json(&fs::read_to_string(p.as_ref())?)
+    }
+
     pub fn len(&self) -> usize {
-        self.index.len()
+        self.executables.len()
     }
 
     pub fn is_empty(&self) -> bool {
-        self.index.is_empty()
+        self.executables.is_empty()
     }
 
+    /// Executable with the given name, the first one inserted if several files have it
     pub fn get(&self, dllname: &str) -> Option<&Executable> {
-        self.index.get(&dllname.to_lowercase())
+        self.names
+            .get(&dllname.to_lowercase())
+            .and_then(|keys| keys.first())
+            .and_then(|key| self.executables.get(key))
+    }
+
+    /// All the executables with the given name, in order of insertion (more than one only if they
+    /// were kept with `DuplicatePolicy::KeepAll`)
+    pub fn get_all(&self, dllname: &str) -> Vec<&Executable> {
+        self.names
+            .get(&dllname.to_lowercase())
+            .map(|keys| {
+                keys.iter()
+                    .filter_map(|k| self.executables.get(k))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Executable read from the given file
+    pub fn get_by_path<P: AsRef<Path>>(&self, path: P) -> Option<&Executable> {
+        self.executables
+            .get(&path_to_string(path.as_ref()).to_lowercase())
     }
 
     pub fn contains(&self, dllname: &str) -> bool {
-        self.index.contains_key(&dllname.to_lowercase())
+        self.names.contains_key(&dllname.to_lowercase())
     }
 
-    /// Get the root executable file (i.e. the only one with depth equal to zero)
-    pub fn get_root(&self) -> Result<Option<&Executable>, LookupError> {
-        if self.index.is_empty() {
-            return Ok(None);
+    /// Get the root executable files (i.e. the ones with depth equal to zero), in order of
+    /// insertion
+    ///
+    /// A scan has one root for each target of the query, e.g. an executable and its plugins; the
+    /// target executable of the query comes first.
+    pub fn get_roots(&self) -> Result<Vec<&Executable>, LookupError> {
+        if self.executables.is_empty() {
+            return Ok(Vec::new());
         }
-        let root_candidates: Vec<&Executable> = self
-            .index
-            .values()
-            .filter(|v| v.depth_first_appearance == 0)
-            .collect();
-        if root_candidates.is_empty() {
+        if self.roots.is_empty() {
             return Err(LookupError::ScanError(
                 "The executable tree has no roots".to_string(),
             ));
         }
-        if root_candidates.len() > 1 {
-            let names: Vec<&str> = root_candidates.iter().map(|n| n.dllname.as_ref()).collect();
-            return Err(LookupError::ScanError(format!(
-                "The executable tree has multiple roots: {}",
-                names.join(";")
-            )));
-        }
-        Ok(root_candidates.first().copied())
+        Ok(self
+            .roots
+            .iter()
+            .filter_map(|key| self.executables.get(key))
+            .collect())
+    }
+
+    /// Names of the root executable files, joined with the given separator (empty if there are
+    /// none)
+    pub fn root_names(&self, separator: &str) -> String {
+        self.get_roots()
+            .unwrap_or_default()
+            .iter()
+            .map(|r| r.dllname.as_str())
+            .collect::<Vec<_>>()
+            .join(separator)
     }
 
+    /// Executables sorted by depth, then by name (the roots in order of insertion), so that the
+    /// order is stable across runs
     pub fn sorted_by_first_appearance(&self) -> Vec<&Executable> {
-        let mut sorted_executables: Vec<_> = self.index.values().collect();
-        sorted_executables
-            .sort_by(|e1, e2| e1.depth_first_appearance.cmp(&e2.depth_first_appearance));
-        sorted_executables
+        let mut sorted_executables: Vec<_> = self
+            .executables
+            .iter()
+            .map(|(key, e)| (Self::sort_key(&self.roots, key, e), e))
+            .collect();
+        sorted_executables.sort_by(|(a_key, a), (b_key, b)| {
+            a.depth_first_appearance
+                .cmp(&b.depth_first_appearance)
+                .then_with(|| a_key.cmp(b_key))
+        });
+        sorted_executables.into_iter().map(|(_, e)| e).collect()
+    }
+
+    /// Iterate over the executables in order of first appearance (see `sorted_by_first_appearance`)
+    pub fn iter(&self) -> std::vec::IntoIter<&Executable> {
+        self.sorted_by_first_appearance().into_iter()
+    }
+
+    /// Copy of these executables with only the ones matching the predicate, keeping the
+    /// diagnostics and the scan metrics
+    pub fn filter<F: FnMut(&Executable) -> bool>(&self, predicate: F) -> Executables {
+        let mut ret = self.clone();
+        ret.retain(predicate);
+        ret
+    }
+
+    /// Keep only the executables matching the predicate, and the dependency edges of their
+    /// importers
+    pub fn retain<F: FnMut(&Executable) -> bool>(&mut self, mut predicate: F) {
+        self.executables.retain(|_, e| predicate(e));
+        for keys in self.names.values_mut() {
+            keys.retain(|k| self.executables.contains_key(k));
+        }
+        self.names.retain(|_, keys| !keys.is_empty());
+        self.roots.retain(|k| self.executables.contains_key(k));
+        let names = &self.names;
+        self.edges
+            .retain(|e| names.contains_key(&e.importer.to_lowercase()));
+    }
+
+    /// Symbols matching the pattern, by raw or demangled name, exported or imported by the
+    /// executables, in order of first appearance of the modules
+    ///
+    /// The symbols must have been extracted by the scan (see `LookupParameters::extract_symbols`);
+    /// the ones imported by ordinal are skipped.
+    pub fn find_symbol(&self, pattern: &SymbolPattern) -> Vec<SymbolMatch> {
+        self.iter()
+            .flat_map(|exe| exe.find_symbol(pattern))
+            .collect()
+    }
+
+    /// Files to ship with the application: the found dependencies which are neither system DLLs
+    /// nor KnownDLLs nor API sets, plus the ones the policy includes anyway, and the roots if the
+    /// policy says so
+    ///
+    /// The roots lying under the directory of the first root (e.g. plugins) keep their relative
+    /// location; the DLLs are installed in the installation directory itself, next to the
+    /// executable, where the loader looks first. Each file is listed once, in order of first
+    /// appearance.
+    pub fn deployment_closure(
+        &self,
+        policy: &DeploymentPolicy,
+    ) -> Result<Vec<DeploymentFile>, LookupError> {
+        let app_dir = self
+            .get_roots()?
+            .first()
+            .and_then(|r| r.details.as_ref())
+            .and_then(|d| d.full_path.as_deref()?.parent())
+            .map(Path::to_path_buf);
+        let mut sources = HashSet::new();
+        let mut files = Vec::new();
+        for exe in self.iter() {
+            if exe.depth_first_appearance == 0 && !policy.include_roots {
+                continue;
+            }
+            let Some(details) = exe.details.as_ref().filter(|_| exe.found) else {
+                continue;
+            };
+            if details.is_api_set || policy.exclude.matches(&exe.dllname) {
+                continue;
+            }
+            if (details.is_system || details.is_known_dll)
+                && exe.depth_first_appearance != 0
+                && !policy.include.matches(&exe.dllname)
+            {
+                continue;
+            }
+            let Some(full_path) = details.full_path.as_ref() else {
+                continue;
+            };
+            let Some(file_name) = full_path.file_name() else {
+                continue;
+            };
+            if !sources.insert(full_path.clone()) {
+                continue;
+            }
+            let destination = app_dir
+                .as_ref()
+                .filter(|_| exe.depth_first_appearance == 0)
+                .and_then(|dir| full_path.strip_prefix(dir).ok())
+                .map(Path::to_path_buf)
+                .unwrap_or_else(|| PathBuf::from(file_name));
+            files.push(DeploymentFile {
+                dllname: exe.dllname.clone(),
+                source: full_path.clone(),
+                destination,
+            });
+        }
+        Ok(files)
+    }
+
+    /// Order of the executables with the same depth: the roots in order of insertion, the others
+    /// by name, then by path
+    fn sort_key(roots: &[String], key: &str, e: &Executable) -> (usize, String) {
+        let position = roots.iter().position(|r| r == key).unwrap_or(0);
+        (position, format!("{}\0{key}", e.dllname.to_lowercase()))
+    }
+
+    /// Dependency relationships between the executables, in order of insertion of the importers
+    pub fn edges(&self) -> &[DependencyEdge] {
+        &self.edges
+    }
+
+    /// Edges pointing to the given DLL, i.e. the executables depending on it
+    pub fn importers_of(&self, dllname: &str) -> Vec<&DependencyEdge> {
+        self.edges
+            .iter()
+            .filter(|e| e.importee.eq_ignore_ascii_case(dllname))
+            .collect()
+    }
+
+    /// Shortest chains of dependencies leading from a root executable to the given DLL, each as
+    /// the list of names from the root to the DLL (empty if the DLL is not part of the tree)
+    pub fn dependency_chains(&self, dllname: &str) -> Result<Vec<Vec<String>>, LookupError> {
+        let roots: Vec<String> = self
+            .get_roots()?
+            .iter()
+            .map(|r| r.dllname.to_lowercase())
+            .collect();
+        let target = dllname.to_lowercase();
+
+        // breadth-first visit, recording the importers through which each DLL is first reached
+        let mut importers: HashMap<String, Vec<String>> = HashMap::new();
+        let mut visited: HashSet<String> = roots.iter().cloned().collect();
+        let mut level = roots.clone();
+        while !level.is_empty() && !visited.contains(&target) {
+            let mut next_level: Vec<String> = Vec::new();
+            for importer in &level {
+                for edge in self
+                    .edges
+                    .iter()
+                    .filter(|e| e.importer.eq_ignore_ascii_case(importer))
+                {
+                    let importee = edge.importee.to_lowercase();
+                    if visited.contains(&importee) {
+                        continue;
+                    }
+                    let importee_importers = importers.entry(importee.clone()).or_default();
+                    if !importee_importers.contains(importer) {
+                        importee_importers.push(importer.clone());
+                    }
+                    if !next_level.contains(&importee) {
+                        next_level.push(importee);
+                    }
+                }
+            }
+            visited.extend(next_level.iter().cloned());
+            level = next_level;
+        }
+        if !visited.contains(&target) {
+            return Ok(Vec::new());
+        }
+
+        // walk back from the DLL to the root through all the recorded importers
+        let mut chains: Vec<Vec<String>> = Vec::new();
+        let mut partial_chains = vec![vec![target]];
+        while let Some(chain) = partial_chains.pop() {
+            let last = chain.last().expect("chains are never empty");
+            if roots.contains(last) {
+                chains.push(
+                    chain
+                        .iter()
+                        .rev()
+                        .map(|n| self.get(n).map(|e| e.dllname.clone()).unwrap_or(n.clone()))
+                        .collect(),
+                );
+                continue;
+            }
+            for importer in importers.get(last).into_iter().flatten() {
+                let mut longer_chain = chain.clone();
+                longer_chain.push(importer.clone());
+                partial_chains.push(longer_chain);
+            }
+        }
+        chains.sort();
+        Ok(chains)
+    }
+
+    /// Duration of the scan and use of the lookup cache, if the executables come from a scan
+    pub fn scan_metrics(&self) -> Option<&ScanMetrics> {
+        self.scan_metrics.as_ref()
+    }
+
+    pub(crate) fn set_scan_metrics(&mut self, scan_metrics: ScanMetrics) {
+        self.scan_metrics = Some(scan_metrics);
+    }
+
+    /// Set the executable the roots were scanned as loaded by, whose side-by-side assemblies are
+    /// checked along with the ones of the executables
+    pub(crate) fn set_host(&mut self, name: String, assemblies: Vec<AssemblyDependency>) {
+        self.host = Some((name, assemblies));
+    }
+
+    /// Keep only the exported symbols imported by the other executables, which are the ones the
+    /// symbol check needs (the exports of a few large DLLs are often most of the symbols of a scan)
+    ///
+    /// The result should not be rescanned by a query extracting symbols without `low_memory`, as
+    /// the exports used by new importers would be missing.
+    pub fn prune_exported_symbols(&mut self) {
+        let imported = self.imported_symbols();
+        for exe in self.executables.values_mut() {
+            let used = imported.get(&exe.dllname.to_lowercase());
+            if let Some(symbols) = exe.details.as_mut().and_then(|d| d.symbols.as_mut()) {
+                symbols
+                    .exported
+                    .retain(|s| used.is_some_and(|u| u.contains(s)));
+                symbols.exported.shrink_to_fit();
+            }
+        }
+    }
+
+    /// Read the exported symbols imported by the other executables, once the scan is complete
+    /// (see `LookupParameters::low_memory`)
+    ///
+    /// The exports of each file are read with the given function, one file at a time, and only
+    /// the imported ones are kept. The files that cannot be read again are reported as
+    /// diagnostics, and keep no exports.
+    pub(crate) fn read_used_exported_symbols<F>(&mut self, mut read_exports: F)
+    where
+        F: FnMut(&Path) -> Result<HashSet<String>, LookupError>,
+    {
+        let imported = self.imported_symbols();
+        let mut diagnostics = Vec::new();
+        for exe in self.executables.values_mut() {
+            let Some(details) = exe.details.as_mut() else {
+                continue;
+            };
+            let (Some(symbols), Some(path)) = (details.symbols.as_mut(), &details.full_path) else {
+                continue;
+            };
+            symbols.exported.clear();
+            // the DLLs nothing imports from, e.g. the targets, are not read again
+            let Some(used) = imported.get(&exe.dllname.to_lowercase()) else {
+                continue;
+            };
+            match read_exports(path) {
+                Ok(exported) => symbols.exported = exported.intersection(used).cloned().collect(),
+                Err(e) => diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::SymbolExtractionError,
+                    Some(path),
+                    format!("Error extracting symbols: {e}"),
+                )),
+            }
+        }
+        self.diagnostics.extend(diagnostics);
+    }
+
+    /// Names of the symbols imported from each DLL (by lowercase name) by these executables
+    fn imported_symbols(&self) -> HashMap<String, HashSet<String>> {
+        let mut imported: HashMap<String, HashSet<String>> = HashMap::new();
+        for exe in self.executables.values() {
+            if let Some(symbols) = exe.details.as_ref().and_then(|d| d.symbols.as_ref()) {
+                for (dll_name, names) in &symbols.imported {
+                    imported
+                        .entry(dll_name.to_lowercase())
+                        .or_default()
+                        .extend(names.iter().cloned());
+                }
+            }
+        }
+        imported
     }
 
-    /// Add a new executable
+    /// Summary statistics: number of executables per kind, size of the non-system closure,
+    /// depth of the tree and, if available, duration of the scan and cache hit rate
+    pub fn stats(&self) -> ExecutablesStats {
+        let mut stats = ExecutablesStats::default();
+        for e in self.executables.values() {
+            stats.max_depth = stats.max_depth.max(e.depth_first_appearance);
+            match e.details.as_ref() {
+                _ if !e.found => stats.missing += 1,
+                // found without being read, e.g. assumed present by a DLL override
+                None => {}
+                Some(d) if d.is_api_set => stats.api_sets += 1,
+                Some(d) if d.is_known_dll => stats.known_dlls += 1,
+                Some(d) if d.is_system => stats.system += 1,
+                Some(d) => {
+                    stats.user += 1;
+                    stats.closure_size += d.file_stamp.as_ref().map(|s| s.size).unwrap_or(0);
+                }
+            }
+        }
+        if let Some(metrics) = &self.scan_metrics {
+            stats.scan_duration_ms = Some(metrics.duration_ms);
+            let lookups = metrics.cache_hits + metrics.cache_misses;
+            if lookups > 0 {
+                stats.cache_hit_rate = Some(metrics.cache_hits as f64 / lookups as f64);
+            }
+        }
+        stats
+    }
+
+    /// Non-fatal issues found during the scan
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    pub(crate) fn add_diagnostics<I: IntoIterator<Item = Diagnostic>>(&mut self, diagnostics: I) {
+        self.diagnostics.extend(diagnostics);
+    }
+
+    /// Add a new executable, dropping it if another file with the same name was already added
     pub(crate) fn insert(&mut self, new_exe: Executable) {
+        // first-wins never fails
+        let _ = self.insert_with_policy(new_exe, DuplicatePolicy::FirstWins);
+    }
+
+    /// Add a new executable, handling another file with the same name according to the policy
+    ///
+    /// Adding the same file twice has no effect.
+    pub(crate) fn insert_with_policy(
+        &mut self,
+        new_exe: Executable,
+        policy: DuplicatePolicy,
+    ) -> Result<(), LookupError> {
+        // the API sets share the path of their host
+        let key = match new_exe.details.as_ref().and_then(|d| d.full_path.as_ref()) {
+            Some(path) if !new_exe.details.as_ref().is_some_and(|d| d.is_api_set) => {
+                path_to_string(path).to_lowercase()
+            }
+            _ => new_exe.dllname.to_lowercase(),
+        };
+        if self.executables.contains_key(&key) {
+            return Ok(());
+        }
         if let Some(older_finding) = self.get(&new_exe.dllname) {
-            eprintln!(
-                "Found two DLLs with the same name! {:?} and {:?}",
-                new_exe
-                    .details
+            let describe = |e: &Executable| {
+                e.details
                     .as_ref()
-                    .and_then(|d| readable_canonical_path(&d.full_path).ok())
-                    .unwrap_or(new_exe.dllname),
-                older_finding
-                    .details
-                    .as_ref()
-                    .and_then(|d| readable_canonical_path(&d.full_path).ok())
-                    .unwrap_or_else(|| older_finding.dllname.clone()),
+                    .and_then(|d| d.full_path.as_deref())
+                    .map(readable_path)
+                    .unwrap_or_else(|| e.dllname.clone())
+            };
+            let (first, second) = (describe(older_finding), describe(&new_exe));
+            if policy == DuplicatePolicy::Error {
+                return Err(LookupError::DuplicateName {
+                    dllname: new_exe.dllname,
+                    first,
+                    second,
+                });
+            }
+            let diagnostic = Diagnostic::new(
+                DiagnosticKind::DuplicateName,
+                new_exe.details.as_ref().and_then(|d| d.full_path.as_ref()),
+                format!("Found two DLLs with the same name! {second:?} and {first:?}"),
+            );
+            self.diagnostics.push(diagnostic);
+            if policy == DuplicatePolicy::FirstWins {
+                return Ok(());
+            }
+        }
+        if let Some(details) = new_exe.details.as_ref() {
+            self.edges.extend(
+                details
+                    .dependency_edges()
+                    .into_iter()
+                    .map(|(importee, kind)| DependencyEdge {
+                        importer: new_exe.dllname.clone(),
+                        importee: importee.to_owned(),
+                        kind,
+                    }),
             );
-        } else {
-            self.index.insert(new_exe.dllname.to_lowercase(), new_exe);
         }
+        self.names
+            .entry(new_exe.dllname.to_lowercase())
+            .or_default()
+            .push(key.clone());
+        if new_exe.depth_first_appearance == 0 {
+            self.roots.push(key.clone());
+        }
+        self.executables.insert(key, new_exe);
+        Ok(())
     }
 
-    /// Check that all referenced DLLs are found, and (if available) that imported symbols are present
+    /// Check that all referenced DLLs are found, that they match the architecture of their
+    /// importers, that side-by-side assembly versions agree, that C runtimes are not mixed and (if
+    /// available) that imported symbols are present and that no dependency is linked without
+    /// importing anything from it
+    ///
+    /// The findings are sorted by decreasing severity.
     pub fn check(&self, extract_symbols: bool) -> Result<ExecutablesCheckReport, LookupError> {
-        let mut report = ExecutablesCheckReport::new();
+        let mut report = self.check_missing();
 
         if extract_symbols {
-            let symbols_report = self
-                .index
-                .values()
-                .map(|e| self.check_imports(&e.dllname))
-                .fold(ExecutablesCheckReport::new(), |mut r, pr| {
-                    if let Ok(rr) = pr {
-                        r.extend(rr);
-                    }
-                    r
-                });
-            report.extend(symbols_report);
+            for e in self.executables.values() {
+                report.extend(self.check_imports(e));
+            }
+            report.extend(self.check_load_only());
         }
 
+        report.extend(self.check_architectures());
+        report.extend(self.check_versions());
+        report.extend(self.check_crt()?);
+        report.sort();
+
         Ok(report)
     }
 
-    /// Check that every dependency exports the symbols imported by this file
-    fn check_imports(&self, name: &str) -> Result<ExecutablesCheckReport, LookupError> {
-        let exe = self
-            .get(name)
-            .ok_or_else(|| LookupError::ScanError(format!("Could not find file {name}")))?;
+    /// Check that all dependencies were found; missing delay-load and forwarded dependencies are
+    /// only warnings, since the importer can be loaded without them, and missing possible dynamic
+    /// loads are only informational
+    ///
+    /// The findings about Visual C++ runtime DLLs suggest the Redistributable package installing
+    /// them, and the ones about optional system DLLs the Windows feature providing them.
+    fn check_missing(&self) -> ExecutablesCheckReport {
+        let features = WindowsFeatures::bundled();
+        let mut missing: HashMap<(String, String), Severity> = HashMap::new();
+        for edge in &self.edges {
+            if self.get(&edge.importee).map(|e| e.found).unwrap_or(true) {
+                continue;
+            }
+            let severity = match edge.kind {
+                DependencyKind::Static | DependencyKind::ApiSet => Severity::Error,
+                DependencyKind::DelayLoad | DependencyKind::Forwarded => Severity::Warning,
+                DependencyKind::DynamicLoad => Severity::Info,
+            };
+            let entry = missing
+                .entry((edge.importer.clone(), edge.importee.clone()))
+                .or_insert(severity);
+            *entry = (*entry).max(severity);
+        }
+        missing
+            .into_iter()
+            .map(|((importer, dll), severity)| {
+                let architecture = self
+                    .get(&importer)
+                    .and_then(|e| e.details.as_ref())
+                    .and_then(|d| d.architecture);
+                let suggestion = runtime_suggestion(&dll, architecture).or_else(|| {
+                    features
+                        .feature_providing(&dll)
+                        .map(|f| format!("provided by {f}"))
+                });
+                Finding::new(severity, FindingKind::MissingDll { importer, dll })
+                    .with_suggestion(suggestion)
+            })
+            .collect()
+    }
 
-        if exe.details.as_ref().map(|d| d.is_api_set).unwrap_or(true) {
-            return Ok(ExecutablesCheckReport::new());
+    /// Check that the non-system dependencies were built for the same machine as their importers
+    ///
+    /// System DLLs are skipped, since the loader redirects 32-bit processes to the WOW64 system
+    /// directory.
+    fn check_architectures(&self) -> ExecutablesCheckReport {
+        let mut report = ExecutablesCheckReport::new();
+        for exe in self.executables.values() {
+            let details = match exe.details.as_ref() {
+                Some(details) => details,
+                None => continue,
+            };
+            let importer_architecture = match details.architecture {
+                Some(architecture) => architecture,
+                None => continue,
+            };
+            for dep_name in details.all_dependencies() {
+                let dep_details = match self.get(dep_name).and_then(|d| d.details.as_ref()) {
+                    Some(dep_details) if !dep_details.is_system && !dep_details.is_api_set => {
+                        dep_details
+                    }
+                    _ => continue,
+                };
+                if let Some(dll_architecture) = dep_details.architecture {
+                    if dll_architecture != importer_architecture {
+                        report.push(Finding::new(
+                            Severity::Error,
+                            FindingKind::ArchMismatch {
+                                importer: exe.dllname.clone(),
+                                importer_architecture,
+                                dll: dep_name.to_owned(),
+                                dll_architecture,
+                            },
+                        ));
+                    }
+                }
+            }
         }
+        report
+    }
 
-        let imported_symbols = &exe
-            .details
-            .as_ref()
-            .ok_or_else(|| {
-                LookupError::ScanError(format!("Could not find details for file {name}"))
-            })?
-            .symbols
-            .as_ref()
-            .ok_or_else(|| {
-                LookupError::ScanError(format!("Could not find symbols for file {name}"))
-            })?
-            .imported;
+    /// Check that the non-system executables are built with ASLR and DEP
+    ///
+    /// Not part of `check`, since many third-party DLLs are built without them: security reviews
+    /// of the dependency closure can run it in addition.
+    pub fn check_security(&self) -> ExecutablesCheckReport {
+        let mut report: ExecutablesCheckReport = self
+            .executables
+            .values()
+            .filter_map(|exe| {
+                let details = exe.details.as_ref()?;
+                if details.is_system || details.is_api_set {
+                    return None;
+                }
+                let missing = details.security?.missing_essential();
+                (!missing.is_empty()).then(|| {
+                    Finding::new(
+                        Severity::Warning,
+                        FindingKind::MissingMitigations {
+                            dll: exe.dllname.clone(),
+                            missing,
+                        },
+                    )
+                })
+            })
+            .collect();
+        report.sort();
+        report
+    }
 
-        let mut missing_imports = ExecutablesCheckReport::new();
+    /// Check that all executables request the same version of each side-by-side assembly
+    fn check_versions(&self) -> ExecutablesCheckReport {
+        let mut requested_versions: HashMap<String, BTreeMap<String, BTreeSet<String>>> =
+            HashMap::new();
+        let executables = self.executables.values().map(|exe| {
+            let assemblies = exe
+                .details
+                .as_ref()
+                .and_then(|d| d.assembly_dependencies.as_deref());
+            (&exe.dllname, assemblies.unwrap_or_default())
+        });
+        let host = self
+            .host
+            .iter()
+            .map(|(name, assemblies)| (name, assemblies.as_slice()));
+        for (name, assemblies) in executables.chain(host) {
+            for assembly in assemblies {
+                requested_versions
+                    .entry(assembly.name.to_lowercase())
+                    .or_default()
+                    .entry(assembly.version.clone().unwrap_or_default())
+                    .or_default()
+                    .insert(name.clone());
+            }
+        }
+
+        requested_versions
+            .into_iter()
+            .filter(|(_, versions)| versions.len() > 1)
+            .map(|(assembly, versions)| {
+                Finding::new(
+                    Severity::Warning,
+                    FindingKind::VersionConflict { assembly, versions },
+                )
+            })
+            .collect()
+    }
+
+    /// Check that the tree does not mix debug and release builds of the C/C++ runtime, nor
+    /// several generations of the Visual C++ runtime
+    fn check_crt(&self) -> Result<ExecutablesCheckReport, LookupError> {
+        // e.g. msvcr120.dll, msvcp140d.dll, msvcp140_1d.dll, vcruntime140_1.dll
+        let vc_runtime = regex::Regex::new(
+            r"^(?:msvcr|msvcp|vcruntime)(\d{2,3})(d?)(?:_[a-z0-9_]+?)?(d?)\.dll$",
+        )?;
+        let mut debug: BTreeSet<String> = BTreeSet::new();
+        let mut release: BTreeSet<String> = BTreeSet::new();
+        let mut generations: BTreeMap<String, BTreeSet<String>> = BTreeMap::new();
+        for exe in self.executables.values() {
+            let name = exe.dllname.to_lowercase();
+            let is_debug = if let Some(captures) = vc_runtime.captures(&name) {
+                generations
+                    .entry(captures[1].to_owned())
+                    .or_default()
+                    .insert(exe.dllname.clone());
+                !captures[2].is_empty() || !captures[3].is_empty()
+            } else if name == "ucrtbase.dll" || name == "ucrtbased.dll" {
+                name == "ucrtbased.dll"
+            } else {
+                continue;
+            };
+            if is_debug {
+                debug.insert(exe.dllname.clone());
+            } else {
+                release.insert(exe.dllname.clone());
+            }
+        }
 
-        for dll_name in imported_symbols.keys() {
-            if let Some(dll_exe) = self.get(dll_name) {
-                // TODO: following should distinguish if not found (in case report missing library), or if system/api set
-                if dll_exe.found {
-                    if !dll_exe
-                        .details
-                        .as_ref()
-                        .map(|d| d.is_system)
-                        .unwrap_or(true)
+        let mut report = ExecutablesCheckReport::new();
+        if !debug.is_empty() && !release.is_empty() {
+            report.push(Finding::new(
+                Severity::Warning,
+                FindingKind::MixedCrt { debug, release },
+            ));
+        }
+        if generations.len() > 1 {
+            report.push(Finding::new(
+                Severity::Warning,
+                FindingKind::CrtGenerations { generations },
+            ));
+        }
+        Ok(report)
+    }
+
+    /// Check that the API set contracts and the system symbols imported by the non-system
+    /// executables are available on the given target Windows version
+    ///
+    /// The symbols are only checked if they were extracted during the scan.
+    pub fn check_target_os(&self, profile: &OsProfile) -> ExecutablesCheckReport {
+        let availability = SymbolAvailability::bundled();
+        let mut report = ExecutablesCheckReport::new();
+        let target_os = &profile.description;
+        for exe in self.executables.values() {
+            let details = match exe.details.as_ref() {
+                Some(d) if !d.is_system && !d.is_api_set => d,
+                _ => continue,
+            };
+            // contracts that were not found at all are reported by check_missing
+            if !profile.api_sets.is_empty() {
+                for dep_name in details.all_dependencies() {
+                    let found = self.get(dep_name).map(|e| e.found).unwrap_or(false);
+                    if found
+                        && dep_name.to_lowercase().starts_with("api-ms-")
+                        && !profile.provides_api_set(dep_name)
                     {
-                        let res = self.check_symbols(name, dll_name)?;
-                        missing_imports.extend(res);
+                        report.push(Finding::new(
+                            Severity::Error,
+                            FindingKind::UnavailableOnTargetOs {
+                                importer: exe.dllname.clone(),
+                                dll: dep_name.to_owned(),
+                                symbol: None,
+                                target_os: target_os.clone(),
+                            },
+                        ));
                     }
-                } else {
-                    missing_imports
-                        .not_found_libraries
-                        .entry(name.to_owned())
-                        .or_default()
-                        .insert(dll_name.clone());
                 }
-            } else {
-                // TODO: it was not looked up
+            }
+            let imported = details.symbols.as_ref().map(|s| &s.imported);
+            for (dll_name, symbols) in imported.into_iter().flatten() {
+                let mut unavailable: Vec<&String> = symbols
+                    .iter()
+                    .filter(|s| {
+                        availability
+                            .introduced_in(dll_name, s)
+                            .is_some_and(|build| build > profile.build)
+                    })
+                    .collect();
+                unavailable.sort();
+                for symbol in unavailable {
+                    report.push(Finding::new(
+                        Severity::Error,
+                        FindingKind::UnavailableOnTargetOs {
+                            importer: exe.dllname.clone(),
+                            dll: dll_name.clone(),
+                            symbol: Some(symbol.clone()),
+                            target_os: target_os.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+        report.sort();
+        report
+    }
+
+    /// Check that every found non-system executable lies in one of the given directories (or their
+    /// subdirectories), e.g. that an installation does not depend on DLLs found along the PATH
+    pub fn check_allowed_directories<P: AsRef<Path>>(
+        &self,
+        allowed_dirs: &[P],
+    ) -> ExecutablesCheckReport {
+        // compared case-insensitively and without the extended path prefix
+        let comparable =
+            |p: &Path| PathBuf::from(decanonicalize(&path_to_string(p)).to_lowercase());
+        let allowed_dirs: Vec<PathBuf> = allowed_dirs
+            .iter()
+            .map(|d| comparable(d.as_ref()))
+            .collect();
+        let mut report: ExecutablesCheckReport = self
+            .executables
+            .values()
+            .filter_map(|exe| {
+                let details = exe.details.as_ref()?;
+                let full_path = details.full_path.as_ref()?;
+                if !exe.found || details.is_system || details.is_api_set {
+                    return None;
+                }
+                let path = comparable(full_path);
+                (!allowed_dirs.iter().any(|d| path.starts_with(d))).then(|| {
+                    Finding::new(
+                        Severity::Warning,
+                        FindingKind::OutsideAllowedDirectories {
+                            dll: exe.dllname.clone(),
+                            path: full_path.clone(),
+                        },
+                    )
+                })
+            })
+            .collect();
+        report.sort();
+        report
+    }
+
+    /// Check the found non-system dependencies against a lock file: every locked dependency must
+    /// be found at a path matching its pattern, with the locked version and digest (if they are
+    /// recorded, and the digests computed by the scan); the dependencies missing from the lock file
+    /// are only warnings
+    pub fn check_lock(&self, lock: &DependencyLock) -> Result<ExecutablesCheckReport, LookupError> {
+        let app_dir = lock::app_dir(self)?;
+        let mut report = ExecutablesCheckReport::new();
+        let mut mismatch = |severity, dll: &str, property: &str, expected, found| {
+            report.push(Finding::new(
+                severity,
+                FindingKind::LockMismatch {
+                    dll: dll.to_owned(),
+                    property: property.to_owned(),
+                    expected,
+                    found,
+                },
+            ))
+        };
+        for (dllname, locked) in &lock.dependencies {
+            let Some((details, full_path)) = self.get(dllname).and_then(lock::lockable_details)
+            else {
+                mismatch(
+                    Severity::Error,
+                    dllname,
+                    "path",
+                    Some(locked.path.clone()),
+                    None,
+                );
+                continue;
+            };
+            let path = lock::lock_path(app_dir.as_deref(), full_path);
+            if !locked.matches_path(&path)? {
+                mismatch(
+                    Severity::Error,
+                    dllname,
+                    "path",
+                    Some(locked.path.clone()),
+                    Some(path),
+                );
+            }
+            if locked.version.is_some() && details.file_version != locked.version {
+                mismatch(
+                    Severity::Error,
+                    dllname,
+                    "version",
+                    locked.version.clone(),
+                    Some(details.file_version.clone().unwrap_or_default()),
+                );
+            }
+            let sha256 = details.hashes.as_ref().map(|h| &h.sha256);
+            if let (Some(expected), Some(found)) = (locked.sha256.as_ref(), sha256) {
+                if !expected.eq_ignore_ascii_case(found) {
+                    mismatch(
+                        Severity::Error,
+                        dllname,
+                        "sha256",
+                        Some(expected.clone()),
+                        Some(found.clone()),
+                    );
+                }
+            }
+        }
+        for exe in self.iter().filter(|e| e.depth_first_appearance > 0) {
+            if let Some((_, full_path)) = lock::lockable_details(exe) {
+                if lock.get(&exe.dllname).is_none() {
+                    mismatch(
+                        Severity::Warning,
+                        &exe.dllname,
+                        "path",
+                        None,
+                        Some(lock::lock_path(app_dir.as_deref(), full_path)),
+                    );
+                }
+            }
+        }
+        report.sort();
+        Ok(report)
+    }
+
+    /// Check that no executable imports a symbol forbidden by the given list
+    ///
+    /// System DLLs are skipped; the symbols are only checked if they were extracted during the
+    /// scan.
+    pub fn check_banned_imports(&self, banned: &BannedImports) -> ExecutablesCheckReport {
+        let mut report = ExecutablesCheckReport::new();
+        for exe in self.executables.values() {
+            let imported = match exe.details.as_ref() {
+                Some(d) if !d.is_system && !d.is_api_set => d.symbols.as_ref().map(|s| &s.imported),
+                _ => continue,
+            };
+            for (dll_name, symbols) in imported.into_iter().flatten() {
+                let mut symbols: Vec<&String> = symbols.iter().collect();
+                symbols.sort();
+                for symbol in symbols {
+                    if let Some(entry) = banned.find(dll_name, symbol) {
+                        report.push(Finding::new(
+                            Severity::Error,
+                            FindingKind::BannedImport {
+                                importer: exe.dllname.clone(),
+                                dll: dll_name.clone(),
+                                symbol: symbol.clone(),
+                                reason: entry.reason.clone(),
+                            },
+                        ));
+                    }
+                }
+            }
+        }
+        report.sort();
+        report
+    }
+
+    /// Check that every found non-system dependency exports the symbols imported by this file
+    ///
+    /// The dependencies whose symbols could not be extracted are skipped, as well as the whole
+    /// file if its own symbols could not be extracted.
+    fn check_imports(&self, exe: &Executable) -> ExecutablesCheckReport {
+        let mut missing_imports = ExecutablesCheckReport::new();
+
+        let Some(details) = exe.details.as_ref() else {
+            return missing_imports;
+        };
+        if details.is_api_set {
+            return missing_imports;
+        }
+        let Some(symbols) = details.symbols.as_ref() else {
+            return missing_imports;
+        };
+
+        for (dll_name, imported) in &symbols.imported {
+            // missing libraries are reported by check_missing
+            let Some(dll_details) = self
+                .get(dll_name)
+                .filter(|dll_exe| dll_exe.found)
+                .and_then(|dll_exe| dll_exe.details.as_ref())
+            else {
+                continue;
+            };
+            if dll_details.is_system {
+                continue;
+            }
+            if let Some(dll_symbols) = dll_details.symbols.as_ref() {
+                missing_imports.extend(Self::check_symbols(
+                    &exe.dllname,
+                    dll_name,
+                    imported,
+                    &dll_symbols.exported,
+                ));
             }
         }
 
-        Ok(missing_imports)
+        missing_imports
+    }
+
+    /// Find the static dependencies of the non-system executables from which no symbol (by name
+    /// or by ordinal) is imported, which are only informational, since loading a DLL may be
+    /// intended for its side effects
+    fn check_load_only(&self) -> ExecutablesCheckReport {
+        let mut report = ExecutablesCheckReport::new();
+        for exe in self.executables.values() {
+            let Some(details) = exe.details.as_ref() else {
+                continue;
+            };
+            if details.is_system || details.is_api_set {
+                continue;
+            }
+            let (Some(dependencies), Some(symbols)) =
+                (details.dependencies.as_ref(), details.symbols.as_ref())
+            else {
+                continue;
+            };
+            for dll in dependencies {
+                let imports_any = symbols
+                    .imported
+                    .iter()
+                    .any(|(name, imported)| name.eq_ignore_ascii_case(dll) && !imported.is_empty());
+                if !imports_any {
+                    report.push(Finding::new(
+                        Severity::Info,
+                        FindingKind::LoadOnlyDependency {
+                            importer: exe.dllname.clone(),
+                            dll: dll.clone(),
+                        },
+                    ));
+                }
+            }
+        }
+        report
     }
 
-    fn get_notfound_children(&self, e: &Executable) -> Vec<Executable> {
+    /// Executables not found below `e`, followed by their found importers up to `e`
+    ///
+    /// `ancestors` holds the lowercase names of the executables on the branch above `e`; the
+    /// dependencies closing a cycle are not followed.
+    fn get_notfound_children(
+        &self,
+        e: &Executable,
+        ancestors: &mut Vec<String>,
+    ) -> Vec<Executable> {
         if !e.found {
             return vec![e.clone()];
         }
 
+        let key = e.dllname.to_lowercase();
+        if ancestors.contains(&key) {
+            return vec![];
+        }
+
         if let Some(details) = &e.details {
-            if let Some(dependencies) = &details.dependencies {
-                let mut deps_with_notfound_children: Vec<Executable> = dependencies
-                    .iter()
+            if details.dependencies.is_some() {
+                ancestors.push(key);
+                let mut deps_with_notfound_children: Vec<Executable> = details
+                    .all_dependencies()
+                    .into_iter()
                     .flat_map(|d| {
                         if let Some(c) = self.get(d) {
-                            self.get_notfound_children(c)
+                            self.get_notfound_children(c, ancestors)
                         } else {
                             vec![]
                         }
                     })
                     .collect();
+                ancestors.pop();
 
                 if !deps_with_notfound_children.is_empty() {
                     deps_with_notfound_children.extend(vec![e.clone()])
@@ -276,9 +1831,10 @@ impl Executables {
 
     pub fn filter_only_notfound(&self) -> Result<Executables, LookupError> {
         let mut ret = Executables::new();
+        ret.diagnostics = self.diagnostics.clone();
 
-        if let Some(root) = self.get_root()? {
-            for e in self.get_notfound_children(root) {
+        for root in self.get_roots()? {
+            for e in self.get_notfound_children(root, &mut Vec::new()) {
                 ret.insert(e.clone())
             }
         }
@@ -288,88 +1844,54 @@ impl Executables {
 
     /// Check that the exporting DLL has all symbols imported by the importing executable file
     fn check_symbols(
-        &self,
         importer: &str,
         exporter: &str,
-    ) -> Result<ExecutablesCheckReport, LookupError> {
-        let exe = self
-            .get(importer)
-            .ok_or_else(|| LookupError::ScanError(format!("Could not find file {importer}")))?;
-        let imported_symbols = &exe
-            .details
-            .as_ref()
-            .ok_or_else(|| {
-                LookupError::ScanError(format!("Could not find details for file {importer}"))
-            })?
-            .symbols
-            .as_ref()
-            .ok_or_else(|| {
-                LookupError::ScanError(format!("Could not find symbols for file {importer}"))
-            })?
-            .imported;
-        let imported_symbols_this_dep = imported_symbols.get(exporter).ok_or_else(|| {
-            LookupError::ScanError(format!(
-                "Could not find list of symbols imported by {importer} from {exporter}"))
-        })?;
-
-        let dep_exe = self
-            .get(exporter)
-            .ok_or_else(|| LookupError::ScanError(format!("Could not find file {exporter}")))?;
-        let exported_symbols = &dep_exe
-            .details
-            .as_ref()
-            .ok_or_else(|| {
-                LookupError::ScanError(format!("Could not find details for file {exporter}"))
-            })?
-            .symbols
-            .as_ref()
-            .ok_or_else(|| {
-                LookupError::ScanError(format!("Could not find symbols for file {exporter}"))
-            })?
-            .exported;
-
-        let mut missing_symbols: HashSet<String> = HashSet::new();
-
-        for d in imported_symbols_this_dep {
-            if !exported_symbols.contains(d) {
-                missing_symbols.insert(d.clone());
-            }
-        }
-
-        let not_found_symbols = if missing_symbols.is_empty() {
-            None
-        } else {
-            Some(
-                vec![(
-                    importer.to_owned(),
-                    vec![(exporter.to_owned(), missing_symbols)]
-                        .into_iter()
-                        .collect(),
-                )]
-                    .into_iter()
-                    .collect(),
-            )
-        };
+        imported_symbols: &HashSet<String>,
+        exported_symbols: &HashSet<String>,
+    ) -> ExecutablesCheckReport {
+        // the symbols imported by ordinal are listed as an empty name, and the ordinals of the
+        // exports are not known
+        let mut missing_symbols: Vec<&String> = imported_symbols
+            .iter()
+            .filter(|d| !d.is_empty() && !exported_symbols.contains(*d))
+            .collect();
+        missing_symbols.sort();
 
-        Ok(ExecutablesCheckReport {
-            not_found_libraries: HashMap::new(),
-            not_found_symbols,
-        })
+        missing_symbols
+            .into_iter()
+            .map(|symbol| {
+                Finding::new(
+                    Severity::Error,
+                    FindingKind::MissingSymbol {
+                        importer: importer.to_owned(),
+                        exporter: exporter.to_owned(),
+                        symbol: symbol.clone(),
+                    },
+                )
+            })
+            .collect()
     }
 }
 
 #[cfg(test)]
 mod tests {
-    use std::collections::HashSet;
+    use std::collections::{BTreeSet, HashSet};
     use std::iter::FromIterator;
+    use std::path::PathBuf;
 
     use fs_err as fs;
 
-    use crate::common::LookupError;
-    use crate::executable::Executables;
+    use crate::common::{DiagnosticKind, LookupError};
+    use crate::executable::{
+        Architecture, AssemblyDependency, DependencyKind, Executables, FileHashes,
+        SecurityFeatures, SymbolPattern, SymbolUse, JSON_SCHEMA_VERSION,
+    };
+    use crate::inventory::bundled_os_profile;
     use crate::path::LookupPath;
-    use crate::query::LookupQuery;
+    use crate::query::{DuplicatePolicy, LookupQuery};
+    use crate::report::{Finding, FindingKind, Severity};
     use crate::runner::run;
+    use crate::testing::{dll, exe_requesting, importing, missing, CRT_VERSION};
 
     #[test]
     fn empty_executables() -> Result<(), LookupError> {
@@ -378,7 +1900,7 @@ mod tests {
 
         assert!(exes.get("NonExistingExecutable.exe").is_none());
 
-        assert!(exes.get_root()?.is_none());
+        assert!(exes.get_roots()?.is_empty());
 
         assert!(exes.sorted_by_first_appearance().is_empty());
 
@@ -403,7 +1925,8 @@ mod tests {
         assert!(exes.get("NonExistingExecutable.exe").is_none());
         assert!(exes.get("DepRunTest.exe").is_some());
 
-        assert_eq!(exes.get_root()?.unwrap().dllname, "DepRunTest.exe");
+        assert_eq!(exes.get_roots()?.len(), 1);
+        assert_eq!(exes.get_roots()?[0].dllname, "DepRunTest.exe");
 
         let sorted = exes.sorted_by_first_appearance();
         let sorted_names: HashSet<&str> = sorted
@@ -415,15 +1938,524 @@ mod tests {
             HashSet::from_iter(["DepRunTestLib.dll", "DepRunTest.exe"].iter().copied());
         assert_eq!(sorted_names, expected_names);
 
-        let exe_p = &exes
-            .get_root()?
+        let exe_p = &exes.get_roots()?[0].details.as_ref().unwrap().full_path;
+        assert_eq!(
+            exe_p.as_deref(),
+            Some(fs::canonicalize(exe_path)?.as_path())
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn json_round_trip() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+
+        let json = serde_json::to_string(&exes)?;
+        let temp_dir = tempfile::tempdir()?;
+        let json_path = temp_dir.path().join("exes.json");
+        fs::write(&json_path, &json)?;
+        let reloaded = Executables::from_json_file(&json_path)?;
+
+        assert_eq!(reloaded.len(), exes.len());
+        assert_eq!(reloaded.edges().len(), exes.edges().len());
+        assert_eq!(serde_json::to_string(&reloaded)?, json);
+
+        let stats = exes.stats();
+        assert_eq!(reloaded.stats(), stats);
+        assert_eq!(
+            stats.user + stats.system + stats.api_sets + stats.known_dlls + stats.missing,
+            exes.len()
+        );
+        assert!(stats.user >= 2);
+        assert!(stats.closure_size > 0);
+        assert!(stats.scan_duration_ms.is_some());
+
+        // plain lists written before the schema version are still accepted
+        let legacy = serde_json::to_string(&exes.sorted_by_first_appearance())?;
+        assert_eq!(Executables::from_json(&legacy)?.len(), exes.len());
+
+        // version 1 wrote an empty full path for the files resolved without a path
+        let mut v1: serde_json::Value = serde_json::from_str(&json)?;
+        v1["schema_version"] = 1.into();
+        v1["executables"][0]["details"]["full_path"] = "".into();
+        let dllname = v1["executables"][0]["dllname"].as_str().unwrap().to_owned();
+        let v1 = Executables::from_json(&v1.to_string())?;
+        let details = v1.get(&dllname).unwrap().details.as_ref().unwrap();
+        assert_eq!(details.full_path, None);
+
+        let future = json.replacen(
+            &format!("\"schema_version\":{JSON_SCHEMA_VERSION}"),
+            "\"schema_version\":999",
+            1,
+        );
+        assert!(Executables::from_json(&future).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn dependency_edges() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let context = LookupPath::deduce(&query);
+        let exes = run(&query, &context)?;
+
+        assert!(exes
+            .edges()
+            .iter()
+            .any(|e| e.importer == "DepRunTest.exe" && e.importee == "DepRunTestLib.dll"));
+
+        let importers: HashSet<&str> = exes
+            .importers_of("vcruntime140d.dll")
+            .iter()
+            .map(|e| e.importer.as_str())
+            .collect();
+        assert_eq!(
+            importers,
+            HashSet::from_iter(["DepRunTest.exe", "DepRunTestLib.dll"])
+        );
+        assert!(exes
+            .importers_of("VCRUNTIME140D.dll")
+            .iter()
+            .all(|e| e.kind == DependencyKind::Static));
+        assert!(exes.importers_of("DepRunTest.exe").is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn duplicate_policies() -> Result<(), LookupError> {
+        let first = dll("a.dll", 1, &[]);
+        let mut second = first.clone();
+        second.details.as_mut().unwrap().full_path = Some("other/a.dll".into());
+
+        let mut exes = Executables::new();
+        exes.insert(first.clone());
+        exes.insert(first.clone());
+        assert!(exes.diagnostics().is_empty());
+        exes.insert(second.clone());
+        assert_eq!(exes.len(), 1);
+        assert_eq!(exes.diagnostics()[0].kind, DiagnosticKind::DuplicateName);
+
+        let mut exes = Executables::new();
+        exes.insert_with_policy(first.clone(), DuplicatePolicy::Error)?;
+        match exes.insert_with_policy(second.clone(), DuplicatePolicy::Error) {
+            Err(LookupError::DuplicateName { dllname, .. }) => assert_eq!(dllname, "a.dll"),
+            _ => panic!("the duplicate must be an error"),
+        }
+
+        let mut exes = Executables::new();
+        exes.insert_with_policy(first, DuplicatePolicy::KeepAll)?;
+        exes.insert_with_policy(second, DuplicatePolicy::KeepAll)?;
+        assert_eq!(exes.len(), 2);
+        assert_eq!(exes.get_all("A.dll").len(), 2);
+        assert_eq!(
+            exes.get("a.dll")
+                .unwrap()
+                .details
+                .as_ref()
+                .unwrap()
+                .full_path,
+            Some(PathBuf::from("a.dll"))
+        );
+        assert!(exes.get_by_path("other/a.dll").is_some());
+        let round_trip = Executables::from_json(&serde_json::to_string(&exes)?)?;
+        assert_eq!(round_trip.get_all("a.dll").len(), 2);
+        Ok(())
+    }
+
+    #[test]
+    fn find_symbol() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.extract_symbols = true;
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+
+        // matched by demangled name
+        let matches = exes.find_symbol(&SymbolPattern::glob("*TestClass::testMethod*")?);
+        assert_eq!(matches.len(), 2);
+        assert_eq!(matches[0].dllname, "DepRunTest.exe");
+        assert_eq!(
+            matches[0].usage,
+            SymbolUse::Imported("DepRunTestLib.dll".to_owned())
+        );
+        assert_eq!(matches[1].dllname, "DepRunTestLib.dll");
+        assert_eq!(matches[1].usage, SymbolUse::Exported);
+        assert!(matches[1].symbol.starts_with('?'));
+
+        // matched by raw name
+        let matches = exes.find_symbol(&SymbolPattern::regex("^GetCurrentProcessId$")?);
+        assert!(matches.iter().any(|m| m.dllname == "DepRunTest.exe"));
+        assert!(matches.iter().all(|m| m.demangled.is_none()));
+        Ok(())
+    }
+
+    #[test]
+    fn collection_traits() {
+        let exes: Executables = vec![
+            dll("app.exe", 0, &["b.dll", "a.dll"]),
+            dll("b.dll", 1, &[]),
+            dll("a.dll", 1, &["c.dll"]),
+            dll("c.dll", 2, &[]),
+        ]
+        .into_iter()
+        .collect();
+        let names: Vec<&str> = exes.iter().map(|e| e.dllname.as_str()).collect();
+        assert_eq!(names, vec!["app.exe", "a.dll", "b.dll", "c.dll"]);
+        assert_eq!(exes["A.DLL"].depth_first_appearance, 1);
+        assert_eq!((&exes).into_iter().count(), 4);
+
+        let shallow = exes.filter(|e| e.depth_first_appearance < 2);
+        assert_eq!(shallow.len(), 3);
+        assert_eq!(shallow.importers_of("c.dll").len(), 1);
+        assert_eq!(exes.len(), 4);
+
+        let mut exes = exes;
+        exes.retain(|e| e.dllname != "a.dll");
+        assert!(!exes.contains("a.dll"));
+        assert!(exes.importers_of("c.dll").is_empty());
+        exes.extend(vec![dll("a.dll", 1, &[])]);
+        let owned: Vec<String> = exes.into_iter().map(|e| e.dllname).collect();
+        assert_eq!(owned, vec!["app.exe", "a.dll", "b.dll", "c.dll"]);
+    }
+
+    #[test]
+    fn version_conflicts() -> Result<(), LookupError> {
+        let mut exes: Executables = [dll("app.exe", 0, &[]), dll("plugin.dll", 1, &[])]
+            .into_iter()
+            .collect();
+        assert!(exes.check(false)?.with_code("DR0004").next().is_none());
+
+        exes.insert(exe_requesting(
+            "legacy.dll",
+            1,
+            "9.0.30729.1",
+            Architecture::X64,
+            &[],
+        ));
+        let report = exes.check(false)?;
+        let conflicts: Vec<&Finding> = report.with_code("DR0004").collect();
+        assert_eq!(conflicts.len(), 1);
+        assert_eq!(conflicts[0].severity, Severity::Warning);
+        match &conflicts[0].kind {
+            FindingKind::VersionConflict { assembly, versions } => {
+                assert_eq!(assembly, "microsoft.vc90.crt");
+                assert_eq!(versions.len(), 2);
+                assert_eq!(
+                    versions["9.0.21022.8"],
+                    BTreeSet::from_iter(["app.exe".to_owned(), "plugin.dll".to_owned()])
+                );
+            }
+            other => panic!("unexpected finding {other:?}"),
+        }
+
+        // a DLL scanned as loaded by a host requesting another version
+        let mut exes: Executables = [dll("plugin.dll", 0, &[])].into_iter().collect();
+        exes.set_host(
+            "host.exe".to_owned(),
+            vec![AssemblyDependency {
+                name: "Microsoft.VC90.CRT".to_owned(),
+                version: Some("9.0.30729.1".to_owned()),
+            }],
+        );
+        let report = exes.check(false)?;
+        let conflicts: Vec<&Finding> = report.with_code("DR0004").collect();
+        assert_eq!(conflicts.len(), 1);
+        match &conflicts[0].kind {
+            FindingKind::VersionConflict { versions, .. } => assert_eq!(
+                versions["9.0.30729.1"],
+                BTreeSet::from_iter(["host.exe".to_owned()])
+            ),
+            other => panic!("unexpected finding {other:?}"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn missing_dlls_and_arch_mismatches() -> Result<(), LookupError> {
+        let exes: Executables = [
+            dll("app.exe", 0, &["plugin.dll", "missing.dll"]),
+            exe_requesting("plugin.dll", 1, CRT_VERSION, Architecture::X86, &[]),
+            missing("missing.dll", 1),
+        ]
+        .into_iter()
+        .collect();
+
+        let report = exes.check(false)?;
+        assert_eq!(report.max_severity(), Some(Severity::Error));
+        assert_eq!(report.findings().len(), 2);
+        assert!(matches!(
+            &report.findings()[0].kind,
+            FindingKind::MissingDll { importer, dll } if importer == "app.exe" && dll == "missing.dll"
+        ));
+        assert!(matches!(
+            &report.findings()[1].kind,
+            FindingKind::ArchMismatch {
+                dll_architecture: Architecture::X86,
+                ..
+            }
+        ));
+        assert!(report.findings()[1].involves("plugin.dll"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn dependency_chains() -> Result<(), LookupError> {
+        let exes: Executables = [
+            dll("app.exe", 0, &["a.dll", "b.dll"]),
+            dll("a.dll", 1, &["c.dll"]),
+            dll("b.dll", 1, &["C.DLL", "a.dll"]),
+            dll("c.dll", 2, &["d.dll"]),
+            dll("d.dll", 3, &[]),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(
+            exes.dependency_chains("D.dll")?,
+            vec![
+                vec!["app.exe", "a.dll", "c.dll", "d.dll"],
+                vec!["app.exe", "b.dll", "c.dll", "d.dll"],
+            ]
+        );
+        assert_eq!(exes.dependency_chains("app.exe")?, vec![vec!["app.exe"]]);
+        assert!(exes.dependency_chains("e.dll")?.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn mixed_crt() -> Result<(), LookupError> {
+        let debug_crt = ["MSVCP140D.dll", "VCRUNTIME140_1D.dll", "ucrtbased.dll"];
+        let mut exes: Executables = debug_crt.iter().map(|name| dll(name, 1, &[])).collect();
+        exes.insert(dll("app.exe", 0, &debug_crt));
+        assert!(exes.check(false)?.is_empty());
+
+        let release_crt = ["msvcp140.dll", "msvcr120.dll"];
+        exes.insert(dll("plugin.dll", 1, &release_crt));
+        exes.extend(release_crt.iter().map(|name| dll(name, 2, &[])));
+        let report = exes.check(false)?;
+        let mixed = report.with_code("DR0006").next().unwrap();
+        assert!(matches!(
+            &mixed.kind,
+            FindingKind::MixedCrt { debug, release }
+                if debug.len() == 3 && release.contains("msvcp140.dll")
+        ));
+        let generations = report.with_code("DR0007").next().unwrap();
+        assert!(matches!(
+            &generations.kind,
+            FindingKind::CrtGenerations { generations }
+                if generations.keys().eq(["120", "140"].iter())
+        ));
+        Ok(())
+    }
+
+    #[test]
+    fn security_features() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+        let mut exes = run(&query, &LookupPath::deduce(&query))?;
+        let details = exes.get("DepRunTest.exe").unwrap().details.clone().unwrap();
+        let security = details.security.unwrap();
+        assert!(security.aslr && security.high_entropy_va && security.dep && !security.cfg);
+        assert_eq!(security.to_string(), "ASLR, high-entropy VA, DEP");
+        assert!(details.entry_point.is_some());
+        assert!(exes.check_security().is_empty());
+
+        // relocations stripped: the dynamic base flag has no effect
+        let legacy = SecurityFeatures::from_characteristics(0x0040, 0x0001);
+        assert_eq!(legacy.missing_essential(), vec!["ASLR", "DEP"]);
+        let mut legacy_exe = dll("legacy.dll", 1, &[]);
+        legacy_exe.details.as_mut().unwrap().security = Some(legacy);
+        exes.insert(legacy_exe);
+        let report = exes.check_security();
+        assert_eq!(report.findings().len(), 1);
+        assert_eq!(
+            report.findings()[0].to_string(),
+            "[warning DR0008] legacy.dll: built without ASLR and DEP"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn missing_dll_suggestions() -> Result<(), LookupError> {
+        let dependencies = ["MSVCP140.dll", "mfplat.dll", "other.dll"];
+        let mut exes: Executables = dependencies.iter().map(|name| missing(name, 1)).collect();
+        exes.insert(exe_requesting(
+            "app.exe",
+            0,
+            CRT_VERSION,
+            Architecture::X86,
+            &dependencies,
+        ));
+        let report = exes.check(false)?;
+        let suggestion = |dll: &str| {
+            report
+                .findings()
+                .iter()
+                .find(|f| f.involves(dll))
+                .and_then(|f| f.suggestion.clone())
+        };
+        assert_eq!(
+            suggestion("MSVCP140.dll").as_deref(),
+            Some("provided by the Microsoft Visual C++ 2015-2022 Redistributable (x86) (https://aka.ms/vs/17/release/vc_redist.x86.exe)")
+        );
+        assert!(suggestion("mfplat.dll")
+            .unwrap()
+            .starts_with("provided by Media Feature Pack"));
+        assert_eq!(suggestion("other.dll"), None);
+        Ok(())
+    }
+
+    #[test]
+    fn load_only_dependencies() -> Result<(), LookupError> {
+        let dependencies = ["lib.dll", "hook.dll", "ordinals.dll"];
+        let app = importing(
+            dll("app.exe", 0, &dependencies),
+            &[
+                ("LIB.dll", &["f"]),
+                ("hook.dll", &[]),
+                ("ordinals.dll", &[""]),
+            ],
+        );
+        let mut exes: Executables = dependencies.iter().map(|name| dll(name, 1, &[])).collect();
+        exes.insert(app);
+
+        // without symbols, nothing is known about the imports
+        assert_eq!(exes.check(false)?.with_code("DR0012").count(), 0);
+        let report = exes.check(true)?;
+        let findings: Vec<&Finding> = report.with_code("DR0012").collect();
+        assert_eq!(findings.len(), 1);
+        assert_eq!(findings[0].severity, Severity::Info);
+        assert_eq!(
+            findings[0].kind.to_string(),
+            "app.exe: no symbol imported from dependency hook.dll"
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn missing_symbols() -> Result<(), LookupError> {
+        let app = importing(
+            dll("app.exe", 0, &["lib.dll", "opaque.dll"]),
+            &[("LIB.dll", &["f", "g", ""]), ("opaque.dll", &["h"])],
+        );
+        let mut lib = importing(dll("lib.dll", 1, &[]), &[]);
+        lib.details
+            .as_mut()
             .unwrap()
-            .details
-            .as_ref()
+            .symbols
+            .as_mut()
             .unwrap()
-            .full_path;
-        assert_eq!(exe_p, &fs::canonicalize(exe_path)?);
+            .exported = HashSet::from_iter(["f".to_owned()]);
+        let mut exes = Executables::new();
+        exes.insert(app);
+        exes.insert(lib);
+        // the symbols of this dependency could not be extracted
+        exes.insert(dll("opaque.dll", 1, &[]));
 
+        // the import by ordinal and the imports from opaque.dll are not reported
+        let report = exes.check(true)?;
+        let findings: Vec<String> = report
+            .with_code("DR0002")
+            .map(|f| f.kind.to_string())
+            .collect();
+        assert_eq!(findings, vec!["app.exe: symbol g not exported by LIB.dll"]);
         Ok(())
     }
+
+    #[test]
+    fn target_os_availability() {
+        let app = importing(
+            dll(
+                "app.exe",
+                0,
+                &["KERNEL32.dll", "api-ms-win-core-synch-l1-2-0.dll"],
+            ),
+            &[
+                ("KERNEL32.dll", &["CreateFileW", "SetThreadDescription"]),
+                ("api-ms-win-core-synch-l1-2-0.dll", &["WaitOnAddress"]),
+            ],
+        );
+        let mut exes = Executables::new();
+        exes.insert(app);
+        for (name, is_api_set) in [
+            ("KERNEL32.dll", false),
+            ("api-ms-win-core-synch-l1-2-0.dll", true),
+        ] {
+            let mut system_dll = dll(name, 1, &[]);
+            let details = system_dll.details.as_mut().unwrap();
+            details.is_system = true;
+            details.is_api_set = is_api_set;
+            exes.insert(system_dll);
+        }
+
+        let findings = |os: &str| -> Vec<String> {
+            exes.check_target_os(&bundled_os_profile(os).unwrap())
+                .with_code("DR0009")
+                .map(|f| f.kind.to_string())
+                .collect()
+        };
+        assert_eq!(
+            findings("win7"),
+            vec![
+                "app.exe: symbol SetThreadDescription of KERNEL32.dll not available on Windows 7 SP1",
+                "app.exe: API set api-ms-win-core-synch-l1-2-0.dll not available on Windows 7 SP1",
+                "app.exe: symbol WaitOnAddress of api-ms-win-core-synch-l1-2-0.dll not available on Windows 7 SP1",
+            ]
+        );
+        assert_eq!(
+            findings("win81"),
+            vec![
+                "app.exe: symbol SetThreadDescription of KERNEL32.dll not available on Windows 8.1"
+            ]
+        );
+        assert!(findings("win10-22h2").is_empty());
+    }
+
+    #[test]
+    fn allowed_directories() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build");
+        let exe_path = build_dir.join("DepRunTest/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = None;
+        query.target.user_path = vec![build_dir.join("DepRunTestLib/Debug")];
+        let lookup_path = LookupPath::deduce(&query);
+        let exes = run(&query, &lookup_path)?;
+
+        let report = exes.check_allowed_directories(&[fs::canonicalize(&build_dir)?]);
+        assert_eq!(report.with_code("DR0011").count(), 0);
+
+        let report =
+            exes.check_allowed_directories(&[fs::canonicalize(build_dir.join("DepRunTest"))?]);
+        let strays: Vec<_> = report.with_code("DR0011").collect();
+        assert_eq!(strays.len(), 1);
+        assert!(strays[0].involves("DepRunTestLib.dll"));
+        Ok(())
+    }
+
+    #[test]
+    fn file_hashes() {
+        let hashes = FileHashes::compute(b"abc");
+        assert_eq!(
+            hashes.sha256,
+            "ba7816bf8f01cfea414140de5dae2223b00361a396177a9cb410ff61f20015ad"
+        );
+        assert_eq!(hashes.md5, "900150983cd24fb0d6963f7d28e17f72");
+    }
 }
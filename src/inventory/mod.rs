@@ -0,0 +1,248 @@
+/*!
+Inventories of the DLLs shipped with Windows ("assume present" OS profiles)
+
+Without access to a Windows partition, the system DLLs (kernel32.dll, ntdll.dll, the API sets...)
+cannot be found, and would be reported as missing. An [`OsProfile`] lists the DLLs and API set
+contracts guaranteed by a given Windows version, like the OS profiles of Dependency Walker: the
+ones that are not found along the lookup path are considered present when scanning for that
+target OS.
+
+A few profiles are bundled with the library (see [`bundled_os_profile`]); others can be read from
+a JSON file with the same structure with [`OsProfile::load`].
+
+The symbols added to the system DLLs by later Windows versions are listed in
+[`SymbolAvailability`], to tell whether the imports of an executable are available on the target
+OS. The system DLLs that are only installed with an optional Windows feature, an update or a
+driver are listed in [`WindowsFeatures`], to tell where a missing one comes from.
+ */
+
+use std::collections::{BTreeSet, HashMap};
+use std::path::Path;
+
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+use crate::apiset::{bundled_apiset_map, lookup_apiset, ApisetMap};
+use crate::common::{LookupError, NamePatterns};
+
+/// DLLs and API set contracts guaranteed to be present on a Windows version
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct OsProfile {
+    /// short name, e.g. win10-22h2
+    pub name: String,
+    /// e.g. Windows 10 version 22H2
+    pub description: String,
+    /// build number of the Windows version, to order the profiles
+    pub build: u16,
+    /// lowercase names of the DLLs installed with Windows, e.g. kernel32.dll
+    pub dlls: BTreeSet<String>,
+    /// lowercase names of the API set contracts resolved by the loader, without extension
+    #[serde(default)]
+    pub api_sets: BTreeSet<String>,
+}
+
+/// Name of an API set contract without its revision number (the last one), which the loader
+/// ignores when resolving it
+fn contract_without_revision(name: &str) -> &str {
+    name.rsplit_once('-').map(|(h, _)| h).unwrap_or(name)
+}
+
+impl OsProfile {
+    /// Read a profile from a JSON file
+    pub fn load<P: AsRef<Path>>(p: P) -> Result<Self, LookupError> {
+        let content = fs::read_to_string(p.as_ref())?;
+        Self::from_json(&content)
+    }
+
+    pub fn from_json(s: &str) -> Result<Self, LookupError> {
+        let mut profile: Self = serde_json::from_str(s)?;
+        profile.dlls = profile.dlls.iter().map(|d| d.to_lowercase()).collect();
+        profile.api_sets = profile.api_sets.iter().map(|a| a.to_lowercase()).collect();
+        Ok(profile)
+    }
+
+    /// Whether the given API set contract is resolved on this Windows version
+    pub fn provides_api_set(&self, dllname: &str) -> bool {
+        let name = dllname.to_lowercase();
+        let name = name.trim_end_matches(".dll");
+        let contract = contract_without_revision(name);
+        self.api_sets
+            .iter()
+            .any(|a| a == name || contract_without_revision(a) == contract)
+    }
+
+    /// Whether the given DLL (or API set contract) is guaranteed to be present on this Windows
+    /// version
+    pub fn provides(&self, dllname: &str) -> bool {
+        self.dlls.contains(&dllname.to_lowercase()) || self.provides_api_set(dllname)
+    }
+}
+
+/// Names of the OS profiles bundled with the library
+pub const BUNDLED_OS_PROFILES: &[&str] = &["win7", "win81", "win10-22h2", "win11-23h2"];
+
+/// Get one of the OS profiles bundled with the library, by name (see [`BUNDLED_OS_PROFILES`])
+///
+/// The bundled profiles only contain the DLLs and API sets commonly found in the import tables
+/// of applications. Other names are rejected.
+pub fn bundled_os_profile(name: &str) -> Result<OsProfile, LookupError> {
+    let json = match name.to_lowercase().as_str() {
+        "win7" => include_str!("data/win7.json"),
+        "win81" => include_str!("data/win81.json"),
+        "win10-22h2" | "win10" => include_str!("data/win10-22h2.json"),
+        "win11-23h2" | "win11" => include_str!("data/win11-23h2.json"),
+        _ => {
+            return Err(LookupError::ContextDeductionError(format!(
+                "no bundled OS profile named {name} (available: {})",
+                BUNDLED_OS_PROFILES.join(", ")
+            )))
+        }
+    };
+    OsProfile::from_json(json)
+}
+
+/// Windows builds in which the symbols of the system DLLs were first exported, to check the imports
+/// of an executable against an older target OS
+#[derive(Debug, Clone, Default)]
+pub struct SymbolAvailability {
+    /// build of the first Windows version exporting each symbol, by lowercase DLL name
+    introductions: HashMap<String, HashMap<String, u16>>,
+    /// map from the API set contracts to the DLLs hosting their symbols
+    apiset_map: ApisetMap,
+}
+
+impl SymbolAvailability {
+    /// Builds in which the symbols commonly imported by applications were added to the system DLLs
+    /// since Windows 7
+    pub fn bundled() -> Self {
+        Self {
+            introductions: serde_json::from_str(include_str!("data/symbols.json"))
+                .unwrap_or_default(),
+            apiset_map: bundled_apiset_map("win10").unwrap_or_default(),
+        }
+    }
+
+    /// Build of the first Windows version exporting the symbol from the given DLL, or from the
+    /// host of the given API set contract, if known
+    pub fn introduced_in(&self, dllname: &str, symbol: &str) -> Option<u16> {
+        let dllname = dllname.to_lowercase();
+        let hosts = lookup_apiset(&self.apiset_map, &dllname)
+            .cloned()
+            .unwrap_or_else(|| vec![dllname]);
+        hosts
+            .iter()
+            .filter_map(|h| self.introductions.get(&h.to_lowercase())?.get(symbol))
+            .copied()
+            .max()
+    }
+}
+
+/// Optional Windows features, updates and drivers installing system DLLs that are not always
+/// present (e.g. the Media Feature Pack on the N editions of Windows)
+#[derive(Debug, Clone, Default)]
+pub struct WindowsFeatures {
+    /// patterns of the DLL names, and the feature installing them
+    entries: Vec<(NamePatterns, String)>,
+}
+
+/// Entry of the bundled table of features
+#[derive(Deserialize)]
+struct WindowsFeatureEntry {
+    feature: String,
+    dlls: Vec<String>,
+}
+
+impl WindowsFeatures {
+    /// Features installing the well-known system DLLs, bundled with the library
+    pub fn bundled() -> Self {
+        let entries: Vec<WindowsFeatureEntry> =
+            serde_json::from_str(include_str!("data/features.json")).unwrap_or_default();
+        Self {
+            entries: entries
+                .into_iter()
+                .filter_map(|e| Some((NamePatterns::new(&e.dlls).ok()?, e.feature)))
+                .collect(),
+        }
+    }
+
+    /// Feature installing the given DLL, if it is a known one
+    pub fn feature_providing(&self, dllname: &str) -> Option<&str> {
+        self.entries
+            .iter()
+            .find(|(dlls, _)| dlls.matches(dllname))
+            .map(|(_, feature)| feature.as_str())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::common::LookupError;
+    use crate::inventory::{bundled_os_profile, WindowsFeatures, BUNDLED_OS_PROFILES};
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+
+    #[test]
+    fn assume_present_os_profiles() -> Result<(), LookupError> {
+        for name in BUNDLED_OS_PROFILES {
+            let profile = bundled_os_profile(name)?;
+            assert_eq!(&profile.name, name);
+            assert!(profile.provides("KERNEL32.dll"));
+        }
+        assert!(bundled_os_profile("win95").is_err());
+
+        let win7 = bundled_os_profile("win7")?;
+        assert!(win7.provides("api-ms-win-core-synch-l1-1-0.dll"));
+        // later versions of a contract are not available
+        assert!(!win7.provides("api-ms-win-core-synch-l1-2-0.dll"));
+        assert!(!win7.provides("ucrtbase.dll"));
+        let win10 = bundled_os_profile("win10-22h2")?;
+        assert!(win10.provides("api-ms-win-crt-runtime-l1-1-0.dll"));
+        assert!(win10.provides("ucrtbase.dll"));
+
+        // without a Windows partition, the system DLLs are found only thanks to the profile
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = None;
+        query.target.user_path.clear();
+        let lookup_path = LookupPath::deduce(&query);
+        assert!(
+            !run(&query, &lookup_path)?
+                .get("KERNEL32.dll")
+                .unwrap()
+                .found
+        );
+
+        let mut win10_query = query.clone();
+        win10_query.target_os = Some(win10);
+        let lookup_path = LookupPath::deduce(&win10_query);
+        let res = run(&win10_query, &lookup_path)?;
+        let kernel32 = res.get("KERNEL32.dll").unwrap();
+        assert!(kernel32.found);
+        assert!(kernel32.details.as_ref().unwrap().is_system);
+        assert!(res.get("DepRunTestLib.dll").unwrap().found);
+        Ok(())
+    }
+
+    #[test]
+    fn windows_features() {
+        let features = WindowsFeatures::bundled();
+        assert!(features
+            .feature_providing("MFPlat.DLL")
+            .unwrap()
+            .starts_with("Media Feature Pack"));
+        assert_eq!(
+            features.feature_providing("d3dcompiler_43.dll"),
+            features.feature_providing("d3dx9_43.dll")
+        );
+        assert_ne!(
+            features.feature_providing("d3dcompiler_47.dll"),
+            features.feature_providing("d3dcompiler_43.dll")
+        );
+        assert!(features.feature_providing("kernel32.dll").is_none());
+    }
+}
@@ -35,21 +35,30 @@ unsafe fn u16_ptr_to_string(ptr: *const u16) -> OsString {
 ///
 /// They are all located in the system directory and can't be overridden/hijacked.
 pub fn get_known_dlls() -> anyhow::Result<Vec<String>> {
-    const KNOWN_DLLS_NAME_BUFFER: &[WCHAR] = &[
-        '\\' as _, 'K' as _, 'n' as _, 'o' as _, 'w' as _, 'n' as _, 'D' as _, 'l' as _, 'l' as _,
-        's' as _,
-    ];
-
-    const KNOWN_DLLS_NAME: UNICODE_STRING = UNICODE_STRING {
-        Length: (KNOWN_DLLS_NAME_BUFFER.len() * size_of::<WCHAR>()) as USHORT,
-        MaximumLength: (KNOWN_DLLS_NAME_BUFFER.len() * size_of::<WCHAR>()) as USHORT,
-        Buffer: KNOWN_DLLS_NAME_BUFFER.as_ptr() as *mut _,
+    list_known_dlls_directory(r"\KnownDlls")
+}
+
+/// Returns the complete list of KnownDlls of the 32-bit processes running under WOW64
+///
+/// They are located in the SysWOW64 directory.
+pub fn get_known_dlls32() -> anyhow::Result<Vec<String>> {
+    list_known_dlls_directory(r"\KnownDlls32")
+}
+
+/// List the sections of the given object directory
+fn list_known_dlls_directory(directory: &str) -> anyhow::Result<Vec<String>> {
+    let name_buffer: Vec<WCHAR> = directory.encode_utf16().collect();
+
+    let name: UNICODE_STRING = UNICODE_STRING {
+        Length: (name_buffer.len() * size_of::<WCHAR>()) as USHORT,
+        MaximumLength: (name_buffer.len() * size_of::<WCHAR>()) as USHORT,
+        Buffer: name_buffer.as_ptr() as *mut _,
     };
 
     let mut oa: OBJECT_ATTRIBUTES = OBJECT_ATTRIBUTES {
         Length: size_of::<OBJECT_ATTRIBUTES>() as ULONG,
         RootDirectory: null_mut(),
-        ObjectName: &KNOWN_DLLS_NAME as *const _ as *mut _,
+        ObjectName: &name as *const _ as *mut _,
         Attributes: 0,
         SecurityDescriptor: null_mut(),
         SecurityQualityOfService: null_mut(),
@@ -67,19 +76,20 @@ pub fn get_known_dlls() -> anyhow::Result<Vec<String>> {
         );
         if status != ntstatus::STATUS_SUCCESS {
             let raw_err = std::io::Error::from_raw_os_error(RtlNtStatusToDosError(status) as i32);
-            eprintln!("Failed to open KnownDll: {}", raw_err);
+            tracing::warn!(error = %raw_err, "failed to open the KnownDlls directory");
         }
     }
     if !NT_SUCCESS(status) {
-        match status {
-            ntstatus::STATUS_INSUFFICIENT_RESOURCES => eprintln!("Insufficient resources"),
-            ntstatus::STATUS_INVALID_PARAMETER => eprintln!("INVALID_PARAMETER"),
-            ntstatus::STATUS_OBJECT_NAME_INVALID => eprintln!("OBJECT_NAME_INVALID"),
-            ntstatus::STATUS_OBJECT_NAME_NOT_FOUND => eprintln!("OBJECT_NAME_NOT_FOUND"),
-            ntstatus::STATUS_OBJECT_PATH_NOT_FOUND => eprintln!("OBJECT_PATH_NOT_FOUND"),
-            ntstatus::STATUS_OBJECT_PATH_SYNTAX_BAD => eprintln!("OBJECT_PATH_SYNTAX_BAD"),
-            _ => eprintln!("Error: other"),
-        }
+        let reason = match status {
+            ntstatus::STATUS_INSUFFICIENT_RESOURCES => "Insufficient resources",
+            ntstatus::STATUS_INVALID_PARAMETER => "INVALID_PARAMETER",
+            ntstatus::STATUS_OBJECT_NAME_INVALID => "OBJECT_NAME_INVALID",
+            ntstatus::STATUS_OBJECT_NAME_NOT_FOUND => "OBJECT_NAME_NOT_FOUND",
+            ntstatus::STATUS_OBJECT_PATH_NOT_FOUND => "OBJECT_PATH_NOT_FOUND",
+            ntstatus::STATUS_OBJECT_PATH_SYNTAX_BAD => "OBJECT_PATH_SYNTAX_BAD",
+            _ => "other",
+        };
+        tracing::warn!(status, reason, "NtOpenDirectoryObject failed");
     }
 
     let mut first_time = TRUE;
@@ -152,8 +162,8 @@ pub fn get_known_dlls() -> anyhow::Result<Vec<String>> {
 
 #[cfg(test)]
 mod tests {
-    use crate::knowndlls::get_known_dlls;
     use crate::common::LookupError;
+    use crate::knowndlls::get_known_dlls;
 
     #[cfg(windows)]
     #[test]
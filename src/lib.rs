@@ -9,17 +9,27 @@
 //!
 //! The basic workflow is to first create a LookupQuery, which contains the path to the root
 //! executable whose dependencies should be found, a reference to the Windows root partition to use
-//! as reference for the scan, and various parameters for performing the scan.
+//! as reference for the scan, and various parameters for performing the scan. For CMake projects,
+//! the queries of all the built targets can be obtained from the build directory (see the cmake module;
+//! the meson module does the same for Meson).
 //!
 //! Then the LookupPath can be computed given the query. The path contains a list of entries, which
 //! will be probed for a DLL with the name registered in the import table as dependency for the
 //! target executable. This search is performed recursively. The path can be freely manipulated
 //! after deduction for advanced use cases. It can also be built from a DependencyRunner dwp file,
-//! or from a Visual Studio vcxproj or vcxproj.user file.
+//! or from a Visual Studio vcxproj or vcxproj.user file. For packaged (MSIX/AppX) apps, the package
+//! graph read from the AppxManifest.xml is added to the query (see the appx module).
+//! The directories and files are read from the local file system, unless another backend is
+//! plugged into the path (see the provider module).
 //!
 //! Once all information is available, the recursive DLL lookup can be performed to obtain a
 //! list of interdependent executables. This list represents a directed acyclic graph through the
 //! dependency list for each node, and can be visited according to various strategies.
+//! When scanning many targets, a ScanSession (see the session module) shares the Windows
+//! installation, the directory listings and the parsed DLLs between the scans.
+//! With the `async` feature, `runner::run_async` reads the directories and files through tokio, to
+//! be awaited from an async application.
+//! With the `capi` feature, the capi module exposes the scanner through a C API.
 //!
 //! Sanity checks can be run on the list of executables to find missing DLL dependencies or
 //! symbols therein.  
@@ -40,16 +50,50 @@
 
 extern crate thiserror;
 
-mod apiset;
+pub mod apiset;
+pub mod appx;
+pub mod archive;
+pub mod audit;
+pub mod banned;
+pub mod bundle;
+#[cfg(feature = "capi")]
+pub mod capi;
+pub mod cmake;
 pub mod common;
+pub mod diff;
+pub mod directory;
+pub mod environment;
 pub mod executable;
-#[cfg(windows)]
+pub mod inventory;
+#[cfg(all(windows, feature = "knowndlls"))]
 mod knowndlls;
+pub mod live;
+pub mod lock;
+pub mod meson;
+pub mod minidump;
+pub mod output;
 pub mod path;
 pub mod pe;
+pub mod provider;
 pub mod query;
+pub mod redist;
+pub mod registration;
+pub mod registry;
+pub mod report;
 pub mod runner;
-#[cfg(not(windows))]
+pub mod sbom;
+pub mod session;
+pub mod shortcut;
+pub mod signature;
+#[cfg(all(feature = "skim", not(any(windows, target_family = "wasm"))))]
 pub mod skim;
 pub mod system;
+#[cfg(test)]
+mod testing;
+#[cfg(all(feature = "tui", not(target_family = "wasm")))]
+pub mod tui;
+#[cfg(feature = "vcx")]
 pub mod vcx;
+pub mod verify;
+#[cfg(feature = "notify")]
+pub mod watch;
@@ -0,0 +1,252 @@
+//! Comparison of a scan with the modules actually loaded by a running process
+//!
+//! The static scan predicts which DLLs a process loads at startup; a snapshot of a running process
+//! (Windows only) or the module list of a minidump (see [`crate::minidump`]) tells which ones it
+//! really loaded, including the ones loaded at runtime with LoadLibrary, and from which file.
+
+use std::collections::HashMap;
+use std::path::PathBuf;
+
+use serde::Serialize;
+
+#[cfg(windows)]
+use crate::common::LookupError;
+use crate::common::{decanonicalize, path_to_string};
+use crate::executable::Executables;
+
+/// Module (executable or DLL) loaded by a process
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct LoadedModule {
+    pub name: String,
+    pub path: PathBuf,
+    /// file version of the loaded module, if known (e.g. recorded in a minidump)
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+}
+
+/// DLL loaded from another file than the one predicted by the scan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct PathMismatch {
+    pub dllname: String,
+    pub predicted: PathBuf,
+    pub loaded: PathBuf,
+}
+
+/// DLL loaded with another file version than the one of the file found by the scan
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct VersionMismatch {
+    pub dllname: String,
+    pub predicted: Option<String>,
+    pub loaded: String,
+}
+
+/// Differences between the DLLs predicted by a scan and the modules loaded by a process
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct LiveComparison {
+    /// modules loaded by the process but not predicted by the scan, i.e. loaded dynamically or
+    /// missed by the scan
+    pub unexpected: Vec<LoadedModule>,
+    /// names of the DLLs found by the scan but not loaded by the process (e.g. delay-loaded DLLs
+    /// that were not needed yet)
+    pub not_loaded: Vec<String>,
+    /// DLLs loaded from another file than the predicted one
+    pub path_mismatches: Vec<PathMismatch>,
+    /// DLLs loaded with another version than the predicted file, if the versions of the modules
+    /// are known
+    pub version_mismatches: Vec<VersionMismatch>,
+}
+
+/// Path in a form that can be compared, whatever its prefix and case
+fn comparable_path(path: &std::path::Path) -> String {
+    decanonicalize(&path_to_string(path)).to_lowercase()
+}
+
+impl LiveComparison {
+    /// Compare a scan with the modules loaded by a process, reporting the executables of the scan
+    /// in order of first appearance and the modules in order of loading
+    pub fn compare(executables: &Executables, modules: &[LoadedModule]) -> Self {
+        let loaded: HashMap<String, &LoadedModule> =
+            modules.iter().map(|m| (m.name.to_lowercase(), m)).collect();
+
+        let mut comparison = Self::default();
+        for e in executables.iter() {
+            let predicted = match e.details.as_ref().and_then(|d| d.full_path.as_ref()) {
+                // API sets resolved through a map have no file to load
+                Some(path) if e.found => path,
+                _ => continue,
+            };
+            let Some(m) = loaded.get(&e.dllname.to_lowercase()) else {
+                comparison.not_loaded.push(e.dllname.clone());
+                continue;
+            };
+            if comparable_path(&m.path) != comparable_path(predicted) {
+                comparison.path_mismatches.push(PathMismatch {
+                    dllname: e.dllname.clone(),
+                    predicted: predicted.clone(),
+                    loaded: m.path.clone(),
+                })
+            }
+            let predicted_version = e.details.as_ref().and_then(|d| d.file_version.clone());
+            if let Some(version) = m.version.as_ref() {
+                if predicted_version.as_ref() != Some(version) {
+                    comparison.version_mismatches.push(VersionMismatch {
+                        dllname: e.dllname.clone(),
+                        predicted: predicted_version,
+                        loaded: version.clone(),
+                    })
+                }
+            }
+        }
+        comparison.unexpected = modules
+            .iter()
+            .filter(|m| !executables.get(&m.name).map(|e| e.found).unwrap_or(false))
+            .cloned()
+            .collect();
+        comparison
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.unexpected.is_empty()
+            && self.not_loaded.is_empty()
+            && self.path_mismatches.is_empty()
+            && self.version_mismatches.is_empty()
+    }
+}
+
+impl std::fmt::Display for LiveComparison {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "The process loaded exactly the predicted DLLs");
+        }
+        for m in &self.unexpected {
+            writeln!(
+                f,
+                "+ {} => {}",
+                m.name,
+                decanonicalize(&path_to_string(&m.path))
+            )?;
+        }
+        for name in &self.not_loaded {
+            writeln!(f, "- {name}")?;
+        }
+        for mismatch in &self.path_mismatches {
+            writeln!(f, "~ {}", mismatch.dllname)?;
+            writeln!(
+                f,
+                "\tpredicted: {}",
+                decanonicalize(&path_to_string(&mismatch.predicted))
+            )?;
+            writeln!(
+                f,
+                "\tloaded: {}",
+                decanonicalize(&path_to_string(&mismatch.loaded))
+            )?;
+        }
+        for mismatch in &self.version_mismatches {
+            writeln!(f, "~ {}", mismatch.dllname)?;
+            writeln!(
+                f,
+                "\tpredicted version: {}",
+                mismatch.predicted.as_deref().unwrap_or("unknown")
+            )?;
+            writeln!(f, "\tloaded version: {}", mismatch.loaded)?;
+        }
+        Ok(())
+    }
+}
+
+/// Modules currently loaded by a process, in order of loading (the executable comes first)
+#[cfg(windows)]
+pub fn loaded_modules(pid: u32) -> Result<Vec<LoadedModule>, LookupError> {
+    extern crate winapi;
+
+    use std::ffi::OsString;
+    use std::os::windows::ffi::OsStringExt;
+    use winapi::um::handleapi::{CloseHandle, INVALID_HANDLE_VALUE};
+    use winapi::um::tlhelp32::{
+        CreateToolhelp32Snapshot, Module32FirstW, Module32NextW, MODULEENTRY32W, TH32CS_SNAPMODULE,
+        TH32CS_SNAPMODULE32,
+    };
+
+    fn from_wide(s: &[u16]) -> OsString {
+        let len = s.iter().position(|&c| c == 0).unwrap_or(s.len());
+        OsString::from_wide(&s[..len])
+    }
+
+    // 32-bit modules are listed too, for the processes running under WOW64
+    let snapshot =
+        unsafe { CreateToolhelp32Snapshot(TH32CS_SNAPMODULE | TH32CS_SNAPMODULE32, pid) };
+    if snapshot == INVALID_HANDLE_VALUE {
+        return Err(LookupError::ScanError(format!(
+            "could not list the modules of process {pid}: {}",
+            std::io::Error::last_os_error()
+        )));
+    }
+    let mut modules = Vec::new();
+    let mut entry: MODULEENTRY32W = unsafe { std::mem::zeroed() };
+    entry.dwSize = std::mem::size_of::<MODULEENTRY32W>() as u32;
+    let mut more = unsafe { Module32FirstW(snapshot, &mut entry) } != 0;
+    while more {
+        modules.push(LoadedModule {
+            name: from_wide(&entry.szModule).to_string_lossy().into_owned(),
+            path: PathBuf::from(from_wide(&entry.szExePath)),
+            version: None,
+        });
+        more = unsafe { Module32NextW(snapshot, &mut entry) } != 0;
+    }
+    unsafe { CloseHandle(snapshot) };
+    Ok(modules)
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::common::LookupError;
+    use crate::live::{LiveComparison, LoadedModule};
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+
+    #[test]
+    fn compare_with_loaded_modules() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+        query.system = None;
+        query.target.user_path.clear();
+        let lookup_path = LookupPath::deduce(&query);
+        let executables = run(&query, &lookup_path)?;
+
+        let other_lib = d.join("test_data/test_project1/DepRunTestLibWrong/build/Debug");
+        let modules = vec![
+            LoadedModule {
+                name: "DepRunTest.exe".to_owned(),
+                path: exe_path,
+                version: None,
+            },
+            LoadedModule {
+                name: "DepRunTestLib.dll".to_owned(),
+                path: other_lib.join("DepRunTestLib.dll"),
+                version: None,
+            },
+            LoadedModule {
+                name: "Plugin.dll".to_owned(),
+                path: other_lib.join("Plugin.dll"),
+                version: None,
+            },
+        ];
+        let comparison = LiveComparison::compare(&executables, &modules);
+        assert_eq!(comparison.unexpected, modules[2..]);
+        assert!(comparison.not_loaded.is_empty());
+        assert_eq!(comparison.path_mismatches.len(), 1);
+        assert_eq!(comparison.path_mismatches[0].dllname, "DepRunTestLib.dll");
+
+        let comparison = LiveComparison::compare(&executables, &modules[..1]);
+        assert!(comparison.unexpected.is_empty());
+        assert_eq!(comparison.not_loaded, vec!["DepRunTestLib.dll".to_owned()]);
+
+        Ok(())
+    }
+}
@@ -0,0 +1,207 @@
+//! Lock files recording the expected location, version and content of the dependencies of an
+//! application, to detect the DLLs swapped or upgraded between two builds
+//!
+//! A lock file is a JSON document mapping each non-system dependency to a pattern of the path it is
+//! loaded from (relative to the directory of the target, or absolute for the files outside it), its
+//! file version and the SHA-256 digest of its content. The patterns can be edited by hand, e.g. to
+//! `plugins/*.dll`; an entry without version or digest does not check them.
+
+use std::collections::BTreeMap;
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{decanonicalize, path_to_string, LookupError};
+use crate::executable::{Executable, ExecutableDetails, Executables};
+
+/// Version of the layout of the lock files
+pub const LOCK_SCHEMA_VERSION: u32 = 1;
+
+/// Expected properties of a dependency
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct LockedDependency {
+    /// pattern of the path of the file, matched case-insensitively, with / as separator
+    pub path: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub version: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub sha256: Option<String>,
+}
+
+impl LockedDependency {
+    /// Whether the locked path pattern matches the path written for a lock file
+    pub fn matches_path(&self, path: &str) -> Result<bool, LookupError> {
+        let options = glob::MatchOptions {
+            case_sensitive: false,
+            ..Default::default()
+        };
+        Ok(glob::Pattern::new(&self.path)?.matches_with(path, options))
+    }
+}
+
+/// Expected dependencies of an application, by DLL name
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct DependencyLock {
+    pub schema_version: u32,
+    pub dependencies: BTreeMap<String, LockedDependency>,
+}
+
+impl DependencyLock {
+    /// Lock the found non-system dependencies of the scan (the roots are not locked, since they
+    /// change with every build); the digests are only recorded if the scan computed them
+    pub fn from_executables(executables: &Executables) -> Result<Self, LookupError> {
+        let app_dir = app_dir(executables)?;
+        let dependencies = executables
+            .iter()
+            .filter(|e| e.depth_first_appearance > 0)
+            .filter_map(|e| {
+                let (details, full_path) = lockable_details(e)?;
+                Some((
+                    e.dllname.clone(),
+                    LockedDependency {
+                        path: lock_path(app_dir.as_deref(), full_path),
+                        version: details.file_version.clone(),
+                        sha256: details.hashes.as_ref().map(|h| h.sha256.clone()),
+                    },
+                ))
+            })
+            .collect();
+        Ok(Self {
+            schema_version: LOCK_SCHEMA_VERSION,
+            dependencies,
+        })
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, LookupError> {
+        let lock: Self = serde_json::from_str(&fs::read_to_string(path.as_ref())?)?;
+        if lock.schema_version > LOCK_SCHEMA_VERSION {
+            return Err(LookupError::ParseError(format!(
+                "{}: unsupported lock file schema version {}",
+                path.as_ref().display(),
+                lock.schema_version
+            )));
+        }
+        Ok(lock)
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), LookupError> {
+        fs::write(path, serde_json::to_string_pretty(self)? + "\n")?;
+        Ok(())
+    }
+
+    /// Entry of the given DLL, looked up case-insensitively
+    pub fn get(&self, dllname: &str) -> Option<(&String, &LockedDependency)> {
+        self.dependencies
+            .iter()
+            .find(|(name, _)| name.eq_ignore_ascii_case(dllname))
+    }
+}
+
+/// Details and path of the executable if it is a found non-system file, whose properties can be
+/// locked
+pub(crate) fn lockable_details(exe: &Executable) -> Option<(&ExecutableDetails, &Path)> {
+    let details = exe
+        .details
+        .as_ref()
+        .filter(|d| exe.found && !d.is_system && !d.is_api_set)?;
+    Some((details, details.full_path.as_deref()?))
+}
+
+/// Directory of the first root of the scan, the locked paths are relative to
+pub(crate) fn app_dir(executables: &Executables) -> Result<Option<PathBuf>, LookupError> {
+    Ok(executables
+        .get_roots()?
+        .first()
+        .and_then(|r| r.details.as_ref())
+        .and_then(|d| d.full_path.as_deref()?.parent())
+        .map(Path::to_path_buf))
+}
+
+/// Path of the file as written in a lock file: relative to the application directory if it lies
+/// under it, with / as separator
+pub(crate) fn lock_path(app_dir: Option<&Path>, path: &Path) -> String {
+    let path = app_dir
+        .and_then(|dir| path.strip_prefix(dir).ok())
+        .unwrap_or(path);
+    decanonicalize(&path_to_string(path)).replace('\\', "/")
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::LookupError;
+    use crate::lock::{DependencyLock, LockedDependency};
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::report::FindingKind;
+    use crate::runner::run;
+
+    #[test]
+    fn lock_and_check() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.compute_hashes = true;
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+
+        let lock = DependencyLock::from_executables(&exes)?;
+        let lib = &lock.dependencies["DepRunTestLib.dll"];
+        assert_eq!(lib.path, "DepRunTestLib.dll");
+        assert_eq!(lib.sha256.as_ref().unwrap().len(), 64);
+        assert_eq!(lock.dependencies.len(), 1);
+        assert!(exes.check_lock(&lock)?.is_empty());
+
+        let temp_dir = tempfile::tempdir()?;
+        let lock_path = temp_dir.path().join("deprun.lock");
+        lock.write(&lock_path)?;
+        assert_eq!(DependencyLock::read(&lock_path)?, lock);
+
+        // a pattern, another digest and a dependency which is not scanned
+        let mut edited = lock.clone();
+        let lib = edited.dependencies.get_mut("DepRunTestLib.dll").unwrap();
+        lib.path = "bin/*.dll".to_owned();
+        lib.sha256 = Some("00".repeat(32));
+        edited.dependencies.insert(
+            "other.dll".to_owned(),
+            LockedDependency {
+                path: "other.dll".to_owned(),
+                version: None,
+                sha256: None,
+            },
+        );
+        let report = exes.check_lock(&edited)?;
+        let properties: Vec<(&str, bool)> = report
+            .findings()
+            .iter()
+            .map(|f| match &f.kind {
+                FindingKind::LockMismatch {
+                    dll,
+                    property,
+                    found,
+                    ..
+                } => (
+                    if dll == "other.dll" {
+                        "missing"
+                    } else {
+                        property.as_str()
+                    },
+                    found.is_some(),
+                ),
+                _ => panic!("unexpected finding {f}"),
+            })
+            .collect();
+        assert_eq!(
+            properties,
+            vec![("path", true), ("sha256", true), ("missing", false)]
+        );
+
+        // the dependencies not in the lock file are only warnings
+        let report = exes.check_lock(&DependencyLock::default())?;
+        assert_eq!(
+            report.findings()[0].to_string(),
+            "[warning DR0013] DepRunTestLib.dll: not in the lock file (loaded from DepRunTestLib.dll)"
+        );
+        Ok(())
+    }
+}
@@ -0,0 +1,191 @@
+//! Enumeration of the runtime targets (executables and DLLs) of a Meson build directory
+//!
+//! The targets are read from the introspection files that Meson writes in the meson-info
+//! subdirectory of every build directory (https://mesonbuild.com/IDE-integration.html).
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use serde::{Deserialize, Serialize};
+
+use crate::common::{host_path, LookupError};
+use crate::directory::{scan_executables, DirectoryScan};
+use crate::query::LookupQuery;
+use crate::session::ScanSession;
+
+/// Kind of a Meson runtime target
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub enum MesonTargetKind {
+    Executable,
+    SharedLibrary,
+    /// shared_module(), i.e. a plugin loaded at runtime
+    SharedModule,
+}
+
+/// Executable or DLL produced by a Meson build directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MesonTarget {
+    pub name: String,
+    pub kind: MesonTargetKind,
+    /// path of the produced file
+    pub artifact: PathBuf,
+}
+
+impl MesonTarget {
+    /// Query for the scan of the produced file, deduced from its location
+    pub fn lookup_query(&self) -> Result<LookupQuery, LookupError> {
+        LookupQuery::deduce_from_executable_location(&self.artifact)
+    }
+}
+
+#[derive(Deserialize)]
+struct MesonInfo {
+    directories: MesonInfoDirectories,
+}
+
+#[derive(Deserialize)]
+struct MesonInfoDirectories {
+    build: String,
+}
+
+#[derive(Deserialize)]
+struct IntroTarget {
+    name: String,
+    #[serde(rename = "type")]
+    target_type: String,
+    #[serde(default)]
+    filename: Vec<String>,
+}
+
+fn normalize_separators(path: &str) -> String {
+    path.replace('\\', "/").trim_end_matches('/').to_owned()
+}
+
+/// Path on this host of a file written by Meson in the build directory
+///
+/// The introspection files contain absolute paths, which are relocated if the build directory was
+/// moved (e.g. copied from the machine or container that cross-compiled the project).
+fn artifact_path(filename: &str, original_build_dir: Option<&str>, build_dir: &Path) -> PathBuf {
+    let filename = normalize_separators(filename);
+    if let Some(original_build_dir) = original_build_dir {
+        let original_build_dir = normalize_separators(original_build_dir);
+        if let Some(relative) = filename
+            .strip_prefix(&original_build_dir)
+            .and_then(|r| r.strip_prefix('/'))
+        {
+            return build_dir.join(relative);
+        }
+    }
+    let path = PathBuf::from(&filename);
+    if path.exists() {
+        path
+    } else {
+        host_path(&filename, build_dir)
+    }
+}
+
+/// Runtime targets of a Meson build directory, read from meson-info/intro-targets.json
+///
+/// Static libraries and custom targets are skipped.
+pub fn read_build_dir<P: AsRef<Path>>(build_dir: P) -> Result<Vec<MesonTarget>, LookupError> {
+    let build_dir = fs::canonicalize(build_dir.as_ref())?;
+    let info_dir = build_dir.join("meson-info");
+    let targets_path = info_dir.join("intro-targets.json");
+    if !targets_path.is_file() {
+        return Err(LookupError::ContextDeductionError(format!(
+            "{} is not a Meson build directory (no meson-info/intro-targets.json found)",
+            build_dir.display()
+        )));
+    }
+    let info_path = info_dir.join("meson-info.json");
+    let original_build_dir = if info_path.is_file() {
+        let info: MesonInfo = serde_json::from_str(&fs::read_to_string(info_path)?)?;
+        Some(info.directories.build)
+    } else {
+        None
+    };
+    let intro_targets: Vec<IntroTarget> = serde_json::from_str(&fs::read_to_string(targets_path)?)?;
+
+    let mut targets = Vec::new();
+    for target in intro_targets {
+        let kind = match target.target_type.as_str() {
+            "executable" => MesonTargetKind::Executable,
+            "shared library" => MesonTargetKind::SharedLibrary,
+            "shared module" => MesonTargetKind::SharedModule,
+            _ => continue,
+        };
+        // the first output is the executable or DLL, possibly followed by the import library
+        let filename = target
+            .filename
+            .iter()
+            .find(|f| {
+                let f = f.to_ascii_lowercase();
+                f.ends_with(".exe") || f.ends_with(".dll")
+            })
+            .or_else(|| target.filename.first());
+        if let Some(filename) = filename {
+            targets.push(MesonTarget {
+                name: target.name,
+                kind,
+                artifact: artifact_path(filename, original_build_dir.as_deref(), &build_dir),
+            });
+        }
+    }
+    Ok(targets)
+}
+
+/// Scan and check the runtime targets of a Meson build directory that exist on disk
+///
+/// As for `scan_directory`, the query of each target is passed to `configure` for adjustments.
+pub fn scan_build_dir<P: AsRef<Path>, F: Fn(&mut LookupQuery)>(
+    build_dir: P,
+    configure: F,
+) -> Result<DirectoryScan, LookupError> {
+    let build_dir = fs::canonicalize(build_dir.as_ref())?;
+    let artifacts = read_build_dir(&build_dir)?
+        .into_iter()
+        .map(|t| t.artifact)
+        .filter(|a| a.is_file())
+        .collect();
+    Ok(scan_executables(
+        ScanSession::new(),
+        build_dir,
+        artifacts,
+        configure,
+    ))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::LookupError;
+    use crate::meson::{read_build_dir, scan_build_dir, MesonTargetKind};
+
+    #[test]
+    fn intro_targets() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build-same-output");
+
+        // the build directory was relocated from the one recorded in meson-info.json
+        let targets = read_build_dir(&build_dir)?;
+        assert_eq!(targets.len(), 3);
+        assert_eq!(targets[0].name, "DepRunTest");
+        assert_eq!(targets[0].kind, MesonTargetKind::Executable);
+        assert!(targets[0].artifact.ends_with("bin/Debug/DepRunTest.exe"));
+        assert!(targets[0].artifact.is_file());
+        assert_eq!(targets[1].kind, MesonTargetKind::SharedLibrary);
+        assert!(targets[1].artifact.ends_with("bin/Debug/DepRunTestLib.dll"));
+        assert_eq!(targets[2].kind, MesonTargetKind::SharedModule);
+
+        let query = targets[0].lookup_query()?;
+        assert_eq!(query.target.target_exe, targets[0].artifact);
+
+        // the plugin was not built, so only the executable and its DLL are scanned
+        let scan = scan_build_dir(&build_dir, |_| {})?;
+        assert_eq!(scan.targets.len(), 2);
+        assert!(!scan.summary().missing_dlls.contains("DepRunTestLib.dll"));
+
+        assert!(read_build_dir(d.join("test_data/vcx")).is_err());
+
+        Ok(())
+    }
+}
@@ -0,0 +1,264 @@
+//! Module list of Windows minidumps (.dmp), telling which DLLs a crashed process had loaded
+//!
+//! The modules can be compared with a static scan of the executable (see
+//! [`crate::live::LiveComparison`]), or used to reconstruct the dependency tree of what was
+//! loaded, by forcing the resolution of each DLL to the file recorded in the dump (see
+//! [`override_with_modules`]).
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use serde::Serialize;
+
+use crate::common::{FileOperation, LookupError};
+use crate::live::LoadedModule;
+use crate::query::{DllOverride, LookupQuery};
+
+/// "MDMP"
+const MINIDUMP_SIGNATURE: u32 = 0x504d_444d;
+const MODULE_LIST_STREAM: u32 = 4;
+/// size of a MINIDUMP_MODULE record
+const MODULE_SIZE: usize = 108;
+/// signature of a VS_FIXEDFILEINFO structure
+const FIXED_FILE_INFO_SIGNATURE: u32 = 0xfeef_04bd;
+
+/// Module recorded in a minidump
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct MinidumpModule {
+    pub name: String,
+    /// path of the module on the machine where the dump was written
+    pub path: PathBuf,
+    /// file version from the version resource, if the module had one
+    pub version: Option<String>,
+    pub base_address: u64,
+    pub size: u32,
+    /// link time stamp of the module
+    pub timestamp: u32,
+}
+
+impl MinidumpModule {
+    pub fn loaded_module(&self) -> LoadedModule {
+        LoadedModule {
+            name: self.name.clone(),
+            path: self.path.clone(),
+            version: self.version.clone(),
+        }
+    }
+}
+
+/// Read the module list of a minidump, in order of loading (the executable comes first)
+pub fn read_modules<P: AsRef<Path>>(path: P) -> Result<Vec<MinidumpModule>, LookupError> {
+    let content = fs::read(path.as_ref())?;
+    parse_modules(&content).map_err(|e| e.in_file(path, FileOperation::Parse))
+}
+
+/// Parse the module list of the content of a minidump
+pub fn parse_modules(content: &[u8]) -> Result<Vec<MinidumpModule>, LookupError> {
+    let truncated = || LookupError::ParseError("truncated minidump".to_owned());
+    // offsets computed from the values of the dump, which cannot be trusted
+    let offset = |base: usize, index: usize, size: usize| {
+        index
+            .checked_mul(size)
+            .and_then(|o| o.checked_add(base))
+            .ok_or_else(truncated)
+    };
+    let field = |base: usize, delta: usize| offset(base, 1, delta);
+    let u32_at = |offset: usize| -> Result<u32, LookupError> {
+        let end = offset.checked_add(4).ok_or_else(truncated)?;
+        let bytes = content.get(offset..end).ok_or_else(truncated)?;
+        Ok(u32::from_le_bytes(bytes.try_into().unwrap()))
+    };
+    let u64_at = |at: usize| -> Result<u64, LookupError> {
+        Ok(u64::from(u32_at(at)?) | u64::from(u32_at(field(at, 4)?)?) << 32)
+    };
+
+    if u32_at(0)? != MINIDUMP_SIGNATURE {
+        return Err(LookupError::ParseError(
+            "not a minidump (wrong signature)".to_owned(),
+        ));
+    }
+    let stream_count = u32_at(8)? as usize;
+    let directory = u32_at(12)? as usize;
+    let module_list = (0..stream_count)
+        .map_while(|i| offset(directory, i, 12).ok())
+        .find(|&entry| u32_at(entry).ok() == Some(MODULE_LIST_STREAM))
+        .ok_or_else(|| LookupError::ParseError("the minidump has no module list".to_owned()))?;
+    let module_list = u32_at(field(module_list, 8)?)? as usize;
+
+    let module_count = u32_at(module_list)? as usize;
+    // the count is not trusted to reserve memory: at most one module fits in each record
+    let mut modules = Vec::with_capacity(module_count.min(content.len() / MODULE_SIZE));
+    for i in 0..module_count {
+        let module = offset(field(module_list, 4)?, i, MODULE_SIZE)?;
+        let path = PathBuf::from(read_string(content, u32_at(field(module, 20)?)? as usize)?);
+        // the dump holds Windows paths, whatever the host
+        let name = path
+            .to_string_lossy()
+            .rsplit(['\\', '/'])
+            .next()
+            .unwrap_or_default()
+            .to_owned();
+        let version = if u32_at(field(module, 24)?)? == FIXED_FILE_INFO_SIGNATURE {
+            let (ms, ls) = (u32_at(field(module, 32)?)?, u32_at(field(module, 36)?)?);
+            (ms != 0 || ls != 0)
+                .then(|| format!("{}.{}.{}.{}", ms >> 16, ms & 0xffff, ls >> 16, ls & 0xffff))
+        } else {
+            None
+        };
+        modules.push(MinidumpModule {
+            name,
+            path,
+            version,
+            base_address: u64_at(module)?,
+            size: u32_at(field(module, 8)?)?,
+            timestamp: u32_at(field(module, 16)?)?,
+        });
+    }
+    Ok(modules)
+}
+
+/// Read a MINIDUMP_STRING (length in bytes, then UTF-16 characters)
+fn read_string(content: &[u8], offset: usize) -> Result<String, LookupError> {
+    let truncated = || LookupError::ParseError("truncated minidump".to_owned());
+    let start = offset.checked_add(4).ok_or_else(truncated)?;
+    let length = content.get(offset..start).ok_or_else(truncated)?;
+    let length = u32::from_le_bytes(length.try_into().unwrap()) as usize;
+    let end = start.checked_add(length).ok_or_else(truncated)?;
+    let bytes = content.get(start..end).ok_or_else(truncated)?;
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    Ok(String::from_utf16_lossy(&units))
+}
+
+/// Force the resolution of the DLLs of the query to the modules of a dump: the ones whose file
+/// exists on this machine are loaded from it, the others are assumed present
+///
+/// A scan of the executable of the dump with this query shows the dependency tree of what was
+/// actually loaded.
+pub fn override_with_modules(query: &mut LookupQuery, modules: &[MinidumpModule]) {
+    let target_name = query
+        .target
+        .target_exe
+        .file_name()
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap_or_default();
+    for module in modules {
+        if module.name.eq_ignore_ascii_case(&target_name) {
+            continue;
+        }
+        let dll_override = if module.path.is_file() {
+            DllOverride::Path(module.path.clone())
+        } else {
+            DllOverride::AssumePresent
+        };
+        query.override_dll(&module.name, dll_override);
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::common::LookupError;
+    use crate::live::LiveComparison;
+    use crate::minidump::{override_with_modules, parse_modules};
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+
+    /// Minidump with just a module list, of the given paths and file versions
+    fn minidump(modules: &[(&str, Option<(u32, u32)>)]) -> Vec<u8> {
+        let module_list = 44;
+        let strings = module_list + 4 + modules.len() * 108;
+        let mut content = Vec::new();
+        for value in [0x504d444d, 0xa793, 1, 32, 0, 0, 0, 0] {
+            content.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        for value in [4, (strings - module_list) as u32, module_list as u32] {
+            content.extend_from_slice(&u32::to_le_bytes(value));
+        }
+        content.extend_from_slice(&u32::to_le_bytes(modules.len() as u32));
+        let mut string_data = Vec::new();
+        for (i, (path, version)) in modules.iter().enumerate() {
+            let mut module = vec![0u8; 108];
+            module[..8].copy_from_slice(&u64::to_le_bytes(0x1_4000_0000 + i as u64 * 0x10000));
+            module[8..12].copy_from_slice(&u32::to_le_bytes(0x10000));
+            let name_rva = (strings + string_data.len()) as u32;
+            module[20..24].copy_from_slice(&u32::to_le_bytes(name_rva));
+            if let Some((ms, ls)) = version {
+                module[24..28].copy_from_slice(&u32::to_le_bytes(0xfeef04bd));
+                module[32..36].copy_from_slice(&u32::to_le_bytes(*ms));
+                module[36..40].copy_from_slice(&u32::to_le_bytes(*ls));
+            }
+            content.extend_from_slice(&module);
+            let units: Vec<u16> = path.encode_utf16().collect();
+            string_data.extend_from_slice(&u32::to_le_bytes(units.len() as u32 * 2));
+            string_data.extend(units.iter().flat_map(|u| u.to_le_bytes()));
+        }
+        content.extend_from_slice(&string_data);
+        content
+    }
+
+    #[test]
+    fn minidump_modules() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let other_lib =
+            d.join("test_data/test_project1/DepRunTestLibWrong/build/Debug/DepRunTestLib.dll");
+        let content = minidump(&[
+            (exe_path.to_str().unwrap(), None),
+            (
+                other_lib.to_str().unwrap(),
+                Some((0x0001_0002, 0x0003_0004)),
+            ),
+            (
+                r"C:\Windows\System32\KERNEL32.DLL",
+                Some((0x000a_0000, 0x4a61_0001)),
+            ),
+            (r"C:\Program Files\App\Plugin.dll", None),
+        ]);
+        let modules = parse_modules(&content)?;
+        assert_eq!(modules.len(), 4);
+        assert_eq!(modules[1].name, "DepRunTestLib.dll");
+        assert_eq!(modules[1].version.as_deref(), Some("1.2.3.4"));
+        assert_eq!(modules[2].name, "KERNEL32.DLL");
+        assert_eq!(modules[2].version.as_deref(), Some("10.0.19041.1"));
+        assert_eq!(
+            modules[3].path,
+            PathBuf::from(r"C:\Program Files\App\Plugin.dll")
+        );
+        assert_eq!(modules[3].base_address, 0x1_4003_0000);
+        assert!(parse_modules(&content[..100]).is_err());
+
+        // a forged module count or string offset is reported as truncation, without reserving
+        // memory for it
+        let mut forged = content.clone();
+        forged[44..48].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_modules(&forged).is_err());
+        let mut forged = content.clone();
+        forged[48 + 20..48 + 24].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert!(parse_modules(&forged).is_err());
+
+        // the tree of what was loaded
+        let mut query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+        query.system = None;
+        let static_scan = run(&query, &LookupPath::deduce(&query))?;
+        override_with_modules(&mut query, &modules);
+        let loaded = run(&query, &LookupPath::deduce(&query))?;
+        let lib = loaded.get("DepRunTestLib.dll").unwrap();
+        assert_eq!(lib.details.as_ref().unwrap().full_path, Some(other_lib));
+        assert!(loaded.get("KERNEL32.dll").unwrap().found);
+
+        // the differences with the static scan
+        let loaded_modules: Vec<_> = modules.iter().map(|m| m.loaded_module()).collect();
+        let comparison = LiveComparison::compare(&static_scan, &loaded_modules);
+        assert_eq!(comparison.path_mismatches.len(), 1);
+        assert_eq!(comparison.version_mismatches.len(), 1);
+        assert_eq!(comparison.version_mismatches[0].loaded, "1.2.3.4");
+        assert!(comparison.unexpected.iter().any(|m| m.name == "Plugin.dll"));
+        Ok(())
+    }
+}
@@ -0,0 +1,893 @@
+//! Rendering of the results of a scan in the output formats supported by the binaries
+
+use std::collections::{BTreeMap, HashSet};
+use std::fmt::Write;
+use std::path::Path;
+
+use serde_json::json;
+
+use crate::common::{decanonicalize, path_to_string, readable_path, xml_escape, LookupError};
+use crate::executable::{DependencyKind, Executable, ExecutableDetails, Executables};
+use crate::report::{ExecutablesCheckReport, Rule, Severity};
+
+/// Format of a rendered scan result
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum OutputFormat {
+    /// dependency tree, visited depth-first (deprun)
+    Tree,
+    /// flat list of DLLs with their locations, as GNU ldd (wldd)
+    Ldd,
+    /// versioned JSON document, which can be loaded back with `Executables::from_json`
+    Json,
+    /// one JSON object per executable per line
+    Ndjson,
+    Csv,
+    /// Graphviz graph of the dependency edges
+    Dot,
+    /// standalone HTML page with the executables and the check findings
+    Html,
+    /// SARIF log of the check findings, for code scanning tools
+    Sarif,
+    /// JUnit XML test report of the check findings, for CI servers
+    Junit,
+    /// JSON dependency chain with the layout of the Dependencies tool (`Dependencies.exe -chain
+    /// -json`)
+    Dependencies,
+    /// tab-separated module list with the columns and markers of Dependency Walker
+    DwModules,
+}
+
+impl OutputFormat {
+    /// Whether rendering needs the result of the checks on the executables
+    pub fn needs_check(&self) -> bool {
+        matches!(self, Self::Html | Self::Sarif | Self::Junit)
+    }
+}
+
+impl std::str::FromStr for OutputFormat {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "tree" => Ok(Self::Tree),
+            "ldd" => Ok(Self::Ldd),
+            "json" => Ok(Self::Json),
+            "ndjson" => Ok(Self::Ndjson),
+            "csv" => Ok(Self::Csv),
+            "dot" => Ok(Self::Dot),
+            "html" => Ok(Self::Html),
+            "sarif" => Ok(Self::Sarif),
+            "junit" => Ok(Self::Junit),
+            "dependencies" => Ok(Self::Dependencies),
+            "dw-modules" => Ok(Self::DwModules),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown output format {s} (expected tree, ldd, json, ndjson, csv, dot, html, sarif, junit, dependencies or dw-modules)"
+            ))),
+        }
+    }
+}
+
+/// Options shared by the output formats
+#[derive(Debug, Clone, Default)]
+pub struct OutputOptions {
+    /// include system DLLs in the tree, ldd, CSV and DOT outputs
+    pub include_system_dlls: bool,
+    /// maximum depth of the tree output
+    pub max_depth: Option<usize>,
+    /// append the architecture and subsystem of each DLL to the ldd output, e.g. "(x64, GUI)"
+    pub show_architecture: bool,
+    /// color the tree output with ANSI escape codes (missing: red, system: dim, known DLL: blue)
+    pub color: bool,
+}
+
+/// Whether to color the output
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ColorChoice {
+    /// color if the standard output is a terminal and the NO_COLOR variable is not set
+    Auto,
+    Always,
+    Never,
+}
+
+impl ColorChoice {
+    /// Whether the output to the standard output should be colored
+    pub fn enabled(&self) -> bool {
+        use std::io::IsTerminal;
+        match self {
+            Self::Always => true,
+            Self::Never => false,
+            Self::Auto => {
+                std::env::var_os("NO_COLOR")
+                    .map(|v| v.is_empty())
+                    .unwrap_or(true)
+                    && std::io::stdout().is_terminal()
+            }
+        }
+    }
+}
+
+impl std::str::FromStr for ColorChoice {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "auto" => Ok(Self::Auto),
+            "always" => Ok(Self::Always),
+            "never" => Ok(Self::Never),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown color choice {s} (expected auto, always or never)"
+            ))),
+        }
+    }
+}
+
+/// Render the executables (and for some formats the check report) in the given format
+pub fn render(
+    format: OutputFormat,
+    executables: &Executables,
+    report: &ExecutablesCheckReport,
+    options: &OutputOptions,
+) -> Result<String, LookupError> {
+    Ok(match format {
+        OutputFormat::Tree => render_tree(executables, options)?,
+        OutputFormat::Ldd => render_ldd(executables, options),
+        OutputFormat::Json => serde_json::to_string(executables)?,
+        OutputFormat::Ndjson => {
+            let mut out = String::new();
+            for e in executables.iter() {
+                out += &serde_json::to_string(e)?;
+                out.push('\n');
+            }
+            out
+        }
+        OutputFormat::Csv => render_csv(executables, options),
+        OutputFormat::Dot => render_dot(executables, options),
+        OutputFormat::Html => render_html(executables, report),
+        OutputFormat::Sarif => serde_json::to_string_pretty(&sarif(executables, report))?,
+        OutputFormat::Junit => report.to_junit_xml(executables),
+        OutputFormat::Dependencies => {
+            serde_json::to_string_pretty(&dependencies_chain(executables, options)?)?
+        }
+        OutputFormat::DwModules => render_dw_modules(executables, options),
+    })
+}
+
+fn is_shown(e: &Executable, options: &OutputOptions) -> bool {
+    options.include_system_dlls || !e.details.as_ref().map(|d| d.is_system).unwrap_or(false)
+}
+
+fn render_tree(executables: &Executables, options: &OutputOptions) -> Result<String, LookupError> {
+    let mut out = String::new();
+    // one tree for each target of the scan
+    for root in executables.get_roots()? {
+        if is_shown(root, options) && options.max_depth.map(|d| d > 0).unwrap_or(true) {
+            visit_depth_first(
+                root,
+                "",
+                "",
+                executables,
+                options,
+                &mut Vec::new(),
+                &mut out,
+            );
+        }
+    }
+    Ok(out)
+}
+
+const ANSI_RED: &str = "\x1b[31m";
+const ANSI_BLUE: &str = "\x1b[34m";
+const ANSI_DIM: &str = "\x1b[2m";
+const ANSI_RESET: &str = "\x1b[0m";
+
+/// Print an executable and its dependencies with tree branches: `prefix` is drawn before the
+/// branch of this executable, and continued for its dependencies
+///
+/// `ancestors` holds the lowercase names of the executables on the branch above this one, whose
+/// number is the depth of this one; an executable reached again below itself closes a cycle,
+/// which is marked instead of followed.
+fn visit_depth_first(
+    e: &Executable,
+    prefix: &str,
+    branch: &str,
+    exes: &Executables,
+    options: &OutputOptions,
+    ancestors: &mut Vec<String>,
+    out: &mut String,
+) {
+    let key = e.dllname.to_lowercase();
+    if ancestors.contains(&key) {
+        let _ = writeln!(out, "{prefix}{branch}{} (cycle)", e.dllname);
+        return;
+    }
+    let folder = if !e.found {
+        "not found".to_owned()
+    } else if let Some(details) = &e.details {
+        match details.full_path.as_deref().and_then(Path::parent) {
+            Some(parent) => readable_path(parent),
+            // API set resolved through a map, without a file on disk
+            None if details.is_api_set => "(virtual)".to_owned(),
+            // DLL guaranteed by the target OS profile
+            None => "(assumed present)".to_owned(),
+        }
+    } else {
+        "not searched".to_owned()
+    };
+    let is_known_dll = e.details.as_ref().map(|d| d.is_known_dll).unwrap_or(false);
+    let extra_tag = if is_known_dll {
+        " [Known DLL]".to_owned()
+    } else if let Some(host) = e.details.as_ref().and_then(|d| d.api_set_host.as_ref()) {
+        format!(" [API set -> {host}]")
+    } else {
+        "".to_owned()
+    };
+    let label = format!("{} => {}{}", e.dllname, folder, extra_tag);
+    let style = if !options.color {
+        None
+    } else if !e.found {
+        Some(ANSI_RED)
+    } else if is_known_dll {
+        Some(ANSI_BLUE)
+    } else if e.details.as_ref().map(|d| d.is_system).unwrap_or(false) {
+        Some(ANSI_DIM)
+    } else {
+        None
+    };
+    let _ = match style {
+        Some(style) => writeln!(out, "{prefix}{branch}{style}{label}{ANSI_RESET}"),
+        None => writeln!(out, "{prefix}{branch}{label}"),
+    };
+
+    if options
+        .max_depth
+        .map(|d| ancestors.len() + 1 >= d)
+        .unwrap_or(false)
+    {
+        return;
+    }
+    let dependencies: Vec<&Executable> = e
+        .details
+        .iter()
+        .flat_map(|details| details.all_dependencies())
+        .filter_map(|d| exes.get(d))
+        .filter(|de| is_shown(de, options))
+        .collect();
+    let child_prefix = match branch {
+        "" => prefix.to_owned(),
+        "└── " => format!("{prefix}    "),
+        _ => format!("{prefix}│   "),
+    };
+    ancestors.push(key);
+    for (i, de) in dependencies.iter().enumerate() {
+        let child_branch = if i + 1 == dependencies.len() {
+            "└── "
+        } else {
+            "├── "
+        };
+        visit_depth_first(
+            de,
+            &child_prefix,
+            child_branch,
+            exes,
+            options,
+            ancestors,
+            out,
+        );
+    }
+    ancestors.pop();
+}
+
+fn render_ldd(executables: &Executables, options: &OutputOptions) -> String {
+    let prefix = " ".repeat(8); // as ldd
+    let mut out = String::new();
+    for e in executables.iter().skip(1) {
+        if !is_shown(e, options) {
+            continue;
+        }
+        if e.found {
+            let details = e.details.as_ref();
+            let location = match details {
+                Some(details) => match &details.full_path {
+                    Some(full_path) => decanonicalize(&path_to_string(full_path)),
+                    // API set resolved through a map, without a file on disk
+                    None => format!(
+                        "{} (API set)",
+                        details.api_set_host.as_deref().unwrap_or_default()
+                    ),
+                },
+                None => String::new(),
+            };
+            let machine = details
+                .filter(|_| options.show_architecture)
+                .map(|d| {
+                    let fields: Vec<String> = [
+                        d.architecture.map(|a| a.to_string()),
+                        d.subsystem.map(|s| s.to_string()),
+                    ]
+                    .into_iter()
+                    .flatten()
+                    .collect();
+                    fields
+                })
+                .filter(|fields| !fields.is_empty())
+                .map(|fields| format!(" ({})", fields.join(", ")))
+                .unwrap_or_default();
+            let _ = writeln!(out, "{}{} => {}{}", &prefix, &e.dllname, location, machine);
+        } else {
+            let _ = writeln!(
+                out,
+                "{}{} => not found",
+                &prefix,
+                e.details
+                    .as_ref()
+                    .and_then(|d| d.full_path.as_deref())
+                    .map(readable_path)
+                    .unwrap_or(format!("{:?}", e.dllname))
+            );
+        }
+    }
+    out
+}
+
+fn csv_field(s: &str) -> String {
+    if s.contains([',', '"', '\n']) {
+        format!("\"{}\"", s.replace('"', "\"\""))
+    } else {
+        s.to_owned()
+    }
+}
+
+fn render_csv(executables: &Executables, options: &OutputOptions) -> String {
+    let mut out = String::from(
+        "dllname,depth,found,is_system,is_api_set,is_known_dll,full_path,file_version\n",
+    );
+    for e in executables.iter() {
+        if !is_shown(e, options) {
+            continue;
+        }
+        let details = e.details.as_ref();
+        let flag = |f: fn(&ExecutableDetails) -> bool| {
+            details.map(|d| f(d).to_string()).unwrap_or_default()
+        };
+        let _ = writeln!(
+            out,
+            "{},{},{},{},{},{},{},{}",
+            csv_field(&e.dllname),
+            e.depth_first_appearance,
+            e.found,
+            flag(|d| d.is_system),
+            flag(|d| d.is_api_set),
+            flag(|d| d.is_known_dll),
+            csv_field(
+                &details
+                    .and_then(|d| d.full_path.as_deref())
+                    .map(path_to_string)
+                    .unwrap_or_default()
+            ),
+            csv_field(
+                details
+                    .and_then(|d| d.file_version.as_deref())
+                    .unwrap_or_default()
+            ),
+        );
+    }
+    out
+}
+
+/// Dependency chain in the layout of the Dependencies tool: the root module with its dependencies,
+/// recursively (the modules already listed are not expanded again); a list of them if the scan has
+/// several roots
+fn dependencies_chain(
+    executables: &Executables,
+    options: &OutputOptions,
+) -> Result<serde_json::Value, LookupError> {
+    let roots = executables.get_roots()?;
+    let app_dir = roots
+        .first()
+        .and_then(|r| r.details.as_ref())
+        .and_then(|d| d.full_path.as_deref()?.parent());
+    let mut expanded = HashSet::new();
+    let mut chains: Vec<serde_json::Value> = roots
+        .iter()
+        .map(|root| {
+            json!({
+                "Root": dependencies_node(root, 0, app_dir, executables, options, &mut expanded)
+            })
+        })
+        .collect();
+    Ok(if chains.len() == 1 {
+        chains.remove(0)
+    } else {
+        serde_json::Value::Array(chains)
+    })
+}
+
+fn dependencies_node(
+    e: &Executable,
+    depth: usize,
+    app_dir: Option<&Path>,
+    executables: &Executables,
+    options: &OutputOptions,
+    expanded: &mut HashSet<String>,
+) -> serde_json::Value {
+    let mut dependencies = Vec::new();
+    if expanded.insert(e.dllname.to_lowercase()) {
+        for name in e
+            .details
+            .as_ref()
+            .map(|d| d.all_dependencies())
+            .unwrap_or_default()
+        {
+            if let Some(dependency) = executables.get(name).filter(|d| is_shown(d, options)) {
+                dependencies.push(dependencies_node(
+                    dependency,
+                    depth + 1,
+                    app_dir,
+                    executables,
+                    options,
+                    expanded,
+                ));
+            }
+        }
+    }
+    json!({
+        "Name": e.dllname,
+        "Filepath": e
+            .details
+            .as_ref()
+            .and_then(|d| d.full_path.as_deref())
+            .map(readable_path),
+        "ModuleLocation": dependencies_module_location(e, depth, app_dir),
+        "Depth": depth,
+        "Dependencies": dependencies,
+    })
+}
+
+/// Search strategy of the Dependencies tool (ModuleSearchStrategy) which finds the module
+fn dependencies_module_location(
+    e: &Executable,
+    depth: usize,
+    app_dir: Option<&Path>,
+) -> &'static str {
+    let details = match &e.details {
+        _ if depth == 0 => return "ROOT",
+        Some(d) if e.found => d,
+        _ => return "NOT_FOUND",
+    };
+    let dir = details.full_path.as_deref().and_then(Path::parent);
+    if details.is_api_set {
+        "ApiSetSchema"
+    } else if details.is_known_dll {
+        "WellKnownDlls"
+    } else if details.is_system {
+        let in_system_dir = dir.and_then(|d| d.file_name()).is_some_and(|n| {
+            n.eq_ignore_ascii_case("system32") || n.eq_ignore_ascii_case("syswow64")
+        });
+        if in_system_dir {
+            "System32Folder"
+        } else {
+            "WindowsFolder"
+        }
+    } else if dir.is_some() && dir == app_dir {
+        "ApplicationDirectory"
+    } else {
+        "Environment"
+    }
+}
+
+/// Module list with the columns of Dependency Walker, whose first one holds its markers: `?` for
+/// the missing modules, `D` for the delay-load ones and `*` for the dynamic ones
+fn render_dw_modules(executables: &Executables, options: &OutputOptions) -> String {
+    let mut out = String::from("PI\tModule\tFile Size\tCPU\tSubsystem\tFile Ver\n");
+    for e in executables.iter() {
+        if !is_shown(e, options) {
+            continue;
+        }
+        let importers = executables.importers_of(&e.dllname);
+        let only = |kind: DependencyKind| {
+            !importers.is_empty() && importers.iter().all(|edge| edge.kind == kind)
+        };
+        let marker = if !e.found {
+            "?"
+        } else if only(DependencyKind::DelayLoad) {
+            "D"
+        } else if only(DependencyKind::DynamicLoad) {
+            "*"
+        } else {
+            ""
+        };
+        let details = e.details.as_ref();
+        let _ = writeln!(
+            out,
+            "{marker}\t{}\t{}\t{}\t{}\t{}",
+            details
+                .and_then(|d| d.full_path.as_deref())
+                .map(readable_path)
+                .unwrap_or_else(|| e.dllname.clone()),
+            details
+                .and_then(|d| d.file_stamp.as_ref())
+                .map(|s| s.size.to_string())
+                .unwrap_or_default(),
+            details
+                .and_then(|d| d.architecture)
+                .map(|a| a.to_string())
+                .unwrap_or_default(),
+            details
+                .and_then(|d| d.subsystem)
+                .map(|s| s.to_string())
+                .unwrap_or_default(),
+            details
+                .and_then(|d| d.file_version.as_deref())
+                .unwrap_or_default(),
+        );
+    }
+    out
+}
+
+fn render_dot(executables: &Executables, options: &OutputOptions) -> String {
+    let shown = |name: &str| executables.get(name).map(|e| is_shown(e, options));
+    let mut out = String::from("digraph dependencies {\n    node [shape=box];\n");
+    for e in executables.iter() {
+        if !is_shown(e, options) {
+            continue;
+        }
+        let style = if !e.found {
+            " color=red fontcolor=red"
+        } else if e.details.as_ref().map(|d| d.is_system).unwrap_or(false) {
+            " color=gray fontcolor=gray"
+        } else {
+            ""
+        };
+        let _ = writeln!(
+            out,
+            "    \"{}\" [label=\"{}\"{style}];",
+            dot_escape(&e.dllname.to_lowercase()),
+            dot_escape(&e.dllname)
+        );
+    }
+    for edge in executables.edges() {
+        if shown(&edge.importer) != Some(true) || shown(&edge.importee) != Some(true) {
+            continue;
+        }
+        let style = match edge.kind {
+            DependencyKind::Static | DependencyKind::ApiSet => "",
+            DependencyKind::DelayLoad => " [style=dashed]",
+            DependencyKind::Forwarded => " [style=dotted]",
+            DependencyKind::DynamicLoad => " [style=dashed, color=gray]",
+        };
+        let _ = writeln!(
+            out,
+            "    \"{}\" -> \"{}\"{style};",
+            dot_escape(&edge.importer.to_lowercase()),
+            dot_escape(&edge.importee.to_lowercase())
+        );
+    }
+    out += "}\n";
+    out
+}
+
+fn render_html(executables: &Executables, report: &ExecutablesCheckReport) -> String {
+    let title = executables.root_names(", ");
+    let mut out = format!(
+        "<!DOCTYPE html>\n<html>\n<head>\n<meta charset=\"utf-8\">\n\
+         <title>Dependencies of {0}</title>\n\
+         <style>table {{ border-collapse: collapse; }} td, th {{ border: 1px solid #ccc; padding: 2px 6px; }} \
+         .missing {{ color: red; }} .system {{ color: gray; }}</style>\n\
+         </head>\n<body>\n<h1>Dependencies of {0}</h1>\n",
+        xml_escape(&title)
+    );
+
+    out += "<h2>Findings</h2>\n";
+    if report.is_empty() {
+        out += "<p>No problems detected</p>\n";
+    } else {
+        out += "<table>\n<tr><th>Severity</th><th>Code</th><th>Description</th></tr>\n";
+        for finding in report.findings() {
+            let _ = writeln!(
+                out,
+                "<tr><td>{}</td><td>{}</td><td>{}</td></tr>",
+                finding.severity,
+                finding.code,
+                xml_escape(&finding.message())
+            );
+        }
+        out += "</table>\n";
+        let rules: BTreeMap<&str, &Rule> = report
+            .findings()
+            .iter()
+            .map(|f| (f.code.as_str(), f.kind.rule()))
+            .collect();
+        out += "<dl>\n";
+        for rule in rules.values() {
+            let _ = writeln!(
+                out,
+                "<dt>{} {}</dt><dd>{}</dd>",
+                rule.code,
+                rule.name,
+                xml_escape(rule.explanation)
+            );
+        }
+        out += "</dl>\n";
+    }
+
+    out += "<h2>Executables</h2>\n<table>\n\
+            <tr><th>Name</th><th>Depth</th><th>Location</th><th>Version</th></tr>\n";
+    for e in executables.iter() {
+        let details = e.details.as_ref();
+        let class = if !e.found {
+            " class=\"missing\""
+        } else if details.map(|d| d.is_system).unwrap_or(false) {
+            " class=\"system\""
+        } else {
+            ""
+        };
+        let location = match details {
+            Some(d) if e.found => d
+                .full_path
+                .as_deref()
+                .map(path_to_string)
+                .unwrap_or_default(),
+            _ => "not found".to_owned(),
+        };
+        let _ = writeln!(
+            out,
+            "<tr{class}><td>{}</td><td>{}</td><td>{}</td><td>{}</td></tr>",
+            xml_escape(&e.dllname),
+            e.depth_first_appearance,
+            xml_escape(&location),
+            xml_escape(
+                details
+                    .and_then(|d| d.file_version.as_deref())
+                    .unwrap_or_default()
+            )
+        );
+    }
+    out += "</table>\n</body>\n</html>\n";
+    out
+}
+
+/// Build a SARIF 2.1.0 log with one result for each finding of the report
+fn sarif(executables: &Executables, report: &ExecutablesCheckReport) -> serde_json::Value {
+    let rules: BTreeMap<&str, &Rule> = report
+        .findings()
+        .iter()
+        .map(|f| (f.code.as_str(), f.kind.rule()))
+        .collect();
+    let rules: Vec<serde_json::Value> = rules
+        .into_values()
+        .map(|rule| {
+            json!({
+                "id": rule.code,
+                "name": rule.name,
+                "shortDescription": { "text": rule.summary },
+                "fullDescription": { "text": rule.explanation },
+            })
+        })
+        .collect();
+
+    let results: Vec<serde_json::Value> = report
+        .findings()
+        .iter()
+        .map(|f| {
+            let level = match f.severity {
+                Severity::Error => "error",
+                Severity::Warning => "warning",
+                Severity::Info => "note",
+            };
+            let locations: Vec<serde_json::Value> = f
+                .files
+                .first()
+                .and_then(|name| executables.get(name))
+                .and_then(|e| e.details.as_ref()?.full_path.as_deref())
+                .map(|path| {
+                    json!({
+                        "physicalLocation": {
+                            "artifactLocation": { "uri": file_uri(&path_to_string(path)) }
+                        }
+                    })
+                })
+                .into_iter()
+                .collect();
+            json!({
+                "ruleId": f.code,
+                "level": level,
+                "message": { "text": f.message() },
+                "locations": locations,
+            })
+        })
+        .collect();
+
+    json!({
+        "$schema": "https://json.schemastore.org/sarif-2.1.0.json",
+        "version": "2.1.0",
+        "runs": [{
+            "tool": {
+                "driver": {
+                    "name": env!("CARGO_PKG_NAME"),
+                    "version": env!("CARGO_PKG_VERSION"),
+                    "informationUri": env!("CARGO_PKG_REPOSITORY"),
+                    "rules": rules,
+                }
+            },
+            "results": results,
+        }]
+    })
+}
+
+/// Escape a string to be quoted in a DOT file
+fn dot_escape(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+/// URI of a file, with the characters that are not allowed in a path percent-encoded
+fn file_uri(path: &str) -> String {
+    let path = decanonicalize(path).replace('\\', "/");
+    let mut encoded = String::with_capacity(path.len());
+    for byte in path.bytes() {
+        match byte {
+            b'A'..=b'Z' | b'a'..=b'z' | b'0'..=b'9' | b'-' | b'.' | b'_' | b'~' | b'/' | b':' => {
+                encoded.push(byte as char)
+            }
+            _ => {
+                let _ = write!(encoded, "%{byte:02X}");
+            }
+        }
+    }
+    if encoded.starts_with('/') {
+        format!("file://{encoded}")
+    } else {
+        format!("file:///{encoded}")
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::LookupError;
+    use crate::executable::{DependencyKind, Executables};
+    use crate::output::{dot_escape, file_uri, render, OutputFormat, OutputOptions};
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::report::ExecutablesCheckReport;
+    use crate::runner::run;
+    use crate::testing::{dll, missing};
+
+    #[test]
+    fn dependency_cycles() -> Result<(), LookupError> {
+        let exes: Executables = [
+            dll("app.exe", 0, &["core.dll"]),
+            dll("core.dll", 1, &["plugin.dll"]),
+            dll("plugin.dll", 2, &["core.dll", "absent.dll"]),
+            missing("absent.dll", 3),
+        ]
+        .into_iter()
+        .collect();
+
+        let tree = render(
+            OutputFormat::Tree,
+            &exes,
+            &ExecutablesCheckReport::new(),
+            &OutputOptions::default(),
+        )?;
+        let lines: Vec<&str> = tree.lines().collect();
+        assert_eq!(lines.len(), 5);
+        assert_eq!(lines[3], "        ├── core.dll (cycle)");
+        assert_eq!(lines[4], "        └── absent.dll => not found");
+
+        let notfound = exes.filter_only_notfound()?;
+        assert_eq!(notfound.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn dynamic_load_cycles() -> Result<(), LookupError> {
+        // the host names its plugin in its data, and the plugin imports the host
+        let mut core = dll("core.dll", 1, &[]);
+        core.details.as_mut().unwrap().dynamic_load_dependencies =
+            Some(vec!["plugin.dll".to_owned()]);
+        let exes: Executables = [
+            dll("app.exe", 0, &["core.dll"]),
+            core,
+            dll("plugin.dll", 2, &["core.dll", "absent.dll"]),
+            missing("absent.dll", 3),
+        ]
+        .into_iter()
+        .collect();
+        assert!(exes.edges().iter().any(|e| e.importer == "core.dll"
+            && e.importee == "plugin.dll"
+            && e.kind == DependencyKind::DynamicLoad));
+
+        let tree = render(
+            OutputFormat::Tree,
+            &exes,
+            &ExecutablesCheckReport::new(),
+            &OutputOptions::default(),
+        )?;
+        assert!(tree.contains("\n        ├── core.dll (cycle)\n"));
+        assert_eq!(exes.filter_only_notfound()?.len(), 4);
+        Ok(())
+    }
+
+    #[test]
+    fn render_formats() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe");
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+        let report = exes.check(false)?;
+        let options = OutputOptions::default();
+        let render = |format: &str| render(format.parse()?, &exes, &report, &options);
+
+        let tree = render("tree")?;
+        assert!(tree.starts_with("DepRunTest.exe => "));
+        assert!(tree.contains("\n├── DepRunTestLib.dll => not found\n"));
+        assert!(tree.lines().last().unwrap().starts_with("└── "));
+        let colored_options = OutputOptions {
+            color: true,
+            ..OutputOptions::default()
+        };
+        let colored_tree = super::render(OutputFormat::Tree, &exes, &report, &colored_options)?;
+        assert!(colored_tree.contains("├── \x1b[31mDepRunTestLib.dll => not found\x1b[0m"));
+
+        assert!(render("ldd")?.contains("\"DepRunTestLib.dll\" => not found"));
+        let ldd_options = OutputOptions {
+            show_architecture: true,
+            ..OutputOptions::default()
+        };
+        let same_output_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let same_output_query = LookupQuery::deduce_from_executable_location(same_output_path)?;
+        let same_output_exes = run(&same_output_query, &LookupPath::deduce(&same_output_query))?;
+        let ldd = super::render(OutputFormat::Ldd, &same_output_exes, &report, &ldd_options)?;
+        assert!(ldd.contains("DepRunTestLib.dll (x64, console)\n"));
+        assert_eq!(
+            render("ndjson")?.lines().count(),
+            exes.sorted_by_first_appearance().len()
+        );
+        assert_eq!(
+            crate::executable::Executables::from_json(&render("json")?)?.len(),
+            exes.len()
+        );
+        assert!(render("csv")?.contains("\nDepRunTestLib.dll,1,false,"));
+        assert!(render("dot")?.contains("\"depruntest.exe\" -> \"depruntestlib.dll\";"));
+        assert!(render("html")?.contains("<td>DR0001</td>"));
+        let chain: serde_json::Value = serde_json::from_str(&render("dependencies")?)?;
+        assert_eq!(chain["Root"]["Name"], "DepRunTest.exe");
+        assert_eq!(chain["Root"]["ModuleLocation"], "ROOT");
+        assert!(chain["Root"]["Dependencies"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .any(|d| d["Name"] == "DepRunTestLib.dll"
+                && d["ModuleLocation"] == "NOT_FOUND"
+                && d["Filepath"].is_null()));
+        assert!(render("dw-modules")?.contains("\n?\tDepRunTestLib.dll\t"));
+
+        let sarif: serde_json::Value = serde_json::from_str(&render("sarif")?)?;
+        let results = sarif["runs"][0]["results"].as_array().unwrap();
+        assert!(results
+            .iter()
+            .any(|r| r["ruleId"] == "DR0001" && r["level"] == "error"));
+        let rules = sarif["runs"][0]["tool"]["driver"]["rules"]
+            .as_array()
+            .unwrap();
+        assert!(rules
+            .iter()
+            .any(|r| r["id"] == "DR0001" && r["fullDescription"]["text"].is_string()));
+
+        assert!(render("junit")?.contains("<testsuite name=\"DepRunTest.exe\""));
+        assert!("yaml".parse::<OutputFormat>().is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn escaped_uris_and_labels() {
+        assert_eq!(
+            file_uri(r"C:\Program Files\App #1\100%.dll"),
+            "file:///C:/Program%20Files/App%20%231/100%25.dll"
+        );
+        assert_eq!(file_uri("/opt/app/lib.dll"), "file:///opt/app/lib.dll");
+        assert_eq!(dot_escape(r#"a"b\c"#), r#"a\"b\\c"#);
+    }
+}
@@ -1,20 +1,26 @@
-//! This is the workhorse of the library: the LookupPath contains the list of possible locations for 
+//! This is the workhorse of the library: the LookupPath contains the list of possible locations for
 //! a dependency, performs the actual lookup and caching of the results and of all filesystem access.
 
 use crate::apiset;
-use crate::common::LookupError;
-use crate::query::LookupQuery;
+use crate::common::{host_path, FileOperation, LookupError};
+use crate::provider::FileProvider;
+use crate::query::{DllOverride, LookupQuery};
 use crate::system::{KnownDLLList, WinFileSystemCache, WindowsSystem};
-#[cfg(windows)]
 use fs_err as fs;
+use serde::{Deserialize, Serialize};
 use std::ffi::OsStr;
 use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::time::Duration;
 
 /// Directory/set of DLLs to be searched, and relative metadata
 #[derive(Eq, PartialEq, Debug, Clone)]
 pub enum LookupPathEntry<'a> {
     /// The DLL is implicitly loaded by the OS for every process, and not looked up every time
     KnownDLLs(&'a KnownDLLList),
+    /// Directory of a package of the package graph of a packaged (MSIX/AppX) app: its own
+    /// package, or one it depends on
+    PackageGraph(PathBuf),
     /// Directory where the root executable sits
     ExecutableDir(PathBuf),
     /// Directory containing the "proxy" DLLs that implement the API set feature
@@ -40,13 +46,34 @@ impl<'a> LookupPathEntry<'a> {
         )
     }
 
+    /// Kind of entry, e.g. `ResolutionEntry::SystemDir`
+    pub fn kind(&self) -> ResolutionEntry {
+        match self {
+            Self::KnownDLLs(_) => ResolutionEntry::KnownDLLs,
+            Self::PackageGraph(_) => ResolutionEntry::PackageGraph,
+            Self::ExecutableDir(_) => ResolutionEntry::ExecutableDir,
+            Self::ApiSet(_) => ResolutionEntry::ApiSet,
+            Self::SystemDir(_) => ResolutionEntry::SystemDir,
+            Self::WindowsDir(_) => ResolutionEntry::WindowsDir,
+            Self::WorkingDir(_) => ResolutionEntry::WorkingDir,
+            Self::SystemPath(_) => ResolutionEntry::SystemPath,
+            Self::UserPath(_) => ResolutionEntry::UserPath,
+        }
+    }
+
+    /// Name of the kind of entry, e.g. "SystemDir"
+    pub fn kind_name(&self) -> &'static str {
+        self.kind().name()
+    }
+
     pub fn get_path(&self) -> Option<PathBuf> {
         match self {
             // we have a fixed list, no need to scan
             Self::KnownDLLs(_) => None,
             Self::ApiSet(_) => None,
             // else
-            Self::ExecutableDir(p)
+            Self::PackageGraph(p)
+            | Self::ExecutableDir(p)
             | Self::SystemDir(p)
             | Self::WindowsDir(p)
             | Self::WorkingDir(p)
@@ -56,10 +83,215 @@ impl<'a> LookupPathEntry<'a> {
     }
 }
 
+/// Kind of the entry a DLL is resolved from: an entry of the lookup path, or the query itself
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash, Serialize, Deserialize)]
+pub enum ResolutionEntry {
+    KnownDLLs,
+    PackageGraph,
+    ExecutableDir,
+    ApiSet,
+    SystemDir,
+    WindowsDir,
+    WorkingDir,
+    SystemPath,
+    UserPath,
+    /// DLL override of the query: loaded from the given file, or assumed present or missing
+    DllOverride,
+    /// DLL guaranteed by the target OS of the query, but not available to the scan
+    TargetOs,
+}
+
+impl ResolutionEntry {
+    pub fn name(&self) -> &'static str {
+        match self {
+            Self::KnownDLLs => "KnownDLLs",
+            Self::PackageGraph => "PackageGraph",
+            Self::ExecutableDir => "ExecutableDir",
+            Self::ApiSet => "ApiSet",
+            Self::SystemDir => "SystemDir",
+            Self::WindowsDir => "WindowsDir",
+            Self::WorkingDir => "WorkingDir",
+            Self::SystemPath => "SystemPath",
+            Self::UserPath => "UserPath",
+            Self::DllOverride => "DllOverride",
+            Self::TargetOs => "TargetOs",
+        }
+    }
+}
+
+impl std::fmt::Display for ResolutionEntry {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(self.name())
+    }
+}
+
+impl From<&LookupPathEntry<'_>> for ResolutionSource {
+    fn from(entry: &LookupPathEntry<'_>) -> Self {
+        Self {
+            entry: entry.kind(),
+            dir: entry.get_path(),
+        }
+    }
+}
+
+/// Entry of the lookup path a DLL was loaded from
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ResolutionSource {
+    /// kind of the entry, e.g. `ExecutableDir`, or `DllOverride` and `TargetOs` for the DLLs
+    /// resolved by the query rather than by the lookup path
+    pub entry: ResolutionEntry,
+    /// directory of the entry, if it has one
+    pub dir: Option<PathBuf>,
+}
+
 /// Full location of a DLL found during lookup
 pub struct LookupResult<'a> {
     pub location: LookupPathEntry<'a>,
-    pub fullpath: PathBuf,
+    /// None for API sets resolved without a system directory to look the host up in
+    pub fullpath: Option<PathBuf>,
+    /// For API sets, name of the DLL implementing the contract (fullpath points to it)
+    pub apiset_host: Option<String>,
+}
+
+/// What was found when probing an entry of the lookup path for a DLL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum ProbeOutcome {
+    /// the DLL can be loaded from this entry, at the given path (none for API sets resolved
+    /// through the map alone)
+    Found(Option<PathBuf>),
+    /// the DLL is not in the KnownDLLs list
+    NotAKnownDll,
+    /// the name is not an API set contract of the map
+    NotAnApiSet,
+    /// the name is an API set contract, but its host DLL was not found in the system directory
+    ApiSetHostNotFound(String),
+    /// there is no file with that name in the directory
+    NotInDirectory,
+    /// the DLL is considered found because of a DLL override of the query, or because the target
+    /// OS of the query is guaranteed to have it
+    AssumedPresent,
+    /// the DLL is considered missing because of a DLL override of the query
+    AssumedMissing,
+    /// the entry was disabled, and not looked into
+    Disabled,
+}
+
+impl std::fmt::Display for ProbeOutcome {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Found(Some(p)) => write!(f, "found {}", p.display()),
+            Self::Found(None) => write!(f, "found (virtual API set)"),
+            Self::NotAKnownDll => write!(f, "not a known DLL"),
+            Self::NotAnApiSet => write!(f, "not an API set"),
+            Self::ApiSetHostNotFound(host) => write!(f, "API set host {host} not found"),
+            Self::NotInDirectory => write!(f, "not in directory"),
+            Self::AssumedPresent => write!(f, "assumed present"),
+            Self::AssumedMissing => write!(f, "assumed missing"),
+            Self::Disabled => write!(f, "disabled"),
+        }
+    }
+}
+
+/// Entry of the lookup path probed for a DLL, and the outcome
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Probe {
+    /// kind of the entry, e.g. `SystemDir`
+    pub entry: ResolutionEntry,
+    /// directory of the entry, if it has one
+    pub dir: Option<PathBuf>,
+    /// file tested for the DLL, if any
+    #[serde(default)]
+    pub candidate: Option<PathBuf>,
+    pub outcome: ProbeOutcome,
+}
+
+impl Probe {
+    pub fn is_found(&self) -> bool {
+        matches!(
+            self.outcome,
+            ProbeOutcome::Found(_) | ProbeOutcome::AssumedPresent
+        )
+    }
+
+    /// Index of the probe the DLL is loaded from, if any: the first successful one, unless the
+    /// DLL is assumed missing
+    pub fn winner(probes: &[Probe]) -> Option<usize> {
+        match probes
+            .iter()
+            .position(|p| p.is_found() || p.outcome == ProbeOutcome::AssumedMissing)
+        {
+            Some(i) if probes[i].is_found() => Some(i),
+            _ => None,
+        }
+    }
+}
+
+impl std::fmt::Display for Probe {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match &self.dir {
+            Some(dir) => write!(f, "{} ({}): {}", self.entry, dir.display(), self.outcome),
+            None => write!(f, "{}: {}", self.entry, self.outcome),
+        }
+    }
+}
+
+/// Resolution of a single DLL name across the lookup path (see `LookupPath::probe`)
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ProbeReport {
+    pub dllname: String,
+    pub found: bool,
+    /// kind of the entry the DLL would be loaded from, e.g. `KnownDLLs`
+    pub entry: Option<ResolutionEntry>,
+    /// file the DLL would be loaded from (none for API sets resolved without a system directory)
+    pub path: Option<PathBuf>,
+    /// for API sets, name of the DLL implementing the contract
+    pub apiset_host: Option<String>,
+    /// every entry of the lookup path, in order, with what was found there
+    pub probes: Vec<Probe>,
+}
+
+impl ProbeReport {
+    /// Files with the same name found after the one the DLL would be loaded from, which it shadows
+    /// (the same directory can be listed by several entries, e.g. as application and working
+    /// directory)
+    pub fn shadowed(&self) -> Vec<&Path> {
+        let Some(winner) = Probe::winner(&self.probes) else {
+            return Vec::new();
+        };
+        let mut shadowed: Vec<&Path> = Vec::new();
+        for probe in &self.probes[winner + 1..] {
+            if let ProbeOutcome::Found(Some(path)) = &probe.outcome {
+                if self.path.as_ref() != Some(path) && !shadowed.contains(&path.as_path()) {
+                    shadowed.push(path);
+                }
+            }
+        }
+        shadowed
+    }
+}
+
+impl std::fmt::Display for ProbeReport {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "Lookup of {}:", self.dllname)?;
+        let winner = Probe::winner(&self.probes);
+        let shadowed = self.shadowed();
+        for (i, probe) in self.probes.iter().enumerate() {
+            let mark = match (winner, &probe.outcome) {
+                (Some(w), _) if w == i => " <= loaded from here",
+                (Some(w), ProbeOutcome::Found(Some(path)))
+                    if w < i && shadowed.contains(&path.as_path()) =>
+                {
+                    " (shadowed)"
+                }
+                _ => "",
+            };
+            writeln!(f, "\t{probe}{mark}")?;
+        }
+        if !self.found {
+            writeln!(f, "{} not found", self.dllname)?;
+        }
+        Ok(())
+    }
 }
 
 /// Linearized lookup path
@@ -70,6 +302,10 @@ pub struct LookupResult<'a> {
 /// The path is computed from the user-provided query before scanning the dependency tree. It acts
 /// as a reification of the computed path itself, as an interface to look up executables across it
 /// and a cache for the metadata of the DLLs found through it.
+///
+/// Clones share the cache of the directory listings, so that experiments on a copy of the path
+/// (e.g. disabling some of its entries) are cheap.
+#[derive(Clone)]
 pub struct LookupPath<'a> {
     /// Sorted list of directories to be looked up when searching for a DLL
     /// It is built from a query, depending on the current system configuration
@@ -77,12 +313,14 @@ pub struct LookupPath<'a> {
     pub entries: Vec<LookupPathEntry<'a>>,
     /// Cache of file lookup on disk
     /// (filesystem access is the true bottleneck in DLL dependency resolution)
-    fs_cache: std::cell::RefCell<WinFileSystemCache>,
+    fs_cache: Arc<WinFileSystemCache>,
+    /// Entries skipped by the lookup, as if they were removed (see `set_entry_enabled`)
+    disabled_entries: Vec<LookupPathEntry<'a>>,
 }
 
 impl<'a> LookupPath<'a> {
     /// Deduces the lookup path from the given user query applying sensible defaults
-    /// The user can still manipulate the entries afterwards in a manual fashion
+    /// The user can still customize the entries afterwards, preferably through `LookupPath::builder`
     pub fn deduce(query: &'a LookupQuery) -> Self {
         let entries = if let Some(system) = query.system.as_ref() {
             let knowndlls_entry = if let Some(known_dlls) = system.known_dlls.as_ref() {
@@ -90,7 +328,11 @@ impl<'a> LookupPath<'a> {
             } else {
                 vec![]
             };
-            let apiset_entry = if let Some(apiset_map) = system.apiset_map.as_ref() {
+            let apiset_entry = if let Some(apiset_map) = system
+                .apiset_map
+                .as_ref()
+                .or(query.fallback_apiset_map.as_ref())
+            {
                 vec![LookupPathEntry::ApiSet(apiset_map)]
             } else {
                 vec![]
@@ -101,7 +343,20 @@ impl<'a> LookupPath<'a> {
                 LookupPathEntry::WindowsDir(system.win_dir.clone()),
             ];
 
-            if system.safe_dll_search_mode_on.unwrap_or(true) {
+            if !query.target.package_graph.is_empty() {
+                // packaged apps do not look in the Windows and working directories, nor in the PATH
+                [
+                    knowndlls_entry,
+                    apiset_entry,
+                    Self::package_graph_entries(query),
+                    vec![
+                        LookupPathEntry::ExecutableDir(query.target.app_dir.clone()),
+                        LookupPathEntry::SystemDir(system.sys_dir.clone()),
+                    ],
+                    Self::user_path_entries(query),
+                ]
+                .concat()
+            } else if system.safe_dll_search_mode_on.unwrap_or(true) {
                 // default mode (assume if not specified)
                 [
                     knowndlls_entry,
@@ -131,11 +386,23 @@ impl<'a> LookupPath<'a> {
                 .concat()
             }
         } else {
+            let apiset_entry = if let Some(apiset_map) = query.fallback_apiset_map.as_ref() {
+                vec![LookupPathEntry::ApiSet(apiset_map)]
+            } else {
+                vec![]
+            };
+            let working_dir_entry = if query.target.package_graph.is_empty() {
+                vec![LookupPathEntry::WorkingDir(
+                    query.target.working_dir.clone(),
+                )]
+            } else {
+                vec![]
+            };
             [
-                vec![
-                    LookupPathEntry::ExecutableDir(query.target.app_dir.clone()),
-                    LookupPathEntry::WorkingDir(query.target.working_dir.clone()),
-                ],
+                apiset_entry,
+                Self::package_graph_entries(query),
+                vec![LookupPathEntry::ExecutableDir(query.target.app_dir.clone())],
+                working_dir_entry,
                 Self::user_path_entries(query),
             ]
             .concat()
@@ -144,12 +411,13 @@ impl<'a> LookupPath<'a> {
         Self {
             // system: sys,
             entries,
-            fs_cache: std::cell::RefCell::new(WinFileSystemCache::new()),
+            fs_cache: Arc::new(WinFileSystemCache::new()),
+            disabled_entries: Vec::new(),
         }
     }
 
     /// Parse an entry in a .dwp file
-    #[cfg(windows)]
+    #[cfg(feature = "dwp")]
     fn dwp_string_to_context_entry(
         s: &str,
         q: &'a LookupQuery,
@@ -195,7 +463,13 @@ impl<'a> LookupPath<'a> {
                 },
             ),
             _ if s.starts_with("UserDir ") => {
-                Ok(vec![LookupPathEntry::UserPath(PathBuf::from(&s[8..]))])
+                // the directories are relative to the Windows partition, if one is mounted
+                let anchor = q
+                    .system
+                    .as_ref()
+                    .and_then(|s| s.win_dir.parent())
+                    .unwrap_or(&q.target.app_dir);
+                Ok(vec![LookupPathEntry::UserPath(host_path(&s[8..], anchor))])
             }
             _ => Err(LookupError::ParseError(format!(
                 "Unknown key in dwp file: {}",
@@ -205,81 +479,373 @@ impl<'a> LookupPath<'a> {
     }
 
     /// Build a LookupPath from the content of a Dependency Walker .dwp file
-    #[cfg(windows)]
+    #[cfg(feature = "dwp")]
     pub fn from_dwp_file<P: AsRef<Path>>(
         dwp_path: P,
         query: &'a LookupQuery,
     ) -> Result<Self, LookupError> {
         // https://www.dependencywalker.com/help/html/path_files.htm
         let comment_chars = [':', ';', '/', '\'', '#'];
-        let lines: Vec<String> = fs::read_to_string(dwp_path)?
+        let dwp_path = dwp_path.as_ref();
+        let lines: Vec<String> = fs::read_to_string(dwp_path)
+            .map_err(|e| LookupError::from(e).in_file(dwp_path, FileOperation::Read))?
             .lines()
             .filter(|s| !(s.is_empty() || comment_chars.contains(&s.chars().next().unwrap())))
             .map(str::to_owned)
             .collect();
         let entries_vecs = lines
             .iter()
-            .map(|e| Self::dwp_string_to_context_entry(e, &query))
-            .collect::<Result<Vec<Vec<LookupPathEntry>>, LookupError>>()?;
+            .map(|e| Self::dwp_string_to_context_entry(e, query))
+            .collect::<Result<Vec<Vec<LookupPathEntry>>, LookupError>>()
+            .map_err(|e| e.in_file(dwp_path, FileOperation::Parse))?;
         Ok(Self {
             entries: entries_vecs.concat(),
-            fs_cache: std::cell::RefCell::new(WinFileSystemCache::new()),
+            fs_cache: Arc::new(WinFileSystemCache::new()),
+            disabled_entries: Vec::new(),
         })
     }
 
+    /// Build a LookupPath from a path profile file (see `PathProfile`)
+    pub fn from_path_profile<P: AsRef<Path>>(
+        profile_path: P,
+        query: &'a LookupQuery,
+    ) -> Result<Self, LookupError> {
+        let profile_path = profile_path.as_ref();
+        let profile = PathProfile::read(profile_path)?;
+        let profile_dir = profile_path
+            .parent()
+            .map(Path::to_path_buf)
+            .unwrap_or_default();
+        profile.lookup_path(query, &profile_dir)
+    }
+
+    /// Builder starting from the entries deduced from the query, to customize them safely
+    pub fn builder(query: &'a LookupQuery) -> LookupPathBuilder<'a> {
+        Self::deduce(query).into_builder()
+    }
+
+    /// Builder starting from the entries of this lookup path (without the disabled ones)
+    pub fn into_builder(self) -> LookupPathBuilder<'a> {
+        let disabled_entries = self.disabled_entries;
+        LookupPathBuilder {
+            entries: self
+                .entries
+                .into_iter()
+                .filter(|e| !disabled_entries.contains(e))
+                .collect(),
+        }
+    }
+
+    /// Disable or enable again the entry at the given index: the lookup skips disabled entries, as
+    /// if they were removed from the path
+    ///
+    /// All the entries equal to the given one (e.g. the same directory listed twice in the PATH)
+    /// are affected alike.
+    pub fn set_entry_enabled(&mut self, index: usize, enabled: bool) -> Result<(), LookupError> {
+        let entry = self.entries.get(index).cloned().ok_or_else(|| {
+            LookupError::LookupPathError(format!(
+                "no entry {index}, the path has {} entries",
+                self.entries.len()
+            ))
+        })?;
+        self.disabled_entries.retain(|e| *e != entry);
+        if !enabled {
+            self.disabled_entries.push(entry);
+        }
+        Ok(())
+    }
+
+    /// Whether the entry at the given index is looked up
+    pub fn is_entry_enabled(&self, index: usize) -> bool {
+        self.entries
+            .get(index)
+            .map(|e| !self.disabled_entries.contains(e))
+            .unwrap_or(false)
+    }
+
+    /// Entries looked up, in order
+    pub fn enabled_entries(&self) -> impl Iterator<Item = &LookupPathEntry<'a>> {
+        self.entries
+            .iter()
+            .filter(|e| !self.disabled_entries.contains(e))
+    }
+
+    /// Read the directories and the files found in them through another backend than the local
+    /// file system (e.g. an archive of a deployment); the cached directory listings are dropped
+    pub fn set_file_provider(&mut self, provider: Arc<dyn FileProvider>) {
+        self.fs_cache = Arc::new(WinFileSystemCache::with_provider(provider));
+    }
+
+    /// Backend the directories and files are read through
+    pub fn file_provider(&self) -> &dyn FileProvider {
+        self.fs_cache.provider()
+    }
+
+    /// Cache of the directory listings, to be shared with other lookup paths (see `set_cache`) or
+    /// invalidated selectively
+    pub fn cache(&self) -> &Arc<WinFileSystemCache> {
+        &self.fs_cache
+    }
+
+    /// Use the given cache of directory listings, e.g. one shared with other lookup paths over the
+    /// same directories
+    pub fn set_cache(&mut self, cache: Arc<WinFileSystemCache>) {
+        self.fs_cache = cache;
+    }
+
+    /// Check the modification time of the directories at each lookup, and list them again if they
+    /// changed (see `WinFileSystemCache::validating_modification_time`); the cached directory
+    /// listings are dropped
+    pub fn validate_modification_times(&mut self) {
+        self.fs_cache = Arc::new(
+            WinFileSystemCache::with_provider(self.fs_cache.shared_provider())
+                .validating_modification_time(),
+        );
+    }
+
+    /// Forget the cached directory listings, e.g. after files were added to or removed from them
+    pub fn clear_cache(&self) {
+        self.fs_cache.clear();
+    }
+
+    /// Number of file lookups answered from the cache, and of lookups that read a directory
+    pub(crate) fn cache_counters(&self) -> (usize, usize) {
+        self.fs_cache.counters()
+    }
+
+    /// Total time spent listing the directories of the lookup path
+    pub(crate) fn directory_listing_time(&self) -> Duration {
+        self.fs_cache.listing_time()
+    }
+
+    /// List concurrently all the directories of the path that are not cached yet, instead of
+    /// each one at the first lookup reaching it (see `WinFileSystemCache::prefetch`)
+    pub fn prefetch_directories(&self) {
+        self.fs_cache.prefetch(&self.search_path());
+    }
+
     /// linearize the lookup context into a single vector of directories
     pub fn search_path(&self) -> Vec<PathBuf> {
-        self.entries.iter().flat_map(|e| e.get_path()).collect()
+        self.enabled_entries().flat_map(|e| e.get_path()).collect()
     }
 
     /// look for a DLL by name across the entries
-    pub fn search_dll(&self, library: &str) -> Result<Option<LookupResult>, LookupError> {
+    pub fn search_dll(&self, library: &str) -> Result<Option<LookupResult<'_>>, LookupError> {
+        for e in self.enabled_entries() {
+            if let Some(r) = self.search_dll_in_entry(e, library)? {
+                return Ok(Some(r));
+            }
+        }
+        Ok(None)
+    }
+
+    /// look for a DLL by name in every entry, listing all the locations it could be loaded from
+    /// (the first one is the one the loader would pick)
+    pub fn search_dll_everywhere(
+        &self,
+        library: &str,
+    ) -> Result<Vec<LookupResult<'_>>, LookupError> {
+        let mut ret = Vec::new();
+        for e in self.enabled_entries() {
+            if let Some(r) = self.search_dll_in_entry(e, library)? {
+                ret.push(r);
+            }
+        }
+        Ok(ret)
+    }
+
+    /// probe every entry for a DLL, in order, recording what was found in each one (the first
+    /// entry where the DLL is found is the one the loader would pick)
+    pub fn probe_dll(&self, library: &str) -> Result<Vec<Probe>, LookupError> {
+        Ok(self.search_dll_with_probes(library)?.1)
+    }
+
+    /// resolve a single DLL name as a scan of the query would (DLL overrides, KnownDLLs, API sets,
+    /// the directories, then the DLLs guaranteed by the target OS), reporting the location it
+    /// would be loaded from and every entry probed
+    pub fn probe(&self, query: &LookupQuery, library: &str) -> Result<ProbeReport, LookupError> {
+        let (result, mut probes) = self.search_dll_with_probes(library)?;
+        let report = |found: bool, entry, path, apiset_host, probes| ProbeReport {
+            dllname: library.to_owned(),
+            found,
+            entry,
+            path,
+            apiset_host,
+            probes,
+        };
+        // a DLL override is recorded as the first probe, followed by the path walk it short-circuits
+        let override_probe = |candidate: Option<&PathBuf>, outcome| Probe {
+            entry: ResolutionEntry::DllOverride,
+            dir: None,
+            candidate: candidate.cloned(),
+            outcome,
+        };
+        Ok(match query.dll_override(library) {
+            Some(DllOverride::Path(path)) => {
+                if !path.is_file() {
+                    return Err(LookupError::ScanError(format!(
+                        "{} does not exist, but {} was overridden to be loaded from it",
+                        path.display(),
+                        library
+                    )));
+                }
+                let outcome = ProbeOutcome::Found(Some(path.clone()));
+                probes.insert(0, override_probe(Some(path), outcome));
+                report(
+                    true,
+                    Some(ResolutionEntry::DllOverride),
+                    Some(path.clone()),
+                    None,
+                    probes,
+                )
+            }
+            Some(DllOverride::AssumePresent) => {
+                probes.insert(0, override_probe(None, ProbeOutcome::AssumedPresent));
+                report(true, Some(ResolutionEntry::DllOverride), None, None, probes)
+            }
+            Some(DllOverride::AssumeMissing) => {
+                probes.insert(0, override_probe(None, ProbeOutcome::AssumedMissing));
+                report(false, None, None, None, probes)
+            }
+            None => match result {
+                Some(r) => report(
+                    true,
+                    Some(r.location.kind()),
+                    r.fullpath,
+                    r.apiset_host,
+                    probes,
+                ),
+                None if query
+                    .target_os
+                    .as_ref()
+                    .is_some_and(|os| os.provides(library)) =>
+                {
+                    probes.push(Probe {
+                        entry: ResolutionEntry::TargetOs,
+                        dir: None,
+                        candidate: None,
+                        outcome: ProbeOutcome::AssumedPresent,
+                    });
+                    report(true, Some(ResolutionEntry::TargetOs), None, None, probes)
+                }
+                None => report(false, None, None, None, probes),
+            },
+        })
+    }
+
+    /// look for a DLL by name, probing every entry and recording what was found in each one
+    ///
+    /// The result is the same as the one of `search_dll`; the trace also covers the entries
+    /// after the one the DLL is loaded from, to show the files it shadows.
+    pub fn search_dll_with_probes(
+        &self,
+        library: &str,
+    ) -> Result<(Option<LookupResult<'_>>, Vec<Probe>), LookupError> {
+        let mut result = None;
+        let mut probes = Vec::with_capacity(self.entries.len());
         for e in &self.entries {
-            match e {
-                LookupPathEntry::KnownDLLs(kd) => {
-                    if let Ok(Some(lp)) = kd.search_dll_in_known_dlls(library) {
-                        let ret = Some(LookupResult {
-                            location: LookupPathEntry::KnownDLLs(kd),
-                            fullpath: lp,
-                        });
-                        return Ok(ret);
+            let dir = e.get_path();
+            if self.disabled_entries.contains(e) {
+                probes.push(Probe {
+                    entry: e.kind(),
+                    dir,
+                    candidate: None,
+                    outcome: ProbeOutcome::Disabled,
+                });
+                continue;
+            }
+            let (candidate, outcome) = match (self.search_dll_in_entry(e, library)?, e) {
+                (Some(r), _) => {
+                    let candidate = r.fullpath.clone();
+                    let outcome = ProbeOutcome::Found(r.fullpath.clone());
+                    result = result.or(Some(r));
+                    (candidate, outcome)
+                }
+                (None, LookupPathEntry::KnownDLLs(_)) => (None, ProbeOutcome::NotAKnownDll),
+                (None, LookupPathEntry::ApiSet(apis)) => {
+                    match apiset::lookup_apiset(apis, library).and_then(|h| h.first()) {
+                        Some(host) => (None, ProbeOutcome::ApiSetHostNotFound(host.clone())),
+                        None => (None, ProbeOutcome::NotAnApiSet),
                     }
                 }
-                LookupPathEntry::ApiSet(apis) => {
-                    let apiset_name = library.to_lowercase().trim_end_matches(".dll").to_owned();
-                    if apis.contains_key(&apiset_name) {
-                        if let Some(system32_dir) = self
-                            .entries
-                            .iter()
-                            .find(|e| std::matches!(e, LookupPathEntry::SystemDir(_)))
-                        {
-                            let p = self.search_file_in_folder(
+                (None, _) => (
+                    dir.as_ref().map(|d| d.join(library)),
+                    ProbeOutcome::NotInDirectory,
+                ),
+            };
+            probes.push(Probe {
+                entry: e.kind(),
+                dir,
+                candidate,
+                outcome,
+            });
+        }
+        Ok((result, probes))
+    }
+
+    /// look for a DLL by name in a single entry
+    fn search_dll_in_entry(
+        &self,
+        e: &LookupPathEntry<'a>,
+        library: &str,
+    ) -> Result<Option<LookupResult<'_>>, LookupError> {
+        match e {
+            LookupPathEntry::KnownDLLs(kd) => {
+                if let Ok(Some(lp)) = kd.search_dll_in_known_dlls(library) {
+                    return Ok(Some(LookupResult {
+                        location: LookupPathEntry::KnownDLLs(kd),
+                        fullpath: Some(lp),
+                        apiset_host: None,
+                    }));
+                }
+            }
+            LookupPathEntry::ApiSet(apis) => {
+                if let Some(host) = apiset::lookup_apiset(apis, library).and_then(|h| h.first()) {
+                    if let Some(system32_dir) = self.entries.iter().find_map(|e| match e {
+                        LookupPathEntry::SystemDir(p) => Some(p),
+                        _ => None,
+                    }) {
+                        // older systems ship the contracts as proxy DLLs in the downlevel folder
+                        let p = match self.search_file_in_folder(OsStr::new(host), system32_dir)? {
+                            Some(p) => Some(p),
+                            None => self.search_file_in_folder(
                                 OsStr::new(library),
-                                system32_dir.get_path().unwrap().join("downlevel"),
-                            );
-                            return p.map(|p| {
-                                Some(LookupResult {
-                                    location: e.clone(),
-                                    fullpath: p?,
-                                })
-                            });
+                                system32_dir.join("downlevel"),
+                            )?,
+                        };
+                        if let Some(p) = p {
+                            return Ok(Some(LookupResult {
+                                location: e.clone(),
+                                fullpath: Some(p),
+                                apiset_host: Some(host.clone()),
+                            }));
                         }
-                    }
-                }
-                LookupPathEntry::ExecutableDir(p)
-                | LookupPathEntry::SystemDir(p)
-                | LookupPathEntry::WindowsDir(p)
-                | LookupPathEntry::SystemPath(p)
-                | LookupPathEntry::UserPath(p)
-                | LookupPathEntry::WorkingDir(p) => {
-                    if let Some(r) = self.search_file_in_folder(OsStr::new(library), p)? {
+                    } else {
+                        // no system partition to read the host from: the contract is resolved
+                        // virtually, through the map alone
                         return Ok(Some(LookupResult {
                             location: e.clone(),
-                            fullpath: r,
+                            fullpath: None,
+                            apiset_host: Some(host.clone()),
                         }));
                     }
                 }
             }
+            LookupPathEntry::PackageGraph(p)
+            | LookupPathEntry::ExecutableDir(p)
+            | LookupPathEntry::SystemDir(p)
+            | LookupPathEntry::WindowsDir(p)
+            | LookupPathEntry::SystemPath(p)
+            | LookupPathEntry::UserPath(p)
+            | LookupPathEntry::WorkingDir(p) => {
+                if let Some(r) = self.search_file_in_folder(OsStr::new(library), p)? {
+                    return Ok(Some(LookupResult {
+                        location: e.clone(),
+                        fullpath: Some(r),
+                        apiset_host: None,
+                    }));
+                }
+            }
         }
         Ok(None)
     }
@@ -291,12 +857,11 @@ impl<'a> LookupPath<'a> {
         p: P,
     ) -> Result<Option<PathBuf>, LookupError> {
         self.fs_cache
-            .borrow_mut()
             .test_file_in_folder_case_insensitive(filename, p.as_ref())
     }
 
     /// Get the PATH entries specified by the system
-    fn system_path_entries(system: &WindowsSystem) -> Vec<LookupPathEntry> {
+    fn system_path_entries(system: &WindowsSystem) -> Vec<LookupPathEntry<'_>> {
         system
             .system_path
             .as_ref()
@@ -306,8 +871,17 @@ impl<'a> LookupPath<'a> {
             .collect::<Vec<_>>()
     }
 
+    /// Get the directories of the package graph, if the target is a packaged app
+    fn package_graph_entries(q: &LookupQuery) -> Vec<LookupPathEntry<'_>> {
+        q.target
+            .package_graph
+            .iter()
+            .map(|p| LookupPathEntry::PackageGraph(p.clone()))
+            .collect()
+    }
+
     /// Get the PATH entries that were provided by the user when running the program
-    fn user_path_entries(q: &LookupQuery) -> Vec<LookupPathEntry> {
+    fn user_path_entries(q: &LookupQuery) -> Vec<LookupPathEntry<'_>> {
         q.target
             .user_path
             .iter()
@@ -316,13 +890,319 @@ impl<'a> LookupPath<'a> {
     }
 }
 
-#[cfg(windows)]
+/// Entry of a path profile
+///
+/// The directories that depend on the scanned executable and on the system are stored by kind,
+/// and are filled in from the query when the profile is loaded.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum PathProfileEntry {
+    KnownDLLs,
+    ApiSet,
+    /// all the directories of the package graph of a packaged app
+    PackageGraph,
+    ExecutableDir,
+    SystemDir,
+    WindowsDir,
+    WorkingDir,
+    /// all the entries of the system PATH
+    SystemPath,
+    /// directory relative to the profile file, or absolute (Windows paths are relative to the
+    /// Windows partition, if one is mounted)
+    UserPath(PathBuf),
+}
+
+/// Lookup path configuration stored in a JSON file, e.g. in the repository of a project
+///
+/// This is a replacement for the .dwp files of Dependency Walker.
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct PathProfile {
+    pub entries: Vec<PathProfileEntry>,
+}
+
+impl PathProfile {
+    /// Describe a lookup path, keeping only the user directories verbatim
+    pub fn from_lookup_path(lookup_path: &LookupPath) -> Self {
+        let mut entries: Vec<PathProfileEntry> = Vec::new();
+        for e in lookup_path.enabled_entries() {
+            let entry = match e {
+                LookupPathEntry::KnownDLLs(_) => PathProfileEntry::KnownDLLs,
+                LookupPathEntry::ApiSet(_) => PathProfileEntry::ApiSet,
+                LookupPathEntry::PackageGraph(_) => PathProfileEntry::PackageGraph,
+                LookupPathEntry::ExecutableDir(_) => PathProfileEntry::ExecutableDir,
+                LookupPathEntry::SystemDir(_) => PathProfileEntry::SystemDir,
+                LookupPathEntry::WindowsDir(_) => PathProfileEntry::WindowsDir,
+                LookupPathEntry::WorkingDir(_) => PathProfileEntry::WorkingDir,
+                LookupPathEntry::SystemPath(_) => PathProfileEntry::SystemPath,
+                LookupPathEntry::UserPath(p) => PathProfileEntry::UserPath(p.clone()),
+            };
+            // the system PATH and the package graph are stored as a single entry
+            if matches!(
+                entry,
+                PathProfileEntry::SystemPath | PathProfileEntry::PackageGraph
+            ) && entries.contains(&entry)
+            {
+                continue;
+            }
+            entries.push(entry);
+        }
+        Self { entries }
+    }
+
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, LookupError> {
+        let path = path.as_ref();
+        let content = fs::read_to_string(path)
+            .map_err(|e| LookupError::from(e).in_file(path, FileOperation::Read))?;
+        serde_json::from_str(&content)
+            .map_err(|e| LookupError::from(e).in_file(path, FileOperation::Parse))
+    }
+
+    pub fn write<P: AsRef<Path>>(&self, path: P) -> Result<(), LookupError> {
+        fs::write(path, serde_json::to_string_pretty(self)?)?;
+        Ok(())
+    }
+
+    /// Build the lookup path described by this profile for the given query
+    ///
+    /// Relative user directories are resolved against `base_dir`; the entries that are not
+    /// available for the query (e.g. the system directories without a Windows partition) are
+    /// skipped.
+    pub fn lookup_path<'a>(
+        &self,
+        query: &'a LookupQuery,
+        base_dir: &Path,
+    ) -> Result<LookupPath<'a>, LookupError> {
+        let system = query.system.as_ref();
+        let mut builder = LookupPathBuilder::new();
+        for entry in &self.entries {
+            let entries: Vec<LookupPathEntry<'a>> = match entry {
+                PathProfileEntry::KnownDLLs => system
+                    .and_then(|s| s.known_dlls.as_ref())
+                    .map(LookupPathEntry::KnownDLLs)
+                    .into_iter()
+                    .collect(),
+                PathProfileEntry::ApiSet => system
+                    .and_then(|s| s.apiset_map.as_ref())
+                    .or(query.fallback_apiset_map.as_ref())
+                    .map(LookupPathEntry::ApiSet)
+                    .into_iter()
+                    .collect(),
+                PathProfileEntry::PackageGraph => LookupPath::package_graph_entries(query),
+                PathProfileEntry::ExecutableDir => {
+                    vec![LookupPathEntry::ExecutableDir(query.target.app_dir.clone())]
+                }
+                PathProfileEntry::SystemDir => system
+                    .map(|s| LookupPathEntry::SystemDir(s.sys_dir.clone()))
+                    .into_iter()
+                    .collect(),
+                PathProfileEntry::WindowsDir => system
+                    .map(|s| LookupPathEntry::WindowsDir(s.win_dir.clone()))
+                    .into_iter()
+                    .collect(),
+                PathProfileEntry::WorkingDir => vec![LookupPathEntry::WorkingDir(
+                    query.target.working_dir.clone(),
+                )],
+                PathProfileEntry::SystemPath => system
+                    .map(LookupPath::system_path_entries)
+                    .unwrap_or_default(),
+                PathProfileEntry::UserPath(p) => {
+                    let p = p.to_string_lossy();
+                    let is_absolute =
+                        p.starts_with(['\\', '/']) || p.as_bytes().get(1) == Some(&b':');
+                    let anchor = if is_absolute {
+                        system.and_then(|s| s.win_dir.parent()).unwrap_or(base_dir)
+                    } else {
+                        base_dir
+                    };
+                    vec![LookupPathEntry::UserPath(
+                        base_dir.join(host_path(&p, anchor)),
+                    )]
+                }
+            };
+            for e in entries {
+                builder = builder.push(e);
+            }
+        }
+        builder.build()
+    }
+}
+
+/// Position of the working directory in the lookup path, depending on the SafeDllSearchMode
+/// registry value
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum DllSearchMode {
+    /// the working directory comes after the system directories (default)
+    Safe,
+    /// the working directory comes right after the directory of the executable
+    Unsafe,
+}
+
+/// Builder of a customized lookup path
+///
+/// Entries are added and moved relative to the ones already present; the order is validated when
+/// the lookup path is built.
+#[derive(Debug, Clone, Default)]
+pub struct LookupPathBuilder<'a> {
+    entries: Vec<LookupPathEntry<'a>>,
+}
+
+impl<'a> LookupPathBuilder<'a> {
+    /// Builder without any entry
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Current entries, in lookup order
+    pub fn entries(&self) -> &[LookupPathEntry<'a>] {
+        &self.entries
+    }
+
+    /// Add an entry at the end of the lookup path
+    pub fn push(mut self, entry: LookupPathEntry<'a>) -> Self {
+        self.entries.push(entry);
+        self
+    }
+
+    /// Look up the given directory before all the other directories (but after the known DLLs and
+    /// the API sets, which the loader always resolves first)
+    pub fn prepend_user_dir<P: Into<PathBuf>>(mut self, dir: P) -> Self {
+        let position = self
+            .entries
+            .iter()
+            .position(|e| e.get_path().is_some())
+            .unwrap_or(self.entries.len());
+        self.entries
+            .insert(position, LookupPathEntry::UserPath(dir.into()));
+        self
+    }
+
+    /// Look up the given directory after all the other entries
+    pub fn append_user_dir<P: Into<PathBuf>>(self, dir: P) -> Self {
+        self.push(LookupPathEntry::UserPath(dir.into()))
+    }
+
+    /// Look up the given directory right after the system and Windows directories
+    pub fn insert_after_system<P: Into<PathBuf>>(mut self, dir: P) -> Result<Self, LookupError> {
+        let position = self
+            .entries
+            .iter()
+            .rposition(|e| {
+                matches!(
+                    e,
+                    LookupPathEntry::SystemDir(_) | LookupPathEntry::WindowsDir(_)
+                )
+            })
+            .ok_or_else(|| {
+                LookupError::LookupPathError(
+                    "there are no system directories to insert the directory after".to_owned(),
+                )
+            })?;
+        self.entries
+            .insert(position + 1, LookupPathEntry::UserPath(dir.into()));
+        Ok(self)
+    }
+
+    /// Do not look up the working directory, as after a call to SetDllDirectory
+    pub fn remove_working_dir(mut self) -> Self {
+        self.entries
+            .retain(|e| !matches!(e, LookupPathEntry::WorkingDir(_)));
+        self
+    }
+
+    /// Move the working directory where the given search mode puts it
+    pub fn set_search_mode(mut self, mode: DllSearchMode) -> Result<Self, LookupError> {
+        let position = match self
+            .entries
+            .iter()
+            .position(|e| matches!(e, LookupPathEntry::WorkingDir(_)))
+        {
+            Some(position) => position,
+            // nothing to move
+            None => return Ok(self),
+        };
+        let working_dir = self.entries.remove(position);
+        let anchor = match mode {
+            DllSearchMode::Safe => self.entries.iter().rposition(|e| {
+                matches!(
+                    e,
+                    LookupPathEntry::SystemDir(_) | LookupPathEntry::WindowsDir(_)
+                )
+            }),
+            DllSearchMode::Unsafe => self
+                .entries
+                .iter()
+                .position(|e| matches!(e, LookupPathEntry::ExecutableDir(_))),
+        };
+        let anchor = anchor.ok_or_else(|| {
+            LookupError::LookupPathError(format!(
+                "the working directory cannot be placed for the {mode:?} search mode without {} entry",
+                match mode {
+                    DllSearchMode::Safe => "a system directory",
+                    DllSearchMode::Unsafe => "an executable directory",
+                }
+            ))
+        })?;
+        self.entries.insert(anchor + 1, working_dir);
+        Ok(self)
+    }
+
+    /// Validate the order of the entries and build the lookup path
+    ///
+    /// The known DLLs and the API sets must come before all the directories, and only the user,
+    /// PATH and package graph directories can appear more than once.
+    pub fn build(self) -> Result<LookupPath<'a>, LookupError> {
+        if self.entries.is_empty() {
+            return Err(LookupError::LookupPathError(
+                "the lookup path has no entries".to_owned(),
+            ));
+        }
+        if let Some(first_dir) = self.entries.iter().position(|e| e.get_path().is_some()) {
+            if let Some(e) = self.entries[first_dir..]
+                .iter()
+                .find(|e| e.get_path().is_none())
+            {
+                return Err(LookupError::LookupPathError(format!(
+                    "{} must come before all the directories",
+                    e.kind_name()
+                )));
+            }
+        }
+        for (i, e) in self.entries.iter().enumerate() {
+            let unique = !matches!(
+                e,
+                LookupPathEntry::UserPath(_)
+                    | LookupPathEntry::SystemPath(_)
+                    | LookupPathEntry::PackageGraph(_)
+            );
+            if unique
+                && self.entries[..i]
+                    .iter()
+                    .any(|o| o.kind_name() == e.kind_name())
+            {
+                return Err(LookupError::LookupPathError(format!(
+                    "{} appears more than once",
+                    e.kind_name()
+                )));
+            }
+        }
+        Ok(LookupPath {
+            entries: self.entries,
+            fs_cache: Arc::new(WinFileSystemCache::new()),
+            disabled_entries: Vec::new(),
+        })
+    }
+}
+
 #[cfg(test)]
 mod tests {
     use crate::common::LookupError;
-    use crate::path::{LookupPath, LookupPathEntry};
-    use crate::query::LookupQuery;
+    use crate::path::{
+        DllSearchMode, LookupPath, LookupPathBuilder, LookupPathEntry, PathProfile,
+        PathProfileEntry, ResolutionEntry,
+    };
+    use crate::query::{DllOverride, LookupQuery};
+    use std::path::PathBuf;
 
+    #[cfg(all(windows, feature = "dwp"))]
     #[test]
     fn parse_dwp() -> Result<(), LookupError> {
         let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -349,6 +1229,171 @@ mod tests {
             assert!(std::matches!(path.entries[2], LookupPathEntry::SystemDir(_)));
         }
 
+        Ok(())
+    }
+    #[cfg(all(not(windows), feature = "dwp"))]
+    #[test]
+    fn parse_dwp_without_windows_partition() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let dwp_file_path = d.join("test_data/dwp/lookup_path.dwp");
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let mut query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+        query.system = None;
+
+        let path = LookupPath::from_dwp_file(&dwp_file_path, &query)?;
+        assert_eq!(path.entries.len(), 3);
+        assert!(std::matches!(
+            path.entries[0],
+            LookupPathEntry::ExecutableDir(_)
+        ));
+        assert_eq!(
+            path.entries[1],
+            LookupPathEntry::UserPath(std::path::PathBuf::from("/Users"))
+        );
+        assert_eq!(
+            path.entries[2],
+            LookupPathEntry::UserPath(std::path::PathBuf::from("/Users/user"))
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn probe_single_dll() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let app_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        let lib_dir = d.join("test_data/test_project1/DepRunTest/build/DepRunTestLib/Debug");
+        let path = LookupPathBuilder::new()
+            .push(LookupPathEntry::ExecutableDir(app_dir.clone()))
+            .append_user_dir(&lib_dir)
+            .build()?;
+        let mut query =
+            LookupQuery::deduce_from_executable_location(app_dir.join("DepRunTest.exe"))?;
+
+        let report = path.probe(&query, "deprunTESTlib.dll")?;
+        assert!(report.found);
+        assert_eq!(report.entry, Some(ResolutionEntry::ExecutableDir));
+        assert_eq!(report.path, Some(app_dir.join("DepRunTestLib.dll")));
+        assert_eq!(report.probes.len(), 2);
+        assert_eq!(report.shadowed(), vec![lib_dir.join("DepRunTestLib.dll")]);
+        assert!(report.to_string().contains("(shadowed)"));
+
+        let missing = path.probe(&query, "nonexistent.dll")?;
+        assert!(!missing.found);
+        assert!(missing.path.is_none() && missing.shadowed().is_empty());
+
+        // the DLL overrides of the query are applied as by a scan
+        let override_path = lib_dir.join("DepRunTestLib.dll");
+        query.override_dll(
+            "depruntestlib.dll",
+            DllOverride::Path(override_path.clone()),
+        );
+        let overridden = path.probe(&query, "DepRunTestLib.dll")?;
+        assert!(overridden.found);
+        assert_eq!(overridden.entry, Some(ResolutionEntry::DllOverride));
+        assert_eq!(overridden.path, Some(override_path));
+        assert_eq!(overridden.probes.len(), 3);
+        query.override_dll("nonexistent.dll", DllOverride::AssumePresent);
+        assert!(path.probe(&query, "nonexistent.dll")?.found);
+        query.override_dll("depruntestlib.dll", DllOverride::AssumeMissing);
+        let missing = path.probe(&query, "DepRunTestLib.dll")?;
+        assert!(!missing.found && missing.path.is_none());
+        assert!(missing.to_string().contains("not found"));
+        Ok(())
+    }
+
+    #[test]
+    fn build_custom_path() -> Result<(), LookupError> {
+        let apiset_map = crate::apiset::ApisetMap::new();
+        let builder = LookupPathBuilder::new()
+            .push(LookupPathEntry::ApiSet(&apiset_map))
+            .push(LookupPathEntry::ExecutableDir(PathBuf::from("/app")))
+            .push(LookupPathEntry::SystemDir(PathBuf::from("/win/System32")))
+            .push(LookupPathEntry::WindowsDir(PathBuf::from("/win")))
+            .push(LookupPathEntry::WorkingDir(PathBuf::from("/cwd")));
+
+        let path = builder
+            .clone()
+            .prepend_user_dir("/first")
+            .insert_after_system("/after_system")?
+            .append_user_dir("/last")
+            .set_search_mode(DllSearchMode::Unsafe)?
+            .build()?;
+        let kinds: Vec<&str> = path.entries.iter().map(|e| e.kind_name()).collect();
+        assert_eq!(
+            kinds,
+            [
+                "ApiSet",
+                "UserPath",
+                "ExecutableDir",
+                "WorkingDir",
+                "SystemDir",
+                "WindowsDir",
+                "UserPath",
+                "UserPath"
+            ]
+        );
+        assert_eq!(
+            path.search_path(),
+            [
+                "/first",
+                "/app",
+                "/cwd",
+                "/win/System32",
+                "/win",
+                "/after_system",
+                "/last"
+            ]
+            .map(PathBuf::from)
+        );
+
+        let path = builder.clone().remove_working_dir().build()?;
+        assert_eq!(path.entries.len(), 4);
+
+        // nonsensical orders are rejected
+        assert!(LookupPathBuilder::new().build().is_err());
+        assert!(builder
+            .clone()
+            .push(LookupPathEntry::ApiSet(&apiset_map))
+            .build()
+            .is_err());
+        assert!(builder
+            .clone()
+            .push(LookupPathEntry::WindowsDir(PathBuf::from("/win")))
+            .build()
+            .is_err());
+        assert!(LookupPathBuilder::new()
+            .append_user_dir("/dir")
+            .insert_after_system("/dir")
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn load_path_profile() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let profile_path = d.join("test_data/path_profile/profile.json");
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe");
+
+        let mut query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+        query.system = None;
+
+        // the system entries are skipped without a Windows partition
+        let path = LookupPath::from_path_profile(&profile_path, &query)?;
+        assert_eq!(path.entries.len(), 3);
+        assert!(matches!(path.entries[0], LookupPathEntry::ExecutableDir(_)));
+        assert!(matches!(path.entries[2], LookupPathEntry::WorkingDir(_)));
+        let lib = path.search_dll("DepRunTestLib.dll")?.unwrap();
+        assert_eq!(lib.location.kind_name(), "UserPath");
+
+        let profile = PathProfile::from_lookup_path(&path);
+        assert_eq!(profile.entries[0], PathProfileEntry::ExecutableDir);
+        assert!(matches!(profile.entries[1], PathProfileEntry::UserPath(_)));
+
         Ok(())
     }
 }
@@ -1,9 +1,14 @@
 //! Low-level PE file format access through the goblin and pelite libraries
 
+extern crate cpp_demangle;
 extern crate msvc_demangler;
 extern crate multimap;
 extern crate thiserror;
-use crate::common::LookupError;
+use crate::common::{Diagnostic, DiagnosticKind, FileOperation, LookupError};
+use crate::executable::{
+    Architecture, AssemblyDependency, ImportDescriptor, ImportedSymbol, SecurityFeatures, Subsystem,
+};
+use crate::provider::{FileProvider, LocalFileSystem};
 use std::collections::{HashMap, HashSet};
 use std::path::{Path, PathBuf};
 
@@ -14,46 +19,391 @@ pub struct PEFileMap {
 
 impl PEFileMap {
     pub fn new<P: AsRef<Path>>(path: P) -> Result<Self, LookupError> {
+        Self::from_provider(path, &LocalFileSystem)
+    }
+
+    /// Read the file through the given backend
+    pub fn from_provider<P: AsRef<Path>>(
+        path: P,
+        provider: &dyn FileProvider,
+    ) -> Result<Self, LookupError> {
         Ok(Self {
             path: PathBuf::from(path.as_ref()),
-            content: std::fs::read(path)?,
+            content: provider
+                .read_file(path.as_ref())
+                .map_err(|e| e.in_file(&path, FileOperation::Read))?,
+        })
+    }
+
+    /// Map of a file already in memory (e.g. uploaded to a web page), without file system access
+    pub fn from_bytes<P: AsRef<Path>>(path: P, content: Vec<u8>) -> Self {
+        Self {
+            path: PathBuf::from(path.as_ref()),
+            content,
+        }
+    }
+
+    /// Path of the file this map was read from
+    pub fn path(&self) -> &Path {
+        &self.path
+    }
+
+    /// Raw content of the file
+    pub fn content(&self) -> &[u8] {
+        &self.content
+    }
+}
+
+/// Format of a file that could not be parsed as a PE executable or DLL, as detected from its content
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum FileFormat {
+    Empty,
+    /// OLE compound document, e.g. an MSI installer
+    Msi,
+    /// Script starting with a shebang line
+    Script {
+        interpreter: String,
+    },
+    Elf,
+    MachO,
+    /// ZIP archive (a self-extractor with a valid PE stub is parsed as a PE file instead)
+    Zip,
+    /// MZ executable without a PE header (16-bit DOS, NE or LE executable)
+    DosExecutable,
+    /// PE file whose headers or sections extend beyond the end of the file
+    TruncatedPe,
+    /// PE file with complete but invalid headers
+    CorruptPe,
+    Text,
+    Unknown,
+}
+
+impl std::fmt::Display for FileFormat {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FileFormat::Empty => write!(f, "an empty file"),
+            FileFormat::Msi => write!(f, "an MSI installer or other OLE compound document"),
+            FileFormat::Script { interpreter } => write!(f, "a script for {interpreter}"),
+            FileFormat::Elf => write!(f, "an ELF binary (Linux/Unix)"),
+            FileFormat::MachO => write!(f, "a Mach-O binary (macOS)"),
+            FileFormat::Zip => write!(f, "a ZIP archive"),
+            FileFormat::DosExecutable => write!(f, "a DOS executable without a PE header"),
+            FileFormat::TruncatedPe => write!(f, "a truncated PE file"),
+            FileFormat::CorruptPe => write!(f, "a corrupt PE file"),
+            FileFormat::Text => write!(f, "a text file"),
+            FileFormat::Unknown => write!(f, "an unknown file format"),
+        }
+    }
+}
+
+fn read_u16_at(content: &[u8], offset: usize) -> Option<usize> {
+    let bytes = content.get(offset..offset.checked_add(2)?)?;
+    Some(u16::from_le_bytes([bytes[0], bytes[1]]) as usize)
+}
+
+fn read_u32_at(content: &[u8], offset: usize) -> Option<usize> {
+    let bytes = content.get(offset..offset.checked_add(4)?)?;
+    Some(u32::from_le_bytes([bytes[0], bytes[1], bytes[2], bytes[3]]) as usize)
+}
+
+/// Tell a truncated PE file from one with invalid headers, or from a DOS executable
+fn detect_mz_format(content: &[u8]) -> FileFormat {
+    let pe_offset = match read_u32_at(content, 0x3c) {
+        Some(o) => o,
+        None => return FileFormat::TruncatedPe,
+    };
+    // the offsets read from the file can be arbitrary, so the additions must not overflow
+    match pe_offset
+        .checked_add(4)
+        .and_then(|end| content.get(pe_offset..end))
+    {
+        Some(b"PE\0\0") => {}
+        Some(_) => return FileFormat::DosExecutable,
+        None if pe_offset > 0x10000 => return FileFormat::DosExecutable,
+        None => return FileFormat::TruncatedPe,
+    }
+    let (sections, optional_header_size) = match (
+        read_u16_at(content, pe_offset + 6),
+        read_u16_at(content, pe_offset + 20),
+    ) {
+        (Some(s), Some(o)) => (s, o),
+        _ => return FileFormat::TruncatedPe,
+    };
+    let section_table = pe_offset + 24 + optional_header_size;
+    if content.len() < section_table + 40 * sections {
+        return FileFormat::TruncatedPe;
+    }
+    let truncated_section = (0..sections).any(|i| {
+        let header = section_table + 40 * i;
+        let raw_size = read_u32_at(content, header + 16).unwrap_or(0);
+        let raw_offset = read_u32_at(content, header + 20).unwrap_or(0);
+        raw_size > 0
+            && raw_offset
+                .checked_add(raw_size)
+                .is_none_or(|end| end > content.len())
+    });
+    if truncated_section {
+        FileFormat::TruncatedPe
+    } else {
+        FileFormat::CorruptPe
+    }
+}
+
+/// Guess the format of a file that is not a valid PE file from its first bytes
+pub fn detect_file_format(content: &[u8]) -> FileFormat {
+    match content {
+        [] => FileFormat::Empty,
+        [0xd0, 0xcf, 0x11, 0xe0, 0xa1, 0xb1, 0x1a, 0xe1, ..] => FileFormat::Msi,
+        [b'#', b'!', rest @ ..] => {
+            let line = rest.split(|&b| b == b'\n').next().unwrap_or_default();
+            FileFormat::Script {
+                interpreter: String::from_utf8_lossy(line).trim().to_owned(),
+            }
+        }
+        [0x7f, b'E', b'L', b'F', ..] => FileFormat::Elf,
+        [0xfe, 0xed, 0xfa, 0xce | 0xcf, ..]
+        | [0xce | 0xcf, 0xfa, 0xed, 0xfe, ..]
+        | [0xca, 0xfe, 0xba, 0xbe, ..] => FileFormat::MachO,
+        [b'P', b'K', 0x03, 0x04, ..] | [b'P', b'K', 0x05, 0x06, ..] => FileFormat::Zip,
+        [b'M', b'Z', ..] => detect_mz_format(content),
+        _ if std::str::from_utf8(content).is_ok() => FileFormat::Text,
+        _ => FileFormat::Unknown,
+    }
+}
+
+/// File names ending in ".dll" found in the runs of printable characters of a buffer
+///
+/// Only the last word or path component is kept; names containing format placeholders are skipped.
+fn dll_name_strings(data: &[u8]) -> Vec<String> {
+    let is_path_char = |b: &u8| b.is_ascii_alphanumeric() || b"_-.+~$% \\/:".contains(b);
+    data.split(|b| !is_path_char(b))
+        .filter_map(|run| std::str::from_utf8(run).ok())
+        .filter_map(|run| {
+            let name = run.rsplit(['\\', '/', ' ']).next()?;
+            let stem = name.get(..name.len().checked_sub(4)?)?;
+            (name[stem.len()..].eq_ignore_ascii_case(".dll")
+                && stem.len() >= 2
+                && !name.contains('%'))
+            .then(|| name.to_owned())
         })
+        .collect()
+}
+
+/// Which of the two parsing libraries reads the tables of the files
+///
+/// goblin parses all the tables upfront, pelite reads them on demand; each one is used as a
+/// fallback when the other cannot parse the file.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum ParserBackend {
+    /// goblin, which seems to be less fragile
+    #[default]
+    PreferGoblin,
+    /// pelite, which does not parse the tables upfront
+    PreferPelite,
+    /// read the tables with both libraries, reporting the differences as diagnostics
+    /// (`DiagnosticKind::ParserMismatch`); the results of goblin are used
+    Compare,
+}
+
+impl std::str::FromStr for ParserBackend {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "prefer-goblin" => Ok(Self::PreferGoblin),
+            "prefer-pelite" => Ok(Self::PreferPelite),
+            "compare" => Ok(Self::Compare),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown parser backend {s} (expected prefer-goblin, prefer-pelite or compare)"
+            ))),
+        }
     }
 }
 
 pub struct PEFile<'a> {
+    filemap: &'a PEFileMap,
     pefile: Option<pelite::PeFile<'a>>,
     peobject: Option<goblin::pe::PE<'a>>,
+    backend: ParserBackend,
+    diagnostics: Vec<Diagnostic>,
 }
 
 impl<'a> PEFile<'a> {
     pub fn new(filemap: &'a PEFileMap) -> Result<Self, LookupError> {
+        Self::with_backend(filemap, ParserBackend::default())
+    }
+
+    /// Parse the file with the given backend selection
+    pub fn with_backend(
+        filemap: &'a PEFileMap,
+        backend: ParserBackend,
+    ) -> Result<Self, LookupError> {
+        let _span =
+            tracing::debug_span!("parse", path = %filemap.path.display(), ?backend).entered();
+        let pe = Self::parse(filemap, backend)?;
+        for diagnostic in &pe.diagnostics {
+            tracing::debug!(kind = ?diagnostic.kind, "{}", diagnostic.message);
+        }
+        Ok(pe)
+    }
+
+    fn parse(filemap: &'a PEFileMap, backend: ParserBackend) -> Result<Self, LookupError> {
+        let mut pe = Self::headers_only(filemap)?;
+        pe.backend = backend;
+        if let Ok((_, issues)) = pe.pelite_imports_lenient() {
+            for issue in issues {
+                pe.diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::ParseError,
+                    Some(&filemap.path),
+                    format!("pelite: skipped a malformed import entry: {issue}"),
+                ));
+            }
+        }
+        // goblin is only a fallback if pelite parsed the file
+        if backend == ParserBackend::PreferPelite && pe.pefile.is_some() {
+            return Ok(pe);
+        }
+        pe.peobject = match goblin::Object::parse(&filemap.content) {
+            Ok(goblin::Object::PE(pef)) => Some(pef),
+            Ok(ukn) => {
+                pe.diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::WrongFileFormat,
+                    Some(&filemap.path),
+                    format!("goblin: unexpected executable format: {ukn:?}"),
+                ));
+                None
+            }
+            Err(e) => {
+                pe.diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::ParseError,
+                    Some(&filemap.path),
+                    format!("goblin: {e}"),
+                ));
+                None
+            }
+        };
+        if backend == ParserBackend::Compare {
+            pe.compare_backends();
+        }
+        Ok(pe)
+    }
+
+    /// Parse only the headers of the file, for when its tables are not needed (e.g. system DLLs
+    /// whose dependencies are not scanned)
+    ///
+    /// The accessors still work, but read the tables on demand through pelite only, instead of
+    /// parsing them all upfront as `new` does.
+    pub fn headers_only(filemap: &'a PEFileMap) -> Result<Self, LookupError> {
+        let mut diagnostics = Vec::new();
+        let pefile = match pelite::PeFile::from_bytes(&filemap.content) {
+            Ok(pef) => Some(pef),
+            Err(e) => {
+                let kind = match e {
+                    pelite::Error::BadMagic | pelite::Error::PeMagic => {
+                        DiagnosticKind::WrongFileFormat
+                    }
+                    _ => DiagnosticKind::ParseError,
+                };
+                diagnostics.push(Diagnostic::new(
+                    kind,
+                    Some(&filemap.path),
+                    format!("pelite: {e}"),
+                ));
+                None
+            }
+        };
         Ok(Self {
-            pefile: match pelite::PeFile::from_bytes(&filemap.content) {
-                Ok(pef) => Some(pef),
-                Err(e) => {
-                    match e {
-                        pelite::Error::BadMagic | pelite::Error::PeMagic => {
-                            eprintln!("{:?}", LookupError::WrongFileFormatError(e))
-                        }
+            filemap,
+            pefile,
+            peobject: None,
+            backend: ParserBackend::default(),
+            diagnostics,
+        })
+    }
 
-                        _ => eprintln!("{:?}", LookupError::PEError(e)),
-                    };
-                    None
-                }
-            },
-            peobject: match goblin::Object::parse(&filemap.content) {
-                Ok(goblin::Object::PE(pef)) => Some(pef),
-                Ok(ukn) => {
-                    eprintln!("unexpected executable format: {ukn:?}");
-                    None
-                }
-                Err(e) => {
-                    eprintln!("{:?}", LookupError::GoblinError(e));
-                    None
+    /// Non-fatal issues found while parsing the file
+    pub fn diagnostics(&self) -> &[Diagnostic] {
+        &self.diagnostics
+    }
+
+    /// Access the goblin representation, if the file was parsed by goblin and its results are to be
+    /// used
+    fn goblin(&self) -> Option<&goblin::pe::PE<'a>> {
+        match self.backend {
+            ParserBackend::PreferPelite if self.pefile.is_some() => None,
+            _ => self.peobject.as_ref(),
+        }
+    }
+
+    /// Report the differences between the tables read by goblin and by pelite
+    fn compare_backends(&mut self) {
+        let Some(peo) = self.peobject.as_ref() else {
+            return;
+        };
+        if self.pefile.is_none() {
+            return;
+        }
+        let mut mismatches = Vec::new();
+        let mut compare =
+            |table: &str, goblin: HashSet<String>, pelite: Result<HashSet<String>, LookupError>| {
+                let message = match pelite {
+                    Ok(pelite) => backend_differences(&goblin, &pelite),
+                    Err(e) => Some(format!("pelite failed: {e}")),
+                };
+                if let Some(message) = message {
+                    mismatches.push(format!("{table}: {message}"));
                 }
-            },
-        })
+            };
+        compare(
+            "import table",
+            goblin_dependencies(peo).into_iter().collect(),
+            self.pelite_dependencies()
+                .map(|names| names.into_iter().collect()),
+        );
+        compare("export table", goblin_exports(peo), self.pelite_exports());
+        compare(
+            "export forwarders",
+            goblin_forwarded_dependencies(peo).into_iter().collect(),
+            self.pelite_forwarded_dependencies()
+                .map(|names| names.into_iter().collect()),
+        );
+        let flatten = |imports: HashMap<String, HashSet<String>>| -> HashSet<String> {
+            imports
+                .into_iter()
+                .flat_map(|(dll, names)| names.into_iter().map(move |n| format!("{dll}!{n}")))
+                .collect()
+        };
+        compare(
+            "imported symbols",
+            flatten(goblin_imported_names(peo)),
+            self.pelite_imported_names().map(flatten),
+        );
+        for mismatch in mismatches {
+            self.diagnostics.push(Diagnostic::new(
+                DiagnosticKind::ParserMismatch,
+                Some(&self.filemap.path),
+                format!("goblin and pelite disagree on the {mismatch}"),
+            ));
+        }
+    }
+
+    /// Access the pelite representation, if the file could be parsed by pelite
+    ///
+    /// Otherwise, the error names the file and what it appears to be instead.
+    fn pelite(&self) -> Result<pelite::PeFile<'a>, LookupError> {
+        self.pefile.ok_or_else(|| self.wrong_file_format())
+    }
+
+    /// Attach the path of the file to the errors of the parsers
+    fn parsed<T>(&self, result: Result<T, LookupError>) -> Result<T, LookupError> {
+        result.map_err(|e| e.in_file(&self.filemap.path, FileOperation::Parse))
+    }
+
+    fn wrong_file_format(&self) -> LookupError {
+        LookupError::WrongFileFormat {
+            path: self.filemap.path.clone(),
+            detected: detect_file_format(&self.filemap.content),
+        }
     }
 
     /// Read the DLL name as specified in the PE file headers
@@ -61,99 +411,365 @@ impl<'a> PEFile<'a> {
     /// This should match the dependency name specified in the import table of the file depending on
     /// this DLL
     pub fn read_dll_name(&self) -> Result<String, LookupError> {
-        Ok(self.pefile.unwrap().exports()?.dll_name()?.to_string())
+        self.parsed(self.pelite_dll_name())
+    }
+
+    fn pelite_dll_name(&self) -> Result<String, LookupError> {
+        Ok(self.pelite()?.exports()?.dll_name()?.to_string())
+    }
+
+    /// Read the file version from the version resource, if the file has one
+    pub fn read_file_version(&self) -> Option<String> {
+        let version_info = self.pefile?.resources().ok()?.version_info().ok()?;
+        let v = version_info.fixed()?.dwFileVersion;
+        Some(format!("{}.{}.{}.{}", v.Major, v.Minor, v.Patch, v.Build))
+    }
+
+    /// Read the machine type from the COFF header
+    pub fn read_architecture(&self) -> Option<Architecture> {
+        let machine = match (&self.peobject, &self.pefile) {
+            (Some(pe), _) => pe.header.coff_header.machine,
+            (None, Some(pef)) => pef.file_header().Machine,
+            (None, None) => return None,
+        };
+        Some(Architecture::from_machine(machine))
+    }
+
+    /// Read the subsystem from the optional header
+    pub fn read_subsystem(&self) -> Option<Subsystem> {
+        let subsystem = match (&self.peobject, &self.pefile) {
+            (Some(pe), _) => pe.header.optional_header?.windows_fields.subsystem,
+            (None, Some(pef)) => match pef.optional_header() {
+                pelite::Wrap::T32(header) => header.Subsystem,
+                pelite::Wrap::T64(header) => header.Subsystem,
+            },
+            (None, None) => return None,
+        };
+        Some(Subsystem::from_value(subsystem))
+    }
+
+    /// Read the exploit mitigations enabled in the headers
+    pub fn read_security_features(&self) -> Option<SecurityFeatures> {
+        let (dll_characteristics, characteristics) = match (&self.peobject, &self.pefile) {
+            (Some(pe), _) => (
+                pe.header
+                    .optional_header?
+                    .windows_fields
+                    .dll_characteristics,
+                pe.header.coff_header.characteristics,
+            ),
+            (None, Some(pef)) => (
+                match pef.optional_header() {
+                    pelite::Wrap::T32(header) => header.DllCharacteristics,
+                    pelite::Wrap::T64(header) => header.DllCharacteristics,
+                },
+                pef.file_header().Characteristics,
+            ),
+            (None, None) => return None,
+        };
+        Some(SecurityFeatures::from_characteristics(
+            dll_characteristics,
+            characteristics,
+        ))
+    }
+
+    /// Read the address (RVA) of the entry point, if the file has one
+    pub fn read_entry_point(&self) -> Option<u32> {
+        let entry_point = match (&self.peobject, &self.pefile) {
+            (Some(pe), _) => {
+                pe.header
+                    .optional_header?
+                    .standard_fields
+                    .address_of_entry_point as u32
+            }
+            (None, Some(pef)) => match pef.optional_header() {
+                pelite::Wrap::T32(header) => header.AddressOfEntryPoint,
+                pelite::Wrap::T64(header) => header.AddressOfEntryPoint,
+            },
+            (None, None) => return None,
+        };
+        (entry_point != 0).then_some(entry_point)
+    }
+
+    /// Read the names of the Authenticode signers from the certificate table, if the file is signed
+    pub fn read_signers(&self) -> Option<Vec<String>> {
+        // unlike the other data directories, the certificate table is located by file offset
+        let (offset, size) = match (&self.peobject, &self.pefile) {
+            (Some(pe), _) => {
+                let optional_header = pe.header.optional_header?;
+                let table = *optional_header.data_directories.get_certificate_table()?;
+                (table.virtual_address, table.size)
+            }
+            (None, Some(pef)) => {
+                let table = pef
+                    .data_directory()
+                    .get(pelite::image::IMAGE_DIRECTORY_ENTRY_SECURITY)?;
+                (table.VirtualAddress, table.Size)
+            }
+            (None, None) => return None,
+        };
+        if size == 0 {
+            return None;
+        }
+        let start = offset as usize;
+        let end = start.checked_add(size as usize)?;
+        let table = self.filemap.content.get(start..end)?;
+        Some(crate::signature::signer_names(table))
+    }
+
+    /// Read the side-by-side assemblies requested by the embedded manifest, if the file has one
+    pub fn read_assembly_dependencies(&self) -> Option<Vec<AssemblyDependency>> {
+        let manifest = self.pefile?.resources().ok()?.manifest().ok()?;
+        parse_manifest_dependencies(manifest).ok()
     }
 
     /// read the names of the DLLs this executable depends on
     pub fn read_dependencies(&self) -> Result<Vec<String>, LookupError> {
-        // prefer goblin since it seems to be less fragile
-        if let Some(peo) = self.peobject.as_ref() {
-            return Ok(peo.libraries.iter().map(|i| i.to_string()).collect());
+        match self.goblin() {
+            Some(peo) => Ok(goblin_dependencies(peo)),
+            None => self.parsed(self.pelite_dependencies()),
         }
+    }
 
+    fn pelite_dependencies(&self) -> Result<Vec<String>, LookupError> {
         // Access the import directory
-        let imports = self
-            .pefile
-            .unwrap()
-            .imports()
-            .map_err(LookupError::PEError)?;
+        let imports = self.pelite()?.imports().map_err(LookupError::PEError)?;
 
-        let names: Vec<&pelite::util::CStr> = imports
+        // the descriptors whose name cannot be read are skipped (see `pelite_imports_lenient`)
+        Ok(imports
             .iter()
-            .map(|desc| desc.dll_name())
-            .collect::<Result<Vec<&pelite::util::CStr>, pelite::Error>>()
-            .map_err(LookupError::PEError)?;
+            .filter_map(|desc| desc.dll_name().ok()?.to_str().ok().map(str::to_owned))
+            .collect())
+    }
 
-        Ok(names
-            .iter()
-            .filter_map(|s| s.to_str().ok())
-            .map(|s| s.to_string())
-            .collect::<Vec<String>>())
+    /// Read the names of the DLLs this executable loads on first use (delay-load import table)
+    pub fn read_delay_load_dependencies(&self) -> Result<Vec<String>, LookupError> {
+        self.parsed(self.pelite_delay_load_dependencies())
     }
 
-    /// Get the list of symbols imported by this file from each of its dependencies
-    pub fn read_imports(&self) -> Result<HashMap<String, HashSet<String>>, LookupError> {
-        // prefer goblin since it seems to be less fragile
-        if let Some(peo) = self.peobject.as_ref() {
-            let imports: multimap::MultiMap<&str, &str> = peo
-                .imports
-                .iter()
-                .map(|i| (i.dll, i.name.as_ref()))
-                .collect();
-
-            let ret: HashMap<String, HashSet<String>> = imports
-                .iter_all()
-                .map(|(k, v)| (k.to_string(), v.iter().map(ToString::to_string).collect()))
-                .collect();
-
-            return Ok(ret);
+    fn pelite_delay_load_dependencies(&self) -> Result<Vec<String>, LookupError> {
+        let pe = self.pelite()?;
+        let directory = match pe
+            .data_directory()
+            .get(pelite::image::IMAGE_DIRECTORY_ENTRY_DELAY_IMPORT)
+        {
+            Some(directory) if directory.VirtualAddress != 0 => directory,
+            _ => return Ok(Vec::new()),
+        };
+        let image_base = match pe.optional_header() {
+            pelite::Wrap::T32(header) => header.ImageBase as u64,
+            pelite::Wrap::T64(header) => header.ImageBase,
+        };
+
+        // each descriptor is made of 8 DWORDs, the table is terminated by an empty one
+        let mut names = Vec::new();
+        let mut rva = directory.VirtualAddress;
+        loop {
+            let descriptor: [u32; 8] = pe.derva_copy(rva)?;
+            if descriptor == [0; 8] {
+                break;
+            }
+            let [attributes, name, ..] = descriptor;
+            // descriptors written by old linkers contain virtual addresses instead of RVAs
+            let name_rva = if attributes & 1 == 0 {
+                (name as u64).wrapping_sub(image_base) as u32
+            } else {
+                name
+            };
+            names.push(pe.derva_c_str(name_rva)?.to_str()?.to_owned());
+            rva += std::mem::size_of_val(&descriptor) as u32;
         }
+        Ok(names)
+    }
 
-        use LookupError::PEError;
-        // Access the import directory
-        let imports = self.pefile.unwrap().imports().map_err(PEError)?;
+    /// Read the names of the DLLs some of the exports of this DLL are forwarded to
+    pub fn read_forwarded_dependencies(&self) -> Result<Vec<String>, LookupError> {
+        match self.goblin() {
+            Some(peo) => Ok(goblin_forwarded_dependencies(peo)),
+            None => self.parsed(self.pelite_forwarded_dependencies()),
+        }
+    }
 
-        let mut ret = HashMap::new();
+    fn pelite_forwarded_dependencies(&self) -> Result<Vec<String>, LookupError> {
+        let exports = match self.pelite()?.exports() {
+            Ok(exports) => exports,
+            Err(pelite::Error::Null) => return Ok(Vec::new()),
+            Err(e) => return Err(LookupError::PEError(e)),
+        };
+        let by = exports.by()?;
+        let mut forwards = Vec::new();
+        for export in by.iter() {
+            if let Some(forward) = export?.forward() {
+                // forwarders have the form "DLLNAME.symbol" or "DLLNAME.#ordinal"
+                let forward = forward.to_str()?;
+                if let Some((lib, _)) = forward.rsplit_once('.') {
+                    forwards.push(lib.to_owned());
+                }
+            }
+        }
+        Ok(forwarded_dll_names(forwards))
+    }
 
-        use pelite::pe32::imports::Import;
+    /// Find DLL names among the strings of the data sections (ASCII and UTF-16), excluding the
+    /// ones referenced by the import, delay-load import and export tables
+    ///
+    /// This is a heuristic: such names are often passed to LoadLibrary to load plugins or optional
+    /// components at runtime, but they can also be plain text.
+    pub fn read_dynamic_load_candidates(&self) -> Result<Vec<String>, LookupError> {
+        self.parsed(self.pelite_dynamic_load_candidates())
+    }
 
-        for desc in imports.iter() {
-            // Import Address Table and Import Name Table for this imported DLL
-            let dllname = desc.dll_name()?;
-            let importednames: HashSet<_> = desc
-                .int()?
-                .flat_map(|imp| match imp {
-                    Ok(Import::ByName { hint: _, name }) => Ok(name.to_string()),
-                    Ok(Import::ByOrdinal { ord: _ }) => {
-                        // println!("by ordinal");
-                        Ok("".to_owned()) // TODO apparently we can't check much here...
-                    }
-                    Err(err) => {
-                        eprintln!("Error parsing import: {err}");
-                        Err(err)
+    fn pelite_dynamic_load_candidates(&self) -> Result<Vec<String>, LookupError> {
+        let pe = self.pelite()?;
+        let mut known: Vec<String> = self.read_dependencies()?;
+        known.extend(self.read_delay_load_dependencies().unwrap_or_default());
+        known.extend(self.read_forwarded_dependencies().unwrap_or_default());
+        known.extend(self.read_dll_name().ok());
+
+        let mut names: Vec<String> = Vec::new();
+        for section in pe.section_headers().iter() {
+            // skip the sections containing code
+            if section.Characteristics & (0x20 | 0x2000_0000) != 0 {
+                continue;
+            }
+            let start = section.PointerToRawData as usize;
+            let end = start.saturating_add(section.SizeOfRawData as usize);
+            let data = match self.filemap.content.get(start..end) {
+                Some(data) => data,
+                None => continue,
+            };
+            // UTF-16 strings are scanned as ASCII after dropping their zero high bytes
+            let wide = |alignment: usize| -> Vec<u8> {
+                data[alignment.min(data.len())..]
+                    .chunks_exact(2)
+                    .map(|c| if c[1] == 0 { c[0] } else { 0 })
+                    .collect()
+            };
+            for text in [data.to_vec(), wide(0), wide(1)] {
+                for name in dll_name_strings(&text) {
+                    if !known.iter().any(|k| k.eq_ignore_ascii_case(&name))
+                        && !names.iter().any(|n| n.eq_ignore_ascii_case(&name))
+                    {
+                        names.push(name);
                     }
-                })
-                .collect();
+                }
+            }
+        }
+        Ok(names)
+    }
 
-            ret.insert(dllname.to_str()?.to_owned(), importednames);
+    /// Get the list of symbols imported by this file from each of its dependencies
+    ///
+    /// Symbols imported by ordinal are listed as an empty name.
+    pub fn read_imported_names(&self) -> Result<HashMap<String, HashSet<String>>, LookupError> {
+        match self.goblin() {
+            Some(peo) => Ok(goblin_imported_names(peo)),
+            None => self.parsed(self.pelite_imported_names()),
         }
+    }
 
+    fn pelite_imported_names(&self) -> Result<HashMap<String, HashSet<String>>, LookupError> {
+        let mut ret: HashMap<String, HashSet<String>> = HashMap::new();
+        for symbol in self.pelite_imports()? {
+            ret.entry(symbol.dll)
+                .or_default()
+                .insert(symbol.name.unwrap_or_default());
+        }
         Ok(ret)
     }
 
+    /// Read the entries of the import table, with the way each symbol is imported
+    ///
+    /// Malformed entries are skipped, and reported in the diagnostics of the file.
+    pub fn read_imports(&self) -> Result<Vec<ImportedSymbol>, LookupError> {
+        self.parsed(self.pelite_imports())
+    }
+
+    fn pelite_imports(&self) -> Result<Vec<ImportedSymbol>, LookupError> {
+        Ok(self.pelite_imports_lenient()?.0)
+    }
+
+    /// Read the entries of the import table, skipping the malformed ones (descriptors whose DLL
+    /// name cannot be read, names or thunks out of the file), which are described in the returned
+    /// issues
+    fn pelite_imports_lenient(&self) -> Result<(Vec<ImportedSymbol>, Vec<String>), LookupError> {
+        use pelite::pe32::imports::Import;
+
+        let imports = match self.pelite()?.imports() {
+            Ok(imports) => imports,
+            // there is no import directory
+            Err(pelite::Error::Null) => return Ok((Vec::new(), Vec::new())),
+            Err(e) => return Err(LookupError::PEError(e)),
+        };
+
+        let mut ret = Vec::new();
+        let mut issues = Vec::new();
+        for (index, desc) in imports.iter().enumerate() {
+            let dll = match desc.dll_name() {
+                Ok(name) => match name.to_str() {
+                    Ok(name) => name.to_owned(),
+                    Err(e) => {
+                        issues.push(format!("import descriptor {index}: {e}"));
+                        continue;
+                    }
+                },
+                Err(e) => {
+                    issues.push(format!("import descriptor {index}: {e}"));
+                    continue;
+                }
+            };
+            let image = desc.image();
+            let descriptor = ImportDescriptor {
+                characteristics: image.OriginalFirstThunk,
+                time_date_stamp: image.TimeDateStamp,
+                forwarder_chain: image.ForwarderChain,
+                first_thunk: image.FirstThunk,
+            };
+            let thunks = match desc.int() {
+                Ok(thunks) => thunks,
+                Err(e) => {
+                    issues.push(format!("imports from {dll}: {e}"));
+                    continue;
+                }
+            };
+            for import in thunks {
+                let (name, ordinal, hint) = match import {
+                    Ok(Import::ByName { hint, name }) => match name.to_str() {
+                        Ok(name) => (Some(name.to_owned()), None, Some(hint as u16)),
+                        Err(e) => {
+                            issues.push(format!("imports from {dll}: {e}"));
+                            continue;
+                        }
+                    },
+                    Ok(Import::ByOrdinal { ord }) => (None, Some(ord), None),
+                    Err(e) => {
+                        issues.push(format!("imports from {dll}: {e}"));
+                        continue;
+                    }
+                };
+                ret.push(ImportedSymbol {
+                    dll: dll.clone(),
+                    name,
+                    ordinal,
+                    hint,
+                    descriptor,
+                });
+            }
+        }
+        Ok((ret, issues))
+    }
+
     /// Get the list of symbols exported by this DLL
     pub fn read_exports(&self) -> Result<HashSet<String>, LookupError> {
-        // prefer goblin since it seems to be less fragile
-        if let Some(peo) = self.peobject.as_ref() {
-            return Ok(peo
-                .exports
-                .iter()
-                .map(|i| i.name.unwrap_or("<unnamed>").to_string())
-                .collect());
+        match self.goblin() {
+            Some(peo) => Ok(goblin_exports(peo)),
+            None => self.parsed(self.pelite_exports()),
         }
+    }
 
+    fn pelite_exports(&self) -> Result<HashSet<String>, LookupError> {
         // To query the exports
-        let exports = match self.pefile.unwrap().exports() {
+        let exports = match self.pelite()?.exports() {
             Ok(exports) => exports,
             // there is no export directory, e.g. in case of an executable
             Err(pelite::Error::Null) => return Ok(HashSet::new()),
@@ -161,15 +777,165 @@ impl<'a> PEFile<'a> {
         };
         let by = exports.by()?;
 
-        Ok(by
-            .iter_names()
-            .map(|(name, _)| name.unwrap().to_str().unwrap().to_owned())
-            .collect())
+        // unnamed exports (only exported by ordinal) are not listed, as by goblin_exports
+        by.iter_names()
+            .map(|(name, _)| Ok(name?.to_str()?.to_owned()))
+            .collect()
+    }
+}
+
+fn goblin_dependencies(peo: &goblin::pe::PE) -> Vec<String> {
+    peo.libraries.iter().map(|i| i.to_string()).collect()
+}
+
+/// Names of the exported symbols; the symbols only exported by ordinal have no name, and are not
+/// listed
+fn goblin_exports(peo: &goblin::pe::PE) -> HashSet<String> {
+    peo.exports
+        .iter()
+        .filter_map(|i| i.name.map(ToString::to_string))
+        .collect()
+}
+
+fn goblin_forwarded_dependencies(peo: &goblin::pe::PE) -> Vec<String> {
+    forwarded_dll_names(
+        peo.exports
+            .iter()
+            .filter_map(|e| match e.reexport {
+                Some(goblin::pe::export::Reexport::DLLName { lib, .. })
+                | Some(goblin::pe::export::Reexport::DLLOrdinal { lib, .. }) => {
+                    Some(lib.to_owned())
+                }
+                None => None,
+            })
+            .collect(),
+    )
+}
+
+/// Names of the imported symbols, by DLL; the symbols imported by ordinal are listed as an empty
+/// name, as by pelite_imported_names
+fn goblin_imported_names(peo: &goblin::pe::PE) -> HashMap<String, HashSet<String>> {
+    let imports: multimap::MultiMap<&str, &str> = peo
+        .imports
+        .iter()
+        // goblin names them "ORDINAL n", which cannot be the name of a symbol
+        .map(|i| {
+            if i.name.starts_with("ORDINAL ") {
+                (i.dll, "")
+            } else {
+                (i.dll, i.name.as_ref())
+            }
+        })
+        .collect();
+    imports
+        .iter_all()
+        .map(|(k, v)| (k.to_string(), v.iter().map(ToString::to_string).collect()))
+        .collect()
+}
+
+/// Names of the DLLs targeted by export forwarders, without duplicates and with the default
+/// extension
+fn forwarded_dll_names(forwards: Vec<String>) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    for lib in forwards {
+        let name = if lib.contains('.') { lib } else { lib + ".dll" };
+        if !names.iter().any(|n| n.eq_ignore_ascii_case(&name)) {
+            names.push(name);
+        }
+    }
+    names
+}
+
+/// Description of the differences between the entries read by goblin and by pelite, if any
+fn backend_differences(goblin: &HashSet<String>, pelite: &HashSet<String>) -> Option<String> {
+    let sorted = |names: &mut Vec<&String>| {
+        names.sort();
+        names
+            .iter()
+            .map(|n| n.as_str())
+            .collect::<Vec<_>>()
+            .join(", ")
+    };
+    let mut only_goblin: Vec<&String> = goblin.difference(pelite).collect();
+    let mut only_pelite: Vec<&String> = pelite.difference(goblin).collect();
+    if only_goblin.is_empty() && only_pelite.is_empty() {
+        return None;
+    }
+    let mut parts = Vec::new();
+    if !only_goblin.is_empty() {
+        parts.push(format!("only goblin read {}", sorted(&mut only_goblin)));
+    }
+    if !only_pelite.is_empty() {
+        parts.push(format!("only pelite read {}", sorted(&mut only_pelite)));
+    }
+    Some(parts.join("; "))
+}
+
+/// Extract the dependentAssembly entries of a manifest
+fn parse_manifest_dependencies(manifest: &str) -> Result<Vec<AssemblyDependency>, LookupError> {
+    let doc = roxmltree::Document::parse(manifest)
+        .map_err(|e| LookupError::ParseError(format!("Invalid manifest: {e}")))?;
+    let mut dependencies = Vec::new();
+    for identity in doc
+        .descendants()
+        .filter(|n| n.tag_name().name() == "dependentAssembly")
+        .filter_map(|n| {
+            n.children()
+                .find(|c| c.tag_name().name() == "assemblyIdentity")
+        })
+    {
+        if let Some(name) = identity.attribute("name") {
+            let dependency = AssemblyDependency {
+                name: name.to_owned(),
+                version: identity.attribute("version").map(str::to_owned),
+            };
+            if !dependencies.contains(&dependency) {
+                dependencies.push(dependency);
+            }
+        }
+    }
+    Ok(dependencies)
+}
+
+/// Name mangling scheme of a C++ symbol
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ManglingScheme {
+    /// Visual C++ (e.g. ?foo@@YAXXZ)
+    Msvc,
+    /// Itanium C++ ABI, used by GCC/MinGW and Clang (e.g. _Z3foov)
+    Itanium,
+}
+
+/// Itanium-mangled name, without the extra underscore prepended on 32-bit targets
+fn itanium_name(symbol: &str) -> Option<&str> {
+    if symbol.starts_with("_Z") {
+        Some(symbol)
+    } else {
+        symbol.strip_prefix('_').filter(|s| s.starts_with("_Z"))
+    }
+}
+
+/// Mangling scheme of the symbol, or None if it is not mangled (e.g. C functions)
+pub fn mangling_scheme(symbol: &str) -> Option<ManglingScheme> {
+    if symbol.starts_with('?') {
+        Some(ManglingScheme::Msvc)
+    } else if itanium_name(symbol).is_some() {
+        Some(ManglingScheme::Itanium)
+    } else {
+        None
     }
 }
 
 /// Get a humanly-readable version of the (imported or exported) symbol
+///
+/// Both the MSVC and the Itanium (MinGW) manglings are supported.
 pub fn demangle_symbol(symbol: &str) -> Result<String, LookupError> {
+    if let Some(name) = itanium_name(symbol) {
+        return cpp_demangle::Symbol::new(name)
+            .ok()
+            .and_then(|s| s.demangle(&cpp_demangle::DemangleOptions::default()).ok())
+            .ok_or_else(|| LookupError::DemanglingError(symbol.to_owned()));
+    }
     let flags =
         msvc_demangler::DemangleFlags::llvm() | msvc_demangler::DemangleFlags::NO_MS_KEYWORDS;
     msvc_demangler::demangle(symbol, flags)
@@ -179,7 +945,12 @@ pub fn demangle_symbol(symbol: &str) -> Result<String, LookupError> {
 #[cfg(test)]
 mod tests {
     use crate::common::LookupError;
-    use crate::pe::PEFile;
+    use crate::executable::{Architecture, AssemblyDependency, Subsystem};
+    use crate::pe::{
+        backend_differences, demangle_symbol, detect_file_format, dll_name_strings,
+        mangling_scheme, parse_manifest_dependencies, FileFormat, ManglingScheme, PEFile,
+        ParserBackend,
+    };
     use std::collections::HashSet;
     use std::fs;
 
@@ -230,6 +1001,8 @@ mod tests {
         .collect();
         let exe_deps: HashSet<String> = pefile.read_dependencies()?.into_iter().collect();
         assert_eq!(exe_deps, expected_exe_deps);
+        assert_eq!(pefile.read_architecture(), Some(Architecture::X64));
+        assert_eq!(pefile.read_subsystem(), Some(Subsystem::WindowsConsole));
 
         Ok(())
     }
@@ -256,6 +1029,303 @@ mod tests {
         let lib_deps: HashSet<String> = pefile.read_dependencies()?.into_iter().collect();
         assert_eq!(lib_deps, expected_lib_deps);
 
+        // the tables are still readable on demand without the upfront parse
+        let headers = PEFile::headers_only(&pefilemap)?;
+        assert_eq!(headers.read_dll_name()?, "DepRunTestLib.dll");
+        assert_eq!(headers.read_architecture(), pefile.read_architecture());
+        let lib_deps: HashSet<String> = headers.read_dependencies()?.into_iter().collect();
+        assert_eq!(lib_deps, expected_lib_deps);
+
+        // the same from a buffer, e.g. a file uploaded to a web page
+        let in_memory =
+            crate::pe::PEFileMap::from_bytes("DepRunTestLib.dll", pefilemap.content().to_vec());
+        let lib_deps: HashSet<String> = PEFile::new(&in_memory)?
+            .read_dependencies()?
+            .into_iter()
+            .collect();
+        assert_eq!(lib_deps, expected_lib_deps);
+
+        Ok(())
+    }
+
+    #[test]
+    fn parser_backends() -> Result<(), LookupError> {
+        let cargo_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let lib_path = cargo_dir.join(
+            "test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTestLib.dll",
+        );
+        let pefilemap = crate::pe::PEFileMap::new(lib_path)?;
+        let goblin = PEFile::with_backend(&pefilemap, ParserBackend::PreferGoblin)?;
+        let pelite = PEFile::with_backend(&pefilemap, ParserBackend::PreferPelite)?;
+        let as_set = |names: Vec<String>| names.into_iter().collect::<HashSet<_>>();
+        assert_eq!(
+            as_set(goblin.read_dependencies()?),
+            as_set(pelite.read_dependencies()?)
+        );
+        assert_eq!(goblin.read_exports()?, pelite.read_exports()?);
+        assert_eq!(goblin.read_imported_names()?, pelite.read_imported_names()?);
+
+        // the two libraries agree on the test files
+        let compared = PEFile::with_backend(&pefilemap, ParserBackend::Compare)?;
+        assert!(compared.diagnostics().is_empty());
+
+        let goblin_names: HashSet<String> = ["a.dll", "b.dll"].map(String::from).into();
+        let pelite_names: HashSet<String> = ["b.dll", "c.dll"].map(String::from).into();
+        assert_eq!(
+            backend_differences(&goblin_names, &pelite_names).as_deref(),
+            Some("only goblin read a.dll; only pelite read c.dll")
+        );
+        assert_eq!(backend_differences(&goblin_names, &goblin_names), None);
+        assert_eq!("compare".parse::<ParserBackend>()?, ParserBackend::Compare);
+        Ok(())
+    }
+
+    #[test]
+    fn import_table_entries() -> Result<(), LookupError> {
+        let cargo_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path = cargo_dir
+            .join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let pefilemap = crate::pe::PEFileMap::new(exe_path)?;
+        let pefile = PEFile::new(&pefilemap)?;
+
+        let imports = pefile.read_imports()?;
+        let from_lib: Vec<_> = imports
+            .iter()
+            .filter(|i| i.dll == "DepRunTestLib.dll")
+            .collect();
+        assert!(!from_lib.is_empty());
+        assert!(from_lib
+            .iter()
+            .all(|i| i.name.is_some() && i.hint.is_some() && !i.is_by_ordinal()));
+        assert!(from_lib
+            .iter()
+            .all(|i| i.descriptor == from_lib[0].descriptor && !i.descriptor.is_bound()));
+        assert_ne!(from_lib[0].descriptor.characteristics, 0);
+
+        // the names are the same as the ones read by goblin
+        let names = pefile.read_imported_names()?;
+        let lib_names: HashSet<String> = from_lib.iter().filter_map(|i| i.name.clone()).collect();
+        assert_eq!(names["DepRunTestLib.dll"], lib_names);
+
+        Ok(())
+    }
+
+    #[test]
+    fn backends_agree_on_ordinal_imports() -> Result<(), LookupError> {
+        let cargo_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path = cargo_dir
+            .join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let pefilemap = crate::pe::PEFileMap::new(&exe_path)?;
+        let pe = PEFile::new(&pefilemap)?.pelite()?;
+        assert!(matches!(pe.optional_header(), pelite::Wrap::T64(_)));
+        let first_thunk = pe
+            .imports()?
+            .iter()
+            .find(|desc| {
+                desc.dll_name()
+                    .is_ok_and(|name| name.to_str() == Ok("DepRunTestLib.dll"))
+            })
+            .map(|desc| desc.image().OriginalFirstThunk)
+            .unwrap();
+        let thunk_offset = pe.derva::<u64>(first_thunk)? as *const u64 as usize
+            - pefilemap.content().as_ptr() as usize;
+
+        // import the first symbol of the library by ordinal
+        let mut content = pefilemap.content().to_vec();
+        content[thunk_offset..thunk_offset + 8]
+            .copy_from_slice(&(0x8000_0000_0000_0000u64 | 7).to_le_bytes());
+        let patched = crate::pe::PEFileMap::from_bytes(&exe_path, content);
+        let goblin = PEFile::with_backend(&patched, ParserBackend::PreferGoblin)?;
+        let pelite = PEFile::with_backend(&patched, ParserBackend::PreferPelite)?;
+        let imported = goblin.read_imported_names()?;
+        assert!(imported["DepRunTestLib.dll"].contains(""));
+        assert_eq!(imported, pelite.read_imported_names()?);
+        let compared = PEFile::with_backend(&patched, ParserBackend::Compare)?;
+        assert!(compared.diagnostics().is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn malformed_import_descriptor_is_skipped() -> Result<(), LookupError> {
+        let cargo_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path = cargo_dir
+            .join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let pefilemap = crate::pe::PEFileMap::new(&exe_path)?;
+        let pefile = PEFile::new(&pefilemap)?;
+        let descriptor_offset = pefile
+            .pelite()?
+            .imports()?
+            .iter()
+            .find(|desc| {
+                desc.dll_name()
+                    .is_ok_and(|name| name.to_str() == Ok("DepRunTestLib.dll"))
+            })
+            .map(|desc| desc.image() as *const _ as usize - pefilemap.content().as_ptr() as usize)
+            .unwrap();
+        let dependencies = pefile.read_dependencies()?;
+
+        // point the name of the descriptor out of the file
+        let mut content = pefilemap.content().to_vec();
+        content[descriptor_offset + 12..descriptor_offset + 16]
+            .copy_from_slice(&0xFFFF_FFF0u32.to_le_bytes());
+        let corrupted = crate::pe::PEFileMap::from_bytes(&exe_path, content);
+        let pefile = PEFile::with_backend(&corrupted, ParserBackend::PreferPelite)?;
+
+        let imports = pefile.read_imports()?;
+        assert!(!imports.is_empty());
+        assert!(imports.iter().all(|i| i.dll != "DepRunTestLib.dll"));
+        assert_eq!(pefile.read_dependencies()?.len(), dependencies.len() - 1);
+        assert!(pefile
+            .diagnostics()
+            .iter()
+            .any(|d| d.kind == crate::common::DiagnosticKind::ParseError));
+
+        Ok(())
+    }
+
+    #[test]
+    fn diagnostics_for_non_pe_file() -> Result<(), LookupError> {
+        let temp_dir = tempfile::tempdir()?;
+        let path = temp_dir.path().join("not_a_pe.dll");
+        fs::write(&path, b"this is not a PE file")?;
+        let pefilemap = crate::pe::PEFileMap::new(&path)?;
+        let pefile = PEFile::new(&pefilemap)?;
+
+        assert!(!pefile.diagnostics().is_empty());
+        assert!(pefile
+            .diagnostics()
+            .iter()
+            .all(|d| d.path.as_deref() == Some(path.as_path())));
+        match pefile.read_dependencies() {
+            Err(LookupError::WrongFileFormat { path: p, detected }) => {
+                assert_eq!(p, path);
+                assert_eq!(detected, FileFormat::Text);
+            }
+            _ => panic!("a text file must be reported as such"),
+        }
+
+        Ok(())
+    }
+
+    #[test]
+    fn dynamic_load_candidates() -> Result<(), LookupError> {
+        assert_eq!(
+            dll_name_strings(b"\0plugin.dll\0C:\\Program Files\\App\\Core.DLL\0fmt_%s.dll\0x.dll"),
+            vec!["plugin.dll".to_owned(), "Core.DLL".to_owned()]
+        );
+
+        let cargo_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let lib_path = cargo_dir.join(
+            "test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTestLib.dll",
+        );
+        let pefilemap = crate::pe::PEFileMap::new(lib_path)?;
+        let pefile = PEFile::new(&pefilemap)?;
+
+        // the debug CRT loads these at runtime to report errors; imported DLLs are excluded
+        let candidates = pefile.read_dynamic_load_candidates()?;
+        assert!(candidates.iter().any(|c| c == "MSPDB140.DLL"));
+        assert!(candidates.iter().any(|c| c == "advapi32.dll"));
+        assert!(!candidates
+            .iter()
+            .any(|c| c.eq_ignore_ascii_case("KERNEL32.dll")));
+
+        Ok(())
+    }
+
+    #[test]
+    fn detect_non_pe_formats() -> Result<(), LookupError> {
+        assert_eq!(detect_file_format(b""), FileFormat::Empty);
+        assert_eq!(
+            detect_file_format(b"\xd0\xcf\x11\xe0\xa1\xb1\x1a\xe1\0\0"),
+            FileFormat::Msi
+        );
+        assert_eq!(
+            detect_file_format(b"#!/usr/bin/env python3\nprint()\n"),
+            FileFormat::Script {
+                interpreter: "/usr/bin/env python3".to_owned()
+            }
+        );
+        assert_eq!(detect_file_format(b"\x7fELF\x02\x01"), FileFormat::Elf);
+        assert_eq!(detect_file_format(b"\xcf\xfa\xed\xfe"), FileFormat::MachO);
+        assert_eq!(detect_file_format(b"PK\x03\x04\x14\0"), FileFormat::Zip);
+        assert_eq!(detect_file_format(b"\x00\x9f\x92\x96"), FileFormat::Unknown);
+
+        let cargo_dir = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let lib_path = cargo_dir.join(
+            "test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTestLib.dll",
+        );
+        let content = fs::read(lib_path)?;
+        assert_eq!(detect_file_format(&content[..200]), FileFormat::TruncatedPe);
+        assert_eq!(
+            detect_file_format(&content[..content.len() - 100]),
+            FileFormat::TruncatedPe
+        );
+        let mut dos_only = content[..0x40].to_vec();
+        dos_only.extend_from_slice(b"NE\0\0");
+        dos_only[0x3c..0x40].copy_from_slice(&0x40u32.to_le_bytes());
+        assert_eq!(detect_file_format(&dos_only), FileFormat::DosExecutable);
+        // offsets pointing past the end of the address space
+        dos_only[0x3c..0x40].copy_from_slice(&u32::MAX.to_le_bytes());
+        assert_eq!(detect_file_format(&dos_only), FileFormat::DosExecutable);
+
+        Ok(())
+    }
+
+    #[test]
+    fn manifest_dependencies() -> Result<(), LookupError> {
+        let manifest = r#"<?xml version='1.0' encoding='UTF-8' standalone='yes'?>
+<assembly xmlns='urn:schemas-microsoft-com:asm.v1' manifestVersion='1.0'>
+  <dependency>
+    <dependentAssembly>
+      <assemblyIdentity type='win32' name='Microsoft.VC90.CRT' version='9.0.21022.8' processorArchitecture='x86' publicKeyToken='1fc8b3b9a1e18e3b' />
+    </dependentAssembly>
+  </dependency>
+  <dependency>
+    <dependentAssembly>
+      <assemblyIdentity
+          type="win32"
+          name="Microsoft.Windows.Common-Controls"
+          version="6.0.0.0"
+          language="*"/>
+    </dependentAssembly>
+  </dependency>
+</assembly>"#;
+
+        assert_eq!(
+            parse_manifest_dependencies(manifest)?,
+            vec![
+                AssemblyDependency {
+                    name: "Microsoft.VC90.CRT".to_owned(),
+                    version: Some("9.0.21022.8".to_owned()),
+                },
+                AssemblyDependency {
+                    name: "Microsoft.Windows.Common-Controls".to_owned(),
+                    version: Some("6.0.0.0".to_owned()),
+                },
+            ]
+        );
+        assert!(parse_manifest_dependencies("<assembly></assembly>")?.is_empty());
+
+        Ok(())
+    }
+
+    #[test]
+    fn demangle_both_schemes() -> Result<(), LookupError> {
+        assert_eq!(mangling_scheme("?foo@@YAXXZ"), Some(ManglingScheme::Msvc));
+        assert_eq!(demangle_symbol("?foo@@YAXXZ")?, "void foo(void)");
+
+        assert_eq!(
+            mangling_scheme("_ZN3Foo3barEi"),
+            Some(ManglingScheme::Itanium)
+        );
+        assert_eq!(demangle_symbol("_ZN3Foo3barEi")?, "Foo::bar(int)");
+        // 32-bit MinGW prepends an underscore
+        assert_eq!(demangle_symbol("__ZN3Foo3barEi")?, "Foo::bar(int)");
+
+        assert_eq!(mangling_scheme("CreateFileW"), None);
+        assert!(demangle_symbol("CreateFileW").is_err());
+        assert!(demangle_symbol("_Zinvalid").is_err());
         Ok(())
     }
 }
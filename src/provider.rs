@@ -0,0 +1,184 @@
+//! Access to the files scanned during a lookup
+//!
+//! The directories of the lookup path are listed, and the executables found in them are read,
+//! through a `FileProvider`. By default this is the local file system, but other backends (e.g. an
+//! archive of a deployment, a disk image or a remote share) can be plugged into a `LookupPath` with
+//! `LookupPath::set_file_provider`, to scan artifacts without extracting them.
+
+use std::collections::{BTreeSet, HashMap};
+use std::ffi::OsString;
+use std::path::{Path, PathBuf};
+use std::time::SystemTime;
+
+use fs_err as fs;
+
+use crate::common::{extended_length_path, LookupError};
+use crate::executable::FileStamp;
+
+/// Backend providing the content of the directories and files looked up
+pub trait FileProvider: Send + Sync {
+    /// Names of the files (not subdirectories) contained in a directory
+    fn list_files(&self, dir: &Path) -> Result<Vec<OsString>, LookupError>;
+
+    /// Names of the subdirectories of a directory, to find the executables under it (see
+    /// `directory::find_executables_with_provider`)
+    ///
+    /// Backends without a hierarchy of directories keep the default.
+    fn list_subdirectories(&self, _dir: &Path) -> Result<Vec<OsString>, LookupError> {
+        Ok(Vec::new())
+    }
+
+    /// Whole content of a file
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>, LookupError>;
+
+    /// Size and modification time of a file, used to tell whether it changed since a previous scan
+    ///
+    /// Backends that cannot provide it leave the default, and their files are read again on every
+    /// rescan.
+    fn file_stamp(&self, path: &Path) -> Result<FileStamp, LookupError> {
+        Err(LookupError::ScanError(format!(
+            "no file stamp available for {}",
+            path.display()
+        )))
+    }
+
+    /// Modification time of a file or directory, used to tell whether the cached listing of a
+    /// directory is still valid (see `WinFileSystemCache::validating_modification_time`)
+    ///
+    /// Backends that cannot provide it return None, and their listings are never refreshed.
+    fn modified(&self, _path: &Path) -> Option<SystemTime> {
+        None
+    }
+}
+
+/// The local file system (the default provider)
+#[derive(Debug, Clone, Copy, Default)]
+pub struct LocalFileSystem;
+
+impl FileProvider for LocalFileSystem {
+    fn list_files(&self, dir: &Path) -> Result<Vec<OsString>, LookupError> {
+        Ok(fs::read_dir(extended_length_path(dir))?
+            .filter_map(|entry| entry.ok())
+            .filter(|entry| entry.metadata().map_or_else(|_| false, |m| m.is_file()))
+            .map(|entry| entry.file_name())
+            .collect())
+    }
+
+    fn list_subdirectories(&self, dir: &Path) -> Result<Vec<OsString>, LookupError> {
+        let mut subdirectories = Vec::new();
+        for entry in fs::read_dir(extended_length_path(dir))? {
+            let entry = entry?;
+            if entry.path().is_dir() {
+                subdirectories.push(entry.file_name());
+            }
+        }
+        Ok(subdirectories)
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>, LookupError> {
+        Ok(fs::read(extended_length_path(path))?)
+    }
+
+    fn file_stamp(&self, path: &Path) -> Result<FileStamp, LookupError> {
+        FileStamp::read(path)
+    }
+
+    fn modified(&self, path: &Path) -> Option<SystemTime> {
+        fs::metadata(extended_length_path(path))
+            .and_then(|m| m.modified())
+            .ok()
+    }
+}
+
+/// Files held in memory, e.g. extracted from an archive or uploaded to a web page
+///
+/// No file system access is needed to scan them, so that the library can analyze executables
+/// where there is none (e.g. compiled to WebAssembly); the lookups of the directories that are not
+/// the parent of any file find nothing.
+#[derive(Debug, Clone, Default)]
+pub struct MemoryFiles {
+    files: HashMap<PathBuf, Vec<u8>>,
+}
+
+impl MemoryFiles {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Add a file, replacing the one with the same path if any
+    pub fn insert<P: Into<PathBuf>>(&mut self, path: P, content: Vec<u8>) {
+        self.files.insert(path.into(), content);
+    }
+}
+
+impl FileProvider for MemoryFiles {
+    fn list_files(&self, dir: &Path) -> Result<Vec<OsString>, LookupError> {
+        Ok(self
+            .files
+            .keys()
+            .filter(|p| p.parent() == Some(dir))
+            .filter_map(|p| p.file_name().map(|n| n.to_os_string()))
+            .collect())
+    }
+
+    fn list_subdirectories(&self, dir: &Path) -> Result<Vec<OsString>, LookupError> {
+        let mut names = BTreeSet::new();
+        for path in self.files.keys() {
+            for ancestor in path.ancestors().skip(1) {
+                if let (Some(dir_name), true) =
+                    (ancestor.file_name(), ancestor.parent() == Some(dir))
+                {
+                    names.insert(dir_name.to_os_string());
+                }
+            }
+        }
+        Ok(names.into_iter().collect())
+    }
+
+    fn read_file(&self, path: &Path) -> Result<Vec<u8>, LookupError> {
+        self.files
+            .get(path)
+            .cloned()
+            .ok_or_else(|| LookupError::ScanError(format!("{} not found", path.display())))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+    use std::sync::Arc;
+
+    use fs_err as fs;
+
+    use crate::common::LookupError;
+    use crate::path::LookupPath;
+    use crate::provider::MemoryFiles;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+
+    #[test]
+    fn run_with_file_provider() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        let archive_dir = PathBuf::from("/archive/DepRunTest");
+        let mut files = MemoryFiles::new();
+        for f in ["DepRunTest.exe", "DepRunTestLib.dll"] {
+            files.insert(archive_dir.join(f), fs::read(exe_dir.join(f))?);
+        }
+
+        // no Windows installation, nor any other file system access
+        let query = LookupQuery::deduce_with_system(archive_dir.join("DepRunTest.exe"), None)?;
+        assert_eq!(query.target.app_dir, archive_dir);
+        let mut lookup_path = LookupPath::deduce(&query);
+        lookup_path.set_file_provider(Arc::new(files));
+
+        let res = run(&query, &lookup_path)?;
+        let lib = res.get("DepRunTestLib.dll").unwrap();
+        assert!(lib.found);
+        assert_eq!(
+            lib.details.as_ref().unwrap().full_path,
+            Some(archive_dir.join("DepRunTestLib.dll"))
+        );
+        Ok(())
+    }
+}
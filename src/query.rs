@@ -1,21 +1,92 @@
 //! Data structures that must be filled with the input and the parameters for the DLL scan
 
-use crate::common::LookupError;
+use crate::apiset::ApisetMap;
+use crate::appx::AppxManifest;
+use crate::common::{host_path, LookupError};
+use crate::environment::Environment;
+use crate::executable::{Architecture, AssemblyDependency};
+use crate::inventory::OsProfile;
+use crate::pe::{PEFile, PEFileMap, ParserBackend};
+use crate::registration::{
+    read_com_class, read_scheduled_task, read_service, RegisteredExecutable,
+};
+use crate::registry::Registry;
+use crate::shortcut::Shortcut;
 use crate::system::WindowsSystem;
-use crate::vcx::{VcxDebuggingConfiguration, VcxExecutableInformation};
+#[cfg(feature = "vcx")]
+use crate::vcx::{is_wow64_platform, VcxDebuggingConfiguration, VcxExecutableInformation};
 use fs_err as fs;
+use std::collections::HashMap;
 use std::path::{Path, PathBuf};
 
 #[derive(Clone, Debug)]
 pub struct LookupTarget {
     /// Path to the target executable
     pub target_exe: PathBuf,
+    /// Other executables scanned along with target_exe (e.g. its plugins), each one being a root
+    /// of the result of the scan (see `LookupQuery::add_target`)
+    pub additional_targets: Vec<PathBuf>,
     /// Parent directory of target_exe, cached for performance purposes
     pub app_dir: PathBuf,
     /// Working directory as it should appear in the search path
     pub working_dir: PathBuf,
     /// Additional executable search path set by the user
     pub user_path: Vec<PathBuf>,
+    /// Architecture the target runs as, read from its headers unless forced with
+    /// `LookupQuery::set_architecture`
+    pub architecture: Option<Architecture>,
+    /// Directories of the package graph, if the target is a packaged (MSIX/AppX) app (see
+    /// `update_from_appx_manifest`)
+    pub package_graph: Vec<PathBuf>,
+    /// Executable loading the target DLL, if it is not scanned on its own (see
+    /// `LookupQuery::with_host`)
+    pub host_exe: Option<PathBuf>,
+    /// Side-by-side assemblies requested by the manifest of host_exe, checked along with the ones
+    /// of the scanned executables
+    pub host_assemblies: Vec<AssemblyDependency>,
+}
+
+/// Order in which the dependency tree is visited
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum TraversalStrategy {
+    /// Level by level, so that each DLL is reported at the minimum depth it appears at
+    /// The DLLs at the same level are processed in parallel
+    #[default]
+    BreadthFirst,
+    /// Following the import tables in order, as the loader does (single-threaded)
+    DepthFirst,
+}
+
+/// What a scan does when two different files have the same DLL name, e.g. in different
+/// directories of a multi-target scan
+///
+/// Except with `FirstWins`, every target is scanned from its own path, and the dependencies with
+/// the name of a target are still looked up across the lookup path.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum DuplicatePolicy {
+    /// Keep the first file found and report the other one as a diagnostic, as the loader loads a
+    /// DLL name only once per process
+    #[default]
+    FirstWins,
+    /// Fail the scan
+    Error,
+    /// Keep all the files (see `Executables::get_all`), reporting them as a diagnostic
+    KeepAll,
+}
+
+impl std::str::FromStr for DuplicatePolicy {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "first-wins" => Ok(Self::FirstWins),
+            "error" => Ok(Self::Error),
+            "keep-all" => Ok(Self::KeepAll),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown duplicate policy {s}, expected first-wins, error or keep-all"
+            ))),
+        }
+    }
 }
 
 #[derive(Clone, Debug)]
@@ -26,6 +97,61 @@ pub struct LookupParameters {
     pub skip_system_dlls: bool,
     /// Extract symbols from found DLLs
     pub extract_symbols: bool,
+    /// Compute the SHA-256 and MD5 hashes of found files
+    pub compute_hashes: bool,
+    /// Order in which the dependency tree is visited
+    pub traversal: TraversalStrategy,
+    /// Patterns of DLL names (e.g. "nv*.dll") whose dependencies should not be scanned
+    pub prune_below: Vec<String>,
+    /// Patterns of DLL names that should not be looked up at all
+    pub skip_dlls: Vec<String>,
+    /// If not empty, only the dependencies of DLLs matching one of these patterns are scanned
+    /// (the dependencies of the target executable are always scanned)
+    pub only_descend_into: Vec<String>,
+    /// Also scan the dependencies of the DLLs found in system directories (by default the scan
+    /// stops at them)
+    pub scan_system_dlls: bool,
+    /// Record the entries of the lookup path probed for each DLL, and what was found in each one
+    pub record_probes: bool,
+    /// Also follow the DLL names found among the strings of the scanned files, as possible
+    /// dynamic loads (heuristic)
+    pub detect_dynamic_loads: bool,
+    /// Bound the memory used by very large scans: the files are read and parsed one at a time,
+    /// and their exports are read again once the tree is complete, keeping only the ones imported
+    /// by the scanned executables
+    pub low_memory: bool,
+    /// Which of goblin and pelite reads the tables of the files, or whether both are compared
+    pub parser_backend: ParserBackend,
+    /// What to do when two different files have the same DLL name
+    pub duplicate_policy: DuplicatePolicy,
+}
+
+/// Resolution of a DLL decided by the user, consulted before the lookup path
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum DllOverride {
+    /// the DLL is loaded from this file, e.g. from a custom location at runtime
+    Path(PathBuf),
+    /// the DLL is considered found without being scanned, e.g. if it is installed by a
+    /// prerequisite installer
+    AssumePresent,
+    /// the DLL is considered missing, wherever it could be found
+    AssumeMissing,
+}
+
+impl std::str::FromStr for DllOverride {
+    type Err = LookupError;
+
+    /// Parse "present", "missing" or a file path
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "" => Err(LookupError::ParseError(
+                "empty DLL override, expected present, missing or a path".to_owned(),
+            )),
+            "present" => Ok(Self::AssumePresent),
+            "missing" => Ok(Self::AssumeMissing),
+            _ => Ok(Self::Path(PathBuf::from(s))),
+        }
+    }
 }
 
 /// Complete specification of a search task
@@ -34,9 +160,30 @@ pub struct LookupQuery {
     pub system: Option<WindowsSystem>,
     pub target: LookupTarget,
     pub parameters: LookupParameters,
+    /// API set map to use if none can be read from the system (e.g. no Windows partition available)
+    pub fallback_apiset_map: Option<ApisetMap>,
+    /// Resolutions forced by the user, by lowercase DLL name (see `override_dll`)
+    pub dll_overrides: HashMap<String, DllOverride>,
+    /// Environment of the simulated process, if not the one of the current shell (see
+    /// `with_environment`)
+    pub environment: Option<Environment>,
+    /// Windows version the target is meant to run on: the DLLs it is guaranteed to have are
+    /// considered present if they are not found along the lookup path
+    pub target_os: Option<OsProfile>,
 }
 
 impl LookupQuery {
+    /// Force the resolution of a DLL, regardless of the lookup path
+    pub fn override_dll(&mut self, dllname: &str, dll_override: DllOverride) {
+        self.dll_overrides
+            .insert(dllname.to_lowercase(), dll_override);
+    }
+
+    /// Resolution forced by the user for the given DLL name (case-insensitive), if any
+    pub fn dll_override(&self, dllname: &str) -> Option<&DllOverride> {
+        self.dll_overrides.get(&dllname.to_lowercase())
+    }
+
     /// autodetects the settings with sensible defaults
     ///
     /// The working directory will be set to the one containing the executable (i.e. the app_dir)
@@ -44,27 +191,7 @@ impl LookupQuery {
     pub fn deduce_from_executable_location<P: AsRef<Path>>(
         target_exe: P,
     ) -> Result<Self, LookupError> {
-        let app_dir = target_exe
-            .as_ref()
-            .parent()
-            .ok_or(LookupError::ContextDeductionError(
-                "Could not find application directory for given executable ".to_owned()
-                    + target_exe.as_ref().to_str().unwrap_or("---"),
-            ))?;
-        Ok(Self {
-            system: Some(WindowsSystem::current()?),
-            target: LookupTarget {
-                user_path: vec![],
-                target_exe: target_exe.as_ref().into(),
-                app_dir: app_dir.canonicalize()?,
-                working_dir: app_dir.canonicalize()?,
-            },
-            parameters: LookupParameters {
-                max_depth: None,
-                skip_system_dlls: false,
-                extract_symbols: false,
-            },
-        })
+        Self::deduce_with_system(target_exe, Some(WindowsSystem::current()?))
     }
 
     /// autodetects the settings with sensible defaults
@@ -73,6 +200,16 @@ impl LookupQuery {
     #[cfg(not(windows))]
     pub fn deduce_from_executable_location<P: AsRef<Path>>(
         target_exe: P,
+    ) -> Result<Self, LookupError> {
+        let system = WindowsSystem::from_exe_location(&target_exe)?;
+        Self::deduce_with_system(target_exe, system)
+    }
+
+    /// same as `deduce_from_executable_location`, with an already detected Windows installation
+    /// (e.g. the one shared by the scans of a `session::ScanSession`)
+    pub fn deduce_with_system<P: AsRef<Path>>(
+        target_exe: P,
+        system: Option<WindowsSystem>,
     ) -> Result<Self, LookupError> {
         let app_dir = target_exe.as_ref().parent().ok_or_else(|| {
             LookupError::ContextDeductionError(
@@ -80,25 +217,172 @@ impl LookupQuery {
                     + target_exe.as_ref().to_str().unwrap_or(""),
             )
         })?;
-        Ok(Self {
-            system: WindowsSystem::from_exe_location(&target_exe)?,
+        #[cfg(windows)]
+        let app_dir = app_dir.canonicalize()?;
+        #[cfg(not(windows))]
+        let app_dir = app_dir.to_owned();
+        let mut query = Self {
+            system,
             target: LookupTarget {
+                package_graph: Vec::new(),
+                host_exe: None,
+                host_assemblies: Vec::new(),
+                additional_targets: Vec::new(),
+                architecture: None,
                 user_path: Vec::new(),
                 target_exe: target_exe.as_ref().to_owned(),
-                app_dir: app_dir.to_owned(),
-                working_dir: app_dir.to_owned(),
+                app_dir: app_dir.clone(),
+                working_dir: app_dir,
             },
             parameters: LookupParameters {
                 max_depth: None,
                 skip_system_dlls: false,
                 extract_symbols: false,
+                compute_hashes: false,
+                traversal: TraversalStrategy::BreadthFirst,
+                prune_below: Vec::new(),
+                skip_dlls: Vec::new(),
+                only_descend_into: Vec::new(),
+                scan_system_dlls: false,
+                record_probes: false,
+                detect_dynamic_loads: false,
+                low_memory: false,
+                parser_backend: ParserBackend::default(),
+                duplicate_policy: DuplicatePolicy::default(),
             },
-        })
+            fallback_apiset_map: None,
+            dll_overrides: HashMap::new(),
+            environment: None,
+            target_os: None,
+        };
+        query.detect_architecture();
+        Ok(query)
+    }
+
+    /// use the given environment for the simulated process, instead of the one of the current
+    /// shell
+    ///
+    /// Its PATH, with the variables expanded from the environment itself, replaces the PATH of the
+    /// system (or is added to the user path, if there is no Windows installation). On Linux and
+    /// macOS its entries are looked up under the Windows partition (see `common::host_path`).
+    pub fn with_environment<E: Into<Environment>>(mut self, environment: E) -> Self {
+        let environment = environment.into();
+        let anchor = self
+            .system
+            .as_ref()
+            .and_then(|s| s.win_dir.parent())
+            .unwrap_or(&self.target.app_dir)
+            .to_owned();
+        let path: Vec<PathBuf> = environment
+            .path()
+            .iter()
+            .map(|entry| host_path(entry, &anchor))
+            .collect();
+        match self.system.as_mut() {
+            Some(system) => system.system_path = Some(path),
+            None => self.target.user_path.extend(path),
+        }
+        self.environment = Some(environment);
+        self
+    }
+
+    /// force the architecture the target runs as, which determines the system directory, the
+    /// KnownDLLs and the API set schema used (see `WindowsSystem::pivot_to_architecture`)
+    pub fn set_architecture(&mut self, architecture: Architecture) {
+        self.target.architecture = Some(architecture);
+        if let Some(system) = self.system.as_mut() {
+            system.pivot_to_architecture(architecture);
+        }
+    }
+
+    /// read the architecture of the target from its headers, and use it (if it could be read)
+    fn detect_architecture(&mut self) {
+        if let Some(architecture) = read_architecture(&self.target.target_exe) {
+            self.set_architecture(architecture);
+        }
+    }
+
+    /// scan the target DLL as loaded by the given host executable
+    ///
+    /// The directory of the host is looked up instead of the one of the DLL, and is the working
+    /// directory; the architecture is the one of the host. The DLL itself is loaded from its path,
+    /// even if a file with the same name is found across the lookup path. With
+    /// `altered_search_path` (`LoadLibraryEx` with `LOAD_WITH_ALTERED_SEARCH_PATH`), the directory
+    /// of the DLL is looked up instead of the one of the host.
+    ///
+    /// Side-by-side assemblies are not resolved by the scan, so the activation context of the
+    /// host is not simulated; the versions requested by the host are checked against the ones of
+    /// the scanned executables (see `Executables::check`).
+    pub fn with_host<P: AsRef<Path>>(
+        mut self,
+        host_exe: P,
+        altered_search_path: bool,
+    ) -> Result<Self, LookupError> {
+        let host_exe = fs::canonicalize(host_exe.as_ref())?;
+        let host_dir = host_exe.parent().ok_or_else(|| {
+            LookupError::ContextDeductionError(
+                "Could not find application directory for given executable ".to_owned()
+                    + host_exe.to_str().unwrap_or(""),
+            )
+        })?;
+        if !altered_search_path {
+            self.target.app_dir = host_dir.to_owned();
+        }
+        self.target.working_dir = host_dir.to_owned();
+        if let Ok(filemap) = PEFileMap::new(&host_exe) {
+            if let Ok(pefile) = PEFile::new(&filemap) {
+                if let Some(architecture) = pefile.read_architecture() {
+                    self.set_architecture(architecture);
+                }
+                self.target.host_assemblies =
+                    pefile.read_assembly_dependencies().unwrap_or_default();
+            }
+        }
+        let target_exe = self.target.target_exe.clone();
+        if let Some(dllname) = target_exe.file_name() {
+            let dllname = dllname.to_string_lossy();
+            self.override_dll(&dllname, DllOverride::Path(target_exe.clone()));
+        }
+        self.target.host_exe = Some(host_exe);
+        Ok(self)
+    }
+
+    /// add an executable to be scanned along with the target, sharing the lookup path and the
+    /// dependencies found; like the target, it is loaded from the given path
+    pub fn add_target<P: AsRef<Path>>(&mut self, exe: P) {
+        self.target.additional_targets.push(exe.as_ref().to_owned());
+    }
+
+    /// All the executables to be scanned: the target, then the additional ones
+    pub fn targets(&self) -> impl Iterator<Item = &Path> {
+        std::iter::once(self.target.target_exe.as_path())
+            .chain(self.target.additional_targets.iter().map(PathBuf::as_path))
+    }
+
+    /// update this Query for a packaged app, whose DLLs are looked up in its package graph
+    ///
+    /// The packages the app depends on are looked up next to its own package and in the
+    /// WindowsApps directory of the Windows installation; the names of the ones that were not
+    /// found are returned.
+    pub fn update_from_appx_manifest(&mut self, manifest: &AppxManifest) -> Vec<String> {
+        let mut package_roots: Vec<PathBuf> = manifest
+            .package_dir
+            .parent()
+            .into_iter()
+            .map(Path::to_path_buf)
+            .collect();
+        if let Some(root) = self.system.as_ref().and_then(|s| s.win_dir.parent()) {
+            package_roots.push(root.join("Program Files").join("WindowsApps"));
+        }
+        let (package_graph, missing) = manifest.package_graph(&package_roots);
+        self.target.package_graph = package_graph;
+        missing
     }
 
     /// update this Query with the information contained in a .vcxproj.user file
     ///
     /// Will set the working directory and the PATH to the ones specified in the file
+    #[cfg(feature = "vcx")]
     pub fn update_from_vcx_debugging_configuration(
         &mut self,
         debugging_configuration: &VcxDebuggingConfiguration,
@@ -114,7 +398,9 @@ impl LookupQuery {
     /// create a Query with the information contained in a .vcxproj file
     ///
     /// Will extract the executable location from the file
+    /// For 32-bit platforms (Win32), the system directory is SysWOW64 if the installation has one
     /// If the respective .vcxproj.user file is found, the contained information will be used
+    #[cfg(feature = "vcx")]
     pub fn read_from_vcx_executable_information(
         exe_info: &VcxExecutableInformation,
     ) -> Result<Self, LookupError> {
@@ -135,6 +421,11 @@ impl LookupQuery {
         let mut ret = Self {
             system,
             target: LookupTarget {
+                package_graph: Vec::new(),
+                host_exe: None,
+                host_assemblies: Vec::new(),
+                additional_targets: Vec::new(),
+                architecture: None,
                 user_path: Vec::new(),
                 target_exe: exe_path.to_owned(),
                 app_dir: app_dir.to_owned(),
@@ -144,21 +435,159 @@ impl LookupQuery {
                 max_depth: None,
                 skip_system_dlls: false,
                 extract_symbols: false,
+                compute_hashes: false,
+                traversal: TraversalStrategy::BreadthFirst,
+                prune_below: Vec::new(),
+                skip_dlls: Vec::new(),
+                only_descend_into: Vec::new(),
+                scan_system_dlls: false,
+                record_probes: false,
+                detect_dynamic_loads: false,
+                low_memory: false,
+                parser_backend: ParserBackend::default(),
+                duplicate_policy: DuplicatePolicy::default(),
             },
+            fallback_apiset_map: None,
+            dll_overrides: HashMap::new(),
+            environment: None,
+            target_os: None,
         };
 
+        // 32-bit executables see SysWOW64 as their system directory
+        if exe_info.platform.as_deref().is_some_and(is_wow64_platform) {
+            ret.set_architecture(Architecture::X86);
+        } else {
+            ret.detect_architecture();
+        }
+
         if let Some(debugging_config) = &exe_info.debugging_configuration {
             ret.update_from_vcx_debugging_configuration(debugging_config);
         }
 
         Ok(ret)
     }
+
+    /// create a Query for an executable registered in the given Windows installation
+    ///
+    /// The working directory and the PATH are the registered ones, if any
+    pub fn read_from_registered_executable(
+        registered: &RegisteredExecutable,
+        system: WindowsSystem,
+    ) -> Result<Self, LookupError> {
+        let exe_path = fs::canonicalize(&registered.executable_path)?;
+        let app_dir = exe_path.parent().ok_or_else(|| {
+            LookupError::ContextDeductionError(
+                "Could not find application directory for given executable ".to_owned()
+                    + exe_path.to_str().unwrap_or(""),
+            )
+        })?;
+        let mut query = Self {
+            system: Some(system),
+            target: LookupTarget {
+                package_graph: Vec::new(),
+                host_exe: None,
+                host_assemblies: Vec::new(),
+                additional_targets: Vec::new(),
+                architecture: None,
+                user_path: registered.path.clone(),
+                target_exe: exe_path.to_owned(),
+                app_dir: app_dir.to_owned(),
+                working_dir: registered
+                    .working_directory
+                    .clone()
+                    .unwrap_or_else(|| app_dir.to_owned()),
+            },
+            parameters: LookupParameters {
+                max_depth: None,
+                skip_system_dlls: false,
+                extract_symbols: false,
+                compute_hashes: false,
+                traversal: TraversalStrategy::BreadthFirst,
+                prune_below: Vec::new(),
+                skip_dlls: Vec::new(),
+                only_descend_into: Vec::new(),
+                scan_system_dlls: false,
+                record_probes: false,
+                detect_dynamic_loads: false,
+                low_memory: false,
+                parser_backend: ParserBackend::default(),
+                duplicate_policy: DuplicatePolicy::default(),
+            },
+            fallback_apiset_map: None,
+            dll_overrides: HashMap::new(),
+            environment: None,
+            target_os: None,
+        };
+        if registered.wow64 {
+            query.set_architecture(Architecture::X86);
+        } else {
+            query.detect_architecture();
+        }
+        Ok(query)
+    }
+
+    /// create a Query for the target of a Windows shortcut (.lnk file)
+    ///
+    /// The working directory is the one set in the shortcut, if any
+    pub fn from_shortcut<P: AsRef<Path>>(shortcut_path: P) -> Result<Self, LookupError> {
+        let shortcut_path = shortcut_path.as_ref();
+        let shortcut = Shortcut::read(shortcut_path)?;
+        let target = shortcut
+            .target_on_host(shortcut_path)
+            .filter(|t| t.is_file())
+            .ok_or_else(|| {
+                LookupError::ContextDeductionError(format!(
+                    "The target of the shortcut {} was not found",
+                    shortcut_path.display()
+                ))
+            })?;
+        let mut query = Self::deduce_from_executable_location(fs::canonicalize(target)?)?;
+        if let Some(working_dir) = shortcut.working_directory_on_host(shortcut_path) {
+            query.target.working_dir = working_dir;
+        }
+        Ok(query)
+    }
+
+    /// create a Query for the executable (or service DLL) started by a Windows service
+    pub fn from_service(
+        name: &str,
+        registry: &Registry,
+        system: WindowsSystem,
+    ) -> Result<Self, LookupError> {
+        let registered = read_service(name, registry, &system)?;
+        Self::read_from_registered_executable(&registered, system)
+    }
+
+    /// create a Query for the executable started by a scheduled task (name or XML definition)
+    pub fn from_scheduled_task(task: &str, system: WindowsSystem) -> Result<Self, LookupError> {
+        let registered = read_scheduled_task(task, &system)?;
+        Self::read_from_registered_executable(&registered, system)
+    }
+
+    /// create a Query for the server of a COM class
+    pub fn from_com_class(
+        clsid: &str,
+        registry: &Registry,
+        system: WindowsSystem,
+    ) -> Result<Self, LookupError> {
+        let registered = read_com_class(clsid, registry, &system)?;
+        Self::read_from_registered_executable(&registered, system)
+    }
+}
+
+/// Architecture read from the headers of the executable, if it could be read
+fn read_architecture(exe: &Path) -> Option<Architecture> {
+    PEFileMap::new(exe)
+        .ok()
+        .and_then(|filemap| PEFile::new(&filemap).ok()?.read_architecture())
 }
 
 #[cfg(test)]
 mod tests {
     use crate::common::LookupError;
+    use crate::path::LookupPath;
     use crate::query::LookupQuery;
+    use crate::runner::run;
     use fs_err as fs;
 
     #[test]
@@ -188,4 +617,38 @@ mod tests {
 
         Ok(())
     }
+
+    #[test]
+    fn scan_dll_in_host() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let host_exe =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let host_dir = fs::canonicalize(host_exe.parent().unwrap())?;
+        let dll_path =
+            d.join("test_data/test_project1/DepRunTestLibWrong/build/Debug/DepRunTestLib.dll");
+
+        let query =
+            LookupQuery::deduce_from_executable_location(&dll_path)?.with_host(&host_exe, false)?;
+        assert_eq!(query.target.app_dir, host_dir);
+        assert_eq!(query.target.working_dir, host_dir);
+        assert_eq!(
+            query.target.host_exe.as_deref(),
+            Some(host_dir.join("DepRunTest.exe").as_path())
+        );
+        // the host directory has another DepRunTestLib.dll, but the target is loaded from its path
+        let lookup_path = LookupPath::deduce(&query);
+        assert_eq!(lookup_path.search_path()[0], host_dir);
+        let executables = run(&query, &lookup_path)?;
+        let target = executables.get("DepRunTestLib.dll").unwrap();
+        assert_eq!(
+            target.details.as_ref().unwrap().full_path.as_ref(),
+            Some(&dll_path)
+        );
+
+        let query =
+            LookupQuery::deduce_from_executable_location(&dll_path)?.with_host(&host_exe, true)?;
+        assert_eq!(query.target.app_dir, dll_path.parent().unwrap());
+        assert_eq!(query.target.working_dir, host_dir);
+        Ok(())
+    }
 }
@@ -0,0 +1,158 @@
+//! Visual C++ Redistributable packages, to tell how to install the missing runtime DLLs
+//!
+//! The Visual C++ runtimes (msvcr*, msvcp*, vcruntime*, the MFC, OpenMP and concurrency runtimes)
+//! and the Universal CRT are installed by the Redistributable package of the version of Visual
+//! Studio that built the importer, for its architecture.
+
+use serde::Serialize;
+
+use crate::executable::Architecture;
+
+/// Page listing the Redistributable packages of all Visual C++ versions
+const REDIST_DOWNLOADS: &str = "https://learn.microsoft.com/cpp/windows/latest-supported-vc-redist";
+
+/// Package installing a Visual C++ runtime DLL
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct Redistributable {
+    /// e.g. Microsoft Visual C++ 2015-2022 Redistributable (x64)
+    pub name: String,
+    pub url: String,
+}
+
+impl std::fmt::Display for Redistributable {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{} ({})", self.name, self.url)
+    }
+}
+
+/// Visual C++ runtime DLL, recognized by its name
+struct VcRuntimeDll {
+    /// e.g. 140 for vcruntime140.dll
+    generation: String,
+    debug: bool,
+}
+
+impl VcRuntimeDll {
+    fn parse(dllname: &str) -> Option<Self> {
+        // e.g. msvcr120.dll, msvcp140_1d.dll, vcruntime140_1.dll, mfc140u.dll, vcomp140d.dll
+        let vc_runtime = regex::Regex::new(
+            r"^(?:msvcr|msvcp|vcruntime|concrt|vccorlib|vcomp|vcamp|mfcm|mfc)(\d{2,3})(u?)(d?)(?:_[a-z0-9_]+?)?(d?)\.dll$",
+        )
+        .expect("valid regex");
+        let name = dllname.to_lowercase();
+        let captures = vc_runtime.captures(&name)?;
+        Some(Self {
+            generation: captures[1].to_owned(),
+            debug: !captures[3].is_empty() || !captures[4].is_empty(),
+        })
+    }
+}
+
+/// Version of Visual Studio (as in the name of its Redistributable) shipping a runtime generation
+fn visual_studio_version(generation: &str) -> Option<&'static str> {
+    match generation {
+        "80" => Some("2005"),
+        "90" => Some("2008"),
+        "100" => Some("2010"),
+        "110" => Some("2012"),
+        "120" => Some("2013"),
+        "140" => Some("2015-2022"),
+        _ => None,
+    }
+}
+
+fn package_architecture(architecture: Option<Architecture>) -> &'static str {
+    match architecture {
+        Some(Architecture::X86) => "x86",
+        Some(Architecture::Arm64) => "ARM64",
+        _ => "x64",
+    }
+}
+
+/// Redistributable package installing the given Visual C++ runtime or Universal CRT DLL, for an
+/// importer of the given architecture (x64 if unknown)
+///
+/// Debug runtimes are not redistributable (see `is_debug_runtime`), and yield None.
+pub fn redistributable_for(
+    dllname: &str,
+    architecture: Option<Architecture>,
+) -> Option<Redistributable> {
+    let package_architecture = package_architecture(architecture);
+    let latest = |name: String| {
+        let url = format!(
+            "https://aka.ms/vs/17/release/vc_redist.{}.exe",
+            package_architecture.to_lowercase()
+        );
+        Redistributable { name, url }
+    };
+    let name = dllname.to_lowercase();
+    // the Universal CRT is part of Windows 10, and installed by the latest package before
+    if name == "ucrtbase.dll" || name.starts_with("api-ms-win-crt-") {
+        return Some(latest(format!(
+            "Microsoft Visual C++ 2015-2022 Redistributable ({package_architecture}), or Windows 10"
+        )));
+    }
+    let runtime = VcRuntimeDll::parse(&name).filter(|r| !r.debug)?;
+    let version = visual_studio_version(&runtime.generation)?;
+    let name = format!("Microsoft Visual C++ {version} Redistributable ({package_architecture})");
+    Some(if runtime.generation == "140" {
+        latest(name)
+    } else {
+        Redistributable {
+            name,
+            url: REDIST_DOWNLOADS.to_owned(),
+        }
+    })
+}
+
+/// Whether the DLL is a debug build of a Visual C++ runtime or of the Universal CRT, which are
+/// only installed with Visual Studio and cannot be redistributed
+pub fn is_debug_runtime(dllname: &str) -> bool {
+    dllname.eq_ignore_ascii_case("ucrtbased.dll")
+        || VcRuntimeDll::parse(dllname).is_some_and(|r| r.debug)
+}
+
+/// How to install a missing runtime DLL, if it is one, to be added to the missing DLL findings
+pub fn runtime_suggestion(dllname: &str, architecture: Option<Architecture>) -> Option<String> {
+    if is_debug_runtime(dllname) {
+        return Some(
+            "debug runtime, only installed with Visual Studio: deploy a release build".to_owned(),
+        );
+    }
+    redistributable_for(dllname, architecture).map(|r| format!("provided by the {r}"))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::executable::Architecture;
+    use crate::redist::{is_debug_runtime, redistributable_for, runtime_suggestion};
+
+    #[test]
+    fn redistributables() {
+        let vcruntime = redistributable_for("VCRUNTIME140_1.dll", Some(Architecture::X86)).unwrap();
+        assert_eq!(
+            vcruntime.name,
+            "Microsoft Visual C++ 2015-2022 Redistributable (x86)"
+        );
+        assert_eq!(
+            vcruntime.url,
+            "https://aka.ms/vs/17/release/vc_redist.x86.exe"
+        );
+        assert_eq!(
+            redistributable_for("msvcr120.dll", None).unwrap().name,
+            "Microsoft Visual C++ 2013 Redistributable (x64)"
+        );
+        assert!(redistributable_for("mfc140u.dll", Some(Architecture::Arm64)).is_some());
+        assert!(redistributable_for("api-ms-win-crt-runtime-l1-1-0.dll", None).is_some());
+        assert!(redistributable_for("kernel32.dll", None).is_none());
+
+        assert!(is_debug_runtime("MSVCP140D.dll"));
+        assert!(is_debug_runtime("vcruntime140_1d.dll"));
+        assert!(is_debug_runtime("ucrtbased.dll"));
+        assert!(!is_debug_runtime("msvcp140.dll"));
+        assert!(redistributable_for("msvcp140d.dll", None).is_none());
+        assert!(runtime_suggestion("msvcp140d.dll", None)
+            .unwrap()
+            .starts_with("debug runtime"));
+    }
+}
@@ -0,0 +1,424 @@
+//! Executables that a Windows installation starts on its own: services, scheduled tasks and COM
+//! servers
+//!
+//! Their command line, working directory and environment are read from the registry (live or
+//! offline, see the registry module) or from the task definitions, so that the scan sees what the
+//! system will actually load.
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use regex::Regex;
+
+use crate::common::{host_path, path_to_string, LookupError};
+use crate::registry::{Registry, RegistryRoot, RegistryValue};
+use crate::system::WindowsSystem;
+
+/// Executable started by the system, as registered
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub struct RegisteredExecutable {
+    /// Path of the executable or DLL (for services hosted by svchost.exe and in-process COM
+    /// servers) on this host
+    pub executable_path: PathBuf,
+    /// Arguments of the command line, if any
+    pub arguments: Option<String>,
+    /// Working directory the executable is started in, if known
+    pub working_directory: Option<PathBuf>,
+    /// PATH entries set by the registration (e.g. in the environment of a service)
+    pub path: Vec<PathBuf>,
+    /// Registered for 32-bit processes (under WOW6432Node)
+    pub wow64: bool,
+}
+
+/// Default value of the environment variables used in registrations, for offline installations
+pub(crate) fn default_environment_variable(name: &str) -> Option<&'static str> {
+    Some(match name.to_ascii_lowercase().as_str() {
+        "systemroot" | "windir" => r"C:\Windows",
+        "systemdrive" => "C:",
+        "programfiles" | "programw6432" => r"C:\Program Files",
+        "programfiles(x86)" => r"C:\Program Files (x86)",
+        "programdata" | "allusersprofile" => r"C:\ProgramData",
+        "commonprogramfiles" => r"C:\Program Files\Common Files",
+        _ => return None,
+    })
+}
+
+/// Replace the references to environment variables (e.g. %ProgramFiles%) with their value, if
+/// known, as in ExpandEnvironmentStrings: the values are not expanded in turn, and unknown
+/// variables are left as they are
+pub(crate) fn expand_variables<F: Fn(&str) -> Option<String>>(s: &str, value: F) -> String {
+    let variable = Regex::new(r"%([^%]+)%").expect("valid regex");
+    variable
+        .replace_all(s, |captures: &regex::Captures| {
+            value(&captures[1]).unwrap_or_else(|| captures[0].to_owned())
+        })
+        .into_owned()
+}
+
+/// Value of the standard environment variables in the given Windows installation: its Windows
+/// directory, and the default system directories on its partition
+fn system_environment_variable(name: &str, system: &WindowsSystem) -> Option<String> {
+    if ["systemroot", "windir"].contains(&name.to_ascii_lowercase().as_str()) {
+        return Some(path_to_string(&system.win_dir));
+    }
+    let root = system.win_dir.parent().unwrap_or(&system.win_dir);
+    let rest = default_environment_variable(name)?.strip_prefix("C:")?;
+    Some(format!(
+        "{}{rest}",
+        path_to_string(root).trim_end_matches(['\\', '/'])
+    ))
+}
+
+/// Replace the references to environment variables (e.g. %SystemRoot%), and turn the NT paths used
+/// by services (e.g. \SystemRoot\System32\...) into Windows paths
+///
+/// The standard system directories are the ones of the given Windows installation (on this host,
+/// e.g. under its mount point), if any. Otherwise, on Windows the variables of the current
+/// environment are used; elsewhere, only the default system directories are known. Unknown
+/// variables are left as they are.
+pub fn expand_environment_strings(s: &str, system: Option<&WindowsSystem>) -> String {
+    let expanded = expand_variables(s, |name| {
+        if let Some(value) = system.and_then(|system| system_environment_variable(name, system)) {
+            return Some(value);
+        }
+        #[cfg(windows)]
+        if let Ok(value) = std::env::var(name) {
+            return Some(value);
+        }
+        default_environment_variable(name).map(str::to_owned)
+    });
+    let expanded = expanded.strip_prefix(r"\??\").unwrap_or(&expanded);
+    let system_root = match system {
+        Some(system) => path_to_string(&system.win_dir),
+        None => r"C:\Windows".to_owned(),
+    };
+    if expanded
+        .get(..12)
+        .is_some_and(|p| p.eq_ignore_ascii_case(r"\SystemRoot\"))
+    {
+        format!(r"{system_root}\{}", &expanded[12..])
+    } else if !expanded.starts_with(['\\', '/', '%']) && expanded.as_bytes().get(1) != Some(&b':') {
+        // as for the drivers, relative paths are relative to the Windows directory
+        format!(r"{system_root}\{expanded}")
+    } else {
+        expanded.to_owned()
+    }
+}
+
+/// Split a command line into the executable path and the arguments
+///
+/// Unquoted paths containing spaces are split after the ".exe" extension, as CreateProcess would
+/// eventually find them.
+fn split_command_line(command_line: &str) -> (String, Option<String>) {
+    let command_line = command_line.trim();
+    let (executable, arguments) = if let Some(quoted) = command_line.strip_prefix('"') {
+        match quoted.split_once('"') {
+            Some((executable, arguments)) => (executable, arguments),
+            None => (quoted, ""),
+        }
+    } else if let Some(end) = command_line.to_ascii_lowercase().find(".exe ") {
+        command_line.split_at(end + 4)
+    } else {
+        command_line.split_once(' ').unwrap_or((command_line, ""))
+    };
+    let arguments = arguments.trim();
+    (
+        executable.to_owned(),
+        (!arguments.is_empty()).then(|| arguments.to_owned()),
+    )
+}
+
+/// Path on this host of a path of the Windows installation
+fn system_path(windows_path: &str, system: &WindowsSystem) -> PathBuf {
+    let root = system.win_dir.parent().unwrap_or(&system.win_dir);
+    host_path(
+        &expand_environment_strings(windows_path, Some(system)),
+        root,
+    )
+}
+
+fn string_value(value: Option<RegistryValue>) -> Option<String> {
+    value.and_then(|v| v.as_str().map(str::to_owned))
+}
+
+/// Executable (or DLL, for services hosted by svchost.exe) started for the given service
+///
+/// The working directory of services is the system directory; the PATH set in the Environment
+/// value of the service is taken into account.
+pub fn read_service(
+    name: &str,
+    registry: &Registry,
+    system: &WindowsSystem,
+) -> Result<RegisteredExecutable, LookupError> {
+    let key = format!(r"CurrentControlSet\Services\{name}");
+    let image_path = string_value(registry.value(RegistryRoot::System, &key, "ImagePath")?)
+        .ok_or_else(|| {
+            LookupError::ContextDeductionError(format!("Service {name} not found in the registry"))
+        })?;
+    let (executable, arguments) = split_command_line(&image_path);
+
+    // shared services are DLLs loaded by svchost.exe
+    let is_svchost = executable.to_ascii_lowercase().ends_with("svchost.exe");
+    let service_dll = if is_svchost {
+        match registry.value(
+            RegistryRoot::System,
+            &format!(r"{key}\Parameters"),
+            "ServiceDll",
+        )? {
+            Some(value) => string_value(Some(value)),
+            None => string_value(registry.value(RegistryRoot::System, &key, "ServiceDll")?),
+        }
+    } else {
+        None
+    };
+
+    let path = match registry.value(RegistryRoot::System, &key, "Environment")? {
+        Some(RegistryValue::MultiString(variables)) => variables
+            .iter()
+            .filter_map(|v| v.split_once('='))
+            .filter(|(name, _)| name.eq_ignore_ascii_case("PATH"))
+            .flat_map(|(_, value)| value.split(';'))
+            .filter(|entry| !entry.is_empty())
+            .map(|entry| system_path(entry, system))
+            .collect(),
+        _ => Vec::new(),
+    };
+
+    Ok(RegisteredExecutable {
+        executable_path: system_path(service_dll.as_deref().unwrap_or(&executable), system),
+        arguments,
+        working_directory: Some(system_path(r"%SystemRoot%\System32", system)),
+        path,
+        wow64: false,
+    })
+}
+
+/// Executable started by the given scheduled task
+///
+/// The task is either the path of its XML definition (as exported by the Task Scheduler), or the
+/// name of a task registered in the installation (e.g. "Microsoft\Windows\Defrag\ScheduledDefrag").
+/// Only the first action starting a program is considered.
+pub fn read_scheduled_task(
+    task: &str,
+    system: &WindowsSystem,
+) -> Result<RegisteredExecutable, LookupError> {
+    let task_path = if Path::new(task).is_file() {
+        PathBuf::from(task)
+    } else {
+        let relative = task.trim_start_matches('\\').replace('\\', "/");
+        system.win_dir.join("System32").join("Tasks").join(relative)
+    };
+    let bytes = fs::read(&task_path)?;
+    // the Task Scheduler writes the definitions in UTF-16
+    let xml = match bytes.strip_prefix(&[0xff, 0xfe]) {
+        Some(utf16) => {
+            let units: Vec<u16> = utf16
+                .chunks_exact(2)
+                .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                .collect();
+            String::from_utf16_lossy(&units)
+        }
+        None => String::from_utf8_lossy(bytes.strip_prefix(b"\xef\xbb\xbf").unwrap_or(&bytes))
+            .into_owned(),
+    };
+    let doc = roxmltree::Document::parse(&xml)
+        .map_err(|e| LookupError::ParseError(format!("{}: {e}", task_path.display())))?;
+    let actions = doc
+        .descendants()
+        .find(|n| n.has_tag_name("Actions"))
+        .ok_or_else(|| {
+            LookupError::ParseError(format!("{}: no actions defined", task_path.display()))
+        })?;
+    let child_text = |node: roxmltree::Node, name: &str| {
+        node.children()
+            .find(|n| n.has_tag_name(name))
+            .and_then(|n| n.text())
+            .map(|t| t.trim().to_owned())
+            .filter(|t| !t.is_empty())
+    };
+    let exec = match actions.children().find(|n| n.has_tag_name("Exec")) {
+        Some(exec) => exec,
+        None => {
+            return Err(LookupError::ContextDeductionError(
+                match actions.children().find(|n| n.has_tag_name("ComHandler")) {
+                    Some(handler) => format!(
+                        "The task {task} starts the COM class {}: scan that instead",
+                        child_text(handler, "ClassId").unwrap_or_default()
+                    ),
+                    None => format!("The task {task} does not start a program"),
+                },
+            ))
+        }
+    };
+    let command = child_text(exec, "Command").ok_or_else(|| {
+        LookupError::ParseError(format!("{}: action without command", task_path.display()))
+    })?;
+    let (executable, _) = split_command_line(&command);
+
+    Ok(RegisteredExecutable {
+        executable_path: system_path(&executable, system),
+        arguments: child_text(exec, "Arguments"),
+        working_directory: child_text(exec, "WorkingDirectory").map(|w| system_path(&w, system)),
+        path: Vec::new(),
+        wow64: false,
+    })
+}
+
+/// Server of the given COM class (e.g. "{00000000-0000-0000-0000-000000000000}")
+///
+/// In-process servers (DLLs) are preferred to local servers (executables); the classes registered
+/// for 32-bit processes are found as well.
+pub fn read_com_class(
+    clsid: &str,
+    registry: &Registry,
+    system: &WindowsSystem,
+) -> Result<RegisteredExecutable, LookupError> {
+    let clsid = format!(
+        "{{{}}}",
+        clsid.trim_start_matches('{').trim_end_matches('}')
+    );
+    let class_roots = [
+        (r"Classes\CLSID", false),
+        (r"Classes\WOW6432Node\CLSID", true),
+        (r"WOW6432Node\Classes\CLSID", true),
+    ];
+    for (class_root, wow64) in class_roots {
+        for server in ["InprocServer32", "LocalServer32"] {
+            let key = format!(r"{class_root}\{clsid}\{server}");
+            if let Some(command_line) =
+                string_value(registry.value(RegistryRoot::Software, &key, "")?)
+            {
+                let (executable, arguments) = split_command_line(&command_line);
+                return Ok(RegisteredExecutable {
+                    executable_path: system_path(&executable, system),
+                    arguments,
+                    working_directory: None,
+                    path: Vec::new(),
+                    wow64,
+                });
+            }
+        }
+    }
+    Err(LookupError::ContextDeductionError(format!(
+        "COM class {clsid} not found in the registry"
+    )))
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::registration::{expand_environment_strings, split_command_line};
+
+    #[test]
+    fn command_lines() {
+        assert_eq!(
+            split_command_line(r#""C:\Program Files\A\a.exe" -x "y z""#),
+            (
+                r"C:\Program Files\A\a.exe".to_owned(),
+                Some(r#"-x "y z""#.to_owned())
+            )
+        );
+        assert_eq!(
+            split_command_line(r"C:\Program Files\A\a.exe -k netsvcs"),
+            (
+                r"C:\Program Files\A\a.exe".to_owned(),
+                Some("-k netsvcs".to_owned())
+            )
+        );
+        assert_eq!(
+            split_command_line(r"C:\A\b.dll"),
+            (r"C:\A\b.dll".to_owned(), None)
+        );
+        assert_eq!(
+            expand_environment_strings(r"\SystemRoot\System32\drivers\x.sys", None),
+            r"C:\Windows\System32\drivers\x.sys"
+        );
+        assert_eq!(
+            expand_environment_strings(r"System32\drivers\x.sys", None),
+            r"C:\Windows\System32\drivers\x.sys"
+        );
+        assert_eq!(
+            expand_environment_strings(r"%UNKNOWN_VARIABLE%\x.exe", None),
+            r"%UNKNOWN_VARIABLE%\x.exe"
+        );
+    }
+
+    #[cfg(not(windows))]
+    #[test]
+    fn offline_registrations() -> Result<(), crate::common::LookupError> {
+        use crate::registration::{read_com_class, read_scheduled_task, read_service};
+        use crate::registry::Registry;
+        use crate::system::WindowsSystem;
+        use fs_err as fs;
+
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let test_data = d.join("test_data/registration");
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+
+        // Windows installation with the test project installed in Program Files
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().to_path_buf();
+        let config_dir = root.join("Windows/System32/config");
+        let install_dir = root.join("Program Files/DepRunTest");
+        fs::create_dir_all(&config_dir)?;
+        fs::create_dir_all(root.join("Windows/System32/Tasks"))?;
+        fs::create_dir_all(install_dir.join("lib"))?;
+        for hive in ["SYSTEM", "SOFTWARE"] {
+            fs::copy(test_data.join(hive), config_dir.join(hive))?;
+        }
+        fs::copy(
+            test_data.join("DepRunTestTask"),
+            root.join("Windows/System32/Tasks/DepRunTestTask"),
+        )?;
+        for file in ["DepRunTest.exe", "DepRunTestLib.dll"] {
+            fs::copy(build_dir.join(file), install_dir.join(file))?;
+        }
+
+        let system = WindowsSystem::from_root(&root).unwrap();
+        let registry = Registry::from_root(&root)?;
+
+        // the system directories are the ones of the installation, under its mount point
+        assert_eq!(
+            expand_environment_strings(r"\SystemRoot\System32\x.sys", Some(&system)),
+            format!(r"{}\System32\x.sys", system.win_dir.display())
+        );
+        assert_eq!(
+            expand_environment_strings(r"%ProgramFiles%\DepRunTest", Some(&system)),
+            format!(r"{}\Program Files\DepRunTest", root.display())
+        );
+
+        let service = read_service("DepRunTestSvc", &registry, &system)?;
+        assert_eq!(service.executable_path, install_dir.join("DepRunTest.exe"));
+        assert_eq!(service.arguments.as_deref(), Some("--service"));
+        assert_eq!(service.path, vec![install_dir.join("lib")]);
+        assert_eq!(
+            service.working_directory,
+            Some(root.join("Windows/System32"))
+        );
+        let hosted = read_service("DepRunTestHosted", &registry, &system)?;
+        assert_eq!(
+            hosted.executable_path,
+            install_dir.join("DepRunTestLib.dll")
+        );
+        assert!(read_service("Missing", &registry, &system).is_err());
+
+        let task = read_scheduled_task("DepRunTestTask", &system)?;
+        assert_eq!(task.executable_path, install_dir.join("DepRunTest.exe"));
+        assert_eq!(task.arguments.as_deref(), Some("--scheduled"));
+        assert_eq!(task.working_directory, Some(install_dir.join("lib")));
+        let task_file = test_data.join("DepRunTestTask");
+        assert_eq!(
+            read_scheduled_task(task_file.to_str().unwrap(), &system)?,
+            task
+        );
+
+        let inproc = read_com_class("5C9F5B8E-1D1A-4C53-9D0B-0F3C1E2A7B01", &registry, &system)?;
+        assert_eq!(
+            inproc.executable_path,
+            install_dir.join("DepRunTestLib.dll")
+        );
+        let local = read_com_class("{5c9f5b8e-1d1a-4c53-9d0b-0f3c1e2a7b02}", &registry, &system)?;
+        assert_eq!(local.executable_path, install_dir.join("DepRunTest.exe"));
+        assert_eq!(local.arguments.as_deref(), Some("-Embedding"));
+        Ok(())
+    }
+}
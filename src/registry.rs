@@ -0,0 +1,538 @@
+//! Read-only access to the Windows registry, live or from the hive files of a Windows installation
+//!
+//! The hive files (Windows\System32\config\SYSTEM and SOFTWARE) are read with a minimal parser of
+//! the regf format (https://github.com/msuhanov/regf/blob/master/Windows%20registry%20file%20format%20specification.md),
+//! so that the installations mounted on other OSes can be inspected as well.
+
+use std::path::Path;
+
+use fs_err as fs;
+
+use crate::common::LookupError;
+
+/// Value stored in the registry
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum RegistryValue {
+    /// REG_SZ
+    String(String),
+    /// REG_EXPAND_SZ: string containing references to environment variables (e.g. %SystemRoot%)
+    ExpandString(String),
+    /// REG_MULTI_SZ
+    MultiString(Vec<String>),
+    /// REG_DWORD
+    Dword(u32),
+    /// REG_QWORD
+    Qword(u64),
+    /// any other type
+    Binary(Vec<u8>),
+}
+
+impl RegistryValue {
+    const REG_SZ: u32 = 1;
+    const REG_EXPAND_SZ: u32 = 2;
+    const REG_DWORD: u32 = 4;
+    const REG_MULTI_SZ: u32 = 7;
+    const REG_QWORD: u32 = 11;
+
+    fn from_raw(value_type: u32, data: &[u8]) -> Self {
+        match value_type {
+            Self::REG_SZ => Self::String(utf16_string(data)),
+            Self::REG_EXPAND_SZ => Self::ExpandString(utf16_string(data)),
+            Self::REG_MULTI_SZ => Self::MultiString(
+                utf16_string(data)
+                    .split('\0')
+                    .filter(|s| !s.is_empty())
+                    .map(str::to_owned)
+                    .collect(),
+            ),
+            Self::REG_DWORD if data.len() >= 4 => {
+                Self::Dword(u32::from_le_bytes([data[0], data[1], data[2], data[3]]))
+            }
+            Self::REG_QWORD if data.len() >= 8 => {
+                let mut bytes = [0u8; 8];
+                bytes.copy_from_slice(&data[..8]);
+                Self::Qword(u64::from_le_bytes(bytes))
+            }
+            _ => Self::Binary(data.to_vec()),
+        }
+    }
+
+    /// Content of a REG_SZ or REG_EXPAND_SZ value
+    pub fn as_str(&self) -> Option<&str> {
+        match self {
+            Self::String(s) | Self::ExpandString(s) => Some(s),
+            _ => None,
+        }
+    }
+}
+
+/// UTF-16LE string, up to the terminating NUL if the data is a single string
+fn utf16_string(data: &[u8]) -> String {
+    let units: Vec<u16> = data
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .collect();
+    String::from_utf16_lossy(&units)
+        .trim_end_matches('\0')
+        .to_owned()
+}
+
+/// Registry hive file (e.g. Windows\System32\config\SYSTEM) loaded in memory
+pub struct RegistryHive {
+    data: Vec<u8>,
+    root_cell: u32,
+}
+
+// offset of the first hive bin: the cell offsets are relative to it
+const HBIN_START: usize = 0x1000;
+// name stored as ASCII instead of UTF-16 (KEY_COMP_NAME for keys, VALUE_COMP_NAME for values)
+const KEY_COMP_NAME: u16 = 0x20;
+const VALUE_COMP_NAME: u16 = 0x1;
+// maximum size of a value stored in a single cell; bigger ones are split in a "db" record
+const MAX_CELL_DATA: usize = 16344;
+
+impl RegistryHive {
+    pub fn open<P: AsRef<Path>>(path: P) -> Result<Self, LookupError> {
+        Self::from_bytes(fs::read(path.as_ref())?)
+    }
+
+    pub fn from_bytes(data: Vec<u8>) -> Result<Self, LookupError> {
+        if data.len() < HBIN_START || &data[0..4] != b"regf" {
+            return Err(LookupError::RegistryError(
+                "not a registry hive file".to_owned(),
+            ));
+        }
+        let root_cell = u32::from_le_bytes([data[0x24], data[0x25], data[0x26], data[0x27]]);
+        Ok(Self { data, root_cell })
+    }
+
+    fn read_u32(&self, offset: usize) -> Result<u32, LookupError> {
+        self.data
+            .get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| LookupError::RegistryError(format!("offset {offset:#x} out of bounds")))
+    }
+
+    /// Content of the cell at the given offset (without the size field)
+    fn cell(&self, cell_offset: u32) -> Result<&[u8], LookupError> {
+        let start = HBIN_START + cell_offset as usize;
+        let size = self.read_u32(start)? as i32;
+        // allocated cells have a negative size, which includes the size field itself
+        let size = size.unsigned_abs() as usize;
+        if size < 4 {
+            return Err(LookupError::RegistryError(format!(
+                "invalid cell at {cell_offset:#x}"
+            )));
+        }
+        self.data.get(start + 4..start + size).ok_or_else(|| {
+            LookupError::RegistryError(format!("cell at {cell_offset:#x} out of bounds"))
+        })
+    }
+
+    fn cell_u16(cell: &[u8], offset: usize) -> Result<u16, LookupError> {
+        cell.get(offset..offset + 2)
+            .map(|b| u16::from_le_bytes([b[0], b[1]]))
+            .ok_or_else(|| LookupError::RegistryError("truncated cell".to_owned()))
+    }
+
+    fn cell_u32(cell: &[u8], offset: usize) -> Result<u32, LookupError> {
+        cell.get(offset..offset + 4)
+            .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+            .ok_or_else(|| LookupError::RegistryError("truncated cell".to_owned()))
+    }
+
+    fn record_name(
+        cell: &[u8],
+        offset: usize,
+        len: usize,
+        ascii: bool,
+    ) -> Result<String, LookupError> {
+        let bytes = cell
+            .get(offset..offset + len)
+            .ok_or_else(|| LookupError::RegistryError("truncated name".to_owned()))?;
+        Ok(if ascii {
+            bytes.iter().map(|&b| b as char).collect()
+        } else {
+            utf16_string(bytes)
+        })
+    }
+
+    /// Key node (nk record) at the given offset
+    fn key_node(&self, cell_offset: u32) -> Result<&[u8], LookupError> {
+        let cell = self.cell(cell_offset)?;
+        if !cell.starts_with(b"nk") {
+            return Err(LookupError::RegistryError(format!(
+                "expected a key node at {cell_offset:#x}"
+            )));
+        }
+        Ok(cell)
+    }
+
+    fn key_name(&self, key_node: &[u8]) -> Result<String, LookupError> {
+        let flags = Self::cell_u16(key_node, 2)?;
+        let len = Self::cell_u16(key_node, 72)? as usize;
+        Self::record_name(key_node, 76, len, flags & KEY_COMP_NAME != 0)
+    }
+
+    /// Offsets of the key nodes listed in a subkeys list (lf, lh, li or ri record)
+    fn subkey_offsets(&self, list_offset: u32, out: &mut Vec<u32>) -> Result<(), LookupError> {
+        let list = self.cell(list_offset)?;
+        let count = Self::cell_u16(list, 2)? as usize;
+        match &list[0..2] {
+            // offset and name hint/hash for each subkey
+            b"lf" | b"lh" => {
+                for i in 0..count {
+                    out.push(Self::cell_u32(list, 4 + i * 8)?);
+                }
+            }
+            b"li" => {
+                for i in 0..count {
+                    out.push(Self::cell_u32(list, 4 + i * 4)?);
+                }
+            }
+            // index of lists
+            b"ri" => {
+                for i in 0..count {
+                    self.subkey_offsets(Self::cell_u32(list, 4 + i * 4)?, out)?;
+                }
+            }
+            _ => {
+                return Err(LookupError::RegistryError(format!(
+                    "unknown subkeys list at {list_offset:#x}"
+                )))
+            }
+        }
+        Ok(())
+    }
+
+    fn subkey(&self, key_offset: u32, name: &str) -> Result<Option<u32>, LookupError> {
+        let key_node = self.key_node(key_offset)?;
+        if Self::cell_u32(key_node, 20)? == 0 {
+            return Ok(None);
+        }
+        let mut offsets = Vec::new();
+        self.subkey_offsets(Self::cell_u32(key_node, 28)?, &mut offsets)?;
+        for offset in offsets {
+            if self
+                .key_name(self.key_node(offset)?)?
+                .eq_ignore_ascii_case(name)
+            {
+                return Ok(Some(offset));
+            }
+        }
+        Ok(None)
+    }
+
+    /// Offset of the key at the given path (components separated by backslashes), if it exists
+    fn find_key(&self, key_path: &str) -> Result<Option<u32>, LookupError> {
+        let mut offset = self.root_cell;
+        for component in key_path.split('\\').filter(|c| !c.is_empty()) {
+            match self.subkey(offset, component)? {
+                Some(subkey) => offset = subkey,
+                None => return Ok(None),
+            }
+        }
+        Ok(Some(offset))
+    }
+
+    /// Names of the subkeys of the key at the given path
+    pub fn subkeys(&self, key_path: &str) -> Result<Vec<String>, LookupError> {
+        let key_offset = match self.find_key(key_path)? {
+            Some(key_offset) => key_offset,
+            None => return Ok(Vec::new()),
+        };
+        let key_node = self.key_node(key_offset)?;
+        if Self::cell_u32(key_node, 20)? == 0 {
+            return Ok(Vec::new());
+        }
+        let mut offsets = Vec::new();
+        self.subkey_offsets(Self::cell_u32(key_node, 28)?, &mut offsets)?;
+        offsets
+            .into_iter()
+            .map(|o| self.key_name(self.key_node(o)?))
+            .collect()
+    }
+
+    /// Data of a value (vk record)
+    fn value_data(&self, value_node: &[u8]) -> Result<Vec<u8>, LookupError> {
+        let truncated = || LookupError::RegistryError("truncated value data".to_owned());
+        let size = Self::cell_u32(value_node, 4)?;
+        // the data of up to 4 bytes is stored in place of its offset
+        if size & 0x8000_0000 != 0 {
+            let size = (size & 0x7fff_ffff).min(4) as usize;
+            return value_node
+                .get(8..8 + size)
+                .map(<[u8]>::to_vec)
+                .ok_or_else(truncated);
+        }
+        let size = size as usize;
+        let data = self.cell(Self::cell_u32(value_node, 8)?)?;
+        if size > MAX_CELL_DATA && data.starts_with(b"db") {
+            let segments = Self::cell_u16(data, 2)? as usize;
+            let list = self.cell(Self::cell_u32(data, 4)?)?;
+            let mut out = Vec::with_capacity(size.min(segments * MAX_CELL_DATA));
+            for i in 0..segments {
+                let segment = self.cell(Self::cell_u32(list, i * 4)?)?;
+                let len = (size - out.len()).min(MAX_CELL_DATA);
+                out.extend_from_slice(segment.get(..len).ok_or_else(truncated)?);
+            }
+            if out.len() < size {
+                return Err(truncated());
+            }
+            return Ok(out);
+        }
+        data.get(..size).map(<[u8]>::to_vec).ok_or_else(truncated)
+    }
+
+    /// Value of the key at the given path ("" for the default value)
+    pub fn value(&self, key_path: &str, name: &str) -> Result<Option<RegistryValue>, LookupError> {
+        let key_offset = match self.find_key(key_path)? {
+            Some(key_offset) => key_offset,
+            None => return Ok(None),
+        };
+        let key_node = self.key_node(key_offset)?;
+        let count = Self::cell_u32(key_node, 36)? as usize;
+        if count == 0 {
+            return Ok(None);
+        }
+        let list = self.cell(Self::cell_u32(key_node, 40)?)?;
+        for i in 0..count {
+            let value_node = self.cell(Self::cell_u32(list, i * 4)?)?;
+            if !value_node.starts_with(b"vk") {
+                continue;
+            }
+            let name_len = Self::cell_u16(value_node, 2)? as usize;
+            let flags = Self::cell_u16(value_node, 16)?;
+            let value_name =
+                Self::record_name(value_node, 20, name_len, flags & VALUE_COMP_NAME != 0)?;
+            if value_name.eq_ignore_ascii_case(name) {
+                let value_type = Self::cell_u32(value_node, 12)?;
+                return Ok(Some(RegistryValue::from_raw(
+                    value_type,
+                    &self.value_data(value_node)?,
+                )));
+            }
+        }
+        Ok(None)
+    }
+}
+
+/// Hive of HKEY_LOCAL_MACHINE
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum RegistryRoot {
+    /// HKEY_LOCAL_MACHINE\SYSTEM
+    System,
+    /// HKEY_LOCAL_MACHINE\SOFTWARE
+    Software,
+}
+
+/// Registry of a Windows installation
+pub enum Registry {
+    /// Hive files of an installation, e.g. on a mounted partition (None if missing)
+    Offline {
+        system: Option<RegistryHive>,
+        software: Option<RegistryHive>,
+    },
+    /// Registry of the running system
+    #[cfg(windows)]
+    Live,
+}
+
+impl Registry {
+    /// Registry of the Windows installation at the given path (the C:\ partition)
+    pub fn from_root<P: AsRef<Path>>(root_path: P) -> Result<Self, LookupError> {
+        let config_dir = root_path
+            .as_ref()
+            .join("Windows")
+            .join("System32")
+            .join("config");
+        let open = |name: &str| -> Result<Option<RegistryHive>, LookupError> {
+            let path = config_dir.join(name);
+            if path.is_file() {
+                Ok(Some(RegistryHive::open(path)?))
+            } else {
+                Ok(None)
+            }
+        };
+        let system = open("SYSTEM")?;
+        let software = open("SOFTWARE")?;
+        if system.is_none() && software.is_none() {
+            return Err(LookupError::RegistryError(format!(
+                "no registry hive found in {}",
+                config_dir.display()
+            )));
+        }
+        Ok(Self::Offline { system, software })
+    }
+
+    /// Value of a key of HKEY_LOCAL_MACHINE\SYSTEM or SOFTWARE ("" for the default value)
+    ///
+    /// In the SYSTEM hive, CurrentControlSet refers to the control set in use.
+    pub fn value(
+        &self,
+        root: RegistryRoot,
+        key_path: &str,
+        name: &str,
+    ) -> Result<Option<RegistryValue>, LookupError> {
+        match self {
+            Self::Offline { system, software } => {
+                let hive = match root {
+                    RegistryRoot::System => system,
+                    RegistryRoot::Software => software,
+                };
+                let hive = match hive {
+                    Some(hive) => hive,
+                    None => return Ok(None),
+                };
+                // CurrentControlSet only exists at runtime, as a link to the one in Select\Current
+                let key_path = match (root, key_path.split_once('\\')) {
+                    (RegistryRoot::System, Some((first, rest)))
+                        if first.eq_ignore_ascii_case("CurrentControlSet") =>
+                    {
+                        let current = match hive.value("Select", "Current")? {
+                            Some(RegistryValue::Dword(current)) => current,
+                            _ => 1,
+                        };
+                        format!("ControlSet{current:03}\\{rest}")
+                    }
+                    _ => key_path.to_owned(),
+                };
+                hive.value(&key_path, name)
+            }
+            #[cfg(windows)]
+            Self::Live => live::value(root, key_path, name),
+        }
+    }
+}
+
+#[cfg(windows)]
+mod live {
+    extern crate winapi;
+
+    use std::ffi::OsStr;
+    use std::os::windows::ffi::OsStrExt;
+    use std::ptr::null_mut;
+
+    use winapi::shared::minwindef::DWORD;
+    use winapi::shared::winerror::{ERROR_FILE_NOT_FOUND, ERROR_SUCCESS};
+    use winapi::um::winreg::{RegGetValueW, HKEY_LOCAL_MACHINE, RRF_NOEXPAND, RRF_RT_ANY};
+
+    use super::{RegistryRoot, RegistryValue};
+    use crate::common::LookupError;
+
+    fn wide(s: &str) -> Vec<u16> {
+        OsStr::new(s).encode_wide().chain(Some(0)).collect()
+    }
+
+    pub(super) fn value(
+        root: RegistryRoot,
+        key_path: &str,
+        name: &str,
+    ) -> Result<Option<RegistryValue>, LookupError> {
+        let prefix = match root {
+            RegistryRoot::System => "SYSTEM",
+            RegistryRoot::Software => "SOFTWARE",
+        };
+        let key = wide(&format!("{prefix}\\{key_path}"));
+        let name = wide(name);
+        let mut value_type: DWORD = 0;
+        let mut size: DWORD = 0;
+        // first call to get the size of the data, second one to read it
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                key.as_ptr(),
+                name.as_ptr(),
+                RRF_RT_ANY | RRF_NOEXPAND,
+                &mut value_type,
+                null_mut(),
+                &mut size,
+            )
+        };
+        if status == ERROR_FILE_NOT_FOUND as i32 {
+            return Ok(None);
+        }
+        if status != ERROR_SUCCESS as i32 {
+            return Err(LookupError::RegistryError(
+                std::io::Error::from_raw_os_error(status).to_string(),
+            ));
+        }
+        let mut data = vec![0u8; size as usize];
+        let status = unsafe {
+            RegGetValueW(
+                HKEY_LOCAL_MACHINE,
+                key.as_ptr(),
+                name.as_ptr(),
+                RRF_RT_ANY | RRF_NOEXPAND,
+                &mut value_type,
+                data.as_mut_ptr() as *mut _,
+                &mut size,
+            )
+        };
+        if status != ERROR_SUCCESS as i32 {
+            return Err(LookupError::RegistryError(
+                std::io::Error::from_raw_os_error(status).to_string(),
+            ));
+        }
+        data.truncate(size as usize);
+        Ok(Some(RegistryValue::from_raw(value_type, &data)))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::LookupError;
+    use crate::registry::{Registry, RegistryHive, RegistryRoot, RegistryValue};
+
+    #[test]
+    fn read_hives() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let hive = RegistryHive::open(d.join("test_data/registration/SYSTEM"))?;
+
+        assert_eq!(
+            hive.value("Select", "Current")?,
+            Some(RegistryValue::Dword(1))
+        );
+        // the subkeys of Services are listed in an index of lists
+        let services = hive.subkeys("ControlSet001\\Services")?;
+        assert!(services.len() > 2);
+        assert!(services.iter().any(|s| s == "DepRunTestSvc"));
+        assert_eq!(
+            hive.value("controlset001\\services\\DepRunTestSvc", "Start")?,
+            Some(RegistryValue::Dword(2))
+        );
+        assert_eq!(
+            hive.value("ControlSet001\\Services\\DepRunTestSvc", "Environment")?,
+            Some(RegistryValue::MultiString(vec![
+                r"PATH=C:\Program Files\DepRunTest\lib".to_owned(),
+                "DEPRUNTEST=1".to_owned()
+            ]))
+        );
+        assert_eq!(
+            hive.value("ControlSet001\\Services\\Missing", "Start")?,
+            None
+        );
+        assert!(RegistryHive::open(d.join("test_data/registration/DepRunTestTask")).is_err());
+
+        let software = RegistryHive::open(d.join("test_data/registration/SOFTWARE"))?;
+        let registry = Registry::Offline {
+            system: Some(hive),
+            software: Some(software),
+        };
+        assert!(registry
+            .value(
+                RegistryRoot::System,
+                "CurrentControlSet\\Services\\DepRunTestSvc",
+                "ImagePath"
+            )?
+            .and_then(|v| v.as_str().map(str::to_owned))
+            .is_some_and(|p| p.contains("DepRunTest.exe")));
+        // a value stored in several cells
+        assert!(matches!(
+            registry.value(RegistryRoot::Software, "DepRunTest", "Big")?,
+            Some(RegistryValue::Binary(b)) if b.len() == 20000 && b.iter().all(|&x| x == 0x5a)
+        ));
+
+        Ok(())
+    }
+}
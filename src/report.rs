@@ -0,0 +1,923 @@
+//! Findings of the sanity checks run on the result of a scan
+
+use std::collections::{BTreeMap, BTreeSet};
+use std::path::PathBuf;
+
+use serde::{Deserialize, Serialize};
+
+use crate::audit::{HijackRisk, HijackRiskKind};
+use crate::common::{readable_path, xml_escape, LookupError};
+use crate::executable::{Architecture, Executables};
+use crate::pe::demangle_symbol;
+
+/// How serious a finding is
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Hash, Serialize, Deserialize)]
+pub enum Severity {
+    Info,
+    Warning,
+    Error,
+}
+
+impl std::fmt::Display for Severity {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.write_str(match self {
+            Self::Info => "info",
+            Self::Warning => "warning",
+            Self::Error => "error",
+        })
+    }
+}
+
+/// Problem found by a check, with the data needed to describe it
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub enum FindingKind {
+    /// a dependency could not be found
+    MissingDll { importer: String, dll: String },
+    /// a symbol imported from a found dependency is not exported by it
+    MissingSymbol {
+        importer: String,
+        exporter: String,
+        symbol: String,
+    },
+    /// a dependency was built for a different architecture than its importer
+    ArchMismatch {
+        importer: String,
+        importer_architecture: Architecture,
+        dll: String,
+        dll_architecture: Architecture,
+    },
+    /// different versions of the same side-by-side assembly are requested, with the requesters
+    VersionConflict {
+        assembly: String,
+        versions: BTreeMap<String, BTreeSet<String>>,
+    },
+    /// a dependency could be hijacked through the DLL search order
+    HijackRisk {
+        dll: String,
+        risk: HijackRiskKind,
+        path: Option<PathBuf>,
+    },
+    /// the tree contains both debug and release builds of the C/C++ runtime
+    MixedCrt {
+        debug: BTreeSet<String>,
+        release: BTreeSet<String>,
+    },
+    /// the tree contains several generations of the Visual C++ runtime (e.g. msvcr120 and
+    /// vcruntime140), with the DLLs of each one
+    CrtGenerations {
+        generations: BTreeMap<String, BTreeSet<String>>,
+    },
+    /// a non-system executable is built without essential exploit mitigations (ASLR, DEP)
+    MissingMitigations { dll: String, missing: Vec<String> },
+    /// an API set contract, or a symbol imported from a system DLL (if given), is not available on
+    /// the target Windows version
+    UnavailableOnTargetOs {
+        importer: String,
+        dll: String,
+        symbol: Option<String>,
+        target_os: String,
+    },
+    /// an executable imports a symbol forbidden by a list of banned imports
+    BannedImport {
+        importer: String,
+        dll: String,
+        symbol: String,
+        /// why the import is forbidden, if the list gives it
+        reason: Option<String>,
+    },
+    /// a non-system dependency was loaded from outside the allowed directories (e.g. from a
+    /// directory of the PATH instead of the installation directory)
+    OutsideAllowedDirectories { dll: String, path: PathBuf },
+    /// an executable links to a dependency without importing any symbol from it, so the DLL is
+    /// only loaded (for its side effects, or because of a stale link)
+    LoadOnlyDependency { importer: String, dll: String },
+    /// a dependency differs from its entry in a lock file: a property (path, version or sha256)
+    /// has another value, the dependency is missing (no value found) or it is not locked (no value
+    /// expected)
+    LockMismatch {
+        dll: String,
+        property: String,
+        expected: Option<String>,
+        found: Option<String>,
+    },
+}
+
+/// Documentation of a kind of finding, referenced by its code in the reports
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize)]
+pub struct Rule {
+    /// stable identifier, e.g. DR0001; never reused for another kind of finding
+    pub code: &'static str,
+    /// e.g. MissingDll
+    pub name: &'static str,
+    /// one-line description
+    pub summary: &'static str,
+    /// why the finding matters, and how it is usually solved
+    pub explanation: &'static str,
+}
+
+const MISSING_DLL: Rule = Rule {
+    code: "DR0001",
+    name: "MissingDll",
+    summary: "A dependency could not be found",
+    explanation:
+        "The DLL is not found in any directory of the lookup path, so the loader will fail to \
+                  start the executable (or to load the importing DLL). Deploy the DLL next to the \
+                  executable, add its directory to the PATH, or install the package providing it.",
+};
+
+const MISSING_SYMBOL: Rule = Rule {
+    code: "DR0002",
+    name: "MissingSymbol",
+    summary: "An imported symbol is not exported by the dependency",
+    explanation: "The DLL was found, but it does not export a symbol imported from it: it is probably \
+                  a different version than the one the importer was linked against. The loader will \
+                  fail with an entry point not found error.",
+};
+
+const ARCH_MISMATCH: Rule = Rule {
+    code: "DR0003",
+    name: "ArchMismatch",
+    summary: "A dependency was built for a different architecture than its importer",
+    explanation:
+        "A process can only load DLLs of its own architecture (e.g. x64): the found DLL will \
+                  be rejected by the loader. This usually means that the directories of the lookup \
+                  path point to the build of the wrong platform.",
+};
+
+const VERSION_CONFLICT: Rule = Rule {
+    code: "DR0004",
+    name: "VersionConflict",
+    summary: "Different versions of the same side-by-side assembly are requested",
+    explanation: "The manifests of the executables request different versions of a side-by-side \
+                  assembly (e.g. the Visual C++ 2008 runtime), which may not all be installed, and \
+                  lead to several copies of the same library in the process.",
+};
+
+const HIJACK_RISK: Rule = Rule {
+    code: "DR0005",
+    name: "HijackRisk",
+    summary: "A dependency could be hijacked through the DLL search order",
+    explanation: "A directory searched before the one the DLL is loaded from is writable by other \
+                  users, or a DLL with the same name is shadowed by it: planting a DLL there would run \
+                  foreign code in the process. Deploy the DLL in the application directory, or remove \
+                  the unsafe directory from the lookup path.",
+};
+
+const MIXED_CRT: Rule = Rule {
+    code: "DR0006",
+    name: "MixedCrt",
+    summary: "Debug and release builds of the C/C++ runtime are mixed",
+    explanation: "Objects allocated by one runtime and freed by the other, or passed across the \
+                  boundary (e.g. STL containers), corrupt the heap. All the modules should be built with \
+                  the same configuration.",
+};
+
+const CRT_GENERATIONS: Rule = Rule {
+    code: "DR0007",
+    name: "CrtGenerations",
+    summary: "Several generations of the Visual C++ runtime are loaded",
+    explanation: "The modules were built with different versions of Visual Studio, whose runtimes do \
+                  not share their state (heap, locale, file descriptors): C/C++ objects must not be \
+                  passed between them, and every runtime must be deployed.",
+};
+
+const MISSING_MITIGATIONS: Rule = Rule {
+    code: "DR0008",
+    name: "MissingMitigations",
+    summary: "An executable is built without essential exploit mitigations",
+    explanation:
+        "The executable is not compatible with ASLR or DEP, which makes the whole process \
+                  easier to exploit. Link it with /DYNAMICBASE and /NXCOMPAT.",
+};
+
+const UNAVAILABLE_ON_TARGET_OS: Rule = Rule {
+    code: "DR0009",
+    name: "UnavailableOnTargetOs",
+    summary: "An import is not available on the target Windows version",
+    explanation: "The API set contract or the symbol of the system DLL was introduced by a later \
+                  Windows version than the targeted one, so the executable will not start there. Load \
+                  it dynamically with GetProcAddress, or raise the minimum supported version.",
+};
+
+const BANNED_IMPORT: Rule = Rule {
+    code: "DR0010",
+    name: "BannedImport",
+    summary: "An executable imports a banned symbol",
+    explanation: "The symbol is forbidden by a list of banned imports (e.g. the unsafe string \
+                  functions banned by the Security Development Lifecycle). Replace it with the \
+                  suggested alternative.",
+};
+
+const OUTSIDE_ALLOWED_DIRECTORIES: Rule = Rule {
+    code: "DR0011",
+    name: "OutsideAllowedDirectories",
+    summary: "A dependency is loaded from outside the allowed directories",
+    explanation:
+        "A non-system DLL is resolved from a directory that is not part of the deployment \
+                  (e.g. from a directory of the PATH), so the application depends on what else is \
+                  installed on the machine. Deploy the DLL in an allowed directory.",
+};
+
+const LOAD_ONLY_DEPENDENCY: Rule = Rule {
+    code: "DR0012",
+    name: "LoadOnlyDependency",
+    summary: "A dependency is linked without importing any symbol",
+    explanation:
+        "The import table of the executable lists the DLL, but no symbol is imported from \
+                  it: the loader loads it (and runs its initialization) for nothing, unless this \
+                  side effect is intended. Usually a leftover library in the link command; remove \
+                  it, or load the DLL explicitly if the side effect is needed.",
+};
+
+const LOCK_MISMATCH: Rule = Rule {
+    code: "DR0013",
+    name: "LockMismatch",
+    summary: "A dependency differs from the lock file",
+    explanation: "The DLL is loaded from another location, has another version or content than \
+                  recorded in the lock file, is missing, or is not recorded at all. This catches \
+                  swapped files and unintended upgrades between builds; if the change is \
+                  intended, regenerate the lock file with deprun lock.",
+};
+
+/// Rules of all the kinds of findings, ordered by code
+pub const RULES: &[Rule] = &[
+    MISSING_DLL,
+    MISSING_SYMBOL,
+    ARCH_MISMATCH,
+    VERSION_CONFLICT,
+    HIJACK_RISK,
+    MIXED_CRT,
+    CRT_GENERATIONS,
+    MISSING_MITIGATIONS,
+    UNAVAILABLE_ON_TARGET_OS,
+    BANNED_IMPORT,
+    OUTSIDE_ALLOWED_DIRECTORIES,
+    LOAD_ONLY_DEPENDENCY,
+    LOCK_MISMATCH,
+];
+
+/// Rule with the given code (case-insensitive), if any
+pub fn rule(code: &str) -> Option<&'static Rule> {
+    RULES.iter().find(|r| r.code.eq_ignore_ascii_case(code))
+}
+
+impl FindingKind {
+    /// Documentation of the kind of finding
+    pub fn rule(&self) -> &'static Rule {
+        match self {
+            Self::MissingDll { .. } => &MISSING_DLL,
+            Self::MissingSymbol { .. } => &MISSING_SYMBOL,
+            Self::ArchMismatch { .. } => &ARCH_MISMATCH,
+            Self::VersionConflict { .. } => &VERSION_CONFLICT,
+            Self::HijackRisk { .. } => &HIJACK_RISK,
+            Self::MixedCrt { .. } => &MIXED_CRT,
+            Self::CrtGenerations { .. } => &CRT_GENERATIONS,
+            Self::MissingMitigations { .. } => &MISSING_MITIGATIONS,
+            Self::UnavailableOnTargetOs { .. } => &UNAVAILABLE_ON_TARGET_OS,
+            Self::BannedImport { .. } => &BANNED_IMPORT,
+            Self::OutsideAllowedDirectories { .. } => &OUTSIDE_ALLOWED_DIRECTORIES,
+            Self::LoadOnlyDependency { .. } => &LOAD_ONLY_DEPENDENCY,
+            Self::LockMismatch { .. } => &LOCK_MISMATCH,
+        }
+    }
+
+    /// Stable identifier of the kind of finding, to be used e.g. in suppression lists
+    pub fn code(&self) -> &'static str {
+        self.rule().code
+    }
+
+    /// Name of the kind of finding, e.g. "MissingDll"
+    pub fn name(&self) -> &'static str {
+        self.rule().name
+    }
+}
+
+/// Single result of a check
+#[derive(Debug, Clone, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Finding {
+    pub severity: Severity,
+    /// same as kind.code(), repeated for the consumers of the serialized report
+    pub code: String,
+    /// names of the executables involved
+    pub files: Vec<String>,
+    pub kind: FindingKind,
+    /// how to solve the problem, if known (e.g. the package installing a missing DLL)
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub suggestion: Option<String>,
+}
+
+impl Finding {
+    pub fn new(severity: Severity, kind: FindingKind) -> Self {
+        let files = match &kind {
+            FindingKind::MissingDll { importer, dll }
+            | FindingKind::ArchMismatch { importer, dll, .. }
+            | FindingKind::UnavailableOnTargetOs { importer, dll, .. }
+            | FindingKind::BannedImport { importer, dll, .. }
+            | FindingKind::LoadOnlyDependency { importer, dll } => {
+                vec![importer.clone(), dll.clone()]
+            }
+            FindingKind::MissingSymbol {
+                importer, exporter, ..
+            } => vec![importer.clone(), exporter.clone()],
+            FindingKind::VersionConflict { versions, .. } => versions
+                .values()
+                .flatten()
+                .cloned()
+                .collect::<BTreeSet<_>>()
+                .into_iter()
+                .collect(),
+            FindingKind::HijackRisk { dll, .. }
+            | FindingKind::MissingMitigations { dll, .. }
+            | FindingKind::OutsideAllowedDirectories { dll, .. }
+            | FindingKind::LockMismatch { dll, .. } => {
+                vec![dll.clone()]
+            }
+            FindingKind::MixedCrt { debug, release } => {
+                debug.iter().chain(release).cloned().collect()
+            }
+            FindingKind::CrtGenerations { generations } => {
+                generations.values().flatten().cloned().collect()
+            }
+        };
+        Self {
+            severity,
+            code: kind.code().to_owned(),
+            files,
+            kind,
+            suggestion: None,
+        }
+    }
+
+    pub fn with_suggestion(mut self, suggestion: Option<String>) -> Self {
+        self.suggestion = suggestion;
+        self
+    }
+
+    /// Description of the finding, followed by the suggestion if any
+    pub fn message(&self) -> String {
+        match &self.suggestion {
+            Some(suggestion) => format!("{}; {suggestion}", self.kind),
+            None => self.kind.to_string(),
+        }
+    }
+
+    /// Whether the given executable is involved in this finding
+    pub fn involves(&self, dllname: &str) -> bool {
+        self.files.iter().any(|f| f.eq_ignore_ascii_case(dllname))
+    }
+}
+
+impl From<HijackRisk> for Finding {
+    fn from(risk: HijackRisk) -> Self {
+        let severity = match risk.kind {
+            HijackRiskKind::WorkingDirectory => Severity::Error,
+            _ => Severity::Warning,
+        };
+        Finding::new(
+            severity,
+            FindingKind::HijackRisk {
+                dll: risk.dllname,
+                risk: risk.kind,
+                path: risk.path,
+            },
+        )
+    }
+}
+
+impl std::fmt::Display for Finding {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "[{} {}] {}", self.severity, self.code, self.message())
+    }
+}
+
+impl std::fmt::Display for FindingKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            FindingKind::MissingDll { importer, dll } => {
+                write!(f, "{importer}: dependency {dll} not found")
+            }
+            FindingKind::MissingSymbol {
+                importer,
+                exporter,
+                symbol,
+            } => write!(
+                f,
+                "{importer}: symbol {} not exported by {exporter}",
+                demangle_symbol(symbol).as_ref().unwrap_or(symbol)
+            ),
+            FindingKind::ArchMismatch {
+                importer,
+                importer_architecture,
+                dll,
+                dll_architecture,
+            } => write!(
+                f,
+                "{importer} ({importer_architecture}) depends on {dll} ({dll_architecture})"
+            ),
+            FindingKind::VersionConflict { assembly, versions } => {
+                write!(f, "conflicting versions of assembly {assembly}:")?;
+                for (version, requesters) in versions {
+                    let requesters: Vec<&str> = requesters.iter().map(String::as_str).collect();
+                    write!(f, " {version} (by {})", requesters.join(", "))?;
+                }
+                Ok(())
+            }
+            FindingKind::HijackRisk { dll, risk, path } => {
+                write!(f, "{dll}: {risk}")?;
+                if let Some(path) = path {
+                    write!(f, " ({})", path.display())?;
+                }
+                Ok(())
+            }
+            FindingKind::MixedCrt { debug, release } => {
+                let debug: Vec<&str> = debug.iter().map(String::as_str).collect();
+                let release: Vec<&str> = release.iter().map(String::as_str).collect();
+                write!(
+                    f,
+                    "debug and release C runtimes are mixed: {} and {}",
+                    debug.join(", "),
+                    release.join(", ")
+                )
+            }
+            FindingKind::CrtGenerations { generations } => {
+                write!(f, "several Visual C++ runtime generations are loaded:")?;
+                for (generation, dlls) in generations {
+                    let dlls: Vec<&str> = dlls.iter().map(String::as_str).collect();
+                    write!(f, " {generation} ({})", dlls.join(", "))?;
+                }
+                Ok(())
+            }
+            FindingKind::MissingMitigations { dll, missing } => {
+                write!(f, "{dll}: built without {}", missing.join(" and "))
+            }
+            FindingKind::UnavailableOnTargetOs {
+                importer,
+                dll,
+                symbol: Some(symbol),
+                target_os,
+            } => write!(
+                f,
+                "{importer}: symbol {} of {dll} not available on {target_os}",
+                demangle_symbol(symbol).as_ref().unwrap_or(symbol)
+            ),
+            FindingKind::UnavailableOnTargetOs {
+                importer,
+                dll,
+                symbol: None,
+                target_os,
+            } => write!(f, "{importer}: API set {dll} not available on {target_os}"),
+            FindingKind::BannedImport {
+                importer,
+                dll,
+                symbol,
+                reason,
+            } => {
+                write!(
+                    f,
+                    "{importer}: banned import {} from {dll}",
+                    demangle_symbol(symbol).as_ref().unwrap_or(symbol)
+                )?;
+                if let Some(reason) = reason {
+                    write!(f, " ({reason})")?;
+                }
+                Ok(())
+            }
+            FindingKind::OutsideAllowedDirectories { dll, path } => write!(
+                f,
+                "{dll}: loaded from {}, outside the allowed directories",
+                readable_path(path)
+            ),
+            FindingKind::LoadOnlyDependency { importer, dll } => {
+                write!(f, "{importer}: no symbol imported from dependency {dll}")
+            }
+            FindingKind::LockMismatch {
+                dll,
+                expected: None,
+                found,
+                ..
+            } => write!(
+                f,
+                "{dll}: not in the lock file (loaded from {})",
+                found.as_deref().unwrap_or("?")
+            ),
+            FindingKind::LockMismatch {
+                dll,
+                expected: Some(expected),
+                found: None,
+                ..
+            } => write!(
+                f,
+                "{dll}: locked dependency not found (expected {expected})"
+            ),
+            FindingKind::LockMismatch {
+                dll,
+                property,
+                expected: Some(expected),
+                found: Some(found),
+            } => write!(
+                f,
+                "{dll}: {property} {found} does not match the lock file ({expected})"
+            ),
+        }
+    }
+}
+
+/// Result of the checks run on a scan
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+pub struct ExecutablesCheckReport {
+    findings: Vec<Finding>,
+}
+
+impl ExecutablesCheckReport {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn push(&mut self, finding: Finding) {
+        self.findings.push(finding);
+    }
+
+    pub fn extend(&mut self, other: ExecutablesCheckReport) {
+        self.findings.extend(other.findings);
+    }
+
+    pub fn findings(&self) -> &[Finding] {
+        &self.findings
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.findings.is_empty()
+    }
+
+    /// Severity of the most serious finding, if any
+    pub fn max_severity(&self) -> Option<Severity> {
+        self.findings.iter().map(|f| f.severity).max()
+    }
+
+    /// Findings at least as serious as the given severity
+    pub fn at_least(&self, severity: Severity) -> impl Iterator<Item = &Finding> {
+        self.findings.iter().filter(move |f| f.severity >= severity)
+    }
+
+    /// Findings with the given code
+    pub fn with_code<'a>(&'a self, code: &'a str) -> impl Iterator<Item = &'a Finding> {
+        self.findings.iter().filter(move |f| f.code == code)
+    }
+
+    /// Findings that are not in the baseline, i.e. that were not known when the baseline was
+    /// recorded (severities are ignored in the comparison)
+    pub fn new_since(&self, baseline: &ExecutablesCheckReport) -> Self {
+        self.findings
+            .iter()
+            .filter(|f| !baseline.findings.iter().any(|b| b.kind == f.kind))
+            .cloned()
+            .collect()
+    }
+
+    /// Read a report written as JSON, e.g. a baseline of known findings
+    pub fn from_json_file<P: AsRef<std::path::Path>>(path: P) -> Result<Self, LookupError> {
+        Ok(serde_json::from_str(&fs_err::read_to_string(path)?)?)
+    }
+
+    /// Sort the findings by decreasing severity, then by code and involved files
+    pub fn sort(&mut self) {
+        self.findings.sort_by(|a, b| {
+            b.severity
+                .cmp(&a.severity)
+                .then_with(|| a.code.cmp(&b.code))
+                .then_with(|| a.files.cmp(&b.files))
+        });
+    }
+}
+
+impl ExecutablesCheckReport {
+    /// Render the report as JUnit XML, with one test case for each non-system executable of the
+    /// scan; errors involving an executable are reported as failures, the other findings as output
+    pub fn to_junit_xml(&self, executables: &Executables) -> String {
+        let root_names = executables.root_names(", ");
+        let suite_name = if root_names.is_empty() {
+            "dependency_runner"
+        } else {
+            root_names.as_str()
+        };
+        let targets: Vec<&str> = executables
+            .iter()
+            .filter(|e| e.details.as_ref().map(|d| !d.is_system).unwrap_or(true))
+            .map(|e| e.dllname.as_str())
+            .collect();
+
+        let mut test_cases = String::new();
+        let mut failures = 0;
+        for target in &targets {
+            let (errors, others): (Vec<&Finding>, Vec<&Finding>) = self
+                .findings
+                .iter()
+                .filter(|f| f.involves(target))
+                .partition(|f| f.severity == Severity::Error);
+            test_cases += &format!(
+                "    <testcase classname=\"{}\" name=\"{}\">\n",
+                xml_escape(suite_name),
+                xml_escape(target)
+            );
+            if !errors.is_empty() {
+                failures += 1;
+                let codes: BTreeSet<&str> = errors.iter().map(|f| f.code.as_str()).collect();
+                let codes: Vec<&str> = codes.into_iter().collect();
+                test_cases += &format!(
+                    "      <failure message=\"{} error(s)\" type=\"{}\">",
+                    errors.len(),
+                    codes.join(",")
+                );
+                for finding in &errors {
+                    test_cases += &format!("{}\n", xml_escape(&finding.to_string()));
+                }
+                test_cases += "</failure>\n";
+            }
+            if !others.is_empty() {
+                test_cases += "      <system-out>";
+                for finding in &others {
+                    test_cases += &format!("{}\n", xml_escape(&finding.to_string()));
+                }
+                test_cases += "</system-out>\n";
+            }
+            test_cases += "    </testcase>\n";
+        }
+
+        format!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n\
+             <testsuites>\n  \
+             <testsuite name=\"{}\" tests=\"{}\" failures=\"{failures}\" errors=\"0\">\n\
+             {test_cases}  \
+             </testsuite>\n\
+             </testsuites>\n",
+            xml_escape(suite_name),
+            targets.len()
+        )
+    }
+}
+
+/// Findings that make a scan fail, e.g. to use it as a gate in a CI pipeline
+///
+/// The errors of the check against a lock file (DR0013) fail the scan with every policy, since they
+/// are only reported when a lock file is given.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Serialize, Deserialize)]
+pub enum FailOn {
+    /// never fail
+    None,
+    /// fail if a static dependency is missing
+    MissingDlls,
+    /// fail if a static dependency or an imported symbol is missing, or not available on the
+    /// target OS
+    MissingSymbols,
+    /// fail on any finding of severity warning or above
+    Warnings,
+}
+
+impl std::str::FromStr for FailOn {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "none" => Ok(Self::None),
+            "missing-dlls" => Ok(Self::MissingDlls),
+            "missing-symbols" => Ok(Self::MissingSymbols),
+            "warnings" => Ok(Self::Warnings),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown failure policy {s} (expected none, missing-dlls, missing-symbols or warnings)"
+            ))),
+        }
+    }
+}
+
+/// Exit code of a scan that could not be completed
+pub const EXIT_SCAN_ERROR: i32 = 1;
+
+/// Outcome of a scan according to a failure policy
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ScanOutcome {
+    Clean,
+    MissingDlls,
+    MissingSymbols,
+    LockMismatch,
+    Warnings,
+}
+
+impl ScanOutcome {
+    /// Process exit code for the outcome (2 is left to command line usage errors)
+    pub fn exit_code(&self) -> i32 {
+        match self {
+            Self::Clean => 0,
+            Self::MissingDlls => 3,
+            Self::MissingSymbols => 4,
+            Self::Warnings => 5,
+            Self::LockMismatch => 6,
+        }
+    }
+}
+
+impl ExecutablesCheckReport {
+    /// Most serious outcome among the findings the policy fails on
+    pub fn outcome(&self, policy: FailOn) -> ScanOutcome {
+        let fails = |f: &Finding| match (&f.kind, policy) {
+            (_, FailOn::Warnings) => f.severity >= Severity::Warning,
+            (FindingKind::LockMismatch { .. }, _) => f.severity == Severity::Error,
+            (_, FailOn::None) => false,
+            (FindingKind::MissingDll { .. }, _) => f.severity == Severity::Error,
+            (
+                FindingKind::MissingSymbol { .. } | FindingKind::UnavailableOnTargetOs { .. },
+                FailOn::MissingSymbols,
+            ) => true,
+            _ => false,
+        };
+        let failing: Vec<&Finding> = self.findings.iter().filter(|f| fails(f)).collect();
+        if failing
+            .iter()
+            .any(|f| matches!(f.kind, FindingKind::MissingDll { .. }))
+        {
+            ScanOutcome::MissingDlls
+        } else if failing.iter().any(|f| {
+            matches!(
+                f.kind,
+                FindingKind::MissingSymbol { .. } | FindingKind::UnavailableOnTargetOs { .. }
+            )
+        }) {
+            ScanOutcome::MissingSymbols
+        } else if failing
+            .iter()
+            .any(|f| matches!(f.kind, FindingKind::LockMismatch { .. }))
+        {
+            ScanOutcome::LockMismatch
+        } else if !failing.is_empty() {
+            ScanOutcome::Warnings
+        } else {
+            ScanOutcome::Clean
+        }
+    }
+}
+
+impl FromIterator<Finding> for ExecutablesCheckReport {
+    fn from_iter<I: IntoIterator<Item = Finding>>(iter: I) -> Self {
+        Self {
+            findings: iter.into_iter().collect(),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::LookupError;
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::report::{
+        rule, ExecutablesCheckReport, FailOn, Finding, FindingKind, ScanOutcome, Severity, RULES,
+    };
+    use crate::runner::run;
+
+    #[test]
+    fn rules() {
+        for pair in RULES.windows(2) {
+            assert!(pair[0].code < pair[1].code);
+        }
+        assert_eq!(rule("dr0002").unwrap().name, "MissingSymbol");
+        assert!(rule("DR9999").is_none());
+        let kind = FindingKind::OutsideAllowedDirectories {
+            dll: "a.dll".to_owned(),
+            path: "a.dll".into(),
+        };
+        assert_eq!(kind.rule().code, kind.code());
+        assert_eq!(kind.rule().name, kind.name());
+    }
+
+    #[test]
+    fn report_helpers() {
+        let missing = |importer: &str, dll: &str| {
+            Finding::new(
+                Severity::Error,
+                FindingKind::MissingDll {
+                    importer: importer.to_owned(),
+                    dll: dll.to_owned(),
+                },
+            )
+        };
+        let mut report: ExecutablesCheckReport = [
+            Finding::new(
+                Severity::Warning,
+                FindingKind::MissingSymbol {
+                    importer: "app.exe".to_owned(),
+                    exporter: "lib.dll".to_owned(),
+                    symbol: "foo".to_owned(),
+                },
+            ),
+            missing("lib.dll", "b.dll"),
+            missing("app.exe", "a.dll"),
+        ]
+        .into_iter()
+        .collect();
+
+        assert_eq!(report.max_severity(), Some(Severity::Error));
+        assert_eq!(report.at_least(Severity::Error).count(), 2);
+        assert_eq!(report.with_code("DR0002").count(), 1);
+        assert!(report.findings()[0].involves("LIB.dll"));
+
+        report.sort();
+        let order: Vec<(&str, &str)> = report
+            .findings()
+            .iter()
+            .map(|f| (f.code.as_str(), f.files[0].as_str()))
+            .collect();
+        assert_eq!(
+            order,
+            vec![
+                ("DR0001", "app.exe"),
+                ("DR0001", "lib.dll"),
+                ("DR0002", "app.exe")
+            ]
+        );
+        assert!(ExecutablesCheckReport::new().max_severity().is_none());
+
+        assert_eq!(report.outcome(FailOn::None), ScanOutcome::Clean);
+        assert_eq!(
+            report.outcome(FailOn::MissingDlls),
+            ScanOutcome::MissingDlls
+        );
+        let symbols_only: ExecutablesCheckReport = report
+            .findings()
+            .iter()
+            .filter(|f| f.code == "DR0002")
+            .cloned()
+            .collect();
+        assert_eq!(
+            symbols_only.outcome(FailOn::MissingDlls),
+            ScanOutcome::Clean
+        );
+        assert_eq!(
+            symbols_only.outcome(FailOn::MissingSymbols),
+            ScanOutcome::MissingSymbols
+        );
+        assert_eq!(ScanOutcome::MissingSymbols.exit_code(), 4);
+
+        let lock_mismatch = |severity| {
+            Finding::new(
+                severity,
+                FindingKind::LockMismatch {
+                    dll: "LIB.dll".to_owned(),
+                    property: "sha256".to_owned(),
+                    expected: Some("0".to_owned()),
+                    found: Some("1".to_owned()),
+                },
+            )
+        };
+        let lock_report: ExecutablesCheckReport =
+            [lock_mismatch(Severity::Error)].into_iter().collect();
+        assert_eq!(lock_report.outcome(FailOn::None), ScanOutcome::LockMismatch);
+        assert_eq!(ScanOutcome::LockMismatch.exit_code(), 6);
+        let unlocked: ExecutablesCheckReport =
+            [lock_mismatch(Severity::Warning)].into_iter().collect();
+        assert_eq!(unlocked.outcome(FailOn::MissingSymbols), ScanOutcome::Clean);
+
+        let baseline: ExecutablesCheckReport = report.findings()[..2].iter().cloned().collect();
+        let new_findings = report.new_since(&baseline);
+        assert_eq!(new_findings.findings(), &report.findings()[2..]);
+        assert!(report.new_since(&report).is_empty());
+    }
+
+    #[test]
+    fn junit_xml() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe");
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+        let report = exes.check(false)?;
+
+        let xml = report.to_junit_xml(&exes);
+        let doc =
+            roxmltree::Document::parse(&xml).map_err(|e| LookupError::ParseError(e.to_string()))?;
+        let suite = doc
+            .descendants()
+            .find(|n| n.has_tag_name("testsuite"))
+            .unwrap();
+        assert_eq!(suite.attribute("name"), Some("DepRunTest.exe"));
+        let test_case = |name: &str| {
+            suite
+                .children()
+                .find(|n| n.has_tag_name("testcase") && n.attribute("name") == Some(name))
+                .unwrap()
+        };
+        let failure = test_case("DepRunTest.exe")
+            .children()
+            .find(|n| n.has_tag_name("failure"))
+            .unwrap();
+        assert_eq!(failure.attribute("type"), Some("DR0001"));
+        assert!(failure.text().unwrap().contains("DepRunTestLib.dll"));
+        assert_eq!(
+            suite.attribute("tests").unwrap().parse::<usize>().unwrap(),
+            suite
+                .children()
+                .filter(|n| n.has_tag_name("testcase"))
+                .count()
+        );
+
+        Ok(())
+    }
+}
@@ -1,138 +1,1099 @@
 //! Routine to perform a recursive lookup according to the parameters in the user-provided query and
 //! the lookup path computed from it (and eventually adjusted by the user)
 
-use crate::common::{readable_canonical_path, LookupError};
-use crate::executable::{Executable, ExecutableDetails, ExecutableSymbols, Executables};
-use crate::path::{LookupPath, LookupPathEntry};
+use crate::common::{
+    path_to_string, Diagnostic, DiagnosticKind, Instant, LookupError, NamePatterns,
+};
+use crate::diff::ExecutablesDiff;
+use crate::executable::{
+    Executable, ExecutableDetails, ExecutableSymbols, Executables, FileHashes, ScanMetrics,
+};
+use crate::path::{
+    LookupPath, LookupPathEntry, LookupResult, Probe, ProbeOutcome, ResolutionEntry,
+    ResolutionSource,
+};
 use crate::pe;
-use crate::query::LookupQuery;
+use crate::provider::FileProvider;
+use crate::query::{
+    DllOverride, DuplicatePolicy, LookupParameters, LookupQuery, TraversalStrategy,
+};
+use std::collections::{HashMap, HashSet, VecDeque};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+#[cfg(feature = "async")]
+use std::sync::Arc;
+use std::sync::Mutex;
+use std::time::Duration;
 
 #[derive(Debug)]
 struct Job {
     pub dllname: String,
     pub depth: usize,
+    /// path of the executable, for the targets of the query
+    pub path: Option<PathBuf>,
+    /// executable importing this one, for the dependencies of the targets
+    pub importer: Option<String>,
+}
+
+impl Job {
+    /// Key the lookups are deduplicated by: the lowercase DLL name, as the loader loads a name
+    /// only once per process, unless the files with the same name are all to be found, in which
+    /// case the targets are keyed by their path
+    fn key(&self, policy: DuplicatePolicy) -> String {
+        match &self.path {
+            Some(path) if policy != DuplicatePolicy::FirstWins => {
+                path_to_string(path).to_lowercase()
+            }
+            _ => self.dllname.to_lowercase(),
+        }
+    }
+
+    /// Attach the DLL of this job, and the lookup path entry it was found in, to an error
+    fn error_context(&self, error: LookupError, entry: Option<&LookupPathEntry>) -> LookupError {
+        let entry = entry.map(describe_entry);
+        error.in_dependency(&self.dllname, self.importer.as_deref(), entry)
+    }
+}
+
+/// Lookup path entry named in the error messages, e.g. "SystemDir C:\Windows\System32"
+fn describe_entry(entry: &LookupPathEntry) -> String {
+    match entry.get_path() {
+        Some(dir) => format!("{} {}", entry.kind_name(), dir.display()),
+        None => entry.kind_name().to_owned(),
+    }
+}
+
+/// Executable file found by the lookup of a job, still to be read and parsed
+struct FoundFile {
+    dllname: String,
+    depth: usize,
+    importer: Option<String>,
+    /// lookup path entry the file was found in, for the error messages
+    entry: String,
+    fullpath: PathBuf,
+    is_system: bool,
+    is_api_set: bool,
+    is_known_dll: bool,
+    apiset_host: Option<String>,
+    resolved_from: ResolutionSource,
+    probes: Option<Vec<Probe>>,
+}
+
+/// Outcome of the lookup of a job across the lookup path, before reading any file
+enum Lookup {
+    /// nothing to read: the DLL is missing, assumed present, an API set without a host file, or
+    /// unchanged since the previous scan
+    Done(Box<Executable>),
+    Found(FoundFile),
+}
+
+/// Counters of the work done while looking up the executables, shared by the threads of the scan
+struct ScanCounters {
+    start: Instant,
+    /// cache counters and listing time of the lookup path when the scan started
+    cache_before: (usize, usize, Duration),
+    /// time spent listing directories outside of the cache of the lookup path
+    directory_listing_us: AtomicU64,
+    pe_parsing_us: AtomicU64,
+    symbol_extraction_us: AtomicU64,
+    files_parsed: AtomicUsize,
+    files_reused: AtomicUsize,
+}
+
+impl ScanCounters {
+    fn new(lookup_path: &LookupPath) -> Self {
+        let (hits, misses) = lookup_path.cache_counters();
+        Self {
+            start: Instant::now(),
+            cache_before: (hits, misses, lookup_path.directory_listing_time()),
+            directory_listing_us: AtomicU64::new(0),
+            pe_parsing_us: AtomicU64::new(0),
+            symbol_extraction_us: AtomicU64::new(0),
+            files_parsed: AtomicUsize::new(0),
+            files_reused: AtomicUsize::new(0),
+        }
+    }
+
+    /// Add the time elapsed since `start` to the given counter
+    fn add_time(counter: &AtomicU64, start: Instant) -> u64 {
+        let us = start.elapsed().as_micros() as u64;
+        counter.fetch_add(us, Ordering::Relaxed);
+        us
+    }
+
+    fn metrics(&self, lookup_path: &LookupPath) -> ScanMetrics {
+        let (hits_before, misses_before, listing_before) = self.cache_before;
+        let (hits, misses) = lookup_path.cache_counters();
+        let cache_listing_us =
+            (lookup_path.directory_listing_time() - listing_before).as_micros() as u64;
+        ScanMetrics {
+            duration_ms: self.start.elapsed().as_millis() as u64,
+            cache_hits: hits - hits_before,
+            cache_misses: misses - misses_before,
+            directory_listing_us: cache_listing_us
+                + self.directory_listing_us.load(Ordering::Relaxed),
+            pe_parsing_us: self.pe_parsing_us.load(Ordering::Relaxed),
+            symbol_extraction_us: self.symbol_extraction_us.load(Ordering::Relaxed),
+            files_parsed: self.files_parsed.load(Ordering::Relaxed),
+            files_reused: self.files_reused.load(Ordering::Relaxed),
+        }
+    }
+}
+
+/// DLL name patterns restricting the scan, compiled from the query parameters
+struct NameFilters {
+    prune_below: NamePatterns,
+    skip_dlls: NamePatterns,
+    only_descend_into: NamePatterns,
+}
+
+impl NameFilters {
+    fn new(query: &LookupQuery) -> Result<Self, LookupError> {
+        Ok(Self {
+            prune_below: NamePatterns::new(&query.parameters.prune_below)?,
+            skip_dlls: NamePatterns::new(&query.parameters.skip_dlls)?,
+            only_descend_into: NamePatterns::new(&query.parameters.only_descend_into)?,
+        })
+    }
+
+    /// Whether the dependencies of the given executable should be scanned
+    fn descend_into(&self, executable: &Executable) -> bool {
+        !self.prune_below.matches(&executable.dllname)
+            && (executable.depth_first_appearance == 0
+                || self.only_descend_into.is_empty()
+                || self.only_descend_into.matches(&executable.dllname))
+    }
 }
 
 /// Find the dependencies of the specified executable within the given path
 /// The dependencies are resolved recursively, in a breadth-first fashion.
+/// Each DLL is looked up and parsed in parallel as soon as an importer names it.
 pub fn run(query: &LookupQuery, lookup_path: &LookupPath) -> Result<Executables, LookupError> {
-    let mut executables_to_lookup: Vec<Job> = Vec::new();
-    let mut executables_found = Executables::new();
+    scan(query, lookup_path, None)
+}
 
-    let filename = query
-        .target
-        .target_exe
-        .file_name()
-        .and_then(|s| s.to_str())
-        .ok_or_else(|| {
-            LookupError::ScanError(
-                "could not open file ".to_owned() + query.target.target_exe.to_str().unwrap_or(""),
-            )
-        })?
-        .to_owned();
+/// Update the result of a previous scan of the same target
+///
+/// Every DLL name is resolved again across the path (directory listings are cheap compared to PE
+/// parsing), so that a DLL shadowed by a new file or moved elsewhere is picked up. Files found at
+/// the same location as in the previous scan, with unchanged size and modification time (and
+/// SHA-256 digest, if the previous scan computed it), are not parsed again: their dependencies and
+/// symbols are taken from the previous result.
+pub fn rescan(
+    query: &LookupQuery,
+    lookup_path: &LookupPath,
+    previous: &Executables,
+) -> Result<Executables, LookupError> {
+    scan(query, lookup_path, Some(previous))
+}
 
-    executables_to_lookup.push(Job {
-        dllname: filename,
-        depth: 0,
+/// Differences with a previous scan if some entries of the lookup path were disabled, e.g. to
+/// answer "what breaks if this directory is removed from the PATH?"
+///
+/// The previous scan must have been done with `lookup_path`; it is rescanned with a copy of it,
+/// sharing its directory listings, where the given entries are disabled.
+pub fn what_if_disabled(
+    query: &LookupQuery,
+    lookup_path: &LookupPath,
+    previous: &Executables,
+    disabled_entries: &[usize],
+) -> Result<ExecutablesDiff, LookupError> {
+    let mut experiment = lookup_path.clone();
+    for &index in disabled_entries {
+        experiment.set_entry_enabled(index, false)?;
+    }
+    let executables = rescan(query, &experiment, previous)?;
+    Ok(ExecutablesDiff::compare(previous, &executables))
+}
+
+/// Same as `run`, without blocking the tokio runtime it is awaited in
+///
+/// The directories of the lookup path are listed, and the files are read and parsed, on the
+/// blocking threads of the runtime; the files of each level of the tree are parsed concurrently,
+/// so the traversal is always breadth-first. The directories and files are read through the file
+/// provider of the lookup path, and its cache of directory listings is kept. The `low_memory`
+/// parameter of the query is ignored.
+#[cfg(feature = "async")]
+pub async fn run_async(
+    query: &LookupQuery,
+    lookup_path: &LookupPath<'_>,
+) -> Result<Executables, LookupError> {
+    let filters = NameFilters::new(query)?;
+    let counters = Arc::new(ScanCounters::new(lookup_path));
+    // the files are always parsed concurrently, and their exports kept
+    let parameters = Arc::new(LookupParameters {
+        low_memory: false,
+        ..query.parameters.clone()
     });
 
-    while let Some(lookup_query) = executables_to_lookup.pop() {
-        if lookup_query.depth <= query.parameters.max_depth.unwrap_or(usize::MAX) {
-            // don't search again if we already found the executable
-            if executables_found.contains(&lookup_query.dllname) {
-                continue;
+    let cache = lookup_path.cache().clone();
+    let dirs = lookup_path.search_path();
+    tokio::task::spawn_blocking(move || cache.prefetch(&dirs))
+        .await
+        .map_err(|e| LookupError::ScanError(format!("listing the directories failed: {e}")))?;
+
+    let mut jobs = root_jobs(query)?;
+    let mut traversal = Traversal::new(query, &filters, &jobs);
+    while !jobs.is_empty() {
+        // the names are resolved against the cached listings, only the files found are read
+        let mut results = Vec::with_capacity(jobs.len());
+        let mut tasks = tokio::task::JoinSet::new();
+        for (index, job) in jobs.iter().enumerate() {
+            match resolve_executable(query, lookup_path, None, &counters, job) {
+                Ok(Lookup::Done(executable)) => results.push(Some(Ok((*executable, Vec::new())))),
+                Ok(Lookup::Found(file)) => {
+                    let cache = lookup_path.cache().clone();
+                    let parameters = parameters.clone();
+                    let counters = counters.clone();
+                    tasks.spawn_blocking(move || {
+                        let result =
+                            parse_executable(file, cache.provider(), &parameters, &counters);
+                        (index, result)
+                    });
+                    results.push(None);
+                }
+                Err(e) => results.push(Some(Err(e))),
             }
-            if let Some(r) = lookup_path
-                .search_dll(&lookup_query.dllname)
-                .unwrap_or(None)
-            {
-                let pefilemap = pe::PEFileMap::new(&r.fullpath)?;
-                let pefile = pe::PEFile::new(&pefilemap)?;
-
-                let dllname = pefile
-                    .read_dll_name()
-                    .unwrap_or_else(|_| lookup_query.dllname.clone());
-                let is_system = r.location.is_system();
-                let is_api_set = std::matches!(r.location, LookupPathEntry::ApiSet(_));
-                let is_known_dll = std::matches!(r.location, LookupPathEntry::KnownDLLs(_));
-                let dependencies = if is_api_set {
-                    query
-                        .system
-                        .as_ref()
-                        .and_then(|s| s.apiset_map.as_ref())
-                        .and_then(|am| am.get(dllname.trim_end_matches(".dll")).cloned())
-                } else if r.location.is_system()
-                    && !std::matches!(r.location, LookupPathEntry::ApiSet(_))
-                {
-                    // system DLLs have just too many dependencies
-                    None
-                } else {
-                    Some(pefile.read_dependencies()?)
-                };
-                let symbols = if !is_api_set && query.parameters.extract_symbols {
-                    let exported = pefile.read_exports();
-                    let imported = pefile.read_imports();
-                    if let (Ok(exported), Ok(imported)) = (exported, imported) {
-                        Some(ExecutableSymbols {
-                            exported,
-                            imported,
-                        })
-                    } else {
-                        eprintln!(
-                            "Error extracting symbols of library {}",
-                            readable_canonical_path(&r.fullpath)?
-                        );
-                        None
-                    }
-                } else {
-                    None
+        }
+        while let Some(task) = tasks.join_next().await {
+            let (index, result) =
+                task.map_err(|e| LookupError::ScanError(format!("parsing a file failed: {e}")))?;
+            results[index] = Some(result);
+        }
+        for (job, result) in std::mem::take(&mut jobs).iter().zip(results) {
+            let result = result
+                .unwrap_or_else(|| Err(LookupError::ScanError("file not parsed".to_owned())))
+                .map_err(|e| job.error_context(e, None));
+            jobs.extend(traversal.complete(job, result));
+        }
+        if traversal.failed() {
+            break;
+        }
+    }
+    let mut executables = traversal.finish(query.parameters.duplicate_policy)?;
+    let metrics = counters.metrics(lookup_path);
+    tracing::info!(
+        executables = executables.len(),
+        duration_ms = metrics.duration_ms,
+        files_parsed = metrics.files_parsed,
+        "scan finished"
+    );
+    executables.set_scan_metrics(metrics);
+    set_host(query, &mut executables);
+    Ok(executables)
+}
+
+/// Record the host the target was scanned as loaded by, to check its side-by-side assemblies
+fn set_host(query: &LookupQuery, executables: &mut Executables) {
+    if let Some(name) = query
+        .target
+        .host_exe
+        .as_ref()
+        .and_then(|host_exe| host_exe.file_name())
+    {
+        executables.set_host(
+            name.to_string_lossy().into_owned(),
+            query.target.host_assemblies.clone(),
+        );
+    }
+}
+
+/// Jobs looking up the targets of the query
+fn root_jobs(query: &LookupQuery) -> Result<Vec<Job>, LookupError> {
+    let policy = query.parameters.duplicate_policy;
+    let mut roots: Vec<Job> = Vec::new();
+    for target in query.targets() {
+        let filename = target
+            .file_name()
+            .and_then(|s| s.to_str())
+            .ok_or_else(|| {
+                LookupError::ScanError(
+                    "could not open file ".to_owned() + target.to_str().unwrap_or(""),
+                )
+            })?
+            .to_owned();
+        // a DLL can be loaded only once per process, whatever the path it is loaded from
+        let job = Job {
+            dllname: filename,
+            depth: 0,
+            path: Some(target.to_owned()),
+            importer: None,
+        };
+        if !roots.iter().any(|r| r.key(policy) == job.key(policy)) {
+            roots.push(job);
+        }
+    }
+    Ok(roots)
+}
+
+fn scan(
+    query: &LookupQuery,
+    lookup_path: &LookupPath,
+    previous: Option<&Executables>,
+) -> Result<Executables, LookupError> {
+    let _span = tracing::info_span!("scan", target = %query.target.target_exe.display()).entered();
+    let roots = root_jobs(query)?;
+    let filters = NameFilters::new(query)?;
+
+    let counters = ScanCounters::new(lookup_path);
+    lookup_path.prefetch_directories();
+    let mut executables = match query.parameters.traversal {
+        TraversalStrategy::BreadthFirst => {
+            scan_breadth_first(query, lookup_path, previous, &filters, &counters, roots)
+        }
+        TraversalStrategy::DepthFirst => {
+            scan_depth_first(query, lookup_path, previous, &filters, &counters, roots)
+        }
+    }?;
+    if query.parameters.low_memory && query.parameters.extract_symbols {
+        // one file at a time, as during the scan
+        executables.read_used_exported_symbols(|path| {
+            let pefilemap = pe::PEFileMap::from_provider(path, lookup_path.file_provider())?;
+            pe::PEFile::with_backend(&pefilemap, query.parameters.parser_backend)?.read_exports()
+        });
+    }
+    let metrics = counters.metrics(lookup_path);
+    tracing::info!(
+        executables = executables.len(),
+        duration_ms = metrics.duration_ms,
+        files_parsed = metrics.files_parsed,
+        "scan finished"
+    );
+    executables.set_scan_metrics(metrics);
+    set_host(query, &mut executables);
+    Ok(executables)
+}
+
+fn scan_breadth_first(
+    query: &LookupQuery,
+    lookup_path: &LookupPath,
+    previous: Option<&Executables>,
+    filters: &NameFilters,
+    counters: &ScanCounters,
+    roots: Vec<Job>,
+) -> Result<Executables, LookupError> {
+    let traversal = Mutex::new(Traversal::new(query, filters, &roots));
+    let lookup = |job: &Job| {
+        lookup_executable(query, lookup_path, previous, counters, job)
+            .map_err(|e| job.error_context(e, None))
+    };
+    if query.parameters.low_memory {
+        // a single file content is held at a time
+        let mut queue: VecDeque<Job> = roots.into();
+        while let Some(job) = queue.pop_front() {
+            let result = lookup(&job);
+            queue.extend(traversal.lock().unwrap().complete(&job, result));
+        }
+    } else {
+        // every DLL is looked up as soon as it is discovered, so that a slow file only delays
+        // its own dependencies
+        fn spawn_lookup<'s, F>(
+            scope: &rayon::Scope<'s>,
+            traversal: &'s Mutex<Traversal>,
+            lookup: &'s F,
+            job: Job,
+        ) where
+            F: Fn(&Job) -> Result<(Executable, Vec<Diagnostic>), LookupError> + Sync,
+        {
+            scope.spawn(move |scope| {
+                if traversal.lock().unwrap().failed() {
+                    return;
+                }
+                let result = lookup(&job);
+                let jobs = traversal.lock().unwrap().complete(&job, result);
+                for job in jobs {
+                    spawn_lookup(scope, traversal, lookup, job);
+                }
+            });
+        }
+        rayon::scope(|scope| {
+            for job in roots {
+                spawn_lookup(scope, &traversal, &lookup, job);
+            }
+        });
+    }
+    traversal
+        .into_inner()
+        .unwrap()
+        .finish(query.parameters.duplicate_policy)
+}
+
+/// State of a breadth-first scan, updated as the DLLs are looked up in any order
+///
+/// A DLL is looked up once, from the first job naming it. If it is later found to be imported at a
+/// smaller depth, its depth is lowered and its dependencies are queued again from there, so that
+/// the result is the same as visiting the tree level by level.
+struct Traversal<'a> {
+    query: &'a LookupQuery,
+    filters: &'a NameFilters,
+    /// smallest depth each DLL (by key of its job) was queued at
+    depths: HashMap<String, usize>,
+    /// DLLs looked up so far, by key of their job, with the issues found along the way
+    found: HashMap<String, (Executable, Vec<Diagnostic>)>,
+    /// first error, by depth and name so that the same error is reported across runs
+    error: Option<(usize, String, LookupError)>,
+}
+
+impl<'a> Traversal<'a> {
+    fn new(query: &'a LookupQuery, filters: &'a NameFilters, roots: &[Job]) -> Self {
+        Self {
+            query,
+            filters,
+            depths: roots
+                .iter()
+                .map(|r| (r.key(query.parameters.duplicate_policy), r.depth))
+                .collect(),
+            found: HashMap::new(),
+            error: None,
+        }
+    }
+
+    fn failed(&self) -> bool {
+        self.error.is_some()
+    }
+
+    /// Record the result of a job, and return the jobs of the dependencies not queued yet
+    fn complete(
+        &mut self,
+        job: &Job,
+        result: Result<(Executable, Vec<Diagnostic>), LookupError>,
+    ) -> Vec<Job> {
+        let policy = self.query.parameters.duplicate_policy;
+        let key = job.key(policy);
+        let (mut executable, diagnostics) = match result {
+            Ok(found) => found,
+            Err(e) => {
+                let replace = match &self.error {
+                    Some((depth, name, _)) => (job.depth, &key) < (*depth, name),
+                    None => true,
                 };
+                if replace {
+                    self.error = Some((job.depth, key, e));
+                }
+                return Vec::new();
+            }
+        };
+        // the depth might have been lowered while the file was being read
+        executable.depth_first_appearance = self.depths.get(&key).copied().unwrap_or(job.depth);
+        let mut pending = dependencies_to_lookup(self.query, self.filters, &executable);
+        self.found.insert(key, (executable, diagnostics));
 
-                if let Some(deps) = &dependencies {
-                    for d in deps {
-                        if !executables_found.contains(d.as_ref()) {
-                            executables_to_lookup.push(Job {
-                                dllname: d.to_owned(),
-                                depth: lookup_query.depth + 1,
-                            })
-                        }
+        let mut jobs = Vec::new();
+        while let Some(dependency) = pending.pop() {
+            let key = dependency.key(policy);
+            match self.depths.get_mut(&key) {
+                None => {
+                    self.depths.insert(key, dependency.depth);
+                    jobs.push(dependency);
+                }
+                Some(depth) if dependency.depth < *depth => {
+                    *depth = dependency.depth;
+                    if let Some((found, _)) = self.found.get_mut(&key) {
+                        found.depth_first_appearance = dependency.depth;
+                        pending.extend(dependencies_to_lookup(self.query, self.filters, found));
                     }
                 }
-                executables_found.insert(Executable {
-                    dllname,
+                Some(_) => {}
+            }
+        }
+        // queued in import order
+        jobs.reverse();
+        jobs
+    }
+
+    /// Collect the executables found, in order of depth and name so that the result does not
+    /// depend on the order of the lookups
+    fn finish(self, policy: DuplicatePolicy) -> Result<Executables, LookupError> {
+        if let Some((_, _, error)) = self.error {
+            return Err(error);
+        }
+        let mut found: Vec<(String, (Executable, Vec<Diagnostic>))> =
+            self.found.into_iter().collect();
+        found.sort_by(|(a_key, (a, _)), (b_key, (b, _))| {
+            a.depth_first_appearance
+                .cmp(&b.depth_first_appearance)
+                .then_with(|| a_key.cmp(b_key))
+        });
+        let mut executables = Executables::new();
+        for (_, (executable, diagnostics)) in found {
+            executables.add_diagnostics(diagnostics);
+            executables.insert_with_policy(executable, policy)?;
+        }
+        Ok(executables)
+    }
+}
+
+fn scan_depth_first(
+    query: &LookupQuery,
+    lookup_path: &LookupPath,
+    previous: Option<&Executables>,
+    filters: &NameFilters,
+    counters: &ScanCounters,
+    mut roots: Vec<Job>,
+) -> Result<Executables, LookupError> {
+    let policy = query.parameters.duplicate_policy;
+    let mut executables_found = Executables::new();
+    let mut visited: HashSet<String> = HashSet::new();
+    let root_keys: Vec<String> = roots.iter().map(|r| r.key(policy)).collect();
+    // reversed, so that the first target is visited first
+    roots.reverse();
+    let mut executables_to_lookup: Vec<Job> = roots;
+
+    while let Some(job) = executables_to_lookup.pop() {
+        // don't search again if we already found the executable
+        if !visited.insert(job.key(policy)) {
+            continue;
+        }
+        let (executable, diagnostics) =
+            lookup_executable(query, lookup_path, previous, counters, &job)
+                .map_err(|e| job.error_context(e, None))?;
+        executables_found.add_diagnostics(diagnostics);
+        // pushed in reverse, so that the first import is visited first
+        for dependency_job in dependencies_to_lookup(query, filters, &executable)
+            .into_iter()
+            .rev()
+        {
+            // the targets imported by another one are visited as roots
+            let key = dependency_job.key(policy);
+            if !visited.contains(&key) && !root_keys.contains(&key) {
+                executables_to_lookup.push(dependency_job);
+            }
+        }
+        executables_found.insert_with_policy(executable, policy)?;
+    }
+
+    Ok(executables_found)
+}
+
+/// Dependencies of the given executable to be looked up, according to the query limits
+fn dependencies_to_lookup(
+    query: &LookupQuery,
+    filters: &NameFilters,
+    executable: &Executable,
+) -> Vec<Job> {
+    let depth = executable.depth_first_appearance + 1;
+    if depth > query.parameters.max_depth.unwrap_or(usize::MAX) || !filters.descend_into(executable)
+    {
+        return Vec::new();
+    }
+    executable
+        .details
+        .as_ref()
+        .map(|details| {
+            details
+                .all_dependencies()
+                .into_iter()
+                .filter(|d| !filters.skip_dlls.matches(d))
+                .map(|d| Job {
+                    dllname: d.to_owned(),
+                    depth,
+                    path: None,
+                    importer: Some(executable.dllname.clone()),
+                })
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Details of a system DLL found without a file to read, e.g. an API set resolved through a map
+fn system_details_without_file(
+    is_api_set: bool,
+    api_set_host: Option<String>,
+    resolved_from: ResolutionSource,
+) -> ExecutableDetails {
+    ExecutableDetails {
+        is_api_set,
+        is_system: true,
+        is_known_dll: false,
+        api_set_host,
+        full_path: None,
+        resolved_from: Some(resolved_from),
+        file_stamp: None,
+        file_version: None,
+        hashes: None,
+        architecture: None,
+        subsystem: None,
+        security: None,
+        entry_point: None,
+        signers: None,
+        assembly_dependencies: None,
+        dependencies: None,
+        delay_load_dependencies: None,
+        forwarded_dependencies: None,
+        dynamic_load_dependencies: None,
+        symbols: None,
+    }
+}
+
+/// Whether a file still has the SHA-256 digest recorded by a previous scan
+///
+/// Without a recorded digest, the size and modification time of the file are trusted.
+fn same_digest(lookup_path: &LookupPath, path: &Path, previous: &ExecutableDetails) -> bool {
+    match previous.hashes.as_ref() {
+        Some(hashes) => lookup_path
+            .file_provider()
+            .read_file(path)
+            .is_ok_and(|content| FileHashes::compute(&content).sha256 == hashes.sha256),
+        None => true,
+    }
+}
+
+/// Look up a single DLL across the path, and read its metadata if found
+///
+/// Non-fatal issues found along the way are returned alongside the result
+#[tracing::instrument(
+    name = "lookup",
+    level = "debug",
+    skip_all,
+    fields(dll = %lookup_query.dllname, depth = lookup_query.depth)
+)]
+fn lookup_executable(
+    query: &LookupQuery,
+    lookup_path: &LookupPath,
+    previous: Option<&Executables>,
+    counters: &ScanCounters,
+    lookup_query: &Job,
+) -> Result<(Executable, Vec<Diagnostic>), LookupError> {
+    match resolve_executable(query, lookup_path, previous, counters, lookup_query)? {
+        Lookup::Done(executable) => Ok((*executable, Vec::new())),
+        Lookup::Found(file) => parse_executable(
+            file,
+            lookup_path.file_provider(),
+            &query.parameters,
+            counters,
+        ),
+    }
+}
+
+/// Resolve a single DLL name across the path, reusing the result of the previous scan if its file
+/// did not change
+fn resolve_executable(
+    query: &LookupQuery,
+    lookup_path: &LookupPath,
+    previous: Option<&Executables>,
+    counters: &ScanCounters,
+    lookup_query: &Job,
+) -> Result<Lookup, LookupError> {
+    let dllname = &lookup_query.dllname;
+    let record_probes = query.parameters.record_probes;
+    // a DLL override is recorded as the first probe, followed by the path walk it short-circuits
+    let override_probe =
+        |candidate: Option<&PathBuf>, outcome: ProbeOutcome| -> Result<_, LookupError> {
+            let mut probes = vec![Probe {
+                entry: ResolutionEntry::DllOverride,
+                dir: None,
+                candidate: candidate.cloned(),
+                outcome,
+            }];
+            probes.extend(lookup_path.probe_dll(dllname)?);
+            Ok(probes)
+        };
+    let (result, probes) = match (query.dll_override(dllname), &lookup_query.path) {
+        // the targets are loaded from their own path, even if their directory is not looked up
+        // (e.g. it was disabled in the lookup path, or it is the directory of a plugin)
+        (None, Some(path)) => {
+            let dir = path.parent().map(Path::to_path_buf).unwrap_or_default();
+            let probes = record_probes.then(|| {
+                vec![Probe {
+                    entry: ResolutionEntry::ExecutableDir,
+                    dir: Some(dir.clone()),
+                    candidate: Some(path.clone()),
+                    outcome: ProbeOutcome::Found(Some(path.clone())),
+                }]
+            });
+            let result = LookupResult {
+                location: LookupPathEntry::ExecutableDir(dir),
+                fullpath: Some(path.clone()),
+                apiset_host: None,
+            };
+            (Some(result), probes)
+        }
+        (Some(DllOverride::Path(path)), _) => {
+            if !path.is_file() {
+                return Err(LookupError::ScanError(format!(
+                    "{} does not exist, but {} was overridden to be loaded from it",
+                    path.display(),
+                    dllname
+                )));
+            }
+            let result = LookupResult {
+                location: LookupPathEntry::UserPath(
+                    path.parent().map(Path::to_path_buf).unwrap_or_default(),
+                ),
+                fullpath: Some(path.clone()),
+                apiset_host: None,
+            };
+            let probes = if record_probes {
+                Some(override_probe(
+                    Some(path),
+                    ProbeOutcome::Found(Some(path.clone())),
+                )?)
+            } else {
+                None
+            };
+            (Some(result), probes)
+        }
+        (Some(DllOverride::AssumePresent), _) => {
+            let probes = if record_probes {
+                Some(override_probe(None, ProbeOutcome::AssumedPresent)?)
+            } else {
+                None
+            };
+            // nothing to read: the DLL is found, but its details and dependencies are unknown
+            return Ok(Lookup::Done(Box::new(Executable {
+                dllname: dllname.clone(),
+                depth_first_appearance: lookup_query.depth,
+                found: true,
+                details: None,
+                probes,
+            })));
+        }
+        (Some(DllOverride::AssumeMissing), _) => {
+            let probes = if record_probes {
+                Some(override_probe(None, ProbeOutcome::AssumedMissing)?)
+            } else {
+                None
+            };
+            (None, probes)
+        }
+        (None, None) if record_probes => {
+            let (result, probes) = lookup_path.search_dll_with_probes(dllname)?;
+            (result, Some(probes))
+        }
+        (None, None) => (lookup_path.search_dll(dllname).unwrap_or(None), None),
+    };
+    let r = match result {
+        Some(r) => r,
+        // DLLs guaranteed by the target OS, but not available to the scan
+        None if query.dll_override(dllname).is_none()
+            && query
+                .target_os
+                .as_ref()
+                .is_some_and(|os| os.provides(dllname)) =>
+        {
+            let probes = probes.map(|mut probes| {
+                probes.push(Probe {
+                    entry: ResolutionEntry::TargetOs,
+                    dir: None,
+                    candidate: None,
+                    outcome: ProbeOutcome::AssumedPresent,
+                });
+                probes
+            });
+            let is_api_set = query
+                .target_os
+                .as_ref()
+                .is_some_and(|os| os.provides_api_set(dllname));
+            return Ok(Lookup::Done(Box::new(Executable {
+                dllname: lookup_query.dllname.clone(),
+                depth_first_appearance: lookup_query.depth,
+                found: true,
+                details: Some(system_details_without_file(
+                    is_api_set,
+                    None,
+                    ResolutionSource {
+                        entry: ResolutionEntry::TargetOs,
+                        dir: None,
+                    },
+                )),
+                probes,
+            })));
+        }
+        None => {
+            tracing::debug!(dll = %dllname, "not found");
+            return Ok(Lookup::Done(Box::new(Executable {
+                dllname: lookup_query.dllname.clone(),
+                depth_first_appearance: lookup_query.depth,
+                found: false,
+                details: None,
+                probes,
+            })));
+        }
+    };
+
+    let resolved_from = match query.dll_override(dllname) {
+        Some(DllOverride::Path(path)) => ResolutionSource {
+            entry: ResolutionEntry::DllOverride,
+            dir: path.parent().map(Path::to_path_buf),
+        },
+        _ => ResolutionSource::from(&r.location),
+    };
+    let Some(fullpath) = r.fullpath else {
+        // API set resolved through a map, there is no file to read
+        return Ok(Lookup::Done(Box::new(Executable {
+            dllname: lookup_query.dllname.clone(),
+            depth_first_appearance: lookup_query.depth,
+            found: true,
+            details: Some(system_details_without_file(
+                true,
+                r.apiset_host,
+                resolved_from,
+            )),
+            probes,
+        })));
+    };
+    tracing::debug!(
+        dll = %dllname,
+        path = %fullpath.display(),
+        entry = r.location.kind_name(),
+        "found"
+    );
+    let is_system = r.location.is_system();
+    let is_api_set = std::matches!(r.location, LookupPathEntry::ApiSet(_));
+    let is_known_dll = std::matches!(r.location, LookupPathEntry::KnownDLLs(_));
+
+    // reuse the result of the previous scan if the file didn't change
+    if let Some(previous_executable) = previous.and_then(|p| p.get(&lookup_query.dllname)) {
+        if let Some(previous_details) = previous_executable.details.as_ref() {
+            let file_stamp = lookup_path.file_provider().file_stamp(&fullpath).ok();
+            if previous_details.full_path.as_ref() == Some(&fullpath)
+                && file_stamp.is_some()
+                && previous_details.file_stamp == file_stamp
+                && (previous_details.symbols.is_some()
+                    || is_api_set
+                    || !query.parameters.extract_symbols)
+                // the exports kept by a low memory scan depend on the importers
+                && !(query.parameters.low_memory && query.parameters.extract_symbols)
+                && (previous_details.hashes.is_some() || !query.parameters.compute_hashes)
+                && (previous_details.dynamic_load_dependencies.is_some()
+                    || previous_details.dependencies.is_none()
+                    || !query.parameters.detect_dynamic_loads)
+                && (!is_system
+                    || is_api_set
+                    || previous_details.dependencies.is_some() == query.parameters.scan_system_dlls)
+                && same_digest(lookup_path, &fullpath, previous_details)
+            {
+                let executable = Executable {
+                    dllname: previous_executable.dllname.clone(),
                     depth_first_appearance: lookup_query.depth,
                     found: true,
                     details: Some(ExecutableDetails {
                         is_api_set,
                         is_system,
                         is_known_dll,
-                        full_path: r.fullpath,
-                        dependencies,
-                        symbols,
+                        api_set_host: r.apiset_host,
+                        full_path: Some(fullpath),
+                        resolved_from: Some(resolved_from),
+                        file_stamp,
+                        file_version: previous_details.file_version.clone(),
+                        hashes: if query.parameters.compute_hashes {
+                            previous_details.hashes.clone()
+                        } else {
+                            None
+                        },
+                        architecture: previous_details.architecture,
+                        subsystem: previous_details.subsystem,
+                        security: previous_details.security,
+                        entry_point: previous_details.entry_point,
+                        signers: previous_details.signers.clone(),
+                        assembly_dependencies: previous_details.assembly_dependencies.clone(),
+                        dependencies: previous_details.dependencies.clone(),
+                        delay_load_dependencies: previous_details.delay_load_dependencies.clone(),
+                        forwarded_dependencies: previous_details.forwarded_dependencies.clone(),
+                        dynamic_load_dependencies: if query.parameters.detect_dynamic_loads {
+                            previous_details.dynamic_load_dependencies.clone()
+                        } else {
+                            None
+                        },
+                        symbols: if query.parameters.extract_symbols {
+                            previous_details.symbols.clone()
+                        } else {
+                            None
+                        },
                     }),
-                });
-            } else {
-                executables_found.insert(Executable {
-                    dllname: lookup_query.dllname,
-                    depth_first_appearance: lookup_query.depth,
-                    found: false,
-                    details: None,
-                });
+                    probes,
+                };
+                tracing::trace!(dll = %dllname, "reused from the previous scan");
+                counters.files_reused.fetch_add(1, Ordering::Relaxed);
+                return Ok(Lookup::Done(Box::new(executable)));
             }
         }
     }
 
-    Ok(executables_found)
+    Ok(Lookup::Found(FoundFile {
+        dllname: lookup_query.dllname.clone(),
+        depth: lookup_query.depth,
+        importer: lookup_query.importer.clone(),
+        entry: describe_entry(&r.location),
+        fullpath,
+        is_system,
+        is_api_set,
+        is_known_dll,
+        apiset_host: r.apiset_host,
+        resolved_from,
+        probes,
+    }))
+}
+
+/// Read and parse the file found for a DLL
+///
+/// It only depends on owned data, to run on a blocking task of an async scan (see `run_async`).
+fn parse_executable(
+    file: FoundFile,
+    provider: &dyn FileProvider,
+    parameters: &LookupParameters,
+    counters: &ScanCounters,
+) -> Result<(Executable, Vec<Diagnostic>), LookupError> {
+    let FoundFile {
+        dllname: job_dllname,
+        depth,
+        importer,
+        entry,
+        fullpath,
+        is_system,
+        is_api_set,
+        is_known_dll,
+        apiset_host,
+        resolved_from,
+        probes,
+    } = file;
+    let file_stamp = provider.file_stamp(&fullpath).ok();
+
+    let parsing_start = Instant::now();
+    let in_entry =
+        |e: LookupError| e.in_dependency(&job_dllname, importer.as_deref(), Some(entry.clone()));
+    let pefilemap = pe::PEFileMap::from_provider(&fullpath, provider).map_err(in_entry)?;
+    // the tables of the system DLLs we do not descend into are mostly discarded
+    let pefile = if is_system && !parameters.scan_system_dlls {
+        pe::PEFile::headers_only(&pefilemap)
+    } else {
+        pe::PEFile::with_backend(&pefilemap, parameters.parser_backend)
+    }
+    .map_err(in_entry)?;
+    let mut diagnostics = pefile.diagnostics().to_vec();
+
+    // an API set is known by its contract name, the file we read is its host
+    let dllname = if is_api_set {
+        job_dllname.clone()
+    } else {
+        pefile
+            .read_dll_name()
+            .unwrap_or_else(|_| job_dllname.clone())
+    };
+    let (dependencies, delay_load_dependencies, forwarded_dependencies, dynamic_load_dependencies) =
+        if is_api_set {
+            (apiset_host.clone().map(|h| vec![h]), None, None, None)
+        } else if is_system && !parameters.scan_system_dlls {
+            // system DLLs have just too many dependencies
+            (None, None, None, None)
+        } else {
+            let mut read_optional =
+                |result: Result<Vec<String>, LookupError>, what: &str| match result {
+                    Ok(names) => Some(names),
+                    Err(e) => {
+                        diagnostics.push(Diagnostic::new(
+                            DiagnosticKind::ParseError,
+                            Some(&fullpath),
+                            format!("Error reading the {what}: {e}"),
+                        ));
+                        None
+                    }
+                };
+            let delay_load_dependencies = read_optional(
+                pefile.read_delay_load_dependencies(),
+                "delay-load import table",
+            );
+            let forwarded_dependencies =
+                read_optional(pefile.read_forwarded_dependencies(), "export forwarders");
+            let dynamic_load_dependencies = if parameters.detect_dynamic_loads {
+                read_optional(
+                    pefile.read_dynamic_load_candidates(),
+                    "data section strings",
+                )
+            } else {
+                None
+            };
+            (
+                Some(pefile.read_dependencies().map_err(in_entry)?),
+                delay_load_dependencies,
+                forwarded_dependencies,
+                dynamic_load_dependencies,
+            )
+        };
+    let symbols_start = Instant::now();
+    let symbols = if !is_api_set && parameters.extract_symbols {
+        // the exports of a low memory scan are read once all the importers are known (see `scan`)
+        let exported = if parameters.low_memory {
+            Ok(HashSet::new())
+        } else {
+            pefile.read_exports()
+        };
+        let imported = pefile.read_imported_names();
+        match (exported, imported) {
+            (Ok(exported), Ok(imported)) => Some(ExecutableSymbols { exported, imported }),
+            (Err(e), _) | (_, Err(e)) => {
+                diagnostics.push(Diagnostic::new(
+                    DiagnosticKind::SymbolExtractionError,
+                    Some(&fullpath),
+                    format!("Error extracting symbols: {e}"),
+                ));
+                None
+            }
+        }
+    } else {
+        None
+    };
+    let symbol_extraction_us =
+        ScanCounters::add_time(&counters.symbol_extraction_us, symbols_start);
+
+    let executable = Executable {
+        dllname,
+        depth_first_appearance: depth,
+        found: true,
+        details: Some(ExecutableDetails {
+            is_api_set,
+            is_system,
+            is_known_dll,
+            api_set_host: apiset_host,
+            full_path: Some(fullpath),
+            resolved_from: Some(resolved_from),
+            file_stamp,
+            file_version: pefile.read_file_version(),
+            hashes: parameters
+                .compute_hashes
+                .then(|| FileHashes::compute(pefilemap.content())),
+            architecture: pefile.read_architecture(),
+            subsystem: pefile.read_subsystem(),
+            security: pefile.read_security_features(),
+            entry_point: pefile.read_entry_point(),
+            signers: pefile.read_signers(),
+            assembly_dependencies: if is_api_set {
+                None
+            } else {
+                pefile.read_assembly_dependencies()
+            },
+            dependencies,
+            delay_load_dependencies,
+            forwarded_dependencies,
+            dynamic_load_dependencies,
+            symbols,
+        }),
+        probes,
+    };
+    let parsing_us = parsing_start.elapsed().as_micros() as u64;
+    counters.pe_parsing_us.fetch_add(
+        parsing_us.saturating_sub(symbol_extraction_us),
+        Ordering::Relaxed,
+    );
+    counters.files_parsed.fetch_add(1, Ordering::Relaxed);
+    Ok((executable, diagnostics))
 }
 
 #[cfg(test)]
 mod tests {
-    use crate::common::LookupError;
-    use crate::path::LookupPath;
-    use crate::query::LookupQuery;
-    use crate::runner::run;
+    use crate::common::{DiagnosticKind, LookupError};
+    use crate::diff::ExecutableChange;
+    use crate::executable::Executables;
+    use crate::path::{LookupPath, Probe, ProbeOutcome, ResolutionEntry};
+    use crate::query::DuplicatePolicy;
+    use crate::query::{DllOverride, LookupQuery, TraversalStrategy};
+    use crate::runner::{rescan, run, what_if_disabled, Job, NameFilters, Traversal};
+    use crate::system::WindowsSystem;
+    use crate::testing::dll;
+    use fs_err as fs;
     use std::collections::HashSet;
     use std::iter::FromIterator;
+    use std::path::PathBuf;
 
     #[test]
     fn run_build_same_output() -> Result<(), LookupError> {
@@ -140,7 +1101,7 @@ mod tests {
         let exe_path =
             d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
 
-        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let mut query = LookupQuery::deduce_from_executable_location(&exe_path)?;
         query.parameters.skip_system_dlls = true;
         let lookup_path = LookupPath::deduce(&query);
         let res = run(&query, &lookup_path)?;
@@ -154,6 +1115,692 @@ mod tests {
             HashSet::from_iter(["DepRunTestLib.dll", "DepRunTest.exe"].iter().copied());
         assert_eq!(sorted_names, expected_names);
 
+        let lib = res
+            .get("DepRunTestLib.dll")
+            .unwrap()
+            .details
+            .as_ref()
+            .unwrap();
+        let resolved_from = lib.resolved_from.as_ref().unwrap();
+        assert_eq!(resolved_from.entry, ResolutionEntry::ExecutableDir);
+        assert_eq!(resolved_from.dir.as_deref(), exe_path.parent());
+        assert!(serde_json::to_string(&res)?
+            .contains(r#""resolved_from":{"entry":"ExecutableDir","dir":"#));
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_fallback_apiset_map() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = None;
+        let mut apiset_map = crate::apiset::ApisetMap::new();
+        apiset_map.insert("ucrtbased".to_owned(), vec!["ucrtbase.dll".to_owned()]);
+        query.fallback_apiset_map = Some(apiset_map);
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+
+        let ucrt = res.get("ucrtbased.dll").unwrap();
+        assert!(ucrt.found);
+        let details = ucrt.details.as_ref().unwrap();
+        assert!(details.is_api_set);
+        assert_eq!(details.api_set_host.as_deref(), Some("ucrtbase.dll"));
+        assert!(!res.contains("ucrtbase.dll"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn rescan_reuses_unchanged_files() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+        let metrics = res.scan_metrics().unwrap();
+        assert!(metrics.files_parsed >= 2);
+        assert_eq!(metrics.files_reused, 0);
+        let json = serde_json::to_string(&res.sorted_by_first_appearance())?;
+
+        // tamper with the previous result: the change is only picked up if the file is parsed
+        let mut previous: serde_json::Value = serde_json::from_str(&json)?;
+        for e in previous.as_array_mut().unwrap() {
+            if e["dllname"] == "DepRunTest.exe" {
+                e["details"]["dependencies"] = serde_json::json!(["KERNEL32.dll"]);
+            }
+        }
+        let previous = Executables::from_json(&previous.to_string())?;
+        let rescanned = rescan(&query, &lookup_path, &previous)?;
+        assert!(rescanned.contains("DepRunTest.exe"));
+        assert!(!rescanned.contains("DepRunTestLib.dll"));
+        assert!(rescanned.scan_metrics().unwrap().files_reused >= 1);
+
+        // a changed file is parsed again
+        let mut previous: serde_json::Value = serde_json::from_str(&json)?;
+        for e in previous.as_array_mut().unwrap() {
+            if e["dllname"] == "DepRunTest.exe" {
+                e["details"]["dependencies"] = serde_json::json!(["KERNEL32.dll"]);
+                e["details"]["file_stamp"]["size"] = serde_json::json!(0);
+            }
+        }
+        let previous = Executables::from_json(&previous.to_string())?;
+        let rescanned = rescan(&query, &lookup_path, &previous)?;
+        assert!(rescanned.contains("DepRunTestLib.dll"));
+
+        // so is a file whose content no longer has the recorded digest
+        let mut query = query.clone();
+        query.parameters.compute_hashes = true;
+        let res = run(&query, &lookup_path)?;
+        let json = serde_json::to_string(&res.sorted_by_first_appearance())?;
+        let mut previous: serde_json::Value = serde_json::from_str(&json)?;
+        for e in previous.as_array_mut().unwrap() {
+            if e["dllname"] == "DepRunTest.exe" {
+                e["details"]["dependencies"] = serde_json::json!(["KERNEL32.dll"]);
+            }
+        }
+        let unchanged = Executables::from_json(&previous.to_string())?;
+        let rescanned = rescan(&query, &lookup_path, &unchanged)?;
+        assert!(!rescanned.contains("DepRunTestLib.dll"));
+        for e in previous.as_array_mut().unwrap() {
+            if e["dllname"] == "DepRunTest.exe" {
+                e["details"]["hashes"]["sha256"] = serde_json::json!("0".repeat(64));
+            }
+        }
+        let tampered = Executables::from_json(&previous.to_string())?;
+        let rescanned = rescan(&query, &lookup_path, &tampered)?;
+        assert!(rescanned.contains("DepRunTestLib.dll"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_depth_first() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let lookup_path = LookupPath::deduce(&query);
+        let breadth_first = run(&query, &lookup_path)?;
+        let mut depth_first_query = query.clone();
+        depth_first_query.parameters.traversal = TraversalStrategy::DepthFirst;
+        let depth_first = run(&depth_first_query, &LookupPath::deduce(&depth_first_query))?;
+
+        let names = |exes: &crate::executable::Executables| -> HashSet<String> {
+            exes.sorted_by_first_appearance()
+                .iter()
+                .map(|e| e.dllname.to_lowercase())
+                .collect()
+        };
+        assert_eq!(names(&breadth_first), names(&depth_first));
+        // VCRUNTIME140D.dll is imported by both DepRunTest.exe and DepRunTestLib.dll
+        assert_eq!(
+            breadth_first
+                .get("VCRUNTIME140D.dll")
+                .unwrap()
+                .depth_first_appearance,
+            1
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_multiple_targets() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build");
+        let exe_path = build_dir.join("DepRunTest/Debug/DepRunTest.exe");
+        let plugin_path =
+            fs::canonicalize(build_dir.join("DepRunTestLib/Debug/DepRunTestLib.dll"))?;
+
+        for traversal in [
+            TraversalStrategy::BreadthFirst,
+            TraversalStrategy::DepthFirst,
+        ] {
+            let mut query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+            query.parameters.traversal = traversal;
+            // the DLL lies outside of the lookup path of the executable, but is loaded from its
+            // own path as a target, so that the import of the executable is satisfied
+            query.add_target(&plugin_path);
+            query.add_target(&exe_path);
+            let lookup_path = LookupPath::deduce(&query);
+            let res = run(&query, &lookup_path)?;
+
+            let roots: Vec<&str> = res
+                .get_roots()?
+                .iter()
+                .map(|r| r.dllname.as_str())
+                .collect();
+            assert_eq!(roots, vec!["DepRunTest.exe", "DepRunTestLib.dll"]);
+            let lib = res.get("DepRunTestLib.dll").unwrap();
+            assert!(lib.found);
+            assert_eq!(
+                lib.details.as_ref().unwrap().full_path.as_ref(),
+                Some(&plugin_path)
+            );
+            assert_eq!(
+                res.dependency_chains("DepRunTestLib.dll")?,
+                vec![vec!["DepRunTestLib.dll".to_owned()]]
+            );
+        }
+
+        // a target is loaded from its own path, even if a DLL with its name is found first across
+        // the lookup path; the target of the query stays the first root
+        let same_output_exe = fs::canonicalize(d.join(
+            "test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe",
+        ))?;
+        let mut query = LookupQuery::deduce_from_executable_location(&same_output_exe)?;
+        query.add_target(&plugin_path);
+        let res = run(&query, &LookupPath::deduce(&query))?;
+        let roots = res.get_roots()?;
+        assert_eq!(roots[0].dllname, "DepRunTest.exe");
+        assert_eq!(
+            roots[1].details.as_ref().unwrap().full_path.as_ref(),
+            Some(&plugin_path)
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_duplicate_policies() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_dir = fs::canonicalize(
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug"),
+        )?;
+        let exe_path = exe_dir.join("DepRunTest.exe");
+        // another build of the DLL imported by the executable, which finds its own next to it
+        let plugin_path = fs::canonicalize(d.join(
+            "test_data/test_project1/DepRunTest/build/DepRunTestLib/Debug/DepRunTestLib.dll",
+        ))?;
+
+        let scan = |traversal: TraversalStrategy, policy: DuplicatePolicy| {
+            let mut query = LookupQuery::deduce_from_executable_location(&exe_path)?;
+            query.parameters.traversal = traversal;
+            query.parameters.duplicate_policy = policy;
+            query.add_target(&plugin_path);
+            // the same target twice is scanned once
+            query.add_target(&plugin_path);
+            run(&query, &LookupPath::deduce(&query))
+        };
+        for traversal in [
+            TraversalStrategy::BreadthFirst,
+            TraversalStrategy::DepthFirst,
+        ] {
+            let res = scan(traversal, DuplicatePolicy::KeepAll)?;
+            let mut paths: Vec<PathBuf> = res
+                .get_all("DepRunTestLib.dll")
+                .iter()
+                .filter_map(|e| e.details.as_ref()?.full_path.clone())
+                .collect();
+            paths.sort();
+            let mut expected = vec![plugin_path.clone(), exe_dir.join("DepRunTestLib.dll")];
+            expected.sort();
+            assert_eq!(paths, expected);
+            assert_eq!(res.diagnostics()[0].kind, DiagnosticKind::DuplicateName);
+
+            assert!(std::matches!(
+                scan(traversal, DuplicatePolicy::Error),
+                Err(LookupError::DuplicateName { .. })
+            ));
+            // by default, the target shadows the DLL found next to the executable
+            let res = scan(traversal, DuplicatePolicy::FirstWins)?;
+            assert_eq!(res.get_all("DepRunTestLib.dll").len(), 1);
+        }
+
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn run_async_same_as_run() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let lookup_path = LookupPath::deduce(&query);
+        let expected = run(&query, &lookup_path)?;
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let res = runtime.block_on(crate::runner::run_async(&query, &lookup_path))?;
+
+        let names = |e: &Executables| -> Vec<(String, bool)> {
+            e.sorted_by_first_appearance()
+                .iter()
+                .map(|e| (e.dllname.clone(), e.found))
+                .collect()
+        };
+        assert_eq!(names(&res), names(&expected));
+        let lib = res.get("DepRunTestLib.dll").unwrap();
+        assert_eq!(
+            lib.details.as_ref().unwrap().dependencies,
+            expected
+                .get("DepRunTestLib.dll")
+                .unwrap()
+                .details
+                .as_ref()
+                .unwrap()
+                .dependencies
+        );
+        assert!(res.scan_metrics().unwrap().files_parsed >= 2);
+        Ok(())
+    }
+
+    #[cfg(feature = "async")]
+    #[test]
+    fn run_async_with_file_provider() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        let archive_dir = std::path::PathBuf::from("/archive/DepRunTest");
+        let mut files = crate::provider::MemoryFiles::new();
+        for f in ["DepRunTest.exe", "DepRunTestLib.dll"] {
+            files.insert(archive_dir.join(f), std::fs::read(exe_dir.join(f))?);
+        }
+
+        let query = LookupQuery::deduce_with_system(archive_dir.join("DepRunTest.exe"), None)?;
+        let mut lookup_path = LookupPath::deduce(&query);
+        lookup_path.set_file_provider(std::sync::Arc::new(files));
+        lookup_path.prefetch_directories();
+        let runtime = tokio::runtime::Builder::new_current_thread().build()?;
+        let res = runtime.block_on(crate::runner::run_async(&query, &lookup_path))?;
+
+        // the files are read through the provider, and the listings cached beforehand are kept
+        let lib = res.get("DepRunTestLib.dll").unwrap();
+        assert!(lib.found);
+        assert_eq!(
+            lib.details.as_ref().unwrap().full_path,
+            Some(archive_dir.join("DepRunTestLib.dll"))
+        );
+        let metrics = res.scan_metrics().unwrap();
+        assert_eq!(metrics.cache_misses, 0);
+        assert_eq!(metrics.files_parsed, 2);
+        Ok(())
+    }
+
+    #[test]
+    fn run_low_memory() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.extract_symbols = true;
+        let lookup_path = LookupPath::deduce(&query);
+        let full = run(&query, &lookup_path)?;
+        let mut low_memory_query = query.clone();
+        low_memory_query.parameters.low_memory = true;
+        let res = run(&low_memory_query, &lookup_path)?;
+
+        let exports = |e: &Executables, name: &str| {
+            let details = e.get(name).unwrap().details.as_ref().unwrap();
+            details.symbols.as_ref().unwrap().exported.clone()
+        };
+        // only the exports imported by the executable are kept
+        let imported = &res.get("DepRunTest.exe").unwrap().details.as_ref().unwrap();
+        let imported = &imported.symbols.as_ref().unwrap().imported["DepRunTestLib.dll"];
+        assert_eq!(&exports(&res, "DepRunTestLib.dll"), imported);
+        assert!(exports(&full, "DepRunTestLib.dll").is_superset(imported));
+        assert!(exports(&res, "DepRunTest.exe").is_empty());
+        assert_eq!(
+            res.check(true)?.findings().len(),
+            full.check(true)?.findings().len()
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn run_what_if_disabled() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+
+        // the DLL lies in the directory of the executable, which is also the working directory
+        let app_dir_entries: Vec<usize> = lookup_path
+            .entries
+            .iter()
+            .enumerate()
+            .filter(|(_, e)| e.get_path().as_ref() == Some(&query.target.app_dir))
+            .map(|(i, _)| i)
+            .collect();
+        assert_eq!(app_dir_entries.len(), 2);
+        let diff = what_if_disabled(&query, &lookup_path, &res, &app_dir_entries[..1])?;
+        assert!(diff.is_empty());
+        let diff = what_if_disabled(&query, &lookup_path, &res, &app_dir_entries)?;
+        let lib_change = diff
+            .changed
+            .iter()
+            .find(|c| c.dllname == "DepRunTestLib.dll")
+            .unwrap();
+        assert_eq!(lib_change.changes, vec![ExecutableChange::NowMissing]);
+        // the experiment does not touch the original path
+        assert!(lookup_path.is_entry_enabled(app_dir_entries[0]));
+
+        let mut experiment = lookup_path.clone();
+        experiment.set_entry_enabled(app_dir_entries[0], false)?;
+        let probes = experiment.probe_dll("DepRunTestLib.dll")?;
+        assert_eq!(probes[app_dir_entries[0]].outcome, ProbeOutcome::Disabled);
+        assert!(experiment
+            .set_entry_enabled(lookup_path.entries.len(), false)
+            .is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_prune_below() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.prune_below = vec!["depruntest*.dll".to_owned()];
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+
+        assert!(res.contains("DepRunTestLib.dll"));
+        assert!(!res.contains("MSVCP140D.dll"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_skip_dlls() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.skip_dlls = vec!["vcruntime*".to_owned()];
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+
+        assert!(res.contains("DepRunTestLib.dll"));
+        assert!(res.contains("MSVCP140D.dll"));
+        assert!(!res.contains("VCRUNTIME140D.dll"));
+        assert!(!res.contains("VCRUNTIME140_1D.dll"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_dll_overrides() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe");
+        let lib_path = d
+            .join("test_data/test_project1/DepRunTest/build/DepRunTestLib/Debug/DepRunTestLib.dll");
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = None;
+        query.override_dll("depruntestlib.dll", DllOverride::Path(lib_path.clone()));
+        query.override_dll("VCRUNTIME140D.dll", "present".parse()?);
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+
+        // the DLL is not next to the executable, but it is loaded from the given file
+        let lib = res.get("DepRunTestLib.dll").unwrap();
+        assert!(lib.found);
+        assert_eq!(lib.details.as_ref().unwrap().full_path, Some(lib_path));
+        let resolved_from = lib
+            .details
+            .as_ref()
+            .unwrap()
+            .resolved_from
+            .as_ref()
+            .unwrap();
+        assert_eq!(resolved_from.entry, ResolutionEntry::DllOverride);
+        assert!(res.contains("MSVCP140D.dll"));
+        let vcruntime = res.get("VCRUNTIME140D.dll").unwrap();
+        assert!(vcruntime.found && vcruntime.details.is_none());
+        assert_eq!(res.stats().missing, 4);
+
+        let mut missing_query = query.clone();
+        missing_query.override_dll("DepRunTestLib.dll", DllOverride::AssumeMissing);
+        let res = run(&missing_query, &lookup_path)?;
+        assert!(!res.get("DepRunTestLib.dll").unwrap().found);
+        assert!(!res.contains("MSVCP140D.dll"));
+
+        let mut wrong_query = query.clone();
+        wrong_query.override_dll(
+            "DepRunTestLib.dll",
+            DllOverride::Path(d.join("nonexistent.dll")),
+        );
+        assert!(run(&wrong_query, &lookup_path).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_only_descend_into() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.only_descend_into = vec!["nothing*.dll".to_owned()];
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+
+        // the direct dependencies of the target are always scanned
+        assert!(res.contains("DepRunTestLib.dll"));
+        assert!(!res.contains("MSVCP140D.dll"));
+
+        let mut query = query.clone();
+        query.parameters.only_descend_into = vec!["depruntest*.dll".to_owned()];
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+        assert!(res.contains("MSVCP140D.dll"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_record_probes() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let lookup_path = LookupPath::deduce(&query);
+        assert!(run(&query, &lookup_path)?
+            .get("DepRunTestLib.dll")
+            .unwrap()
+            .probes
+            .is_none());
+
+        query.parameters.record_probes = true;
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+        let probes = res
+            .get("DepRunTestLib.dll")
+            .unwrap()
+            .probes
+            .clone()
+            .unwrap();
+        assert_eq!(probes.len(), lookup_path.entries.len());
+        let winner = probes.iter().find(|p| p.is_found()).unwrap();
+        assert_eq!(winner.entry, ResolutionEntry::ExecutableDir);
+
+        let missing_probes = lookup_path.probe_dll("nonexistent.dll")?;
+        assert!(missing_probes.iter().all(|p| !p.is_found()));
+        assert!(missing_probes
+            .iter()
+            .any(|p| p.outcome == ProbeOutcome::NotInDirectory
+                && p.candidate.as_ref().unwrap().ends_with("nonexistent.dll")));
+        assert_eq!(Probe::winner(&missing_probes), None);
+
+        // the override comes first, and hides where the DLL would be found
+        let mut missing_query = query.clone();
+        missing_query.override_dll("DepRunTestLib.dll", DllOverride::AssumeMissing);
+        let res = run(&missing_query, &lookup_path)?;
+        let probes = res
+            .get("DepRunTestLib.dll")
+            .unwrap()
+            .probes
+            .clone()
+            .unwrap();
+        assert_eq!(probes.len(), lookup_path.entries.len() + 1);
+        assert_eq!(probes[0].outcome, ProbeOutcome::AssumedMissing);
+        assert!(probes.iter().any(|p| p.is_found()));
+        assert_eq!(Probe::winner(&probes), None);
+
+        Ok(())
+    }
+
+    #[test]
+    fn run_scan_system_dlls() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe");
+
+        // fake Windows partition, whose system directory provides the DLL of the test project
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().to_path_buf();
+        let sys_dir = root.join("Windows").join("System32");
+        fs::create_dir_all(&sys_dir)?;
+        fs::copy(
+            d.join(
+                "test_data/test_project1/DepRunTest/build/DepRunTestLib/Debug/DepRunTestLib.dll",
+            ),
+            sys_dir.join("DepRunTestLib.dll"),
+        )?;
+
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.system = WindowsSystem::from_root(&root);
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+        assert!(
+            res.get("DepRunTestLib.dll")
+                .unwrap()
+                .details
+                .as_ref()
+                .unwrap()
+                .is_system
+        );
+        assert!(!res.contains("MSVCP140D.dll"));
+
+        query.parameters.scan_system_dlls = true;
+        let lookup_path = LookupPath::deduce(&query);
+        let full = run(&query, &lookup_path)?;
+        assert!(full.contains("MSVCP140D.dll"));
+
+        // a previous result that stopped at the system DLLs is not reused
+        let rescanned = rescan(&query, &lookup_path, &res)?;
+        assert!(rescanned.contains("MSVCP140D.dll"));
+        Ok(())
+    }
+
+    #[test]
+    fn run_no_duplicates_within_level() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let lookup_path = LookupPath::deduce(&query);
+        let res = run(&query, &lookup_path)?;
+
+        // KERNEL32.dll is imported by both DepRunTest.exe and DepRunTestLib.dll
+        assert!(res.diagnostics().is_empty());
+        assert_eq!(res.get("KERNEL32.dll").unwrap().depth_first_appearance, 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn traversal_out_of_order() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        let filters = NameFilters::new(&query)?;
+        let root = Job {
+            dllname: "app.exe".to_owned(),
+            depth: 0,
+            path: None,
+            importer: None,
+        };
+        let mut traversal = Traversal::new(&query, &filters, std::slice::from_ref(&root));
+        let names = |jobs: &[Job]| -> Vec<(String, usize)> {
+            jobs.iter().map(|j| (j.dllname.clone(), j.depth)).collect()
+        };
+
+        let jobs = traversal.complete(&root, Ok((dll("app.exe", 0, &["a.dll", "b.dll"]), vec![])));
+        assert_eq!(
+            names(&jobs),
+            [("a.dll".to_owned(), 1), ("b.dll".to_owned(), 1)]
+        );
+        let (a, b) = (&jobs[0], &jobs[1]);
+        // the branch of b.dll is looked up first, and finds d.dll at depth 3
+        let c = traversal.complete(b, Ok((dll("b.dll", 1, &["c.dll"]), vec![])));
+        let d = traversal.complete(&c[0], Ok((dll("c.dll", 2, &["d.dll"]), vec![])));
+        let e = traversal.complete(&d[0], Ok((dll("d.dll", 3, &["e.dll"]), vec![])));
+        assert_eq!(names(&e), [("e.dll".to_owned(), 4)]);
+        // a.dll imports d.dll too: it is moved up with its dependencies, already queued
+        let jobs = traversal.complete(a, Ok((dll("a.dll", 1, &["D.DLL", "b.dll"]), vec![])));
+        assert!(jobs.is_empty());
+        assert!(traversal
+            .complete(&e[0], Ok((dll("e.dll", 4, &[]), vec![])))
+            .is_empty());
+
+        let exes = traversal.finish(DuplicatePolicy::FirstWins)?;
+        let depths: Vec<(&str, usize)> = exes
+            .sorted_by_first_appearance()
+            .iter()
+            .map(|e| (e.dllname.as_str(), e.depth_first_appearance))
+            .collect();
+        assert_eq!(
+            depths,
+            [
+                ("app.exe", 0),
+                ("a.dll", 1),
+                ("b.dll", 1),
+                ("c.dll", 2),
+                ("d.dll", 2),
+                ("e.dll", 3)
+            ]
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn run_error_context() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let temp_dir = tempfile::tempdir()?;
+        let dir = fs::canonicalize(temp_dir.path())?;
+        fs::copy(
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe"),
+            dir.join("DepRunTest.exe"),
+        )?;
+        let broken = dir.join("DepRunTestLib.dll");
+        fs::write(&broken, b"this is not a PE file")?;
+
+        let mut query = LookupQuery::deduce_from_executable_location(dir.join("DepRunTest.exe"))?;
+        query.parameters.skip_system_dlls = true;
+        let lookup_path = LookupPath::deduce(&query);
+        let err = run(&query, &lookup_path).unwrap_err();
+
+        assert_eq!(err.dllname(), Some("DepRunTestLib.dll"));
+        assert_eq!(err.path(), Some(broken.as_path()));
+        match &err {
+            LookupError::DependencyError {
+                importer, entry, ..
+            } => {
+                assert_eq!(importer.as_deref(), Some("DepRunTest.exe"));
+                assert!(entry.as_ref().unwrap().starts_with("ExecutableDir"));
+            }
+            _ => panic!("the error must name the DLL being scanned"),
+        }
         Ok(())
     }
 }
@@ -0,0 +1,395 @@
+//! Software bill of materials (SBOM) generation from the result of a scan
+//!
+//! The document follows the CycloneDX 1.5 or the SPDX 2.3 JSON format: each found file becomes a
+//! component (a package in SPDX), described by its file version, hashes (if computed) and
+//! Authenticode signers, and the dependency edges of the scan become the dependency graph of the
+//! document.
+
+use std::time::{Duration, SystemTime};
+
+use serde_json::{json, Value};
+
+use crate::common::{path_to_string, LookupError};
+use crate::executable::{Executable, Executables};
+
+/// Version of the CycloneDX specification the generated documents conform to
+pub const CYCLONEDX_SPEC_VERSION: &str = "1.5";
+
+/// Version of the SPDX specification the generated documents conform to
+pub const SPDX_VERSION: &str = "SPDX-2.3";
+
+/// Format of a software bill of materials
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Default)]
+pub enum SbomFormat {
+    #[default]
+    CycloneDx,
+    Spdx,
+}
+
+impl std::str::FromStr for SbomFormat {
+    type Err = LookupError;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "cyclonedx" => Ok(Self::CycloneDx),
+            "spdx" => Ok(Self::Spdx),
+            _ => Err(LookupError::ParseError(format!(
+                "unknown SBOM format {s} (expected cyclonedx or spdx)"
+            ))),
+        }
+    }
+}
+
+/// Build a JSON document in the given format listing the files found by the scan
+///
+/// `created` is the creation time recorded by SPDX documents.
+pub fn to_sbom(executables: &Executables, format: SbomFormat, created: SystemTime) -> Value {
+    match format {
+        SbomFormat::CycloneDx => to_cyclonedx(executables),
+        SbomFormat::Spdx => to_spdx(executables, created),
+    }
+}
+
+/// Build a CycloneDX JSON document listing the files found by the scan
+///
+/// API sets resolved without a file and missing DLLs are not included, since there is no file to
+/// describe; system DLLs are included and marked with a property, so that they can be filtered.
+/// The root executable is the component the document describes; if the scan has several roots
+/// (e.g. an executable and its plugins), they are all listed as application components instead.
+pub fn to_cyclonedx(executables: &Executables) -> Value {
+    let roots = executables.get_roots().unwrap_or_default();
+    let root = match roots.as_slice() {
+        [root] => Some(*root),
+        _ => None,
+    };
+    let components: Vec<Value> = executables
+        .iter()
+        .filter(|e| root.map(|r| r.dllname != e.dllname).unwrap_or(true) && is_component(e))
+        .map(component)
+        .collect();
+
+    let dependencies: Vec<Value> = executables
+        .iter()
+        .filter(|e| is_component(e))
+        .map(|e| {
+            let mut depends_on: Vec<String> = executables
+                .edges()
+                .iter()
+                .filter(|edge| edge.importer == e.dllname)
+                .filter(|edge| executables.get(&edge.importee).is_some_and(is_component))
+                .map(|edge| bom_ref(&edge.importee))
+                .collect();
+            depends_on.sort();
+            depends_on.dedup();
+            json!({ "ref": bom_ref(&e.dllname), "dependsOn": depends_on })
+        })
+        .collect();
+
+    let mut metadata = json!({
+        "tools": {
+            "components": [{
+                "type": "application",
+                "name": env!("CARGO_PKG_NAME"),
+                "version": env!("CARGO_PKG_VERSION"),
+            }]
+        }
+    });
+    if let Some(root) = root.filter(|r| is_component(r)) {
+        metadata["component"] = component(root);
+    }
+
+    json!({
+        "bomFormat": "CycloneDX",
+        "specVersion": CYCLONEDX_SPEC_VERSION,
+        "version": 1,
+        "metadata": metadata,
+        "components": components,
+        "dependencies": dependencies,
+    })
+}
+
+/// Build an SPDX JSON document listing the files found by the scan
+///
+/// The same files as in `to_cyclonedx` are listed, as packages; the document describes the root
+/// executables, and each import becomes a DEPENDS_ON relationship. The first Authenticode signer
+/// of a file is reported as the supplier of its package.
+pub fn to_spdx(executables: &Executables, created: SystemTime) -> Value {
+    let packages: Vec<&Executable> = executables.iter().filter(|e| is_component(e)).collect();
+    let spdx_id = |dllname: &str| {
+        let index = packages.iter().position(|p| p.dllname == dllname)?;
+        let name: String = dllname
+            .chars()
+            .map(|c| match c {
+                'a'..='z' | 'A'..='Z' | '0'..='9' | '.' | '-' => c,
+                _ => '-',
+            })
+            .collect();
+        Some(format!("SPDXRef-{index}-{name}"))
+    };
+
+    let mut relationships: Vec<Value> = executables
+        .get_roots()
+        .unwrap_or_default()
+        .iter()
+        .filter_map(|root| spdx_id(&root.dllname))
+        .map(|id| {
+            json!({
+                "spdxElementId": "SPDXRef-DOCUMENT",
+                "relationshipType": "DESCRIBES",
+                "relatedSpdxElement": id,
+            })
+        })
+        .collect();
+    let mut dependencies: Vec<(String, String)> = executables
+        .edges()
+        .iter()
+        .filter_map(|edge| Some((spdx_id(&edge.importer)?, spdx_id(&edge.importee)?)))
+        .collect();
+    dependencies.sort();
+    dependencies.dedup();
+    relationships.extend(dependencies.into_iter().map(|(importer, importee)| {
+        json!({
+            "spdxElementId": importer,
+            "relationshipType": "DEPENDS_ON",
+            "relatedSpdxElement": importee,
+        })
+    }));
+
+    let packages: Vec<Value> = packages
+        .iter()
+        .map(|e| {
+            let mut package = json!({
+                "SPDXID": spdx_id(&e.dllname),
+                "name": e.dllname,
+                "downloadLocation": "NOASSERTION",
+                "filesAnalyzed": false,
+                "primaryPackagePurpose": if e.depth_first_appearance == 0 { "APPLICATION" } else { "LIBRARY" },
+            });
+            if let Some(details) = e.details.as_ref() {
+                if let Some(version) = details.file_version.as_ref() {
+                    package["versionInfo"] = json!(version);
+                }
+                if let Some(hashes) = details.hashes.as_ref() {
+                    package["checksums"] = json!([
+                        { "algorithm": "SHA256", "checksumValue": hashes.sha256 },
+                        { "algorithm": "MD5", "checksumValue": hashes.md5 },
+                    ]);
+                }
+                package["supplier"] = match details.signers.as_ref().and_then(|s| s.first()) {
+                    Some(signer) => json!(format!("Organization: {signer}")),
+                    None => json!("NOASSERTION"),
+                };
+                if let Some(path) = details.full_path.as_deref() {
+                    package["comment"] = json!(format!("found at {}", path_to_string(path)));
+                }
+            }
+            package
+        })
+        .collect();
+
+    let name = executables
+        .get_roots()
+        .unwrap_or_default()
+        .first()
+        .map(|r| r.dllname.clone())
+        .unwrap_or_else(|| env!("CARGO_PKG_NAME").to_owned());
+    let seconds = created
+        .duration_since(SystemTime::UNIX_EPOCH)
+        .unwrap_or(Duration::ZERO)
+        .as_secs();
+    json!({
+        "spdxVersion": SPDX_VERSION,
+        "dataLicense": "CC0-1.0",
+        "SPDXID": "SPDXRef-DOCUMENT",
+        "name": name,
+        "documentNamespace": format!(
+            "https://spdx.org/spdxdocs/{}-{}-{seconds}",
+            env!("CARGO_PKG_NAME"),
+            bom_ref(&name)
+        ),
+        "creationInfo": {
+            "created": utc_timestamp(seconds),
+            "creators": [format!("Tool: {}-{}", env!("CARGO_PKG_NAME"), env!("CARGO_PKG_VERSION"))],
+        },
+        "packages": packages,
+        "relationships": relationships,
+    })
+}
+
+/// Format a time (in seconds since the Unix epoch) as required by SPDX, e.g. 2024-01-31T12:00:00Z
+fn utc_timestamp(seconds: u64) -> String {
+    let (days, time) = (seconds / 86400, seconds % 86400);
+    // civil date from the number of days since 1970-01-01 (proleptic Gregorian calendar)
+    let z = days + 719468;
+    let era = z / 146097;
+    let day_of_era = z % 146097;
+    let year_of_era =
+        (day_of_era - day_of_era / 1460 + day_of_era / 36524 - day_of_era / 146096) / 365;
+    let day_of_year = day_of_era - (365 * year_of_era + year_of_era / 4 - year_of_era / 100);
+    let mp = (5 * day_of_year + 2) / 153;
+    let day = day_of_year - (153 * mp + 2) / 5 + 1;
+    let month = if mp < 10 { mp + 3 } else { mp - 9 };
+    let year = year_of_era + era * 400 + u64::from(month <= 2);
+    format!(
+        "{year:04}-{month:02}-{day:02}T{:02}:{:02}:{:02}Z",
+        time / 3600,
+        time % 3600 / 60,
+        time % 60
+    )
+}
+
+/// Whether the executable is backed by a file that can be described in the SBOM
+fn is_component(e: &Executable) -> bool {
+    e.found
+        && e.details
+            .as_ref()
+            .map(|d| d.full_path.is_some() && !d.is_api_set)
+            .unwrap_or(false)
+}
+
+fn bom_ref(dllname: &str) -> String {
+    dllname.to_lowercase()
+}
+
+fn component(e: &Executable) -> Value {
+    let mut component = json!({
+        "type": if e.depth_first_appearance == 0 { "application" } else { "library" },
+        "bom-ref": bom_ref(&e.dllname),
+        "name": e.dllname,
+    });
+    if let Some(details) = e.details.as_ref() {
+        if let Some(version) = details.file_version.as_ref() {
+            component["version"] = json!(version);
+        }
+        if let Some(hashes) = details.hashes.as_ref() {
+            component["hashes"] = json!([
+                { "alg": "SHA-256", "content": hashes.sha256 },
+                { "alg": "MD5", "content": hashes.md5 },
+            ]);
+        }
+        let path = details
+            .full_path
+            .as_deref()
+            .map(path_to_string)
+            .unwrap_or_default();
+        component["properties"] = json!([
+            { "name": "dependency_runner:path", "value": path },
+            { "name": "dependency_runner:system", "value": details.is_system.to_string() },
+        ]);
+        if let Some(signers) = details.signers.as_ref().filter(|s| !s.is_empty()) {
+            component["publisher"] = json!(signers.join(", "));
+            for signer in signers {
+                component["properties"]
+                    .as_array_mut()
+                    .expect("properties are an array")
+                    .push(json!({ "name": "dependency_runner:signer", "value": signer }));
+            }
+        }
+    }
+    component
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::LookupError;
+    use crate::executable::Executables;
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+    use crate::sbom::{to_cyclonedx, to_spdx, utc_timestamp};
+    use serde_json::Value;
+    use std::time::{Duration, SystemTime};
+
+    #[test]
+    fn cyclonedx_document() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.compute_hashes = true;
+        let exes = run(&query, &LookupPath::deduce(&query))?;
+
+        let sbom = to_cyclonedx(&exes);
+        assert_eq!(sbom["bomFormat"], "CycloneDX");
+        assert_eq!(sbom["metadata"]["component"]["name"], "DepRunTest.exe");
+
+        let components = sbom["components"].as_array().unwrap();
+        let lib = components
+            .iter()
+            .find(|c| c["name"] == "DepRunTestLib.dll")
+            .unwrap();
+        assert_eq!(lib["hashes"][0]["alg"], "SHA-256");
+        assert_eq!(lib["hashes"][0]["content"].as_str().unwrap().len(), 64);
+        assert!(components.iter().all(|c| c["name"] != "DepRunTest.exe"));
+
+        let root_dependencies = sbom["dependencies"]
+            .as_array()
+            .unwrap()
+            .iter()
+            .find(|d| d["ref"] == "depruntest.exe")
+            .unwrap();
+        assert!(root_dependencies["dependsOn"]
+            .as_array()
+            .unwrap()
+            .contains(&"depruntestlib.dll".into()));
+
+        Ok(())
+    }
+
+    #[test]
+    fn spdx_document() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.compute_hashes = true;
+        // none of the test files is signed
+        let exes: Executables = run(&query, &LookupPath::deduce(&query))?
+            .iter()
+            .cloned()
+            .map(|mut e| {
+                if let Some(details) = e
+                    .details
+                    .as_mut()
+                    .filter(|_| e.dllname == "DepRunTestLib.dll")
+                {
+                    details.signers = Some(vec!["Example Corp".to_owned()]);
+                }
+                e
+            })
+            .collect();
+
+        let created = SystemTime::UNIX_EPOCH + Duration::from_secs(1_700_000_000);
+        let sbom = to_spdx(&exes, created);
+        assert_eq!(sbom["spdxVersion"], "SPDX-2.3");
+        assert_eq!(sbom["creationInfo"]["created"], "2023-11-14T22:13:20Z");
+
+        let packages = sbom["packages"].as_array().unwrap();
+        let package = |name: &str| packages.iter().find(|p| p["name"] == name).unwrap();
+        let (exe, lib) = (package("DepRunTest.exe"), package("DepRunTestLib.dll"));
+        assert_eq!(lib["checksums"][0]["algorithm"], "SHA256");
+        assert_eq!(lib["supplier"], "Organization: Example Corp");
+        assert_eq!(exe["supplier"], "NOASSERTION");
+
+        let relationships = sbom["relationships"].as_array().unwrap();
+        let relationship = |kind: &str, from: &Value, to: &Value| {
+            relationships.iter().any(|r| {
+                r["relationshipType"] == kind
+                    && r["spdxElementId"] == *from
+                    && r["relatedSpdxElement"] == *to
+            })
+        };
+        let document = Value::from("SPDXRef-DOCUMENT");
+        assert!(relationship("DESCRIBES", &document, &exe["SPDXID"]));
+        assert!(relationship("DEPENDS_ON", &exe["SPDXID"], &lib["SPDXID"]));
+
+        Ok(())
+    }
+
+    #[test]
+    fn utc_timestamps() {
+        assert_eq!(utc_timestamp(0), "1970-01-01T00:00:00Z");
+        assert_eq!(utc_timestamp(951_782_400), "2000-02-29T00:00:00Z");
+        assert_eq!(utc_timestamp(4_102_444_799), "2099-12-31T23:59:59Z");
+    }
+}
@@ -0,0 +1,147 @@
+//! State shared by the scans of several targets
+//!
+//! Scanning many executables one after the other (e.g. all the ones of a directory) would detect
+//! the Windows installation (API set schema, KnownDLLs...), list the system directories and parse
+//! the system DLLs again for each of them. A [`ScanSession`] does it once: the queries it deduces
+//! share the Windows installation, the lookup paths share the directory listings, and the files
+//! parsed by a scan are reused by the following ones if they did not change.
+
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+
+use crate::archive::{self, ArchiveFileProvider};
+use crate::common::LookupError;
+use crate::executable::Executables;
+use crate::path::LookupPath;
+use crate::query::LookupQuery;
+use crate::runner::rescan;
+use crate::system::{WinFileSystemCache, WindowsSystem};
+
+/// Caches shared by the scans of several targets
+#[derive(Default)]
+pub struct ScanSession {
+    /// Windows installations detected for the previous targets
+    systems: Vec<WindowsSystem>,
+    fs_cache: Arc<WinFileSystemCache>,
+    /// executables parsed by the previous scans, by name
+    parsed: Executables,
+    /// archive whose executables are scanned, if any (see `with_archive`)
+    archive_path: Option<PathBuf>,
+}
+
+impl ScanSession {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Session scanning the executables contained in an archive, whose files are read through the
+    /// given provider along with the ones of the local file system
+    pub fn with_archive(provider: ArchiveFileProvider) -> Self {
+        Self {
+            archive_path: Some(provider.archive_path().to_owned()),
+            fs_cache: Arc::new(WinFileSystemCache::with_provider(Arc::new(provider))),
+            ..Self::default()
+        }
+    }
+
+    /// Cache of the directory listings, to be set on the lookup paths not built by the session
+    /// (see `LookupPath::set_cache`)
+    pub fn cache(&self) -> &Arc<WinFileSystemCache> {
+        &self.fs_cache
+    }
+
+    /// Windows installation of the given target, detected only if it is not the one of a previous
+    /// target
+    #[cfg_attr(windows, allow(unused_variables))]
+    fn system_for(&mut self, target_exe: &Path) -> Result<Option<WindowsSystem>, LookupError> {
+        #[cfg(windows)]
+        if let Some(system) = self.systems.first() {
+            return Ok(Some(system.clone()));
+        }
+        #[cfg(not(windows))]
+        if let Some(system) = self.systems.iter().find(|s| {
+            s.win_dir
+                .parent()
+                .is_some_and(|root| target_exe.starts_with(root))
+        }) {
+            return Ok(Some(system.clone()));
+        }
+        #[cfg(windows)]
+        let system = Some(WindowsSystem::current()?);
+        #[cfg(not(windows))]
+        let system = WindowsSystem::from_exe_location(target_exe)?;
+        self.systems.extend(system.clone());
+        Ok(system)
+    }
+
+    /// Same as `LookupQuery::deduce_from_executable_location`, reusing the Windows installation
+    /// detected for a previous target
+    pub fn deduce_query<P: AsRef<Path>>(
+        &mut self,
+        target_exe: P,
+    ) -> Result<LookupQuery, LookupError> {
+        let target_exe = target_exe.as_ref();
+        let system = self.system_for(target_exe)?;
+        match &self.archive_path {
+            Some(archive_path) if target_exe.starts_with(archive_path) => {
+                archive::member_query(archive_path, target_exe, system)
+            }
+            _ => LookupQuery::deduce_with_system(target_exe, system),
+        }
+    }
+
+    /// Same as `LookupPath::deduce`, sharing the directory listings with the other lookup paths of
+    /// the session
+    pub fn deduce_lookup_path<'a>(&self, query: &'a LookupQuery) -> LookupPath<'a> {
+        let mut lookup_path = LookupPath::deduce(query);
+        lookup_path.set_cache(self.fs_cache.clone());
+        lookup_path
+    }
+
+    /// Scan the target of the query, reusing the unchanged files parsed by the previous scans of
+    /// the session (see `runner::rescan`)
+    pub fn scan(
+        &mut self,
+        query: &LookupQuery,
+        lookup_path: &LookupPath,
+    ) -> Result<Executables, LookupError> {
+        let executables = rescan(query, lookup_path, &self.parsed)?;
+        for exe in executables.iter() {
+            if exe.details.is_some() && !self.parsed.contains(&exe.dllname) {
+                self.parsed.insert(exe.clone());
+            }
+        }
+        Ok(executables)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::common::LookupError;
+    use crate::session::ScanSession;
+
+    #[test]
+    fn shared_caches() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let bin_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        let mut session = ScanSession::new();
+
+        let query = session.deduce_query(bin_dir.join("DepRunTest.exe"))?;
+        let lookup_path = session.deduce_lookup_path(&query);
+        let first = session.scan(&query, &lookup_path)?;
+        assert!(first.get("DepRunTestLib.dll").unwrap().found);
+        assert_eq!(first.scan_metrics().unwrap().files_reused, 0);
+
+        // the directories were already listed, and the DLL already parsed
+        let query = session.deduce_query(bin_dir.join("DepRunTestLib.dll"))?;
+        let lookup_path = session.deduce_lookup_path(&query);
+        let second = session.scan(&query, &lookup_path)?;
+        let metrics = second.scan_metrics().unwrap();
+        assert_eq!(metrics.cache_misses, 0);
+        assert!(metrics.files_reused >= 1);
+        assert!(second.get("KERNEL32.dll").is_some());
+        Ok(())
+    }
+}
@@ -0,0 +1,289 @@
+//! Windows shortcuts (.lnk files), as found on the desktop and in the start menu
+//!
+//! The target, arguments, working directory and icon location are read from the Shell Link binary
+//! format ([MS-SHLLINK]), so that the scan sees the executable the way the shortcut starts it.
+
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+
+use crate::common::{host_path, LookupError};
+use crate::registration::expand_environment_strings;
+
+const HEADER_SIZE: usize = 0x4C;
+const LINK_CLSID: [u8; 16] = [
+    0x01, 0x14, 0x02, 0x00, 0x00, 0x00, 0x00, 0x00, 0xC0, 0x00, 0x00, 0x00, 0x00, 0x00, 0x00, 0x46,
+];
+
+const HAS_LINK_TARGET_ID_LIST: u32 = 0x1;
+const HAS_LINK_INFO: u32 = 0x2;
+const HAS_NAME: u32 = 0x4;
+const HAS_RELATIVE_PATH: u32 = 0x8;
+const HAS_WORKING_DIR: u32 = 0x10;
+const HAS_ARGUMENTS: u32 = 0x20;
+const HAS_ICON_LOCATION: u32 = 0x40;
+const IS_UNICODE: u32 = 0x80;
+
+const VOLUME_ID_AND_LOCAL_BASE_PATH: u32 = 0x1;
+const COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX: u32 = 0x2;
+
+const ENVIRONMENT_VARIABLE_DATA_BLOCK: u32 = 0xA000_0001;
+
+/// Content of a shortcut relevant to the scan, with the paths as written in the file
+#[derive(Debug, Clone, Default, PartialEq, Eq)]
+pub struct Shortcut {
+    /// absolute path of the target, as resolved when the shortcut was created
+    pub target_path: Option<String>,
+    /// path of the target containing environment variables (e.g. %ProgramFiles%\app\app.exe)
+    pub expandable_target_path: Option<String>,
+    /// path of the target relative to the shortcut
+    pub relative_path: Option<String>,
+    pub working_directory: Option<String>,
+    pub arguments: Option<String>,
+    /// file containing the icon, e.g. %SystemRoot%\System32\shell32.dll
+    pub icon_location: Option<String>,
+    pub description: Option<String>,
+}
+
+fn parse_error(message: &str) -> LookupError {
+    LookupError::ParseError(format!("invalid shortcut: {message}"))
+}
+
+fn read_u16(data: &[u8], offset: usize) -> Result<u16, LookupError> {
+    data.get(offset..offset + 2)
+        .map(|b| u16::from_le_bytes([b[0], b[1]]))
+        .ok_or_else(|| parse_error("truncated file"))
+}
+
+fn read_u32(data: &[u8], offset: usize) -> Result<u32, LookupError> {
+    data.get(offset..offset + 4)
+        .map(|b| u32::from_le_bytes([b[0], b[1], b[2], b[3]]))
+        .ok_or_else(|| parse_error("truncated file"))
+}
+
+/// Null-terminated string in the code page of the system that created the shortcut, decoded as
+/// Latin-1 (exact for the ASCII paths)
+fn read_ansi_string(data: &[u8], offset: usize) -> Result<String, LookupError> {
+    let bytes = data
+        .get(offset..)
+        .ok_or_else(|| parse_error("truncated file"))?;
+    Ok(bytes
+        .iter()
+        .take_while(|&&b| b != 0)
+        .map(|&b| b as char)
+        .collect())
+}
+
+/// Null-terminated UTF-16 string
+fn read_unicode_string(data: &[u8], offset: usize) -> Result<String, LookupError> {
+    let bytes = data
+        .get(offset..)
+        .ok_or_else(|| parse_error("truncated file"))?;
+    let units: Vec<u16> = bytes
+        .chunks_exact(2)
+        .map(|c| u16::from_le_bytes([c[0], c[1]]))
+        .take_while(|&u| u != 0)
+        .collect();
+    Ok(String::from_utf16_lossy(&units))
+}
+
+/// Target path stored in the LinkInfo structure starting at the beginning of `info`
+fn read_link_info(info: &[u8]) -> Result<Option<String>, LookupError> {
+    let header_size = read_u32(info, 4)? as usize;
+    let flags = read_u32(info, 8)?;
+    // string whose offset is in the given fields of the header, preferably the Unicode one (only
+    // present in the larger headers); empty if the offset is zero
+    let string_at = |ansi_field: usize, unicode_field: usize| -> Result<String, LookupError> {
+        let unicode_offset = if header_size >= 0x24 {
+            read_u32(info, unicode_field)? as usize
+        } else {
+            0
+        };
+        let ansi_offset = read_u32(info, ansi_field)? as usize;
+        if unicode_offset != 0 {
+            read_unicode_string(info, unicode_offset)
+        } else if ansi_offset != 0 {
+            read_ansi_string(info, ansi_offset)
+        } else {
+            Ok(String::new())
+        }
+    };
+    let suffix = string_at(0x18, 0x20)?;
+    if flags & VOLUME_ID_AND_LOCAL_BASE_PATH != 0 {
+        return Ok(Some(string_at(0x10, 0x1C)? + &suffix));
+    }
+    if flags & COMMON_NETWORK_RELATIVE_LINK_AND_PATH_SUFFIX != 0 {
+        let link = info
+            .get(read_u32(info, 0x14)? as usize..)
+            .ok_or_else(|| parse_error("truncated file"))?;
+        let net_name_offset = read_u32(link, 8)?;
+        let net_name = if net_name_offset > 0x14 {
+            read_unicode_string(link, read_u32(link, 0x14)? as usize)?
+        } else {
+            read_ansi_string(link, net_name_offset as usize)?
+        };
+        return Ok(Some(format!("{net_name}\\{suffix}")));
+    }
+    Ok(None)
+}
+
+impl Shortcut {
+    /// Read a .lnk file
+    pub fn read<P: AsRef<Path>>(path: P) -> Result<Self, LookupError> {
+        Self::parse(&fs::read(path.as_ref())?)
+    }
+
+    /// Parse the content of a .lnk file
+    pub fn parse(data: &[u8]) -> Result<Self, LookupError> {
+        if read_u32(data, 0)? as usize != HEADER_SIZE || data.get(4..20) != Some(&LINK_CLSID[..]) {
+            return Err(parse_error("not a Shell Link file"));
+        }
+        let flags = read_u32(data, 0x14)?;
+        let mut shortcut = Self::default();
+        let mut offset = HEADER_SIZE;
+
+        if flags & HAS_LINK_TARGET_ID_LIST != 0 {
+            // the shell namespace items of the target are not needed: the LinkInfo has its path
+            offset += 2 + read_u16(data, offset)? as usize;
+        }
+        if flags & HAS_LINK_INFO != 0 {
+            let size = read_u32(data, offset)? as usize;
+            let info = data
+                .get(offset..offset + size)
+                .ok_or_else(|| parse_error("truncated file"))?;
+            shortcut.target_path = read_link_info(info)?;
+            offset += size;
+        }
+
+        let unicode = flags & IS_UNICODE != 0;
+        let mut read_string_data = |flag: u32| -> Result<Option<String>, LookupError> {
+            if flags & flag == 0 {
+                return Ok(None);
+            }
+            let length = read_u16(data, offset)? as usize;
+            let size = if unicode { 2 * length } else { length };
+            let bytes = data
+                .get(offset + 2..offset + 2 + size)
+                .ok_or_else(|| parse_error("truncated file"))?;
+            offset += 2 + size;
+            Ok(Some(if unicode {
+                let units: Vec<u16> = bytes
+                    .chunks_exact(2)
+                    .map(|c| u16::from_le_bytes([c[0], c[1]]))
+                    .collect();
+                String::from_utf16_lossy(&units)
+            } else {
+                bytes.iter().map(|&b| b as char).collect()
+            }))
+        };
+        shortcut.description = read_string_data(HAS_NAME)?;
+        shortcut.relative_path = read_string_data(HAS_RELATIVE_PATH)?;
+        shortcut.working_directory = read_string_data(HAS_WORKING_DIR)?;
+        shortcut.arguments = read_string_data(HAS_ARGUMENTS)?;
+        shortcut.icon_location = read_string_data(HAS_ICON_LOCATION)?;
+
+        // extra data blocks, until the terminal one (smaller than 4 bytes)
+        while let Ok(size) = read_u32(data, offset) {
+            let size = size as usize;
+            if size < 8 {
+                break;
+            }
+            if read_u32(data, offset + 4)? == ENVIRONMENT_VARIABLE_DATA_BLOCK {
+                let unicode_target = read_unicode_string(data, offset + 8 + 260)?;
+                shortcut.expandable_target_path = Some(if unicode_target.is_empty() {
+                    read_ansi_string(data, offset + 8)?
+                } else {
+                    unicode_target
+                })
+                .filter(|t| !t.is_empty());
+            }
+            offset += size;
+        }
+
+        Ok(shortcut)
+    }
+
+    /// Path on this host of the target of the shortcut located at `shortcut_path`
+    ///
+    /// The path with environment variables is preferred, then the absolute one, then the one
+    /// relative to the shortcut; the first one found on this host is returned. Elsewhere than on
+    /// Windows, the absolute paths are looked up under the ancestors of the shortcut (see
+    /// `common::host_path`).
+    pub fn target_on_host<P: AsRef<Path>>(&self, shortcut_path: P) -> Option<PathBuf> {
+        let shortcut_dir = shortcut_path.as_ref().parent().unwrap_or(Path::new(""));
+        let candidates: Vec<PathBuf> = self
+            .expandable_target_path
+            .iter()
+            .map(|t| expand_environment_strings(t, None))
+            // variables unknown on this host (e.g. %USERPROFILE%) stay unexpanded
+            .filter(|t| !t.contains('%'))
+            .chain(self.target_path.iter().cloned())
+            .map(|t| host_path(&t, shortcut_dir))
+            .chain(
+                self.relative_path
+                    .iter()
+                    .map(|r| shortcut_dir.join(r.replace('\\', "/"))),
+            )
+            .collect();
+        candidates
+            .iter()
+            .find(|c| c.is_file())
+            .or(candidates.first())
+            .cloned()
+    }
+
+    /// Path on this host of the working directory of the shortcut located at `shortcut_path`, if
+    /// it sets one
+    pub fn working_directory_on_host<P: AsRef<Path>>(&self, shortcut_path: P) -> Option<PathBuf> {
+        let shortcut_dir = shortcut_path.as_ref().parent().unwrap_or(Path::new(""));
+        self.working_directory
+            .as_deref()
+            .filter(|w| !w.is_empty())
+            .map(|w| host_path(&expand_environment_strings(w, None), shortcut_dir))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use std::path::PathBuf;
+
+    use crate::common::LookupError;
+    use crate::query::LookupQuery;
+    use crate::shortcut::Shortcut;
+
+    #[test]
+    fn read_shortcut() -> Result<(), LookupError> {
+        let d = PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let shortcut_path = d.join("test_data/shortcut/DepRunTest.lnk");
+        let shortcut = Shortcut::read(&shortcut_path)?;
+        assert_eq!(
+            shortcut.target_path.as_deref(),
+            Some(
+                r"C:\Users\dev\test_data\test_project1\DepRunTest\build-same-output\bin\Debug\DepRunTest.exe"
+            )
+        );
+        assert_eq!(
+            shortcut.arguments.as_deref(),
+            Some(r#"--verbose "some file.txt""#)
+        );
+        assert_eq!(
+            shortcut.icon_location.as_deref(),
+            Some(r"%SystemRoot%\System32\shell32.dll")
+        );
+        assert_eq!(shortcut.description.as_deref(), Some("DepRunTest"));
+
+        let query = LookupQuery::from_shortcut(&shortcut_path)?;
+        let exe_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        assert_eq!(query.target.target_exe, exe_dir.join("DepRunTest.exe"));
+        assert_eq!(query.target.app_dir, exe_dir);
+        // the working directory is not relocated on Windows
+        #[cfg(not(windows))]
+        assert_eq!(
+            query.target.working_dir,
+            d.join("test_data/test_project1/DepRunTest/build-vcxproj-user")
+        );
+
+        assert!(Shortcut::parse(b"not a shortcut").is_err());
+        Ok(())
+    }
+}
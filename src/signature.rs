@@ -0,0 +1,291 @@
+//! Names of the signers of Authenticode-signed files
+//!
+//! The certificate table of a PE file (pointed to by its security data directory) holds
+//! `WIN_CERTIFICATE` entries, each one wrapping a PKCS#7 SignedData structure. The common name of
+//! the certificate of each signer is read from it, to describe the file; the signatures are not
+//! verified.
+
+/// `WIN_CERTIFICATE` entries holding a PKCS#7 SignedData structure
+const WIN_CERT_TYPE_PKCS_SIGNED_DATA: u16 = 0x0002;
+
+const TAG_INTEGER: u8 = 0x02;
+const TAG_SEQUENCE: u8 = 0x30;
+const TAG_SET: u8 = 0x31;
+const TAG_OID: u8 = 0x06;
+/// [0] of a constructed context-specific element, e.g. the certificates of a SignedData structure
+const TAG_CONTEXT_0: u8 = 0xa0;
+
+/// id-at-commonName (2.5.4.3)
+const OID_COMMON_NAME: &[u8] = &[0x55, 0x04, 0x03];
+/// id-at-organizationName (2.5.4.10), used when a certificate has no common name
+const OID_ORGANIZATION_NAME: &[u8] = &[0x55, 0x04, 0x0a];
+
+/// Names of the signers found in the certificate table of a PE file, in order and without
+/// duplicates
+///
+/// Malformed or unsupported entries (e.g. encoded with indefinite lengths) are skipped.
+pub fn signer_names(certificate_table: &[u8]) -> Vec<String> {
+    let mut names: Vec<String> = Vec::new();
+    let mut rest = certificate_table;
+    while rest.len() >= 8 {
+        let length = u32::from_le_bytes([rest[0], rest[1], rest[2], rest[3]]) as usize;
+        let certificate_type = u16::from_le_bytes([rest[6], rest[7]]);
+        let Some(certificate) = rest.get(8..length) else {
+            break;
+        };
+        if certificate_type == WIN_CERT_TYPE_PKCS_SIGNED_DATA {
+            for name in signed_data_signers(certificate).unwrap_or_default() {
+                if !names.contains(&name) {
+                    names.push(name);
+                }
+            }
+        }
+        // the entries are aligned on 8 bytes
+        match length.checked_next_multiple_of(8) {
+            Some(next) if next <= rest.len() => rest = &rest[next..],
+            _ => break,
+        }
+    }
+    names
+}
+
+/// Element of a DER encoding
+#[derive(Clone, Copy)]
+struct Element<'a> {
+    tag: u8,
+    content: &'a [u8],
+}
+
+/// Split the first element of a DER encoding from the data following it
+fn read_element(data: &[u8]) -> Option<(Element<'_>, &[u8])> {
+    let (&tag, data) = data.split_first()?;
+    // tags spanning several bytes are not used by the structures read here
+    if tag & 0x1f == 0x1f {
+        return None;
+    }
+    let (&first, data) = data.split_first()?;
+    let (length, data) = if first < 0x80 {
+        (first as usize, data)
+    } else {
+        // indefinite lengths (0x80) are only allowed by BER
+        let count = (first & 0x7f) as usize;
+        if count == 0 || count > 4 {
+            return None;
+        }
+        let bytes = data.get(..count)?;
+        let length = bytes.iter().fold(0usize, |l, &b| (l << 8) | b as usize);
+        (length, &data[count..])
+    };
+    let content = data.get(..length)?;
+    Some((Element { tag, content }, &data[length..]))
+}
+
+/// Elements contained in a constructed element, e.g. a SEQUENCE
+fn elements(mut data: &[u8]) -> Option<Vec<Element<'_>>> {
+    let mut elements = Vec::new();
+    while !data.is_empty() {
+        let (element, rest) = read_element(data)?;
+        elements.push(element);
+        data = rest;
+    }
+    Some(elements)
+}
+
+/// Names of the signers of a PKCS#7 ContentInfo structure wrapping a SignedData one
+fn signed_data_signers(der: &[u8]) -> Option<Vec<String>> {
+    let (content_info, _) = read_element(der)?;
+    let wrapped = *elements(content_info.content)?
+        .get(1)
+        .filter(|e| e.tag == TAG_CONTEXT_0)?;
+    let (signed_data, _) = read_element(wrapped.content)?;
+    let fields = elements(signed_data.content)?;
+    let certificates = match fields.iter().find(|e| e.tag == TAG_CONTEXT_0) {
+        Some(certificates) => elements(certificates.content)?,
+        None => Vec::new(),
+    };
+    let signer_infos = fields.iter().rev().find(|e| e.tag == TAG_SET)?;
+
+    let mut names = Vec::new();
+    for signer_info in elements(signer_infos.content)? {
+        // version 1 signers are identified by the issuer and serial number of their certificate
+        let Some(issuer_and_serial) = elements(signer_info.content)?
+            .get(1)
+            .copied()
+            .filter(|e| e.tag == TAG_SEQUENCE)
+        else {
+            continue;
+        };
+        let id = elements(issuer_and_serial.content)?;
+        let (Some(issuer), Some(serial)) = (id.first(), id.get(1)) else {
+            continue;
+        };
+        let subject = certificates.iter().find_map(|certificate| {
+            let (cert_serial, cert_issuer, subject) = certificate_identity(certificate)?;
+            (cert_serial == serial.content && cert_issuer == issuer.content).then_some(subject)
+        });
+        if let Some(name) = subject.and_then(display_name) {
+            names.push(name);
+        }
+    }
+    Some(names)
+}
+
+/// Serial number, issuer and subject of an X.509 certificate
+fn certificate_identity<'a>(
+    certificate: &Element<'a>,
+) -> Option<(&'a [u8], &'a [u8], Element<'a>)> {
+    let tbs_certificate = *elements(certificate.content)?.first()?;
+    let mut fields = elements(tbs_certificate.content)?;
+    // the version is optional, and tagged [0]
+    if fields.first()?.tag == TAG_CONTEXT_0 {
+        fields.remove(0);
+    }
+    // serialNumber, signature, issuer, validity, subject
+    let serial = fields.first().filter(|e| e.tag == TAG_INTEGER)?;
+    let issuer = fields.get(2).filter(|e| e.tag == TAG_SEQUENCE)?;
+    let subject = fields.get(4).filter(|e| e.tag == TAG_SEQUENCE)?;
+    Some((serial.content, issuer.content, *subject))
+}
+
+/// Common name of a distinguished name, or its organization if it has none
+fn display_name(name: Element) -> Option<String> {
+    let mut organization = None;
+    for relative_name in elements(name.content)? {
+        for attribute in elements(relative_name.content)? {
+            let attribute = elements(attribute.content)?;
+            let (Some(oid), Some(value)) = (attribute.first(), attribute.get(1)) else {
+                continue;
+            };
+            if oid.tag != TAG_OID {
+                continue;
+            }
+            if oid.content == OID_COMMON_NAME {
+                return decode_string(value);
+            } else if oid.content == OID_ORGANIZATION_NAME {
+                organization = decode_string(value);
+            }
+        }
+    }
+    organization
+}
+
+/// Text of an ASN.1 string
+fn decode_string(value: &Element) -> Option<String> {
+    match value.tag {
+        // BMPString
+        0x1e => {
+            let units: Vec<u16> = value
+                .content
+                .chunks_exact(2)
+                .map(|c| u16::from_be_bytes([c[0], c[1]]))
+                .collect();
+            Some(String::from_utf16_lossy(&units))
+        }
+        // UTF8String, PrintableString, TeletexString, IA5String
+        0x0c | 0x13 | 0x14 | 0x16 => Some(String::from_utf8_lossy(value.content).into_owned()),
+        _ => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::signature::signer_names;
+
+    /// DER encoding of an element
+    fn der(tag: u8, content: &[u8]) -> Vec<u8> {
+        let mut encoded = vec![tag];
+        match content.len() {
+            len if len < 0x80 => encoded.push(len as u8),
+            len if len < 0x100 => encoded.extend([0x81, len as u8]),
+            len => encoded.extend([0x82, (len >> 8) as u8, len as u8]),
+        }
+        encoded.extend(content);
+        encoded
+    }
+
+    fn name(oid: &[u8], value: &str) -> Vec<u8> {
+        let attribute = [der(0x06, oid), der(0x0c, value.as_bytes())].concat();
+        der(0x30, &der(0x31, &der(0x30, &attribute)))
+    }
+
+    fn certificate(serial: u8, issuer: &[u8], subject: &[u8]) -> Vec<u8> {
+        let algorithm = der(0x30, &der(0x06, &[0x2a, 0x86, 0x48]));
+        let tbs = [
+            der(0xa0, &der(0x02, &[2])),
+            der(0x02, &[serial]),
+            algorithm.clone(),
+            issuer.to_vec(),
+            der(0x30, &[]),
+            subject.to_vec(),
+        ]
+        .concat();
+        der(
+            0x30,
+            &[der(0x30, &tbs), algorithm, der(0x03, &[0])].concat(),
+        )
+    }
+
+    fn win_certificate(
+        certificates: &[Vec<u8>],
+        signer_serial: u8,
+        signer_issuer: &[u8],
+    ) -> Vec<u8> {
+        let signer_info = der(
+            0x30,
+            &[
+                der(0x02, &[1]),
+                der(
+                    0x30,
+                    &[signer_issuer.to_vec(), der(0x02, &[signer_serial])].concat(),
+                ),
+            ]
+            .concat(),
+        );
+        let signed_data = der(
+            0x30,
+            &[
+                der(0x02, &[1]),
+                der(0x31, &[]),
+                der(0x30, &der(0x06, &[0x2b, 0x06])),
+                der(0xa0, &certificates.concat()),
+                der(0x31, &signer_info),
+            ]
+            .concat(),
+        );
+        let content_info = der(
+            0x30,
+            &[der(0x06, &[0x2a, 0x86]), der(0xa0, &signed_data)].concat(),
+        );
+        let length = 8 + content_info.len();
+        let mut entry = (length as u32).to_le_bytes().to_vec();
+        entry.extend(0x0200u16.to_le_bytes());
+        entry.extend(0x0002u16.to_le_bytes());
+        entry.extend(content_info);
+        entry.resize(length.next_multiple_of(8), 0);
+        entry
+    }
+
+    #[test]
+    fn signers_of_certificate_table() {
+        let ca = name(&[0x55, 0x04, 0x03], "Example Root CA");
+        let publisher = name(&[0x55, 0x04, 0x03], "Example Corp");
+        let organization = name(&[0x55, 0x04, 0x0a], "Other Corp");
+        let certificates = [
+            certificate(7, &ca, &ca),
+            certificate(42, &ca, &publisher),
+            certificate(43, &ca, &organization),
+        ];
+        let table = [
+            win_certificate(&certificates, 42, &ca),
+            win_certificate(&certificates, 43, &ca),
+            win_certificate(&certificates, 42, &ca),
+        ]
+        .concat();
+        assert_eq!(signer_names(&table), vec!["Example Corp", "Other Corp"]);
+
+        // no certificate matching the signer, truncated table
+        assert!(signer_names(&win_certificate(&certificates, 1, &ca)).is_empty());
+        assert!(signer_names(&table[..table.len() / 6]).is_empty());
+        assert!(signer_names(&[]).is_empty());
+    }
+}
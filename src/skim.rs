@@ -4,40 +4,29 @@ extern crate skim;
 
 use skim::prelude::*;
 
-use crate::common::readable_canonical_path;
-use crate::executable::Executables;
+use crate::common::readable_path;
+use crate::executable::{Executables, SymbolMatch, SymbolPattern, SymbolUse};
 use crate::pe::demangle_symbol;
 
-enum SymbolLocation {
-    Exported,
-    Imported(String),
-}
-
-struct SymbolItem {
-    symbol: String,
-    dllname: String,
-    location: SymbolLocation,
-}
+struct SymbolItem(SymbolMatch);
 
 impl SkimItem for SymbolItem {
-    fn text(&self) -> Cow<str> {
-        if let Ok(demangled) = demangle_symbol(&self.symbol) {
-            Cow::from(demangled)
-        } else {
-            Cow::Borrowed(&self.symbol)
-        }
+    fn text(&self) -> Cow<'_, str> {
+        Cow::Borrowed(self.0.demangled.as_deref().unwrap_or(&self.0.symbol))
     }
 
     fn preview(&self, _context: PreviewContext) -> ItemPreview {
-        let demangled = demangle_symbol(&self.symbol).unwrap_or(self.symbol.clone());
-        let export_msg = if let SymbolLocation::Imported(dll) = &self.location {
+        let export_msg = if let SymbolUse::Imported(dll) = &self.0.usage {
             format!("imported from {}", dll)
         } else {
             "exported".to_string()
         };
         ItemPreview::Text(format!(
             "dll:\n\t{}\n\nlocation:\n\t{}\n\nsymbol:\n\t{}\n\nraw symbol:\n\t{}",
-            self.dllname, export_msg, &demangled, self.symbol
+            self.0.dllname,
+            export_msg,
+            self.0.demangled.as_deref().unwrap_or(&self.0.symbol),
+            self.0.symbol
         ))
     }
 }
@@ -52,7 +41,7 @@ struct ExecutableItem {
 }
 
 impl SkimItem for ExecutableItem {
-    fn text(&self) -> Cow<str> {
+    fn text(&self) -> Cow<'_, str> {
         Cow::Borrowed(&self.name)
     }
 
@@ -105,31 +94,19 @@ pub fn skim_symbols(exes: &Executables, selected_dlls: Option<Vec<String>>) -> O
     crossbeam::scope(|s| {
         // Producer thread
         s.spawn(|_| {
-            for e in exes.sorted_by_first_appearance() {
-                if selected_dlls
+            let all = SymbolPattern::glob("*").expect("valid pattern");
+            // the symbols are demangled one at a time, and shown while the others are read
+            let selected = exes.iter().filter(|e| {
+                selected_dlls
                     .as_ref()
                     .map(|sd| sd.contains(&e.dllname))
                     .unwrap_or(true)
-                {
-                    if let Some(d) = e.details.as_ref() {
-                        if let Some(syms) = d.symbols.as_ref() {
-                            for syms in &syms.exported {
-                                let _ = tx_item.send(Arc::new(SymbolItem {
-                                    symbol: syms.to_string(),
-                                    dllname: e.dllname.clone(),
-                                    location: SymbolLocation::Exported,
-                                }));
-                            }
-                            for syms in &syms.imported {
-                                for sym in syms.1 {
-                                    let _ = tx_item.send(Arc::new(SymbolItem {
-                                        symbol: sym.to_string(),
-                                        dllname: e.dllname.clone(),
-                                        location: SymbolLocation::Imported(syms.0.to_string()),
-                                    }));
-                                }
-                            }
-                        }
+            });
+            for exe in selected {
+                for found in exe.find_symbol(&all) {
+                    if tx_item.send(Arc::new(SymbolItem(found))).is_err() {
+                        // skim was closed
+                        return;
                     }
                 }
             }
@@ -142,9 +119,7 @@ pub fn skim_symbols(exes: &Executables, selected_dlls: Option<Vec<String>>) -> O
 
         let was_aborted = output.as_ref().map(|o| o.is_abort).unwrap_or(true);
 
-        let selected_items = output
-            .map(|out| out.selected_items)
-            .unwrap_or_else(Vec::new);
+        let selected_items = output.map(|out| out.selected_items).unwrap_or_default();
 
         if !was_aborted {
             Some(
@@ -168,7 +143,8 @@ pub fn skim_dlls(exes: &Executables) -> Option<Vec<String>> {
         .multi(true)
         .header(Some(
             "Ctrl+g or ESC to quit\nSelect DLLs with TAB and press Enter to inspect symbols"
-            .to_string()))
+                .to_string(),
+        ))
         .prompt("Fuzzy query: >".to_string())
         .build()
         .unwrap();
@@ -178,13 +154,13 @@ pub fn skim_dlls(exes: &Executables) -> Option<Vec<String>> {
     crossbeam::scope(|s| {
         // Producer thread
         s.spawn(|_| {
-            for e in exes.sorted_by_first_appearance() {
+            for e in exes.iter() {
                 let name = e.dllname.clone();
                 let path = e
                     .details
                     .as_ref()
-                    .map(|d| readable_canonical_path(&d.full_path).ok())
-                    .unwrap_or(None);
+                    .and_then(|d| d.full_path.as_deref())
+                    .map(readable_path);
                 let kind = e
                     .details
                     .as_ref()
@@ -218,7 +194,7 @@ pub fn skim_dlls(exes: &Executables) -> Option<Vec<String>> {
                                         .map(|ms| {
                                             format!(
                                                 "{} ({})",
-                                                demangle_symbol(&ms).unwrap_or(ms.to_string()),
+                                                demangle_symbol(ms).unwrap_or(ms.to_string()),
                                                 idll
                                             )
                                         })
@@ -235,7 +211,7 @@ pub fn skim_dlls(exes: &Executables) -> Option<Vec<String>> {
                         d.symbols.as_ref().map(|s| {
                             s.exported
                                 .iter()
-                                .map(|ms| demangle_symbol(&ms).unwrap_or(ms.to_string()))
+                                .map(|ms| demangle_symbol(ms).unwrap_or(ms.to_string()))
                                 .collect::<Vec<String>>()
                         })
                     })
@@ -257,9 +233,7 @@ pub fn skim_dlls(exes: &Executables) -> Option<Vec<String>> {
 
         let was_aborted = output.as_ref().map(|o| o.is_abort).unwrap_or(true);
 
-        let selected_items = output
-            .map(|out| out.selected_items)
-            .unwrap_or_else(Vec::new);
+        let selected_items = output.map(|out| out.selected_items).unwrap_or_default();
 
         if !was_aborted {
             Some(
@@ -3,16 +3,23 @@
 #[cfg(windows)]
 extern crate winapi;
 use crate::apiset;
-use crate::common::LookupError;
-#[cfg(windows)]
+use crate::common::{decanonicalize, FileOperation, Instant, LookupError};
+use crate::executable::Architecture;
+#[cfg(all(windows, feature = "knowndlls"))]
 use crate::knowndlls;
+use crate::provider::{FileProvider, LocalFileSystem};
 use fs_err as fs;
+use rayon::prelude::*;
+use serde::Serialize;
 use std::collections::HashMap;
 #[cfg(windows)]
 use std::ffi::OsString;
 #[cfg(windows)]
 use std::os::windows::ffi::OsStringExt;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicU64, AtomicUsize, Ordering};
+use std::sync::{Arc, PoisonError, RwLock, RwLockReadGuard};
+use std::time::{Duration, SystemTime};
 
 /// List of DLLs provided by the operating system and hardcoded into the loader
 /// If a DLL with this name is required, the OS will not perform any further lookup but load the
@@ -34,6 +41,50 @@ impl KnownDLLList {
     }
 }
 
+/// Version of a Windows installation, as read from the version resource of its kernel image
+#[derive(Eq, PartialEq, Ord, PartialOrd, Hash, Debug, Clone, Copy, Serialize)]
+pub struct WindowsVersion {
+    pub major: u16,
+    pub minor: u16,
+    pub build: u16,
+    pub revision: u16,
+}
+
+impl WindowsVersion {
+    /// Read the file version of the kernel image (ntoskrnl.exe) in the given system directory
+    pub fn from_sys_dir<P: AsRef<Path>>(sys_dir: P) -> Option<Self> {
+        let filemap = pelite::FileMap::open(&sys_dir.as_ref().join("ntoskrnl.exe")).ok()?;
+        let pefile = pelite::PeFile::from_bytes(&filemap).ok()?;
+        let version_info = pefile.resources().ok()?.version_info().ok()?;
+        let file_version = version_info.fixed()?.dwFileVersion;
+        Some(Self {
+            major: file_version.Major,
+            minor: file_version.Minor,
+            build: file_version.Patch,
+            revision: file_version.Build,
+        })
+    }
+}
+
+impl std::fmt::Display for WindowsVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(
+            f,
+            "{}.{}.{}.{}",
+            self.major, self.minor, self.build, self.revision
+        )
+    }
+}
+
+/// A Windows installation found on the filesystem, which can be used as reference for a scan
+#[derive(Eq, PartialEq, Debug, Clone, Serialize)]
+pub struct WindowsRootCandidate {
+    /// Path to the partition root (the directory containing Windows\System32)
+    pub root: PathBuf,
+    /// Version of the installation, if it could be read from the kernel image
+    pub version: Option<WindowsVersion>,
+}
+
 // supported DLL search modes: standard for desktop application, safe or unsafe, as specified by the registry (if running on Windows)
 // TODO: read HKEY_LOCAL_MACHINE\System\CurrentControlSet\Control\Session Manager\SafeDllSearchMode  and pick mode accordingly
 // the other modes are activated programmatically, and there is no hope to be able to handle that properly
@@ -52,6 +103,8 @@ pub struct WindowsSystem {
     pub sys_dir: PathBuf,
     // sys16_dir ignored, since it is not supported on 64-bit systems
     pub system_path: Option<Vec<PathBuf>>,
+    /// Version of the installation, if it could be read from the kernel image
+    pub version: Option<WindowsVersion>,
 }
 
 impl WindowsSystem {
@@ -64,7 +117,7 @@ impl WindowsSystem {
         let apiset = match apiset::parse_apiset(sys_dir.join("apisetschema.dll")) {
             Ok(apiset) => Some(apiset),
             Err(e) => {
-                eprintln!("{:?}", e);
+                tracing::warn!(error = %e, "could not read the API set schema");
                 None
             }
         };
@@ -77,12 +130,17 @@ impl WindowsSystem {
                     .collect())
             })
             .ok();
+        #[cfg(feature = "knowndlls")]
         let known_dlls = knowndlls::get_known_dlls().ok().map(|v| KnownDLLList {
             entries: v
                 .iter()
                 .map(|kd| (kd.to_lowercase(), sys_dir.join(kd)))
                 .collect(),
         });
+        // without the knowndlls feature, the list is not read from the object manager
+        #[cfg(not(feature = "knowndlls"))]
+        let known_dlls = None;
+        let version = WindowsVersion::from_sys_dir(&sys_dir);
         Ok(Self {
             safe_dll_search_mode_on: None,
             apiset_map: apiset,
@@ -90,6 +148,7 @@ impl WindowsSystem {
             win_dir,
             sys_dir,
             system_path: path,
+            version,
         })
     }
 
@@ -133,6 +192,7 @@ impl WindowsSystem {
                 safe_dll_search_mode_on: None,
                 apiset_map: apiset::parse_apiset(sys_dir.join("apisetschema.dll")).ok(),
                 known_dlls: None,
+                version: WindowsVersion::from_sys_dir(&sys_dir),
                 win_dir,
                 sys_dir,
                 system_path: None,
@@ -141,6 +201,132 @@ impl WindowsSystem {
             None
         }
     }
+
+    /// Check whether the given path is the root of a Windows partition (contains Windows\System32)
+    pub fn is_root<P: AsRef<Path>>(p: P) -> bool {
+        p.as_ref().join("Windows").join("System32").is_dir()
+    }
+
+    /// Use the system directory the loader redirects 32-bit processes to (Windows\SysWOW64), if the
+    /// installation has one
+    ///
+    /// Returns whether the system directory was changed.
+    pub fn use_wow64_system_dir(&mut self) -> bool {
+        self.pivot_to_architecture(Architecture::X86)
+    }
+
+    /// Use the system directory, KnownDLLs and API set schema seen by the processes of the given
+    /// architecture
+    ///
+    /// 32-bit x86 and ARM processes are redirected to Windows\SysWOW64 and Windows\SysArm32
+    /// respectively, if the installation has them; the others use Windows\System32. Returns
+    /// whether the system directory was changed.
+    pub fn pivot_to_architecture(&mut self, architecture: Architecture) -> bool {
+        let sys_dir_name = match architecture {
+            Architecture::X86 => "SysWOW64",
+            Architecture::Arm => "SysArm32",
+            _ => "System32",
+        };
+        let sys_dir = self.win_dir.join(sys_dir_name);
+        let unchanged = self
+            .sys_dir
+            .file_name()
+            .is_some_and(|n| n.eq_ignore_ascii_case(sys_dir_name));
+        if unchanged || !sys_dir.is_dir() {
+            return false;
+        }
+
+        if let Ok(apiset_map) = apiset::parse_apiset(sys_dir.join("apisetschema.dll")) {
+            self.apiset_map = Some(apiset_map);
+        }
+        if let Some(known_dlls) = self.known_dlls.as_mut() {
+            // the 32-bit processes have their own list, mapped from their system directory
+            #[cfg(all(windows, feature = "knowndlls"))]
+            if let Ok(names) = if sys_dir_name == "System32" {
+                knowndlls::get_known_dlls()
+            } else {
+                knowndlls::get_known_dlls32()
+            } {
+                known_dlls.entries = names
+                    .iter()
+                    .map(|kd| (kd.to_lowercase(), PathBuf::from(kd)))
+                    .collect();
+            }
+            for path in known_dlls.entries.values_mut() {
+                if let Some(name) = path.file_name() {
+                    *path = sys_dir.join(name);
+                }
+            }
+        }
+        self.sys_dir = sys_dir;
+        true
+    }
+
+    /// Enumerate the Windows installations found at the given locations
+    ///
+    /// Each of the given directories is tested, as well as its direct subdirectories, so that a
+    /// mount point such as /mnt can be passed directly. Duplicates are removed.
+    pub fn enumerate_roots<P: AsRef<Path>>(search_dirs: &[P]) -> Vec<WindowsRootCandidate> {
+        let mut roots: Vec<PathBuf> = Vec::new();
+        for d in search_dirs {
+            let d = d.as_ref();
+            let subdirs: Vec<PathBuf> = fs::read_dir(d)
+                .map(|entries| {
+                    entries
+                        .filter_map(|e| e.ok())
+                        .map(|e| e.path())
+                        .filter(|p| p.is_dir())
+                        .collect()
+                })
+                .unwrap_or_default();
+            for candidate in std::iter::once(d.to_owned()).chain(subdirs) {
+                if Self::is_root(&candidate) {
+                    let candidate = fs::canonicalize(&candidate).unwrap_or(candidate);
+                    if !roots.contains(&candidate) {
+                        roots.push(candidate);
+                    }
+                }
+            }
+        }
+        roots
+            .into_iter()
+            .map(|root| WindowsRootCandidate {
+                version: WindowsVersion::from_sys_dir(root.join("Windows").join("System32")),
+                root,
+            })
+            .collect()
+    }
+
+    /// Directories where Windows partitions are typically mounted on the host operating system
+    pub fn default_mount_points() -> Vec<PathBuf> {
+        #[cfg(windows)]
+        {
+            (b'A'..=b'Z')
+                .map(|l| PathBuf::from(format!("{}:\\", l as char)))
+                .filter(|p| p.exists())
+                .collect()
+        }
+        #[cfg(not(windows))]
+        {
+            let mut ret: Vec<PathBuf> = ["/mnt", "/Volumes", "/media"]
+                .iter()
+                .map(PathBuf::from)
+                .filter(|p| p.is_dir())
+                .collect();
+            // removable media are mounted per-user (e.g. /media/<user>/<label>)
+            for per_user_dir in ["/media", "/run/media"] {
+                if let Ok(entries) = fs::read_dir(per_user_dir) {
+                    ret.extend(
+                        entries
+                            .filter_map(|e| e.ok())
+                            .map(|e| e.path())
+                            .filter(|p| p.is_dir()),
+                    );
+                }
+            }
+            ret
+        }
+    }
 }
 
 impl PartialEq for WindowsSystem {
@@ -150,6 +336,7 @@ impl PartialEq for WindowsSystem {
             && self.safe_dll_search_mode_on == other.safe_dll_search_mode_on
             && self.known_dlls == other.known_dlls
             && self.system_path == other.system_path
+            && self.version == other.version
     }
 }
 
@@ -187,78 +374,217 @@ fn get_windows_directory() -> Result<PathBuf, std::io::Error> {
     return get_winapi_directory(winapi::um::sysinfoapi::GetWindowsDirectoryW);
 }
 
+/// Listing of a scanned directory
+struct CachedDir {
+    /// modification time of the directory when it was listed, if checked
+    modified: Option<SystemTime>,
+    /// files of the directory, by lowercase name
+    files: HashMap<String, PathBuf>,
+}
+
 /// Caches the content of already scanned directories, to avoid repeated expensive filesystem access
-pub(crate) struct WinFileSystemCache {
-    files_in_dirs: HashMap<String, HashMap<String, PathBuf>>,
+///
+/// The cache can be shared across threads, and between several `LookupPath`s (see
+/// `LookupPath::set_cache`); no lock is held while a directory is being read.
+/// The directories are listed through a `FileProvider`, by default the local file system.
+pub struct WinFileSystemCache {
+    provider: Arc<dyn FileProvider>,
+    files_in_dirs: RwLock<HashMap<String, CachedDir>>,
+    /// whether the modification time of the directories is checked at each lookup
+    validate_modification_time: bool,
+    /// lookups answered from an already scanned directory
+    hits: AtomicUsize,
+    /// lookups (or prefetches) that required reading a directory
+    misses: AtomicUsize,
+    /// total time spent listing directories, in nanoseconds
+    listing_nanos: AtomicU64,
 }
 
 impl WinFileSystemCache {
-    pub(crate) fn new() -> Self {
+    /// Cache of the directories of the local file system
+    pub fn new() -> Self {
+        Self::with_provider(Arc::new(LocalFileSystem))
+    }
+
+    /// Cache of the directories listed through the given backend
+    pub fn with_provider(provider: Arc<dyn FileProvider>) -> Self {
         Self {
-            files_in_dirs: HashMap::new(),
+            provider,
+            files_in_dirs: RwLock::new(HashMap::new()),
+            validate_modification_time: false,
+            hits: AtomicUsize::new(0),
+            misses: AtomicUsize::new(0),
+            listing_nanos: AtomicU64::new(0),
         }
     }
 
+    /// Check the modification time of a directory at each lookup, and list it again if it changed
+    /// (i.e. files were added to, removed from or renamed in it)
+    ///
+    /// This costs an access to the file system per lookup, but keeps the cache valid in long
+    /// running sessions.
+    pub fn validating_modification_time(mut self) -> Self {
+        self.validate_modification_time = true;
+        self
+    }
+
+    /// Backend the directories are listed through
+    pub(crate) fn provider(&self) -> &dyn FileProvider {
+        self.provider.as_ref()
+    }
+
+    /// Same as `provider`, to build another cache over the same backend
+    pub(crate) fn shared_provider(&self) -> Arc<dyn FileProvider> {
+        self.provider.clone()
+    }
+
+    /// Number of lookups answered from the cache, and of lookups that read a directory
+    pub(crate) fn counters(&self) -> (usize, usize) {
+        (
+            self.hits.load(Ordering::Relaxed),
+            self.misses.load(Ordering::Relaxed),
+        )
+    }
+
+    /// Total time spent listing directories (summed over the threads listing them concurrently)
+    pub(crate) fn listing_time(&self) -> Duration {
+        Duration::from_nanos(self.listing_nanos.load(Ordering::Relaxed))
+    }
+
     pub(crate) fn test_file_in_folder_case_insensitive<P: AsRef<Path>, Q: AsRef<Path>>(
-        &mut self,
+        &self,
         filename: P,
         folder: Q,
     ) -> Result<Option<PathBuf>, LookupError> {
-        let folder_str: String = folder
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| {
-                LookupError::ScanError(format!(
-                    "Could not scan directory {:?}",
-                    &folder.as_ref().to_str()
-                ))
-            })?
-            .to_owned();
-        if !self.files_in_dirs.contains_key(&folder_str) {
-            self.scan_folder(&folder)?;
+        let folder_str = Self::cache_key(&folder)?;
+        let filename = filename.as_ref().to_str().unwrap().to_lowercase();
+        let modified = if self.validate_modification_time {
+            self.provider.modified(folder.as_ref())
+        } else {
+            None
+        };
+        if let Some(dir) = self.read_cache().get(&folder_str) {
+            if dir.modified == modified {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(dir.files.get(&filename).map(|p| folder.as_ref().join(p)));
+            }
         }
-        let dir = self.files_in_dirs.get(&folder_str).ok_or_else(|| {
-            LookupError::ScanError(format!(
-                "Could not scan directory {:?}",
-                &folder.as_ref().to_str()
-            ))
-        })?;
-        Ok(dir
-            .get(&filename.as_ref().to_str().unwrap().to_lowercase())
-            .map(|p| folder.as_ref().join(p)))
-    }
-
-    pub(crate) fn scan_folder<P: AsRef<Path>>(&mut self, folder: P) -> Result<(), LookupError> {
-        let folder_str: String = folder
-            .as_ref()
-            .to_str()
-            .ok_or_else(|| {
-                LookupError::ScanError(format!(
-                    "Could not scan directory {:?}",
-                    &folder.as_ref().to_str()
-                ))
-            })?
-            .to_owned();
-        if let std::collections::hash_map::Entry::Vacant(e) = self.files_in_dirs.entry(folder_str) {
-            let matching_entries: HashMap<String, PathBuf> = fs::read_dir(folder.as_ref())?
-                .filter_map(|entry| entry.ok())
-                .filter(|entry| entry.metadata().map_or_else(|_| false, |m| m.is_file()))
-                .filter_map(|entry| {
-                    entry
-                        .file_name()
-                        .to_str()
-                        .map(|s| (s.to_lowercase(), entry.file_name().into()))
+        let files = self.list_dir(folder.as_ref())?;
+        let found = files.get(&filename).map(|p| folder.as_ref().join(p));
+        self.files_in_dirs
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .insert(folder_str, CachedDir { modified, files });
+        Ok(found)
+    }
+
+    /// List concurrently the given directories that are not cached yet, so that the following
+    /// lookups are answered from the cache; the directories that cannot be read are skipped
+    ///
+    /// Listing all the directories of a lookup path upfront saves the serialized listing of each
+    /// one at its first lookup, which dominates cold scans when some of them are on network shares.
+    pub fn prefetch<P: AsRef<Path> + Sync>(&self, dirs: &[P]) {
+        let mut keys: Vec<String> = Vec::new();
+        let to_list: Vec<(&Path, String)> = {
+            let cache = self.read_cache();
+            dirs.iter()
+                .filter_map(|dir| {
+                    let key = Self::cache_key(dir).ok()?;
+                    if cache.contains_key(&key) || keys.contains(&key) {
+                        return None;
+                    }
+                    keys.push(key.clone());
+                    Some((dir.as_ref(), key))
                 })
-                .collect();
-            e.insert(matching_entries);
+                .collect()
+        };
+        let listed: Vec<(String, CachedDir)> = to_list
+            .into_par_iter()
+            .filter_map(|(dir, key)| {
+                let modified = if self.validate_modification_time {
+                    self.provider.modified(dir)
+                } else {
+                    None
+                };
+                let files = self.list_dir(dir).ok()?;
+                Some((key, CachedDir { modified, files }))
+            })
+            .collect();
+        self.files_in_dirs
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .extend(listed);
+    }
+
+    /// Read the files of a directory, by lowercase name
+    fn list_dir(&self, folder: &Path) -> Result<HashMap<String, PathBuf>, LookupError> {
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let start = Instant::now();
+        let files: HashMap<String, PathBuf> = self
+            .provider
+            .list_files(folder)
+            .map_err(|e| e.in_file(folder, FileOperation::ListDirectory))?
+            .into_iter()
+            .filter_map(|name| {
+                name.to_str()
+                    .map(|s| (s.to_lowercase(), PathBuf::from(&name)))
+            })
+            .collect();
+        let elapsed = start.elapsed();
+        self.listing_nanos
+            .fetch_add(elapsed.as_nanos() as u64, Ordering::Relaxed);
+        tracing::trace!(dir = %folder.display(), files = files.len(), ?elapsed, "listed");
+        Ok(files)
+    }
+
+    /// Forget the content of a directory, so that it is read again on the next lookup
+    pub fn invalidate<P: AsRef<Path>>(&self, dir: P) {
+        if let Ok(key) = Self::cache_key(dir) {
+            self.files_in_dirs
+                .write()
+                .unwrap_or_else(PoisonError::into_inner)
+                .remove(&key);
         }
-        Ok(())
+    }
+
+    /// Forget the content of the scanned directories, so that it is read again on the next lookup
+    pub fn clear(&self) {
+        self.files_in_dirs
+            .write()
+            .unwrap_or_else(PoisonError::into_inner)
+            .clear();
+    }
+
+    /// Key of a directory in the cache, the same whether or not its path has the extended prefix
+    fn cache_key<P: AsRef<Path>>(folder: P) -> Result<String, LookupError> {
+        folder.as_ref().to_str().map(decanonicalize).ok_or_else(|| {
+            LookupError::ScanError(format!("Could not scan directory {:?}", folder.as_ref()))
+        })
+    }
+
+    fn read_cache(&self) -> RwLockReadGuard<'_, HashMap<String, CachedDir>> {
+        // the map is never left in an inconsistent state, so a poisoned lock can be recovered
+        self.files_in_dirs
+            .read()
+            .unwrap_or_else(PoisonError::into_inner)
+    }
+}
+
+impl Default for WinFileSystemCache {
+    fn default() -> Self {
+        Self::new()
     }
 }
 
 #[cfg(test)]
 mod tests {
+    use std::ffi::OsString;
+    use std::path::Path;
+    use std::sync::{Arc, Mutex};
+    use std::time::{Duration, SystemTime};
+
     use crate::common::LookupError;
+    use crate::provider::FileProvider;
     use crate::system::WinFileSystemCache;
 
     #[cfg(windows)]
@@ -282,6 +608,88 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn enumerate_roots() -> Result<(), LookupError> {
+        use super::WindowsSystem;
+        use fs_err as fs;
+
+        let temp_dir = tempfile::tempdir()?;
+        let mount_point = temp_dir.path().to_path_buf();
+        fs::create_dir_all(mount_point.join("c").join("Windows").join("System32"))?;
+        fs::create_dir_all(mount_point.join("d").join("Windows").join("System32"))?;
+        fs::create_dir_all(mount_point.join("e").join("Data"))?;
+
+        let candidates = WindowsSystem::enumerate_roots(&[&mount_point, &mount_point]);
+        let mut roots: Vec<_> = candidates
+            .iter()
+            .map(|c| c.root.file_name().unwrap().to_str().unwrap().to_owned())
+            .collect();
+        roots.sort();
+        assert_eq!(roots, vec!["c", "d"]);
+        assert!(candidates.iter().all(|c| c.version.is_none()));
+
+        fs::create_dir_all(mount_point.join("c").join("Windows").join("SysWOW64"))?;
+        let mut c = WindowsSystem::from_root(mount_point.join("c")).unwrap();
+        assert!(c.use_wow64_system_dir());
+        assert!(c.sys_dir.ends_with("Windows/SysWOW64"));
+        let mut d = WindowsSystem::from_root(mount_point.join("d")).unwrap();
+        assert!(!d.use_wow64_system_dir());
+        assert!(d.sys_dir.ends_with("Windows/System32"));
+        Ok(())
+    }
+
+    #[test]
+    fn pivot_to_architecture() -> Result<(), LookupError> {
+        use super::{KnownDLLList, WindowsSystem};
+        use crate::executable::Architecture;
+        use fs_err as fs;
+
+        let temp_dir = tempfile::tempdir()?;
+        let root = temp_dir.path().to_path_buf();
+        let win_dir = root.join("Windows");
+        fs::create_dir_all(win_dir.join("System32"))?;
+        fs::create_dir_all(win_dir.join("SysWOW64"))?;
+
+        let mut system = WindowsSystem::from_root(&root).unwrap();
+        system.known_dlls = Some(KnownDLLList {
+            entries: [(
+                "kernel32.dll".to_owned(),
+                win_dir.join("System32").join("kernel32.dll"),
+            )]
+            .into_iter()
+            .collect(),
+        });
+        let known_kernel32 = |system: &WindowsSystem| {
+            system
+                .known_dlls
+                .as_ref()
+                .unwrap()
+                .search_dll_in_known_dlls("KERNEL32.dll")
+                .unwrap()
+                .unwrap()
+        };
+
+        assert!(!system.pivot_to_architecture(Architecture::X64));
+        assert!(system.pivot_to_architecture(Architecture::X86));
+        assert_eq!(system.sys_dir, win_dir.join("SysWOW64"));
+        assert_eq!(
+            known_kernel32(&system),
+            win_dir.join("SysWOW64").join("kernel32.dll")
+        );
+        // no SysArm32 in this installation
+        assert!(!system.pivot_to_architecture(Architecture::Arm));
+        assert!(system.pivot_to_architecture(Architecture::Arm64));
+        assert_eq!(system.sys_dir, win_dir.join("System32"));
+        assert_eq!(
+            known_kernel32(&system),
+            win_dir.join("System32").join("kernel32.dll")
+        );
+
+        assert_eq!("AMD64".parse::<Architecture>()?, Architecture::X64);
+        assert!("mips".parse::<Architecture>().is_err());
+        Ok(())
+    }
+
     #[test]
     fn fscache() -> Result<(), LookupError> {
         let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
@@ -290,7 +698,7 @@ mod tests {
         assert!(test_file_path.exists());
         let folder = std::fs::canonicalize(test_file_path.parent().unwrap())?;
 
-        let mut fscache = WinFileSystemCache::new();
+        let fscache = WinFileSystemCache::new();
         let expected_res = Some(folder.join("DepRunTest.exe"));
         assert_eq!(
             fscache.test_file_in_folder_case_insensitive("depruntest.exe", &folder)?,
@@ -306,4 +714,108 @@ mod tests {
         );
         Ok(())
     }
+
+    /// Directory whose listing and modification time are changed by the tests
+    #[derive(Default)]
+    struct ChangingDir {
+        files: Mutex<Vec<OsString>>,
+        modified: Mutex<u64>,
+    }
+
+    impl FileProvider for ChangingDir {
+        fn list_files(&self, _dir: &Path) -> Result<Vec<OsString>, LookupError> {
+            Ok(self.files.lock().unwrap().clone())
+        }
+
+        fn read_file(&self, path: &Path) -> Result<Vec<u8>, LookupError> {
+            Err(LookupError::ScanError(format!(
+                "cannot read {}",
+                path.display()
+            )))
+        }
+
+        fn modified(&self, _path: &Path) -> Option<SystemTime> {
+            Some(SystemTime::UNIX_EPOCH + Duration::from_secs(*self.modified.lock().unwrap()))
+        }
+    }
+
+    #[test]
+    fn fscache_invalidation() -> Result<(), LookupError> {
+        let temp_dir = tempfile::tempdir()?;
+        let dir = temp_dir.path();
+
+        // the listing is kept until the directory is invalidated
+        let fscache = WinFileSystemCache::new();
+        assert_eq!(
+            fscache.test_file_in_folder_case_insensitive("new.dll", dir)?,
+            None
+        );
+        std::fs::write(dir.join("New.dll"), b"")?;
+        assert_eq!(
+            fscache.test_file_in_folder_case_insensitive("new.dll", dir)?,
+            None
+        );
+        fscache.invalidate(dir);
+        assert_eq!(
+            fscache.test_file_in_folder_case_insensitive("new.dll", dir)?,
+            Some(dir.join("New.dll"))
+        );
+
+        // a validating cache lists the directory again when its modification time changes
+        let changing = Arc::new(ChangingDir::default());
+        let validating =
+            WinFileSystemCache::with_provider(changing.clone()).validating_modification_time();
+        assert_eq!(
+            validating.test_file_in_folder_case_insensitive("other.dll", dir)?,
+            None
+        );
+        changing.files.lock().unwrap().push("Other.dll".into());
+        assert_eq!(
+            validating.test_file_in_folder_case_insensitive("other.dll", dir)?,
+            None
+        );
+        *changing.modified.lock().unwrap() += 1;
+        assert_eq!(
+            validating.test_file_in_folder_case_insensitive("other.dll", dir)?,
+            Some(dir.join("Other.dll"))
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn fscache_prefetch() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let folder = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        let missing = d.join("test_data/no_such_directory");
+
+        // each directory is listed once, the ones that cannot be read are skipped
+        let fscache = WinFileSystemCache::new();
+        fscache.prefetch(&[&folder, &missing, &folder]);
+        assert_eq!(fscache.counters(), (0, 2));
+        assert_eq!(
+            fscache.test_file_in_folder_case_insensitive("deprunTESTlib.dll", &folder)?,
+            Some(folder.join("DepRunTestLib.dll"))
+        );
+        assert_eq!(fscache.counters(), (1, 2));
+        assert!(fscache
+            .test_file_in_folder_case_insensitive("deprunTESTlib.dll", &missing)
+            .is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn fscache_long_path() -> Result<(), LookupError> {
+        let temp_dir = tempfile::tempdir()?;
+        let folder = (0..6).fold(temp_dir.path().to_path_buf(), |p, i| {
+            p.join(format!("{i}{}", "x".repeat(50)))
+        });
+        assert!(folder.as_os_str().len() > 260);
+        std::fs::create_dir_all(&folder)?;
+        std::fs::write(folder.join("LongPath.dll"), b"")?;
+
+        let fscache = WinFileSystemCache::new();
+        let found = fscache.test_file_in_folder_case_insensitive("longpath.dll", &folder)?;
+        assert_eq!(found, Some(folder.join("LongPath.dll")));
+        Ok(())
+    }
 }
@@ -0,0 +1,85 @@
+//! Fixtures shared by the unit tests, to describe scan results without scanning files
+
+use std::collections::HashSet;
+
+use crate::executable::{
+    Architecture, AssemblyDependency, Executable, ExecutableDetails, ExecutableSymbols,
+};
+
+/// Version of the VC90 CRT side-by-side assembly requested by `dll`
+pub(crate) const CRT_VERSION: &str = "9.0.21022.8";
+
+/// Found x64 executable with the given dependencies
+pub(crate) fn dll(name: &str, depth: usize, dependencies: &[&str]) -> Executable {
+    exe_requesting(name, depth, CRT_VERSION, Architecture::X64, dependencies)
+}
+
+/// Found executable requesting the given version of the VC90 CRT side-by-side assembly
+pub(crate) fn exe_requesting(
+    name: &str,
+    depth: usize,
+    version: &str,
+    architecture: Architecture,
+    dependencies: &[&str],
+) -> Executable {
+    Executable {
+        dllname: name.to_owned(),
+        depth_first_appearance: depth,
+        found: true,
+        details: Some(ExecutableDetails {
+            is_api_set: false,
+            is_system: false,
+            is_known_dll: false,
+            api_set_host: None,
+            full_path: Some(name.into()),
+            resolved_from: None,
+            file_stamp: None,
+            file_version: None,
+            hashes: None,
+            architecture: Some(architecture),
+            subsystem: None,
+            security: None,
+            entry_point: None,
+            signers: None,
+            assembly_dependencies: Some(vec![AssemblyDependency {
+                name: "Microsoft.VC90.CRT".to_owned(),
+                version: Some(version.to_owned()),
+            }]),
+            dependencies: Some(dependencies.iter().map(|d| d.to_string()).collect()),
+            delay_load_dependencies: None,
+            forwarded_dependencies: None,
+            dynamic_load_dependencies: None,
+            symbols: None,
+        }),
+        probes: None,
+    }
+}
+
+/// DLL that was not found
+pub(crate) fn missing(name: &str, depth: usize) -> Executable {
+    Executable {
+        dllname: name.to_owned(),
+        depth_first_appearance: depth,
+        found: false,
+        details: None,
+        probes: None,
+    }
+}
+
+/// Set the symbols an executable imports from each of its dependencies (an empty name stands for
+/// an import by ordinal)
+pub(crate) fn importing(mut exe: Executable, imports: &[(&str, &[&str])]) -> Executable {
+    if let Some(details) = exe.details.as_mut() {
+        details.symbols = Some(ExecutableSymbols {
+            exported: HashSet::new(),
+            imported: imports
+                .iter()
+                .map(|(dll, names)| {
+                    let names = names.iter().map(|n| n.to_string()).collect();
+                    (dll.to_string(), names)
+                })
+                .collect(),
+        });
+    }
+    exe
+}
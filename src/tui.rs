@@ -0,0 +1,488 @@
+//! Interactive terminal browser of the dependency tree and of the symbols of the DLLs
+//!
+//! Unlike the skim module, it works on all the platforms (Windows included).
+
+use std::collections::HashSet;
+
+use ratatui::crossterm::event::{self, Event, KeyCode, KeyEvent, KeyEventKind};
+use ratatui::layout::{Constraint, Layout};
+use ratatui::style::{Color, Modifier, Style};
+use ratatui::text::Line;
+use ratatui::widgets::{Block, Borders, List, ListItem, ListState, Paragraph, Wrap};
+use ratatui::Frame;
+
+use crate::common::{demangled, open_location, readable_path, LookupError};
+use crate::executable::{Executable, Executables};
+
+const HELP: &str = "↑↓ move  →/enter expand  ← collapse  / search  n next match  s symbols  o open location  q quit";
+
+/// Line of the tree, i.e. a DLL reached through a chain of dependencies from a root
+#[derive(Debug, Clone, PartialEq, Eq)]
+struct Row {
+    /// names from the root to this DLL
+    chain: Vec<String>,
+    has_children: bool,
+    expanded: bool,
+}
+
+impl Row {
+    fn name(&self) -> &str {
+        self.chain.last().map(String::as_str).unwrap_or_default()
+    }
+}
+
+/// State of the browser, independent from the terminal
+struct Browser<'a> {
+    executables: &'a Executables,
+    roots: Vec<String>,
+    /// chains of the expanded rows, in lowercase
+    expanded: HashSet<Vec<String>>,
+    rows: Vec<Row>,
+    state: ListState,
+    /// text of the search being typed, if any
+    search_input: Option<String>,
+    /// executables matching the last search, cycled through with `n`
+    matches: Vec<String>,
+    next_match: usize,
+    show_symbols: bool,
+    status: String,
+}
+
+impl<'a> Browser<'a> {
+    fn new(executables: &'a Executables) -> Result<Self, LookupError> {
+        let roots: Vec<String> = executables
+            .get_roots()?
+            .iter()
+            .map(|r| r.dllname.clone())
+            .collect();
+        let mut browser = Self {
+            executables,
+            expanded: roots.iter().map(|r| vec![r.to_lowercase()]).collect(),
+            roots,
+            rows: Vec::new(),
+            state: ListState::default(),
+            search_input: None,
+            matches: Vec::new(),
+            next_match: 0,
+            show_symbols: false,
+            status: HELP.to_owned(),
+        };
+        browser.rebuild_rows();
+        browser
+            .state
+            .select((!browser.rows.is_empty()).then_some(0));
+        Ok(browser)
+    }
+
+    fn children(&self, name: &str) -> Vec<&'a str> {
+        self.executables
+            .get(name)
+            .and_then(|e| e.details.as_ref())
+            .map(|d| d.all_dependencies())
+            .unwrap_or_default()
+    }
+
+    /// Recompute the visible rows from the expanded ones
+    fn rebuild_rows(&mut self) {
+        let mut rows = Vec::new();
+        for root in &self.roots {
+            self.push_rows(vec![root.clone()], &mut rows);
+        }
+        self.rows = rows;
+    }
+
+    fn push_rows(&self, chain: Vec<String>, rows: &mut Vec<Row>) {
+        let name = chain.last().cloned().unwrap_or_default();
+        let key = lowercase_chain(&chain);
+        // a DLL already in the chain would be expanded forever
+        let cyclic = chain[..chain.len() - 1]
+            .iter()
+            .any(|n| n.eq_ignore_ascii_case(&name));
+        let children = if cyclic {
+            Vec::new()
+        } else {
+            self.children(&name)
+        };
+        let expanded = !children.is_empty() && self.expanded.contains(&key);
+        rows.push(Row {
+            chain: chain.clone(),
+            has_children: !children.is_empty(),
+            expanded,
+        });
+        if expanded {
+            for child in children {
+                let mut child_chain = chain.clone();
+                child_chain.push(child.to_owned());
+                self.push_rows(child_chain, rows);
+            }
+        }
+    }
+
+    fn selected_row(&self) -> Option<&Row> {
+        self.state.selected().and_then(|i| self.rows.get(i))
+    }
+
+    fn selected_executable(&self) -> Option<&'a Executable> {
+        self.selected_row()
+            .and_then(|row| self.executables.get(row.name()))
+    }
+
+    fn set_expanded(&mut self, expanded: bool) {
+        let Some(row) = self.selected_row().cloned() else {
+            return;
+        };
+        let key = lowercase_chain(&row.chain);
+        if expanded && row.has_children {
+            self.expanded.insert(key);
+        } else if row.expanded {
+            self.expanded.remove(&key);
+        } else if row.chain.len() > 1 {
+            // collapsing a leaf or a collapsed row goes to its parent
+            let parent = &row.chain[..row.chain.len() - 1];
+            let index = self.rows.iter().position(|r| r.chain == parent);
+            self.state.select(index);
+            return;
+        }
+        self.rebuild_rows();
+    }
+
+    fn move_selection(&mut self, offset: isize) {
+        if self.rows.is_empty() {
+            return;
+        }
+        let current = self.state.selected().unwrap_or(0) as isize;
+        let last = self.rows.len() as isize - 1;
+        self.state
+            .select(Some((current + offset).clamp(0, last) as usize));
+    }
+
+    /// Find the executables whose name contains the text, and show the first one
+    fn search(&mut self, text: &str) {
+        let text = text.to_lowercase();
+        self.matches = self
+            .executables
+            .iter()
+            .filter(|e| e.dllname.to_lowercase().contains(&text))
+            .map(|e| e.dllname.clone())
+            .collect();
+        self.next_match = 0;
+        if self.matches.is_empty() {
+            self.status = format!("No DLL matching \"{text}\"");
+        } else {
+            self.show_next_match();
+        }
+    }
+
+    /// Expand the shortest chain leading to the next match of the search, and select it
+    fn show_next_match(&mut self) {
+        let Some(name) = self.matches.get(self.next_match).cloned() else {
+            return;
+        };
+        self.next_match = (self.next_match + 1) % self.matches.len();
+        let chain = self
+            .executables
+            .dependency_chains(&name)
+            .ok()
+            .and_then(|chains| chains.into_iter().next())
+            .unwrap_or_default();
+        for len in 1..chain.len() {
+            self.expanded.insert(lowercase_chain(&chain[..len]));
+        }
+        self.rebuild_rows();
+        let key = lowercase_chain(&chain);
+        let index = self
+            .rows
+            .iter()
+            .position(|r| lowercase_chain(&r.chain) == key);
+        self.state.select(index.or(self.state.selected()));
+        self.status = format!(
+            "{name} ({} of {} matches, n for the next one)",
+            self.matches
+                .iter()
+                .position(|m| *m == name)
+                .map_or(0, |i| i + 1),
+            self.matches.len()
+        );
+    }
+
+    fn open_selected_location(&mut self) {
+        let path = self
+            .selected_executable()
+            .and_then(|e| e.details.as_ref())
+            .and_then(|d| d.full_path.clone());
+        self.status = match path {
+            Some(path) => match open_location(&path) {
+                Ok(()) => format!("Opened the location of {}", readable_path(&path)),
+                Err(e) => format!("Could not open the location of {}: {e}", path.display()),
+            },
+            None => "The DLL was not found".to_owned(),
+        };
+    }
+
+    /// Apply a key press, returning false if the browser should be closed
+    fn handle_key(&mut self, key: KeyEvent) -> bool {
+        if let Some(input) = &mut self.search_input {
+            match key.code {
+                KeyCode::Enter => {
+                    let text = input.clone();
+                    self.search_input = None;
+                    self.search(&text);
+                }
+                KeyCode::Esc => {
+                    self.search_input = None;
+                    self.status = HELP.to_owned();
+                }
+                KeyCode::Backspace => {
+                    input.pop();
+                }
+                KeyCode::Char(c) => input.push(c),
+                _ => {}
+            }
+            return true;
+        }
+        match key.code {
+            KeyCode::Char('q') | KeyCode::Esc => return false,
+            KeyCode::Up | KeyCode::Char('k') => self.move_selection(-1),
+            KeyCode::Down | KeyCode::Char('j') => self.move_selection(1),
+            KeyCode::PageUp => self.move_selection(-20),
+            KeyCode::PageDown => self.move_selection(20),
+            KeyCode::Right | KeyCode::Enter | KeyCode::Char('l') => self.set_expanded(true),
+            KeyCode::Left | KeyCode::Char('h') => self.set_expanded(false),
+            KeyCode::Char('/') => self.search_input = Some(String::new()),
+            KeyCode::Char('n') => self.show_next_match(),
+            KeyCode::Char('s') => self.show_symbols = !self.show_symbols,
+            KeyCode::Char('o') => self.open_selected_location(),
+            _ => {}
+        }
+        true
+    }
+
+    /// Description of the selected executable, and of its symbols if requested
+    fn details(&self) -> Vec<Line<'static>> {
+        let Some(exe) = self.selected_executable() else {
+            return Vec::new();
+        };
+        let mut lines = vec![Line::styled(
+            exe.dllname.clone(),
+            Style::default().add_modifier(Modifier::BOLD),
+        )];
+        match &exe.details {
+            None => lines.push(Line::raw("not found")),
+            Some(d) => {
+                let kind = if d.is_api_set {
+                    "API set"
+                } else if d.is_known_dll {
+                    "known DLL"
+                } else if d.is_system {
+                    "system"
+                } else {
+                    "user"
+                };
+                if let Some(path) = d.full_path.as_deref() {
+                    lines.push(Line::raw(format!("path: {}", readable_path(path))));
+                }
+                lines.push(Line::raw(format!("kind: {kind}")));
+                if let Some(architecture) = d.architecture {
+                    lines.push(Line::raw(format!("architecture: {architecture}")));
+                }
+                if let Some(version) = &d.file_version {
+                    lines.push(Line::raw(format!("version: {version}")));
+                }
+                lines.push(Line::raw(format!(
+                    "dependencies: {}",
+                    d.all_dependencies().len()
+                )));
+            }
+        }
+        lines.push(Line::raw(format!(
+            "first appears at depth {}",
+            exe.depth_first_appearance
+        )));
+        let importers: Vec<String> = self
+            .executables
+            .importers_of(&exe.dllname)
+            .iter()
+            .map(|e| format!("{} ({})", e.importer, e.kind))
+            .collect();
+        if !importers.is_empty() {
+            lines.push(Line::raw(format!("imported by: {}", importers.join(", "))));
+        }
+        if !self.show_symbols {
+            lines.push(Line::raw(""));
+            lines.push(Line::raw("press s to show the symbols"));
+            return lines;
+        }
+        match exe.details.as_ref().and_then(|d| d.symbols.as_ref()) {
+            None => {
+                lines.push(Line::raw(""));
+                lines.push(Line::raw("no symbols extracted"));
+            }
+            Some(symbols) => {
+                let mut exported: Vec<&String> = symbols.exported.iter().collect();
+                exported.sort();
+                lines.push(Line::raw(""));
+                lines.push(Line::styled(
+                    format!("exports ({})", exported.len()),
+                    Style::default().add_modifier(Modifier::BOLD),
+                ));
+                lines.extend(exported.into_iter().map(|s| Line::raw(demangled(s))));
+                let mut imported: Vec<(&String, _)> = symbols.imported.iter().collect();
+                imported.sort_by(|a, b| a.0.cmp(b.0));
+                for (dll, names) in imported {
+                    let mut names: Vec<&String> = names.iter().collect();
+                    names.sort();
+                    lines.push(Line::raw(""));
+                    lines.push(Line::styled(
+                        format!("imports from {dll} ({})", names.len()),
+                        Style::default().add_modifier(Modifier::BOLD),
+                    ));
+                    lines.extend(names.into_iter().map(|s| Line::raw(demangled(s))));
+                }
+            }
+        }
+        lines
+    }
+
+    fn draw(&mut self, frame: &mut Frame) {
+        let [main, status] =
+            Layout::vertical([Constraint::Min(1), Constraint::Length(1)]).areas(frame.area());
+        let [tree, details] =
+            Layout::horizontal([Constraint::Percentage(50), Constraint::Percentage(50)])
+                .areas(main);
+
+        let items: Vec<ListItem> = self
+            .rows
+            .iter()
+            .map(|row| {
+                let marker = match (row.has_children, row.expanded) {
+                    (false, _) => "  ",
+                    (true, false) => "▸ ",
+                    (true, true) => "▾ ",
+                };
+                let text = format!("{}{marker}{}", "  ".repeat(row.chain.len() - 1), row.name());
+                let found = self.executables.get(row.name()).is_some_and(|e| e.found);
+                let style = match self
+                    .executables
+                    .get(row.name())
+                    .and_then(|e| e.details.as_ref())
+                {
+                    _ if !found => Style::default().fg(Color::Red),
+                    Some(d) if d.is_system => Style::default().fg(Color::DarkGray),
+                    _ => Style::default(),
+                };
+                ListItem::new(text).style(style)
+            })
+            .collect();
+        let list = List::new(items)
+            .block(Block::default().borders(Borders::ALL).title("Dependencies"))
+            .highlight_style(Style::default().add_modifier(Modifier::REVERSED));
+        frame.render_stateful_widget(list, tree, &mut self.state);
+
+        let details_widget = Paragraph::new(self.details())
+            .block(Block::default().borders(Borders::ALL).title("Details"))
+            .wrap(Wrap { trim: false });
+        frame.render_widget(details_widget, details);
+
+        let status_line = match &self.search_input {
+            Some(input) => format!("/{input}"),
+            None => self.status.clone(),
+        };
+        frame.render_widget(Paragraph::new(status_line), status);
+    }
+}
+
+fn lowercase_chain(chain: &[String]) -> Vec<String> {
+    chain.iter().map(|n| n.to_lowercase()).collect()
+}
+
+/// Browse the dependency tree interactively in the terminal, until the user quits
+pub fn browse(executables: &Executables) -> Result<(), LookupError> {
+    let mut browser = Browser::new(executables)?;
+    let mut terminal = ratatui::init();
+    let result = (|| -> std::io::Result<()> {
+        loop {
+            terminal.draw(|frame| browser.draw(frame))?;
+            if let Event::Key(key) = event::read()? {
+                if key.kind == KeyEventKind::Press && !browser.handle_key(key) {
+                    return Ok(());
+                }
+            }
+        }
+    })();
+    ratatui::restore();
+    Ok(result?)
+}
+
+#[cfg(test)]
+mod tests {
+    use ratatui::backend::TestBackend;
+    use ratatui::crossterm::event::{KeyCode, KeyEvent};
+    use ratatui::Terminal;
+
+    use crate::common::LookupError;
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::runner::run;
+    use crate::tui::Browser;
+
+    fn press(browser: &mut Browser, code: KeyCode) -> bool {
+        browser.handle_key(KeyEvent::from(code))
+    }
+
+    #[test]
+    fn browse_tree() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let exe_path =
+            d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+        let mut query = LookupQuery::deduce_from_executable_location(exe_path)?;
+        query.parameters.extract_symbols = true;
+        let lookup_path = LookupPath::deduce(&query);
+        let executables = run(&query, &lookup_path)?;
+
+        // the roots start expanded
+        let mut browser = Browser::new(&executables)?;
+        let names: Vec<&str> = browser.rows.iter().map(|r| r.name()).collect();
+        assert_eq!(names[0], "DepRunTest.exe");
+        assert!(names.contains(&"DepRunTestLib.dll"));
+        let top_level = browser.rows.len();
+
+        // collapse and expand the root
+        press(&mut browser, KeyCode::Left);
+        assert_eq!(browser.rows.len(), 1);
+        press(&mut browser, KeyCode::Right);
+        assert_eq!(browser.rows.len(), top_level);
+
+        // a search expands the chain leading to the DLL
+        press(&mut browser, KeyCode::Char('/'));
+        for c in "deprunTESTlib".chars() {
+            press(&mut browser, KeyCode::Char(c));
+        }
+        press(&mut browser, KeyCode::Enter);
+        assert_eq!(
+            browser.selected_row().map(|r| r.chain.clone()),
+            Some(vec![
+                "DepRunTest.exe".to_owned(),
+                "DepRunTestLib.dll".to_owned()
+            ])
+        );
+        press(&mut browser, KeyCode::Right);
+        assert!(browser.rows.len() > top_level);
+
+        // the details and the symbols of the selection are rendered
+        press(&mut browser, KeyCode::Char('s'));
+        let mut terminal = Terminal::new(TestBackend::new(120, 40))?;
+        terminal.draw(|frame| browser.draw(frame))?;
+        let screen: String = terminal
+            .backend()
+            .buffer()
+            .content()
+            .iter()
+            .map(|c| c.symbol())
+            .collect();
+        assert!(screen.contains("exports ("));
+        assert!(screen.contains("imported by: DepRunTest.exe"));
+
+        assert!(!press(&mut browser, KeyCode::Char('q')));
+        Ok(())
+    }
+}
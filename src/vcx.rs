@@ -1,97 +1,391 @@
 //! Utilities to parse Visual Studio project files
 
-use crate::common::{readable_canonical_path, LookupError};
+use crate::common::{host_path, readable_canonical_path, LookupError};
 use fs_err as fs;
-use std::collections::HashMap;
-use std::path::PathBuf;
+use std::collections::{HashMap, HashSet};
+use std::path::{Path, PathBuf};
 
 // Parsing of Visual Studio files
 
+/// MSBuild properties, used to expand the $(Name) macros found in project files
+///
+/// Seeded from the location of the project and the selected configuration, then extended by
+/// evaluating the property groups of the project files in order, as MSBuild does.
+/// Property names are case-insensitive.
+#[derive(Debug, Clone, Default)]
+pub struct MsBuildProperties {
+    values: HashMap<String, String>,
+    /// properties given on the command line (configuration, platform), which projects cannot override
+    global: HashSet<String>,
+}
+
+impl MsBuildProperties {
+    /// Properties that MSBuild defines for the given project and configuration
+    ///
+    /// Sets Configuration, Platform, ProjectDir, ProjectPath, ProjectName, ProjectFileName,
+    /// ProjectExt and SolutionDir (the closest ancestor directory containing a .sln file).
+    pub fn for_project<P: AsRef<Path>>(
+        project_path: P,
+        configuration: &str,
+        platform: Option<&str>,
+    ) -> Self {
+        let mut properties = Self::default();
+        properties.set_global("Configuration", configuration);
+        if let Some(platform) = platform {
+            properties.set_global("Platform", platform);
+        }
+
+        let project_path = project_path.as_ref();
+        let project_dir = project_path.parent().unwrap_or(Path::new(""));
+        let as_dir = |p: &Path| format!("{}{}", p.display(), std::path::MAIN_SEPARATOR);
+        properties.set("ProjectDir", &as_dir(project_dir));
+        properties.set("ProjectPath", &project_path.display().to_string());
+        if let Some(name) = project_path.file_stem().and_then(|n| n.to_str()) {
+            properties.set("ProjectName", name);
+        }
+        if let Some(file_name) = project_path.file_name().and_then(|n| n.to_str()) {
+            properties.set("ProjectFileName", file_name);
+        }
+        if let Some(ext) = project_path.extension().and_then(|n| n.to_str()) {
+            properties.set("ProjectExt", &format!(".{ext}"));
+        }
+        let solution_dir = project_dir
+            .ancestors()
+            .find(|d| {
+                fs::read_dir(d)
+                    .map(|entries| {
+                        entries.filter_map(Result::ok).any(|e| {
+                            e.path()
+                                .extension()
+                                .map(|ext| ext.eq_ignore_ascii_case("sln"))
+                                .unwrap_or(false)
+                        })
+                    })
+                    .unwrap_or(false)
+            })
+            .unwrap_or(project_dir);
+        properties.set("SolutionDir", &as_dir(solution_dir));
+        properties
+    }
+
+    /// Value of the property, if defined
+    pub fn get(&self, name: &str) -> Option<&str> {
+        self.values
+            .get(&name.to_ascii_lowercase())
+            .map(String::as_str)
+    }
+
+    /// Define the property, unless it is a global one
+    pub fn set(&mut self, name: &str, value: &str) {
+        let key = name.to_ascii_lowercase();
+        if !self.global.contains(&key) {
+            self.values.insert(key, value.to_owned());
+        }
+    }
+
+    fn set_global(&mut self, name: &str, value: &str) {
+        let key = name.to_ascii_lowercase();
+        self.values.insert(key.clone(), value.to_owned());
+        self.global.insert(key);
+    }
+
+    /// Replace the $(Name) macros of the text with the values of the defined properties
+    ///
+    /// Undefined properties, property functions and %VARIABLES% are left as they are.
+    pub fn expand(&self, text: &str) -> String {
+        let mut expanded = String::with_capacity(text.len());
+        let mut rest = text;
+        while let Some(start) = rest.find("$(") {
+            expanded.push_str(&rest[..start]);
+            let macro_text = &rest[start..];
+            match macro_text.find(')') {
+                Some(end) => {
+                    let value = self.get(&macro_text[2..end]);
+                    expanded.push_str(value.unwrap_or(&macro_text[..=end]));
+                    rest = &macro_text[end + 1..];
+                }
+                None => {
+                    rest = macro_text;
+                    break;
+                }
+            }
+        }
+        expanded.push_str(rest);
+        expanded
+    }
+
+    /// Evaluate a Condition attribute of the form 'a'=='b', 'a'!='b' (compared case-insensitively),
+    /// exists('path') or !exists('path')
+    ///
+    /// Conditions that cannot be evaluated are considered false.
+    pub fn condition_holds(&self, condition: &str) -> bool {
+        let expanded = self.expand(condition);
+        let expanded = expanded.trim();
+        let lowercase = expanded.to_ascii_lowercase();
+        let (negated, lowercase) = match lowercase.strip_prefix('!') {
+            Some(rest) => (true, rest.trim_start().to_owned()),
+            None => (false, lowercase),
+        };
+        if lowercase.starts_with("exists(") && lowercase.ends_with(')') {
+            let start = expanded.find('(').unwrap_or_default() + 1;
+            let path = expanded[start..expanded.len() - 1]
+                .trim()
+                .trim_matches('\'');
+            let this_dir = self.get("MSBuildThisFileDirectory").unwrap_or_default();
+            let exists = !path.is_empty()
+                && !path.contains("$(")
+                && host_path(path, Path::new(this_dir)).exists();
+            return exists != negated;
+        }
+        let (operands, equal) = match expanded.split_once("==") {
+            Some(operands) => (operands, true),
+            None => match expanded.split_once("!=") {
+                Some(operands) => (operands, false),
+                None => return false,
+            },
+        };
+        let unquote = |s: &str| s.trim().trim_matches('\'').to_ascii_lowercase();
+        (unquote(operands.0) == unquote(operands.1)) == equal
+    }
+
+    fn node_applies(&self, n: &roxmltree::Node) -> bool {
+        n.attribute("Condition")
+            .map(|c| self.condition_holds(c))
+            .unwrap_or(true)
+    }
+
+    /// Evaluate a project (e.g. a .vcxproj): the closest Directory.Build.props above it, which
+    /// MSBuild imports first, then the project file itself
+    pub fn evaluate_project<P: AsRef<Path>>(&mut self, project_path: P) -> Result<(), LookupError> {
+        let project_path = project_path.as_ref();
+        let directory_build_props = project_path
+            .parent()
+            .into_iter()
+            .flat_map(Path::ancestors)
+            .map(|d| d.join("Directory.Build.props"))
+            .find(|p| p.is_file());
+        if let Some(directory_build_props) = directory_build_props {
+            self.evaluate_file(directory_build_props)?;
+        }
+        self.evaluate_file(project_path)
+    }
+
+    /// Evaluate the property groups of a project file, following its imports, in order
+    ///
+    /// Imports whose path cannot be resolved (e.g. $(VCTargetsPath)\Microsoft.Cpp.props) or which
+    /// are not found are skipped.
+    pub fn evaluate_file<P: AsRef<Path>>(&mut self, path: P) -> Result<(), LookupError> {
+        self.evaluate_file_at_depth(path.as_ref(), 0)
+    }
+
+    fn evaluate_file_at_depth(&mut self, path: &Path, depth: usize) -> Result<(), LookupError> {
+        // guard against import cycles
+        const MAX_IMPORT_DEPTH: usize = 16;
+        if depth > MAX_IMPORT_DEPTH {
+            return Err(LookupError::ParseError(format!(
+                "Too many nested imports in {}",
+                path.display()
+            )));
+        }
+        let filecontent = fs::read_to_string(path)?;
+        let doc = roxmltree::Document::parse(&filecontent)
+            .map_err(|e| LookupError::ParseError(format!("{}: {e}", path.display())))?;
+        let this_dir = path.parent().unwrap_or(Path::new(""));
+        let previous_dir = self.get("MSBuildThisFileDirectory").map(str::to_owned);
+        self.set(
+            "MSBuildThisFileDirectory",
+            &format!("{}{}", this_dir.display(), std::path::MAIN_SEPARATOR),
+        );
+        let result = self.evaluate_node(&doc.root_element(), this_dir, depth);
+        if let Some(previous_dir) = previous_dir {
+            self.set("MSBuildThisFileDirectory", &previous_dir);
+        }
+        result
+    }
+
+    fn evaluate_node(
+        &mut self,
+        node: &roxmltree::Node,
+        this_dir: &Path,
+        depth: usize,
+    ) -> Result<(), LookupError> {
+        for child in node.children().filter(roxmltree::Node::is_element) {
+            if !self.node_applies(&child) {
+                continue;
+            }
+            match child.tag_name().name() {
+                "PropertyGroup" => {
+                    for property in child.children().filter(roxmltree::Node::is_element) {
+                        if self.node_applies(&property) {
+                            let value = self.expand(property.text().unwrap_or_default());
+                            self.set(property.tag_name().name(), &value);
+                        }
+                    }
+                }
+                "ImportGroup" => self.evaluate_node(&child, this_dir, depth)?,
+                "Import" => {
+                    let imported = self.expand(child.attribute("Project").unwrap_or_default());
+                    if imported.is_empty() || imported.contains("$(") || imported.contains('*') {
+                        continue;
+                    }
+                    let imported_path = host_path(&imported, this_dir);
+                    if imported_path.is_file() {
+                        self.evaluate_file_at_depth(&imported_path, depth + 1)?;
+                    }
+                }
+                _ => {}
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Key of a configuration in the maps returned by the parsing functions, e.g. "Debug|x64"
+///
+/// The platform is omitted if the project does not specify one.
+pub fn configuration_key(configuration: &str, platform: Option<&str>) -> String {
+    match platform {
+        Some(platform) => format!("{configuration}|{platform}"),
+        None => configuration.to_owned(),
+    }
+}
+
+/// Whether the platform (e.g. Win32) produces 32-bit x86 executables, which are run under WOW64
+pub fn is_wow64_platform(platform: &str) -> bool {
+    platform.eq_ignore_ascii_case("Win32") || platform.eq_ignore_ascii_case("x86")
+}
+
+/// Pick the key of the configuration matching the given configuration and platform names
+/// (case-insensitively), which may be omitted if only one configuration matches the other
+pub fn select_configuration<'a, T>(
+    per_config: &'a HashMap<String, T>,
+    configuration: Option<&str>,
+    platform: Option<&str>,
+) -> Result<&'a String, LookupError> {
+    let mut available: Vec<&String> = per_config.keys().collect();
+    available.sort();
+    let matching: Vec<&String> = available
+        .iter()
+        .copied()
+        .filter(|key| {
+            let (c, p) = match key.split_once('|') {
+                Some((c, p)) => (c, Some(p)),
+                None => (key.as_str(), None),
+            };
+            configuration.is_none_or(|configuration| configuration.eq_ignore_ascii_case(c))
+                && platform
+                    .is_none_or(|platform| p.is_some_and(|p| platform.eq_ignore_ascii_case(p)))
+        })
+        .collect();
+    match matching.as_slice() {
+        [key] => Ok(key),
+        [] => Err(LookupError::ContextDeductionError(format!(
+            "No configuration matches {}; available configurations: {available:?}",
+            configuration_key(configuration.unwrap_or("*"), platform)
+        ))),
+        _ => Err(LookupError::ContextDeductionError(format!(
+            "Several configurations match, specify the configuration and/or the platform; \
+            matching configurations: {matching:?}"
+        ))),
+    }
+}
+
 /// Debugging configurations
 ///
-/// Extracted from a .vcxproj.user file
-/// Grouped by configuration (e.g. Debug, Release, ...)
+/// Extracted from a .vcxproj.user file, or from the project and its property sheets
+/// Grouped by configuration (e.g. Debug, Release, ...) and platform (e.g. x64, Win32, ...)
 #[derive(Debug)]
 pub struct VcxDebuggingConfiguration {
     pub configuration: String,
+    pub platform: Option<String>,
     pub path: Option<Vec<PathBuf>>,
     pub working_directory: Option<PathBuf>,
 }
 
-fn extract_config_from_node(n: &roxmltree::Node) -> Result<String, LookupError> {
+/// Configuration and platform of a node with a Condition such as '$(Configuration)|$(Platform)'=='Debug|x64'
+fn extract_config_from_node(n: &roxmltree::Node) -> Result<(String, Option<String>), LookupError> {
     let configuration_re =
-        regex::Regex::new(r"'\$\(Configuration\)(?:\|\$\(Platform\))?'=='(\w+)(?:\|\w+)?'")?;
+        regex::Regex::new(r"'\$\(Configuration\)(?:\|\$\(Platform\))?'=='(\w+)(?:\|(\w+))?'")?;
     let configuration_condition_text = n
         .attribute("Condition")
         .ok_or_else(|| LookupError::ParseError("Failed to find Condition group".to_owned()))?;
-    let config: String = configuration_re
+    let captures = configuration_re
         .captures_iter(configuration_condition_text)
         .next()
-        .ok_or_else(|| LookupError::ParseError("Failed to find configuration name".to_owned()))?
+        .ok_or_else(|| LookupError::ParseError("Failed to find configuration name".to_owned()))?;
+    let config: String = captures
         .get(1)
         .ok_or_else(|| LookupError::ParseError("Failed to find configuration name".to_owned()))?
         .as_str()
         .to_owned();
-    Ok(config)
+    Ok((config, captures.get(2).map(|p| p.as_str().to_owned())))
 }
 
-fn extract_debugging_configuration_from_config_node(
-    n: &roxmltree::Node,
-) -> Result<VcxDebuggingConfiguration, LookupError> {
-    let config = extract_config_from_node(n)?;
-
-    let mut ret = VcxDebuggingConfiguration {
-        configuration: config,
-        path: None,
-        working_directory: None,
-    };
-
-    if let Some(environment_node) = n
+/// Configurations (and platforms) of a project: the ProjectConfiguration items, or else the ones
+/// appearing in the conditions of the property groups
+fn project_configurations(project_node: &roxmltree::Node) -> Vec<(String, Option<String>)> {
+    let mut configurations: Vec<(String, Option<String>)> = project_node
         .descendants()
-        .find(|n| n.has_tag_name("LocalDebuggerEnvironment"))
-    {
-        let environment_text = environment_node.text().ok_or_else(|| {
-            LookupError::ParseError("Failed to find LocalDebuggerEnvironment tag".to_owned())
-        })?;
-        let environment_variables: Vec<&str> = environment_text.lines().collect();
-        let path_env_var = environment_variables
-            .iter()
-            .find(|l| l.trim_start().starts_with("PATH="))
-            .ok_or_else(|| LookupError::ParseError("Failed to find PATH variable".to_owned()))?;
-        let path_env_var_without_varname = path_env_var.strip_prefix("PATH=").ok_or_else(|| {
-            LookupError::ParseError("Failed to find LocalDebuggerEnvironment tag".to_owned())
-        })?;
-        let path_entries = path_env_var_without_varname.split(';');
-        let path_entries_no_vars: Vec<PathBuf> = path_entries
-            .filter(|s| !s.contains('$') && !s.contains('%') && !s.is_empty())
-            .map(PathBuf::from)
+        .filter(|n| n.has_tag_name("ProjectConfiguration"))
+        .filter_map(|n| n.attribute("Include"))
+        .map(|c| match c.split_once('|') {
+            Some((config, platform)) => (config.to_owned(), Some(platform.to_owned())),
+            None => (c.to_owned(), None),
+        })
+        .collect();
+    if configurations.is_empty() {
+        configurations = project_node
+            .descendants()
+            .filter(|n| n.has_tag_name("PropertyGroup"))
+            .filter_map(|n| extract_config_from_node(&n).ok())
             .collect();
-        ret.path = Some(path_entries_no_vars);
+        configurations.sort();
+        configurations.dedup();
     }
+    configurations
+}
 
-    if let Some(working_directory_node) = n
-        .descendants()
-        .find(|n| n.has_tag_name("LocalDebuggerWorkingDirectory"))
-    {
-        let working_directory_text = working_directory_node.text().ok_or_else(|| {
-            LookupError::ParseError("Failed to find LocalDebuggerEnvironment tag".to_owned())
-        })?;
-        // TODO fetch properties from vcxproj? may get out of hand
-        if !working_directory_text.starts_with('$') {
-            ret.working_directory = Some(PathBuf::from(working_directory_text));
-        }
+/// Debugging configuration, from the properties evaluated for a configuration
+fn extract_debugging_configuration(
+    configuration: &str,
+    platform: Option<&str>,
+    properties: &MsBuildProperties,
+    anchor: &Path,
+) -> VcxDebuggingConfiguration {
+    // entries with unresolved macros or environment variables are dropped
+    let resolved = |s: &&str| !s.contains('$') && !s.contains('%') && !s.is_empty();
+    let path = properties.get("LocalDebuggerEnvironment").and_then(|env| {
+        let path_env_var = env.lines().find(|l| l.trim_start().starts_with("PATH="))?;
+        let path_entries = path_env_var.trim_start().strip_prefix("PATH=")?.split(';');
+        Some(
+            path_entries
+                .filter(resolved)
+                .map(|s| host_path(s, anchor))
+                .collect(),
+        )
+    });
+    let working_directory = properties
+        .get("LocalDebuggerWorkingDirectory")
+        .filter(resolved)
+        .map(|wd| host_path(wd, anchor));
+    VcxDebuggingConfiguration {
+        configuration: configuration.to_owned(),
+        platform: platform.map(str::to_owned),
+        path,
+        working_directory,
     }
-
-    Ok(ret)
 }
 
 // extracts the debugging configuration for an executable from the respective .vcxproj.user file
 //
 // A .vcxproj file can only relate to a single executable, but there may be specified many
-// configurations (Debug, Release, ...)
+// configurations (Debug, Release, ...) and platforms (x64, Win32, ...), see `configuration_key`
 // Extracted properties:
 // - PATH variable (in the <LocalDebuggerEnvironment> property)
 // - working directory (value of the <LocalDebuggerWorkingDirectory> property)
+//
+// The MSBuild macros are expanded with the properties of the .vcxproj next to the file, if found.
+// The paths are translated with `host_path`, so that the file can also be parsed on other OSes
 pub fn parse_vcxproj_user<P: AsRef<std::path::Path> + ?Sized>(
     p: &P,
 ) -> anyhow::Result<HashMap<String, VcxDebuggingConfiguration>> {
@@ -101,53 +395,128 @@ pub fn parse_vcxproj_user<P: AsRef<std::path::Path> + ?Sized>(
         .descendants()
         .find(|n| n.has_tag_name("Project"))
         .ok_or_else(|| LookupError::ParseError("Failed to find Project tag".to_owned()))?;
-    let configuration_nodes: Vec<_> = project_node
-        .descendants()
-        .filter(|n| n.has_tag_name("PropertyGroup"))
-        .collect();
-    let debugging_config_per_config: HashMap<String, VcxDebuggingConfiguration> =
-        configuration_nodes
-            .iter()
-            .map(extract_debugging_configuration_from_config_node)
-            .filter_map(Result::ok)
-            .map(|e: VcxDebuggingConfiguration| (e.configuration.clone(), e))
-            .collect();
+
+    let user_path = PathBuf::from(readable_canonical_path(p.as_ref())?);
+    let anchor = user_path.parent().unwrap_or(&user_path);
+    // the .vcxproj.user file is imported at the end of the .vcxproj
+    let vcxproj_path = user_path.with_extension("");
+
+    let mut debugging_config_per_config = HashMap::new();
+    for (configuration, platform) in project_configurations(&project_node) {
+        let mut properties =
+            MsBuildProperties::for_project(&vcxproj_path, &configuration, platform.as_deref());
+        if vcxproj_path.exists() {
+            properties.evaluate_project(&vcxproj_path)?;
+        }
+        properties.evaluate_file(&user_path)?;
+        debugging_config_per_config.insert(
+            configuration_key(&configuration, platform.as_deref()),
+            extract_debugging_configuration(
+                &configuration,
+                platform.as_deref(),
+                &properties,
+                anchor,
+            ),
+        );
+    }
     Ok(debugging_config_per_config)
 }
 
 /// Executable Information
 ///
 /// Extracted from a .vcxproj file
-/// Grouped by configuration (e.g. Debug, Release, ...)
+/// Grouped by configuration (e.g. Debug, Release, ...) and platform (e.g. x64, Win32, ...)
 /// Contains VcxDebuggingConfiguration extracted from respective .vcxproj.user, if present
 #[derive(Debug)]
 pub struct VcxExecutableInformation {
     pub configuration: String,
+    pub platform: Option<String>,
     pub executable_path: PathBuf,
     pub debugging_configuration: Option<VcxDebuggingConfiguration>,
+    /// existing directories that likely contain runtime DLLs, deduced from the linker settings and
+    /// the post-build copy steps: candidate user path entries
+    pub suggested_path: Vec<PathBuf>,
 }
 
-fn extract_tag(root: &roxmltree::Node, tag: &str) -> HashMap<String, String> {
-    root.descendants()
-        .filter(|n: &roxmltree::Node| n.has_tag_name(tag))
-        .map(|n| {
-            if let Some(od) = n.text() {
-                extract_config_from_node(&n).map(|c| (c, od.to_owned()))
-            } else {
-                Err(LookupError::ParseError(format!("Empty {tag} tag")))
+/// Directories likely containing the runtime DLLs of a project, for the configuration the
+/// properties were evaluated for
+///
+/// For each directory of <AdditionalLibraryDirectories> (linker settings), the directory itself
+/// and its sibling bin directory if it is a lib directory (as in third_party/foo/lib); and the
+/// directories of the DLLs copied by the <PostBuildEvent> commands. Only the existing directories
+/// are returned.
+fn suggest_runtime_dirs(
+    project_node: &roxmltree::Node,
+    properties: &MsBuildProperties,
+    anchor: &Path,
+) -> Vec<PathBuf> {
+    let mut candidates: Vec<PathBuf> = Vec::new();
+    let item_definitions = project_node
+        .children()
+        .filter(|n| n.has_tag_name("ItemDefinitionGroup") && properties.node_applies(n));
+    for group in item_definitions {
+        let library_dirs = group
+            .descendants()
+            .filter(|n| n.has_tag_name("AdditionalLibraryDirectories"))
+            .filter_map(|n| n.text())
+            .map(|t| properties.expand(t));
+        for library_dir in library_dirs {
+            for entry in library_dir.split(';').map(str::trim) {
+                if entry.is_empty() || entry.contains("$(") || entry.contains('%') {
+                    continue;
+                }
+                let dir = host_path(entry, anchor);
+                let is_lib_dir = dir
+                    .file_name()
+                    .and_then(|n| n.to_str())
+                    .map(|n| n.eq_ignore_ascii_case("lib") || n.eq_ignore_ascii_case("lib64"))
+                    .unwrap_or(false);
+                if is_lib_dir {
+                    if let Some(parent) = dir.parent() {
+                        candidates.push(parent.join("bin"));
+                    }
+                }
+                candidates.push(dir);
             }
-        })
-        .filter_map(Result::ok)
-        .collect()
+        }
+        let commands = group
+            .descendants()
+            .filter(|n| n.has_tag_name("PostBuildEvent"))
+            .flat_map(|n| n.children().filter(|c| c.has_tag_name("Command")))
+            .filter_map(|n| n.text())
+            .map(|t| properties.expand(t));
+        for command in commands {
+            let copied_dlls = command
+                .split(|c: char| c.is_whitespace() || c == '"')
+                .filter(|token| token.to_ascii_lowercase().ends_with(".dll"))
+                .filter(|token| !token.contains("$(") && !token.contains('%'));
+            for dll in copied_dlls {
+                if let Some(dir) = host_path(dll, anchor).parent() {
+                    candidates.push(dir.to_owned());
+                }
+            }
+        }
+    }
+    let mut suggested: Vec<PathBuf> = Vec::new();
+    for candidate in candidates {
+        if candidate.is_dir() && !suggested.contains(&candidate) {
+            suggested.push(candidate);
+        }
+    }
+    suggested
 }
 
 // extracts relevant information for an executable from the respective .vcxproj file
 //
 // A .vcxproj file can only relate to a single executable, but there may be specified many
-// configurations (Debug, Release, ...)
+// configurations (Debug, Release, ...) and platforms (x64, Win32, ...), see `configuration_key`
 // Extracted properties:
 // - output executable path (composed of <OutDir>, <TargetName>, <TargetExt>)
 // - debugging information, if the respective .vcxproj.user is found next to the .vcxproj
+// - directories likely containing runtime DLLs, from the linker settings and post-build steps
+//
+// The MSBuild macros are expanded with the properties evaluated for each configuration.
+// The paths are translated with `host_path`, so that the file can also be parsed on other OSes
 pub fn parse_vcxproj<P: AsRef<std::path::Path> + ?Sized>(
     p: &P,
 ) -> anyhow::Result<HashMap<String, VcxExecutableInformation>> {
@@ -161,57 +530,59 @@ pub fn parse_vcxproj<P: AsRef<std::path::Path> + ?Sized>(
             readable_canonical_path(p.as_ref())?
         )))?;
 
-    // extract the file path the config refers to (outdir + target name + extension)
-    let outdir_per_config = extract_tag(&project_node, "OutDir");
-    let targetname_per_config = extract_tag(&project_node, "TargetName");
-    let targetext_per_config = extract_tag(&project_node, "TargetExt");
+    let vcxproj_path = PathBuf::from(readable_canonical_path(p.as_ref())?);
+    let anchor = vcxproj_path.parent().unwrap_or(&vcxproj_path);
 
-    let configs: Vec<_> = outdir_per_config.keys().collect();
+    // the .vcxproj.user file is imported at the end of the .vcxproj
+    let mut vcxuser_filename = vcxproj_path.as_os_str().to_owned();
+    vcxuser_filename.push(".user");
+    let vcxproj_user_path = PathBuf::from(vcxuser_filename);
 
-    let mut executable_info_per_config: HashMap<String, VcxExecutableInformation> = configs
-        .iter()
-        .map(|&c| {
-            let (e_dir, e_name, e_ext) = (
-                &outdir_per_config[c],
-                &targetname_per_config[c],
-                &targetext_per_config[c],
-            );
-            // the following assumes that parent_dir ends with a backslash
-            if let Some(parent_dir) = std::path::Path::new(e_dir).to_str() {
-                Ok((
-                    c.clone(),
-                    VcxExecutableInformation {
-                        configuration: c.clone(),
-                        executable_path: PathBuf::from(parent_dir.to_owned() + e_name + e_ext),
-                        debugging_configuration: None,
-                    },
-                ))
-            } else {
-                Err(LookupError::ParseError(
-                    "Could not find executable path".to_owned(),
-                ))
-            }
-        })
-        .filter_map(Result::ok)
-        .collect();
-
-    if let Some(parent_dir) = p.as_ref().parent() {
-        if let Some(filename) = p.as_ref().file_name() {
-            let mut vcxuser_filename = filename.to_owned();
-            vcxuser_filename.push(".user");
-            let vcxproj_user_path = parent_dir.join(vcxuser_filename);
-            if vcxproj_user_path.exists() {
-                if let Ok(debugging_configuration_per_config) =
-                    parse_vcxproj_user(&vcxproj_user_path)
-                {
-                    for (c, dc) in debugging_configuration_per_config {
-                        if let Some(outdir) = executable_info_per_config.get_mut(&c) {
-                            outdir.debugging_configuration = Some(dc);
-                        }
-                    }
-                }
-            }
+    // extract the file path the config refers to (outdir + target name + extension)
+    let mut executable_info_per_config: HashMap<String, VcxExecutableInformation> = HashMap::new();
+    for (configuration, platform) in project_configurations(&project_node) {
+        let mut properties =
+            MsBuildProperties::for_project(&vcxproj_path, &configuration, platform.as_deref());
+        properties.evaluate_project(&vcxproj_path)?;
+        let outdir = match properties.get("OutDir") {
+            Some(outdir) => outdir,
+            None => continue,
+        };
+        // the following assumes that the output directory ends with a backslash
+        let e_name = properties
+            .get("TargetName")
+            .or_else(|| properties.get("ProjectName"))
+            .unwrap_or_default();
+        let e_ext = properties.get("TargetExt").unwrap_or(".exe");
+        let executable_path = host_path(&format!("{outdir}{e_name}{e_ext}"), anchor);
+        let suggested_path = suggest_runtime_dirs(&project_node, &properties, anchor);
+
+        // the debugging settings may come from the project, its property sheets or the user file
+        // a user file that cannot be parsed is ignored, as Visual Studio does
+        if vcxproj_user_path.exists() {
+            let _ = properties.evaluate_file(&vcxproj_user_path);
         }
+        let debugging_configuration = (properties.get("LocalDebuggerEnvironment").is_some()
+            || properties.get("LocalDebuggerWorkingDirectory").is_some())
+        .then(|| {
+            extract_debugging_configuration(
+                &configuration,
+                platform.as_deref(),
+                &properties,
+                anchor,
+            )
+        });
+
+        executable_info_per_config.insert(
+            configuration_key(&configuration, platform.as_deref()),
+            VcxExecutableInformation {
+                configuration,
+                platform,
+                executable_path,
+                debugging_configuration,
+                suggested_path,
+            },
+        );
     }
 
     Ok(executable_info_per_config)
@@ -234,13 +605,19 @@ mod tests {
         config.sort();
         assert_eq!(
             config,
-            vec!["Debug", "MinSizeRel", "RelWithDebInfo", "Release"]
+            vec![
+                "Debug|x64",
+                "MinSizeRel|x64",
+                "RelWithDebInfo|x64",
+                "Release|x64"
+            ]
         );
 
-        let debug_exe_info = &p["Debug"];
+        let debug_exe_info = &p["Debug|x64"];
 
-        assert!(&debug_exe_info.executable_path.to_str().unwrap()
-            .ends_with(r"\test_data\test_project1\DepRunTest\build-vcxproj-user\DepRunTest\Debug\DepRunTest.exe"));
+        assert!(debug_exe_info.executable_path.ends_with(
+            "test_data/test_project1/DepRunTest/build-vcxproj-user/DepRunTest/Debug/DepRunTest.exe"
+        ));
 
         assert!(debug_exe_info.debugging_configuration.is_some());
         let deb_config = debug_exe_info.debugging_configuration.as_ref().unwrap();
@@ -248,21 +625,15 @@ mod tests {
         assert_eq!(deb_config.configuration, "Debug");
 
         assert!(deb_config.working_directory.is_some());
-        assert!(deb_config
-            .working_directory
-            .as_ref()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .ends_with(
-                r"test_data\test_project1\DepRunTest\build-vcxproj-user\DepRunTestLib\Debug"
-            ));
+        assert!(deb_config.working_directory.as_ref().unwrap().ends_with(
+            "test_data/test_project1/DepRunTest/build-vcxproj-user/DepRunTestLib/Debug"
+        ));
 
         assert!(deb_config.path.is_some());
         let p = deb_config.path.as_ref().unwrap();
         assert_eq!(p.len(), 1);
-        assert!(p.first().unwrap().to_str().unwrap().ends_with(
-            r"test_data\test_project1\DepRunTest\build-vcxproj-user\DepRunTestLib\Debug"
+        assert!(p.first().unwrap().ends_with(
+            "test_data/test_project1/DepRunTest/build-vcxproj-user/DepRunTestLib/Debug"
         ));
 
         Ok(())
@@ -280,14 +651,19 @@ mod tests {
         config.sort();
         assert_eq!(
             config,
-            vec!["Debug", "MinSizeRel", "RelWithDebInfo", "Release"]
+            vec![
+                "Debug|x64",
+                "MinSizeRel|x64",
+                "RelWithDebInfo|x64",
+                "Release|x64"
+            ]
         );
 
-        let debug_exe_info = &p["Debug"];
+        let debug_exe_info = &p["Debug|x64"];
 
-        assert!(&debug_exe_info.executable_path.to_str().unwrap().ends_with(
-            r"\test_data\test_project1\DepRunTest\build\DepRunTest\Debug\DepRunTest.exe"
-        ));
+        assert!(debug_exe_info
+            .executable_path
+            .ends_with("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe"));
 
         assert!(debug_exe_info.debugging_configuration.is_none());
 
@@ -303,29 +679,109 @@ mod tests {
 
         let mut config: Vec<&String> = p.keys().collect();
         config.sort();
-        assert_eq!(config, vec!["Debug"]);
+        assert_eq!(config, vec!["Debug|x64"]);
 
-        let deb_config = &p["Debug"];
+        let deb_config = &p["Debug|x64"];
 
         assert_eq!(deb_config.configuration, "Debug");
 
-        assert!(deb_config
-            .working_directory
-            .as_ref()
-            .unwrap()
-            .to_str()
-            .unwrap()
-            .ends_with(
-                r"test_data\test_project1\DepRunTest\build-vcxproj-user\DepRunTestLib\Debug"
-            ));
+        assert!(deb_config.working_directory.as_ref().unwrap().ends_with(
+            "test_data/test_project1/DepRunTest/build-vcxproj-user/DepRunTestLib/Debug"
+        ));
 
         assert!(deb_config.path.is_some());
         let p = deb_config.path.as_ref().unwrap();
         assert_eq!(p.len(), 1);
-        assert!(p.first().unwrap().to_str().unwrap().ends_with(
-            r"test_data\test_project1\DepRunTest\build-vcxproj-user\DepRunTestLib\Debug"
+        assert!(p.first().unwrap().ends_with(
+            "test_data/test_project1/DepRunTest/build-vcxproj-user/DepRunTestLib/Debug"
         ));
 
         Ok(())
     }
+
+    #[test]
+    fn vcxproj_macros() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+        let vcxproj_path = d.join("test_data/vcx/Macros/Macros.vcxproj");
+        let p = super::parse_vcxproj(&vcxproj_path)?;
+
+        assert!(p["Debug|x64"]
+            .executable_path
+            .ends_with("test_data/vcx/bin/Debug/Macros_x64.exe"));
+        assert!(p["Release|x64"]
+            .executable_path
+            .ends_with("test_data/vcx/bin/Release/Macros.exe"));
+
+        assert!(p["Debug|Win32"]
+            .executable_path
+            .ends_with("test_data/vcx/bin/Debug/Macros_Win32.exe"));
+        assert!(p["Debug|Win32"].debugging_configuration.is_none());
+
+        let suggested = &p["Debug|x64"].suggested_path;
+        assert_eq!(suggested.len(), 3);
+        assert!(suggested[0].ends_with("test_data/vcx/third_party/foo/bin"));
+        assert!(suggested[1].ends_with("test_data/vcx/third_party/foo/lib"));
+        assert!(suggested[2].ends_with("test_data/vcx/third_party/bar/dlls"));
+        assert!(p["Release|x64"].suggested_path.is_empty());
+
+        assert_eq!(
+            super::select_configuration(&p, Some("release"), None)?,
+            "Release|x64"
+        );
+        assert_eq!(
+            super::select_configuration(&p, Some("Debug"), Some("win32"))?,
+            "Debug|Win32"
+        );
+        assert!(super::select_configuration(&p, Some("Debug"), None).is_err());
+        assert!(super::select_configuration(&p, None, Some("ARM64")).is_err());
+
+        let deb_config = p["Debug|x64"].debugging_configuration.as_ref().unwrap();
+        let path = deb_config.path.as_ref().unwrap();
+        assert_eq!(path.len(), 1);
+        assert!(path[0].ends_with("test_data/vcx/lib/Debug"));
+        assert!(deb_config
+            .working_directory
+            .as_ref()
+            .unwrap()
+            .ends_with("test_data/vcx/bin/Debug"));
+
+        let properties = super::MsBuildProperties::for_project(&vcxproj_path, "Debug", None);
+        assert_eq!(
+            properties.expand("$(configuration)|$(Undefined)|%PATH%"),
+            "Debug|$(Undefined)|%PATH%"
+        );
+        assert!(properties.condition_holds("'$(Configuration)'=='debug'"));
+        assert!(!properties.condition_holds("'$(Configuration)'!='Debug'"));
+        assert!(properties.condition_holds("exists('$(ProjectDir)')"));
+        assert!(!properties.condition_holds("!Exists('$(ProjectDir)Macros.vcxproj')"));
+
+        Ok(())
+    }
+
+    #[test]
+    fn vcxproj_property_sheets() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+
+        let vcxproj_path = d.join("test_data/vcx/Sheets/Sheets.vcxproj");
+        let p = super::parse_vcxproj(&vcxproj_path)?;
+
+        // the output directory comes from Directory.Build.props
+        assert!(p["Debug|x64"]
+            .executable_path
+            .ends_with("test_data/vcx/out/Debug/Sheets.exe"));
+
+        // the debugging environment comes from the imported property sheet
+        let deb_config = p["Debug|x64"].debugging_configuration.as_ref().unwrap();
+        let path = deb_config.path.as_ref().unwrap();
+        assert_eq!(path.len(), 1);
+        assert!(path[0].ends_with("test_data/vcx/deps/bin"));
+        assert!(deb_config
+            .working_directory
+            .as_ref()
+            .unwrap()
+            .ends_with("test_data/vcx/Sheets/props"));
+
+        Ok(())
+    }
 }
@@ -0,0 +1,136 @@
+//! Verification that a deployment directory contains all the non-system dependencies of the
+//! executables in it
+
+use std::collections::HashSet;
+use std::path::{Path, PathBuf};
+
+use fs_err as fs;
+use serde::Serialize;
+
+use crate::common::{LookupError, NamePatterns};
+use crate::path::LookupPath;
+use crate::pe::{PEFile, PEFileMap};
+
+/// Reason why a dependency breaks the self-containment of the directory
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum ClosureViolationKind {
+    /// the DLL is not in the directory, nor anywhere on the lookup path
+    NotFound,
+    /// the DLL is only found outside the directory, in a non-system location
+    ResolvedOutside(PathBuf),
+}
+
+/// Dependency of an executable of the directory that is not provided by the directory itself
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub struct ClosureViolation {
+    /// executable file of the directory
+    pub importer: PathBuf,
+    /// name of the dependency, as referenced by the importer
+    pub dllname: String,
+    pub kind: ClosureViolationKind,
+}
+
+/// Check that every static and delay-load import of the .exe and .dll files in the directory
+/// resolves to a file of the directory, to a system DLL or to a name matching the allowed patterns
+///
+/// The lookup path is used to tell system DLLs apart; it should be deduced for an executable of
+/// the directory. Files that cannot be parsed are skipped.
+pub fn verify_closure<P: AsRef<Path>>(
+    dir: P,
+    lookup_path: &LookupPath,
+    allowed: &NamePatterns,
+) -> Result<Vec<ClosureViolation>, LookupError> {
+    let executables = executables_in_dir(dir.as_ref())?;
+    let provided: HashSet<String> = executables
+        .iter()
+        .filter_map(|p| p.file_name()?.to_str())
+        .map(str::to_lowercase)
+        .collect();
+
+    let mut violations = Vec::new();
+    for importer in executables {
+        let pefilemap = PEFileMap::new(&importer)?;
+        let pefile = PEFile::new(&pefilemap)?;
+        let mut dependencies = match pefile.read_dependencies() {
+            Ok(dependencies) => dependencies,
+            Err(_) => continue,
+        };
+        dependencies.extend(pefile.read_delay_load_dependencies().unwrap_or_default());
+
+        for dllname in dependencies {
+            if provided.contains(&dllname.to_lowercase()) || allowed.matches(&dllname) {
+                continue;
+            }
+            let kind = match lookup_path.search_dll(&dllname)? {
+                Some(r) if r.location.is_system() => continue,
+                Some(r) => match r.fullpath {
+                    Some(fullpath) => ClosureViolationKind::ResolvedOutside(fullpath),
+                    None => continue,
+                },
+                None => ClosureViolationKind::NotFound,
+            };
+            violations.push(ClosureViolation {
+                importer: importer.clone(),
+                dllname,
+                kind,
+            });
+        }
+    }
+    Ok(violations)
+}
+
+/// Paths of the .exe and .dll files in the directory (not recursive), sorted by name
+pub fn executables_in_dir(dir: &Path) -> Result<Vec<PathBuf>, LookupError> {
+    let mut executables = Vec::new();
+    for entry in fs::read_dir(dir)? {
+        let path = entry?.path();
+        let is_executable = path
+            .extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.eq_ignore_ascii_case("exe") || e.eq_ignore_ascii_case("dll"))
+            .unwrap_or(false);
+        if is_executable && path.is_file() {
+            executables.push(path);
+        }
+    }
+    executables.sort();
+    Ok(executables)
+}
+
+#[cfg(test)]
+mod tests {
+    use crate::common::{LookupError, NamePatterns};
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::verify::{verify_closure, ClosureViolationKind};
+
+    #[test]
+    fn verify_directory() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let complete_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        let broken_dir = d.join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug");
+        let system_dlls = NamePatterns::new(&[
+            "kernel32.dll",
+            "ucrtbased.dll",
+            "vcruntime140*.dll",
+            "msvcp140*.dll",
+        ])?;
+
+        let query =
+            LookupQuery::deduce_from_executable_location(complete_dir.join("DepRunTest.exe"))?;
+        let violations = verify_closure(&complete_dir, &LookupPath::deduce(&query), &system_dlls)?;
+        assert!(violations.is_empty(), "{violations:?}");
+
+        let query =
+            LookupQuery::deduce_from_executable_location(broken_dir.join("DepRunTest.exe"))?;
+        let violations = verify_closure(&broken_dir, &LookupPath::deduce(&query), &system_dlls)?;
+        let lib_violation = violations
+            .iter()
+            .find(|v| v.dllname == "DepRunTestLib.dll")
+            .unwrap();
+        assert_eq!(lib_violation.kind, ClosureViolationKind::NotFound);
+        assert!(lib_violation.importer.ends_with("DepRunTest.exe"));
+
+        Ok(())
+    }
+}
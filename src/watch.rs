@@ -0,0 +1,454 @@
+//! Continuous rescanning of a target while the DLLs on its lookup path change
+//!
+//! A [`ScanWatcher`] watches the lookup directories and the directories of the resolved files of
+//! a scan; each batch of changes of executable files is rescanned and reported as a
+//! [`WatchChange`], with the events concerning single dependencies (replaced, removed, shadowed by
+//! a new candidate) and the differences between the two scans.
+
+use std::path::{Path, PathBuf};
+use std::sync::mpsc::{channel, Receiver};
+use std::time::{Duration, Instant};
+
+use notify::{Event, RecommendedWatcher, RecursiveMode, Watcher};
+use serde::Serialize;
+
+use crate::common::{readable_path, LookupError};
+use crate::diff::ExecutablesDiff;
+use crate::executable::{Executable, Executables};
+use crate::path::LookupPath;
+use crate::query::LookupQuery;
+use crate::runner::{rescan, run};
+
+/// Time to wait for further events after a change, so that a batch of copies triggers one rescan
+const DEBOUNCE_INTERVAL: Duration = Duration::from_millis(300);
+
+/// Directories whose content can change the result of the scan: the application and working
+/// directories, and the directories of the lookup path
+pub fn watched_directories(query: &LookupQuery, lookup_path: &LookupPath) -> Vec<PathBuf> {
+    let mut dirs: Vec<PathBuf> = Vec::new();
+    let candidates = [
+        query.target.app_dir.clone(),
+        query.target.working_dir.clone(),
+    ]
+    .into_iter()
+    .chain(lookup_path.search_path());
+    for dir in candidates {
+        if dir.is_dir() && !dirs.contains(&dir) {
+            dirs.push(dir);
+        }
+    }
+    dirs
+}
+
+/// Change of a single dependency noticed by a [`ScanWatcher`]
+#[derive(Debug, Clone, PartialEq, Eq, Serialize)]
+pub enum WatchEvent {
+    /// the file the DLL is loaded from was modified or replaced in place
+    DependencyReplaced { dllname: String, path: PathBuf },
+    /// the file the DLL was loaded from was removed; the DLL is now loaded from another file, if
+    /// any
+    DependencyRemoved {
+        dllname: String,
+        path: PathBuf,
+        replacement: Option<PathBuf>,
+    },
+    /// a new file with the name of the DLL appeared earlier in the lookup path, and is now loaded
+    /// instead of the previous one
+    CandidateShadowing {
+        dllname: String,
+        path: PathBuf,
+        shadowed: PathBuf,
+    },
+    /// a DLL missing from the previous scan is now found
+    DependencyAppeared { dllname: String, path: PathBuf },
+}
+
+impl std::fmt::Display for WatchEvent {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            WatchEvent::DependencyReplaced { dllname, path } => {
+                write!(f, "{dllname}: {} was replaced", readable_path(path))
+            }
+            WatchEvent::DependencyRemoved {
+                dllname,
+                path,
+                replacement: Some(replacement),
+            } => write!(
+                f,
+                "{dllname}: {} was removed, now loaded from {}",
+                readable_path(path),
+                readable_path(replacement)
+            ),
+            WatchEvent::DependencyRemoved {
+                dllname,
+                path,
+                replacement: None,
+            } => write!(
+                f,
+                "{dllname}: {} was removed, now missing",
+                readable_path(path)
+            ),
+            WatchEvent::CandidateShadowing {
+                dllname,
+                path,
+                shadowed,
+            } => write!(
+                f,
+                "{dllname}: {} now shadows {}",
+                readable_path(path),
+                readable_path(shadowed)
+            ),
+            WatchEvent::DependencyAppeared { dllname, path } => {
+                write!(f, "{dllname}: now found in {}", readable_path(path))
+            }
+        }
+    }
+}
+
+/// Result of the rescan triggered by a batch of changes of the watched files
+#[derive(Debug, Clone, Default, PartialEq, Eq, Serialize)]
+pub struct WatchChange {
+    /// changes of the dependencies of the previous scan, in order of first appearance
+    pub events: Vec<WatchEvent>,
+    /// differences between the previous and the new scan
+    pub diff: ExecutablesDiff,
+}
+
+impl WatchChange {
+    /// Compare the scans before and after a change of the watched files
+    pub fn compare(old: &Executables, new: &Executables) -> Self {
+        let mut events = Vec::new();
+        for old_exe in old.iter() {
+            let Some(old_path) = resolved_path(old_exe) else {
+                continue;
+            };
+            let new_exe = new.get(&old_exe.dllname);
+            match new_exe.and_then(resolved_path) {
+                Some(new_path) if new_path == old_path => {
+                    let stamp =
+                        |e: &Executable| e.details.as_ref().and_then(|d| d.file_stamp.clone());
+                    if new_exe.and_then(stamp) != stamp(old_exe) {
+                        events.push(WatchEvent::DependencyReplaced {
+                            dllname: old_exe.dllname.clone(),
+                            path: old_path.to_owned(),
+                        });
+                    }
+                }
+                Some(new_path) if old_path.exists() => {
+                    events.push(WatchEvent::CandidateShadowing {
+                        dllname: old_exe.dllname.clone(),
+                        path: new_path.to_owned(),
+                        shadowed: old_path.to_owned(),
+                    })
+                }
+                new_path => events.push(WatchEvent::DependencyRemoved {
+                    dllname: old_exe.dllname.clone(),
+                    path: old_path.to_owned(),
+                    replacement: new_path.map(Path::to_path_buf),
+                }),
+            }
+        }
+        for new_exe in new.iter() {
+            let was_missing = old.get(&new_exe.dllname).map(|e| !e.found).unwrap_or(false);
+            if let Some(path) = resolved_path(new_exe).filter(|_| was_missing) {
+                events.push(WatchEvent::DependencyAppeared {
+                    dllname: new_exe.dllname.clone(),
+                    path: path.to_owned(),
+                });
+            }
+        }
+        Self {
+            events,
+            diff: ExecutablesDiff::compare(old, new),
+        }
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.events.is_empty() && self.diff.is_empty()
+    }
+}
+
+impl std::fmt::Display for WatchChange {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        for event in &self.events {
+            writeln!(f, "{event}")?;
+        }
+        write!(f, "{}", self.diff)
+    }
+}
+
+/// File the executable is loaded from, if it was found and is not an API set
+fn resolved_path(exe: &Executable) -> Option<&Path> {
+    match exe.details.as_ref() {
+        Some(d) if exe.found && !d.is_api_set => d.full_path.as_deref(),
+        _ => None,
+    }
+}
+
+/// Scan of a target, kept up to date by rescanning it when executable files change in the
+/// watched directories
+pub struct ScanWatcher<'a> {
+    query: &'a LookupQuery,
+    lookup_path: &'a LookupPath<'a>,
+    executables: Executables,
+    directories: Vec<PathBuf>,
+    events: Receiver<notify::Result<Event>>,
+    /// directories where executable files changed since the last rescan
+    changed_dirs: Vec<PathBuf>,
+    /// time of the latest change of an executable file not rescanned yet
+    last_change: Option<Instant>,
+    // dropping the watcher stops the notifications
+    _watcher: RecommendedWatcher,
+}
+
+impl<'a> ScanWatcher<'a> {
+    /// Scan the target and start watching the directories relevant for the scan
+    pub fn new(
+        query: &'a LookupQuery,
+        lookup_path: &'a LookupPath<'a>,
+    ) -> Result<Self, LookupError> {
+        Self::from_scan(query, lookup_path, run(query, lookup_path)?)
+    }
+
+    /// Start watching the directories relevant for a previous scan of the target: the lookup
+    /// directories (see [`watched_directories`]) and the ones the DLLs were loaded from
+    pub fn from_scan(
+        query: &'a LookupQuery,
+        lookup_path: &'a LookupPath<'a>,
+        executables: Executables,
+    ) -> Result<Self, LookupError> {
+        let mut directories = watched_directories(query, lookup_path);
+        for dir in executables
+            .iter()
+            .filter_map(resolved_path)
+            .filter_map(Path::parent)
+        {
+            if dir.is_dir() && !directories.iter().any(|d| d == dir) {
+                directories.push(dir.to_path_buf());
+            }
+        }
+        let (sender, events) = channel();
+        let mut watcher = notify::recommended_watcher(sender)?;
+        for dir in &directories {
+            watcher.watch(dir, RecursiveMode::NonRecursive)?;
+        }
+        Ok(Self {
+            query,
+            lookup_path,
+            executables,
+            directories,
+            events,
+            changed_dirs: Vec::new(),
+            last_change: None,
+            _watcher: watcher,
+        })
+    }
+
+    /// Result of the latest scan
+    pub fn executables(&self) -> &Executables {
+        &self.executables
+    }
+
+    /// Directories being watched
+    pub fn directories(&self) -> &[PathBuf] {
+        &self.directories
+    }
+
+    /// Block until a change of the executable files alters the result of the scan, and return the
+    /// differences with the previous result (None if the watcher stopped)
+    pub fn wait_for_change(&mut self) -> Result<Option<ExecutablesDiff>, LookupError> {
+        loop {
+            match self.next_change()? {
+                Some(change) if change.diff.is_empty() => continue,
+                change => return Ok(change.map(|c| c.diff)),
+            }
+        }
+    }
+
+    /// Block until a change of the executable files alters the result of the scan or replaces
+    /// one of the dependencies, and return what changed (None if the watcher stopped)
+    pub fn next_change(&mut self) -> Result<Option<WatchChange>, LookupError> {
+        loop {
+            if self.last_change.is_none() {
+                match self.events.recv() {
+                    Ok(event) => self.record(event?),
+                    Err(_) => return Ok(None),
+                }
+                continue;
+            }
+            // the batch ends when no further event comes shortly
+            while let Ok(event) = self.events.recv_timeout(DEBOUNCE_INTERVAL) {
+                if let Ok(event) = event {
+                    self.record(event);
+                }
+            }
+            if let Some(change) = self.rescan()? {
+                return Ok(Some(change));
+            }
+        }
+    }
+
+    /// Same as [`ScanWatcher::next_change`] without waiting for events, for event loops (e.g. of
+    /// a GUI) polling the watcher
+    ///
+    /// None if no change is pending, or if the latest batch of changes may not be over yet: the
+    /// target is rescanned by the first call after no event came for a short while.
+    pub fn try_next_change(&mut self) -> Result<Option<WatchChange>, LookupError> {
+        while let Ok(event) = self.events.try_recv() {
+            self.record(event?);
+        }
+        match self.last_change {
+            Some(last_change) if last_change.elapsed() >= DEBOUNCE_INTERVAL => self.rescan(),
+            _ => Ok(None),
+        }
+    }
+
+    /// Collect the directories of the executable files changed by an event, to be rescanned
+    fn record(&mut self, event: Event) {
+        if !concerns_executables(&event) {
+            return;
+        }
+        for dir in event.paths.iter().filter_map(|p| p.parent()) {
+            if !self.changed_dirs.iter().any(|d| d == dir) {
+                self.changed_dirs.push(dir.to_path_buf());
+            }
+        }
+        self.last_change = Some(Instant::now());
+    }
+
+    /// Rescan the target after a batch of changes of executable files
+    fn rescan(&mut self) -> Result<Option<WatchChange>, LookupError> {
+        self.last_change = None;
+        // only the listings of the directories where files changed are read again
+        for dir in self.changed_dirs.drain(..) {
+            self.lookup_path.cache().invalidate(dir);
+        }
+        let executables = rescan(self.query, self.lookup_path, &self.executables)?;
+        let change = WatchChange::compare(&self.executables, &executables);
+        self.executables = executables;
+        Ok(Some(change).filter(|c| !c.is_empty()))
+    }
+}
+
+fn concerns_executables(event: &Event) -> bool {
+    event.paths.iter().any(|p| {
+        p.extension()
+            .and_then(|e| e.to_str())
+            .map(|e| e.eq_ignore_ascii_case("dll") || e.eq_ignore_ascii_case("exe"))
+            .unwrap_or(false)
+    })
+}
+
+#[cfg(test)]
+mod tests {
+    use std::time::{Duration, Instant};
+
+    use fs_err as fs;
+
+    use crate::common::LookupError;
+    use crate::path::LookupPath;
+    use crate::query::LookupQuery;
+    use crate::watch::{ScanWatcher, WatchChange, WatchEvent};
+
+    /// Poll the watcher until it reports a change, for at most a few seconds
+    fn poll_change(watcher: &mut ScanWatcher) -> Result<Option<WatchChange>, LookupError> {
+        let deadline = Instant::now() + Duration::from_secs(10);
+        while Instant::now() < deadline {
+            if let Some(change) = watcher.try_next_change()? {
+                return Ok(Some(change));
+            }
+            std::thread::sleep(Duration::from_millis(50));
+        }
+        Ok(None)
+    }
+
+    #[test]
+    fn watch_new_dll() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        let temp_dir = tempfile::tempdir()?;
+        let watched_dir = fs::canonicalize(temp_dir.path())?;
+        fs::copy(
+            build_dir.join("DepRunTest.exe"),
+            watched_dir.join("DepRunTest.exe"),
+        )?;
+
+        let query =
+            LookupQuery::deduce_from_executable_location(watched_dir.join("DepRunTest.exe"))?;
+        let lookup_path = LookupPath::deduce(&query);
+        let mut watcher = ScanWatcher::new(&query, &lookup_path)?;
+        assert!(
+            !watcher
+                .executables()
+                .get("DepRunTestLib.dll")
+                .unwrap()
+                .found
+        );
+
+        fs::copy(
+            build_dir.join("DepRunTestLib.dll"),
+            watched_dir.join("DepRunTestLib.dll"),
+        )?;
+        let diff = poll_change(&mut watcher)?.unwrap().diff;
+        assert!(diff
+            .changed
+            .iter()
+            .any(|c| c.dllname == "DepRunTestLib.dll"));
+        assert!(
+            watcher
+                .executables()
+                .get("DepRunTestLib.dll")
+                .unwrap()
+                .found
+        );
+        Ok(())
+    }
+
+    #[test]
+    fn watch_events() -> Result<(), LookupError> {
+        let d = std::path::PathBuf::from(env!("CARGO_MANIFEST_DIR"));
+        let build_dir = d.join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug");
+        let temp_dir = tempfile::tempdir()?;
+        let watched_dir = fs::canonicalize(temp_dir.path())?;
+        for file in ["DepRunTest.exe", "DepRunTestLib.dll"] {
+            fs::copy(build_dir.join(file), watched_dir.join(file))?;
+        }
+        let lib_path = watched_dir.join("DepRunTestLib.dll");
+
+        let query =
+            LookupQuery::deduce_from_executable_location(watched_dir.join("DepRunTest.exe"))?;
+        let lookup_path = LookupPath::deduce(&query);
+        let mut watcher = ScanWatcher::new(&query, &lookup_path)?;
+        assert!(watcher.directories().contains(&watched_dir));
+        let start = Instant::now();
+        assert!(watcher.try_next_change()?.is_none());
+        assert!(start.elapsed() < Duration::from_millis(100));
+
+        // appended data does not change the tables, so the scan itself is the same
+        let mut content = fs::read(&lib_path)?;
+        content.extend_from_slice(&[0; 16]);
+        fs::write(&lib_path, content)?;
+        let change = poll_change(&mut watcher)?.unwrap();
+        assert!(change.diff.is_empty());
+        assert_eq!(
+            change.events,
+            vec![WatchEvent::DependencyReplaced {
+                dllname: "DepRunTestLib.dll".to_owned(),
+                path: lib_path.clone(),
+            }]
+        );
+
+        fs::remove_file(&lib_path)?;
+        let change = poll_change(&mut watcher)?.unwrap();
+        assert_eq!(
+            change.events,
+            vec![WatchEvent::DependencyRemoved {
+                dllname: "DepRunTestLib.dll".to_owned(),
+                path: lib_path,
+                replacement: None,
+            }]
+        );
+        assert!(!change.diff.is_empty());
+        Ok(())
+    }
+}
@@ -0,0 +1,97 @@
+//! Runs of the deprun binary, checking its exit code and the files it writes
+
+use std::path::PathBuf;
+use std::process::Command;
+
+use dependency_runner::report::ExecutablesCheckReport;
+
+/// Executable whose DLL is in another build directory, hence reported as missing
+fn broken_exe() -> PathBuf {
+    PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test_data/test_project1/DepRunTest/build/DepRunTest/Debug/DepRunTest.exe")
+}
+
+fn deprun() -> Command {
+    Command::new(env!("CARGO_BIN_EXE_deprun"))
+}
+
+#[test]
+fn update_baseline_without_fail_on() {
+    let dir = tempfile::tempdir().unwrap();
+    let baseline = dir.path().join("baseline.json");
+
+    let status = deprun()
+        .arg("--baseline")
+        .arg(&baseline)
+        .arg("--update-baseline")
+        .arg(broken_exe())
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(0));
+    let findings = ExecutablesCheckReport::from_json_file(&baseline).unwrap();
+    assert!(findings
+        .with_code("DR0001")
+        .any(|f| f.files.iter().any(|file| file == "DepRunTestLib.dll")));
+
+    // the known findings do not fail the scan anymore
+    let status = deprun()
+        .arg("--baseline")
+        .arg(&baseline)
+        .args(["--fail-on", "missing-dlls"])
+        .arg(broken_exe())
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(0));
+}
+
+#[test]
+fn lock_mismatch_fails_without_fail_on() {
+    let dir = tempfile::tempdir().unwrap();
+    let lock = dir.path().join("deps.lock");
+    let working_exe = PathBuf::from(env!("CARGO_MANIFEST_DIR"))
+        .join("test_data/test_project1/DepRunTest/build-same-output/bin/Debug/DepRunTest.exe");
+
+    let status = deprun()
+        .arg("lock")
+        .arg("-o")
+        .arg(&lock)
+        .arg(working_exe)
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(0));
+
+    // the locked DLL is not next to the other build of the executable
+    let status = deprun()
+        .arg("--lock")
+        .arg(&lock)
+        .arg(broken_exe())
+        .output()
+        .unwrap()
+        .status;
+    assert_eq!(status.code(), Some(6));
+}
+
+#[test]
+fn host_exe_requires_dll_target() {
+    let output = deprun()
+        .arg("--host-exe")
+        .arg(broken_exe())
+        .arg(broken_exe())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+    assert!(String::from_utf8_lossy(&output.stderr).contains("requires a DLL target"));
+
+    let output = deprun()
+        .arg("--host-exe")
+        .arg(broken_exe())
+        .arg("--also-scan")
+        .arg(broken_exe())
+        .arg(broken_exe())
+        .output()
+        .unwrap();
+    assert!(!output.status.success());
+}